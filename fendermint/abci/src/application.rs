@@ -10,6 +10,7 @@ use std::{
 use tendermint::abci::{request, response, Request, Response};
 use tower::Service;
 use tower_abci::BoxError;
+use tracing::Instrument;
 
 use crate::util::take_until_max_size;
 
@@ -160,6 +161,9 @@ where
         // See https://github.com/tower-rs/tower/issues/547
         let app: A = std::mem::replace(&mut self.0, app);
 
+        // Everything that happens while handling the request is traced as part of this span.
+        let span = tracing::info_span!("abci", method = request_name(&req));
+
         let res = async move {
             let res = match req {
                 Request::Echo(r) => Response::Echo(app.echo(r).await?),
@@ -189,6 +193,27 @@ where
             };
             Ok(res)
         };
-        res.boxed()
+        res.instrument(span).boxed()
+    }
+}
+
+fn request_name(req: &Request) -> &'static str {
+    match req {
+        Request::Echo(_) => "echo",
+        Request::Flush => "flush",
+        Request::Info(_) => "info",
+        Request::InitChain(_) => "init_chain",
+        Request::Query(_) => "query",
+        Request::CheckTx(_) => "check_tx",
+        Request::PrepareProposal(_) => "prepare_proposal",
+        Request::ProcessProposal(_) => "process_proposal",
+        Request::BeginBlock(_) => "begin_block",
+        Request::DeliverTx(_) => "deliver_tx",
+        Request::EndBlock(_) => "end_block",
+        Request::Commit => "commit",
+        Request::ListSnapshots => "list_snapshots",
+        Request::OfferSnapshot(_) => "offer_snapshot",
+        Request::LoadSnapshotChunk(_) => "load_snapshot_chunk",
+        Request::ApplySnapshotChunk(_) => "apply_snapshot_chunk",
     }
 }
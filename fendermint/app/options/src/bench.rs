@@ -0,0 +1,55 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    #[command(subcommand)]
+    pub command: BenchCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BenchCommands {
+    /// Write block-like workloads to a RocksDB database and report the throughput, the latencies and the write amplification.
+    ///
+    /// The database is opened with the same configuration as the one of the node, next to it in the data directory
+    /// by default, so it's on the same disk. It's removed at the end unless `--keep` is used.
+    Storage {
+        /// Directory of the benchmark database; defaults to `rocksdb-bench` in the data directory.
+        ///
+        /// It cannot be the database of the node.
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Number of blocks to write.
+        #[arg(long, default_value = "100")]
+        blocks: u64,
+
+        /// Number of messages in each block.
+        #[arg(long, default_value = "1000")]
+        msgs_per_block: u64,
+
+        /// Number of accounts sending the messages; each message reads and updates the state of one of them.
+        #[arg(long, default_value = "10000", value_parser = clap::value_parser!(u64).range(1..))]
+        accounts: u64,
+
+        /// Number of contract storage slots each message writes.
+        #[arg(long, default_value = "4")]
+        storage_writes: u64,
+
+        /// Size of the values written to contract storage, in bytes.
+        #[arg(long, default_value = "32")]
+        value_size: usize,
+
+        /// Seed of the workload, to repeat the same one.
+        #[arg(long, default_value = "0")]
+        seed: u64,
+
+        /// Keep the database after the benchmark, e.g. to run the next one on a bigger state.
+        #[arg(long)]
+        keep: bool,
+    },
+}
@@ -0,0 +1,24 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use clap::Args;
+
+/// Download a published snapshot into the snapshot directory, to offer it to peers during state sync.
+#[derive(Args, Debug)]
+pub struct BootstrapArgs {
+    /// CID of a snapshot directory published on IPFS.
+    #[arg(
+        long,
+        conflicts_with = "from_url",
+        required_unless_present = "from_url"
+    )]
+    pub from_ipfs: Option<String>,
+
+    /// HTTP gateway to fetch the snapshot from IPFS through.
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub ipfs_gateway: String,
+
+    /// URL of a published snapshot directory, e.g. in an S3 bucket.
+    #[arg(long)]
+    pub from_url: Option<String>,
+}
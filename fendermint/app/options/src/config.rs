@@ -0,0 +1,56 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand, ValueEnum};
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommands {
+    /// Tune the `config.toml` of CometBFT for a throughput profile, consistently with the Fendermint settings.
+    ///
+    /// Only the keys affecting the connection to Fendermint and the throughput are changed,
+    /// everything else in the input, e.g. the peers, is kept as it is.
+    GenCometbft {
+        /// The `config.toml` created by `cometbft init`.
+        #[arg(long, short)]
+        input_file: PathBuf,
+
+        /// Where to write the tuned config; defaults to overwriting the input.
+        #[arg(long, short)]
+        output_file: Option<PathBuf>,
+
+        /// The load the subnet is expected to handle.
+        #[arg(long, short, value_enum, default_value_t = ThroughputProfile::Standard)]
+        profile: ThroughputProfile,
+
+        /// Transactions per second to size the mempool and the gossip for, instead of the one in the profile.
+        #[arg(long)]
+        target_tps: Option<u64>,
+
+        /// Average size of a transaction in bytes, used to turn the number of transactions into bandwidth.
+        #[arg(long, default_value = "512")]
+        avg_tx_bytes: u64,
+
+        /// Maximum size of a single transaction accepted into the mempool.
+        #[arg(long, default_value = "1048576")]
+        max_tx_bytes: u64,
+    },
+}
+
+/// Presets for the block time and the expected load.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ThroughputProfile {
+    /// Slow blocks and a small mempool, e.g. for local testing.
+    Low,
+    /// Block every second with room for a few hundred transactions per second.
+    Standard,
+    /// Fast blocks with a large mempool and more bandwidth for gossiping transactions.
+    High,
+}
@@ -0,0 +1,58 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use tendermint_rpc::{Url, WebSocketClientUrl};
+
+#[derive(Args, Debug)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub command: DbCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DbCommands {
+    /// Serve queries from a read-only secondary instance of the database of a node running on the same host.
+    ///
+    /// The endpoint can be used in place of the CometBFT RPC: ABCI queries are answered
+    /// from the database, everything else is forwarded to CometBFT.
+    ServeReadonly {
+        /// Address to serve the CometBFT compatible JSON-RPC endpoint on.
+        #[arg(long, short, default_value = "127.0.0.1:26660")]
+        listen: String,
+
+        /// The URL of the Tendermint node's RPC endpoint, to forward anything but queries to.
+        #[arg(
+            long,
+            short = 'u',
+            default_value = "http://127.0.0.1:26657",
+            env = "TENDERMINT_RPC_URL"
+        )]
+        http_url: Url,
+
+        /// The URL of the Tendermint node's WebSocket endpoint, for the subscriptions of the Ethereum API.
+        #[arg(
+            long,
+            short,
+            default_value = "ws://127.0.0.1:26657/websocket",
+            env = "TENDERMINT_WS_URL"
+        )]
+        ws_url: WebSocketClientUrl,
+
+        /// Directory where the secondary instance keeps its own files; each instance needs a different one.
+        ///
+        /// Defaults to `rocksdb-secondary` in the data directory.
+        #[arg(long)]
+        secondary_dir: Option<PathBuf>,
+
+        /// Milliseconds between catching up with the writes of the node.
+        #[arg(long, default_value = "1000")]
+        catch_up_interval: u64,
+
+        /// Also run the Ethereum API, configured by the `eth` settings, using the read-only endpoint.
+        #[arg(long)]
+        eth: bool,
+    },
+}
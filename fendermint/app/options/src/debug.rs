@@ -0,0 +1,24 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use clap::{Args, Subcommand};
+
+#[derive(Args, Debug)]
+pub struct DebugArgs {
+    #[command(subcommand)]
+    pub command: DebugCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DebugCommands {
+    /// Decode the state of an actor into JSON with named fields, e.g. what `rpc query ipld` prints for its state CID.
+    DecodeState {
+        /// Name of the actor the state belongs to, e.g. `account`, `evm` or `governance`.
+        #[arg(long, short)]
+        actor: String,
+
+        /// The state encoded as base64; read from STDIN if missing.
+        #[arg(long, short)]
+        state: Option<String>,
+    },
+}
@@ -17,6 +17,13 @@ pub enum AccountKind {
     Ethereum,
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+pub enum SystemContractKind {
+    Multicall,
+    Deployer,
+    Wfil,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum GenesisCommands {
     /// Create a new Genesis file, with accounts and validators to be added later.
@@ -34,6 +41,10 @@ pub enum GenesisCommands {
     },
     /// Convert the genesis file into the format expected by Tendermint.
     IntoTendermint(GenesisIntoTendermintArgs),
+    /// Check the genesis file for mistakes without starting a chain, printing a JSON report.
+    ///
+    /// Exits with an error if any problem is found, so it can be used to gate a launch in CI.
+    Validate(GenesisValidateArgs),
 }
 
 #[derive(Args, Debug)]
@@ -63,6 +74,55 @@ pub struct GenesisNewArgs {
     /// Number of decimals to use during converting FIL to Power.
     #[arg(long, short)]
     pub power_scale: i8,
+    /// Accept transactions compressed with zstd, e.g. to fit large contract deployments into blocks.
+    #[arg(long)]
+    pub tx_compression: bool,
+    /// Accept messages signed by Filecoin wallets such as Lotus, which sign the CID of the message
+    /// without the chain ID; these can be replayed from other chains where the sender has the same nonce.
+    #[arg(long)]
+    pub filecoin_signatures: bool,
+    /// Utility contracts to deploy at well-known addresses, e.g. `multicall,deployer,wfil`.
+    #[arg(long, value_delimiter = ',')]
+    pub system_contracts: Vec<SystemContractKind>,
+    /// Run a randomness beacon the validators contribute to, with epochs of this many blocks.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+    pub beacon_epoch_length: Option<u64>,
+    /// Pay rewards to the validators out of the fees, distributed at the end of epochs of this many blocks.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub reward_epoch_length: Option<u64>,
+    /// Tokens to mint into the reward pool at the end of every epoch, in full FIL.
+    #[arg(long, value_parser = parse_full_fil, default_value = "0", requires = "reward_epoch_length")]
+    pub reward_inflation_per_epoch: TokenAmount,
+    /// Collect the base fees into the reward pool as well as the tips, instead of burning them.
+    #[arg(long, requires = "reward_epoch_length")]
+    pub reward_base_fees: bool,
+    /// Path to a Wasm module to consult about every user message; the genesis pins its CID,
+    /// and every validator has to load the same module with the `fvm.policy_module` setting.
+    #[arg(long)]
+    pub policy_module: Option<PathBuf>,
+    /// Fuel a single call of the policy module can consume before the message is rejected.
+    #[arg(long, default_value = "10000000")]
+    pub policy_fuel_limit: u64,
+    /// Record the digests of the receipts and events of every block in the state, so light clients
+    /// can verify the result of a transaction with a Merkle proof, without replaying the block.
+    #[arg(long)]
+    pub exec_digests: bool,
+    /// Reject proposals with a block time more than this many seconds after the previous block;
+    /// the chain can't resume after a pause longer than this, so leave plenty of headroom.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_block_interval: Option<u64>,
+    /// Expose block hashes derived from the CometBFT ones in the Ethereum API, rather than the
+    /// CometBFT hashes as they are; this can't be changed later.
+    #[arg(long)]
+    pub eth_block_hash_v1: bool,
+    /// Require the signed messages in every block to be sorted by sender and nonce, and reject
+    /// proposals which aren't; this can't be changed later.
+    #[arg(long)]
+    pub canonical_tx_order: bool,
+    /// Follow the parent without waiting for its blocks to be final, trusting the proposer about
+    /// heights a validator hasn't seen yet; only meant for local devnets.
+    #[arg(long)]
+    pub fast_topdown_finality: bool,
 }
 
 #[derive(Args, Debug)]
@@ -117,11 +177,20 @@ pub struct GenesisIntoTendermintArgs {
     pub block_max_bytes: u64,
 }
 
+#[derive(Args, Debug)]
+pub struct GenesisValidateArgs {
+    /// Treat warnings as errors, e.g. accounts without a balance.
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum GenesisIpcCommands {
     /// Set all gateway parameters.
     Gateway(GenesisIpcGatewayArgs),
     FromParent(Box<GenesisFromParentArgs>),
+    /// Check the IPC configuration of the genesis against the registration of the subnet on the parent.
+    CheckParent(Box<GenesisCheckParentArgs>),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -148,6 +217,10 @@ pub struct GenesisIpcGatewayArgs {
     /// Maximum number of active validators.
     #[arg(long, short = 'v', default_value = "100")]
     pub active_validators_limit: u16,
+
+    /// Number of bottom-up checkpoints to keep in the gateway before they are archived and pruned; 0 keeps all.
+    #[arg(long, default_value = "0")]
+    pub checkpoint_retention: u64,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -180,3 +253,22 @@ pub struct GenesisFromParentArgs {
     #[arg(long, default_value = "3")]
     pub power_scale: i8,
 }
+
+#[derive(Args, Debug, Clone)]
+pub struct GenesisCheckParentArgs {
+    /// The subnet the genesis is supposed to be for; defaults to the one in the genesis.
+    #[arg(long, short)]
+    pub subnet_id: Option<SubnetID>,
+
+    /// Endpoint to the RPC of the child subnet's parent
+    #[arg(long, short)]
+    pub parent_endpoint: url::Url,
+
+    /// IPC gateway of the parent; 20 byte Ethereum address in 0x prefixed hex format
+    #[arg(long, value_parser = parse_eth_address, default_value = "0xff00000000000000000000000000000000000064")]
+    pub parent_gateway: Address,
+
+    /// IPC registry of the parent;  20 byte Ethereum address in 0x prefixed hex format
+    #[arg(long, value_parser = parse_eth_address, default_value = "0xff00000000000000000000000000000000000065")]
+    pub parent_registry: Address,
+}
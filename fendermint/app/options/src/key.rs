@@ -5,12 +5,16 @@ use std::path::PathBuf;
 
 use clap::{Args, Subcommand};
 
+use crate::genesis::AccountKind;
+
 #[derive(Subcommand, Debug)]
 pub enum KeyCommands {
     /// Generate a new Secp256k1 key pair and export them to files in base64 format.
     Gen(KeyGenArgs),
     /// Convert a secret key file from base64 into the format expected by Tendermint.
     IntoTendermint(KeyIntoTendermintArgs),
+    /// Generate a new Ed25519 node key in the format expected by CometBFT and print its node ID.
+    GenNodeKey(KeyGenNodeKeyArgs),
     /// Convert a public key file from base64 into an f1 Address format an print it to STDOUT.
     Address(KeyAddressArgs),
     /// Get the peer ID corresponding to a node ID and its network address and print it to a local file.
@@ -20,6 +24,38 @@ pub enum KeyCommands {
     FromEth(KeyFromEthArgs),
     /// Converts a Base64 encoded Fendermint private key into a hex encoded Ethereum secret key, public key and address (20 bytes).
     IntoEth(KeyIntoEthArgs),
+    /// List the accounts in the keystore.
+    List(KeyListArgs),
+    /// Import a secret key file into the keystore under a name.
+    Import(KeyImportArgs),
+    /// Export an account from the keystore into Base64 encoded secret and public key files.
+    Export(KeyExportArgs),
+    /// Set the account to use by default when none is specified.
+    Use(KeyUseArgs),
+}
+
+/// Arguments to open the keystore with.
+#[derive(Args, Debug, Clone)]
+pub struct KeystoreArgs {
+    /// Directory of the keystore with the named accounts.
+    #[arg(
+        long,
+        default_value = "~/.fendermint/keystore",
+        env = "FM_KEYSTORE_DIR"
+    )]
+    pub keystore_dir: PathBuf,
+    /// Password to encrypt and decrypt secret keys with.
+    ///
+    /// Keys imported without a password are stored in plaintext. The env var is the one
+    /// the node reads the password of its `[keystore]` from, so one setting serves both.
+    #[arg(
+        long,
+        default_value = "",
+        env = "FM_KEYSTORE__PASSWORD",
+        hide_env_values = true,
+        hide_default_value = true
+    )]
+    pub keystore_password: String,
 }
 
 #[derive(Args, Debug)]
@@ -51,6 +87,13 @@ pub struct KeyGenArgs {
     /// Directory to export the key files to; it must exist.
     #[arg(long, short, default_value = ".")]
     pub out_dir: PathBuf,
+    /// CometBFT config directory to also write a matching `priv_validator_key.json`
+    /// and a new `node_key.json` into; it must exist.
+    #[arg(long)]
+    pub cometbft_config_dir: Option<PathBuf>,
+    /// Overwrite the key files in the CometBFT config directory if they already exist.
+    #[arg(long, requires = "cometbft_config_dir")]
+    pub force: bool,
 }
 
 #[derive(Args, Debug)]
@@ -89,9 +132,64 @@ pub struct KeyIntoTendermintArgs {
     pub out: PathBuf,
 }
 
+#[derive(Args, Debug)]
+pub struct KeyGenNodeKeyArgs {
+    /// Output file name for the CometBFT node key JSON file.
+    #[arg(long, short)]
+    pub out: PathBuf,
+    /// Overwrite the node key file if it already exists.
+    #[arg(long)]
+    pub force: bool,
+}
+
 #[derive(Args, Debug)]
 pub struct KeyAddressArgs {
     /// Path to the public key we want to convert to f1 format.
     #[arg(long, short)]
     pub public_key: PathBuf,
 }
+
+#[derive(Args, Debug)]
+pub struct KeyListArgs {
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct KeyImportArgs {
+    /// Name of the account in the keystore.
+    #[arg(long, short)]
+    pub name: String,
+    /// Path to the secret key file to import.
+    #[arg(long, short)]
+    pub secret_key: PathBuf,
+    /// Indicate that the secret key is in hex format, like Ethereum keys, rather than base64.
+    #[arg(long)]
+    pub hex: bool,
+    /// Indicate whether it's a regular or ethereum account.
+    #[arg(long, short, default_value = "regular")]
+    pub account_kind: AccountKind,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct KeyExportArgs {
+    /// Name of the account in the keystore.
+    #[arg(long, short)]
+    pub name: String,
+    /// Directory to export the key files to; it must exist.
+    #[arg(long, short, default_value = ".")]
+    pub out_dir: PathBuf,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct KeyUseArgs {
+    /// Name of the account in the keystore.
+    #[arg(long, short)]
+    pub name: String,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
@@ -6,8 +6,16 @@ use std::path::PathBuf;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use fvm_shared::address::Network;
 
-use self::{eth::EthArgs, genesis::GenesisArgs, key::KeyArgs, rpc::RpcArgs, run::RunArgs};
-
+use self::{
+    bench::BenchArgs, bootstrap::BootstrapArgs, config::ConfigArgs, db::DbArgs, debug::DebugArgs,
+    eth::EthArgs, genesis::GenesisArgs, key::KeyArgs, rpc::RpcArgs, run::RunArgs,
+};
+
+pub mod bench;
+pub mod bootstrap;
+pub mod config;
+pub mod db;
+pub mod debug;
 pub mod eth;
 pub mod genesis;
 pub mod key;
@@ -126,6 +134,16 @@ pub enum Commands {
     Rpc(RpcArgs),
     /// Subcommands related to the Ethereum API facade.
     Eth(EthArgs),
+    /// Subcommands related to the database of a node.
+    Db(DbArgs),
+    /// Subcommands to measure the performance of the node's environment.
+    Bench(BenchArgs),
+    /// Download a published snapshot, e.g. from IPFS, to offer it to peers during state sync.
+    Bootstrap(BootstrapArgs),
+    /// Subcommands to generate the configuration of the components running next to Fendermint.
+    Config(ConfigArgs),
+    /// Subcommands to help make sense of the data of a node.
+    Debug(DebugArgs),
 }
 
 #[cfg(test)]
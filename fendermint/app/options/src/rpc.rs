@@ -12,6 +12,7 @@ use tendermint_rpc::Url;
 
 use crate::{
     genesis::AccountKind,
+    key::KeystoreArgs,
     parse::{parse_address, parse_bytes, parse_cid, parse_full_fil, parse_token_amount},
 };
 
@@ -74,6 +75,88 @@ pub enum RpcCommands {
         #[command(flatten)]
         args: TransArgs,
     },
+    /// Deliver a top-down message from the dead-letter queue again.
+    ///
+    /// Has to be sent by a governance member, or by the governor on chains with access control.
+    RetryDeadLetter {
+        /// ID of the dead letter, as listed by the `dead-letters` query.
+        #[arg(long)]
+        id: u64,
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Propose new values for the chain parameters; the proposal counts as approved by the sender.
+    ///
+    /// Has to be sent by a governance member. All parameters have to be given,
+    /// as the proposal replaces the current values as a whole.
+    GovernancePropose {
+        /// Minimum base fee, in atto.
+        #[arg(long, value_parser = parse_token_amount)]
+        base_fee_floor: TokenAmount,
+        /// Maximum sum of the gas limits of the transactions in a block; 0 means unlimited.
+        #[arg(long)]
+        block_gas_limit: u64,
+        /// Maximum number of top-down messages executed in a batch; 0 means unlimited.
+        #[arg(long)]
+        topdown_max_msgs: u64,
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Propose to shut the subnet down; the proposal counts as approved by the sender.
+    ///
+    /// Once approved, from the next epoch no user transactions are accepted, and the next
+    /// checkpoint is the final one. This cannot be undone.
+    GovernanceProposeShutdown {
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Block a sender on the parent from sending top-down messages, without waiting for approvals.
+    ///
+    /// Messages from the sender are moved to the dead-letter queue instead of being executed.
+    GovernanceBlockTopdownSender {
+        /// Address of the sender on the parent subnet.
+        #[arg(long, value_parser = parse_address)]
+        sender: Address,
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Propose to replace the filter on top-down message senders; the proposal counts as approved by the sender.
+    GovernanceProposeTopdownFilter {
+        /// Senders whose messages are never executed.
+        #[arg(long, value_parser = parse_address)]
+        blocked: Vec<Address>,
+        /// Only execute messages from these senders; all senders are allowed if not given.
+        #[arg(long, value_parser = parse_address)]
+        allowed: Option<Vec<Address>>,
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Transfer the rewards distributed to the validator signing the message to its account.
+    ///
+    /// Has to be sent from the `f1` or the `f410` address of the validator key.
+    ClaimRewards {
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Approve an open proposal to change the chain parameters or to shut the subnet down.
+    GovernanceApprove {
+        /// ID of the proposal, as listed by the `governance` query.
+        #[arg(long)]
+        id: u64,
+        #[command(flatten)]
+        args: TransArgs,
+    },
+    /// Start an interactive shell to run the other commands in, with the results pretty-printed.
+    ///
+    /// Addresses can be given as `@name`, where the name is an account in the keystore
+    /// or a contract deployed at genesis; `book` lists them all.
+    Shell {
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+        /// File to keep the history of the commands in.
+        #[arg(long, default_value = "~/.fendermint/rpc_history")]
+        history_file: PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -92,6 +175,57 @@ pub enum RpcQueryCommands {
     },
     /// Get the slowly changing state parameters.
     StateParams,
+    /// List the top-down messages which failed to be applied; print them as JSON.
+    DeadLetters,
+    /// Show the chain parameters and the open governance proposals; print them as JSON.
+    Governance,
+    /// Show the latest values of the randomness beacon and the progress of the current round; print them as JSON.
+    Beacon,
+    /// Show the rewards the validators can claim and the ones to be distributed at the end of the epoch; print them as JSON.
+    Rewards,
+    /// List the consensus, FVM and Ethereum addresses of current and past validators; print them as JSON.
+    Validators,
+    /// List the validators in the power table which was in effect at the queried height, from the history
+    /// the node keeps even after the state of that height is pruned; print them as JSON.
+    ValidatorSet,
+    /// Break down the app hash of the state committed at the queried height into its components,
+    /// to compare with another node when their app hashes differ; print them as JSON.
+    AppHash,
+    /// List the addresses of the libraries and contracts deployed at genesis by their fully qualified names; print them as JSON.
+    Contracts,
+    /// List the facets and function selectors the IPC diamonds were cut with at genesis; print them as JSON.
+    Diamonds,
+    /// Look up a bottom-up checkpoint pruned from the gateway in the archive of the node; print it as JSON.
+    ArchivedCheckpoint {
+        /// Height of the checkpoint.
+        #[arg(long, short = 'c')]
+        checkpoint_height: u64,
+    },
+    /// Apply staking changes made on the parent to the current validators without committing them,
+    /// and check the resulting power table against the constraints of the subnet; print it as JSON.
+    SimulateStaking {
+        /// JSON file with the list of changes, e.g.
+        /// `[{"configuration_number": 5, "validator": "t410f...", "op": "deposit", "amount": "1000000000000000000"}]`
+        /// with the amount in atto; `set_metadata` changes have a hex encoded `public_key` instead of an amount.
+        #[arg(long, short = 'f')]
+        changes_file: PathBuf,
+        /// Maximum number of validators in the subnet; 0 means it's not checked.
+        #[arg(long, default_value = "0")]
+        max_validators: u16,
+        /// Minimum power every validator needs to have.
+        #[arg(long, default_value = "1")]
+        min_power: u64,
+    },
+    /// Get the receipt of a transaction with its Merkle proof against the execution digests
+    /// recorded for its block, and check the proof; print them as JSON.
+    ReceiptProof {
+        /// Height of the block the transaction is in.
+        #[arg(long, short = 'b')]
+        block_height: u64,
+        /// Index of the transaction in the block.
+        #[arg(long, short = 'i')]
+        index: u64,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -152,9 +286,18 @@ pub struct TransArgs {
     #[arg(long, short, value_parser = parse_full_fil, default_value = "0")]
     pub value: TokenAmount,
     /// Path to the secret key of the sender to sign the transaction.
-    #[arg(long, short)]
-    pub secret_key: PathBuf,
+    ///
+    /// If it's missing, the key is taken from the keystore.
+    #[arg(long, short, conflicts_with = "account")]
+    pub secret_key: Option<PathBuf>,
+    /// Name of the account in the keystore to sign the transaction with; uses the default account if missing.
+    #[arg(long)]
+    pub account: Option<String>,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
     /// Indicate whether its a regular or ethereum account.
+    ///
+    /// Only used with `--secret-key`; the keystore remembers the kind of its accounts.
     #[arg(long, short, default_value = "regular")]
     pub account_kind: AccountKind,
     /// Sender account nonce.
@@ -175,6 +318,11 @@ pub struct TransArgs {
     /// Whether to wait for the results from Tendermint or not.
     #[arg(long, short, default_value = "commit")]
     pub broadcast_mode: BroadcastMode,
+    /// Compress the transaction with zstd, e.g. to fit large contract deployments into blocks.
+    ///
+    /// The chain must have been started with compression enabled in its genesis.
+    #[arg(long)]
+    pub compress: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -1,7 +1,30 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
+
 use clap::Args;
 
 #[derive(Args, Debug)]
-pub struct RunArgs;
+pub struct RunArgs {
+    /// Stop processing blocks after committing this height; overrides the `abci.halt_height` setting.
+    ///
+    /// It doesn't apply to the other instances hosted in the same process.
+    #[arg(long)]
+    pub halt_height: Option<u64>,
+
+    /// Run a development chain without CometBFT, sealing a block with every transaction.
+    ///
+    /// The node serves a CometBFT compatible JSON-RPC endpoint at the `tendermint_rpc_url`,
+    /// along with the `dev_mine` and `dev_warp` methods to produce blocks and move the clock.
+    #[arg(long, requires = "dev_genesis_file")]
+    pub dev: bool,
+
+    /// The Fendermint genesis file of the development chain.
+    #[arg(long, requires = "dev")]
+    pub dev_genesis_file: Option<PathBuf>,
+
+    /// Seal an empty block on the development chain if no transaction arrived for this many seconds.
+    #[arg(long, requires = "dev")]
+    pub dev_block_time: Option<u64>,
+}
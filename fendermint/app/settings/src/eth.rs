@@ -4,19 +4,99 @@
 use fvm_shared::econ::TokenAmount;
 use serde::Deserialize;
 use serde_with::{serde_as, DurationSeconds};
+use std::path::PathBuf;
 use std::time::Duration;
 
-use crate::{IsHumanReadable, SocketAddress};
+use crate::{home_relative, IsHumanReadable, SocketAddress};
 
 /// Ethereum API facade settings.
 #[serde_as]
 #[derive(Debug, Deserialize, Clone)]
 pub struct EthSettings {
+    /// The main endpoint, with the `methods`, `cors`, `rate_limit` and `auth` policies below.
     pub listen: SocketAddress,
     #[serde_as(as = "DurationSeconds<u64>")]
     pub filter_timeout: Duration,
     pub cache_capacity: usize,
     pub gas: GasOpt,
+    pub subscription: SubscriptionOpt,
+    pub sync_guard: SyncGuardOpt,
+    pub limits: LimitsOpt,
+    #[serde(default)]
+    pub auth: AuthOpt,
+    #[serde(default)]
+    pub compat: CompatOpt,
+    #[serde(default)]
+    pub methods: MethodsOpt,
+    #[serde(default)]
+    pub cors: CorsOpt,
+    #[serde(default)]
+    pub rate_limit: RateLimitOpt,
+    /// Further endpoints with policies of their own, served by the same process, sharing the caches.
+    #[serde(default)]
+    pub endpoints: Vec<EndpointSettings>,
+}
+
+impl EthSettings {
+    /// The main endpoint followed by the additional ones.
+    pub fn all_endpoints(&self) -> Vec<EndpointSettings> {
+        let main = EndpointSettings {
+            name: "main".to_owned(),
+            listen: self.listen.clone(),
+            methods: self.methods.clone(),
+            cors: self.cors.clone(),
+            rate_limit: self.rate_limit.clone(),
+            auth: self.auth.clone(),
+        };
+        std::iter::once(main)
+            .chain(self.endpoints.iter().cloned())
+            .collect()
+    }
+}
+
+/// An additional address to serve the API on, e.g. for internal callers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointSettings {
+    /// Name of the endpoint in the logs.
+    pub name: String,
+    pub listen: SocketAddress,
+    #[serde(default)]
+    pub methods: MethodsOpt,
+    #[serde(default)]
+    pub cors: CorsOpt,
+    #[serde(default)]
+    pub rate_limit: RateLimitOpt,
+    /// Unlike the other policies, the authentication is not inherited from the main endpoint either.
+    #[serde(default)]
+    pub auth: AuthOpt,
+}
+
+/// The methods served by an endpoint.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct MethodsOpt {
+    /// Namespaces, e.g. `eth`, or individual methods served; empty means all of them.
+    pub allow: Vec<String>,
+    /// Namespaces or individual methods not served, even if they are allowed.
+    pub deny: Vec<String>,
+}
+
+/// Cross-origin requests from browsers.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct CorsOpt {
+    /// Origins allowed to call the API from a browser, or `*` for any; empty allows none.
+    pub allowed_origins: Vec<String>,
+}
+
+/// Limits on the calls each client IP address can make.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct RateLimitOpt {
+    /// Calls per second; 0 means no limit.
+    pub calls_per_second: u32,
+    /// Calls which can be made at once after being idle; 0 means the same as the rate.
+    pub burst: u32,
 }
 
 #[serde_as]
@@ -28,3 +108,61 @@ pub struct GasOpt {
     pub num_blocks_max_prio_fee: u64,
     pub max_fee_hist_size: u64,
 }
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscriptionOpt {
+    /// Time to wait before trying to re-subscribe to CometBFT after a subscription failed.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub resubscribe_delay: Duration,
+    /// Maximum number of missed block heights to re-fetch after re-subscribing.
+    pub max_gap: u64,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncGuardOpt {
+    /// Reject requests with a "node is syncing" error until the node has caught up.
+    pub enabled: bool,
+    /// Maximum age of the latest block while CometBFT is catching up, for the node to be considered ready.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub max_lag: Duration,
+    /// Time between checks of the CometBFT status.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub check_interval: Duration,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsOpt {
+    /// Maximum number of logs `eth_getLogs` can return.
+    pub max_logs: usize,
+    /// Maximum number of blocks a log query can scan; 0 means no limit.
+    pub max_block_range: u64,
+    /// Maximum number of items on a page of the paginated methods.
+    pub max_page_size: usize,
+}
+
+/// Deviations from the standard Ethereum API.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct CompatOpt {
+    /// Leave out the non-standard FVM fields from transaction receipts.
+    pub strict_receipts: bool,
+}
+
+/// JWT authentication of the sensitive methods, like the Engine API of Ethereum clients.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct AuthOpt {
+    pub enabled: bool,
+    /// File with the hex encoded 32 byte secret the callers sign their tokens with.
+    pub jwt_secret: PathBuf,
+    /// Namespaces of the methods requiring authentication, e.g. `txpool`, or `*` for all.
+    pub namespaces: Vec<String>,
+    /// Individual methods requiring authentication.
+    pub methods: Vec<String>,
+    /// Maximum difference between the issuance time of a token and the local clock.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub max_iat_drift: Duration,
+}
+
+home_relative!(AuthOpt { jwt_secret });
@@ -1,6 +1,8 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
+
 use fvm_shared::econ::TokenAmount;
 use serde::Deserialize;
 use serde_with::serde_as;
@@ -22,6 +24,20 @@ pub struct FvmSettings {
     /// Enabling this option is required to fully support "pending" queries in the Ethereum API,
     /// otherwise only the nonces and balances are projected into a partial state.
     pub exec_in_check: bool,
+    /// Only check the signature and the basic fields of new transactions when they are added
+    /// to the mempool, deferring the checks against the state to the rechecks after each block
+    /// and to the proposer, which drops the transactions that would fail from its proposals.
+    ///
+    /// Reduces the latency of admitting and gossiping transactions under load, at the cost of
+    /// the mempool holding transactions which turn out to be invalid until the next recheck.
+    /// Transactions are not executed in the check, regardless of `exec_in_check`.
+    #[serde(default)]
+    pub defer_check: bool,
+    /// Path to the Wasm module consulted about every user message, if the genesis pins one.
+    ///
+    /// Every validator has to load the same module; it's checked against the CID in the genesis.
+    #[serde(default)]
+    pub policy_module: Option<PathBuf>,
 
     /// Gas fee used when broadcasting transactions.
     #[serde_as(as = "IsHumanReadable")]
@@ -29,4 +45,39 @@ pub struct FvmSettings {
     /// Gas premium used when broadcasting transactions.
     #[serde_as(as = "IsHumanReadable")]
     pub gas_premium: TokenAmount,
+
+    /// Conditions on the balance of the senders to admit their transactions to the mempool.
+    pub admission: AdmissionSettings,
+
+    /// Experimental optimistic parallel execution.
+    pub block_stm: BlockStmSettings,
+}
+
+/// Checks on the balance of the senders beyond covering the gas, against the pending state of
+/// the mempool, so that insolvent accounts can't flood it with transactions bound to fail.
+///
+/// They only apply to admitting transactions; proposals and blocks are not affected.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct AdmissionSettings {
+    /// Require the balance of the sender to cover the value of the message as well as `gas_limit * gas_fee_cap`.
+    pub cover_value: bool,
+    /// Minimum balance of the sender to admit messages which transfer a non-zero value.
+    #[serde_as(as = "Option<IsHumanReadable>")]
+    #[serde(default)]
+    pub min_balance: Option<TokenAmount>,
+}
+
+/// Block-STM style optimistic parallel execution, running alongside the sequential
+/// execution of every block, to measure how much parallelism the blocks would allow.
+///
+/// Only available if the node is built with the `block-stm` feature; meant for benchmarks
+/// on non-production subnets.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockStmSettings {
+    pub enabled: bool,
+    /// Number of threads executing the transactions of a block in parallel.
+    pub threads: usize,
+    /// Compare the receipts with the ones of the sequential execution, and report any difference.
+    pub determinism_check: bool,
 }
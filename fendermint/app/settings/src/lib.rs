@@ -7,12 +7,14 @@ use fvm_shared::address::Address;
 use fvm_shared::econ::TokenAmount;
 use ipc_sdk::subnet_id::SubnetID;
 use serde::Deserialize;
-use serde_with::{serde_as, DurationSeconds};
+use serde_with::{serde_as, DurationMilliSeconds, DurationSeconds};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tendermint_rpc::Url;
 
+use fendermint_rocksdb::{RocksDbConfig, RocksDbProfile};
 use fendermint_vm_encoding::{human_readable_delegate, human_readable_str};
+use fendermint_vm_topdown::divergence::DivergenceAction;
 use fendermint_vm_topdown::BlockHeight;
 
 use self::eth::EthSettings;
@@ -32,6 +34,7 @@ pub mod resolver;
 struct IsHumanReadable;
 
 human_readable_str!(SubnetID);
+human_readable_str!(Address);
 human_readable_delegate!(TokenAmount);
 
 #[derive(Debug, Deserialize, Clone)]
@@ -74,11 +77,92 @@ pub struct SigningKey {
 
 home_relative!(SigningKey { path });
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct KeystoreSettings {
+    /// Directory of the keystore with the named accounts.
+    dir: PathBuf,
+    /// Password to decrypt the accounts with.
+    ///
+    /// Best not to put it in the config file but set it with the `FM_KEYSTORE__PASSWORD` env var.
+    #[serde(default)]
+    pub password: String,
+}
+
+home_relative!(KeystoreSettings { dir });
+
+#[serde_as]
 #[derive(Debug, Deserialize, Clone)]
 pub struct AbciSettings {
     pub listen: SocketAddress,
     /// Queue size for each ABCI component.
     pub bound: usize,
+    /// Number of recent blocks to remember transactions for, so that duplicates can be rejected in `CheckTx`; 0 disables it.
+    pub tx_dedup_blocks: u64,
+    /// Number of transactions to remember the decoded form and the signature check results of,
+    /// so `DeliverTx` can reuse the work done in `CheckTx` and `ProcessProposal`; 0 disables it.
+    pub msg_cache_size: usize,
+    /// Stop processing blocks after committing this height, for coordinated maintenance.
+    #[serde(default)]
+    pub halt_height: Option<u64>,
+    /// Number of recent blocks to keep gas statistics for; 0 disables them.
+    #[serde(default)]
+    pub gas_stats_blocks: u64,
+    /// Time allowed for verifying the signatures in a proposal before voting on it;
+    /// zero leaves them to be verified during delivery.
+    #[serde(default)]
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub proposal_signature_budget: Duration,
+    /// Number of threads to verify the signatures of a block with before executing it; 0 disables it.
+    #[serde(default)]
+    pub block_signature_threads: usize,
+    /// Limits on the resources a query, e.g. `eth_call`, can use.
+    #[serde(default)]
+    pub query_budget: QueryBudgetSettings,
+    /// Priority for the transactions of the node operator in the blocks this node proposes.
+    #[serde(default)]
+    pub operator_lane: OperatorLaneSettings,
+}
+
+/// Transactions signed by the operator keys go in front of the others in the proposals.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct OperatorLaneSettings {
+    pub enabled: bool,
+    /// Addresses of the operator keys, e.g. `t1...` or `t410...` for Ethereum accounts.
+    #[serde_as(as = "Vec<IsHumanReadable>")]
+    pub operators: Vec<Address>,
+    /// Maximum number of operator transactions in a block.
+    pub max_txs: usize,
+    /// Maximum total size of the operator transactions in a block.
+    pub max_bytes: usize,
+    /// Number of proposals within which every accepted transaction is included.
+    pub inclusion_blocks: usize,
+}
+
+/// Limits on the resources a query can use, to protect the node from crafted read-only calls.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct QueryBudgetSettings {
+    /// Maximum gas a call can use; 0 means the block gas limit.
+    pub gas_limit: u64,
+    /// Maximum time a query can take, in milliseconds; 0 means no limit.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub timeout: Duration,
+}
+
+/// Export of traces to an OpenTelemetry collector.
+#[derive(Debug, Deserialize, Clone)]
+pub struct OtlpSettings {
+    pub enabled: bool,
+    /// The gRPC endpoint of the OTLP collector.
+    pub endpoint: String,
+    /// Fraction of the traces to export, between 0 and 1.
+    pub sample_rate: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TracingSettings {
+    pub otlp: OtlpSettings,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -87,6 +171,51 @@ pub struct DbSettings {
     ///
     /// This affects how long we can go back in state queries.
     pub state_hist_size: u64,
+    #[serde(default)]
+    pub rocksdb: RocksDbSettings,
+}
+
+/// Tuning of RocksDB: a preset for the workload of the node, with optional overrides.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RocksDbSettings {
+    pub profile: RocksDbProfile,
+    /// Size of the block cache in bytes.
+    pub block_cache_size: Option<usize>,
+    /// Size of a `memtable` in bytes.
+    pub write_buffer_size: Option<usize>,
+    /// Number of `memtable`s to fill up before writes stall.
+    pub max_write_buffer_number: Option<i32>,
+    /// Compression of each level, from L0 down, e.g. `["none", "lz4", "lz4hc"]`.
+    pub compression_per_level: Option<Vec<String>>,
+    pub max_background_jobs: Option<i32>,
+    /// Bits per key of the bloom filters; 0 disables them.
+    pub bloom_filter_bits: Option<u32>,
+}
+
+impl RocksDbSettings {
+    /// The options of the profile, with the overrides applied.
+    pub fn config(&self) -> RocksDbConfig {
+        let mut config = RocksDbConfig::for_profile(self.profile);
+        if let Some(v) = self.block_cache_size {
+            config.block_cache_size = Some(v);
+        }
+        if let Some(v) = self.write_buffer_size {
+            config.write_buffer_size = v;
+        }
+        if let Some(v) = self.max_write_buffer_number {
+            config.max_write_buffer_number = Some(v);
+        }
+        if let Some(ref v) = self.compression_per_level {
+            config.compression_per_level = v.clone();
+        }
+        if let Some(v) = self.max_background_jobs {
+            config.max_background_jobs = Some(v);
+        }
+        if let Some(v) = self.bloom_filter_bits {
+            config.bloom_filter_bits = v;
+        }
+        config
+    }
 }
 
 /// Settings affecting how we deal with failures in trying to send transactions to the local CometBFT node.
@@ -102,6 +231,134 @@ pub struct BroadcastSettings {
     pub retry_delay: Duration,
     /// Any over-estimation to apply on top of the estimate returned by the API.
     pub gas_overestimation_rate: f64,
+    pub outbox: OutboxSettings,
+}
+
+/// Settings of the durable queue of transactions the validator submits itself, e.g. checkpoint signatures,
+/// which are retried until they are included in a block.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct OutboxSettings {
+    /// Use the outbox; otherwise transactions are only retried `max_retries` times when broadcast.
+    pub enabled: bool,
+    /// Give up on a transaction after this many attempts to broadcast it.
+    pub max_attempts: u32,
+    /// Broadcast a transaction again if it hasn't been included in a block for this long.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub resubmit_after: Duration,
+    /// Upper limit of the exponentially growing delay between failed attempts.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub max_backoff: Duration,
+    /// Time after which a pending transaction is reported as stuck in the metrics.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub stuck_after: Duration,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Rotating CSV files in a directory.
+    Csv,
+    /// JSON lines sent to a Unix socket.
+    Socket,
+}
+
+/// Export of executed messages for analytics.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExportSettings {
+    pub enabled: bool,
+    pub format: ExportFormat,
+    /// Directory of the CSV files, or the path of the Unix socket.
+    pub path: PathBuf,
+    /// Start a new CSV file every so many blocks.
+    pub rotate_blocks: u64,
+}
+
+home_relative!(ExportSettings { path });
+
+/// Indexes of the executed blocks besides the logs blooms the Ethereum API relies on.
+#[derive(Debug, Deserialize, Clone)]
+pub struct EventIndexSettings {
+    pub sqlite: SqliteIndexSettings,
+}
+
+/// Index the transactions and the logs into a SQLite database, for explorers to query with SQL.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SqliteIndexSettings {
+    pub enabled: bool,
+    /// Path of the database file.
+    pub path: PathBuf,
+}
+
+home_relative!(SqliteIndexSettings { path });
+
+/// Recording of the executed blocks as test vectors for the interpreter.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TestVectorSettings {
+    pub enabled: bool,
+    /// Directory to write the vector to.
+    pub dir: PathBuf,
+    /// Stop recording after so many blocks; 0 means no limit.
+    pub max_blocks: u64,
+}
+
+home_relative!(TestVectorSettings { dir });
+
+/// Dump diagnostics when the chain stops committing blocks.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct WatchdogSettings {
+    pub enabled: bool,
+    /// The block time the chain is expected to have.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub expected_block_time: Duration,
+    /// Consider the chain stalled when no block has been committed for this many times the expected block time.
+    pub stall_factor: u32,
+    /// Directory to write the diagnostics to.
+    pub dump_dir: PathBuf,
+}
+
+impl WatchdogSettings {
+    /// How long without a commit before the chain is considered stalled.
+    pub fn stall_timeout(&self) -> Duration {
+        self.expected_block_time * self.stall_factor.max(1)
+    }
+}
+
+home_relative!(WatchdogSettings { dump_dir });
+
+/// Distribution of the builtin actors bundle, which every node needs to initialise the chain.
+#[derive(Debug, Deserialize, Clone)]
+pub struct GenesisCarSettings {
+    /// Serve the CAR file to the other nodes.
+    pub serve: bool,
+    /// Address to serve the CAR file and its checksum on.
+    pub listen: SocketAddress,
+    /// URL of the CAR file served by another node, to download it from before starting.
+    pub url: Option<String>,
+    /// Hex encoded SHA-256 checksum of the CAR file; fetched from the server if empty.
+    pub checksum: Option<String>,
+    /// Number of bytes to download at a time.
+    pub chunk_size: u64,
+}
+
+/// Prometheus metrics exporter.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    /// Address to serve the `/metrics` endpoint on.
+    pub listen: SocketAddress,
+}
+
+/// Operator endpoints to change the behaviour of the node at runtime.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AdminSettings {
+    pub enabled: bool,
+    /// Address to serve the admin endpoints on; it should not be reachable by the public.
+    pub listen: SocketAddress,
+    /// Limits on the queries sent to the admin endpoints, which replace the ones in `abci`.
+    #[serde(default)]
+    pub query_budget: QueryBudgetSettings,
 }
 
 #[serde_as]
@@ -118,9 +375,20 @@ pub struct TopDownSettings {
     pub proposal_delay: BlockHeight,
     /// The max number of blocks one should make the topdown proposal
     pub max_proposal_range: BlockHeight,
-    /// Parent syncing cron period, in seconds
+    /// The max number of parent blocks to cache before pausing the syncer.
+    #[serde(default)]
+    pub max_cache_blocks: Option<BlockHeight>,
+    /// The max number of validator changes and top down messages to keep in memory;
+    /// beyond this the payloads are fetched from the parent again when needed.
+    #[serde(default)]
+    pub max_cache_msgs: Option<usize>,
+    /// Longest parent syncing period, used once the syncer caught up with the parent, in seconds
     #[serde_as(as = "DurationSeconds<u64>")]
     pub polling_interval: Duration,
+    /// Shortest polling period, used while catching up with the parent, in seconds.
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub min_polling_interval: Option<Duration>,
     /// Top down exponential back off retry base
     #[serde_as(as = "DurationSeconds<u64>")]
     pub exponential_back_off: Duration,
@@ -134,6 +402,47 @@ pub struct TopDownSettings {
     /// The parent gateway address
     #[serde(deserialize_with = "deserialize_eth_address_from_str")]
     pub parent_gateway: Address,
+    /// Stop querying the parent for a while when it keeps failing.
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerSettings,
+    /// Refuse to start the chain from a genesis whose IPC configuration doesn't match
+    /// the registration of the subnet on the parent.
+    #[serde(default)]
+    pub check_genesis: bool,
+    /// What to do when the committed parent finality turns out to conflict with the parent:
+    /// `alert` only, stop taking transactions in `read_only` mode, or `halt` block processing.
+    #[serde(default)]
+    pub divergence_action: DivergenceAction,
+    /// How often validators gossip the latest parent block they have seen as final, in seconds,
+    /// through the IPLD Resolver; the votes aren't sent if it's not set.
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub vote_interval: Option<Duration>,
+}
+
+/// Circuit breaking for the queries to the parent, shared by the syncer and the finality provider.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    /// Number of consecutive failed queries after which the parent isn't queried for a while.
+    pub failure_threshold: u32,
+    /// How long to wait before probing the parent again, in seconds.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub open_duration: Duration,
+    /// The wait doubles every time the probe fails, up to this many seconds.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub max_open_duration: Duration,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(10),
+            max_open_duration: Duration::from_secs(300),
+        }
+    }
 }
 
 #[serde_as]
@@ -144,6 +453,31 @@ pub struct IpcSettings {
     /// The config for top down checkpoint. It's None if subnet id is root or not activating
     /// any top down checkpoint related operations
     pub topdown: Option<TopDownSettings>,
+    /// Reject transactions sending bottom-up messages while this many are waiting for a checkpoint; 0 means no limit.
+    #[serde(default)]
+    pub max_bottom_up_queue_depth: u64,
+    /// Number of recent cross-subnet messages to keep latency traces of, per direction; 0 disables them.
+    #[serde(default)]
+    pub msg_traces: u64,
+    /// Monitoring of the account submitting checkpoints to the parent; it needs `topdown` to reach the parent.
+    #[serde(default)]
+    pub relayer: Option<RelayerSettings>,
+}
+
+/// The account which submits bottom-up checkpoints to the parent, to be alerted before it runs out of funds.
+#[serde_as]
+#[derive(Debug, Deserialize, Clone)]
+pub struct RelayerSettings {
+    /// The address of the relayer on the parent.
+    #[serde(deserialize_with = "deserialize_eth_address_from_str")]
+    pub address: Address,
+    /// The gas limit of a checkpoint submission on the parent.
+    pub checkpoint_gas_limit: u64,
+    /// Raise an alert if the balance covers fewer checkpoint submissions than this.
+    pub min_checkpoints: u64,
+    /// How often to check the balance and the gas prices on the parent, in seconds.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub check_interval: Duration,
 }
 
 impl IpcSettings {
@@ -169,6 +503,12 @@ pub struct SnapshotSettings {
     pub hist_size: usize,
     /// Target chunk size, in bytes.
     pub chunk_size_bytes: usize,
+    /// Maximum number of chunks in a snapshot; large snapshots get bigger chunks to stay under it.
+    ///
+    /// 0 means unlimited.
+    pub max_chunks: usize,
+    /// Number of chunks to decompress, verify and write to the blockstore in parallel during state sync.
+    pub restore_concurrency: usize,
     /// How long to keep a snapshot from being purged after it has been requested by a peer.
     #[serde_as(as = "DurationSeconds<u64>")]
     pub last_access_hold: Duration,
@@ -177,6 +517,51 @@ pub struct SnapshotSettings {
     pub sync_poll_interval: Duration,
     /// Temporary directory for downloads.
     download_dir: Option<PathBuf>,
+    /// Compression applied to each chunk of the snapshots we export.
+    pub compression: SnapshotCompression,
+    /// Compression level; for zstd it's between 1 and 22.
+    pub compression_level: i32,
+    /// Where to upload the snapshots we export, besides offering them to peers.
+    #[serde(default)]
+    pub publish: SnapshotPublishSettings,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SnapshotPublishSettings {
+    /// Upload snapshots to an S3 compatible object store.
+    pub s3: Option<S3PublishSettings>,
+    /// Add snapshots to IPFS through the HTTP API of a Kubo node.
+    pub ipfs: Option<IpfsPublishSettings>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct S3PublishSettings {
+    /// URL of the service; buckets are addressed by path.
+    pub endpoint: Url,
+    /// Region to sign the requests for.
+    pub region: String,
+    pub bucket: String,
+    /// Prefix of the object keys.
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key_id: String,
+    /// Best set with the `FM_SNAPSHOTS__PUBLISH__S3__SECRET_ACCESS_KEY` environment variable.
+    pub secret_access_key: String,
+    /// Base URL the bucket is publicly readable at, if different from the endpoint and bucket.
+    pub public_url: Option<Url>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IpfsPublishSettings {
+    /// URL of the HTTP API of the Kubo node.
+    pub api_url: Url,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotCompression {
+    None,
+    Zstd,
 }
 
 impl SnapshotSettings {
@@ -185,10 +570,32 @@ impl SnapshotSettings {
     }
 }
 
+/// Another subnet hosted in the same process, with its own home directory and configuration.
+#[derive(Debug, Deserialize, Clone)]
+pub struct InstanceSettings {
+    /// Home directory of the instance, with its own data, keys and configuration;
+    /// relative paths are relative to the home directory of the process.
+    pub home_dir: PathBuf,
+    /// Configuration directory of the instance, relative to its own home directory.
+    #[serde(default = "default_instance_config_dir")]
+    pub config_dir: PathBuf,
+    /// Serve the Ethereum API of the instance in the same process, according to its `eth` settings.
+    #[serde(default)]
+    pub eth_api: bool,
+}
+
+fn default_instance_config_dir() -> PathBuf {
+    PathBuf::from("config")
+}
+
+home_relative!(InstanceSettings { home_dir });
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     /// Home directory configured on the CLI, to which all paths in settings can be set relative.
     home_dir: PathBuf,
+    /// Run mode configured on the CLI, which selects the overrides in the config directory.
+    run_mode: String,
     /// Database files.
     data_dir: PathBuf,
     /// State snapshots.
@@ -203,7 +610,10 @@ pub struct Settings {
 
     /// Secp256k1 private key used for signing transactions sent in the validator's name. Leave empty if not validating.
     pub validator_key: Option<SigningKey>,
+    /// Name of the account in the keystore to use as the validator key, instead of `validator_key`.
+    pub validator_account: Option<String>,
 
+    pub keystore: KeystoreSettings,
     pub abci: AbciSettings,
     pub db: DbSettings,
     pub snapshots: SnapshotSettings,
@@ -212,6 +622,17 @@ pub struct Settings {
     pub resolver: ResolverSettings,
     pub broadcast: BroadcastSettings,
     pub ipc: IpcSettings,
+    pub tracing: TracingSettings,
+    pub metrics: MetricsSettings,
+    pub admin: AdminSettings,
+    pub export: ExportSettings,
+    pub event_index: EventIndexSettings,
+    pub test_vectors: TestVectorSettings,
+    pub watchdog: WatchdogSettings,
+    pub genesis_car: GenesisCarSettings,
+    /// Other subnets to run in the same process, sharing its runtime and metrics endpoint.
+    #[serde(default)]
+    pub instances: Vec<InstanceSettings>,
 }
 
 #[macro_export]
@@ -267,6 +688,7 @@ impl Settings {
             // The `home_dir` key is not added to `default.toml` so there is no confusion
             // about where it will be coming from.
             .set_override("home_dir", home_dir.to_string_lossy().as_ref())?
+            .set_override("run_mode", run_mode)?
             .build()?;
 
         // Deserialize (and thus freeze) the entire configuration.
@@ -278,6 +700,36 @@ impl Settings {
         &self.home_dir
     }
 
+    /// Load the settings of the other instances to run in this process, in the same run mode.
+    pub fn instance_settings(&self) -> Result<Vec<Settings>, ConfigError> {
+        self.instances
+            .iter()
+            .map(|i| {
+                let home_dir = i.home_dir(&self.home_dir);
+                let config_dir = expand_path(&home_dir, &i.config_dir);
+                Settings::new(&config_dir, &home_dir, &self.run_mode)
+            })
+            .collect()
+    }
+
+    /// Directory of the validator's outbox of pending transactions.
+    pub fn outbox_dir(&self) -> PathBuf {
+        self.data_dir().join("outbox")
+    }
+
+    /// Directory of the CAR files with the bottom-up checkpoints pruned from the gateway.
+    pub fn checkpoint_archive_dir(&self) -> PathBuf {
+        self.data_dir().join("checkpoints")
+    }
+
+    /// Path to the policy module, relative to the home directory, if one is configured.
+    pub fn policy_module(&self) -> Option<PathBuf> {
+        self.fvm
+            .policy_module
+            .as_ref()
+            .map(|p| expand_path(self.home_dir(), p))
+    }
+
     /// Tendermint RPC URL from the environment or the config file.
     pub fn tendermint_rpc_url(&self) -> anyhow::Result<Url> {
         // Prefer the "standard" env var used in the CLI.
@@ -328,11 +780,13 @@ pub fn expand_tilde<P: AsRef<Path>>(path: P) -> PathBuf {
 mod tests {
     use std::path::PathBuf;
     use std::str::FromStr;
+    use std::time::Duration;
 
+    use fendermint_rocksdb::{RocksDbConfig, RocksDbProfile};
     use ipc_sdk::subnet_id::SubnetID;
 
     use super::expand_tilde;
-    use super::Settings;
+    use super::{InstanceSettings, Settings};
 
     fn parse_config(run_mode: &str) -> Settings {
         let current_dir = PathBuf::from(".");
@@ -344,6 +798,12 @@ mod tests {
     fn parse_default_config() {
         let settings = parse_config("");
         assert!(!settings.resolver.enabled());
+        assert_eq!(settings.db.rocksdb.profile, RocksDbProfile::Validator);
+        assert_eq!(
+            settings.db.rocksdb.config(),
+            RocksDbConfig::for_profile(RocksDbProfile::Validator)
+        );
+        assert_eq!(settings.watchdog.stall_timeout(), Duration::from_secs(30));
     }
 
     #[test]
@@ -352,6 +812,23 @@ mod tests {
         assert!(settings.resolver.enabled());
     }
 
+    #[test]
+    fn parse_instance_config() {
+        let mut settings = parse_config("test");
+        assert!(settings.instance_settings().unwrap().is_empty());
+
+        settings.instances.push(InstanceSettings {
+            home_dir: PathBuf::from("."),
+            config_dir: PathBuf::from("../config"),
+            eth_api: false,
+        });
+        let instances = settings.instance_settings().unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].home_dir(), PathBuf::from("."));
+        // The instances are loaded in the same mode.
+        assert!(instances[0].resolver.enabled());
+    }
+
     #[test]
     fn tilde_expands_to_home() {
         let home = std::env::var("HOME").expect("should work on Linux");
@@ -17,6 +17,12 @@ pub struct ResolverSettings {
     /// Time to wait between attempts to resolve a CID, in seconds.
     #[serde_as(as = "DurationSeconds<u64>")]
     pub retry_delay: Duration,
+    /// Maximum number of CIDs resolved at the same time; further ones wait for their turn.
+    pub max_in_flight: usize,
+    /// Time in seconds after which CIDs that couldn't be resolved are dropped, so they are never proposed;
+    /// 0 means they are kept until resolved.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub resolve_ttl: Duration,
     /// The current subnet ID for which this node can serve data.
     ///
     /// TODO #231: Take this from the IPC settings.
@@ -0,0 +1,256 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Operator endpoints which change how the node behaves at runtime,
+//! served separately from the public APIs.
+
+use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine;
+use fendermint_vm_topdown::breaker::{CircuitBreaker, ParentHealth};
+use fendermint_vm_topdown::divergence::{Divergence, FinalityConflict};
+use fendermint_vm_topdown::relayer::{RelayerStatus, RelayerStatusHandle};
+use serde::{Deserialize, Serialize};
+
+use crate::lane::{LaneError, OperatorLane};
+use crate::mempool::{EvictionList, EvictionRequest, MempoolAdmin, MempoolContent};
+use crate::BlockHeight;
+
+/// The height after which the application stops processing blocks.
+///
+/// Shared between the [`App`](crate::App) and the admin endpoint, so the
+/// operator can set or clear it without restarting the node.
+#[derive(Clone, Debug, Default)]
+pub struct HaltHeight(Arc<AtomicU64>);
+
+impl HaltHeight {
+    pub fn new(height: Option<BlockHeight>) -> Self {
+        let hh = Self::default();
+        hh.set(height);
+        hh
+    }
+
+    /// Get the halt height, if there is one.
+    pub fn get(&self) -> Option<BlockHeight> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            h => Some(h),
+        }
+    }
+
+    /// Set or clear the halt height.
+    ///
+    /// Halting at the genesis isn't meaningful, so 0 is treated as clearing it.
+    pub fn set(&self, height: Option<BlockHeight>) {
+        self.0.store(height.unwrap_or_default(), Ordering::Relaxed)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HaltHeightBody {
+    height: Option<BlockHeight>,
+}
+
+#[derive(Deserialize)]
+struct LaneTxBody {
+    /// Base64 encoded transaction, the same as what goes into `broadcast_tx_sync`.
+    tx: String,
+}
+
+#[derive(Serialize)]
+struct LaneTxResult {
+    cid: String,
+    pending: usize,
+}
+
+/// Serve the admin endpoints until the process exits:
+/// * `GET /halt-height` returns the current halt height, if any
+/// * `PUT /halt-height` with `{"height": H}` sets it; `{"height": null}` clears it
+/// * `GET /relayer` returns what the checkpoint relayer can afford on the parent, if it's monitored
+/// * `GET /parent` returns the health of the connection to the parent, if top-down finality is enabled
+/// * `POST /operator-lane` with `{"tx": B64}` puts a transaction signed by an operator key
+///   in front of the next blocks this node proposes, if the lane is enabled
+/// * `GET /mempool` lists the transactions in the CometBFT mempool, decoded and checked
+///   against the committed state
+/// * `POST /mempool/evict` with `{"hashes": [..], "senders": [..], "blocks": N}` drops
+///   transactions from the mempool of this node, and refuses them for the next `N` blocks
+/// * `GET /mempool/evictions` lists the evictions in effect
+/// * `POST /rpc` is a CometBFT compatible JSON-RPC endpoint, if given, e.g. to answer
+///   the queries of trusted callers with a different budget than the public ones,
+///   and the ones only meant for the operator, like the gas statistics
+pub async fn serve<A: ToSocketAddrs>(
+    listen: A,
+    halt_height: HaltHeight,
+    relayer: Option<RelayerStatusHandle>,
+    parent: Option<CircuitBreaker>,
+    divergence: Option<Divergence>,
+    lane: Option<OperatorLane>,
+    mempool: Option<MempoolAdmin>,
+    rpc: Option<Router>,
+) -> anyhow::Result<()> {
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))?;
+
+    let mut app = Router::new()
+        .route("/halt-height", get(get_halt_height).put(put_halt_height))
+        .with_state(halt_height);
+
+    if let Some(relayer) = relayer {
+        app = app.merge(
+            Router::new()
+                .route("/relayer", get(get_relayer_status))
+                .with_state(relayer),
+        );
+    }
+
+    if let Some(parent) = parent {
+        app = app.merge(
+            Router::new()
+                .route("/parent", get(get_parent_health))
+                .with_state(parent),
+        );
+    }
+
+    if let Some(divergence) = divergence {
+        app = app.merge(
+            Router::new()
+                .route(
+                    "/parent/conflict",
+                    get(get_parent_conflict).delete(delete_parent_conflict),
+                )
+                .with_state(divergence),
+        );
+    }
+
+    if let Some(lane) = lane {
+        app = app.merge(
+            Router::new()
+                .route("/operator-lane", post(post_lane_tx))
+                .with_state(lane),
+        );
+    }
+
+    if let Some(mempool) = mempool {
+        app = app.merge(
+            Router::new()
+                .route("/mempool", get(get_mempool))
+                .route("/mempool/evict", post(post_mempool_evict))
+                .route("/mempool/evictions", get(get_mempool_evictions))
+                .with_state(mempool),
+        );
+    }
+
+    if let Some(rpc) = rpc {
+        app = app.nest("/rpc", rpc);
+    }
+
+    tracing::info!(?addr, "serving admin endpoints");
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .context("failed to serve admin endpoints")
+}
+
+async fn get_halt_height(State(hh): State<HaltHeight>) -> Json<HaltHeightBody> {
+    Json(HaltHeightBody { height: hh.get() })
+}
+
+async fn put_halt_height(
+    State(hh): State<HaltHeight>,
+    Json(body): Json<HaltHeightBody>,
+) -> Json<HaltHeightBody> {
+    tracing::warn!(height = body.height, "halt height changed by the operator");
+    hh.set(body.height);
+    Json(HaltHeightBody { height: hh.get() })
+}
+
+async fn get_parent_health(State(breaker): State<CircuitBreaker>) -> Json<ParentHealth> {
+    Json(breaker.health())
+}
+
+async fn get_parent_conflict(
+    State(divergence): State<Divergence>,
+) -> Json<Option<FinalityConflict>> {
+    Json(divergence.conflict())
+}
+
+/// Clearing the conflict doesn't clear the halt height it may have set.
+async fn delete_parent_conflict(
+    State(divergence): State<Divergence>,
+) -> Json<Option<FinalityConflict>> {
+    let conflict = divergence.clear();
+    tracing::warn!(
+        ?conflict,
+        "parent finality conflict cleared by the operator"
+    );
+    Json(conflict)
+}
+
+async fn post_lane_tx(
+    State(lane): State<OperatorLane>,
+    Json(body): Json<LaneTxBody>,
+) -> Result<Json<LaneTxResult>, (StatusCode, String)> {
+    let tx = base64::engine::general_purpose::STANDARD
+        .decode(body.tx)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid base64: {e}")))?;
+
+    match lane.submit(tx) {
+        Ok(cid) => Ok(Json(LaneTxResult {
+            cid: cid.to_string(),
+            pending: lane.pending(),
+        })),
+        Err(e) => {
+            let status = match e {
+                LaneError::NotOperator(_) => StatusCode::FORBIDDEN,
+                LaneError::Full | LaneError::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+                LaneError::Unsigned
+                | LaneError::InvalidSignature(_)
+                | LaneError::TooLarge { .. } => StatusCode::BAD_REQUEST,
+            };
+            Err((status, e.to_string()))
+        }
+    }
+}
+
+async fn get_mempool(
+    State(mempool): State<MempoolAdmin>,
+) -> Result<Json<MempoolContent>, (StatusCode, String)> {
+    mempool
+        .content()
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{e:#}")))
+}
+
+async fn post_mempool_evict(
+    State(mempool): State<MempoolAdmin>,
+    Json(body): Json<EvictionRequest>,
+) -> Result<Json<EvictionList>, (StatusCode, String)> {
+    mempool
+        .evict(body)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))
+}
+
+async fn get_mempool_evictions(State(mempool): State<MempoolAdmin>) -> Json<EvictionList> {
+    Json(mempool.evictions().list())
+}
+
+/// Not found until the first check of the relayer succeeded.
+async fn get_relayer_status(
+    State(relayer): State<RelayerStatusHandle>,
+) -> Result<Json<RelayerStatus>, StatusCode> {
+    relayer.get().map(Json).ok_or(StatusCode::NOT_FOUND)
+}
@@ -1,40 +1,63 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::HashSet;
 use std::future::Future;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, Context, Result};
-use async_stm::{atomically, atomically_or_err};
+use async_stm::atomically;
 use async_trait::async_trait;
 use cid::Cid;
+use ethers::abi::AbiEncode;
 use fendermint_abci::util::take_until_max_size;
 use fendermint_abci::{AbciResult, Application};
 use fendermint_storage::{
-    Codec, Encode, KVCollection, KVRead, KVReadable, KVStore, KVWritable, KVWrite,
+    Codec, Encode, KVCollection, KVError, KVRead, KVReadable, KVResult, KVStore, KVWritable,
+    KVWrite,
 };
 use fendermint_vm_core::Timestamp;
 use fendermint_vm_genesis::{Power, Validator};
 use fendermint_vm_interpreter::bytes::{
-    BytesMessageApplyRes, BytesMessageCheckRes, BytesMessageQuery, BytesMessageQueryRes,
+    parse_genesis, BytesMessageApplyRes, BytesMessageCheckRes, BytesMessageQuery,
+    BytesMessageQueryRes,
 };
 use fendermint_vm_interpreter::chain::{
     ChainMessageApplyRet, CheckpointPool, IllegalMessage, TopDownFinalityProvider,
 };
+use fendermint_vm_interpreter::fvm::state::ipc::GatewayCaller;
 use fendermint_vm_interpreter::fvm::state::{
-    empty_state_tree, CheckStateRef, FvmExecState, FvmGenesisState, FvmQueryState, FvmStateParams,
-    FvmUpdatableParams,
+    empty_state_tree, BlockHash, CheckStateRef, FvmExecState, FvmGenesisState, FvmQueryState,
+    FvmStateParams, FvmUpdatableParams, QueryBudget, QueryBudgetExceeded,
 };
 use fendermint_vm_interpreter::fvm::store::ReadOnlyBlockstore;
-use fendermint_vm_interpreter::fvm::{FvmApplyRet, FvmGenesisOutput};
-use fendermint_vm_interpreter::signed::InvalidSignature;
+use fendermint_vm_interpreter::fvm::{
+    expected_topdown_nonce, topdown_progress, FvmApplyRet, FvmBeginRet, FvmGenesisOutput,
+};
+use fendermint_vm_interpreter::signed::{
+    BlockSignatureCheck, HasFilecoinSignatures, InvalidSignature, ProposalSignatureCheck,
+};
+use fendermint_vm_interpreter::vectors::ReceiptVector;
 use fendermint_vm_interpreter::{
-    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, QueryInterpreter,
+    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, ProposalRejection,
+    ProposalVerdict, QueryInterpreter, VerifyBlockInterpreter,
 };
-use fendermint_vm_message::query::FvmQueryHeight;
+use fendermint_vm_message::block_hash::EthBlockHashVersion;
+use fendermint_vm_message::ipc::ParentFinality;
+use fendermint_vm_message::query::{
+    feature, AppHashReport, Capabilities, FvmQuery, FvmQueryHeight, TopDownMessage, ValidatorSet,
+    APP_HASH_PATH, BLOCK_HEIGHT_BY_HASH_PATH, CHAIN_MESSAGE_VERSION, ETH_BLOCK_HEIGHT_BY_HASH_PATH,
+    GAS_STATS_PATH, LOGS_BLOOM_PATH, MAX_TOPDOWN_MSGS_RANGE, MSG_TRACE_PATH, PARENT_FINALITY_PATH,
+    REJECTED_PROPOSALS_PATH, TOPDOWN_MSGS_PATH, VALIDATOR_SET_PATH,
+};
+use fendermint_vm_message::signed::DomainHash;
 use fendermint_vm_snapshot::{SnapshotClient, SnapshotError};
+use fendermint_vm_topdown::divergence::{Divergence, DivergenceAction, FinalityConflict};
+use fendermint_vm_topdown::{ParentViewProvider, SubnetLabel};
 use fvm::engine::MultiEngine;
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
 use fvm_shared::chainid::ChainID;
 use fvm_shared::clock::ChainEpoch;
 use fvm_shared::econ::TokenAmount;
@@ -44,13 +67,43 @@ use serde::{Deserialize, Serialize};
 use tendermint::abci::request::CheckTxKind;
 use tendermint::abci::{request, response};
 
+use crate::admin::HaltHeight;
+use crate::blockgas;
+use crate::bottomup::{
+    self, is_bottom_up_tx, GatewayView, QueueBounds, QueuedMessage, BOTTOM_UP_QUEUE_DEPTH,
+    BOTTOM_UP_QUEUE_REJECTED,
+};
+use crate::clock::TimeMonitor;
+use crate::dedup::{tx_cid, RecentTxs};
+use crate::export::{ExportRecord, Exporter};
+use crate::gasstats::{BlockGasStats, GasStatsQuery, GasStatsReport};
+use crate::genesischeck::ParentGenesisCheck;
+use crate::index::{BlockHashIndex, EventIndex, LogsBloomIndex};
+use crate::lane::OperatorLane;
+use crate::latency::{
+    self, unix_millis, Direction, MessageTrace, MsgTraceQuery, Stage, TopDownProposals,
+};
+use crate::mempool::{MempoolCheck, MempoolEvictions};
+use crate::ordering;
+use crate::proposals::{self, RecentRejections, RejectReason, RejectedProposal};
+use crate::vectors::VectorRecorder;
+use crate::watchdog::BlockProgress;
 use crate::{tmconv::*, VERSION};
 use crate::{BlockHeight, APP_VERSION};
 
+pub use fendermint_vm_message::query::STATE_SYNC_STATUS_PATH;
+
 #[derive(Serialize)]
 #[repr(u8)]
 pub enum AppStoreKey {
     State,
+    Journal,
+    /// The latest record in the validator set history.
+    ValidatorSet,
+    /// The conflict between the committed parent finality and the parent, if there is one.
+    FinalityConflict,
+    /// The range of nonces in the bottom-up message queue.
+    BottomUpQueue,
 }
 
 // TODO: What range should we use for our own error codes? Should we shift FVM errors?
@@ -65,10 +118,24 @@ pub enum AppError {
     IllegalMessage = 53,
     /// The genesis block hasn't been initialized yet.
     NotInitialized = 54,
+    /// The transaction has already been seen in the mempool or in a recent block.
+    DuplicateTransaction = 55,
+    /// The application has reached its halt height and doesn't take new transactions.
+    Halted = 56,
+    /// The query ran out of the gas or the time it was allowed to use.
+    QueryBudgetExceeded = 57,
+    /// The query is only answered on the admin endpoint.
+    AdminOnly = 58,
+    /// Too many bottom-up messages are waiting for a checkpoint to take new ones.
+    BottomUpQueueFull = 59,
+    /// The operator evicted the transaction, or its sender, from the mempool of this node.
+    Evicted = 60,
+    /// The committed parent finality conflicts with the parent, so no new transactions are taken.
+    ReadOnly = 61,
 }
 
 /// The application state record we keep a history of in the database.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct AppState {
     /// Last committed block height.
     block_height: BlockHeight,
@@ -98,6 +165,38 @@ impl AppState {
     }
 }
 
+/// Marker written before the FVM state is flushed during a commit, and removed once
+/// everything about the block has been written. Finding it on startup means the process
+/// died half way through committing a block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommitJournal {
+    /// Height of the block being committed.
+    block_height: BlockHeight,
+    /// The application state before the commit started, to roll back to.
+    prev_state: AppState,
+}
+
+/// The conflict between the committed parent finality and the parent, kept until the
+/// operator clears it, so that the safety action is taken again after a restart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct ConflictRecord {
+    conflict: FinalityConflict,
+    /// The height the application halted at because of the conflict, if it did.
+    halted_at: Option<BlockHeight>,
+}
+
+/// Transactions checked against the state by the proposer, when `CheckTx` defers it.
+///
+/// Kept until the next commit, so that proposals in later rounds at the same height only
+/// have to check the transactions they haven't accepted yet, on top of the effects of the others.
+/// Rejected transactions are checked again in every round, because they might have only failed
+/// for the lack of another transaction which has arrived since, e.g. one with a lower nonce.
+struct ProposalChecks<SS> {
+    state: FvmExecState<ReadOnlyBlockstore<SS>>,
+    /// The transactions accepted so far, whose effects are in the state.
+    accepted: HashSet<Cid>,
+}
+
 pub struct AppConfig<S: KVStore> {
     /// Namespace to store the current app state.
     pub app_namespace: S::Namespace,
@@ -105,10 +204,48 @@ pub struct AppConfig<S: KVStore> {
     pub state_hist_namespace: S::Namespace,
     /// Size of state history to keep; 0 means unlimited.
     pub state_hist_size: u64,
+    /// Namespace to store the logs bloom of each block.
+    pub logs_bloom_namespace: S::Namespace,
+    /// Namespace to store the height of each block by its hash.
+    pub block_hashes_namespace: S::Namespace,
+    /// Namespace to store the height of each block by its Ethereum hash.
+    pub eth_block_hashes_namespace: S::Namespace,
+    /// Namespace to store the gas statistics of each block.
+    pub gas_stats_namespace: S::Namespace,
+    /// Number of recent blocks to keep gas statistics for; 0 disables them.
+    pub gas_stats_blocks: u64,
+    /// Namespace to store the power table at every height where it changed.
+    pub validator_sets_namespace: S::Namespace,
+    /// Namespace to store the bottom-up messages waiting to be included in a checkpoint.
+    pub bottom_up_queue_namespace: S::Namespace,
+    /// Namespace to store the stages reached by the top-down messages, by their nonce.
+    pub topdown_traces_namespace: S::Namespace,
+    /// Namespace to store the stages reached by the bottom-up messages, by their nonce.
+    pub bottomup_traces_namespace: S::Namespace,
+    /// Number of recent cross-subnet messages to keep traces of, per direction; 0 disables them.
+    pub msg_traces: u64,
     /// Path to the Wasm bundle.
     ///
     /// Only loaded once during genesis; later comes from the [`StateTree`].
     pub builtin_actors_bundle: PathBuf,
+    /// Number of recent blocks to remember transactions for, to reject duplicates in `CheckTx`; 0 disables it.
+    pub tx_dedup_blocks: u64,
+    /// Whether traces are exported, to let clients know through the capabilities query.
+    pub tracing_enabled: bool,
+    /// Exporter of executed messages for analytics, if enabled.
+    pub exporter: Option<Exporter>,
+    /// Recorder of executed blocks as interpreter test vectors, if enabled.
+    pub vector_recorder: Option<VectorRecorder>,
+    /// Height after which the application stops processing blocks.
+    pub halt_height: HaltHeight,
+    /// Reports the drift of the block times from the local clock.
+    pub time_monitor: TimeMonitor,
+    /// Whether `CheckTx` leaves the checks against the state to the proposer.
+    pub defer_check: bool,
+    /// Limits on the resources a query can use.
+    pub query_budget: QueryBudget,
+    /// The subnet the application runs, passed on to label the metrics of its components.
+    pub subnet_id: SubnetLabel,
 }
 
 /// Handle ABCI requests.
@@ -161,6 +298,80 @@ where
     ///
     /// Zero means unlimited.
     state_hist_size: u64,
+    /// Transactions seen recently in the mempool or in blocks.
+    recent_txs: Arc<std::sync::Mutex<RecentTxs>>,
+    /// Whether traces are exported.
+    tracing_enabled: bool,
+    /// Exporter of executed messages for analytics.
+    exporter: Option<Exporter>,
+    /// Recorder of executed blocks as interpreter test vectors.
+    vector_recorder: Option<VectorRecorder>,
+    /// Logs bloom of each committed block, to speed up log queries over long ranges.
+    logs_blooms: Arc<LogsBloomIndex<DB, S>>,
+    /// Height of each committed block by its hash, and by the hash the Ethereum API
+    /// exposes for it if it's derived, to look up blocks by hash.
+    block_hashes: Arc<BlockHashIndex<DB, S>>,
+    /// Further indexes of the executed blocks, e.g. for explorers.
+    event_indexes: Vec<Arc<dyn EventIndex>>,
+    /// Height after which no more blocks are processed, for coordinated maintenance.
+    halt_height: HaltHeight,
+    /// Reports the drift of the block times from the local clock.
+    time_monitor: TimeMonitor,
+    /// Whether the transactions have to be checked against the state before proposing them.
+    defer_check: bool,
+    /// Results of the checks done while proposing, if they are deferred from `CheckTx`.
+    proposal_checks: Arc<tokio::sync::Mutex<Option<ProposalChecks<SS>>>>,
+    /// Limits on the resources a query can use.
+    query_budget: QueryBudget,
+    /// Gas statistics of each committed block, for the window of recent blocks.
+    gas_stats: KVCollection<S, BlockHeight, RawBytes>,
+    /// Gas statistics of the block being executed.
+    block_gas_stats: Arc<std::sync::Mutex<BlockGasStats>>,
+    /// Number of recent blocks to keep gas statistics for; 0 disables them.
+    gas_stats_blocks: u64,
+    /// The power table at every height where it changed, never pruned.
+    validator_sets: KVCollection<S, BlockHeight, RawBytes>,
+    /// Power updates of the block being executed, as `(public_key, power)` pairs.
+    block_power_updates: Arc<std::sync::Mutex<Vec<(Vec<u8>, u64)>>>,
+    /// Bottom-up messages waiting to be included in a checkpoint, by their nonce.
+    bottom_up_queue: KVCollection<S, u64, RawBytes>,
+    /// Reject transactions sending bottom-up messages when this many are waiting; 0 means no limit.
+    max_bottom_up_queue_depth: u64,
+    /// The checkpoint period of the subnet, looked up once; `None` if it has no parent.
+    bottom_up_period: Arc<OnceLock<Option<u64>>>,
+    /// What to do when the committed parent finality turns out to conflict with the parent.
+    divergence_action: DivergenceAction,
+    /// The conflict found by the syncer, if any; never tripped if top-down finality is disabled.
+    divergence: Divergence,
+    /// The stages reached by the top-down messages, by their nonce.
+    topdown_traces: KVCollection<S, u64, RawBytes>,
+    /// The stages reached by the bottom-up messages, by their nonce.
+    bottomup_traces: KVCollection<S, u64, RawBytes>,
+    /// Number of recent cross-subnet messages to keep traces of, per direction; 0 disables them.
+    msg_traces: u64,
+    /// When the parent finalities proposed since the last commit were first seen.
+    topdown_proposals: Arc<std::sync::Mutex<TopDownProposals>>,
+    /// Whether this copy of the application answers the queries meant for the operator.
+    admin_queries: bool,
+    /// The latest proposals this node voted against, and why.
+    rejected_proposals: RecentRejections,
+    /// The last committed block and the requests in progress, for the watchdog.
+    progress: BlockProgress,
+    /// Check the genesis against the parent before initializing the chain.
+    parent_genesis_check: Option<ParentGenesisCheck>,
+    /// Transactions of the operator to put in front of the others in the proposals.
+    operator_lane: Option<OperatorLane>,
+    /// Time allowed for verifying the signatures in a proposal, if they are verified at all.
+    proposal_signature_budget: Option<Duration>,
+    /// Threads to verify the signatures of a block with before executing it, if enabled.
+    block_signature_pool: Option<Arc<rayon::ThreadPool>>,
+    /// Hash and transactions of the latest proposal processed, to verify the signatures of
+    /// when the block is executed, if it's the one that got decided.
+    processed_proposal: Arc<std::sync::Mutex<Option<(BlockHash, Vec<Vec<u8>>)>>>,
+    /// Transactions the operator evicted from the mempool, to fail their checks.
+    mempool_evictions: Option<MempoolEvictions>,
+    /// Label of the metrics the application reports itself, e.g. the bottom-up queue depth.
+    subnet_id: SubnetLabel,
 }
 
 impl<DB, SS, S, I> App<DB, SS, S, I>
@@ -169,7 +380,9 @@ where
         + Codec<AppState>
         + Encode<AppStoreKey>
         + Encode<BlockHeight>
-        + Codec<FvmStateParams>,
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
     DB: KVWritable<S> + KVReadable<S> + Clone + 'static,
     SS: Blockstore + Clone + 'static,
 {
@@ -182,8 +395,64 @@ where
         parent_finality_provider: TopDownFinalityProvider,
         snapshots: Option<SnapshotClient>,
     ) -> Result<Self> {
-        let app = Self {
-            db: Arc::new(db),
+        let app = Self::build(
+            config,
+            db,
+            state_store,
+            interpreter,
+            resolve_pool,
+            parent_finality_provider,
+            snapshots,
+        );
+        app.recover_commit()?;
+        app.init_committed_state()?;
+        Ok(app)
+    }
+
+    /// Create an application on a database it can't write to, e.g. a secondary instance,
+    /// only to answer queries.
+    ///
+    /// Recovering interrupted commits and initializing the state is up to the node
+    /// writing the database, so this only checks that there is a state to query.
+    pub fn new_read_only(
+        config: AppConfig<S>,
+        db: DB,
+        state_store: SS,
+        interpreter: I,
+        resolve_pool: CheckpointPool,
+        parent_finality_provider: TopDownFinalityProvider,
+    ) -> Result<Self> {
+        let app = Self::build(
+            config,
+            db,
+            state_store,
+            interpreter,
+            resolve_pool,
+            parent_finality_provider,
+            None,
+        );
+        if app.get_committed_state()?.is_none() {
+            return Err(anyhow!("there is no committed state to query"));
+        }
+        Ok(app)
+    }
+
+    fn build(
+        config: AppConfig<S>,
+        db: DB,
+        state_store: SS,
+        interpreter: I,
+        resolve_pool: CheckpointPool,
+        parent_finality_provider: TopDownFinalityProvider,
+        snapshots: Option<SnapshotClient>,
+    ) -> Self {
+        let db = Arc::new(db);
+        let divergence = parent_finality_provider
+            .divergence()
+            .cloned()
+            .unwrap_or_default();
+        Self {
+            db: db.clone(),
             state_store: Arc::new(state_store),
             multi_engine: Arc::new(MultiEngine::new(1)),
             builtin_actors_bundle: config.builtin_actors_bundle,
@@ -196,9 +465,129 @@ where
             snapshots,
             exec_state: Arc::new(tokio::sync::Mutex::new(None)),
             check_state: Arc::new(tokio::sync::Mutex::new(None)),
-        };
-        app.init_committed_state()?;
-        Ok(app)
+            recent_txs: Arc::new(std::sync::Mutex::new(RecentTxs::new(
+                config.tx_dedup_blocks,
+            ))),
+            tracing_enabled: config.tracing_enabled,
+            exporter: config.exporter,
+            vector_recorder: config.vector_recorder,
+            logs_blooms: Arc::new(LogsBloomIndex::new(db.clone(), config.logs_bloom_namespace)),
+            block_hashes: Arc::new(BlockHashIndex::new(
+                db,
+                config.block_hashes_namespace,
+                config.eth_block_hashes_namespace,
+            )),
+            event_indexes: Vec::new(),
+            halt_height: config.halt_height,
+            time_monitor: config.time_monitor,
+            defer_check: config.defer_check,
+            proposal_checks: Arc::new(tokio::sync::Mutex::new(None)),
+            query_budget: config.query_budget,
+            gas_stats: KVCollection::new(config.gas_stats_namespace),
+            block_gas_stats: Default::default(),
+            gas_stats_blocks: config.gas_stats_blocks,
+            validator_sets: KVCollection::new(config.validator_sets_namespace),
+            block_power_updates: Default::default(),
+            bottom_up_queue: KVCollection::new(config.bottom_up_queue_namespace),
+            max_bottom_up_queue_depth: 0,
+            bottom_up_period: Default::default(),
+            divergence_action: DivergenceAction::default(),
+            divergence,
+            topdown_traces: KVCollection::new(config.topdown_traces_namespace),
+            bottomup_traces: KVCollection::new(config.bottomup_traces_namespace),
+            msg_traces: config.msg_traces,
+            topdown_proposals: Default::default(),
+            admin_queries: false,
+            rejected_proposals: Default::default(),
+            progress: Default::default(),
+            parent_genesis_check: None,
+            operator_lane: None,
+            proposal_signature_budget: None,
+            block_signature_pool: None,
+            processed_proposal: Default::default(),
+            mempool_evictions: None,
+            subnet_id: config.subnet_id,
+        }
+    }
+
+    /// Use a different query budget, for a copy of the application serving trusted callers.
+    pub fn with_query_budget(mut self, query_budget: QueryBudget) -> Self {
+        self.query_budget = query_budget;
+        self
+    }
+
+    /// Answer the queries meant for the operator, for a copy of the application serving the admin endpoint.
+    pub fn with_admin_queries(mut self, enabled: bool) -> Self {
+        self.admin_queries = enabled;
+        self
+    }
+
+    /// Refuse to initialize the chain from a genesis which doesn't match the subnet on the parent.
+    pub fn with_parent_genesis_check(mut self, check: ParentGenesisCheck) -> Self {
+        self.parent_genesis_check = Some(check);
+        self
+    }
+
+    /// Prioritize the transactions of the operator in the blocks this node proposes.
+    pub fn with_operator_lane(mut self, lane: OperatorLane) -> Self {
+        self.operator_lane = Some(lane);
+        self
+    }
+
+    /// Fail the checks of the transactions the operator evicted from the mempool.
+    pub fn with_mempool_evictions(mut self, evictions: MempoolEvictions) -> Result<Self> {
+        // Evictions last for a number of blocks from the last committed one.
+        if let Some(state) = self.get_committed_state()? {
+            evictions.committed(state.block_height);
+        }
+        self.mempool_evictions = Some(evictions);
+        Ok(self)
+    }
+
+    /// Verify the signatures in the proposals before voting on them, for up to the given time.
+    pub fn with_proposal_signature_budget(mut self, budget: Duration) -> Self {
+        self.proposal_signature_budget = Some(budget);
+        self
+    }
+
+    /// Verify the signatures of the blocks this node voted for in parallel, before executing them.
+    pub fn with_block_signature_threads(mut self, threads: usize) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("signature-verifier-{i}"))
+            .build()
+            .context("failed to build the signature verifier thread pool")?;
+        self.block_signature_pool = Some(Arc::new(pool));
+        Ok(self)
+    }
+
+    /// Reject transactions sending bottom-up messages while this many are waiting for a checkpoint.
+    pub fn with_max_bottom_up_queue_depth(mut self, depth: u64) -> Self {
+        self.max_bottom_up_queue_depth = depth;
+        self
+    }
+
+    /// Take this action when the syncer finds that the committed parent finality conflicts with the parent.
+    pub fn with_divergence_action(mut self, action: DivergenceAction) -> Self {
+        self.divergence_action = action;
+        self.divergence.set_action(action);
+        self
+    }
+
+    /// Index the transactions and the events of every executed block, besides the logs blooms.
+    pub fn with_event_index(mut self, index: Arc<dyn EventIndex>) -> Self {
+        self.event_indexes.push(index);
+        self
+    }
+
+    /// The last committed block and the requests in progress, shared with the watchdog.
+    pub fn progress(&self) -> BlockProgress {
+        self.progress.clone()
+    }
+
+    /// The latest proposals this node voted against, shared with the watchdog.
+    pub fn rejected_proposals(&self) -> RecentRejections {
+        self.rejected_proposals.clone()
     }
 }
 
@@ -208,7 +597,9 @@ where
         + Codec<AppState>
         + Encode<AppStoreKey>
         + Encode<BlockHeight>
-        + Codec<FvmStateParams>,
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
     DB: KVWritable<S> + KVReadable<S> + 'static + Clone,
     SS: Blockstore + 'static + Clone,
 {
@@ -242,6 +633,198 @@ where
         Ok(())
     }
 
+    /// Roll back a commit which was interrupted by a crash, and check that the state
+    /// the application points at is actually in the state store.
+    ///
+    /// The blockstore and the application state live in the same RocksDB instance,
+    /// so the write-ahead log replays them in the order they were written. If the
+    /// process died before the new [`AppState`] was recorded, the blocks written so far
+    /// are unreachable and the application is still at the previous state. If it died
+    /// after, the indexes and other records of the block might be incomplete, so the
+    /// previous state is restored. Either way CometBFT notices during the handshake
+    /// that the application is behind, and replays the block.
+    fn recover_commit(&self) -> Result<()> {
+        let journal: Option<CommitJournal> = {
+            let tx = self.db.read();
+            tx.get(&self.namespace, &AppStoreKey::Journal)
+                .context("get failed")?
+        };
+
+        let mut state = self.get_committed_state()?;
+
+        if let Some(journal) = journal {
+            let prev_state_root = journal.prev_state.state_root();
+
+            if !self.state_store.has(&prev_state_root)? {
+                return Err(anyhow!(
+                    "state root {prev_state_root} to roll back the commit of block {} to is missing from the state store",
+                    journal.block_height
+                ));
+            }
+
+            match state {
+                Some(ref s) if s.block_height < journal.block_height => {
+                    if s.state_root() != prev_state_root {
+                        return Err(anyhow!(
+                            "the commit of block {} started from state root {prev_state_root}, but the application is at {}",
+                            journal.block_height,
+                            s.state_root()
+                        ));
+                    }
+                    tracing::warn!(
+                        block_height = journal.block_height,
+                        prev_state_root = prev_state_root.to_string(),
+                        "commit was interrupted before recording the state; staying at the previous state"
+                    );
+                }
+                Some(ref s) if s.block_height == journal.block_height => {
+                    tracing::warn!(
+                        block_height = journal.block_height,
+                        state_root = s.state_root().to_string(),
+                        prev_state_root = prev_state_root.to_string(),
+                        "commit was interrupted after recording the state; rolling back to the previous state"
+                    );
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "the commit journal of block {} doesn't match the application state",
+                        journal.block_height
+                    ));
+                }
+            }
+
+            let rolled_back = AppState {
+                // The history pruned by the interrupted commit is gone either way.
+                oldest_state_height: state
+                    .as_ref()
+                    .map(|s| s.oldest_state_height)
+                    .unwrap_or_default()
+                    .max(journal.prev_state.oldest_state_height),
+                ..journal.prev_state
+            };
+
+            self.db
+                .with_write(|tx| {
+                    // Nothing can be queried at the height of the block being rolled back.
+                    self.state_hist.delete(tx, &(journal.block_height + 1))?;
+                    self.rollback_validator_set(tx, journal.block_height)?;
+                    tx.put(&self.namespace, &AppStoreKey::State, &rolled_back)?;
+                    tx.delete(&self.namespace, &AppStoreKey::Journal)?;
+                    Ok(())
+                })
+                .context("failed to roll back the interrupted commit")?;
+
+            state = Some(rolled_back);
+        }
+
+        if let Some(state) = state {
+            let state_root = state.state_root();
+            if !self.state_store.has(&state_root)? {
+                return Err(anyhow!(
+                    "state root {state_root} of block {} is missing from the state store",
+                    state.block_height
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the state of a block, flushed to the state store by `flush`, along with
+    /// the power updates of the block and the bottom-up messages it left in the gateway.
+    ///
+    /// A journal to roll back with is written before anything else, and is only removed by
+    /// [`App::end_commit`], once everything else about the block has been written as well.
+    fn write_state<F>(
+        &self,
+        block_height: BlockHeight,
+        timestamp: Timestamp,
+        power_updates: Vec<(Vec<u8>, u64)>,
+        flush: F,
+    ) -> Result<AppState>
+    where
+        F: FnOnce() -> Result<(Cid, FvmUpdatableParams)>,
+    {
+        let prev_state = self.committed_state()?;
+
+        let journal = CommitJournal {
+            block_height,
+            prev_state: prev_state.clone(),
+        };
+        self.db
+            .with_write(|tx| {
+                tx.put(&self.namespace, &AppStoreKey::Journal, &journal)?;
+                Ok(())
+            })
+            .context("failed to write commit journal")?;
+
+        let (
+            state_root,
+            FvmUpdatableParams {
+                base_fee,
+                power_scale,
+                circ_supply,
+            },
+        ) = flush().context("failed to commit FVM")?;
+
+        let mut state = prev_state;
+        state.block_height = block_height;
+        state.state_params.timestamp = timestamp;
+        state.state_params.state_root = state_root;
+        state.state_params.power_scale = power_scale;
+        state.state_params.circ_supply = circ_supply;
+        state.state_params.base_fee = base_fee;
+
+        // The queue is only a local record, so a failure to read the gateway shouldn't stop the chain.
+        let bottom_up = self
+            .read_bottom_up_gateway(block_height, state.state_params.clone())
+            .unwrap_or_else(|e| {
+                tracing::error!(
+                    block_height,
+                    error = format!("{e:#}"),
+                    "failed to read the bottom-up messages of the gateway"
+                );
+                None
+            });
+        let now = unix_millis(SystemTime::now());
+
+        let bounds = self
+            .db
+            .with_write(|tx| {
+                self.put_committed_state(tx, state.clone())?;
+                self.logs_blooms.put(tx, block_height)?;
+                self.put_validator_set(tx, block_height, power_updates, false)?;
+                match bottom_up {
+                    Some(view) => Ok(Some(self.put_bottom_up_queue(
+                        tx,
+                        block_height,
+                        view,
+                        now,
+                    )?)),
+                    None => Ok(None),
+                }
+            })
+            .context("commit failed")?;
+
+        if let Some(bounds) = bounds {
+            BOTTOM_UP_QUEUE_DEPTH
+                .with_label_values(&[&self.subnet_id])
+                .set(bounds.depth() as i64);
+        }
+
+        Ok(state)
+    }
+
+    /// Mark the commit as complete, so it's not rolled back after a crash.
+    fn end_commit(&self) -> Result<()> {
+        self.db
+            .with_write(|tx| {
+                tx.delete(&self.namespace, &AppStoreKey::Journal)?;
+                Ok(())
+            })
+            .context("failed to clear commit journal")
+    }
+
     /// Get the last committed state, if exists.
     fn get_committed_state(&self) -> Result<Option<AppState>> {
         let tx = self.db.read();
@@ -257,137 +840,1191 @@ where
         }
     }
 
-    /// Set the last committed state.
-    fn set_committed_state(&self, mut state: AppState) -> Result<()> {
+    /// Add up the gas statistics of the recent blocks up to a committed height,
+    /// looking back at most as far as they are kept.
+    fn gas_stats(
+        &self,
+        query: &GasStatsQuery,
+        block_height: BlockHeight,
+    ) -> Result<GasStatsReport> {
+        let blocks = query.blocks.clamp(1, self.gas_stats_blocks.max(1));
+        let from = block_height.saturating_sub(blocks - 1);
+        let tx = self.db.read();
+        let mut stats = Vec::new();
+        for height in from..=block_height {
+            if let Some(bz) = self.gas_stats.get(&tx, &height)? {
+                let block_stats = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode block gas statistics")?;
+                stats.push(block_stats);
+            }
+        }
+        Ok(GasStatsReport::aggregate(
+            from,
+            block_height,
+            stats,
+            query.top,
+        ))
+    }
+
+    /// Store the gas statistics of a committed block, forgetting the ones which fell out of the window.
+    fn set_gas_stats(&self, block_height: BlockHeight, stats: BlockGasStats) -> Result<()> {
+        if self.gas_stats_blocks == 0 {
+            return Ok(());
+        }
+        let bz = fvm_ipld_encoding::to_vec(&stats).context("failed to encode gas statistics")?;
         self.db
             .with_write(|tx| {
-                // Insert latest state history point at the `block_height + 1`,
-                // to be consistent with how CometBFT queries are supposed to work.
-                let state_height = state.state_height();
-
-                self.state_hist
-                    .put(tx, &state_height, &state.state_params)?;
-
-                // Prune state history.
-                if self.state_hist_size > 0 && state_height >= self.state_hist_size {
-                    let prune_height = state_height.saturating_sub(self.state_hist_size);
-                    while state.oldest_state_height <= prune_height {
-                        self.state_hist.delete(tx, &state.oldest_state_height)?;
-                        state.oldest_state_height += 1;
-                    }
+                if !stats.is_empty() {
+                    self.gas_stats.put(tx, &block_height, &RawBytes::new(bz))?;
+                }
+                if block_height >= self.gas_stats_blocks {
+                    let prune_height = block_height - self.gas_stats_blocks;
+                    self.gas_stats.delete(tx, &prune_height)?;
                 }
-
-                // Update the application state.
-                tx.put(&self.namespace, &AppStoreKey::State, &state)?;
-
                 Ok(())
             })
-            .context("commit failed")
+            .context("failed to store gas statistics")
     }
 
-    /// Put the execution state during block execution. Has to be empty.
-    async fn put_exec_state(&self, state: FvmExecState<SS>) {
-        let mut guard = self.exec_state.lock().await;
-        assert!(guard.is_none(), "exec state not empty");
-        *guard = Some(state);
+    /// The components of the app hash of the state committed at a height, 0 meaning the latest;
+    /// `None` if the state has been pruned, or the height hasn't been reached yet.
+    fn app_hash_report(&self, height: BlockHeight) -> Result<Option<AppHashReport>> {
+        let state = self.committed_state()?;
+        if height == 0 || height == state.block_height {
+            return Ok(Some(to_app_hash_report(
+                state.block_height,
+                &state.state_params,
+            )));
+        }
+        let tx = self.db.read();
+        let params = self
+            .state_hist
+            .get(&tx, &height)
+            .context("error looking up history")?;
+
+        Ok(params.map(|p| to_app_hash_report(height, &p)))
     }
 
-    /// Take the execution state during block execution. Has to be non-empty.
-    async fn take_exec_state(&self) -> FvmExecState<SS> {
-        let mut guard = self.exec_state.lock().await;
-        guard.take().expect("exec state empty")
+    /// The power table in effect at a committed height, from the last change at or before it;
+    /// `None` if the history doesn't go back that far.
+    fn validator_set(&self, block_height: BlockHeight) -> Result<Option<ValidatorSet>> {
+        let tx = self.db.read();
+        let mut set = match tx.get(&self.namespace, &AppStoreKey::ValidatorSet)? {
+            Some(bz) => decode_validator_set(bz)?,
+            None => return Ok(None),
+        };
+        while set.height > block_height {
+            let prev_height = match set.prev_height {
+                Some(h) => h,
+                None => return Ok(None),
+            };
+            let bz = self
+                .validator_sets
+                .get(&tx, &prev_height)?
+                .ok_or_else(|| anyhow!("validator set missing at height {prev_height}"))?;
+            set = decode_validator_set(bz)?;
+        }
+        Ok(Some(set))
     }
 
-    /// Take the execution state, update it, put it back, return the output.
-    async fn modify_exec_state<T, F, R>(&self, f: F) -> Result<T>
-    where
-        F: FnOnce((CheckpointPool, TopDownFinalityProvider, FvmExecState<SS>)) -> R,
-        R: Future<
-            Output = Result<(
-                (CheckpointPool, TopDownFinalityProvider, FvmExecState<SS>),
-                T,
-            )>,
-        >,
-    {
-        let mut guard = self.exec_state.lock().await;
-        let state = guard.take().expect("exec state empty");
+    /// The parent finality committed in the state at a height, which also tells how far
+    /// the top-down messages have been executed; `None` if the subnet doesn't have IPC.
+    fn parent_finality(&self, height: FvmQueryHeight) -> Result<Option<ParentFinality>> {
+        let (state_params, block_height) = self.state_params_at_height(height)?;
 
-        let ((_pool, _provider, state), ret) = f((
-            self.resolve_pool.clone(),
-            self.parent_finality_provider.clone(),
-            state,
-        ))
-        .await?;
+        if !Self::can_query_state(block_height, &state_params) {
+            return Ok(None);
+        }
 
-        *guard = Some(state);
+        let mut state = FvmExecState::new(
+            ReadOnlyBlockstore::new(self.state_store.clone()),
+            self.multi_engine.as_ref(),
+            block_height as ChainEpoch,
+            state_params,
+        )
+        .context("error creating execution state")?;
 
-        Ok(ret)
+        let gateway = GatewayCaller::default();
+        if !gateway.enabled(&mut state)? {
+            return Ok(None);
+        }
+
+        let finality = gateway
+            .get_latest_parent_finality(&mut state)
+            .context("failed to get the parent finality")?;
+
+        Ok(Some(ParentFinality {
+            height: finality.height as ChainEpoch,
+            block_hash: finality.block_hash,
+        }))
     }
 
-    /// Get a read only fvm execution state. This is useful to perform query commands targeting
-    /// the latest state.
-    pub fn new_read_only_exec_state(
-        &self,
-    ) -> Result<Option<FvmExecState<ReadOnlyBlockstore<Arc<SS>>>>> {
-        let maybe_app_state = self.get_committed_state()?;
+    /// The top-down messages sent by the parent at the heights in an inclusive range.
+    ///
+    /// Only heights which have been finalized in the latest committed state are included,
+    /// so that the messages returned have either been executed, or are about to be.
+    async fn topdown_msgs(&self, from: u64, to: u64) -> Result<Vec<TopDownMessage>> {
+        if !self.parent_finality_provider.is_enabled() {
+            return Ok(Vec::new());
+        }
 
-        Ok(if let Some(app_state) = maybe_app_state {
-            let block_height = app_state.block_height;
-            let state_params = app_state.state_params;
+        let finalized = match self.parent_finality(FvmQueryHeight::Committed)? {
+            Some(finality) => finality.height as u64,
+            None => return Ok(Vec::new()),
+        };
 
-            // wait for block production
-            if !Self::can_query_state(block_height, &state_params) {
-                return Ok(None);
-            }
+        let to = to
+            .min(finalized)
+            .min(from.saturating_add(MAX_TOPDOWN_MSGS_RANGE - 1));
 
-            let exec_state = FvmExecState::new(
-                ReadOnlyBlockstore::new(self.state_store.clone()),
-                self.multi_engine.as_ref(),
-                block_height as ChainEpoch,
-                state_params,
-            )
-            .context("error creating execution state")?;
+        if from > to {
+            return Ok(Vec::new());
+        }
 
-            Some(exec_state)
-        } else {
-            None
-        })
+        let msgs = self
+            .parent_finality_provider
+            .top_down_msgs_from(from, to)
+            .await
+            .context("failed to get the top-down messages")?;
+
+        Ok(msgs.iter().map(TopDownMessage::from).collect())
     }
 
-    /// Look up a past state at a particular height Tendermint Core is looking for.
-    ///
-    /// A height of zero means we are looking for the latest state.
-    /// The genesis block state is saved under height 1.
-    /// Under height 0 we saved the empty state, which we must not query,
-    /// because it doesn't contain any initialized state for the actors.
+    /// Record the power table after the updates of a block, if there were any,
+    /// in the same transaction as the state of the block.
     ///
-    /// Returns the state params and the height of the block which committed it.
-    fn state_params_at_height(
+    /// The history can only be maintained from the genesis, because the updates are relative
+    /// to the previous table; nodes which started later don't record anything.
+    fn put_validator_set(
         &self,
-        height: FvmQueryHeight,
-    ) -> Result<(FvmStateParams, BlockHeight)> {
-        if let FvmQueryHeight::Height(h) = height {
-            let tx = self.db.read();
-            let sh = self
-                .state_hist
-                .get(&tx, &h)
-                .context("error looking up history")?;
+        tx: &mut <DB as KVWritable<S>>::Tx<'_>,
+        block_height: BlockHeight,
+        updates: Vec<(Vec<u8>, u64)>,
+        is_genesis: bool,
+    ) -> KVResult<()> {
+        if updates.is_empty() && !is_genesis {
+            return Ok(());
+        }
+        let latest = tx.get(&self.namespace, &AppStoreKey::ValidatorSet)?;
+        let set = match latest {
+            _ if is_genesis => ValidatorSet::genesis(block_height, updates),
+            Some(bz) => decode_validator_set(bz)?.apply(block_height, updates),
+            None => return Ok(()),
+        };
+        let bz = RawBytes::new(
+            fvm_ipld_encoding::to_vec(&set).map_err(|e| KVError::Codec(Box::new(e)))?,
+        );
+        self.validator_sets.put(tx, &block_height, &bz)?;
+        tx.put(&self.namespace, &AppStoreKey::ValidatorSet, &bz)?;
+        Ok(())
+    }
 
-            if let Some(p) = sh {
-                return Ok((p, h));
+    /// Forget the power table recorded by a block which is being rolled back.
+    fn rollback_validator_set(
+        &self,
+        tx: &mut <DB as KVWritable<S>>::Tx<'_>,
+        block_height: BlockHeight,
+    ) -> KVResult<()> {
+        let set = match tx.get(&self.namespace, &AppStoreKey::ValidatorSet)? {
+            Some(bz) => decode_validator_set(bz)?,
+            None => return Ok(()),
+        };
+        if set.height != block_height {
+            return Ok(());
+        }
+        self.validator_sets.delete(tx, &block_height)?;
+        match set.prev_height {
+            Some(prev_height) => {
+                let prev = self.validator_sets.get(tx, &prev_height)?.ok_or_else(|| {
+                    KVError::Unexpected(
+                        anyhow!("validator set missing at height {prev_height}").into(),
+                    )
+                })?;
+                tx.put(&self.namespace, &AppStoreKey::ValidatorSet, &prev)
             }
+            None => tx.delete(&self.namespace, &AppStoreKey::ValidatorSet),
         }
-        let state = self.committed_state()?;
-        Ok((state.state_params, state.block_height))
     }
 
-    /// Check whether the state has been initialized by genesis.
-    ///
+    /// Let the tally of the top-down votes know about the power of the validators, when it changes,
+    /// or if it doesn't know it yet because the node has just started.
+    async fn update_vote_tally(
+        &self,
+        block_height: BlockHeight,
+        has_power_updates: bool,
+    ) -> Result<()> {
+        let votes = match self.parent_finality_provider.vote_tally() {
+            Some(votes) => votes,
+            None => return Ok(()),
+        };
+        if !has_power_updates && atomically(|| votes.has_power_table()).await {
+            return Ok(());
+        }
+        if let Some(set) = self.validator_set(block_height)? {
+            let power_table = set
+                .validators
+                .into_iter()
+                .map(|v| (v.public_key, v.power))
+                .collect::<Vec<_>>();
+            atomically(|| votes.set_power_table(power_table.clone())).await;
+        }
+        Ok(())
+    }
+
+    /// Read what the gateway has to say about the bottom-up messages from the state a block has
+    /// just been flushed to, so the queue can be updated along with the block.
+    ///
+    /// The gateway is only asked for the messages if its nonce moved since the last update, and
+    /// for the checkpoints waiting for signatures if there are messages in created checkpoints.
+    fn read_bottom_up_gateway(
+        &self,
+        block_height: BlockHeight,
+        state_params: FvmStateParams,
+    ) -> Result<Option<GatewayView>> {
+        if !Self::can_query_state(block_height, &state_params) {
+            return Ok(None);
+        }
+        let mut state = FvmExecState::new(
+            ReadOnlyBlockstore::new(self.state_store.clone()),
+            self.multi_engine.as_ref(),
+            block_height as ChainEpoch,
+            state_params,
+        )
+        .context("error creating execution state")?;
+
+        let gateway = GatewayCaller::default();
+
+        let period = match self.bottom_up_period.get() {
+            Some(period) => *period,
+            None => {
+                let period = if gateway.enabled(&mut state)? && !gateway.is_root(&mut state)? {
+                    Some(gateway.bottom_up_check_period(&mut state)?)
+                } else {
+                    None
+                };
+                *self.bottom_up_period.get_or_init(|| period)
+            }
+        };
+        let period = match period {
+            Some(period) => period,
+            None => return Ok(None),
+        };
+
+        let (bounds, oldest) = {
+            let tx = self.db.read();
+            let bounds = self.get_bottom_up_bounds(&tx)?;
+            let oldest = if bounds.is_empty() {
+                None
+            } else {
+                self.get_queued_msg(&tx, bounds.first)?
+            };
+            (bounds, oldest)
+        };
+
+        let gateway_nonce = gateway
+            .bottom_up_nonce(&mut state)
+            .context("failed to retrieve bottom-up nonce")?;
+
+        let checkpoint_height = bottomup::checkpoint_height(block_height, period);
+
+        let msgs = if gateway_nonce != bounds.gateway_nonce {
+            gateway
+                .bottom_up_msgs(&mut state, checkpoint_height)
+                .context("failed to retrieve bottom-up messages")?
+                .into_iter()
+                .map(|msg| (msg.message.nonce, msg.encode()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // The oldest message is in the oldest checkpoint, so if that hasn't been created yet, none has.
+        let incomplete = match oldest {
+            Some(queued) if queued.checkpoint_height <= block_height => Some(
+                gateway
+                    .incomplete_checkpoints(&mut state)
+                    .context("failed to retrieve incomplete checkpoints")?
+                    .into_iter()
+                    .map(|cp| cp.block_height)
+                    .collect::<HashSet<_>>(),
+            ),
+            _ => None,
+        };
+
+        Ok(Some(GatewayView {
+            gateway_nonce,
+            checkpoint_height,
+            msgs,
+            incomplete,
+        }))
+    }
+
+    /// Mirror the bottom-up messages of the gateway after a block: add the ones sent in the block,
+    /// and drop the ones whose checkpoint has been signed by a quorum.
+    fn put_bottom_up_queue(
+        &self,
+        tx: &mut impl KVWrite<S>,
+        block_height: BlockHeight,
+        view: GatewayView,
+        now: u64,
+    ) -> KVResult<QueueBounds> {
+        let mut bounds = self.get_bottom_up_bounds(tx)?;
+
+        let mut msgs = view.msgs;
+        msgs.sort_by_key(|(nonce, _)| *nonce);
+
+        for (nonce, message) in msgs {
+            if bounds.push(nonce) {
+                let queued = QueuedMessage {
+                    checkpoint_height: view.checkpoint_height,
+                    message,
+                };
+                self.bottom_up_queue
+                    .put(tx, &nonce, &bottomup::encode_entry(&queued)?)?;
+                self.trace_msg(
+                    tx,
+                    Direction::BottomUp,
+                    nonce,
+                    view.checkpoint_height,
+                    Stage::Enqueued,
+                    now,
+                )?;
+            }
+        }
+        bounds.gateway_nonce = view.gateway_nonce;
+
+        // The messages are included in checkpoints in the order of their nonces.
+        bounds.checkpointed = bounds.checkpointed.max(bounds.first);
+        while bounds.checkpointed < bounds.next {
+            let nonce = bounds.checkpointed;
+            if let Some(queued) = self.get_queued_msg(tx, nonce)? {
+                if queued.checkpoint_height > block_height {
+                    break;
+                }
+                self.trace_msg(
+                    tx,
+                    Direction::BottomUp,
+                    nonce,
+                    queued.checkpoint_height,
+                    Stage::Checkpointed,
+                    now,
+                )?;
+            }
+            bounds.checkpointed += 1;
+        }
+
+        // Checkpoints are signed in the order they are created, more or less.
+        while bounds.first < bounds.checkpointed {
+            let nonce = bounds.first;
+            if let Some(queued) = self.get_queued_msg(tx, nonce)? {
+                match view.incomplete {
+                    Some(ref incomplete) if !incomplete.contains(&queued.checkpoint_height) => {}
+                    _ => break,
+                }
+                self.trace_msg(
+                    tx,
+                    Direction::BottomUp,
+                    nonce,
+                    queued.checkpoint_height,
+                    Stage::Quorum,
+                    now,
+                )?;
+            }
+            self.bottom_up_queue.delete(tx, &nonce)?;
+            bounds.first += 1;
+        }
+
+        tx.put(
+            &self.namespace,
+            &AppStoreKey::BottomUpQueue,
+            &bottomup::encode_entry(&bounds)?,
+        )?;
+
+        Ok(bounds)
+    }
+
+    fn get_bottom_up_bounds(&self, tx: &impl KVRead<S>) -> KVResult<QueueBounds> {
+        match tx.get(&self.namespace, &AppStoreKey::BottomUpQueue)? {
+            Some(bz) => bottomup::decode_entry(bz),
+            None => Ok(QueueBounds::default()),
+        }
+    }
+
+    fn get_queued_msg(&self, tx: &impl KVRead<S>, nonce: u64) -> KVResult<Option<QueuedMessage>> {
+        match self.bottom_up_queue.get(tx, &nonce)? {
+            Some(bz) => Ok(Some(bottomup::decode_entry(bz)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn msg_trace_collection(&self, direction: Direction) -> &KVCollection<S, u64, RawBytes> {
+        match direction {
+            Direction::TopDown => &self.topdown_traces,
+            Direction::BottomUp => &self.bottomup_traces,
+        }
+    }
+
+    fn get_msg_trace(
+        &self,
+        tx: &impl KVRead<S>,
+        direction: Direction,
+        nonce: u64,
+    ) -> KVResult<Option<MessageTrace>> {
+        match self.msg_trace_collection(direction).get(tx, &nonce)? {
+            Some(bz) => Ok(Some(bottomup::decode_entry(bz)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the trace of a message, forgetting the one which fell out of the window.
+    fn put_msg_trace(&self, tx: &mut impl KVWrite<S>, trace: &MessageTrace) -> KVResult<()> {
+        let traces = self.msg_trace_collection(trace.direction);
+        traces.put(tx, &trace.nonce, &bottomup::encode_entry(trace)?)?;
+        if trace.nonce >= self.msg_traces {
+            traces.delete(tx, &(trace.nonce - self.msg_traces))?;
+        }
+        Ok(())
+    }
+
+    /// Record a stage reached by a cross-subnet message, if the traces are enabled.
+    fn trace_msg(
+        &self,
+        tx: &mut impl KVWrite<S>,
+        direction: Direction,
+        nonce: u64,
+        height: BlockHeight,
+        stage: Stage,
+        timestamp: u64,
+    ) -> KVResult<()> {
+        if self.msg_traces == 0 {
+            return Ok(());
+        }
+        let mut trace = self
+            .get_msg_trace(tx, direction, nonce)?
+            .unwrap_or_else(|| MessageTrace::new(direction, nonce, height));
+        if trace.reach(&self.subnet_id, stage, timestamp) {
+            self.put_msg_trace(tx, &trace)?;
+        }
+        Ok(())
+    }
+
+    /// The trace of a cross-subnet message, if it's still kept.
+    fn msg_trace(&self, query: &MsgTraceQuery) -> Result<Option<MessageTrace>> {
+        let tx = self.db.read();
+        let trace = self.get_msg_trace(&tx, query.direction, query.nonce)?;
+        Ok(trace)
+    }
+
+    /// Remember when the parent finality in a proposal was first seen.
+    fn record_topdown_proposal(&self, txs: &[impl AsRef<[u8]>]) {
+        if self.msg_traces == 0 || !self.parent_finality_provider.is_enabled() {
+            return;
+        }
+        if let Some(height) = latency::proposed_finality(txs) {
+            self.topdown_proposals
+                .lock()
+                .unwrap()
+                .record(height, unix_millis(SystemTime::now()));
+        }
+    }
+
+    /// Trace the top-down messages executed by the parent finality committed in a block.
+    async fn update_topdown_traces(&self) -> Result<()> {
+        let observations = match self.parent_finality_provider.observations() {
+            Some(observations) => observations,
+            None => return Ok(()),
+        };
+        let finality =
+            match atomically(|| self.parent_finality_provider.last_committed_finality()).await {
+                Some(finality) => finality,
+                None => return Ok(()),
+            };
+
+        // The messages deferred to later blocks or moved to the dead-letter queue weren't executed.
+        let mut state = self.new_check_state()?;
+        let progress =
+            topdown_progress(&mut state).context("failed to get the top-down progress")?;
+
+        // Taken even if the traces are disabled, so that they don't pile up.
+        let executed = observations.take_finalized(finality.height, progress.next_nonce);
+
+        let mut proposals = self.topdown_proposals.lock().unwrap();
+
+        if self.msg_traces > 0 && !executed.is_empty() {
+            let now = unix_millis(SystemTime::now());
+            self.db
+                .with_write(|tx| {
+                    for (nonce, observation) in executed.iter() {
+                        let mut trace = MessageTrace::new(
+                            Direction::TopDown,
+                            *nonce,
+                            observation.parent_height,
+                        );
+                        trace.reach(
+                            &self.subnet_id,
+                            Stage::Observed,
+                            unix_millis(observation.time),
+                        );
+                        if let Some(proposed) = proposals.proposed_at(observation.parent_height) {
+                            trace.reach(&self.subnet_id, Stage::Proposed, proposed);
+                        }
+                        if progress.is_executed(*nonce) {
+                            trace.reach(&self.subnet_id, Stage::Executed, now);
+                        }
+                        self.put_msg_trace(tx, &trace)?;
+                    }
+                    Ok(())
+                })
+                .context("failed to store top-down message traces")?;
+        }
+
+        proposals.finalized(finality.height);
+
+        Ok(())
+    }
+
+    /// Check whether the bottom-up queue is at its limit.
+    fn is_bottom_up_queue_full(&self) -> Result<bool> {
+        if self.max_bottom_up_queue_depth == 0 {
+            return Ok(false);
+        }
+        let tx = self.db.read();
+        let depth = self.get_bottom_up_bounds(&tx)?.depth();
+        Ok(depth >= self.max_bottom_up_queue_depth)
+    }
+
+    /// The bottom-up nonce of the gateway in the check state, if the subnet has a parent.
+    fn check_bottom_up_nonce(
+        &self,
+        state: &mut FvmExecState<ReadOnlyBlockstore<SS>>,
+    ) -> Result<Option<u64>> {
+        match self.bottom_up_period.get() {
+            Some(Some(_)) => Ok(Some(GatewayCaller::default().bottom_up_nonce(state)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn bottom_up_queue_full(&self) -> response::CheckTx {
+        BOTTOM_UP_QUEUE_REJECTED
+            .with_label_values(&[&self.subnet_id])
+            .inc();
+        invalid_check_tx(
+            AppError::BottomUpQueueFull,
+            format!(
+                "there are already {} bottom-up messages waiting for a checkpoint",
+                self.max_bottom_up_queue_depth
+            ),
+        )
+    }
+
+    /// Add an executed transaction to the gas statistics of the current block.
+    fn record_gas(&self, ret: &FvmApplyRet, is_ipc: bool) {
+        if self.gas_stats_blocks > 0 {
+            self.block_gas_stats.lock().unwrap().record(ret, is_ipc);
+        }
+    }
+
+    /// Pass the executed block to the additional event indexes.
+    ///
+    /// They only serve external readers, so a failure is logged but doesn't stop the chain.
+    fn update_event_indexes(&self, f: impl Fn(&dyn EventIndex) -> anyhow::Result<()>) {
+        for index in self.event_indexes.iter() {
+            if let Err(e) = f(index.as_ref()) {
+                tracing::error!(error = format!("{e:#}"), "failed to index events");
+            }
+        }
+    }
+
+    /// Bring back the conflict with the parent found before a restart, halting again if it
+    /// did before and the action is still to halt, and persist the conflict whenever the
+    /// syncer finds one or the operator clears it from now on.
+    pub fn persist_divergence(&self) -> Result<()>
+    where
+        DB: Send + Sync,
+        S: 'static,
+        S::Namespace: Send + Sync,
+    {
+        if let Some(record) = self.get_conflict_record()? {
+            if let (DivergenceAction::Halt, Some(halted_at)) =
+                (self.divergence_action, record.halted_at)
+            {
+                if !matches!(self.halt_height.get(), Some(h) if h <= halted_at) {
+                    self.halt_height.set(Some(halted_at));
+                }
+            }
+            self.divergence.restore(record.conflict);
+        }
+
+        let db = self.db.clone();
+        let namespace = self.namespace.clone();
+        self.divergence.on_change(move |conflict| {
+            let res = db.with_write(|tx| match conflict {
+                Some(conflict) => put_conflict_record::<S>(
+                    tx,
+                    &namespace,
+                    &ConflictRecord {
+                        conflict: conflict.clone(),
+                        halted_at: None,
+                    },
+                ),
+                None => tx.delete(&namespace, &AppStoreKey::FinalityConflict),
+            });
+            if let Err(e) = res {
+                tracing::error!(
+                    error = e.to_string(),
+                    "failed to store the parent finality conflict"
+                );
+            }
+        });
+        Ok(())
+    }
+
+    fn get_conflict_record(&self) -> Result<Option<ConflictRecord>> {
+        let tx = self.db.read();
+        match tx.get(&self.namespace, &AppStoreKey::FinalityConflict)? {
+            Some(bz) => Ok(Some(bottomup::decode_entry(bz)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The conflict with the parent which makes the application refuse new transactions, if any.
+    fn read_only_conflict(&self) -> Option<FinalityConflict> {
+        if self.divergence_action == DivergenceAction::ReadOnly {
+            self.divergence.conflict()
+        } else {
+            None
+        }
+    }
+
+    /// Take the safety action for a conflict with the parent found since the last block;
+    /// the read-only mode is enforced by checking the conflict when transactions come in.
+    fn take_divergence_action(&self, block_height: BlockHeight) {
+        if self.divergence_action != DivergenceAction::Halt {
+            return;
+        }
+        if let Some(conflict) = self.divergence.take_unhandled() {
+            tracing::error!(
+                block_height,
+                parent_height = conflict.height,
+                "the committed parent finality conflicts with the parent; halting"
+            );
+            self.halt_height.set(Some(block_height));
+
+            let record = ConflictRecord {
+                conflict,
+                halted_at: Some(block_height),
+            };
+            let res = self
+                .db
+                .with_write(|tx| put_conflict_record::<S>(tx, &self.namespace, &record));
+            if let Err(e) = res {
+                tracing::error!(
+                    error = e.to_string(),
+                    "failed to store the parent finality conflict"
+                );
+            }
+        }
+    }
+
+    /// Return the halt height if the last committed block reached it, in which case
+    /// the application must not process any further blocks.
+    fn halted_at(&self) -> Result<Option<BlockHeight>> {
+        match self.halt_height.get() {
+            Some(halt_height) => {
+                let state = self.committed_state()?;
+                Ok((state.block_height >= halt_height).then_some(halt_height))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Set the last committed state outside of a block commit, e.g. the empty initial state
+    /// or the state restored from a snapshot, which don't need a journal to roll back with.
+    ///
+    /// Blocks are committed by [`App::write_state`] and [`App::end_commit`] instead.
+    fn set_committed_state(&self, state: AppState) -> Result<()> {
+        self.db
+            .with_write(|tx| self.put_committed_state(tx, state))
+            .context("commit failed")
+    }
+
+    fn put_committed_state(
+        &self,
+        tx: &mut <DB as KVWritable<S>>::Tx<'_>,
+        mut state: AppState,
+    ) -> KVResult<()> {
+        // Insert latest state history point at the `block_height + 1`,
+        // to be consistent with how CometBFT queries are supposed to work.
+        let state_height = state.state_height();
+
+        self.state_hist
+            .put(tx, &state_height, &state.state_params)?;
+
+        // Prune state history.
+        if self.state_hist_size > 0 && state_height >= self.state_hist_size {
+            let prune_height = state_height.saturating_sub(self.state_hist_size);
+            while state.oldest_state_height <= prune_height {
+                self.state_hist.delete(tx, &state.oldest_state_height)?;
+                // The bloom of a block is kept as long as the state after it.
+                self.logs_blooms
+                    .delete(tx, state.oldest_state_height.saturating_sub(1))?;
+                state.oldest_state_height += 1;
+            }
+        }
+
+        // Update the application state.
+        tx.put(&self.namespace, &AppStoreKey::State, &state)?;
+
+        Ok(())
+    }
+
+    /// Put the execution state during block execution. Has to be empty.
+    async fn put_exec_state(&self, state: FvmExecState<SS>) {
+        let mut guard = self.exec_state.lock().await;
+        assert!(guard.is_none(), "exec state not empty");
+        *guard = Some(state);
+    }
+
+    /// Take the execution state during block execution. Has to be non-empty.
+    async fn take_exec_state(&self) -> FvmExecState<SS> {
+        let mut guard = self.exec_state.lock().await;
+        guard.take().expect("exec state empty")
+    }
+
+    /// Take the execution state, update it, put it back, return the output.
+    async fn modify_exec_state<T, F, R>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce((CheckpointPool, TopDownFinalityProvider, FvmExecState<SS>)) -> R,
+        R: Future<
+            Output = Result<(
+                (CheckpointPool, TopDownFinalityProvider, FvmExecState<SS>),
+                T,
+            )>,
+        >,
+    {
+        let mut guard = self.exec_state.lock().await;
+        let state = guard.take().expect("exec state empty");
+
+        let ((_pool, _provider, state), ret) = f((
+            self.resolve_pool.clone(),
+            self.parent_finality_provider.clone(),
+            state,
+        ))
+        .await?;
+
+        *guard = Some(state);
+
+        Ok(ret)
+    }
+
+    /// Get a read only fvm execution state. This is useful to perform query commands targeting
+    /// the latest state.
+    pub fn new_read_only_exec_state(
+        &self,
+    ) -> Result<Option<FvmExecState<ReadOnlyBlockstore<Arc<SS>>>>> {
+        let maybe_app_state = self.get_committed_state()?;
+
+        Ok(if let Some(app_state) = maybe_app_state {
+            let block_height = app_state.block_height;
+            let state_params = app_state.state_params;
+
+            // wait for block production
+            if !Self::can_query_state(block_height, &state_params) {
+                return Ok(None);
+            }
+
+            let exec_state = FvmExecState::new(
+                ReadOnlyBlockstore::new(self.state_store.clone()),
+                self.multi_engine.as_ref(),
+                block_height as ChainEpoch,
+                state_params,
+            )
+            .context("error creating execution state")?;
+
+            Some(exec_state)
+        } else {
+            None
+        })
+    }
+
+    /// Look up a past state at a particular height Tendermint Core is looking for.
+    ///
+    /// A height of zero means we are looking for the latest state.
+    /// The genesis block state is saved under height 1.
+    /// Under height 0 we saved the empty state, which we must not query,
+    /// because it doesn't contain any initialized state for the actors.
+    ///
+    /// Returns the state params and the height of the block which committed it.
+    fn state_params_at_height(
+        &self,
+        height: FvmQueryHeight,
+    ) -> Result<(FvmStateParams, BlockHeight)> {
+        if let FvmQueryHeight::Height(h) = height {
+            let tx = self.db.read();
+            let sh = self
+                .state_hist
+                .get(&tx, &h)
+                .context("error looking up history")?;
+
+            if let Some(p) = sh {
+                return Ok((p, h));
+            }
+        }
+        let state = self.committed_state()?;
+        Ok((state.state_params, state.block_height))
+    }
+
+    /// Create a state to check transactions on top of the last committed one.
+    fn new_check_state(&self) -> Result<FvmExecState<ReadOnlyBlockstore<SS>>> {
+        let db = self.state_store_clone();
+        let state = self.committed_state()?;
+
+        // This would create a partial state, but some client scenarios need the full one.
+        // FvmCheckState::new(db, state.state_root(), state.chain_id())
+        //     .context("error creating check state")?
+
+        FvmExecState::new(
+            ReadOnlyBlockstore::new(db),
+            self.multi_engine.as_ref(),
+            state.block_height.try_into()?,
+            state.state_params,
+        )
+        .context("error creating check state")
+    }
+
+    /// Check the time of a proposed block against the previous block and the limit in the
+    /// committed state, which is the same for every validator judging the proposal.
+    fn check_block_time(&self, height: u64, time: tendermint::Time) -> Result<bool> {
+        let state = self.committed_state()?;
+
+        // The genesis time isn't necessarily close to when the first block is produced.
+        if state.block_height == 0 {
+            return Ok(true);
+        }
+
+        let max_interval = match self.new_read_only_exec_state()? {
+            Some(mut exec_state) => exec_state.max_block_interval()?,
+            None => None,
+        };
+
+        Ok(self.time_monitor.check_block_time(
+            height,
+            state.state_params.timestamp,
+            time,
+            max_interval,
+        ))
+    }
+
+    /// The block gas limit in effect at a height, according to the committed state; 0 means no limit.
+    ///
+    /// The check state isn't used, because the transactions in the mempool of this node
+    /// must not sway how it judges a proposal.
+    fn block_gas_limit(&self, height: u64) -> Result<u64> {
+        match self.new_read_only_exec_state()? {
+            Some(mut state) => state.block_gas_limit_at(height.try_into()?),
+            None => Ok(0),
+        }
+    }
+
+    /// Parameters to verify the signatures in a proposal with, taken from the committed state,
+    /// or `None` if they are left to the delivery.
+    fn proposal_signature_check(&self) -> Result<Option<ProposalSignatureCheck>> {
+        let Some(budget) = self.proposal_signature_budget else {
+            return Ok(None);
+        };
+
+        let Some(mut state) = self.new_read_only_exec_state()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(ProposalSignatureCheck {
+            chain_id: state.chain_id(),
+            filecoin_signatures: state.accepts_filecoin_signatures()?,
+            budget,
+        }))
+    }
+
+    /// The nonce the next top-down message has to have, taken from the committed state, or `None`
+    /// if there is no parent or the chain doesn't record the nonces yet.
+    fn topdown_nonce(&self) -> Result<Option<u64>> {
+        if !self.parent_finality_provider.is_enabled() {
+            return Ok(None);
+        }
+
+        match self.new_read_only_exec_state()? {
+            Some(mut state) => expected_topdown_nonce(&mut state),
+            None => Ok(None),
+        }
+    }
+
+    /// Check the transactions taken from the mempool against the state, in the order they
+    /// would be executed, and drop the ones which would fail, because `CheckTx` didn't.
+    ///
+    /// The signatures have been verified when the transactions were added to the mempool,
+    /// so these are done the same way as rechecks.
+    async fn check_proposed_txs(&self, txs: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>>
+    where
+        I: CheckInterpreter<
+            State = FvmExecState<ReadOnlyBlockstore<SS>>,
+            Message = Vec<u8>,
+            Output = BytesMessageCheckRes,
+        >,
+    {
+        let mut guard = self.proposal_checks.lock().await;
+
+        let mut checks = match guard.take() {
+            Some(checks) => checks,
+            None => ProposalChecks {
+                state: self.new_check_state()?,
+                accepted: HashSet::new(),
+            },
+        };
+
+        let mut included = Vec::new();
+
+        for tx in txs {
+            let cid = tx_cid(&tx);
+
+            if checks.accepted.contains(&cid) {
+                included.push(tx);
+                continue;
+            }
+
+            let (state, result) = self
+                .interpreter
+                .check(checks.state, tx.clone(), true)
+                .await
+                .context("error running proposal check")?;
+
+            checks.state = state;
+
+            match result {
+                Ok(Ok(Ok(ret))) if ret.exit_code.is_success() => {
+                    checks.accepted.insert(cid);
+                    included.push(tx);
+                }
+                Ok(Ok(Ok(ret))) => {
+                    tracing::debug!(
+                        tx = cid.to_string(),
+                        exit_code = ret.exit_code.value(),
+                        info = ret.info.unwrap_or_default(),
+                        "dropping transaction from proposal"
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        *guard = Some(checks);
+
+        Ok(included)
+    }
+
+    /// Check whether the state has been initialized by genesis.
+    ///
     /// We can't run queries on the initial empty state becase the actors haven't been inserted yet.
     fn can_query_state(height: BlockHeight, params: &FvmStateParams) -> bool {
         // It's really the empty state tree that would be the best indicator.
         !(height == 0 && params.timestamp.0 == 0 && params.network_version == NetworkVersion::V0)
     }
+
+    /// Versions and optional features of this node.
+    fn capabilities(&self) -> Result<Capabilities> {
+        let mut features = vec![
+            feature::STATE_SYNC_STATUS.to_owned(),
+            feature::DEAD_LETTERS.to_owned(),
+            feature::LOGS_BLOOM.to_owned(),
+            feature::BLOCK_HASH_INDEX.to_owned(),
+            feature::CHECKPOINT_ARCHIVE.to_owned(),
+            feature::ACCESS_LIST.to_owned(),
+            feature::SIMULATE_STAKING.to_owned(),
+            feature::EXEC_DIGESTS.to_owned(),
+        ];
+        if self.snapshots.is_some() {
+            features.push(feature::SNAPSHOTS.to_owned());
+        }
+        if self.tracing_enabled {
+            features.push(feature::TRACING.to_owned());
+        }
+        if self.parent_finality_provider.is_enabled() {
+            features.push(feature::TOPDOWN.to_owned());
+        }
+        if self.eth_block_hash_version()? == EthBlockHashVersion::V1 {
+            features.push(feature::ETH_BLOCK_HASH_V1.to_owned());
+        }
+        Ok(Capabilities {
+            app_version: APP_VERSION,
+            version: VERSION.to_owned(),
+            features,
+            state_hist_size: self.state_hist_size,
+            message_versions: vec![CHAIN_MESSAGE_VERSION],
+        })
+    }
+
+    /// The derivation of the block hashes the Ethereum API exposes, according to the
+    /// committed state; the CometBFT hashes as they are until there is a state to look at.
+    fn eth_block_hash_version(&self) -> Result<EthBlockHashVersion> {
+        match self.new_read_only_exec_state()? {
+            Some(mut state) => state.eth_block_hash_version(),
+            None => Ok(EthBlockHashVersion::V0),
+        }
+    }
+
+    /// The committed state to resolve the senders of the proposed messages with, if the genesis
+    /// requires the canonical transaction order.
+    ///
+    /// The proposer and the validators look at the same committed state, so they agree on the
+    /// order even if the mempool of some of them has already seen new accounts.
+    fn tx_order_state(&self) -> Result<Option<FvmExecState<ReadOnlyBlockstore<Arc<SS>>>>> {
+        match self.new_read_only_exec_state()? {
+            Some(mut state) if state.canonical_tx_order()? => Ok(Some(state)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Fill in the derived Ethereum hashes of the blocks CometBFT has from before the first
+    /// one in the index, e.g. if the index was lost, so that they can be looked up by hash.
+    ///
+    /// Waits for CometBFT to start, and does nothing on chains which expose the CometBFT hashes.
+    pub async fn backfill_eth_block_hashes<C>(&self, client: C) -> Result<()>
+    where
+        C: tendermint_rpc::Client + Send + Sync,
+    {
+        let version = self.eth_block_hash_version()?;
+        if version == EthBlockHashVersion::V0 {
+            return Ok(());
+        }
+        let first_height = match self.block_hashes.eth_first_height()? {
+            Some(height) => height,
+            None => return Ok(()),
+        };
+        let status = loop {
+            match client.status().await {
+                Ok(status) => break status,
+                Err(e) => {
+                    tracing::debug!(
+                        error = e.to_string(),
+                        "waiting for CometBFT to backfill the block hashes"
+                    );
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        };
+        let earliest = status.sync_info.earliest_block_height.value().max(1);
+        for height in (earliest..first_height).rev() {
+            let header = client
+                .header(tendermint::block::Height::try_from(height)?)
+                .await
+                .with_context(|| format!("failed to get the header at height {height}"))?
+                .header;
+            let block_hash = match header.hash() {
+                tendermint::Hash::Sha256(hash) => hash,
+                tendermint::Hash::None => {
+                    return Err(anyhow!("empty block hash at height {height}"))
+                }
+            };
+            self.block_hashes
+                .backfill_eth(height, version.to_eth_block_hash(&block_hash))?;
+        }
+        if earliest < first_height {
+            tracing::info!(
+                from = earliest,
+                to = first_height - 1,
+                "backfilled the Ethereum block hashes"
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<DB, SS, S, I> App<DB, SS, S, I>
+where
+    S: KVStore,
+    SS: Blockstore + Clone + 'static,
+    I: VerifyBlockInterpreter<Message = Vec<u8>> + 'static,
+{
+    /// Verify the signatures of the block about to be executed in parallel, so `DeliverTx`
+    /// doesn't have to verify them one by one. The ones already verified in `CheckTx` or in
+    /// `ProcessProposal` are skipped.
+    ///
+    /// Only the transactions of the proposals processed by this node are known before they are
+    /// delivered, so this does nothing on full nodes, or if the decided block is a different one.
+    async fn verify_block_signatures(
+        &self,
+        state: &mut FvmExecState<SS>,
+        block_hash: BlockHash,
+    ) -> Result<()> {
+        let Some(ref pool) = self.block_signature_pool else {
+            return Ok(());
+        };
+        let txs = match self.processed_proposal.lock().unwrap().take() {
+            Some((hash, txs)) if hash == block_hash => txs,
+            _ => return Ok(()),
+        };
+
+        let check = BlockSignatureCheck {
+            chain_id: state.chain_id(),
+            filecoin_signatures: state.accepts_filecoin_signatures()?,
+        };
+
+        let pool = pool.clone();
+        let interpreter = self.interpreter.clone();
+        let start = std::time::Instant::now();
+
+        let count = tokio::task::spawn_blocking(move || {
+            pool.install(|| interpreter.verify_block(&check, &txs))
+        })
+        .await?;
+
+        tracing::debug!(
+            count,
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "verified block signatures"
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<DB, SS, S, I> MempoolCheck for App<DB, SS, S, I>
+where
+    S: KVStore
+        + Codec<AppState>
+        + Encode<AppStoreKey>
+        + Encode<BlockHeight>
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
+    S::Namespace: Sync + Send,
+    DB: KVWritable<S> + KVReadable<S> + Clone + Send + Sync + 'static,
+    SS: Blockstore + Clone + Send + Sync + 'static,
+    I: CheckInterpreter<
+        State = FvmExecState<ReadOnlyBlockstore<SS>>,
+        Message = Vec<u8>,
+        Output = BytesMessageCheckRes,
+    >,
+{
+    /// Check the transactions on a state of their own, leaving the one of `CheckTx` alone.
+    async fn check_txs(&self, txs: &[Vec<u8>]) -> Result<Vec<(u32, String)>> {
+        let mut state = self.new_check_state()?;
+        let mut results = Vec::with_capacity(txs.len());
+
+        for tx in txs {
+            let (next, result) = self
+                .interpreter
+                .check(state, tx.clone(), false)
+                .await
+                .context("error running check")?;
+
+            state = next;
+
+            let response = to_check_response(result);
+            results.push((response.code.value(), response.info));
+        }
+
+        Ok(results)
+    }
 }
 
 // NOTE: The `Application` interface doesn't allow failures at the moment. The protobuf
@@ -402,7 +2039,9 @@ where
         + Codec<AppState>
         + Encode<AppStoreKey>
         + Encode<BlockHeight>
-        + Codec<FvmStateParams>,
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
     S::Namespace: Sync + Send,
     DB: KVWritable<S> + KVReadable<S> + Clone + Send + Sync + 'static,
     SS: Blockstore + Clone + Send + Sync + 'static,
@@ -411,11 +2050,19 @@ where
         Genesis = Vec<u8>,
         Output = FvmGenesisOutput,
     >,
-    I: ProposalInterpreter<State = (CheckpointPool, TopDownFinalityProvider), Message = Vec<u8>>,
+    I: ProposalInterpreter<
+        State = (
+            CheckpointPool,
+            TopDownFinalityProvider,
+            Option<ProposalSignatureCheck>,
+            Option<u64>,
+        ),
+        Message = Vec<u8>,
+    >,
     I: ExecInterpreter<
         State = (CheckpointPool, TopDownFinalityProvider, FvmExecState<SS>),
         Message = Vec<u8>,
-        BeginOutput = FvmApplyRet,
+        BeginOutput = FvmBeginRet,
         DeliverOutput = BytesMessageApplyRes,
         EndOutput = Vec<Validator<Power>>,
     >,
@@ -429,11 +2076,16 @@ where
         Query = BytesMessageQuery,
         Output = BytesMessageQueryRes,
     >,
+    I: VerifyBlockInterpreter<Message = Vec<u8>> + 'static,
 {
     /// Provide information about the ABCI application.
     async fn info(&self, _request: request::Info) -> AbciResult<response::Info> {
         let state = self.committed_state()?;
 
+        if let Some(ref lane) = self.operator_lane {
+            lane.set_chain_id(state.chain_id());
+        }
+
         let height = tendermint::block::Height::try_from(state.block_height)?;
 
         let info = response::Info {
@@ -469,6 +2121,18 @@ where
         // Make it easy to spot any discrepancies between nodes.
         tracing::info!(genesis_hash = genesis_hash.to_string(), "genesis");
 
+        if let Some(ref check) = self.parent_genesis_check {
+            let genesis = parse_genesis(&genesis_bytes)?;
+            check
+                .verify(&genesis)
+                .await
+                .context("failed to validate the genesis against the parent")?;
+            tracing::info!(
+                subnet_id = check.subnet_id.to_string(),
+                "genesis matches the parent"
+            );
+        }
+
         let (state, out) = self
             .interpreter
             .init(state, genesis_bytes)
@@ -476,6 +2140,7 @@ where
             .context("failed to init from genesis")?;
 
         let state_root = state.commit().context("failed to commit genesis state")?;
+        let genesis_powers = to_power_updates(&out.validators);
         let validators =
             to_validator_updates(out.validators).context("failed to convert validators")?;
 
@@ -511,25 +2176,165 @@ where
             app_hash: app_state.app_hash(),
         };
 
-        tracing::info!(
-            height,
-            state_root = app_state.state_root().to_string(),
-            app_hash = app_state.app_hash().to_string(),
-            timestamp = app_state.state_params.timestamp.0,
-            chain_id = app_state.state_params.chain_id,
-            "init chain"
-        );
+        tracing::info!(
+            height,
+            state_root = app_state.state_root().to_string(),
+            app_hash = app_state.app_hash().to_string(),
+            timestamp = app_state.state_params.timestamp.0,
+            chain_id = app_state.state_params.chain_id,
+            "init chain"
+        );
+
+        let state_params = app_state.state_params.clone();
+
+        self.db
+            .with_write(|tx| {
+                self.put_committed_state(tx, app_state)?;
+                self.put_validator_set(tx, height, genesis_powers, true)
+            })
+            .context("failed to store the genesis state")?;
+
+        if let Some(ref recorder) = self.vector_recorder {
+            if let Err(e) = recorder
+                .genesis(self.state_store_clone(), state_params, height)
+                .await
+            {
+                tracing::warn!(
+                    error = e.to_string(),
+                    "failed to record test vector genesis"
+                );
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Query the application for data at the current or past height.
+    async fn query(&self, request: request::Query) -> AbciResult<response::Query> {
+        let db = self.state_store_clone();
+        let height = FvmQueryHeight::from(request.height.value());
+        let (state_params, block_height) = self.state_params_at_height(height)?;
+
+        // Capabilities don't depend on the state, so they can be reported even before genesis.
+        if is_capabilities_query(&request.data) {
+            return Ok(to_capabilities(self.capabilities()?, block_height)?);
+        }
+
+        // The status is needed exactly when there is no state to query yet.
+        if request.path == STATE_SYNC_STATUS_PATH {
+            let progress = match self.snapshots {
+                Some(ref client) => atomically(|| client.download_progress()).await,
+                None => None,
+            };
+            return Ok(to_state_sync_status(progress, block_height)?);
+        }
+
+        if request.path == LOGS_BLOOM_PATH {
+            let (from, to) = match fvm_ipld_encoding::from_slice(&request.data) {
+                Ok(range) => range,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let blooms = self.logs_blooms.blooms(from, to)?;
+            return Ok(to_logs_blooms(blooms, block_height)?);
+        }
+
+        if request.path == BLOCK_HEIGHT_BY_HASH_PATH {
+            let height = self.block_hashes.height(&request.data)?;
+            let first_height = self.block_hashes.first_height()?;
+            return Ok(to_block_height_by_hash(height, first_height, block_height)?);
+        }
+
+        if request.path == ETH_BLOCK_HEIGHT_BY_HASH_PATH {
+            let height = self.block_hashes.eth_height(&request.data)?;
+            let first_height = self.block_hashes.eth_first_height()?;
+            return Ok(to_block_height_by_hash(height, first_height, block_height)?);
+        }
+
+        if request.path == GAS_STATS_PATH {
+            if !self.admin_queries {
+                return Ok(invalid_query(
+                    AppError::AdminOnly,
+                    "gas statistics are only available on the admin endpoint".to_owned(),
+                ));
+            }
+            let query = if request.data.is_empty() {
+                GasStatsQuery::default()
+            } else {
+                match serde_json::from_slice(&request.data) {
+                    Ok(query) => query,
+                    Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+                }
+            };
+            let report = self.gas_stats(&query, block_height)?;
+            return Ok(to_gas_stats(report, block_height)?);
+        }
+
+        if request.path == REJECTED_PROPOSALS_PATH {
+            if !self.admin_queries {
+                return Ok(invalid_query(
+                    AppError::AdminOnly,
+                    "rejected proposals are only available on the admin endpoint".to_owned(),
+                ));
+            }
+            let rejections = self.rejected_proposals.list();
+            return Ok(to_rejected_proposals(rejections, block_height)?);
+        }
+
+        if request.path == MSG_TRACE_PATH {
+            if !self.admin_queries {
+                return Ok(invalid_query(
+                    AppError::AdminOnly,
+                    "message traces are only available on the admin endpoint".to_owned(),
+                ));
+            }
+            let query: MsgTraceQuery = match serde_json::from_slice(&request.data) {
+                Ok(query) => query,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let trace = self.msg_trace(&query)?;
+            return Ok(to_msg_trace(trace, block_height)?);
+        }
+
+        if request.path == APP_HASH_PATH {
+            let height: BlockHeight = match fvm_ipld_encoding::from_slice(&request.data) {
+                Ok(height) => height,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let report = self.app_hash_report(height)?;
+            return Ok(to_app_hash_query(report, block_height)?);
+        }
 
-        self.set_committed_state(app_state)?;
+        if request.path == VALIDATOR_SET_PATH {
+            let height: BlockHeight = match fvm_ipld_encoding::from_slice(&request.data) {
+                Ok(height) => height,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let height = if height == 0 {
+                block_height
+            } else {
+                height.min(block_height)
+            };
+            let set = self.validator_set(height)?;
+            return Ok(to_validator_set(set, block_height)?);
+        }
 
-        Ok(response)
-    }
+        if request.path == PARENT_FINALITY_PATH {
+            let height: BlockHeight = match fvm_ipld_encoding::from_slice(&request.data) {
+                Ok(height) => height,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let finality = self.parent_finality(FvmQueryHeight::from(height))?;
+            return Ok(to_parent_finality(finality, block_height)?);
+        }
 
-    /// Query the application for data at the current or past height.
-    async fn query(&self, request: request::Query) -> AbciResult<response::Query> {
-        let db = self.state_store_clone();
-        let height = FvmQueryHeight::from(request.height.value());
-        let (state_params, block_height) = self.state_params_at_height(height)?;
+        if request.path == TOPDOWN_MSGS_PATH {
+            let (from, to) = match fvm_ipld_encoding::from_slice(&request.data) {
+                Ok(range) => range,
+                Err(e) => return Ok(invalid_query(AppError::InvalidEncoding, e.to_string())),
+            };
+            let msgs = self.topdown_msgs(from, to).await?;
+            return Ok(to_topdown_msgs(msgs, block_height)?);
+        }
 
         tracing::debug!(
             query_height = request.height.value(),
@@ -554,15 +2359,23 @@ where
             self.check_state.clone(),
             height == FvmQueryHeight::Pending,
         )
-        .context("error creating query state")?;
+        .context("error creating query state")?
+        .with_budget(self.query_budget);
 
         let qry = (request.path, request.data.to_vec());
 
-        let (_, result) = self
-            .interpreter
-            .query(state, qry)
-            .await
-            .context("error running query")?;
+        let (_, result) = match self.interpreter.query(state, qry).await {
+            Ok(res) => res,
+            Err(e) => match e.downcast_ref::<QueryBudgetExceeded>() {
+                Some(exceeded) => {
+                    return Ok(invalid_query(
+                        AppError::QueryBudgetExceeded,
+                        exceeded.to_string(),
+                    ))
+                }
+                None => return Err(e.context("error running query").into()),
+            },
+        };
 
         let response = match result {
             Err(e) => invalid_query(AppError::InvalidEncoding, e.description),
@@ -573,30 +2386,68 @@ where
 
     /// Check the given transaction before putting it into the local mempool.
     async fn check_tx(&self, request: request::CheckTx) -> AbciResult<response::CheckTx> {
+        if let Some(halt_height) = self.halted_at()? {
+            return Ok(invalid_check_tx(
+                AppError::Halted,
+                format!("the application halted at height {halt_height}"),
+            ));
+        }
+
+        if let Some(conflict) = self.read_only_conflict() {
+            return Ok(invalid_check_tx(
+                AppError::ReadOnly,
+                format!(
+                    "the committed parent finality at height {} conflicts with the parent",
+                    conflict.height
+                ),
+            ));
+        }
+
+        // Failing the recheck is what gets CometBFT to drop the transaction from the mempool.
+        if let Some(ref evictions) = self.mempool_evictions {
+            if evictions.is_evicted(&request.tx) {
+                return Ok(invalid_check_tx(
+                    AppError::Evicted,
+                    "the transaction was evicted by the operator".to_owned(),
+                ));
+            }
+        }
+
+        let cid = tx_cid(&request.tx);
+
+        // Rechecks are done for transactions already in the mempool, so they would be found.
+        if request.kind == CheckTxKind::New && self.recent_txs.lock().unwrap().contains(&cid) {
+            return Ok(invalid_check_tx(
+                AppError::DuplicateTransaction,
+                format!("transaction {cid} has been seen recently"),
+            ));
+        }
+
+        // Rechecks are left alone, so the transactions already in the mempool aren't dropped.
+        let bottom_up_queue_full = request.kind == CheckTxKind::New
+            && self.max_bottom_up_queue_depth > 0
+            && self.is_bottom_up_queue_full()?;
+
+        if bottom_up_queue_full && is_bottom_up_tx(&request.tx) {
+            return Ok(self.bottom_up_queue_full());
+        }
+
         // Keep the guard through the check, so there can be only one at a time.
         let mut guard = self.check_state.lock().await;
 
-        let state = match guard.take() {
+        let mut state = match guard.take() {
             Some(state) => state,
-            None => {
-                let db = self.state_store_clone();
-                let state = self.committed_state()?;
-
-                // This would create a partial state, but some client scenarios need the full one.
-                // FvmCheckState::new(db, state.state_root(), state.chain_id())
-                //     .context("error creating check state")?
+            None => self.new_check_state()?,
+        };
 
-                FvmExecState::new(
-                    ReadOnlyBlockstore::new(db),
-                    self.multi_engine.as_ref(),
-                    state.block_height.try_into()?,
-                    state.state_params,
-                )
-                .context("error creating check state")?
-            }
+        // Contracts can send bottom-up messages too, which shows if the transaction is executed.
+        let bottom_up_nonce = if bottom_up_queue_full {
+            self.check_bottom_up_nonce(&mut state)?
+        } else {
+            None
         };
 
-        let (state, result) = self
+        let (mut state, result) = self
             .interpreter
             .check(
                 state,
@@ -606,17 +2457,24 @@ where
             .await
             .context("error running check")?;
 
+        let sent_bottom_up = match bottom_up_nonce {
+            Some(nonce) => self.check_bottom_up_nonce(&mut state)? != Some(nonce),
+            None => false,
+        };
+
         // Update the check state.
         *guard = Some(state);
 
-        let response = match result {
-            Err(e) => invalid_check_tx(AppError::InvalidEncoding, e.description),
-            Ok(result) => match result {
-                Err(IllegalMessage) => invalid_check_tx(AppError::IllegalMessage, "".to_owned()),
-                Ok(Err(InvalidSignature(d))) => invalid_check_tx(AppError::InvalidSignature, d),
-                Ok(Ok(ret)) => to_check_tx(ret),
-            },
-        };
+        // The effects stay in the check state, which only holds back the sender until the next block.
+        if sent_bottom_up {
+            return Ok(self.bottom_up_queue_full());
+        }
+
+        let response = to_check_response(result);
+
+        if response.code.is_ok() {
+            self.recent_txs.lock().unwrap().checked(cid);
+        }
 
         Ok(response)
     }
@@ -631,23 +2489,74 @@ where
             time = request.time.to_string(),
             "prepare proposal"
         );
+        let _activity = self
+            .progress
+            .enter("prepare_proposal", Some(request.height.value()));
+        if let Some(halt_height) = self.halted_at()? {
+            return Err(anyhow!(
+                "the application halted at height {halt_height}; refusing to propose"
+            )
+            .into());
+        }
         let txs = request.txs.into_iter().map(|tx| tx.to_vec()).collect();
 
+        // Put the operator first, so that the transactions survive the cut at the maximum size.
+        let txs = match self.operator_lane {
+            Some(ref lane) => lane.prioritize(txs),
+            None => txs,
+        };
+
+        let txs = if self.defer_check {
+            self.check_proposed_txs(txs).await?
+        } else {
+            txs
+        };
+
+        let topdown_nonce = self
+            .topdown_nonce()
+            .context("failed to get the next top-down nonce")?;
+
         let txs = self
             .interpreter
             .prepare(
                 (
                     self.resolve_pool.clone(),
                     self.parent_finality_provider.clone(),
+                    None,
+                    topdown_nonce,
                 ),
                 txs,
             )
             .await
             .context("failed to prepare proposal")?;
 
+        let gas_limit = self
+            .block_gas_limit(request.height.value())
+            .context("failed to get the block gas limit")?;
+        let txs = blockgas::take_within_limit(txs, gas_limit);
+
         let txs = txs.into_iter().map(bytes::Bytes::from).collect();
         let txs = take_until_max_size(txs, request.max_tx_bytes.try_into().unwrap());
 
+        // Sorting after the cut, so that the order doesn't affect which transactions get in.
+        let txs = match self.tx_order_state()? {
+            Some(mut state) => {
+                let txs = txs.into_iter().map(|tx| tx.to_vec()).collect();
+                ordering::sort_canonical(txs, |from| state.tx_order_sender(from))
+                    .context("failed to sort the proposal")?
+                    .into_iter()
+                    .map(bytes::Bytes::from)
+                    .collect()
+            }
+            None => txs,
+        };
+
+        if let Some(ref lane) = self.operator_lane {
+            lane.proposed(txs.iter().map(|tx| tx.as_ref()));
+        }
+
+        self.record_topdown_proposal(&txs);
+
         Ok(response::PrepareProposal { txs })
     }
 
@@ -661,24 +2570,89 @@ where
             time = request.time.to_string(),
             "process proposal"
         );
-        let txs = request.txs.into_iter().map(|tx| tx.to_vec()).collect();
+        let _activity = self
+            .progress
+            .enter("process_proposal", Some(request.height.value()));
+        let txs: Vec<Vec<u8>> = request.txs.into_iter().map(|tx| tx.to_vec()).collect();
+        let bytes = proposals::observe_proposal(&self.subnet_id, &txs);
+        let num_txs = txs.len();
+
+        self.record_topdown_proposal(&txs);
+
+        if self.block_signature_pool.is_some() {
+            if let tendermint::Hash::Sha256(hash) = request.hash {
+                *self.processed_proposal.lock().unwrap() = Some((hash, txs.clone()));
+            }
+        }
+
+        let reject = |reason| {
+            self.rejected_proposals.record(
+                &self.subnet_id,
+                RejectedProposal::new(
+                    request.height.value(),
+                    request.time.to_string(),
+                    request.proposer_address.to_string(),
+                    num_txs,
+                    bytes,
+                    reason,
+                ),
+            );
+            response::ProcessProposal::Reject
+        };
+
+        if let Some(halt_height) = self.halted_at()? {
+            return Ok(reject(RejectReason::Halted { halt_height }));
+        }
+        self.time_monitor.observe(request.time);
+
+        if !self.check_block_time(request.height.value(), request.time)? {
+            return Ok(reject(RejectReason::BlockTime));
+        }
+        if let Some(mut state) = self.tx_order_state()? {
+            let non_canonical =
+                ordering::find_non_canonical(&txs, |from| state.tx_order_sender(from))
+                    .context("failed to check the order of the proposal")?;
+            if let Some(index) = non_canonical {
+                return Ok(reject(RejectReason::Tx(
+                    ProposalRejection::NonCanonicalOrder { index },
+                )));
+            }
+        }
+
+        let gas_limit = self
+            .block_gas_limit(request.height.value())
+            .context("failed to get the block gas limit")?;
+        if let Some(index) = blockgas::find_over_limit(&txs, gas_limit) {
+            return Ok(reject(RejectReason::Tx(ProposalRejection::BlockGasLimit {
+                index,
+            })));
+        }
+
+        let signature_check = self
+            .proposal_signature_check()
+            .context("failed to set up the signature check")?;
+
+        let topdown_nonce = self
+            .topdown_nonce()
+            .context("failed to get the next top-down nonce")?;
 
-        let accept = self
+        let verdict = self
             .interpreter
             .process(
                 (
                     self.resolve_pool.clone(),
                     self.parent_finality_provider.clone(),
+                    signature_check,
+                    topdown_nonce,
                 ),
                 txs,
             )
             .await
             .context("failed to process proposal")?;
 
-        if accept {
-            Ok(response::ProcessProposal::Accept)
-        } else {
-            Ok(response::ProcessProposal::Reject)
+        match verdict {
+            ProposalVerdict::Accept => Ok(response::ProcessProposal::Accept),
+            ProposalVerdict::Reject(r) => Ok(reject(RejectReason::Tx(r))),
         }
     }
 
@@ -689,6 +2663,14 @@ where
             tendermint::Hash::Sha256(h) => h,
             tendermint::Hash::None => return Err(anyhow!("empty block hash").into()),
         };
+        let _activity = self.progress.enter("begin_block", Some(block_height));
+
+        if let Some(halt_height) = self.halted_at()? {
+            return Err(anyhow!(
+                "the application halted at height {halt_height}; refusing to begin block {block_height}"
+            )
+            .into());
+        }
 
         let db = self.state_store_clone();
         let state = self.committed_state()?;
@@ -704,13 +2686,38 @@ where
 
         state_params.timestamp = to_timestamp(request.header.time);
 
-        let state = FvmExecState::new(db, self.multi_engine.as_ref(), block_height, state_params)
-            .context("error creating new state")?
-            .with_block_hash(block_hash);
+        // Validators already observed it in `process_proposal`, but full nodes don't get to see proposals.
+        self.time_monitor.observe(request.header.time);
+
+        if let Some(ref recorder) = self.vector_recorder {
+            recorder.begin(
+                request.header.height.value(),
+                state_params.timestamp.0,
+                &block_hash,
+            );
+        }
+
+        let mut state =
+            FvmExecState::new(db, self.multi_engine.as_ref(), block_height, state_params)
+                .context("error creating new state")?
+                .with_block_hash(block_hash);
 
         tracing::debug!("initialized exec state");
 
+        let eth_block_hash = match state.eth_block_hash_version()? {
+            EthBlockHashVersion::V0 => None,
+            version => Some(version.to_eth_block_hash(&block_hash)),
+        };
+
+        self.verify_block_signatures(&mut state, block_hash)
+            .await
+            .context("failed to verify the signatures of the block")?;
+
         self.put_exec_state(state).await;
+        self.logs_blooms.begin();
+        self.block_hashes.begin(block_hash, eth_block_hash);
+        self.update_event_indexes(|index| index.begin(block_height as BlockHeight));
+        *self.block_gas_stats.lock().unwrap() = BlockGasStats::default();
 
         let ret = self
             .modify_exec_state(|s| self.interpreter.begin(s))
@@ -722,29 +2729,65 @@ where
 
     /// Apply a transaction to the application's state.
     async fn deliver_tx(&self, request: request::DeliverTx) -> AbciResult<response::DeliverTx> {
+        let _activity = self.progress.enter("deliver_tx", None);
+        let cid = tx_cid(&request.tx);
+        self.recent_txs.lock().unwrap().delivered(cid);
+
+        if let Some(ref lane) = self.operator_lane {
+            lane.delivered(&cid);
+        }
+
         let msg = request.tx.to_vec();
-        let (result, block_hash) = self
+        let (result, block_hash, block_height) = self
             .modify_exec_state(|s| async {
                 let ((pool, provider, state), res) = self.interpreter.deliver(s, msg).await?;
                 let block_hash = state.block_hash();
-                Ok(((pool, provider, state), (res, block_hash)))
+                let block_height = state.block_height();
+                Ok(((pool, provider, state), (res, block_hash, block_height)))
             })
             .await
             .context("deliver failed")?;
 
-        let response = match result {
-            Err(e) => invalid_deliver_tx(AppError::InvalidEncoding, e.description),
+        if let Some(ref recorder) = self.vector_recorder {
+            recorder.delivered(&request.tx, ReceiptVector::from_apply_res(&result));
+        }
+
+        let export = |ret: &FvmApplyRet, domain_hash: Option<&DomainHash>| {
+            self.exporter.as_ref().map(|_| {
+                ExportRecord::new(block_height as BlockHeight, &request.tx, ret, domain_hash)
+            })
+        };
+
+        let (response, record) = match result {
+            Err(e) => (
+                invalid_deliver_tx(AppError::InvalidEncoding, e.description),
+                None,
+            ),
             Ok(ret) => match ret {
                 ChainMessageApplyRet::Signed(Err(InvalidSignature(d))) => {
-                    invalid_deliver_tx(AppError::InvalidSignature, d)
+                    (invalid_deliver_tx(AppError::InvalidSignature, d), None)
                 }
                 ChainMessageApplyRet::Signed(Ok(ret)) => {
-                    to_deliver_tx(ret.fvm, ret.domain_hash, block_hash)
+                    self.record_gas(&ret.fvm, false);
+                    let record = export(&ret.fvm, ret.domain_hash.as_ref());
+                    (to_deliver_tx(ret.fvm, ret.domain_hash, block_hash), record)
+                }
+                ChainMessageApplyRet::Ipc(ret) => {
+                    self.record_gas(&ret, true);
+                    let record = export(&ret, None);
+                    (to_deliver_tx(ret, None, block_hash), record)
                 }
-                ChainMessageApplyRet::Ipc(ret) => to_deliver_tx(ret, None, block_hash),
             },
         };
 
+        if let Some(ref exporter) = self.exporter {
+            exporter.delivered(record);
+        }
+
+        let block_height = block_height as BlockHeight;
+        self.logs_blooms.delivered(&response);
+        self.update_event_indexes(|index| index.delivered(block_height, &request.tx, &response));
+
         if response.code != 0.into() {
             tracing::info!(
                 "deliver_tx failed: {:?} - {:?}",
@@ -759,6 +2802,9 @@ where
     /// Signals the end of a block.
     async fn end_block(&self, request: request::EndBlock) -> AbciResult<response::EndBlock> {
         tracing::debug!(height = request.height, "end block");
+        let _activity = self
+            .progress
+            .enter("end_block", Some(request.height as BlockHeight));
 
         // TODO: Return events from epoch transitions.
         let ret = self
@@ -766,33 +2812,38 @@ where
             .await
             .context("end failed")?;
 
+        if let Some(ref recorder) = self.vector_recorder {
+            recorder.ended(&ret);
+        }
+
+        *self.block_power_updates.lock().unwrap() = to_power_updates(&ret);
+
         Ok(to_end_block(ret)?)
     }
 
     /// Commit the current state at the current height.
     async fn commit(&self) -> AbciResult<response::Commit> {
+        let _activity = self.progress.enter("commit", None);
         let exec_state = self.take_exec_state().await;
+        let block_height = exec_state.block_height().try_into()?;
+        let timestamp = exec_state.timestamp();
 
-        // Commit the execution state to the datastore.
-        let mut state = self.committed_state()?;
-        state.block_height = exec_state.block_height().try_into()?;
-        state.state_params.timestamp = exec_state.timestamp();
-
-        let (
-            state_root,
-            FvmUpdatableParams {
-                power_scale,
-                circ_supply,
-            },
-            _,
-        ) = exec_state.commit().context("failed to commit FVM")?;
+        let power_updates = std::mem::take(&mut *self.block_power_updates.lock().unwrap());
+        let has_power_updates = !power_updates.is_empty();
 
-        state.state_params.state_root = state_root;
-        state.state_params.power_scale = power_scale;
-        state.state_params.circ_supply = circ_supply;
+        // Commit the execution state to the datastore.
+        let state = self.write_state(block_height, timestamp, power_updates, || {
+            let (state_root, params, _) = exec_state.commit()?;
+            Ok((state_root, params))
+        })?;
 
+        let state_root = state.state_root();
         let app_hash = state.app_hash();
-        let block_height = state.block_height;
+
+        // After the genesis, or a restore from a snapshot, this is where the lane learns the chain ID.
+        if let Some(ref lane) = self.operator_lane {
+            lane.set_chain_id(state.chain_id());
+        }
 
         // Tell CometBFT how much of the block history it can forget.
         let retain_height = if self.state_hist_size == 0 {
@@ -838,17 +2889,75 @@ where
         //    (even though this state will only be available to query from the next height);
         //    CometBFT is going to offer it with the `app_hash` of 901, but in this case that's good, because
         //    that hash reflects the changes made by block 900, which this state param is the result of.
+        // The notification is only sent once the commit is complete, so that we never
+        // snapshot a state which could be rolled back after a crash.
+        let state_params = state.state_params;
+
+        self.recent_txs.lock().unwrap().committed(block_height);
+        if let Some(ref evictions) = self.mempool_evictions {
+            evictions.committed(block_height);
+        }
+
+        self.block_hashes.committed(block_height)?;
+        self.update_event_indexes(|index| index.committed(block_height));
+
+        let gas_stats = std::mem::take(&mut *self.block_gas_stats.lock().unwrap());
+        self.set_gas_stats(block_height, gas_stats)?;
+
+        // The votes only help the proposer pick a parent finality, so a failure to update
+        // the power they are weighed by shouldn't stop the chain.
+        if let Err(e) = self
+            .update_vote_tally(block_height, has_power_updates)
+            .await
+        {
+            tracing::error!(
+                block_height,
+                error = format!("{e:#}"),
+                "failed to update the parent vote tally"
+            );
+        }
+
+        // The traces are only a local record, so a failure to update them shouldn't stop the chain.
+        if let Err(e) = self.update_topdown_traces().await {
+            tracing::error!(
+                block_height,
+                error = format!("{e:#}"),
+                "failed to update the top-down message traces"
+            );
+        }
+
+        // Everything about the block has been written; a crash from here on doesn't roll it back.
+        self.end_commit()?;
+
         if let Some(ref snapshots) = self.snapshots {
-            atomically(|| snapshots.notify(block_height, state.state_params.clone())).await;
+            atomically(|| snapshots.notify(block_height, state_params.clone())).await;
+        }
+
+        if let Some(ref exporter) = self.exporter {
+            exporter.commit(block_height);
+        }
+
+        if let Some(ref recorder) = self.vector_recorder {
+            recorder.commit(state_root);
         }
 
-        // Commit app state to the datastore.
-        self.set_committed_state(state)?;
+        self.take_divergence_action(block_height);
+
+        if self.halt_height.get() == Some(block_height) {
+            tracing::warn!(
+                block_height,
+                "reached the halt height; no further blocks will be processed"
+            );
+        }
 
         // Reset check state.
         let mut guard = self.check_state.lock().await;
         *guard = None;
 
+        *self.proposal_checks.lock().await = None;
+
+        self.progress.committed(block_height, app_hash.to_string());
+
         Ok(response::Commit {
             data: app_hash.into(),
             retain_height: retain_height.try_into().expect("height is valid"),
@@ -909,7 +3018,10 @@ where
                 Ok(manifest) => {
                     tracing::info!(?manifest, "received snapshot offer");
                     // We can look at the version but currently there's only one.
-                    match atomically_or_err(|| client.offer_snapshot(manifest.clone())).await {
+                    match client
+                        .offer_snapshot(manifest.clone(), self.state_store_clone())
+                        .await
+                    {
                         Ok(path) => {
                             tracing::info!(
                                 download_dir = path.to_string_lossy().to_string(),
@@ -948,22 +3060,33 @@ where
         let default = response::ApplySnapshotChunk::default();
 
         if let Some(ref client) = self.snapshots {
-            match atomically_or_err(|| {
-                client.save_chunk(request.index, request.chunk.clone().into())
-            })
-            .await
+            match client
+                .save_chunk(request.index, request.chunk.clone().into())
+                .await
             {
                 Ok(snapshot) => {
-                    if let Some(snapshot) = snapshot {
+                    if let Some(progress) = atomically(|| client.download_progress()).await {
+                        tracing::info!(
+                            chunks_received = progress.chunks_received,
+                            chunks_total = progress.chunks_total,
+                            bytes_received = progress.bytes_received,
+                            blocks_verified = progress.blocks_verified,
+                            eta_secs = progress.eta_secs,
+                            "snapshot download progress"
+                        );
+                    }
+
+                    if let Some((snapshot, metadata_cid)) = snapshot {
                         tracing::info!(
                             download_dir = snapshot.snapshot_dir.to_string_lossy().to_string(),
                             height = snapshot.manifest.block_height,
                             "received all snapshot chunks",
                         );
 
+                        // The chunks have been written to the blockstore as they arrived.
                         // Ideally we would import into some isolated store then validate,
                         // but for now let's trust that all is well.
-                        if let Err(e) = snapshot.import(self.state_store_clone(), true).await {
+                        if let Err(e) = snapshot.load(self.state_store_clone(), metadata_cid) {
                             tracing::error!(error =? e, "failed to import snapshot");
                             return Ok(response::ApplySnapshotChunk {
                                 result: response::ApplySnapshotChunkResult::RejectSnapshot,
@@ -1006,6 +3129,15 @@ where
                         ..default
                     });
                 }
+                Err(SnapshotError::CorruptChunk(chunk, reason)) => {
+                    tracing::warn!(chunk, reason, "corrupt snapshot chunk");
+                    // Blocks can span chunks, so the problem might not be in this one;
+                    // rather than trying to figure out which ones to refetch, try another snapshot.
+                    return Ok(response::ApplySnapshotChunk {
+                        result: response::ApplySnapshotChunkResult::RejectSnapshot,
+                        ..default
+                    });
+                }
                 Err(SnapshotError::WrongChecksum(expected, got)) => {
                     tracing::warn!(?got, ?expected, "wrong snapshot checksum");
                     // We could retry this snapshot, or try another one.
@@ -1029,3 +3161,525 @@ where
         Ok(default)
     }
 }
+
+fn decode_validator_set(bz: RawBytes) -> KVResult<ValidatorSet> {
+    fvm_ipld_encoding::from_slice(&bz).map_err(|e| KVError::Codec(Box::new(e)))
+}
+
+/// Power updates as `(public_key, power)` pairs, the way they are recorded in the validator set history.
+fn to_power_updates(validators: &[Validator<Power>]) -> Vec<(Vec<u8>, u64)> {
+    validators
+        .iter()
+        .map(|v| (v.public_key.0.serialize().to_vec(), v.power.0))
+        .collect()
+}
+
+/// Convert the outcome of checking a transaction into what CometBFT expects.
+fn to_check_response(result: BytesMessageCheckRes) -> response::CheckTx {
+    match result {
+        Err(e) => invalid_check_tx(AppError::InvalidEncoding, e.description),
+        Ok(result) => match result {
+            Err(IllegalMessage) => invalid_check_tx(AppError::IllegalMessage, "".to_owned()),
+            Ok(Err(InvalidSignature(d))) => invalid_check_tx(AppError::InvalidSignature, d),
+            Ok(Ok(ret)) => to_check_tx(ret),
+        },
+    }
+}
+
+fn put_conflict_record<S>(
+    tx: &mut impl KVWrite<S>,
+    namespace: &S::Namespace,
+    record: &ConflictRecord,
+) -> KVResult<()>
+where
+    S: KVStore + Encode<AppStoreKey> + Codec<RawBytes>,
+{
+    tx.put(
+        namespace,
+        &AppStoreKey::FinalityConflict,
+        &bottomup::encode_entry(record)?,
+    )
+}
+
+/// Check whether the query is [`FvmQuery::Capabilities`], without decoding every query to find out.
+fn is_capabilities_query(data: &[u8]) -> bool {
+    fvm_ipld_encoding::to_vec(&FvmQuery::Capabilities)
+        .map(|bz| bz == data)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    use anyhow::anyhow;
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fendermint_rocksdb::{
+        blockstore::{NamespaceBlockstore, SecondaryBlockstore},
+        RocksDb, RocksDbConfig, RocksDbSecondary,
+    };
+    use fendermint_storage::{KVRead, KVReadable, KVWritable};
+    use fendermint_vm_core::Timestamp;
+    use fendermint_vm_interpreter::chain::CheckpointPool;
+    use fendermint_vm_interpreter::fvm::state::FvmUpdatableParams;
+    use fendermint_vm_topdown::divergence::{DivergenceAction, FinalityConflict};
+    use fendermint_vm_topdown::{IPCParentFinality, Toggle};
+    use fvm_ipld_blockstore::Blockstore;
+    use fvm_ipld_encoding::{CborStore, DAG_CBOR};
+    use fvm_shared::econ::TokenAmount;
+
+    use crate::bottomup::GatewayView;
+    use crate::store::AppStore;
+
+    use super::{App, AppConfig, AppStoreKey, BlockHeight, CommitJournal};
+
+    type TestApp = App<RocksDb, NamespaceBlockstore, AppStore, ()>;
+
+    /// Points where the process is killed during a commit.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum CrashPoint {
+        /// After writing the journal, before flushing the state.
+        AfterJournal,
+        /// After flushing the state, before recording it.
+        AfterFlush,
+        /// After recording the state, before the rest of the block is written.
+        AfterState,
+        /// After the commit completed.
+        AfterCommit,
+    }
+
+    fn test_config() -> AppConfig<AppStore> {
+        AppConfig::<AppStore> {
+            app_namespace: "app".to_owned(),
+            state_hist_namespace: "state_hist".to_owned(),
+            state_hist_size: 0,
+            logs_bloom_namespace: "logs_bloom".to_owned(),
+            block_hashes_namespace: "block_hashes".to_owned(),
+            eth_block_hashes_namespace: "eth_block_hashes".to_owned(),
+            gas_stats_namespace: "gas_stats".to_owned(),
+            gas_stats_blocks: 0,
+            validator_sets_namespace: "validator_sets".to_owned(),
+            bottom_up_queue_namespace: "bottom_up_queue".to_owned(),
+            topdown_traces_namespace: "topdown_traces".to_owned(),
+            bottomup_traces_namespace: "bottomup_traces".to_owned(),
+            msg_traces: 0,
+            builtin_actors_bundle: Default::default(),
+            tx_dedup_blocks: 0,
+            tracing_enabled: false,
+            exporter: None,
+            vector_recorder: None,
+            halt_height: Default::default(),
+            time_monitor: Default::default(),
+            defer_check: false,
+            query_budget: Default::default(),
+            subnet_id: String::new(),
+        }
+    }
+
+    fn open_app(path: &Path) -> anyhow::Result<TestApp> {
+        let db = RocksDb::open_cf(
+            path,
+            &RocksDbConfig::default(),
+            [
+                "app",
+                "state_hist",
+                "logs_bloom",
+                "block_hashes",
+                "eth_block_hashes",
+                "gas_stats",
+                "validator_sets",
+                "bottom_up_queue",
+                "topdown_traces",
+                "bottomup_traces",
+                "state_store",
+            ]
+            .iter(),
+        )?;
+        let state_store = NamespaceBlockstore::new(db.clone(), "state_store".to_owned())?;
+        App::new(
+            test_config(),
+            db,
+            state_store,
+            (),
+            CheckpointPool::new(),
+            std::sync::Arc::new(Toggle::disabled()),
+            None,
+        )
+    }
+
+    /// Commit `block_height` the way `commit` does, with a state flushed by a stand-in
+    /// for the FVM, stopping at the crash point.
+    fn commit_until(app: &TestApp, block_height: BlockHeight, crash: CrashPoint) {
+        commit_powers_until(app, block_height, Vec::new(), crash)
+    }
+
+    /// Same as [`commit_until`], with the power updates of the block.
+    fn commit_powers_until(
+        app: &TestApp,
+        block_height: BlockHeight,
+        power_updates: Vec<(Vec<u8>, u64)>,
+        crash: CrashPoint,
+    ) {
+        let res = app.write_state(block_height, Timestamp(block_height), power_updates, || {
+            if crash == CrashPoint::AfterJournal {
+                return Err(anyhow!("crashed before flushing"));
+            }
+            let state_root = app
+                .state_store
+                .put_cbor(&("state", block_height), Code::Blake2b256)?;
+            if crash == CrashPoint::AfterFlush {
+                return Err(anyhow!("crashed after flushing"));
+            }
+            let params = FvmUpdatableParams {
+                base_fee: TokenAmount::from_atto(block_height),
+                circ_supply: TokenAmount::from_atto(block_height),
+                power_scale: 0,
+            };
+            Ok((state_root, params))
+        });
+
+        match crash {
+            CrashPoint::AfterJournal | CrashPoint::AfterFlush => assert!(res.is_err()),
+            CrashPoint::AfterState => {
+                res.unwrap();
+            }
+            CrashPoint::AfterCommit => {
+                res.unwrap();
+                app.end_commit().unwrap();
+            }
+        }
+    }
+
+    fn get_journal(app: &TestApp) -> Option<CommitJournal> {
+        let tx = KVReadable::<AppStore>::read(app.db.as_ref());
+        KVRead::<AppStore>::get(&tx, &app.namespace, &AppStoreKey::Journal).unwrap()
+    }
+
+    #[test]
+    fn recover_after_crash() {
+        for crash in [
+            CrashPoint::AfterJournal,
+            CrashPoint::AfterFlush,
+            CrashPoint::AfterState,
+            CrashPoint::AfterCommit,
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("rocksdb");
+
+            let app = open_app(&path).unwrap();
+            commit_until(&app, 1, CrashPoint::AfterCommit);
+            let committed = app.committed_state().unwrap();
+
+            commit_until(&app, 2, crash);
+            // Kill the process: nothing else gets written and the database is closed.
+            drop(app);
+
+            let app = open_app(&path).unwrap();
+            let state = app.committed_state().unwrap();
+            assert!(get_journal(&app).is_none(), "{crash:?}: journal cleared");
+            assert!(app.state_store.has(&state.state_root()).unwrap());
+
+            match crash {
+                CrashPoint::AfterCommit => assert_eq!(state.block_height, 2, "{crash:?}"),
+                _ => {
+                    assert_eq!(state, committed, "{crash:?}");
+                    // The rolled back block can't be queried.
+                    let tx = KVReadable::<AppStore>::read(app.db.as_ref());
+                    assert!(app.state_hist.get(&tx, &3).unwrap().is_none(), "{crash:?}");
+                }
+            }
+
+            // The node can carry on from where it is.
+            commit_until(&app, 3, CrashPoint::AfterCommit);
+            assert_eq!(app.committed_state().unwrap().block_height, 3);
+        }
+    }
+
+    #[test]
+    fn power_updates_roll_back_with_the_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+
+        let app = open_app(&path).unwrap();
+        app.db
+            .with_write(|tx| app.put_validator_set(tx, 0, vec![(vec![1], 10)], true))
+            .unwrap();
+
+        commit_powers_until(&app, 1, vec![(vec![2], 5)], CrashPoint::AfterCommit);
+        let committed = app.validator_set(1).unwrap().unwrap();
+        assert_eq!(committed.validators.len(), 2);
+
+        commit_powers_until(&app, 2, vec![(vec![2], 0)], CrashPoint::AfterState);
+        assert_eq!(app.validator_set(2).unwrap().unwrap().height, 2);
+        drop(app);
+
+        let app = open_app(&path).unwrap();
+        assert_eq!(app.validator_set(2).unwrap(), Some(committed));
+
+        // Replaying the block records the updates again.
+        commit_powers_until(&app, 2, vec![(vec![2], 0)], CrashPoint::AfterCommit);
+        let set = app.validator_set(2).unwrap().unwrap();
+        assert_eq!(set.height, 2);
+        assert_eq!(set.prev_height, Some(1));
+        assert_eq!(set.validators.len(), 1);
+    }
+
+    #[test]
+    fn read_only_app_starts_on_secondary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+
+        let app = open_app(&path).unwrap();
+        commit_until(&app, 1, CrashPoint::AfterCommit);
+        // The primary is in the middle of a commit, which only it can recover from.
+        commit_until(&app, 2, CrashPoint::AfterState);
+
+        let db = RocksDbSecondary::open(
+            &path,
+            dir.path().join("secondary"),
+            &RocksDbConfig::default(),
+        )
+        .unwrap();
+        let state_store = SecondaryBlockstore::new(db.clone(), "state_store".to_owned()).unwrap();
+
+        let open_secondary = |read_only: bool| {
+            let resolve_pool = CheckpointPool::new();
+            let provider = std::sync::Arc::new(Toggle::disabled());
+            if read_only {
+                App::<_, _, AppStore, ()>::new_read_only(
+                    test_config(),
+                    db.clone(),
+                    state_store.clone(),
+                    (),
+                    resolve_pool,
+                    provider,
+                )
+            } else {
+                App::<_, _, AppStore, ()>::new(
+                    test_config(),
+                    db.clone(),
+                    state_store.clone(),
+                    (),
+                    resolve_pool,
+                    provider,
+                    None,
+                )
+            }
+        };
+
+        // Recovering would mean writing to the database.
+        assert!(open_secondary(false).is_err());
+
+        let secondary = open_secondary(true).unwrap();
+        assert_eq!(
+            secondary.committed_state().unwrap(),
+            app.committed_state().unwrap()
+        );
+    }
+
+    #[test]
+    fn halts_after_height() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = open_app(&dir.path().join("rocksdb")).unwrap();
+
+        app.halt_height.set(Some(2));
+        commit_until(&app, 1, CrashPoint::AfterCommit);
+        assert_eq!(app.halted_at().unwrap(), None);
+
+        commit_until(&app, 2, CrashPoint::AfterCommit);
+        assert_eq!(app.halted_at().unwrap(), Some(2));
+
+        // The operator can lift the halt to carry on.
+        app.halt_height.set(None);
+        assert_eq!(app.halted_at().unwrap(), None);
+    }
+
+    #[test]
+    fn bottom_up_queue_waits_for_quorum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+
+        let msg = |nonce: u64| (nonce, vec![nonce as u8]);
+        let update = |app: &TestApp, block_height, view| {
+            app.db
+                .with_write(|tx| app.put_bottom_up_queue(tx, block_height, view, 0))
+                .unwrap()
+        };
+
+        let app = open_app(&path).unwrap().with_max_bottom_up_queue_depth(3);
+
+        // Two messages are sent for the checkpoint at height 10.
+        let view = GatewayView {
+            gateway_nonce: 2,
+            checkpoint_height: 10,
+            msgs: vec![msg(0), msg(1)],
+            incomplete: None,
+        };
+        let bounds = update(&app, 5, view);
+        assert_eq!(bounds.depth(), 2);
+        assert!(!app.is_bottom_up_queue_full().unwrap());
+
+        // The checkpoint is created but not signed yet, and another message is sent for the next one.
+        let view = GatewayView {
+            gateway_nonce: 3,
+            checkpoint_height: 20,
+            msgs: vec![msg(1), msg(2)],
+            incomplete: Some(HashSet::from([10])),
+        };
+        let bounds = update(&app, 10, view);
+        assert_eq!((bounds.first, bounds.checkpointed, bounds.next), (0, 2, 3));
+        assert!(app.is_bottom_up_queue_full().unwrap());
+        drop(app);
+
+        // The queue survives restarts, and shrinks as the checkpoints are signed.
+        let app = open_app(&path).unwrap().with_max_bottom_up_queue_depth(3);
+        assert!(app.is_bottom_up_queue_full().unwrap());
+
+        let view = GatewayView {
+            gateway_nonce: 3,
+            checkpoint_height: 20,
+            msgs: Vec::new(),
+            incomplete: Some(HashSet::new()),
+        };
+        let bounds = update(&app, 11, view);
+        assert_eq!((bounds.first, bounds.checkpointed, bounds.next), (2, 2, 3));
+        assert!(!app.is_bottom_up_queue_full().unwrap());
+
+        let view = GatewayView {
+            gateway_nonce: 3,
+            checkpoint_height: 30,
+            msgs: Vec::new(),
+            incomplete: Some(HashSet::from([20])),
+        };
+        let bounds = update(&app, 20, view);
+        assert_eq!(bounds.depth(), 1);
+
+        let view = GatewayView {
+            gateway_nonce: 3,
+            checkpoint_height: 30,
+            msgs: Vec::new(),
+            incomplete: Some(HashSet::new()),
+        };
+        let bounds = update(&app, 21, view);
+        assert!(bounds.is_empty());
+        assert_eq!(bounds.gateway_nonce, 3);
+    }
+
+    #[test]
+    fn read_only_conflict_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+        let finality = IPCParentFinality::new(10, vec![1; 32]);
+
+        let app = open_app(&path)
+            .unwrap()
+            .with_divergence_action(DivergenceAction::ReadOnly);
+        app.persist_divergence().unwrap();
+        commit_until(&app, 1, CrashPoint::AfterCommit);
+        assert!(app.read_only_conflict().is_none());
+
+        app.divergence.trip(FinalityConflict::new(&finality, None));
+        app.take_divergence_action(2);
+        assert_eq!(app.read_only_conflict().unwrap().height, 10);
+        assert_eq!(app.halted_at().unwrap(), None);
+        drop(app);
+
+        let app = open_app(&path)
+            .unwrap()
+            .with_divergence_action(DivergenceAction::ReadOnly);
+        app.persist_divergence().unwrap();
+        assert_eq!(app.read_only_conflict().unwrap().height, 10);
+
+        // Once cleared, it's gone after a restart too.
+        app.divergence.clear();
+        assert!(app.read_only_conflict().is_none());
+        drop(app);
+
+        let app = open_app(&path)
+            .unwrap()
+            .with_divergence_action(DivergenceAction::ReadOnly);
+        app.persist_divergence().unwrap();
+        assert!(app.read_only_conflict().is_none());
+    }
+
+    #[test]
+    fn halt_on_conflict_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+        let finality = IPCParentFinality::new(10, vec![1; 32]);
+
+        let app = open_app(&path)
+            .unwrap()
+            .with_divergence_action(DivergenceAction::Halt);
+        app.persist_divergence().unwrap();
+        commit_until(&app, 1, CrashPoint::AfterCommit);
+
+        app.divergence.trip(FinalityConflict::new(&finality, None));
+        commit_until(&app, 2, CrashPoint::AfterCommit);
+        app.take_divergence_action(2);
+        assert_eq!(app.halted_at().unwrap(), Some(2));
+        // Transactions are turned away by the halt, not the read-only mode.
+        assert!(app.read_only_conflict().is_none());
+        drop(app);
+
+        let app = open_app(&path)
+            .unwrap()
+            .with_divergence_action(DivergenceAction::Halt);
+        assert_eq!(app.halted_at().unwrap(), None);
+        app.persist_divergence().unwrap();
+        assert_eq!(app.halted_at().unwrap(), Some(2));
+        assert!(app.divergence.is_tripped());
+
+        // Only halting at the original height; the action isn't taken twice.
+        app.take_divergence_action(2);
+        assert_eq!(app.halted_at().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn block_hashes_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+
+        let app = open_app(&path).unwrap();
+        for height in 5..8 {
+            app.block_hashes
+                .begin([height as u8; 32], Some([height as u8 + 100; 32]));
+            app.block_hashes.committed(height).unwrap();
+        }
+        // Nothing to store without a block being executed.
+        app.block_hashes.committed(8).unwrap();
+        drop(app);
+
+        let app = open_app(&path).unwrap();
+        assert_eq!(app.block_hashes.height(&[6u8; 32]).unwrap(), Some(6));
+        assert_eq!(app.block_hashes.height(&[8u8; 32]).unwrap(), None);
+        assert_eq!(app.block_hashes.height(&[]).unwrap(), None);
+        assert_eq!(app.block_hashes.first_height().unwrap(), Some(5));
+        assert_eq!(app.block_hashes.eth_height(&[106u8; 32]).unwrap(), Some(6));
+        assert_eq!(app.block_hashes.eth_height(&[6u8; 32]).unwrap(), None);
+        assert_eq!(app.block_hashes.eth_first_height().unwrap(), Some(5));
+
+        app.block_hashes.backfill_eth(4, [104u8; 32]).unwrap();
+        app.block_hashes.backfill_eth(9, [109u8; 32]).unwrap();
+        assert_eq!(app.block_hashes.eth_height(&[104u8; 32]).unwrap(), Some(4));
+        assert_eq!(app.block_hashes.eth_first_height().unwrap(), Some(4));
+        assert_eq!(app.block_hashes.first_height().unwrap(), Some(5));
+    }
+
+    #[test]
+    fn missing_state_root_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rocksdb");
+
+        let app = open_app(&path).unwrap();
+        let mut state = app.committed_state().unwrap();
+        // A root which was never written to the state store.
+        state.state_params.state_root = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"dangling"));
+        app.set_committed_state(state).unwrap();
+        drop(app);
+
+        assert!(open_app(&path).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The block gas limit set by governance, applied to proposals.
+//!
+//! Every signed message allocates its gas limit from the block when it's delivered, and the
+//! ones which don't fit are rejected, but they still take up space in the block. The proposer
+//! leaves out the messages which wouldn't fit, and validators reject proposals where the gas
+//! limits of the messages add up to more than the limit.
+//!
+//! Messages which can't be decoded or decompressed don't count, as they are rejected
+//! before being allocated any gas.
+
+use fendermint_vm_message::chain::{ChainMessage, MAX_DECOMPRESSED_SIZE};
+
+fn gas_limit(tx: &[u8]) -> u64 {
+    match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+        Ok(ChainMessage::Signed(msg)) => msg.message.gas_limit,
+        Ok(ChainMessage::Compressed(bz)) => {
+            ChainMessage::decompress(bz.bytes(), MAX_DECOMPRESSED_SIZE)
+                .map(|msg| msg.message.gas_limit)
+                .unwrap_or_default()
+        }
+        _ => 0,
+    }
+}
+
+/// Leave out the messages which don't fit into what's left of the limit; 0 means no limit.
+pub fn take_within_limit(txs: Vec<Vec<u8>>, limit: u64) -> Vec<Vec<u8>> {
+    if limit == 0 {
+        return txs;
+    }
+    let mut available = limit;
+    txs.into_iter()
+        .filter(|tx| match available.checked_sub(gas_limit(tx)) {
+            Some(left) => {
+                available = left;
+                true
+            }
+            None => false,
+        })
+        .collect()
+}
+
+/// Find the first message which doesn't fit into what's left of the limit, if any.
+pub fn find_over_limit(txs: &[Vec<u8>], limit: u64) -> Option<usize> {
+    if limit == 0 {
+        return None;
+    }
+    let mut available = limit;
+    for (index, tx) in txs.iter().enumerate() {
+        match available.checked_sub(gas_limit(tx)) {
+            Some(left) => available = left,
+            None => return Some(index),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_message::chain::ChainMessage;
+    use fendermint_vm_message::ipc::{IpcMessage, ParentFinality};
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{
+        address::Address, crypto::signature::Signature, econ::TokenAmount, message::Message,
+    };
+
+    use super::{find_over_limit, take_within_limit};
+
+    fn signed(gas_limit: u64) -> SignedMessage {
+        let message = Message {
+            version: 0,
+            from: Address::new_id(100),
+            to: Address::new_id(101),
+            sequence: 0,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: RawBytes::default(),
+            gas_limit,
+            gas_fee_cap: TokenAmount::from_atto(100),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        SignedMessage::new_unchecked(message, Signature::new_secp256k1(vec![]))
+    }
+
+    fn tx(msg: ChainMessage) -> Vec<u8> {
+        fvm_ipld_encoding::to_vec(&msg).unwrap()
+    }
+
+    fn ipc() -> Vec<u8> {
+        tx(ChainMessage::Ipc(IpcMessage::TopDownExec(ParentFinality {
+            height: 1,
+            block_hash: vec![0; 32],
+        })))
+    }
+
+    #[test]
+    fn leaves_out_what_does_not_fit() {
+        let txs = vec![
+            tx(ChainMessage::Signed(signed(600))),
+            ipc(),
+            tx(ChainMessage::compress(&signed(300)).unwrap()),
+            tx(ChainMessage::Signed(signed(200))),
+            tx(ChainMessage::Signed(signed(100))),
+        ];
+
+        assert_eq!(find_over_limit(&txs, 0), None);
+        assert_eq!(find_over_limit(&txs, 1200), None);
+        assert_eq!(find_over_limit(&txs, 1000), Some(3));
+
+        let taken = take_within_limit(txs.clone(), 1000);
+        assert_eq!(
+            taken,
+            vec![
+                txs[0].clone(),
+                txs[1].clone(),
+                txs[2].clone(),
+                txs[4].clone()
+            ]
+        );
+        assert_eq!(find_over_limit(&taken, 1000), None);
+        assert_eq!(take_within_limit(txs.clone(), 0), txs);
+    }
+}
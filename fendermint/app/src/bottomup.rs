@@ -0,0 +1,199 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A local queue of the bottom-up messages waiting to be included in a checkpoint.
+//!
+//! The gateway collects the messages sent to the parent in batches, one for each checkpoint,
+//! and they are only read back when the checkpoint is created. The application mirrors the
+//! messages into its own database with every block it commits, ordered by their nonce, so that
+//! the depth of the queue is known at all times, even right after a restart, and new messages
+//! can be turned away from the mempool if the queue grows beyond what the checkpoints can carry.
+//!
+//! A message stays in the queue until the checkpoint including it has been signed by a quorum
+//! of validators, which is when it can be submitted to the parent, so the queue grows if the
+//! validators fall behind with the signatures, not just if the checkpoints are full.
+//!
+//! This is not part of the consensus: the checkpoints are still created from the gateway state,
+//! and the limit only applies when transactions are added to the mempool.
+
+use std::collections::HashSet;
+
+use fendermint_storage::{KVError, KVResult};
+use fendermint_vm_actor_interface::{evm, init::builtin_actor_eth_addr, ipc};
+use fendermint_vm_message::chain::ChainMessage;
+use fvm_ipld_encoding::{BytesDe, RawBytes};
+use fvm_shared::address::Address;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::BlockHeight;
+
+lazy_static! {
+    pub static ref BOTTOM_UP_QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_bottom_up_queue_depth",
+        "Number of bottom-up messages waiting to be included in a checkpoint",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    pub static ref BOTTOM_UP_QUEUE_REJECTED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_bottom_up_queue_rejected_total",
+        "Number of transactions sending bottom-up messages rejected because the queue was full",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// The range of nonces in the queue; the messages are stored under their nonces.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueBounds {
+    /// Nonce of the oldest message in the queue.
+    pub first: u64,
+    /// Nonce after the newest message in the queue.
+    pub next: u64,
+    /// Nonce after the newest message whose checkpoint has been created.
+    #[serde(default)]
+    pub checkpointed: u64,
+    /// The bottom-up nonce of the gateway when the queue was last updated, to tell whether
+    /// there are new messages without reading them.
+    #[serde(default)]
+    pub gateway_nonce: u64,
+}
+
+impl QueueBounds {
+    pub fn depth(&self) -> u64 {
+        self.next.saturating_sub(self.first)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth() == 0
+    }
+
+    /// Make room for a new message, unless it's already in the queue.
+    ///
+    /// Nonces are sequential, so a new message should come right after the last one;
+    /// if the queue is empty, e.g. it has only just been started, it can start anywhere.
+    pub fn push(&mut self, nonce: u64) -> bool {
+        if self.is_empty() {
+            self.first = nonce;
+            self.next = nonce + 1;
+            self.checkpointed = nonce;
+            true
+        } else if nonce < self.next {
+            false
+        } else {
+            if nonce > self.next {
+                tracing::warn!(
+                    nonce,
+                    expected = self.next,
+                    "gap in the nonces of the bottom-up queue"
+                );
+            }
+            self.next = nonce + 1;
+            true
+        }
+    }
+}
+
+/// A message in the queue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueuedMessage {
+    /// Height of the checkpoint the message is going to be included in.
+    pub checkpoint_height: BlockHeight,
+    /// The ABI encoded `CrossMsg`, the way the gateway returns it.
+    pub message: Vec<u8>,
+}
+
+/// What the gateway has to say about the bottom-up messages after a block.
+#[derive(Debug, Clone, Default)]
+pub struct GatewayView {
+    /// The bottom-up nonce of the gateway.
+    pub gateway_nonce: u64,
+    /// Height of the checkpoint which will include the messages sent in the block.
+    pub checkpoint_height: BlockHeight,
+    /// The ABI encoded messages of that checkpoint by their nonces, if the nonce of the gateway moved.
+    pub msgs: Vec<(u64, Vec<u8>)>,
+    /// Heights of the checkpoints waiting for signatures, if there were messages in checkpoints
+    /// which have been created; otherwise none of them is known to have a quorum.
+    pub incomplete: Option<HashSet<BlockHeight>>,
+}
+
+/// Height of the checkpoint which will include the messages sent at a block height.
+///
+/// Checkpoints are created at multiples of the period, and they include the messages
+/// sent in the blocks before them.
+pub fn checkpoint_height(block_height: BlockHeight, period: u64) -> BlockHeight {
+    (block_height / period + 1) * period
+}
+
+/// Check whether a transaction calls the gateway to send a message to the parent.
+pub fn is_bottom_up_tx(tx: &[u8]) -> bool {
+    let msg = match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+        Ok(ChainMessage::Signed(msg)) => msg.message,
+        _ => return false,
+    };
+    if !is_gateway(&msg.to) || msg.method_num != evm::Method::InvokeContract as u64 {
+        return false;
+    }
+    match msg.params.deserialize::<BytesDe>() {
+        Ok(BytesDe(calldata)) => ipc::gateway::is_bottom_up_call(&calldata),
+        Err(_) => false,
+    }
+}
+
+/// Check whether the address is the gateway, by its ID or its delegated address.
+fn is_gateway(addr: &Address) -> bool {
+    *addr == ipc::GATEWAY_ACTOR_ADDR
+        || *addr == Address::from(builtin_actor_eth_addr(ipc::GATEWAY_ACTOR_ID))
+}
+
+pub fn encode_entry<T: Serialize>(value: &T) -> KVResult<RawBytes> {
+    let bz = fvm_ipld_encoding::to_vec(value).map_err(|e| KVError::Codec(Box::new(e)))?;
+    Ok(RawBytes::new(bz))
+}
+
+pub fn decode_entry<T: DeserializeOwned>(bz: RawBytes) -> KVResult<T> {
+    fvm_ipld_encoding::from_slice(&bz).map_err(|e| KVError::Codec(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checkpoint_height, QueueBounds};
+
+    #[test]
+    fn checkpoint_heights() {
+        assert_eq!(checkpoint_height(0, 10), 10);
+        assert_eq!(checkpoint_height(9, 10), 10);
+        assert_eq!(checkpoint_height(10, 10), 20);
+        assert_eq!(checkpoint_height(11, 10), 20);
+    }
+
+    #[test]
+    fn push_nonces() {
+        let mut bounds = QueueBounds::default();
+        assert!(bounds.is_empty());
+
+        assert!(bounds.push(5));
+        assert!(bounds.push(6));
+        assert!(!bounds.push(5));
+        assert_eq!((bounds.first, bounds.next), (5, 7));
+        assert_eq!(bounds.depth(), 2);
+
+        // Gaps are tolerated.
+        assert!(bounds.push(9));
+        assert_eq!(bounds.depth(), 5);
+
+        // Once emptied, it can start again anywhere.
+        bounds.first = bounds.next;
+        assert!(bounds.push(3));
+        assert_eq!(
+            bounds,
+            QueueBounds {
+                first: 3,
+                next: 4,
+                checkpointed: 3,
+                gateway_nonce: 0
+            }
+        );
+    }
+}
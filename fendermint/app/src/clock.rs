@@ -0,0 +1,170 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Check the block times chosen by proposers.
+//!
+//! Contracts can depend on the block timestamp, so a proposer with a skewed clock,
+//! or one manipulating it on purpose, could affect them. Validators reject proposals
+//! with a time too far after the previous block, if the genesis set a limit; this has
+//! to be deterministic, so the local clock is only compared with in the [TimeMonitor]
+//! to expose the drift as a metric.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fendermint_vm_core::Timestamp;
+use fendermint_vm_topdown::SubnetLabel;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+
+lazy_static! {
+    static ref BLOCK_TIME_DRIFT: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_block_time_drift_millis",
+        "Difference between the time of the latest block and the local clock; positive if the block is ahead",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref PROPOSALS_REJECTED_TIME: IntCounterVec = register_int_counter_vec!(
+        "fendermint_proposals_rejected_time_total",
+        "Number of proposals rejected because their time was before or too far after the previous block",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Source of the current time, so that it can be replaced in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The clock of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Compares block times with the local clock and the previous block.
+#[derive(Clone)]
+pub struct TimeMonitor {
+    clock: Arc<dyn Clock>,
+    /// Label of the block time drift and rejected proposal metrics.
+    subnet_id: SubnetLabel,
+}
+
+impl TimeMonitor {
+    pub fn new(clock: Arc<dyn Clock>, subnet_id: String) -> Self {
+        Self { clock, subnet_id }
+    }
+
+    /// Difference between the block time and the local clock, in milliseconds,
+    /// positive if the block is ahead; also recorded as a metric.
+    pub fn observe(&self, time: tendermint::Time) -> i64 {
+        let now = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i128)
+            .unwrap_or_default();
+
+        let time = time.unix_timestamp_nanos() / 1_000_000;
+        let drift = (time - now).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+
+        BLOCK_TIME_DRIFT
+            .with_label_values(&[&self.subnet_id])
+            .set(drift);
+
+        drift
+    }
+
+    /// Check whether the time of a proposed block is within the limit after the previous block.
+    ///
+    /// Only the block times and the limit in the state are used, so every validator comes to
+    /// the same conclusion. Block times are only kept with a precision of seconds, so consecutive
+    /// blocks can have the same one.
+    pub fn check_block_time(
+        &self,
+        height: u64,
+        previous: Timestamp,
+        time: tendermint::Time,
+        max_interval: Option<u64>,
+    ) -> bool {
+        let Some(max_interval) = max_interval else {
+            return true;
+        };
+
+        let secs = time.unix_timestamp();
+
+        if secs >= previous.0 as i64 && secs - previous.0 as i64 <= max_interval as i64 {
+            return true;
+        }
+
+        tracing::warn!(
+            height,
+            time = time.to_string(),
+            previous = previous.0,
+            max_interval,
+            "rejecting proposal with a time before or too far after the previous block"
+        );
+
+        PROPOSALS_REJECTED_TIME
+            .with_label_values(&[&self.subnet_id])
+            .inc();
+
+        false
+    }
+}
+
+impl Default for TimeMonitor {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock), String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use fendermint_vm_core::Timestamp;
+
+    use super::{Clock, TimeMonitor};
+
+    struct FixedClock(SystemTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> SystemTime {
+            self.0
+        }
+    }
+
+    fn block_time(secs: i64) -> tendermint::Time {
+        tendermint::Time::from_unix_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn observe_drift() {
+        let clock = Arc::new(FixedClock(UNIX_EPOCH + Duration::from_secs(1000)));
+
+        let monitor = TimeMonitor::new(clock, String::new());
+
+        assert_eq!(monitor.observe(block_time(1005)), 5000);
+        assert_eq!(monitor.observe(block_time(990)), -10000);
+    }
+
+    #[test]
+    fn check_time_after_previous_block() {
+        let previous = Timestamp(1000);
+        let monitor = TimeMonitor::default();
+
+        assert!(monitor.check_block_time(1, previous, block_time(1000), Some(10)));
+        assert!(monitor.check_block_time(1, previous, block_time(1010), Some(10)));
+        assert!(!monitor.check_block_time(1, previous, block_time(1011), Some(10)));
+        assert!(!monitor.check_block_time(1, previous, block_time(999), Some(10)));
+
+        assert!(monitor.check_block_time(1, previous, block_time(0), None));
+    }
+}
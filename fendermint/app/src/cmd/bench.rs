@@ -0,0 +1,333 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use fendermint_app::AppStore;
+use fendermint_rocksdb::{blockstore::NamespaceBlockstore, RocksDb, RocksDbConfig};
+use fendermint_storage::{KVWritable, KVWrite};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::cmd::{self, Namespaces};
+use crate::options::bench::{BenchArgs, BenchCommands};
+use crate::settings::Settings;
+
+/// Size of the state of an account written by each message, roughly that of an EVM account.
+const ACCOUNT_STATE_SIZE: usize = 96;
+
+cmd! {
+  BenchArgs(self, settings) {
+    match self.command.clone() {
+      BenchCommands::Storage { path, blocks, msgs_per_block, accounts, storage_writes, value_size, seed, keep } => {
+        let path = path.unwrap_or_else(|| settings.data_dir().join("rocksdb-bench"));
+        let workload = StorageWorkload { blocks, msgs_per_block, accounts, storage_writes, value_size, seed };
+        bench_storage(&settings, &path, &workload, keep)
+      }
+    }
+  }
+}
+
+/// Parameters of the blocks written by the storage benchmark.
+#[derive(Debug, Clone)]
+struct StorageWorkload {
+    blocks: u64,
+    msgs_per_block: u64,
+    accounts: u64,
+    storage_writes: u64,
+    value_size: usize,
+    seed: u64,
+}
+
+/// Measurements collected while writing the blocks.
+#[derive(Debug, Default)]
+struct StorageStats {
+    /// Looking up the current state of the sender of a message.
+    reads: Vec<Duration>,
+    /// Writing the state of a block to the state store.
+    flushes: Vec<Duration>,
+    /// Committing the application state after a block.
+    commits: Vec<Duration>,
+    /// Reading, flushing and committing a whole block.
+    blocks: Vec<Duration>,
+    /// Size of the keys and values written by the workload.
+    logical_bytes: u64,
+}
+
+/// Run the workload on a fresh or kept database, print the report, then remove the database unless it's to be kept.
+fn bench_storage(
+    settings: &Settings,
+    path: &Path,
+    workload: &StorageWorkload,
+    keep: bool,
+) -> anyhow::Result<()> {
+    if path == settings.data_dir().join("rocksdb") {
+        bail!("the benchmark cannot be run on the database of the node");
+    }
+
+    tracing::info!(
+        path = path.to_string_lossy().into_owned(),
+        ?workload,
+        "running storage benchmark"
+    );
+
+    // Same as the node, but with statistics to estimate the write amplification.
+    let config = RocksDbConfig {
+        enable_statistics: true,
+        ..Default::default()
+    };
+
+    let ns = Namespaces::default();
+    let db = RocksDb::open_cf(path, &config, ns.values().iter())
+        .context("error opening benchmark DB")?;
+    let state_store = NamespaceBlockstore::new(db.clone(), ns.state_store.clone())
+        .context("error creating state DB")?;
+
+    let started = Instant::now();
+    let stats = run_workload(&db, &state_store, &ns, workload)?;
+    db.flush()?;
+    let elapsed = started.elapsed();
+
+    print_report(
+        workload,
+        &stats,
+        elapsed,
+        db.get_statistics().as_deref(),
+        path,
+    );
+
+    drop(state_store);
+    drop(db);
+
+    if !keep {
+        std::fs::remove_dir_all(path).context("failed to remove the benchmark DB")?;
+    }
+
+    Ok(())
+}
+
+/// Write the blocks of the workload, the way the application would after executing them:
+/// the new actor states go to the state store in one batch, then the application state is
+/// committed in a transaction.
+fn run_workload(
+    db: &RocksDb,
+    state_store: &NamespaceBlockstore,
+    ns: &Namespaces,
+    workload: &StorageWorkload,
+) -> anyhow::Result<StorageStats> {
+    let mut rng = ChaCha8Rng::seed_from_u64(workload.seed);
+    let mut accounts: Vec<Option<Cid>> = vec![None; workload.accounts as usize];
+    let mut stats = StorageStats::default();
+
+    let block_capacity = (workload.msgs_per_block * (1 + workload.storage_writes)) as usize;
+
+    for height in 1..=workload.blocks {
+        let mut batch = Vec::with_capacity(block_capacity);
+        let mut read_time = Duration::ZERO;
+
+        for _ in 0..workload.msgs_per_block {
+            let idx = (rng.next_u64() % workload.accounts) as usize;
+
+            if let Some(cid) = accounts[idx] {
+                let t = Instant::now();
+                state_store
+                    .get(&cid)?
+                    .context("account state missing from the benchmark DB")?;
+                let d = t.elapsed();
+                read_time += d;
+                stats.reads.push(d);
+            }
+
+            let (cid, account) = random_block(&mut rng, ACCOUNT_STATE_SIZE);
+            accounts[idx] = Some(cid);
+            batch.push((cid, account));
+
+            for _ in 0..workload.storage_writes {
+                batch.push(random_block(&mut rng, workload.value_size));
+            }
+        }
+
+        let (state_root, root) = random_block(&mut rng, ACCOUNT_STATE_SIZE);
+        batch.push((state_root, root));
+
+        stats.logical_bytes += batch
+            .iter()
+            .map(|(k, v)| (k.encoded_len() + v.len()) as u64)
+            .sum::<u64>();
+
+        let t = Instant::now();
+        state_store.put_many_keyed(batch)?;
+        let flush_time = t.elapsed();
+
+        let t = Instant::now();
+        KVWritable::<AppStore>::with_write(db, |tx| {
+            tx.put(&ns.app, &"state", &(height, state_root))?;
+            tx.put(&ns.state_hist, &height, &state_root)?;
+            Ok(())
+        })?;
+        let commit_time = t.elapsed();
+
+        stats.flushes.push(flush_time);
+        stats.commits.push(commit_time);
+        stats.blocks.push(read_time + flush_time + commit_time);
+
+        if height % 100 == 0 {
+            tracing::info!(height, "written benchmark blocks");
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Random content with its CID.
+fn random_block(rng: &mut ChaCha8Rng, size: usize) -> (Cid, Vec<u8>) {
+    let mut data = vec![0u8; size];
+    rng.fill_bytes(&mut data);
+    let cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&data));
+    (cid, data)
+}
+
+fn print_report(
+    workload: &StorageWorkload,
+    stats: &StorageStats,
+    elapsed: Duration,
+    db_stats: Option<&str>,
+    path: &Path,
+) {
+    let msgs = workload.blocks * workload.msgs_per_block;
+    let busy = stats.blocks.iter().sum::<Duration>().as_secs_f64();
+
+    println!(
+        "blocks: {}, messages: {msgs}, elapsed: {:.2}s",
+        workload.blocks,
+        elapsed.as_secs_f64()
+    );
+    if busy > 0.0 {
+        println!(
+            "throughput: {:.0} msgs/s, {:.2} MiB/s",
+            msgs as f64 / busy,
+            mib(stats.logical_bytes) / busy
+        );
+    }
+    print_latencies("block", &stats.blocks);
+    print_latencies("state flush", &stats.flushes);
+    print_latencies("app commit", &stats.commits);
+    print_latencies("account read", &stats.reads);
+
+    println!("logical bytes written: {:.2} MiB", mib(stats.logical_bytes));
+
+    let stat = |name| db_stats.and_then(|s| parse_statistic(s, name));
+    if let (Some(wal), Some(flush), Some(compact)) = (
+        stat("rocksdb.wal.bytes"),
+        stat("rocksdb.flush.write.bytes"),
+        stat("rocksdb.compact.write.bytes"),
+    ) {
+        let physical = wal + flush + compact;
+        println!(
+            "physical bytes written: {:.2} MiB (WAL {:.2}, flush {:.2}, compaction {:.2})",
+            mib(physical),
+            mib(wal),
+            mib(flush),
+            mib(compact)
+        );
+        if stats.logical_bytes > 0 {
+            println!(
+                "write amplification: {:.2}",
+                physical as f64 / stats.logical_bytes as f64
+            );
+        }
+    }
+
+    match dir_size(path) {
+        Ok(size) => println!("size on disk: {:.2} MiB", mib(size)),
+        Err(e) => tracing::warn!(error = e.to_string(), "failed to get the size of the DB"),
+    }
+}
+
+fn print_latencies(name: &str, latencies: &[Duration]) {
+    if latencies.is_empty() {
+        return;
+    }
+    let mut sorted = latencies.to_vec();
+    sorted.sort();
+    let ms = |p| percentile(&sorted, p).as_secs_f64() * 1000.0;
+    println!(
+        "{name} latency (ms): p50 {:.3}, p90 {:.3}, p99 {:.3}, max {:.3}",
+        ms(50),
+        ms(90),
+        ms(99),
+        ms(100)
+    );
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+/// Find a ticker like `rocksdb.wal.bytes COUNT : 1234` in the RocksDB statistics.
+fn parse_statistic(stats: &str, name: &str) -> Option<u64> {
+    stats.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix(name)?;
+        let count = rest
+            .trim()
+            .strip_prefix("COUNT")?
+            .trim()
+            .strip_prefix(':')?;
+        count.trim().parse().ok()
+    })
+}
+
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(size)
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_statistic, percentile};
+
+    #[test]
+    fn percentiles() {
+        let sorted = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&sorted, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&sorted, 99), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 100), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn statistics() {
+        let stats = "rocksdb.block.cache.miss COUNT : 10\nrocksdb.wal.bytes COUNT : 1234\nrocksdb.wal.synced COUNT : 3\n";
+        assert_eq!(parse_statistic(stats, "rocksdb.wal.bytes"), Some(1234));
+        assert_eq!(parse_statistic(stats, "rocksdb.wal"), None);
+        assert_eq!(parse_statistic(stats, "rocksdb.flush.write.bytes"), None);
+    }
+}
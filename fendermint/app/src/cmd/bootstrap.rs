@@ -0,0 +1,33 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::anyhow;
+use fendermint_vm_snapshot::{mirror_snapshot, SnapshotSource};
+
+use crate::cmd;
+use crate::options::bootstrap::BootstrapArgs;
+
+cmd! {
+  BootstrapArgs(self, settings) {
+    let source = match (&self.from_ipfs, &self.from_url) {
+        (Some(cid), _) => SnapshotSource::Ipfs { gateway: self.ipfs_gateway.clone(), cid: cid.clone() },
+        (None, Some(url)) => SnapshotSource::Url(url.clone()),
+        (None, None) => return Err(anyhow!("either --from-ipfs or --from-url is required")),
+    };
+
+    let item = mirror_snapshot(&source, &settings.snapshots_dir()).await?;
+
+    tracing::info!(
+        snapshot = item.snapshot_dir.to_string_lossy().to_string(),
+        block_height = item.manifest.block_height,
+        chunks_count = item.manifest.chunks,
+        "downloaded snapshot"
+    );
+
+    if !settings.snapshots.enabled {
+        tracing::warn!("snapshots are disabled; enable them to offer the snapshot to peers");
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,223 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use toml_edit::{value, Document, Item};
+
+use crate::cmd;
+use crate::options::config::{ConfigArgs, ConfigCommands, ThroughputProfile};
+use crate::settings::Settings;
+
+/// Number of blocks worth of transactions the mempool should be able to hold.
+const MEMPOOL_BLOCKS: u64 = 10;
+/// Multiple of the transaction bandwidth to allow for gossip, block parts and votes.
+const GOSSIP_FACTOR: u64 = 4;
+/// CometBFT defaults, which the tuning never goes below.
+const MIN_MEMPOOL_SIZE: u64 = 5000;
+const MIN_CACHE_SIZE: u64 = 10000;
+const MIN_RATE: u64 = 5120000;
+/// The Ethereum API subscribes to events through the WebSocket endpoint for each filter.
+const MIN_SUBSCRIPTIONS_PER_CLIENT: i64 = 1000;
+const MIN_SUBSCRIPTION_CLIENTS: i64 = 10;
+
+cmd! {
+  ConfigArgs(self, settings) {
+    match self.command.clone() {
+      ConfigCommands::GenCometbft { input_file, output_file, profile, target_tps, avg_tx_bytes, max_tx_bytes } => {
+        let input = std::fs::read_to_string(&input_file)
+            .with_context(|| format!("failed to read {}", input_file.to_string_lossy()))?;
+
+        let mut doc = input.parse::<Document>().context("failed to parse CometBFT config")?;
+
+        let mut tuning = Tuning::from(profile);
+        tuning.avg_tx_bytes = avg_tx_bytes;
+        tuning.max_tx_bytes = max_tx_bytes;
+        if let Some(tps) = target_tps {
+            tuning.target_tps = tps;
+        }
+
+        gen_cometbft(&mut doc, &settings, &tuning)?;
+
+        let output_file = output_file.unwrap_or(input_file);
+        std::fs::write(&output_file, doc.to_string())
+            .with_context(|| format!("failed to write {}", output_file.to_string_lossy()))?;
+
+        tracing::info!(
+            path = output_file.to_string_lossy().into_owned(),
+            target_tps = tuning.target_tps,
+            timeout_commit = ?tuning.timeout_commit,
+            min_block_max_bytes = tuning.block_bytes(),
+            "written CometBFT config; the genesis has to allow blocks of at least `min_block_max_bytes`"
+        );
+
+        Ok(())
+      }
+    }
+  }
+}
+
+/// The load to tune CometBFT for.
+#[derive(Debug, Clone)]
+struct Tuning {
+    target_tps: u64,
+    timeout_commit: Duration,
+    avg_tx_bytes: u64,
+    max_tx_bytes: u64,
+}
+
+impl From<ThroughputProfile> for Tuning {
+    fn from(value: ThroughputProfile) -> Self {
+        let (target_tps, timeout_commit) = match value {
+            ThroughputProfile::Low => (50, Duration::from_secs(3)),
+            ThroughputProfile::Standard => (500, Duration::from_secs(1)),
+            ThroughputProfile::High => (3000, Duration::from_millis(500)),
+        };
+        Self {
+            target_tps,
+            timeout_commit,
+            avg_tx_bytes: 512,
+            max_tx_bytes: 1048576,
+        }
+    }
+}
+
+impl Tuning {
+    /// Expected number of transactions in a block.
+    ///
+    /// The block time is at least `timeout_commit`, so this is a lower estimate.
+    fn txs_per_block(&self) -> u64 {
+        let millis = self.timeout_commit.as_millis() as u64;
+        ((self.target_tps * millis + 999) / 1000).max(1)
+    }
+
+    /// Size of a block carrying the expected number of transactions.
+    fn block_bytes(&self) -> u64 {
+        (self.txs_per_block() * self.avg_tx_bytes).max(self.max_tx_bytes)
+    }
+
+    /// Interval for the gossip to keep up with the block time.
+    fn gossip_interval(&self) -> Duration {
+        (self.timeout_commit / 10).min(Duration::from_millis(100))
+    }
+}
+
+/// Change the keys of the CometBFT config which affect the connection to Fendermint and the throughput.
+fn gen_cometbft(doc: &mut Document, settings: &Settings, tuning: &Tuning) -> anyhow::Result<()> {
+    let txs_per_block = tuning.txs_per_block();
+    let mempool_size = (txs_per_block * MEMPOOL_BLOCKS).max(MIN_MEMPOOL_SIZE);
+    let cache_size = (txs_per_block * settings.abci.tx_dedup_blocks)
+        .max(mempool_size)
+        .max(MIN_CACHE_SIZE);
+    let max_txs_bytes =
+        (mempool_size * tuning.avg_tx_bytes).max(tuning.max_tx_bytes * MEMPOOL_BLOCKS);
+    let rate = (tuning.target_tps * tuning.avg_tx_bytes * GOSSIP_FACTOR).max(MIN_RATE);
+
+    // Keep the hosts in the input, which can be container names, and only make the ports agree.
+    let abci = &settings.abci.listen;
+    let proxy_app = match abci.host.as_str() {
+        "0.0.0.0" | "::" => with_port(get_str(doc, "", "proxy_app")?, abci.port)?,
+        host => format!("tcp://{host}:{}", abci.port),
+    };
+    let rpc_port = settings
+        .tendermint_rpc_url()?
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("the Tendermint RPC URL has no port"))?;
+    let rpc_laddr = with_port(get_str(doc, "rpc", "laddr")?, rpc_port as u32)?;
+
+    doc["proxy_app"] = value(proxy_app);
+    doc["rpc"]["laddr"] = value(rpc_laddr);
+    set_at_least(
+        doc,
+        "rpc",
+        "max_subscription_clients",
+        MIN_SUBSCRIPTION_CLIENTS,
+    );
+    set_at_least(
+        doc,
+        "rpc",
+        "max_subscriptions_per_client",
+        MIN_SUBSCRIPTIONS_PER_CLIENT,
+    );
+
+    doc["mempool"]["size"] = value(mempool_size as i64);
+    doc["mempool"]["cache_size"] = value(cache_size as i64);
+    doc["mempool"]["max_txs_bytes"] = value(max_txs_bytes as i64);
+    doc["mempool"]["max_tx_bytes"] = value(tuning.max_tx_bytes as i64);
+
+    doc["consensus"]["timeout_commit"] = value(fmt_duration(tuning.timeout_commit));
+    doc["consensus"]["peer_gossip_sleep_duration"] = value(fmt_duration(tuning.gossip_interval()));
+
+    doc["p2p"]["send_rate"] = value(rate as i64);
+    doc["p2p"]["recv_rate"] = value(rate as i64);
+    doc["p2p"]["flush_throttle_timeout"] = value(fmt_duration(tuning.gossip_interval()));
+
+    Ok(())
+}
+
+/// Look up a string in the root or in a table of the config.
+fn get_str<'a>(doc: &'a Document, table: &str, key: &str) -> anyhow::Result<&'a str> {
+    let item = if table.is_empty() {
+        doc.get(key)
+    } else {
+        doc.get(table).and_then(|t| t.get(key))
+    };
+    item.and_then(Item::as_str)
+        .ok_or_else(|| anyhow!("missing '{key}' in the CometBFT config"))
+}
+
+/// Raise an integer in the config to a minimum.
+fn set_at_least(doc: &mut Document, table: &str, key: &str, min: i64) {
+    let current = doc[table].get(key).and_then(Item::as_integer).unwrap_or(0);
+    if current < min {
+        doc[table][key] = value(min);
+    }
+}
+
+/// Replace the port in an address like `tcp://127.0.0.1:26657`.
+fn with_port(addr: &str, port: u32) -> anyhow::Result<String> {
+    let (host, _) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("'{addr}' has no port"))?;
+    Ok(format!("{host}:{port}"))
+}
+
+/// Format a duration the way Go parses them in the CometBFT config.
+fn fmt_duration(d: Duration) -> String {
+    if d.subsec_millis() == 0 {
+        format!("{}s", d.as_secs())
+    } else {
+        format!("{}ms", d.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::options::config::ThroughputProfile;
+
+    use super::{fmt_duration, with_port, Tuning};
+
+    #[test]
+    fn profile_tuning() {
+        let low = Tuning::from(ThroughputProfile::Low);
+        let high = Tuning::from(ThroughputProfile::High);
+        assert_eq!(low.txs_per_block(), 150);
+        assert_eq!(high.txs_per_block(), 1500);
+        assert_eq!(high.gossip_interval(), Duration::from_millis(50));
+        assert_eq!(low.block_bytes(), low.max_tx_bytes);
+    }
+
+    #[test]
+    fn config_values() {
+        assert_eq!(fmt_duration(Duration::from_secs(1)), "1s");
+        assert_eq!(fmt_duration(Duration::from_millis(500)), "500ms");
+        assert_eq!(
+            with_port("tcp://127.0.0.1:26657", 26658).unwrap(),
+            "tcp://127.0.0.1:26658"
+        );
+        assert!(with_port("tcp", 26658).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use fendermint_app::admin::HaltHeight;
+use fendermint_app::clock::TimeMonitor;
+use fendermint_app::{App, AppConfig, AppStore};
+use fendermint_eth_api::HybridClient;
+use fendermint_rocksdb::{blockstore::SecondaryBlockstore, RocksDbSecondary};
+use fendermint_vm_interpreter::{
+    bytes::ProposalPrepareMode,
+    chain::CheckpointPool,
+    fvm::{AdmissionRules, FvmMessageInterpreter},
+    stack::InterpreterBuilder,
+};
+use fendermint_vm_topdown::Toggle;
+use tendermint_rpc::{Url, WebSocketClientUrl};
+
+use crate::cmd::{self, Namespaces};
+use crate::options::db::{DbArgs, DbCommands};
+use crate::settings::Settings;
+
+cmd! {
+  DbArgs(self, settings) {
+    match self.command.clone() {
+      DbCommands::ServeReadonly { listen, http_url, ws_url, secondary_dir, catch_up_interval, eth } => {
+        let secondary_dir = secondary_dir.unwrap_or_else(|| settings.data_dir().join("rocksdb-secondary"));
+        serve_readonly(settings, listen, http_url, ws_url, secondary_dir, Duration::from_millis(catch_up_interval), eth).await
+      }
+    }
+  }
+}
+
+/// Run the application on a secondary instance of the database, only answering queries.
+async fn serve_readonly(
+    settings: Settings,
+    listen: String,
+    http_url: Url,
+    ws_url: WebSocketClientUrl,
+    secondary_dir: PathBuf,
+    catch_up_interval: Duration,
+    eth: bool,
+) -> anyhow::Result<()> {
+    let tendermint_client = tendermint_rpc::HttpClient::new(http_url.clone())
+        .context("failed to create Tendermint client")?;
+
+    let path = settings.data_dir().join("rocksdb");
+    let config = settings.db.rocksdb.config();
+    tracing::info!(
+        path = path.to_string_lossy().into_owned(),
+        secondary_path = secondary_dir.to_string_lossy().into_owned(),
+        profile = ?settings.db.rocksdb.profile,
+        ?config,
+        "opening database as a secondary instance"
+    );
+    let db = RocksDbSecondary::open(path, secondary_dir, &config).context("error opening DB")?;
+
+    let ns = Namespaces::default();
+    let state_store =
+        SecondaryBlockstore::new(db.clone(), ns.state_store).context("error creating state DB")?;
+
+    // The interpreter is only used for queries, so it doesn't need a validator key.
+    let interpreter = FvmMessageInterpreter::<SecondaryBlockstore, _>::new(
+        tendermint_client,
+        None,
+        settings.contracts_dir(),
+        settings.fvm.gas_overestimation_rate,
+        settings.fvm.gas_search_step,
+        settings.fvm.exec_in_check,
+    )
+    .with_admission(AdmissionRules {
+        cover_value: settings.fvm.admission.cover_value,
+        min_balance: settings.fvm.admission.min_balance.clone(),
+    })
+    .with_checkpoint_archive(settings.checkpoint_archive_dir());
+
+    let interpreter = InterpreterBuilder::new(interpreter)
+        .signed(settings.abci.msg_cache_size)
+        .chain()
+        .bytes(
+            ProposalPrepareMode::AppendOnly,
+            false,
+            settings.abci.msg_cache_size,
+        )
+        .build();
+
+    let app: App<_, _, AppStore, _> = App::new_read_only(
+        AppConfig {
+            app_namespace: ns.app,
+            state_hist_namespace: ns.state_hist,
+            state_hist_size: settings.db.state_hist_size,
+            logs_bloom_namespace: ns.logs_bloom,
+            block_hashes_namespace: ns.block_hashes,
+            eth_block_hashes_namespace: ns.eth_block_hashes,
+            gas_stats_namespace: ns.gas_stats,
+            gas_stats_blocks: settings.abci.gas_stats_blocks,
+            validator_sets_namespace: ns.validator_sets,
+            bottom_up_queue_namespace: ns.bottom_up_queue,
+            topdown_traces_namespace: ns.topdown_traces,
+            bottomup_traces_namespace: ns.bottomup_traces,
+            msg_traces: settings.ipc.msg_traces,
+            builtin_actors_bundle: settings.builtin_actors_bundle(),
+            tx_dedup_blocks: settings.abci.tx_dedup_blocks,
+            tracing_enabled: settings.tracing.otlp.enabled,
+            exporter: None,
+            vector_recorder: None,
+            halt_height: HaltHeight::default(),
+            time_monitor: TimeMonitor::default(),
+            // Nothing is proposed from here, so the checks can't be left to the proposals.
+            defer_check: false,
+            query_budget: cmd::to_query_budget(&settings.abci.query_budget),
+            subnet_id: settings.ipc.subnet_id.to_string(),
+        },
+        db.clone(),
+        state_store,
+        interpreter,
+        CheckpointPool::new(),
+        Arc::new(Toggle::disabled()),
+    )
+    .context("failed to start the application; is the node initialized?")?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(catch_up_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = db.catch_up() {
+                tracing::error!(error = e.to_string(), "failed to catch up with the primary");
+            }
+        }
+    });
+
+    if eth {
+        let endpoints = cmd::eth::to_endpoints(&settings.eth, &settings.home_dir())?;
+        let readonly_url: Url = format!("http://{listen}")
+            .parse()
+            .context("invalid listen address")?;
+
+        let (client, driver) = HybridClient::new(readonly_url, ws_url, Duration::from_secs(5))
+            .context("failed to create HybridClient")?;
+
+        tokio::spawn(async move { driver.run().await });
+
+        let eth_settings = settings.eth.clone();
+        tokio::spawn(async move {
+            if let Err(e) = cmd::eth::run(eth_settings, endpoints, client).await {
+                tracing::error!(error = e.to_string(), "Ethereum API failed");
+            }
+        });
+    }
+
+    fendermint_app::readonly::serve(listen, app, http_url).await
+}
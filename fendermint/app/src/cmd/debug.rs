@@ -0,0 +1,49 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::io::Read;
+
+use anyhow::{anyhow, Context};
+use fendermint_app::schema::{self, SCHEMAS};
+
+use super::from_b64;
+use crate::{
+    cmd,
+    options::debug::{DebugArgs, DebugCommands},
+};
+
+cmd! {
+  DebugArgs(self) {
+    match &self.command {
+      DebugCommands::DecodeState { actor, state } => decode_state(actor, state.as_deref()),
+    }
+  }
+}
+
+/// Print the state of an actor as JSON.
+fn decode_state(actor: &str, state: Option<&str>) -> anyhow::Result<()> {
+    let schema = schema::by_name(actor).ok_or_else(|| {
+        let names = SCHEMAS.iter().map(|s| s.name).collect::<Vec<_>>();
+        anyhow!(
+            "unknown actor: {actor}; expected one of {}",
+            names.join(", ")
+        )
+    })?;
+
+    let state = match state {
+        Some(state) => state.to_owned(),
+        None => {
+            let mut state = String::new();
+            std::io::stdin()
+                .read_to_string(&mut state)
+                .context("failed to read the state from STDIN")?;
+            state
+        }
+    };
+
+    let bz = from_b64(state.trim()).context("failed to decode the state from base64")?;
+    let json = schema.decode(&bz)?;
+
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
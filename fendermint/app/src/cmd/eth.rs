@@ -1,27 +1,29 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::Path;
 use std::time::Duration;
 
-use anyhow::Context;
-use fendermint_eth_api::HybridClient;
+use anyhow::{anyhow, Context};
+use fendermint_eth_api::{AuthOpt, EndpointOpt, HybridClient};
 
 use crate::{
     cmd,
     options::eth::{EthArgs, EthCommands},
-    settings::eth::EthSettings,
+    settings::eth::{AuthOpt as AuthSettings, EthSettings},
 };
 
 cmd! {
-  EthArgs(self, settings: EthSettings) {
+  EthArgs(self, settings) {
     match self.command.clone() {
       EthCommands::Run { ws_url, http_url, connect_retry_delay } => {
+        let endpoints = to_endpoints(&settings.eth, &settings.home_dir())?;
 
         let (client, driver) = HybridClient::new(http_url, ws_url, Duration::from_secs(connect_retry_delay)).context("failed to create HybridClient")?;
 
         let driver_handle = tokio::spawn(async move { driver.run().await });
 
-        let result = run(settings, client).await;
+        let result = run(settings.eth, endpoints, client).await;
 
         // Await the driver's termination to ensure proper connection closure.
         let _ = driver_handle.await;
@@ -31,19 +33,110 @@ cmd! {
   }
 }
 
+/// Resolve the addresses of the endpoints and read the secrets of their authentication.
+pub(crate) fn to_endpoints(
+    settings: &EthSettings,
+    home_dir: &Path,
+) -> anyhow::Result<Vec<EndpointOpt>> {
+    let mut endpoints = Vec::new();
+
+    for ep in settings.all_endpoints() {
+        if endpoints.iter().any(|e: &EndpointOpt| e.name == ep.name) {
+            return Err(anyhow!("duplicate Ethereum API endpoint name: {}", ep.name));
+        }
+        let listen_addr = fendermint_eth_api::to_socket_addr(&ep.listen)
+            .with_context(|| format!("invalid address of the {} Ethereum API endpoint", ep.name))?;
+        let auth = to_auth_opt(&ep.auth, home_dir).with_context(|| {
+            format!(
+                "invalid authentication of the {} Ethereum API endpoint",
+                ep.name
+            )
+        })?;
+
+        endpoints.push(EndpointOpt {
+            name: ep.name,
+            listen_addr,
+            methods: fendermint_eth_api::MethodsOpt {
+                allow: ep.methods.allow,
+                deny: ep.methods.deny,
+            },
+            cors: fendermint_eth_api::CorsOpt {
+                allowed_origins: ep.cors.allowed_origins,
+            },
+            rate_limit: fendermint_eth_api::RateLimitOpt {
+                calls_per_second: ep.rate_limit.calls_per_second,
+                burst: ep.rate_limit.burst,
+            },
+            auth,
+        });
+    }
+
+    Ok(endpoints)
+}
+
+/// Read the secret of the authentication, if it's enabled.
+fn to_auth_opt(auth: &AuthSettings, home_dir: &Path) -> anyhow::Result<AuthOpt> {
+    let secret = if auth.enabled {
+        let path = auth.jwt_secret(home_dir);
+        let hex_secret = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the JWT secret from {path:?}"))?;
+        let hex_secret = hex_secret.trim();
+        let secret = hex::decode(hex_secret.strip_prefix("0x").unwrap_or(hex_secret))
+            .context("the JWT secret is not hex encoded")?;
+        if secret.len() != 32 {
+            return Err(anyhow!("the JWT secret has to be 32 bytes"));
+        }
+        secret
+    } else {
+        Vec::new()
+    };
+    Ok(AuthOpt {
+        enabled: auth.enabled,
+        secret,
+        namespaces: auth.namespaces.clone(),
+        methods: auth.methods.clone(),
+        max_iat_drift: auth.max_iat_drift,
+    })
+}
+
 /// Run the Ethereum API facade.
-async fn run(settings: EthSettings, client: HybridClient) -> anyhow::Result<()> {
+pub(crate) async fn run(
+    settings: EthSettings,
+    endpoints: Vec<EndpointOpt>,
+    client: HybridClient,
+) -> anyhow::Result<()> {
     let gas = fendermint_eth_api::GasOpt {
         min_gas_premium: settings.gas.min_gas_premium,
         num_blocks_max_prio_fee: settings.gas.num_blocks_max_prio_fee,
         max_fee_hist_size: settings.gas.max_fee_hist_size,
     };
+    let sub = fendermint_eth_api::SubscriptionOpt {
+        resubscribe_delay: settings.subscription.resubscribe_delay,
+        max_gap: settings.subscription.max_gap,
+    };
+    let sync = fendermint_eth_api::SyncGuardOpt {
+        enabled: settings.sync_guard.enabled,
+        max_lag: settings.sync_guard.max_lag,
+        check_interval: settings.sync_guard.check_interval,
+    };
+    let limits = fendermint_eth_api::LimitsOpt {
+        max_logs: settings.limits.max_logs,
+        max_block_range: settings.limits.max_block_range,
+        max_page_size: settings.limits.max_page_size,
+    };
+    let compat = fendermint_eth_api::CompatOpt {
+        strict_receipts: settings.compat.strict_receipts,
+    };
     fendermint_eth_api::listen(
-        settings.listen,
+        endpoints,
         client,
         settings.filter_timeout,
         settings.cache_capacity,
         gas,
+        sub,
+        sync,
+        limits,
+        compat,
     )
     .await
 }
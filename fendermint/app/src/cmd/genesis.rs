@@ -1,7 +1,9 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+use fendermint_app::genesisaudit;
+use fendermint_app::genesischeck::ParentGenesisCheck;
 use fendermint_app::APP_VERSION;
 use fendermint_crypto::PublicKey;
 use fvm_shared::address::Address;
@@ -12,8 +14,8 @@ use std::path::PathBuf;
 use fendermint_vm_actor_interface::eam::EthAddress;
 use fendermint_vm_core::{chainid, Timestamp};
 use fendermint_vm_genesis::{
-    ipc, Account, Actor, ActorMeta, Collateral, Genesis, Multisig, SignerAddr, Validator,
-    ValidatorKey,
+    ipc, Account, Actor, ActorMeta, Beacon, Collateral, Genesis, Multisig, Policy, Rewards,
+    SignerAddr, SystemContract, Validator, ValidatorKey,
 };
 
 use crate::cmd;
@@ -30,6 +32,7 @@ cmd! {
         GenesisCommands::AddMultisig(args) => args.exec(genesis_file).await,
         GenesisCommands::AddValidator(args) => args.exec(genesis_file).await,
         GenesisCommands::IntoTendermint(args) => args.exec(genesis_file).await,
+        GenesisCommands::Validate(args) => args.exec(genesis_file).await,
         GenesisCommands::Ipc { command } => command.exec(genesis_file).await,
     }
   }
@@ -45,7 +48,39 @@ cmd! {
       power_scale: self.power_scale,
       validators: Vec::new(),
       accounts: Vec::new(),
-      ipc: None
+      ipc: None,
+      access_control: None,
+      governance: None,
+      scheduled_calls: Vec::new(),
+      tx_compression: self.tx_compression,
+      filecoin_signatures: self.filecoin_signatures,
+      system_contracts: self.system_contracts.iter().map(|c| match c {
+        SystemContractKind::Multicall => SystemContract::Multicall,
+        SystemContractKind::Deployer => SystemContract::Deployer,
+        SystemContractKind::Wfil => SystemContract::Wfil,
+      }).collect(),
+      beacon: self.beacon_epoch_length.map(|epoch_length| Beacon { epoch_length }),
+      rewards: self.reward_epoch_length.map(|epoch_length| Rewards {
+        epoch_length,
+        inflation_per_epoch: self.reward_inflation_per_epoch.clone(),
+        collect_base_fees: self.reward_base_fees,
+      }),
+      policy: match self.policy_module {
+        Some(ref path) => {
+          let bytes = std::fs::read(path)
+            .with_context(|| format!("failed to read the policy module from {path:?}"))?;
+          Some(Policy {
+            module: Policy::module_cid(&bytes),
+            fuel_limit: self.policy_fuel_limit,
+          })
+        }
+        None => None,
+      },
+      exec_digests: self.exec_digests,
+      max_block_interval: self.max_block_interval,
+      eth_block_hash_v1: self.eth_block_hash_v1,
+      canonical_tx_order: self.canonical_tx_order,
+      fast_topdown_finality: self.fast_topdown_finality,
     };
 
     let json = serde_json::to_string_pretty(&genesis)?;
@@ -79,13 +114,21 @@ cmd! {
   }
 }
 
+cmd! {
+  GenesisValidateArgs(self, genesis_file: PathBuf) {
+    validate(&genesis_file, self)
+  }
+}
+
 cmd! {
   GenesisIpcCommands(self, genesis_file: PathBuf) {
     match self {
         GenesisIpcCommands::Gateway(args) =>
             set_ipc_gateway(&genesis_file, args),
         GenesisIpcCommands::FromParent(args) =>
-            new_genesis_from_parent(&genesis_file, args).await
+            new_genesis_from_parent(&genesis_file, args).await,
+        GenesisIpcCommands::CheckParent(args) =>
+            check_parent(&genesis_file, args).await,
     }
   }
 }
@@ -169,7 +212,7 @@ fn add_validator(genesis_file: &PathBuf, args: &GenesisAddValidatorArgs) -> anyh
     })
 }
 
-fn read_genesis(genesis_file: &PathBuf) -> anyhow::Result<Genesis> {
+pub(crate) fn read_genesis(genesis_file: &PathBuf) -> anyhow::Result<Genesis> {
     let json = std::fs::read_to_string(genesis_file).context("failed to read genesis")?;
     let genesis = serde_json::from_str::<Genesis>(&json).context("failed to parse genesis")?;
     Ok(genesis)
@@ -188,7 +231,18 @@ where
 
 fn into_tendermint(genesis_file: &PathBuf, args: &GenesisIntoTendermintArgs) -> anyhow::Result<()> {
     let genesis = read_genesis(genesis_file)?;
-    let genesis_json = serde_json::to_value(&genesis)?;
+    let tmg = to_tendermint_genesis(&genesis, args.block_max_bytes)?;
+    let tmg_json = serde_json::to_string_pretty(&tmg)?;
+    std::fs::write(&args.out, tmg_json)?;
+    Ok(())
+}
+
+/// The CometBFT genesis of the chain, carrying the Fendermint genesis as its app state.
+pub(crate) fn to_tendermint_genesis(
+    genesis: &Genesis,
+    block_max_bytes: u64,
+) -> anyhow::Result<tendermint::Genesis<serde_json::Value>> {
+    let genesis_json = serde_json::to_value(genesis)?;
 
     let chain_id: u64 = chainid::from_str_hashed(&genesis.chain_name)?.into();
     let chain_id = chain_id.to_string();
@@ -202,7 +256,7 @@ fn into_tendermint(genesis_file: &PathBuf, args: &GenesisIntoTendermintArgs) ->
         // Values are based on the default produced by `tendermint init`
         consensus_params: tendermint::consensus::Params {
             block: tendermint::block::Size {
-                max_bytes: args.block_max_bytes,
+                max_bytes: block_max_bytes,
                 max_gas: -1,
                 time_iota_ms: tendermint::block::Size::default_time_iota_ms(),
             },
@@ -225,9 +279,7 @@ fn into_tendermint(genesis_file: &PathBuf, args: &GenesisIntoTendermintArgs) ->
         app_hash: tendermint::AppHash::default(),
         app_state: genesis_json,
     };
-    let tmg_json = serde_json::to_string_pretty(&tmg)?;
-    std::fs::write(&args.out, tmg_json)?;
-    Ok(())
+    Ok(tmg)
 }
 
 fn set_ipc_gateway(genesis_file: &PathBuf, args: &GenesisIpcGatewayArgs) -> anyhow::Result<()> {
@@ -244,10 +296,12 @@ fn set_ipc_gateway(genesis_file: &PathBuf, args: &GenesisIpcGatewayArgs) -> anyh
         let ipc_params = match genesis.ipc {
             Some(mut ipc) => {
                 ipc.gateway = gateway_params;
+                ipc.checkpoint_retention = args.checkpoint_retention;
                 ipc
             }
             None => ipc::IpcParams {
                 gateway: gateway_params,
+                checkpoint_retention: args.checkpoint_retention,
             },
         };
 
@@ -290,6 +344,7 @@ async fn new_genesis_from_parent(
             majority_percentage: genesis_info.majority_percentage,
             active_validators_limit: genesis_info.active_validators_limit,
         },
+        checkpoint_retention: 0,
     };
     let mut genesis = Genesis {
         // We set the genesis epoch as the genesis timestamp so it can be
@@ -304,6 +359,20 @@ async fn new_genesis_from_parent(
         validators: Vec::new(),
         accounts: Vec::new(),
         ipc: Some(ipc_params),
+        access_control: None,
+        governance: None,
+        scheduled_calls: Vec::new(),
+        tx_compression: false,
+        filecoin_signatures: false,
+        system_contracts: Vec::new(),
+        beacon: None,
+        policy: None,
+        rewards: None,
+        exec_digests: false,
+        max_block_interval: None,
+        eth_block_hash_v1: false,
+        canonical_tx_order: false,
+        fast_topdown_finality: false,
     };
 
     for v in genesis_info.validators {
@@ -330,3 +399,49 @@ async fn new_genesis_from_parent(
 
     Ok(())
 }
+
+fn validate(genesis_file: &PathBuf, args: &GenesisValidateArgs) -> anyhow::Result<()> {
+    let genesis = read_genesis(genesis_file)?;
+    let report = genesisaudit::validate(&genesis);
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if !report.valid {
+        bail!("the genesis has {} error(s)", report.errors);
+    }
+    if args.strict && report.warnings > 0 {
+        bail!("the genesis has {} warning(s)", report.warnings);
+    }
+
+    Ok(())
+}
+
+async fn check_parent(genesis_file: &PathBuf, args: &GenesisCheckParentArgs) -> anyhow::Result<()> {
+    let genesis = read_genesis(genesis_file)?;
+
+    let subnet_id = match (&args.subnet_id, &genesis.ipc) {
+        (Some(subnet_id), _) => subnet_id.clone(),
+        (None, Some(ipc)) => ipc.gateway.subnet_id.clone(),
+        (None, None) => {
+            return Err(anyhow!(
+                "the genesis has no IPC configuration; specify the expected subnet ID"
+            ))
+        }
+    };
+
+    let check = ParentGenesisCheck {
+        subnet_id,
+        parent_endpoint: args.parent_endpoint.to_string(),
+        parent_gateway: args.parent_gateway,
+        parent_registry: args.parent_registry,
+    };
+
+    check.verify(&genesis).await?;
+
+    println!(
+        "the genesis matches subnet {} on the parent",
+        check.subnet_id
+    );
+
+    Ok(())
+}
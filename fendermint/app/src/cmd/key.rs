@@ -7,16 +7,26 @@ use fendermint_vm_actor_interface::eam::EthAddress;
 use fvm_shared::address::Address;
 use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
 use serde_json::json;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tendermint_config::NodeKey;
 
-use super::{from_b64, to_b64};
+use super::{
+    from_b64,
+    keystore::{KeyKind, Keystore},
+    to_b64,
+};
 use crate::{
     cmd,
-    options::key::{
-        AddPeer, KeyAddressArgs, KeyArgs, KeyCommands, KeyFromEthArgs, KeyGenArgs, KeyIntoEthArgs,
-        KeyIntoTendermintArgs,
+    options::{
+        genesis::AccountKind,
+        key::{
+            AddPeer, KeyAddressArgs, KeyArgs, KeyCommands, KeyExportArgs, KeyFromEthArgs,
+            KeyGenArgs, KeyGenNodeKeyArgs, KeyImportArgs, KeyIntoEthArgs, KeyIntoTendermintArgs,
+            KeyListArgs, KeyUseArgs, KeystoreArgs,
+        },
     },
+    settings::expand_tilde,
 };
 
 cmd! {
@@ -24,10 +34,15 @@ cmd! {
         match &self.command {
             KeyCommands::Gen(args) => args.exec(()).await,
             KeyCommands::IntoTendermint(args) => args.exec(()).await,
+            KeyCommands::GenNodeKey(args) => args.exec(()).await,
             KeyCommands::AddPeer(args) => args.exec(()).await,
             KeyCommands::Address(args) => args.exec(()).await,
             KeyCommands::FromEth(args) => args.exec(()).await,
             KeyCommands::IntoEth(args) => args.exec(()).await,
+            KeyCommands::List(args) => args.exec(()).await,
+            KeyCommands::Import(args) => args.exec(()).await,
+            KeyCommands::Export(args) => args.exec(()).await,
+            KeyCommands::Use(args) => args.exec(()).await,
         }
     }
 }
@@ -66,6 +81,22 @@ cmd! {
     export(&self.out_dir, &self.name, "sk", &secret_to_b64(&sk))?;
     export(&self.out_dir, &self.name, "pk", &public_to_b64(&pk))?;
 
+    if let Some(ref dir) = self.cometbft_config_dir {
+      let validator_key_path = dir.join("priv_validator_key.json");
+      let node_key_path = dir.join("node_key.json");
+      // Check both up front, so we don't end up with only one of them replaced.
+      if !self.force {
+        for path in [&validator_key_path, &node_key_path] {
+          if path.exists() {
+            return Err(anyhow!("{path:?} already exists; use --force to overwrite it"));
+          }
+        }
+      }
+      write_priv_validator_key(&sk, &validator_key_path, self.force)?;
+      let node_id = write_node_key(&node_key_path, self.force)?;
+      println!("{node_id}");
+    }
+
     Ok(())
   }
 }
@@ -73,28 +104,14 @@ cmd! {
 cmd! {
   KeyIntoTendermintArgs(self) {
     let sk = read_secret_key(&self.secret_key)?;
-    let pk = sk.public_key();
-    let vk = tendermint::crypto::default::ecdsa_secp256k1::VerifyingKey::from_sec1_bytes(&pk.serialize())
-      .map_err(|e| anyhow!("failed to convert public key: {e}"))?;
-    let pub_key = tendermint::PublicKey::Secp256k1(vk);
-    let address = tendermint::account::Id::from(pub_key);
-
-    // tendermint-rs doesn't seem to handle Secp256k1 private keys;
-    // if it did, we could use tendermint_config::PrivateValidatorKey
-    // to encode the data structure. Tendermint should be okay with it
-    // though, as long as we match the expected keys in the JSON.
-    let priv_validator_key = json! ({
-        "address": address,
-        "pub_key": pub_key,
-        "priv_key": {
-            "type": "tendermint/PrivKeySecp256k1",
-            "value": secret_to_b64(&sk)
-        }
-    });
-    let json = serde_json::to_string_pretty(&priv_validator_key)?;
-
-    std::fs::write(&self.out, json)?;
+    write_priv_validator_key(&sk, &self.out, true)
+  }
+}
 
+cmd! {
+  KeyGenNodeKeyArgs(self) {
+    let node_id = write_node_key(&self.out, self.force)?;
+    println!("{node_id}");
     Ok(())
   }
 }
@@ -126,21 +143,85 @@ cmd! {
     }
 }
 
-fn secret_to_b64(sk: &SecretKey) -> String {
+cmd! {
+    KeyListArgs(self) {
+        let ks = open_keystore(&self.keystore)?;
+        let default = ks.default_account()?;
+        let mut accounts = Vec::new();
+        for kf in ks.list()? {
+            accounts.push(json!({
+                "name": &kf.name,
+                "kind": kf.kind,
+                "address": kf.address()?.to_string(),
+                "encrypted": kf.is_encrypted(),
+                "default": default.as_ref() == Some(&kf.name),
+            }));
+        }
+        println!("{}", serde_json::to_string_pretty(&accounts)?);
+        Ok(())
+    }
+}
+
+cmd! {
+    KeyImportArgs(self) {
+        let sk = if self.hex {
+            read_secret_key_hex(&self.secret_key)?
+        } else {
+            read_secret_key(&self.secret_key)?
+        };
+        let kind = match self.account_kind {
+            AccountKind::Regular => KeyKind::Regular,
+            AccountKind::Ethereum => KeyKind::Ethereum,
+        };
+        let ks = open_keystore(&self.keystore)?;
+        let kf = ks.import(&self.name, &sk, kind, &self.keystore.keystore_password)?;
+        if !kf.is_encrypted() {
+            tracing::warn!(name = self.name, "no password given; the secret key is stored in plaintext");
+        }
+        println!("{}", kf.address()?);
+        Ok(())
+    }
+}
+
+cmd! {
+    KeyExportArgs(self) {
+        let ks = open_keystore(&self.keystore)?;
+        let kf = ks.get(&self.name)?;
+        let sk = kf.secret_key(&self.keystore.keystore_password)?;
+
+        export(&self.out_dir, &self.name, "sk", &secret_to_b64(&sk))?;
+        export(&self.out_dir, &self.name, "pk", &public_to_b64(&sk.public_key()))?;
+
+        Ok(())
+    }
+}
+
+cmd! {
+    KeyUseArgs(self) {
+        let ks = open_keystore(&self.keystore)?;
+        ks.set_default(&self.name)
+    }
+}
+
+pub fn open_keystore(args: &KeystoreArgs) -> anyhow::Result<Keystore> {
+    Keystore::open(&expand_tilde(&args.keystore_dir))
+}
+
+pub fn secret_to_b64(sk: &SecretKey) -> String {
     to_b64(sk.serialize().as_ref())
 }
 
-fn public_to_b64(pk: &PublicKey) -> String {
+pub fn public_to_b64(pk: &PublicKey) -> String {
     to_b64(&pk.serialize_compressed())
 }
 
-fn b64_to_public(b64: &str) -> anyhow::Result<PublicKey> {
+pub fn b64_to_public(b64: &str) -> anyhow::Result<PublicKey> {
     let json = serde_json::json!(b64);
     let pk: PublicKey = serde_json::from_value(json)?;
     Ok(pk)
 }
 
-fn b64_to_secret(b64: &str) -> anyhow::Result<SecretKey> {
+pub fn b64_to_secret(b64: &str) -> anyhow::Result<SecretKey> {
     let bz = from_b64(b64)?;
     let sk = SecretKey::try_from(bz)?;
     Ok(sk)
@@ -169,6 +250,91 @@ pub fn read_secret_key(secret_key: &PathBuf) -> anyhow::Result<SecretKey> {
     Ok(sk)
 }
 
+/// Write the secret key as the validator key of CometBFT.
+pub fn write_priv_validator_key(sk: &SecretKey, out: &Path, overwrite: bool) -> anyhow::Result<()> {
+    let pk = sk.public_key();
+    let vk = tendermint::crypto::default::ecdsa_secp256k1::VerifyingKey::from_sec1_bytes(
+        &pk.serialize(),
+    )
+    .map_err(|e| anyhow!("failed to convert public key: {e}"))?;
+    let pub_key = tendermint::PublicKey::Secp256k1(vk);
+    let address = tendermint::account::Id::from(pub_key);
+
+    // tendermint-rs doesn't seem to handle Secp256k1 private keys;
+    // if it did, we could use tendermint_config::PrivateValidatorKey
+    // to encode the data structure. Tendermint should be okay with it
+    // though, as long as we match the expected keys in the JSON.
+    let priv_validator_key = json! ({
+        "address": address,
+        "pub_key": pub_key,
+        "priv_key": {
+            "type": "tendermint/PrivKeySecp256k1",
+            "value": secret_to_b64(sk)
+        }
+    });
+    let json = serde_json::to_string_pretty(&priv_validator_key)?;
+
+    write_secret(out, &json, overwrite)
+}
+
+/// Generate a new node key for CometBFT, used to identify the node among its peers,
+/// and return the node ID.
+pub fn write_node_key(out: &Path, overwrite: bool) -> anyhow::Result<String> {
+    // CometBFT expects the 32 byte secret followed by the 32 byte public key.
+    let keypair = libp2p::identity::ed25519::Keypair::generate();
+
+    let node_key = json!({
+        "priv_key": {
+            "type": "tendermint/PrivKeyEd25519",
+            "value": to_b64(&keypair.encode())
+        }
+    });
+    let json = serde_json::to_string_pretty(&node_key)?;
+
+    write_secret(out, &json, overwrite)?;
+
+    // Read it back to make sure CometBFT will be able to do the same.
+    let node_key = NodeKey::load_json_file(&out).context("failed to read back node key")?;
+
+    Ok(node_key.node_id().to_string())
+}
+
+/// Write a file with a secret in it, only readable by the owner.
+///
+/// Unless `overwrite` is set, an existing file is an error, as it might be a key in use.
+pub fn write_secret(out: &Path, contents: &str, overwrite: bool) -> anyhow::Result<()> {
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true);
+    if overwrite {
+        opts.create(true).truncate(true);
+    } else {
+        opts.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+
+    let mut file = opts.open(out).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            anyhow!("{out:?} already exists; use --force to overwrite it")
+        } else {
+            anyhow!(e).context(format!("failed to open {out:?}"))
+        }
+    })?;
+
+    // The mode only applies to new files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("failed to write {out:?}"))
+}
+
 fn export(output_dir: &Path, name: &str, ext: &str, b64: &str) -> anyhow::Result<()> {
     let output_path = output_dir.join(format!("{name}.{ext}"));
     std::fs::write(output_path, b64)?;
@@ -180,9 +346,11 @@ mod tests {
     use fendermint_vm_genesis::ValidatorKey;
     use quickcheck_macros::quickcheck;
 
+    use tendermint_config::NodeKey;
+
     use crate::cmd::key::b64_to_public;
 
-    use super::public_to_b64;
+    use super::{public_to_b64, write_node_key, write_secret};
 
     #[quickcheck]
     fn prop_public_key_deserialize_to_genesis(vk: ValidatorKey) {
@@ -190,4 +358,34 @@ mod tests {
         let pk = b64_to_public(&b64).unwrap();
         assert_eq!(pk, vk.0)
     }
+
+    #[test]
+    fn node_key_loads_in_tendermint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node_key.json");
+        let node_id = write_node_key(&path, false).unwrap();
+        let node_key = NodeKey::load_json_file(&path).unwrap();
+        assert_eq!(node_key.node_id().to_string(), node_id);
+        assert_eq!(node_id.len(), 40);
+    }
+
+    #[test]
+    fn secrets_are_not_overwritten_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+
+        write_secret(&path, "first", false).unwrap();
+        assert!(write_secret(&path, "second", false).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+
+        write_secret(&path, "second", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
 }
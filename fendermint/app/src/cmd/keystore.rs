@@ -0,0 +1,357 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A directory of named accounts, with the secret keys optionally encrypted by a password.
+//!
+//! Every account is stored in a `<name>.json` file; the name of the account to use
+//! when none is specified is kept in a file called `default`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use fendermint_crypto::{PublicKey, SecretKey};
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fvm_shared::address::Address;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::key::{b64_to_public, b64_to_secret, public_to_b64, secret_to_b64, write_secret};
+
+/// Name of the file in the keystore directory pointing at the default account.
+const DEFAULT_FILE: &str = "default";
+
+/// Scrypt cost parameters recommended for interactive logins.
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// The kind of on-chain account the key is used with, which determines its address.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyKind {
+    Regular,
+    Ethereum,
+}
+
+/// How the secret key is stored in the file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum KeySecret {
+    /// Base64 encoded secret key, readable by anyone who has access to the file.
+    Plain { secret_key: String },
+    /// Secret key encrypted with XChaCha20-Poly1305, using a key derived from a password with scrypt.
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+        /// Hex encoded salt of the KDF.
+        salt: String,
+        /// Hex encoded nonce of the cipher.
+        nonce: String,
+        /// Hex encoded encrypted secret key.
+        ciphertext: String,
+    },
+}
+
+/// The contents of an account file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyFile {
+    pub name: String,
+    pub kind: KeyKind,
+    /// Base64 encoded compressed public key, so the address can be shown without the password.
+    pub public_key: String,
+    secret: KeySecret,
+}
+
+impl KeyFile {
+    pub fn public_key(&self) -> anyhow::Result<PublicKey> {
+        b64_to_public(&self.public_key).context("failed to parse public key")
+    }
+
+    pub fn address(&self) -> anyhow::Result<Address> {
+        let pk = self.public_key()?.serialize();
+        match self.kind {
+            KeyKind::Regular => Ok(Address::new_secp256k1(&pk)?),
+            KeyKind::Ethereum => Ok(Address::from(EthAddress::new_secp256k1(&pk)?)),
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        !matches!(self.secret, KeySecret::Plain { .. })
+    }
+
+    /// Decrypt the secret key; the password is ignored if the key isn't encrypted.
+    pub fn secret_key(&self, password: &str) -> anyhow::Result<SecretKey> {
+        match &self.secret {
+            KeySecret::Plain { secret_key } => b64_to_secret(secret_key),
+            KeySecret::Scrypt {
+                log_n,
+                r,
+                p,
+                salt,
+                nonce,
+                ciphertext,
+            } => {
+                if password.is_empty() {
+                    bail!(
+                        "account '{}' is encrypted; a password is required",
+                        self.name
+                    );
+                }
+                let salt = hex::decode(salt).context("invalid salt")?;
+                let nonce = hex::decode(nonce).context("invalid nonce")?;
+                let ciphertext = hex::decode(ciphertext).context("invalid ciphertext")?;
+
+                let key = derive_key(password, &salt, *log_n, *r, *p)?;
+                let cipher = XChaCha20Poly1305::new_from_slice(key.as_ref())
+                    .map_err(|e| anyhow!("invalid cipher key: {e}"))?;
+
+                if nonce.len() != 24 {
+                    bail!("invalid nonce length: {}", nonce.len());
+                }
+
+                let plaintext = cipher
+                    .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+                    .map_err(|_| anyhow!("wrong password for account '{}'", self.name))?;
+
+                SecretKey::try_from(plaintext).context("failed to parse secret key")
+            }
+        }
+    }
+}
+
+/// A directory of named accounts.
+pub struct Keystore {
+    dir: PathBuf,
+}
+
+impl Keystore {
+    /// Open the keystore, creating the directory if it doesn't exist yet.
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create keystore directory {dir:?}"))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn key_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+
+    /// All the accounts in the keystore, in alphabetical order.
+    pub fn list(&self) -> anyhow::Result<Vec<KeyFile>> {
+        let mut keys = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).context("failed to read keystore")? {
+            let path = entry?.path();
+            if path.extension().map(|e| e == "json").unwrap_or_default() {
+                keys.push(read_key_file(&path)?);
+            }
+        }
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(keys)
+    }
+
+    /// Look up an account by name.
+    pub fn get(&self, name: &str) -> anyhow::Result<KeyFile> {
+        let path = self.key_path(name);
+        if !path.exists() {
+            bail!("account '{name}' not found in the keystore");
+        }
+        read_key_file(&path)
+    }
+
+    /// Add a new account; the secret key is encrypted unless the password is empty.
+    ///
+    /// Fails if the name is already taken, so keys can't be overwritten by accident.
+    pub fn import(
+        &self,
+        name: &str,
+        sk: &SecretKey,
+        kind: KeyKind,
+        password: &str,
+    ) -> anyhow::Result<KeyFile> {
+        validate_name(name)?;
+
+        let path = self.key_path(name);
+        if path.exists() {
+            bail!("account '{name}' already exists in the keystore");
+        }
+
+        let secret = if password.is_empty() {
+            KeySecret::Plain {
+                secret_key: secret_to_b64(sk),
+            }
+        } else {
+            encrypt(sk, password)?
+        };
+
+        let key_file = KeyFile {
+            name: name.to_owned(),
+            kind,
+            public_key: public_to_b64(&sk.public_key()),
+            secret,
+        };
+
+        let json = serde_json::to_string_pretty(&key_file)?;
+        // Even encrypted keys shouldn't be left around for anyone to try their luck with.
+        write_secret(&path, &json, false).context("failed to write key file")?;
+
+        if self.default_account()?.is_none() {
+            self.set_default(name)?;
+        }
+
+        Ok(key_file)
+    }
+
+    /// Name of the account to use when none is specified.
+    pub fn default_account(&self) -> anyhow::Result<Option<String>> {
+        let path = self.dir.join(DEFAULT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let name = std::fs::read_to_string(path).context("failed to read default account")?;
+        Ok(Some(name.trim().to_owned()))
+    }
+
+    /// Make an existing account the default.
+    pub fn set_default(&self, name: &str) -> anyhow::Result<()> {
+        // Make sure it exists.
+        let _ = self.get(name)?;
+        std::fs::write(self.dir.join(DEFAULT_FILE), name).context("failed to write default account")
+    }
+
+    /// Load the account with the given name, or the default one.
+    pub fn resolve(&self, name: Option<&str>) -> anyhow::Result<KeyFile> {
+        match name {
+            Some(name) => self.get(name),
+            None => match self.default_account()? {
+                Some(name) => self.get(&name),
+                None => Err(anyhow!(
+                    "no account specified and the keystore has no default account"
+                )),
+            },
+        }
+    }
+}
+
+fn read_key_file(path: &Path) -> anyhow::Result<KeyFile> {
+    let json = std::fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    serde_json::from_str(&json).with_context(|| format!("failed to parse {path:?}"))
+}
+
+/// Names end up as file names, so keep them simple.
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || name == DEFAULT_FILE
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        || name.starts_with('.')
+    {
+        bail!("invalid account name: '{name}'");
+    }
+    Ok(())
+}
+
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+    let params =
+        scrypt::Params::new(log_n, r, p, 32).map_err(|e| anyhow!("invalid KDF params: {e}"))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut())
+        .map_err(|e| anyhow!("failed to derive key: {e}"))?;
+    Ok(key)
+}
+
+fn encrypt(sk: &SecretKey, password: &str) -> anyhow::Result<KeySecret> {
+    let mut rng = ChaCha20Rng::from_entropy();
+    let mut salt = [0u8; 32];
+    let mut nonce = [0u8; 24];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let key = derive_key(password, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+    let cipher = XChaCha20Poly1305::new_from_slice(key.as_ref())
+        .map_err(|e| anyhow!("invalid cipher key: {e}"))?;
+
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), sk.serialize().as_ref())
+        .map_err(|_| anyhow!("failed to encrypt secret key"))?;
+
+    Ok(KeySecret::Scrypt {
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    use super::{KeyKind, Keystore};
+
+    #[test]
+    fn import_and_decrypt() {
+        let dir = tempfile::tempdir().unwrap();
+        let ks = Keystore::open(dir.path()).unwrap();
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+
+        let alice = SecretKey::random(&mut rng);
+        let bob = SecretKey::random(&mut rng);
+
+        ks.import("alice", &alice, KeyKind::Regular, "secret")
+            .unwrap();
+        ks.import("bob", &bob, KeyKind::Ethereum, "").unwrap();
+
+        // The first account becomes the default.
+        assert_eq!(ks.default_account().unwrap(), Some("alice".to_owned()));
+
+        let keys = ks.list().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert!(keys[0].is_encrypted());
+        assert!(!keys[1].is_encrypted());
+
+        let kf = ks.resolve(None).unwrap();
+        assert!(kf.secret_key("").is_err());
+        assert!(kf.secret_key("wrong").is_err());
+        assert_eq!(kf.secret_key("secret").unwrap(), alice);
+
+        ks.set_default("bob").unwrap();
+        let kf = ks.resolve(None).unwrap();
+        assert_eq!(kf.secret_key("").unwrap(), bob);
+
+        // Names can't be reused.
+        assert!(ks.import("bob", &alice, KeyKind::Regular, "").is_err());
+        assert!(ks.import("../bob", &alice, KeyKind::Regular, "").is_err());
+
+        // Only the owner can read the keys.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for kf in ks.list().unwrap() {
+                let path = dir.path().join(format!("{}.json", kf.name));
+                let mode = std::fs::metadata(path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+    }
+}
@@ -5,20 +5,46 @@
 
 use crate::{
     options::{Commands, Options},
-    settings::{expand_tilde, Settings},
+    settings::{expand_tilde, QueryBudgetSettings, Settings},
 };
 use anyhow::{anyhow, Context};
 use async_trait::async_trait;
 use base64::engine::GeneralPurpose;
 use base64::engine::{DecodePaddingMode, GeneralPurposeConfig};
 use base64::{alphabet, Engine};
+use fendermint_rocksdb::namespaces;
+use fendermint_vm_interpreter::fvm::state::QueryBudget;
 
+pub mod bench;
+pub mod bootstrap;
+pub mod config;
+pub mod db;
+pub mod debug;
 pub mod eth;
 pub mod genesis;
 pub mod key;
 pub mod rpc;
 pub mod run;
 
+mod keystore;
+
+namespaces! {
+    Namespaces {
+        app,
+        state_hist,
+        logs_bloom,
+        block_hashes,
+        eth_block_hashes,
+        gas_stats,
+        validator_sets,
+        bottom_up_queue,
+        topdown_traces,
+        bottomup_traces,
+        state_store,
+        bit_store
+    }
+}
+
 /// A [`GeneralPurpose`] engine using the [`alphabet::STANDARD`] base64 alphabet
 /// padding bytes when writing but requireing no padding when reading.
 const B64_ENGINE: GeneralPurpose = GeneralPurpose::new(
@@ -37,6 +63,14 @@ pub fn from_b64(b64: &str) -> anyhow::Result<Vec<u8>> {
     Ok(B64_ENGINE.decode(b64)?)
 }
 
+/// Limits on the resources of queries, where zero means no limit.
+pub fn to_query_budget(settings: &QueryBudgetSettings) -> QueryBudget {
+    QueryBudget {
+        gas_limit: settings.gas_limit,
+        timeout: Some(settings.timeout).filter(|t| !t.is_zero()),
+    }
+}
+
 #[async_trait]
 pub trait Cmd {
     type Settings;
@@ -84,12 +118,17 @@ pub async fn exec(opts: &Options) -> anyhow::Result<()> {
         Commands::Key(args) => args.exec(()).await,
         Commands::Genesis(args) => args.exec(()).await,
         Commands::Rpc(args) => args.exec(()).await,
-        Commands::Eth(args) => args.exec(settings(opts)?.eth).await,
+        Commands::Eth(args) => args.exec(settings(opts)?).await,
+        Commands::Db(args) => args.exec(settings(opts)?).await,
+        Commands::Bench(args) => args.exec(settings(opts)?).await,
+        Commands::Bootstrap(args) => args.exec(settings(opts)?).await,
+        Commands::Config(args) => args.exec(settings(opts)?).await,
+        Commands::Debug(args) => args.exec(()).await,
     }
 }
 
 /// Try to parse the settings in the configuration directory.
-fn settings(opts: &Options) -> anyhow::Result<Settings> {
+pub fn settings(opts: &Options) -> anyhow::Result<Settings> {
     let config_dir = match expand_tilde(opts.config_dir()) {
         d if !d.exists() => return Err(anyhow!("'{d:?}' does not exist")),
         d if !d.is_dir() => return Err(anyhow!("'{d:?}' is a not a directory")),
@@ -5,32 +5,48 @@ use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
 use bytes::Bytes;
+use cid::Cid;
+use fendermint_app::schema;
+use fendermint_app::to_error_msg;
 use fendermint_app_options::genesis::AccountKind;
 use fendermint_crypto::SecretKey;
-use fendermint_rpc::client::BoundFendermintClient;
+use fendermint_rpc::client::{BoundFendermintClient, TendermintClient};
+use fendermint_rpc::query::{app_hash_report, validator_set, ValidatorSetAddresses};
 use fendermint_rpc::tx::{
     AsyncResponse, BoundClient, CallClient, CommitResponse, SyncResponse, TxAsync, TxClient,
     TxCommit, TxSync,
 };
 use fendermint_vm_core::chainid;
+use fendermint_vm_interpreter::fvm::verify_receipt_proof;
 use fendermint_vm_message::chain::ChainMessage;
-use fendermint_vm_message::query::FvmQueryHeight;
+use fendermint_vm_message::query::{
+    ActorState, FvmQueryHeight, StakingChange, StakingOperation, StakingSimulationParams,
+};
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
 use fvm_shared::econ::TokenAmount;
-use fvm_shared::MethodNum;
-use serde::Serialize;
+use fvm_shared::error::ExitCode;
+use fvm_shared::{ActorID, MethodNum};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tendermint::abci::response::DeliverTx;
 use tendermint::block::Height;
-use tendermint_rpc::HttpClient;
+use tendermint_rpc::{Client, HttpClient};
 
 use fendermint_rpc::message::{GasParams, MessageFactory};
 use fendermint_rpc::{client::FendermintClient, query::QueryClient};
+use fendermint_vm_actor_interface::deadletter::{self, RetryParams, DEADLETTER_ACTOR_ADDR};
 use fendermint_vm_actor_interface::eam::{self, CreateReturn, EthAddress};
+use fendermint_vm_actor_interface::governance::{
+    self, ApproveParams, BlockTopdownSenderParams, ChainParams, ProposeParams,
+    ProposeTopdownFilterParams, TopdownFilter, GOVERNANCE_ACTOR_ADDR,
+};
+use fendermint_vm_actor_interface::rewardpool::{self, REWARD_POOL_ACTOR_ADDR};
+use fendermint_vm_actor_interface::system::{self, SYSTEM_ACTOR_ADDR};
 
 use crate::cmd;
 use crate::options::rpc::{BroadcastMode, FevmArgs, RpcFevmCommands, TransArgs};
@@ -39,40 +55,188 @@ use crate::{
     options::rpc::{RpcArgs, RpcCommands, RpcQueryCommands},
 };
 
-use super::key::read_secret_key;
+use super::key::{open_keystore, read_secret_key};
+
+mod shell;
 
 cmd! {
   RpcArgs(self) {
     let client = FendermintClient::new_http(self.url.clone(), self.proxy_url.clone())?;
     match self.command.clone() {
-      RpcCommands::Query { height, command } => {
-        let height = Height::try_from(height)?;
-        query(client, height, command).await
-      },
-      RpcCommands::Transfer { args, to } => {
-        transfer(client, args, to).await
-      },
-      RpcCommands::Transaction { args, to, method_number, params } => {
-        transaction(client, args, to, method_number, params.clone()).await
-      },
-      RpcCommands::Fevm { args, command } => match command {
-        RpcFevmCommands::Create { contract, constructor_args } => {
-            fevm_create(client, args, contract, constructor_args).await
+      RpcCommands::Shell { keystore, history_file } => {
+        shell::run(client, keystore, history_file).await
+      }
+      command => run_command(client, command, Output::Json).await,
+    }
+  }
+}
+
+/// How the results of the commands are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// JSON as returned by the node, e.g. with amounts in atto.
+    Json,
+    /// JSON meant for people, with amounts in whole tokens and exit codes explained.
+    Pretty,
+}
+
+/// Execute a command other than the shell itself.
+async fn run_command(
+    client: FendermintClient,
+    command: RpcCommands,
+    out: Output,
+) -> anyhow::Result<()> {
+    match command {
+        RpcCommands::Query { height, command } => {
+            let height = Height::try_from(height)?;
+            query(client, height, command, out).await
         }
-        RpcFevmCommands::Invoke { args: FevmArgs { contract, method, method_args }} => {
-            fevm_invoke(client, args, contract, method, method_args).await
+        RpcCommands::Transfer { args, to } => transfer(client, out, args, to).await,
+        RpcCommands::Transaction {
+            args,
+            to,
+            method_number,
+            params,
+        } => transaction(client, out, args, to, method_number, params).await,
+        RpcCommands::Fevm { args, command } => match command {
+            RpcFevmCommands::Create {
+                contract,
+                constructor_args,
+            } => fevm_create(client, out, args, contract, constructor_args).await,
+            RpcFevmCommands::Invoke {
+                args:
+                    FevmArgs {
+                        contract,
+                        method,
+                        method_args,
+                    },
+            } => fevm_invoke(client, out, args, contract, method, method_args).await,
+            RpcFevmCommands::Call {
+                args:
+                    FevmArgs {
+                        contract,
+                        method,
+                        method_args,
+                    },
+                height,
+            } => {
+                let height = Height::try_from(height)?;
+                fevm_call(client, out, args, contract, method, method_args, height).await
+            }
+            RpcFevmCommands::EstimateGas {
+                args:
+                    FevmArgs {
+                        contract,
+                        method,
+                        method_args,
+                    },
+                height,
+            } => {
+                let height = Height::try_from(height)?;
+                fevm_estimate_gas(client, out, args, contract, method, method_args, height).await
+            }
+        },
+        RpcCommands::RetryDeadLetter { args, id } => {
+            let params = RawBytes::serialize(RetryParams { id })?;
+            transaction(
+                client,
+                out,
+                args,
+                DEADLETTER_ACTOR_ADDR,
+                deadletter::Method::Retry as u64,
+                params,
+            )
+            .await
         }
-        RpcFevmCommands::Call { args: FevmArgs { contract, method, method_args }, height} => {
-            let height = Height::try_from(height)?;
-            fevm_call(client, args, contract, method, method_args, height).await
+        RpcCommands::GovernancePropose {
+            args,
+            base_fee_floor,
+            block_gas_limit,
+            topdown_max_msgs,
+        } => {
+            let params = RawBytes::serialize(ProposeParams {
+                params: ChainParams {
+                    base_fee_floor,
+                    block_gas_limit,
+                    topdown_max_msgs,
+                },
+            })?;
+            transaction(
+                client,
+                out,
+                args,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::Propose as u64,
+                params,
+            )
+            .await
         }
-        RpcFevmCommands::EstimateGas { args: FevmArgs { contract, method, method_args }, height} => {
-            let height = Height::try_from(height)?;
-            fevm_estimate_gas(client, args, contract, method, method_args, height).await
+        RpcCommands::GovernanceProposeShutdown { args } => {
+            transaction(
+                client,
+                out,
+                args,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::ProposeShutdown as u64,
+                RawBytes::default(),
+            )
+            .await
         }
-      }
+        RpcCommands::GovernanceBlockTopdownSender { args, sender } => {
+            let params = RawBytes::serialize(BlockTopdownSenderParams { sender })?;
+            transaction(
+                client,
+                out,
+                args,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::BlockTopdownSender as u64,
+                params,
+            )
+            .await
+        }
+        RpcCommands::GovernanceProposeTopdownFilter {
+            args,
+            blocked,
+            allowed,
+        } => {
+            let params = RawBytes::serialize(ProposeTopdownFilterParams {
+                filter: TopdownFilter { blocked, allowed },
+            })?;
+            transaction(
+                client,
+                out,
+                args,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::ProposeTopdownFilter as u64,
+                params,
+            )
+            .await
+        }
+        RpcCommands::ClaimRewards { args } => {
+            transaction(
+                client,
+                out,
+                args,
+                REWARD_POOL_ACTOR_ADDR,
+                rewardpool::Method::Claim as u64,
+                RawBytes::default(),
+            )
+            .await
+        }
+        RpcCommands::GovernanceApprove { args, id } => {
+            let params = RawBytes::serialize(ApproveParams { id })?;
+            transaction(
+                client,
+                out,
+                args,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::Approve as u64,
+                params,
+            )
+            .await
+        }
+        RpcCommands::Shell { .. } => Err(anyhow!("already in the shell")),
     }
-  }
 }
 
 /// Run an ABCI query and print the results on STDOUT.
@@ -80,6 +244,7 @@ async fn query(
     client: FendermintClient,
     height: Height,
     command: RpcQueryCommands,
+    out: Output,
 ) -> anyhow::Result<()> {
     let height = FvmQueryHeight::from(height.value());
     match command {
@@ -90,11 +255,18 @@ async fn query(
         RpcQueryCommands::ActorState { address } => {
             match client.actor_state(&address, height).await?.value {
                 Some((id, state)) => {
-                    let out = json! ({
+                    let decoded = decode_actor_state(&client, id, &state, height)
+                        .await
+                        .unwrap_or_else(|e| {
+                            eprintln!("failed to decode the actor state: {e:#}");
+                            None
+                        });
+                    let state_json = json! ({
                       "id": id,
                       "state": state,
+                      "decoded": decoded,
                     });
-                    print_json(&out)?;
+                    print_json(out, &state_json)?;
                 }
                 None => {
                     eprintln!("actor not found")
@@ -104,16 +276,308 @@ async fn query(
         RpcQueryCommands::StateParams => {
             let res = client.state_params(height).await?;
             let json = json!({ "response": res });
-            print_json(&json)?;
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::DeadLetters => {
+            let res = client.dead_letters(height).await?;
+            let letters = res
+                .value
+                .into_iter()
+                .map(|l| {
+                    json!({
+                        "id": l.id,
+                        "height": l.height,
+                        "nonce": l.msg.msg.nonce,
+                        "value": l.msg.msg.value.to_string(),
+                        "reason": l.reason,
+                        "retries": l.retries,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let json = json!({ "height": res.height, "dead_letters": letters });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Governance => {
+            let res = client.governance(height).await?;
+            let params = |p: &ChainParams| {
+                json!({
+                    "base_fee_floor": p.base_fee_floor.atto().to_string(),
+                    "block_gas_limit": p.block_gas_limit,
+                    "topdown_max_msgs": p.topdown_max_msgs,
+                })
+            };
+            let filter = |f: &TopdownFilter| {
+                let addrs =
+                    |addrs: &[Address]| addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                json!({
+                    "blocked": addrs(&f.blocked),
+                    "allowed": f.allowed.as_deref().map(addrs),
+                })
+            };
+            let json = match res.value {
+                None => json!({ "height": res.height, "governance": null }),
+                Some(gov) => json!({
+                    "height": res.height,
+                    "governance": {
+                        "members": gov.members.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+                        "threshold": gov.threshold,
+                        "epoch_length": gov.epoch_length,
+                        "params": params(&gov.params),
+                        "proposals": gov.proposals.iter().map(|p| json!({
+                            "id": p.id,
+                            "proposer": p.proposer.to_string(),
+                            "height": p.height,
+                            "params": params(&p.params),
+                            "approvals": p.approvals.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+                            "shutdown": p.shutdown,
+                            "topdown_filter": p.topdown_filter.as_ref().map(filter),
+                        })).collect::<Vec<_>>(),
+                        "scheduled": gov.scheduled.as_ref().map(|s| json!({
+                            "proposal_id": s.proposal_id,
+                            "params": params(&s.params),
+                            "shutdown": s.shutdown,
+                        })),
+                        "deferred_topdown_msgs": gov.deferred_topdown_msgs.len(),
+                        "topdown_filter": filter(&gov.topdown_filter),
+                        "shutdown": gov.shutdown.as_ref().map(|s| json!({
+                            "since": s.since,
+                            "final_checkpoint_height": s.final_checkpoint_height,
+                            "terminal_state_root": s.terminal_state_root.map(|c| c.to_string()),
+                        })),
+                    }
+                }),
+            };
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Beacon => {
+            let res = client.beacon(height).await?;
+            let json = match res.value {
+                None => json!({ "height": res.height, "beacon": null }),
+                Some(b) => json!({
+                    "height": res.height,
+                    "beacon": {
+                        "epoch_length": b.epoch_length,
+                        "round": {
+                            "epoch": b.round.epoch,
+                            "contributions": b.round.contributions.iter().map(|c| json!({
+                                "sender": c.sender.to_string(),
+                                "revealed": !c.secret.is_empty(),
+                            })).collect::<Vec<_>>(),
+                        },
+                        "values": b.beacons.iter().rev().map(|v| json!({
+                            "epoch": v.epoch,
+                            "height": v.height,
+                            "value": hex::encode(&v.value),
+                            "contributors": v.contributors,
+                        })).collect::<Vec<_>>(),
+                        "suspensions": b.suspensions.iter().map(|s| json!({
+                            "sender": s.sender.to_string(),
+                            "until_epoch": s.until_epoch,
+                        })).collect::<Vec<_>>(),
+                    }
+                }),
+            };
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Rewards => {
+            let res = client.reward_pool(height).await?;
+            let json = match res.value {
+                None => json!({ "height": res.height, "rewards": null }),
+                Some(pool) => json!({
+                    "height": res.height,
+                    "rewards": {
+                        "epoch_length": pool.state.epoch_length,
+                        "epoch": pool.state.epoch,
+                        "inflation_per_epoch": pool.state.inflation_per_epoch.atto().to_string(),
+                        "collect_base_fees": pool.state.collect_base_fees,
+                        "balance": pool.balance.atto().to_string(),
+                        "undistributed": pool.undistributed().atto().to_string(),
+                        "claimable": pool.state.claimable.iter().map(|c| json!({
+                            "address": Address::new_secp256k1(&c.public_key).map(|a| a.to_string()).ok(),
+                            "public_key": hex::encode(&c.public_key),
+                            "amount": c.amount.atto().to_string(),
+                        })).collect::<Vec<_>>(),
+                    }
+                }),
+            };
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Validators => {
+            let res = client.validators(height).await?;
+            let json = json!({ "height": res.height, "validators": res.value });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::ValidatorSet => {
+            let res = validator_set(client.underlying(), u64::from(height)).await?;
+            let validator_set = res
+                .value
+                .as_ref()
+                .map(ValidatorSetAddresses::try_from)
+                .transpose()?;
+            let json = json!({ "height": res.height, "validator_set": validator_set });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::AppHash => {
+            let res = app_hash_report(client.underlying(), u64::from(height)).await?;
+            let json = json!({ "height": res.height, "report": res.value });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Contracts => {
+            let res = client.contracts(height).await?;
+            let json = json!({ "height": res.height, "contracts": res.value });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::Diamonds => {
+            let res = client.diamonds(height).await?;
+            let json = json!({ "height": res.height, "diamonds": res.value });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::ArchivedCheckpoint { checkpoint_height } => {
+            let res = client
+                .archived_checkpoint(checkpoint_height, height)
+                .await?;
+            let checkpoint = res.value.map(|cp| {
+                json!({
+                    "height": cp.height,
+                    "checkpoint": hex::encode(cp.checkpoint),
+                    "quorum_info": hex::encode(cp.quorum_info),
+                    "signatories": cp.signatories.iter().map(|a| format!("{a:?}")).collect::<Vec<_>>(),
+                    "signatures": cp.signatures.iter().map(|s| hex::encode(s.bytes())).collect::<Vec<_>>(),
+                })
+            });
+            let json = json!({ "height": res.height, "checkpoint": checkpoint });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::SimulateStaking {
+            changes_file,
+            max_validators,
+            min_power,
+        } => {
+            let json = std::fs::read_to_string(&changes_file)
+                .with_context(|| format!("failed to read {}", changes_file.to_string_lossy()))?;
+            let changes: Vec<StakingChangeJson> =
+                serde_json::from_str(&json).context("failed to parse staking changes")?;
+            let params = StakingSimulationParams {
+                changes: changes
+                    .into_iter()
+                    .map(StakingChange::try_from)
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+                max_validators,
+                min_power,
+            };
+            let res = client.simulate_staking_changes(params, height).await?;
+            let sim = res.value;
+            let json = json!({
+                "height": res.height,
+                "configuration_number": sim.configuration_number,
+                "total_power": sim.total_power,
+                "validators": sim.validators.iter().map(|v| json!({
+                    "public_key": hex::encode(&v.public_key),
+                    "power": v.power,
+                })).collect::<Vec<_>>(),
+                "violations": sim.violations,
+            });
+            print_json(out, &json)?;
+        }
+        RpcQueryCommands::ReceiptProof {
+            block_height,
+            index,
+        } => {
+            let res = client.receipt_proof(block_height, index, height).await?;
+
+            // The state at the height of the query is committed to by the app hash in the
+            // header of the next block, which may not have been produced yet.
+            let state_verified = match res.value {
+                Some(ref p) => match client.underlying().commit(res.height.increment()).await {
+                    Ok(c) => Some(verify_receipt_proof(
+                        c.signed_header.header.app_hash.as_bytes(),
+                        p,
+                    )?),
+                    Err(_) => None,
+                },
+                None => None,
+            };
+
+            let proof = res.value.map(|p| {
+                json!({
+                    "verified": p.verify(),
+                    "state_verified": state_verified,
+                    "block_height": p.digest.height,
+                    "tx_count": p.digest.tx_count,
+                    "receipts_root": hex::encode(p.digest.receipts_root),
+                    "events_root": hex::encode(p.digest.events_root),
+                    "receipt": {
+                        "tx_hash": hex::encode_upper(p.receipt.tx_hash),
+                        "exit_code": p.receipt.exit_code.map(|c| c.value()),
+                        "gas_used": p.receipt.gas_used,
+                        "return_data": hex::encode(p.receipt.return_data.bytes()),
+                        "events_root": hex::encode(p.receipt.events_root),
+                    },
+                    "aunts": p.proof.aunts.iter().map(hex::encode).collect::<Vec<_>>(),
+                })
+            });
+            let json = json!({ "height": res.height, "proof": proof });
+            print_json(out, &json)?;
         }
     };
     Ok(())
 }
 
+/// A staking change in the file of the `simulate-staking` query.
+#[derive(Deserialize, Debug)]
+struct StakingChangeJson {
+    configuration_number: u64,
+    validator: String,
+    #[serde(flatten)]
+    op: StakingOperationJson,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum StakingOperationJson {
+    /// Amount in atto.
+    Deposit { amount: String },
+    /// Amount in atto.
+    Withdraw { amount: String },
+    /// Hex encoded uncompressed public key.
+    SetMetadata { public_key: String },
+}
+
+impl TryFrom<StakingChangeJson> for StakingChange {
+    type Error = anyhow::Error;
+
+    fn try_from(value: StakingChangeJson) -> Result<Self, Self::Error> {
+        let atto = |s: &str| -> anyhow::Result<TokenAmount> {
+            let atto = s
+                .parse::<BigInt>()
+                .with_context(|| format!("invalid amount: {s}"))?;
+            Ok(TokenAmount::from_atto(atto))
+        };
+        let operation = match value.op {
+            StakingOperationJson::Deposit { amount } => StakingOperation::Deposit(atto(&amount)?),
+            StakingOperationJson::Withdraw { amount } => StakingOperation::Withdraw(atto(&amount)?),
+            StakingOperationJson::SetMetadata { public_key } => {
+                let pk = hex::decode(public_key.trim_start_matches("0x"))
+                    .context("invalid public key hex")?;
+                StakingOperation::SetMetadata(RawBytes::new(pk))
+            }
+        };
+        Ok(Self {
+            configuration_number: value.configuration_number,
+            validator: value
+                .validator
+                .parse()
+                .with_context(|| format!("invalid validator address: {}", value.validator))?,
+            operation,
+        })
+    }
+}
+
 /// Create a client, make a call to Tendermint with a closure, then maybe extract some JSON
 /// depending on the return value, finally print the result in JSON.
 async fn broadcast_and_print<F, T, G>(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     f: F,
     g: G,
@@ -138,13 +602,19 @@ where
             json!({"response": res.response, "return_data": return_data})
         }
     };
-    print_json(&json)
+    print_json(out, &json)
 }
 
 /// Execute token transfer through RPC and print the response to STDOUT as JSON.
-async fn transfer(client: FendermintClient, args: TransArgs, to: Address) -> anyhow::Result<()> {
+async fn transfer(
+    client: FendermintClient,
+    out: Output,
+    args: TransArgs,
+    to: Address,
+) -> anyhow::Result<()> {
     broadcast_and_print(
         client,
+        out,
         args,
         |mut client, value, gas_params| {
             Box::pin(async move { client.transfer(to, value, gas_params).await })
@@ -159,6 +629,7 @@ async fn transfer(client: FendermintClient, args: TransArgs, to: Address) -> any
 /// If there was any data returned it's rendered in hexadecimal format.
 async fn transaction(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     to: Address,
     method_num: MethodNum,
@@ -166,6 +637,7 @@ async fn transaction(
 ) -> anyhow::Result<()> {
     broadcast_and_print(
         client,
+        out,
         args,
         |mut client, value, gas_params| {
             Box::pin(async move {
@@ -184,6 +656,7 @@ async fn transaction(
 /// The returned EVM contract addresses are included as a JSON object.
 async fn fevm_create(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     contract: PathBuf,
     constructor_args: Bytes,
@@ -194,6 +667,7 @@ async fn fevm_create(
 
     broadcast_and_print(
         client,
+        out,
         args,
         |mut client, value, gas_params| {
             Box::pin(async move {
@@ -210,6 +684,7 @@ async fn fevm_create(
 /// Invoke an EVM contract through RPC and print the response to STDOUT as JSON.
 async fn fevm_invoke(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     contract: Address,
     method: Bytes,
@@ -218,6 +693,7 @@ async fn fevm_invoke(
     let calldata = Bytes::from([method, method_args].concat());
     broadcast_and_print(
         client,
+        out,
         args,
         |mut client, value, gas_params| {
             Box::pin(async move {
@@ -234,6 +710,7 @@ async fn fevm_invoke(
 /// Call an EVM contract through RPC and print the response to STDOUT as JSON.
 async fn fevm_call(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     contract: Address,
     method: Bytes,
@@ -258,12 +735,13 @@ async fn fevm_call(
 
     let json = json!({"response": res.response, "return_data": return_data});
 
-    print_json(&json)
+    print_json(out, &json)
 }
 
 /// Estimate the gas of an EVM call through RPC and print the response to STDOUT as JSON.
 async fn fevm_estimate_gas(
     client: FendermintClient,
+    out: Output,
     args: TransArgs,
     contract: Address,
     method: Bytes,
@@ -283,19 +761,106 @@ async fn fevm_estimate_gas(
 
     let json = json!({ "response": res });
 
-    print_json(&json)
+    print_json(out, &json)
+}
+
+/// Decode the state of an actor with the schema of its code, if there is one.
+async fn decode_actor_state(
+    client: &FendermintClient,
+    id: ActorID,
+    state: &ActorState,
+    height: FvmQueryHeight,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    let get = |cid: Cid| async move {
+        client
+            .ipld(&cid, height)
+            .await?
+            .ok_or_else(|| anyhow!("{cid} not found"))
+    };
+
+    // The names of the actor codes are in the manifest of the bundle the system actor points at.
+    let (_, system_actor) = client
+        .actor_state(&SYSTEM_ACTOR_ADDR, height)
+        .await?
+        .value
+        .ok_or_else(|| anyhow!("system actor not found"))?;
+    let system_state: system::State =
+        fvm_ipld_encoding::from_slice(&get(system_actor.state).await?)
+            .context("failed to decode the system actor state")?;
+    let manifest = get(system_state.builtin_actors).await?;
+
+    let schema = match schema::code_name(&manifest, &state.code)? {
+        Some(code) => schema::lookup(&code, id),
+        None => None,
+    };
+    match schema {
+        Some(schema) => schema.decode(&get(state.state).await?).map(Some),
+        None => Ok(None),
+    }
 }
 
 /// Print out pretty-printed JSON.
 ///
 /// People can use `jq` to turn it into compact form if they want to save the results to a `.jsonline`
 /// file, but the default of having human readable output seems more useful.
-fn print_json<T: Serialize>(value: &T) -> anyhow::Result<()> {
-    let json = serde_json::to_string_pretty(&value)?;
+fn print_json<T: Serialize>(out: Output, value: &T) -> anyhow::Result<()> {
+    let mut json = serde_json::to_value(value)?;
+    if out == Output::Pretty {
+        prettify(&mut json);
+    }
+    let json = serde_json::to_string_pretty(&json)?;
     println!("{}", json);
     Ok(())
 }
 
+/// Fields with amounts in atto, which are easier to read in whole tokens.
+const ATTO_FIELDS: &[&str] = &[
+    "amount",
+    "balance",
+    "base_fee",
+    "base_fee_floor",
+    "circ_supply",
+    "inflation_per_epoch",
+    "undistributed",
+];
+
+/// Fields with exit codes, which are easier to read with their meaning.
+const EXIT_CODE_FIELDS: &[&str] = &["code", "exit_code"];
+
+/// Show amounts in whole tokens and explain exit codes, wherever they are in the JSON.
+fn prettify(json: &mut serde_json::Value) {
+    match json {
+        serde_json::Value::Array(values) => values.iter_mut().for_each(prettify),
+        serde_json::Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if ATTO_FIELDS.contains(&key.as_str()) {
+                    if let Some(atto) = value.as_str().and_then(|s| s.parse::<BigInt>().ok()) {
+                        *value = json!(format!("{} FIL", TokenAmount::from_atto(atto)));
+                    }
+                } else if EXIT_CODE_FIELDS.contains(&key.as_str()) {
+                    if let Some(code) = value.as_u64().and_then(|c| u32::try_from(c).ok()) {
+                        *value = json!(explain_exit_code(code));
+                    }
+                } else {
+                    prettify(value)
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn explain_exit_code(code: u32) -> String {
+    let exit_code = ExitCode::new(code);
+    if exit_code.is_success() {
+        return format!("{code} (OK)");
+    }
+    match to_error_msg(exit_code) {
+        "" => code.to_string(),
+        msg => format!("{code} ({msg})"),
+    }
+}
+
 /// Print all the various addresses we can use to refer to an EVM contract.
 fn create_return_to_json(ret: CreateReturn) -> serde_json::Value {
     // The only reference I can point to about how to use them are the integration tests:
@@ -330,10 +895,21 @@ struct TransClient {
 
 impl TransClient {
     pub fn new(client: FendermintClient, args: &TransArgs) -> anyhow::Result<Self> {
-        let sk = read_secret_key(&args.secret_key)?;
-        let addr = to_address(&sk, &args.account_kind)?;
+        let (sk, addr) = match args.secret_key {
+            Some(ref secret_key) => {
+                let sk = read_secret_key(secret_key)?;
+                let addr = to_address(&sk, &args.account_kind)?;
+                (sk, addr)
+            }
+            None => {
+                let kf = open_keystore(&args.keystore)?.resolve(args.account.as_deref())?;
+                let sk = kf.secret_key(&args.keystore.keystore_password)?;
+                (sk, kf.address()?)
+            }
+        };
         let chain_id = chainid::from_str_hashed(&args.chain_name)?;
-        let mf = MessageFactory::new(sk, addr, args.sequence, chain_id);
+        let mut mf = MessageFactory::new(sk, addr, args.sequence, chain_id);
+        mf.set_compression(args.compress);
         let client = client.bind(mf);
         let client = Self {
             inner: client,
@@ -388,3 +964,32 @@ fn to_address(sk: &SecretKey, kind: &AccountKind) -> anyhow::Result<Address> {
         AccountKind::Ethereum => Ok(Address::from(EthAddress::new_secp256k1(&pk)?)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::prettify;
+
+    #[test]
+    fn prettify_amounts_and_exit_codes() {
+        let mut json = json!({
+            "state": { "code": "bafk2bzace", "balance": "1500000000000000000" },
+            "response": { "check_tx": { "code": 0 }, "deliver_tx": { "code": 16 } },
+            "params": [{ "base_fee_floor": "100" }],
+        });
+        prettify(&mut json);
+
+        assert_eq!(json["state"]["code"], "bafk2bzace");
+        assert_eq!(json["state"]["balance"], "1.5 FIL");
+        assert_eq!(json["response"]["check_tx"]["code"], "0 (OK)");
+        assert_eq!(
+            json["response"]["deliver_tx"]["code"],
+            "16 (The method parameters are invalid.)"
+        );
+        assert_eq!(
+            json["params"][0]["base_fee_floor"],
+            "0.0000000000000001 FIL"
+        );
+    }
+}
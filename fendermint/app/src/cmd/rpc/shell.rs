@@ -0,0 +1,317 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! An interactive shell to run the `rpc` commands in.
+//!
+//! Lines are parsed like the arguments of `fendermint rpc`, so `help` and `--help` work as usual,
+//! except that addresses can be given as `@name` using the names in the address book.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use clap::{CommandFactory, Parser};
+use fendermint_rpc::client::FendermintClient;
+use fendermint_rpc::query::QueryClient;
+use fendermint_vm_message::query::FvmQueryHeight;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+
+use crate::cmd::key::open_keystore;
+use crate::options::key::KeystoreArgs;
+use crate::options::rpc::RpcCommands;
+use crate::settings::expand_tilde;
+
+use super::{run_command, Output};
+
+const PROMPT: &str = "fendermint> ";
+
+/// Commands of the shell itself, as opposed to the `rpc` subcommands.
+const BUILTINS: &[&str] = &["book", "exit", "quit"];
+
+/// A line of input in the shell.
+#[derive(Parser, Debug)]
+#[command(name = "rpc", no_binary_name = true, disable_version_flag = true)]
+struct ShellLine {
+    #[command(subcommand)]
+    command: RpcCommands,
+}
+
+/// Read commands from the terminal and run them until the user exits.
+pub async fn run(
+    client: FendermintClient,
+    keystore: KeystoreArgs,
+    history_file: PathBuf,
+) -> anyhow::Result<()> {
+    let book = AddressBook::load(&client, &keystore).await?;
+
+    let mut command = ShellLine::command();
+    // Adds the `help` subcommand and the `--help` flags, so they can be completed too.
+    command.build();
+
+    let helper = ShellHelper {
+        command,
+        names: book.0.keys().cloned().collect(),
+    };
+
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(helper));
+
+    let history_file = expand_tilde(&history_file);
+    // The file doesn't exist until the shell is exited for the first time.
+    let _ = editor.load_history(&history_file);
+
+    loop {
+        // Waiting for the user blocks the thread, which shouldn't hold up other tasks.
+        let line = match tokio::task::block_in_place(|| editor.readline(PROMPT)) {
+            Ok(line) => line,
+            // Ctrl-C abandons the line being typed, like in other shells.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e).context("failed to read from the terminal"),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        match line {
+            "exit" | "quit" => break,
+            "book" => book.print(),
+            _ => {
+                if let Err(e) = run_line(&client, &book, line).await {
+                    eprintln!("error: {e:#}");
+                }
+            }
+        }
+    }
+
+    if let Some(dir) = history_file.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create history directory {dir:?}"))?;
+    }
+    editor
+        .save_history(&history_file)
+        .with_context(|| format!("failed to save history to {history_file:?}"))
+}
+
+/// Parse a line and run the command in it.
+async fn run_line(client: &FendermintClient, book: &AddressBook, line: &str) -> anyhow::Result<()> {
+    let args = shlex::split(line).ok_or_else(|| anyhow!("unbalanced quotes"))?;
+    let args = book.expand(args)?;
+
+    match ShellLine::try_parse_from(args) {
+        Ok(parsed) => run_command(client.clone(), parsed.command, Output::Pretty).await,
+        Err(e) => {
+            // This is also how the help is printed; either way the shell goes on.
+            e.print()?;
+            Ok(())
+        }
+    }
+}
+
+/// Names which can be used instead of addresses, as `@name`.
+#[derive(Debug, Default)]
+struct AddressBook(BTreeMap<String, String>);
+
+impl AddressBook {
+    /// Collect the accounts in the keystore and the contracts deployed at genesis.
+    ///
+    /// Contracts are known by the name after the `:` in their fully qualified name,
+    /// e.g. `Gateway.sol:Gateway` is `@Gateway`.
+    async fn load(client: &FendermintClient, keystore: &KeystoreArgs) -> anyhow::Result<Self> {
+        let mut book = BTreeMap::new();
+
+        // The shell is still useful with the accounts alone.
+        match client.contracts(FvmQueryHeight::default()).await {
+            Ok(res) => {
+                for c in res.value {
+                    let name = c.fqn.rsplit(':').next().unwrap_or(&c.fqn).to_string();
+                    book.insert(name, c.delegated_address);
+                }
+            }
+            Err(e) => eprintln!("failed to load the contract addresses: {e:#}"),
+        }
+
+        // Accounts take precedence over contracts with the same name.
+        for kf in open_keystore(keystore)?.list()? {
+            book.insert(kf.name.clone(), kf.address()?.to_string());
+        }
+
+        Ok(Self(book))
+    }
+
+    fn print(&self) {
+        for (name, addr) in self.0.iter() {
+            println!("@{name:<32} {addr}");
+        }
+    }
+
+    /// Replace `@name` arguments, and `--flag=@name` ones, with the addresses they stand for.
+    fn expand(&self, args: Vec<String>) -> anyhow::Result<Vec<String>> {
+        args.into_iter()
+            .map(|arg| {
+                let (prefix, name) = match arg.split_once("=@") {
+                    Some((flag, name)) if flag.starts_with("--") => (format!("{flag}="), name),
+                    _ => match arg.strip_prefix('@') {
+                        Some(name) => (String::new(), name),
+                        None => return Ok(arg),
+                    },
+                };
+                match self.0.get(name) {
+                    Some(addr) => Ok(format!("{prefix}{addr}")),
+                    None => Err(anyhow!("'{name}' is not in the address book")),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Completes the subcommands and flags of the `rpc` command, and the names in the address book.
+struct ShellHelper {
+    command: clap::Command,
+    names: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line
+            .rfind(char::is_whitespace)
+            .map(|i| i + 1)
+            .unwrap_or_default();
+
+        let words = line[..start].split_whitespace().collect::<Vec<_>>();
+        let pairs = candidates(&self.command, &self.names, &words, &line[start..])
+            .into_iter()
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Completions of the word being typed, given the words before it.
+fn candidates(
+    command: &clap::Command,
+    names: &[String],
+    words: &[&str],
+    word: &str,
+) -> Vec<String> {
+    if let Some(name) = word.strip_prefix('@') {
+        return names
+            .iter()
+            .filter(|n| n.starts_with(name))
+            .map(|n| format!("@{n}"))
+            .collect();
+    }
+
+    // Follow the subcommands typed so far; anything else is a flag or a value.
+    let mut command = command;
+    for w in words {
+        if let Some(sub) = command.find_subcommand(*w) {
+            command = sub;
+        }
+    }
+
+    let mut cs = Vec::new();
+    if word.starts_with('-') {
+        cs.extend(
+            command
+                .get_arguments()
+                .filter_map(|a| a.get_long())
+                .map(|l| format!("--{l}")),
+        );
+    } else {
+        cs.extend(command.get_subcommands().map(|s| s.get_name().to_string()));
+        if words.is_empty() {
+            cs.extend(BUILTINS.iter().map(|b| b.to_string()));
+        }
+    }
+    cs.retain(|c| c.starts_with(word));
+    cs.sort();
+    cs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use clap::CommandFactory;
+
+    use super::{candidates, AddressBook, RpcCommands, ShellLine};
+
+    fn complete(words: &[&str], word: &str) -> Vec<String> {
+        let mut command = ShellLine::command();
+        command.build();
+        let names = vec!["alice".to_string(), "bob".to_string()];
+        candidates(&command, &names, words, word)
+    }
+
+    #[test]
+    fn complete_subcommands() {
+        assert_eq!(complete(&[], "tra"), vec!["transaction", "transfer"]);
+        assert_eq!(complete(&[], "bo"), vec!["book"]);
+        assert_eq!(complete(&["query"], "actor"), vec!["actor-state"]);
+        assert_eq!(
+            complete(&["query", "-b", "10"], "dead"),
+            vec!["dead-letters"]
+        );
+    }
+
+    #[test]
+    fn complete_flags_and_names() {
+        assert_eq!(complete(&["transfer"], "--chain"), vec!["--chain-name"]);
+        assert!(complete(&["transfer"], "--").contains(&"--to".to_string()));
+        assert_eq!(complete(&["transfer", "--to"], "@a"), vec!["@alice"]);
+    }
+
+    #[test]
+    fn expand_names() {
+        let book = AddressBook(BTreeMap::from([("alice".to_string(), "t1abc".to_string())]));
+        let args = |xs: &[&str]| xs.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            book.expand(args(&["transfer", "--to", "@alice"])).unwrap(),
+            args(&["transfer", "--to", "t1abc"])
+        );
+        assert_eq!(
+            book.expand(args(&["transfer", "--to=@alice"])).unwrap(),
+            args(&["transfer", "--to=t1abc"])
+        );
+        assert!(book.expand(args(&["--to", "@bob"])).is_err());
+    }
+
+    #[test]
+    fn parse_line() {
+        let line = ShellLine::try_parse_from(["query", "state-params"]).unwrap();
+        assert!(matches!(line.command, RpcCommands::Query { .. }));
+    }
+}
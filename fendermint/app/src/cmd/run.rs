@@ -3,20 +3,46 @@
 
 use anyhow::{anyhow, bail, Context};
 use fendermint_abci::ApplicationService;
+use fendermint_app::admin::HaltHeight;
+use fendermint_app::clock::{SystemClock, TimeMonitor};
+use fendermint_app::devchain::DevChainConfig;
+use fendermint_app::export::{ExportSink, Exporter};
+use fendermint_app::genesischeck::ParentGenesisCheck;
+use fendermint_app::index::EventIndex;
+use fendermint_app::lane::{OperatorLane, OperatorLaneConfig};
+use fendermint_app::mempool::{MempoolAdmin, MempoolEvictions};
+use fendermint_app::vectors::VectorRecorder;
+use fendermint_app::votes;
+use fendermint_app::watchdog::{Watchdog, WatchdogConfig};
 use fendermint_app::{App, AppConfig, AppParentFinalityQuery, AppStore, BitswapBlockstore};
-use fendermint_app_settings::AccountKind;
+use fendermint_app_settings::{
+    AccountKind, ExportFormat, SnapshotCompression as SnapshotCompressionSettings,
+};
 use fendermint_crypto::SecretKey;
-use fendermint_rocksdb::{blockstore::NamespaceBlockstore, namespaces, RocksDb, RocksDbConfig};
+use fendermint_eth_api::HybridClient;
+use fendermint_rocksdb::{blockstore::NamespaceBlockstore, RocksDb};
 use fendermint_vm_actor_interface::eam::EthAddress;
+#[cfg(feature = "block-stm")]
+use fendermint_vm_interpreter::fvm::stm::BlockStm;
 use fendermint_vm_interpreter::{
-    bytes::{BytesMessageInterpreter, ProposalPrepareMode},
-    chain::{ChainMessageInterpreter, CheckpointPool},
-    fvm::{Broadcaster, FvmMessageInterpreter, ValidatorContext},
-    signed::SignedMessageInterpreter,
+    bytes::ProposalPrepareMode,
+    chain::CheckpointPool,
+    fvm::{
+        policy::PolicyModule, AdmissionRules, Broadcaster, FvmMessageInterpreter, Outbox,
+        OutboxOpt, ValidatorContext,
+    },
+    stack::InterpreterBuilder,
 };
+use fendermint_vm_message::conv::from_fvm::to_eth_address;
 use fendermint_vm_resolver::ipld::IpldResolver;
-use fendermint_vm_snapshot::{SnapshotManager, SnapshotParams};
-use fendermint_vm_topdown::proxy::IPCProviderProxy;
+use fendermint_vm_resolver::pool::ResolveLimits;
+use fendermint_vm_snapshot::{
+    IpfsPublisher, S3Config, S3Publisher, SnapshotCompression, SnapshotManager, SnapshotParams,
+    SnapshotPublisher,
+};
+use fendermint_vm_topdown::breaker::{BreakerConfig, CircuitBreakerProxy};
+use fendermint_vm_topdown::proxy::{DynParentQueryProxy, IPCProviderProxy};
+use fendermint_vm_topdown::relayer::{RelayerConfig, RelayerMonitor};
 use fendermint_vm_topdown::sync::launch_polling_syncer;
 use fendermint_vm_topdown::{CachedFinalityProvider, Toggle};
 use fvm_shared::address::Address;
@@ -24,13 +50,22 @@ use ipc_provider::config::subnet::{EVMSubnet, SubnetConfig};
 use ipc_provider::IpcProvider;
 use libp2p::identity::secp256k1;
 use libp2p::identity::Keypair;
+use multiaddr::Protocol;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tendermint_rpc::{Url, WebSocketClientUrl};
+use tracing::{info, Instrument};
 
+use crate::cmd::genesis::{read_genesis, to_tendermint_genesis};
 use crate::cmd::key::read_secret_key;
+use crate::cmd::keystore::Keystore;
+use crate::cmd::{to_query_budget, Namespaces};
 use crate::{cmd, options::run::RunArgs, settings::Settings};
 
-fn create_ipc_provider_proxy(settings: &Settings) -> anyhow::Result<IPCProviderProxy> {
+fn create_ipc_provider_proxy(
+    settings: &Settings,
+) -> anyhow::Result<CircuitBreakerProxy<IPCProviderProxy>> {
     let topdown_config = settings.ipc.topdown_config()?;
     let subnet = ipc_provider::config::Subnet {
         id: settings
@@ -52,19 +87,297 @@ fn create_ipc_provider_proxy(settings: &Settings) -> anyhow::Result<IPCProviderP
     info!("init ipc provider with subnet: {}", subnet.id);
 
     let ipc_provider = IpcProvider::new_with_subnet(None, subnet)?;
-    IPCProviderProxy::new(ipc_provider, settings.ipc.subnet_id.clone())
+    let proxy = IPCProviderProxy::new(ipc_provider, settings.ipc.subnet_id.clone())?;
+
+    let breaker = &topdown_config.circuit_breaker;
+    let breaker_config = BreakerConfig {
+        failure_threshold: breaker.failure_threshold,
+        open_duration: breaker.open_duration,
+        max_open_duration: breaker.max_open_duration,
+        subnet_id: settings.ipc.subnet_id.to_string(),
+    };
+    Ok(CircuitBreakerProxy::new(proxy, breaker_config))
+}
+
+/// Monitor the funds of the account submitting checkpoints to the parent, if configured.
+fn create_relayer_monitor(settings: &Settings) -> anyhow::Result<Option<RelayerMonitor>> {
+    let Some(relayer) = settings.ipc.relayer.as_ref() else {
+        return Ok(None);
+    };
+    let topdown_config = settings
+        .ipc
+        .topdown_config()
+        .context("the relayer monitor uses the topdown settings to reach the parent")?;
+
+    let config = RelayerConfig {
+        address: to_eth_address(&relayer.address)
+            .ok_or_else(|| anyhow!("the relayer address has to be an Ethereum address"))?,
+        checkpoint_gas_limit: relayer.checkpoint_gas_limit,
+        min_checkpoints: relayer.min_checkpoints,
+        check_interval: relayer.check_interval,
+        subnet_id: settings.ipc.subnet_id.to_string(),
+    };
+
+    let monitor = RelayerMonitor::new(config, &topdown_config.parent_http_endpoint.to_string())?;
+
+    Ok(Some(monitor))
+}
+
+/// Where to upload the snapshots to, besides offering them to peers.
+fn create_snapshot_publishers(settings: &Settings) -> Vec<Arc<dyn SnapshotPublisher>> {
+    let publish = &settings.snapshots.publish;
+    let mut publishers: Vec<Arc<dyn SnapshotPublisher>> = Vec::new();
+    if let Some(ref s3) = publish.s3 {
+        info!(
+            endpoint = s3.endpoint.to_string(),
+            bucket = s3.bucket.as_str(),
+            "publishing snapshots to S3"
+        );
+        publishers.push(Arc::new(S3Publisher::new(S3Config {
+            endpoint: s3.endpoint.to_string(),
+            region: s3.region.clone(),
+            bucket: s3.bucket.clone(),
+            prefix: s3.prefix.clone(),
+            access_key_id: s3.access_key_id.clone(),
+            secret_access_key: s3.secret_access_key.clone(),
+            public_url: s3.public_url.as_ref().map(|u| u.to_string()),
+        })));
+    }
+    if let Some(ref ipfs) = publish.ipfs {
+        info!(
+            api_url = ipfs.api_url.to_string(),
+            "publishing snapshots to IPFS"
+        );
+        publishers.push(Arc::new(IpfsPublisher::new(ipfs.api_url.to_string())));
+    }
+    publishers
 }
 
+/// Check the genesis against the registration of the subnet on the parent, if configured.
+fn create_genesis_check(settings: &Settings) -> Option<ParentGenesisCheck> {
+    let topdown_config = settings.ipc.topdown.as_ref()?;
+    if !topdown_config.check_genesis {
+        return None;
+    }
+    Some(ParentGenesisCheck {
+        subnet_id: settings.ipc.subnet_id.clone(),
+        parent_endpoint: topdown_config.parent_http_endpoint.to_string(),
+        parent_gateway: topdown_config.parent_gateway,
+        parent_registry: topdown_config.parent_registry,
+    })
+}
+
+fn create_operator_lane(settings: &Settings) -> Option<OperatorLane> {
+    let lane = &settings.abci.operator_lane;
+    if !lane.enabled {
+        return None;
+    }
+    if lane.operators.is_empty() {
+        tracing::warn!("the operator lane is enabled without any operator keys");
+    }
+    Some(OperatorLane::new(OperatorLaneConfig {
+        operators: lane.operators.iter().cloned().collect(),
+        max_txs: lane.max_txs,
+        max_bytes: lane.max_bytes,
+        inclusion_blocks: lane.inclusion_blocks,
+        subnet_id: settings.ipc.subnet_id.to_string(),
+    }))
+}
+
+/// Open the SQLite index of the executed blocks, if it's enabled.
+#[cfg(feature = "sqlite")]
+fn create_event_index(settings: &Settings) -> anyhow::Result<Option<Arc<dyn EventIndex>>> {
+    if !settings.event_index.sqlite.enabled {
+        return Ok(None);
+    }
+    let path = settings.event_index.sqlite.path(settings.home_dir());
+    let index = fendermint_app::index::sqlite::SqliteEventIndex::open(&path)
+        .context("error opening SQLite event index")?;
+    tracing::info!(
+        path = path.to_string_lossy().into_owned(),
+        last_height = index.last_height()?,
+        "indexing events into SQLite"
+    );
+    Ok(Some(Arc::new(index)))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn create_event_index(settings: &Settings) -> anyhow::Result<Option<Arc<dyn EventIndex>>> {
+    if settings.event_index.sqlite.enabled {
+        bail!("the SQLite event index needs a binary built with the `sqlite` feature");
+    }
+    Ok(None)
+}
+
+/// How long to wait between attempts to connect to the CometBFT websocket of an instance.
+const ETH_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Maximum block size of the development chain; the default of `genesis into-tendermint`.
+const DEV_BLOCK_MAX_BYTES: u64 = 22020096;
+
 cmd! {
   RunArgs(self, settings) {
-    run(settings).await
+    let instances = settings
+        .instance_settings()
+        .context("failed to load the settings of the instances")?;
+
+    // The metrics of every instance are registered in the same registry, so they are served once.
+    if settings.metrics.enabled {
+        let listen = settings.metrics.listen.clone();
+        tokio::spawn(async move {
+            if let Err(e) = fendermint_app::metrics::serve(listen).await {
+                tracing::error!(error = e.to_string(), "metrics server failed");
+            }
+        });
+    }
+
+    if self.dev {
+        if !instances.is_empty() {
+            bail!("the development chain cannot host other instances");
+        }
+        let genesis_file = self
+            .dev_genesis_file
+            .as_ref()
+            .ok_or_else(|| anyhow!("the development chain needs a genesis file"))?;
+        let genesis = read_genesis(genesis_file)?;
+        let dev = DevChainConfig {
+            genesis: to_tendermint_genesis(&genesis, DEV_BLOCK_MAX_BYTES)?,
+            proposer: None,
+            block_time: self.dev_block_time.map(Duration::from_secs),
+        };
+        return run(settings, self.halt_height, Some(dev)).await;
+    }
+
+    if instances.is_empty() {
+        return run(settings, self.halt_height, None).await;
+    }
+
+    let instances = settings
+        .instances
+        .iter()
+        .map(|i| i.eth_api)
+        .zip(instances)
+        .collect::<Vec<_>>();
+
+    check_instances(&settings, &instances)?;
+
+    let mut tasks = tokio::task::JoinSet::new();
+
+    for (eth_api, instance) in instances {
+        let subnet_id = instance.ipc.subnet_id.to_string();
+        let span = tracing::info_span!("instance", subnet_id);
+        if eth_api {
+            let _guard = span.enter();
+            spawn_eth_api(&instance)?;
+        }
+        tasks.spawn(run(instance, None, None).instrument(span));
+    }
+    tasks.spawn(run(settings, self.halt_height, None));
+
+    // If any of the instances fails, bring down the whole process, as if it was on its own.
+    while let Some(res) = tasks.join_next().await {
+        res.context("instance panicked")??;
+    }
+
+    Ok(())
   }
 }
 
+/// Check that the instances hosted in the process don't share directories or addresses.
+fn check_instances(settings: &Settings, instances: &[(bool, Settings)]) -> anyhow::Result<()> {
+    if let Some((_, s)) = instances.iter().find(|(_, s)| !s.instances.is_empty()) {
+        bail!(
+            "instance at {} cannot host further instances",
+            s.home_dir().to_string_lossy()
+        );
+    }
+
+    let mut data_dirs = HashSet::new();
+    let mut snapshots_dirs = HashSet::new();
+    let mut addrs = HashSet::new();
+
+    let all = std::iter::once((false, settings)).chain(instances.iter().map(|(e, s)| (*e, s)));
+
+    for (eth_api, s) in all {
+        let home_dir = s.home_dir().to_string_lossy().into_owned();
+
+        if !data_dirs.insert(s.data_dir()) {
+            bail!("instance at {home_dir} shares its data directory with another one");
+        }
+        if s.snapshots.enabled && !snapshots_dirs.insert(s.snapshots_dir()) {
+            bail!("instance at {home_dir} shares its snapshots directory with another one");
+        }
+
+        let mut listen = vec![("ABCI", s.abci.listen.to_string())];
+        if s.admin.enabled {
+            listen.push(("admin", s.admin.listen.to_string()));
+        }
+        // A random port, which libp2p picks when it's 0, can't collide.
+        let random_port = s
+            .resolver
+            .connection
+            .listen_addr
+            .iter()
+            .any(|p| matches!(p, Protocol::Tcp(0) | Protocol::Udp(0)));
+        if s.resolver.enabled() && !random_port {
+            listen.push(("resolver", s.resolver.connection.listen_addr.to_string()));
+        }
+        if eth_api {
+            for ep in s.eth.all_endpoints() {
+                listen.push(("Ethereum API", ep.listen.to_string()));
+            }
+        }
+        for (what, addr) in listen {
+            if !addrs.insert(addr.clone()) {
+                bail!("the {what} address {addr} of the instance at {home_dir} is already in use by another one");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serve the Ethereum API of an instance in the background.
+fn spawn_eth_api(settings: &Settings) -> anyhow::Result<()> {
+    let endpoints = cmd::eth::to_endpoints(&settings.eth, &settings.home_dir())?;
+    let http_url = settings.tendermint_rpc_url()?;
+    let ws_url = to_websocket_url(&http_url)?;
+
+    let (client, driver) = HybridClient::new(http_url, ws_url, ETH_CONNECT_RETRY_DELAY)
+        .context("failed to create HybridClient")?;
+
+    tokio::spawn(async move { driver.run().await }.in_current_span());
+
+    let eth = settings.eth.clone();
+    tokio::spawn(
+        async move {
+            if let Err(e) = cmd::eth::run(eth, endpoints, client).await {
+                tracing::error!(error = e.to_string(), "Ethereum API failed");
+            }
+        }
+        .in_current_span(),
+    );
+
+    Ok(())
+}
+
+/// CometBFT serves its websocket under the same address as the RPC.
+fn to_websocket_url(http_url: &Url) -> anyhow::Result<WebSocketClientUrl> {
+    let url = http_url.to_string().replacen("http", "ws", 1);
+    let url = format!("{}/websocket", url.trim_end_matches('/'));
+    url.parse()
+        .map_err(|e| anyhow!("invalid websocket URL {url}: {e}"))
+}
+
 /// Run the Fendermint ABCI Application.
 ///
 /// This method acts as our composition root.
-async fn run(settings: Settings) -> anyhow::Result<()> {
+///
+/// With a development chain configured, the application is driven by it instead of CometBFT.
+async fn run(
+    settings: Settings,
+    halt_height: Option<u64>,
+    dev: Option<DevChainConfig>,
+) -> anyhow::Result<()> {
     let tendermint_rpc_url = settings.tendermint_rpc_url()?;
     tracing::info!("Connecting to Tendermint at {tendermint_rpc_url}");
 
@@ -72,8 +385,45 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         tendermint_rpc::HttpClient::new(tendermint_rpc_url)
             .context("failed to create Tendermint client")?;
 
-    let validator = match settings.validator_key {
-        Some(ref key) => {
+    let genesis_car = &settings.genesis_car;
+
+    if let Some(url) = genesis_car.url.as_ref().filter(|u| !u.is_empty()) {
+        tracing::info!(url, "fetching the genesis CAR file");
+        fendermint_app::genesiscar::download(
+            url,
+            genesis_car.checksum.as_deref().filter(|c| !c.is_empty()),
+            genesis_car.chunk_size,
+            &settings.builtin_actors_bundle(),
+        )
+        .await
+        .context("failed to download the genesis CAR file")?;
+    }
+
+    if genesis_car.serve {
+        let listen = genesis_car.listen.clone();
+        let path = settings.builtin_actors_bundle();
+        tokio::spawn(async move {
+            if let Err(e) = fendermint_app::genesiscar::serve(listen, path).await {
+                tracing::error!(error = e.to_string(), "genesis CAR server failed");
+            }
+        });
+    }
+
+    let validator = match (&settings.validator_key, &settings.validator_account) {
+        (Some(_), Some(_)) => {
+            bail!("only one of `validator_key` and `validator_account` can be configured")
+        }
+        (None, Some(name)) => {
+            let ks = Keystore::open(&settings.keystore.dir(settings.home_dir()))?;
+            let kf = ks.get(name).context("failed to find validator account")?;
+            let sk = kf
+                .secret_key(&settings.keystore.password)
+                .context("failed to read validator key")?;
+            let addr = kf.address()?;
+            tracing::info!("validator account {name} address: {addr} detected");
+            Some((sk, addr))
+        }
+        (Some(key), None) => {
             let sk = key.path(settings.home_dir());
             if sk.exists() && sk.is_file() {
                 let sk = read_secret_key(&sk).context("failed to read validator key")?;
@@ -84,12 +434,15 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
                 bail!("validator key does not exist: {}", sk.to_string_lossy());
             }
         }
-        None => {
+        (None, None) => {
             tracing::debug!("validator key not configured");
             None
         }
     };
 
+    let validator_key = validator.as_ref().map(|(sk, _)| sk.public_key());
+    let vote_key = validator.as_ref().map(|(sk, _)| sk.clone());
+
     let validator_ctx = validator.map(|(sk, addr)| {
         // For now we are using the validator key for submitting transactions.
         // This allows us to identify transactions coming from bonded validators, to give priority to protocol related transactions.
@@ -104,8 +457,29 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         .with_max_retries(settings.broadcast.max_retries)
         .with_retry_delay(settings.broadcast.retry_delay);
 
-        ValidatorContext::new(sk, broadcaster)
+        let broadcaster = if settings.broadcast.outbox.enabled {
+            let opt = &settings.broadcast.outbox;
+            let outbox = Outbox::open(OutboxOpt {
+                dir: settings.outbox_dir(),
+                max_attempts: opt.max_attempts,
+                resubmit_after: opt.resubmit_after,
+                max_backoff: opt.max_backoff,
+                stuck_after: opt.stuck_after,
+                subnet_id: settings.ipc.subnet_id.to_string(),
+            })
+            .await
+            .context("failed to open the outbox")?;
+
+            let broadcaster = broadcaster.with_outbox(outbox);
+            tokio::spawn(broadcaster.clone().run_outbox());
+            broadcaster
+        } else {
+            broadcaster
+        };
+
+        anyhow::Ok(ValidatorContext::new(sk, broadcaster))
     });
+    let validator_ctx = validator_ctx.transpose()?;
 
     let interpreter = FvmMessageInterpreter::<NamespaceBlockstore, _>::new(
         tendermint_client.clone(),
@@ -114,11 +488,26 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         settings.fvm.gas_overestimation_rate,
         settings.fvm.gas_search_step,
         settings.fvm.exec_in_check,
-    );
-    let interpreter = SignedMessageInterpreter::new(interpreter);
-    let interpreter = ChainMessageInterpreter::<_, NamespaceBlockstore>::new(interpreter);
-    let interpreter =
-        BytesMessageInterpreter::new(interpreter, ProposalPrepareMode::AppendOnly, false);
+    )
+    .with_deferred_check(settings.fvm.defer_check)
+    .with_admission(AdmissionRules {
+        cover_value: settings.fvm.admission.cover_value,
+        min_balance: settings.fvm.admission.min_balance.clone(),
+    })
+    .with_checkpoint_archive(settings.checkpoint_archive_dir());
+
+    let interpreter = match settings.policy_module() {
+        Some(path) => {
+            let policy = PolicyModule::load(&path).context("failed to load the policy module")?;
+            tracing::info!(
+                "policy module {} loaded from {}",
+                policy.cid(),
+                path.to_string_lossy()
+            );
+            interpreter.with_policy(policy)
+        }
+        None => interpreter,
+    };
 
     let ns = Namespaces::default();
     let db = open_db(&settings, &ns).context("error opening DB")?;
@@ -127,10 +516,45 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
     let state_store =
         NamespaceBlockstore::new(db.clone(), ns.state_store).context("error creating state DB")?;
 
-    let resolve_pool = CheckpointPool::new();
+    #[cfg(feature = "block-stm")]
+    let interpreter = if settings.fvm.block_stm.enabled {
+        let opt = &settings.fvm.block_stm;
+        tracing::warn!(
+            threads = opt.threads,
+            determinism_check = opt.determinism_check,
+            "Block-STM experiment enabled"
+        );
+        interpreter.with_block_stm(
+            BlockStm::new(state_store.clone(), opt.threads, opt.determinism_check)
+                .with_subnet_id(settings.ipc.subnet_id.to_string()),
+        )
+    } else {
+        interpreter
+    };
+
+    #[cfg(not(feature = "block-stm"))]
+    if settings.fvm.block_stm.enabled {
+        bail!("the Block-STM experiment requires building with the `block-stm` feature");
+    }
+
+    let interpreter = InterpreterBuilder::new(interpreter)
+        .signed(settings.abci.msg_cache_size)
+        .chain()
+        .bytes(
+            ProposalPrepareMode::AppendOnly,
+            false,
+            settings.abci.msg_cache_size,
+        )
+        .build();
+
+    let resolve_pool = CheckpointPool::with_limits(ResolveLimits {
+        max_in_flight: settings.resolver.max_in_flight,
+        ttl: Some(settings.resolver.resolve_ttl).filter(|ttl| !ttl.is_zero()),
+    });
 
     // If enabled, start a resolver that communicates with the application through the resolve pool.
-    if settings.resolver.enabled() {
+    // The client and the events are kept to gossip the parent votes once the finality provider exists.
+    let vote_gossip = if settings.resolver.enabled() {
         let service =
             make_resolver_service(&settings, db.clone(), state_store.clone(), ns.bit_store)?;
 
@@ -142,6 +566,8 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
             .add_provided_subnet(own_subnet_id.clone())
             .context("error adding own provided subnet.")?;
 
+        let vote_gossip = (client.clone(), service.subscribe(), own_subnet_id.clone());
+
         let resolver = IpldResolver::new(
             client,
             resolve_pool.queue(),
@@ -158,9 +584,22 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
 
         tracing::info!("starting the IPLD Resolver...");
         tokio::spawn(async move { resolver.run().await });
+
+        Some(vote_gossip)
     } else {
-        tracing::info!("IPLD Resolver disabled.")
-    }
+        tracing::info!("IPLD Resolver disabled.");
+        None
+    };
+
+    let vector_recorder = if settings.test_vectors.enabled {
+        let dir = settings.test_vectors.dir(settings.home_dir());
+        Some(
+            VectorRecorder::new(dir, settings.test_vectors.max_blocks)
+                .context("error creating test vector recorder")?,
+        )
+    } else {
+        None
+    };
 
     let (parent_finality_provider, ipc_tuple) = if settings.ipc.is_topdown_enabled() {
         info!("topdown finality enabled");
@@ -172,17 +611,54 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
             topdown_config.exponential_retry_limit,
         )
         .with_proposal_delay(topdown_config.proposal_delay)
-        .with_max_proposal_range(topdown_config.max_proposal_range);
+        .with_max_proposal_range(topdown_config.max_proposal_range)
+        .with_subnet_id(settings.ipc.subnet_id.to_string());
+        let config = match topdown_config.min_polling_interval {
+            Some(d) => config.with_min_polling_interval(d),
+            None => config,
+        };
+        let config = match topdown_config.max_cache_blocks {
+            Some(n) => config.with_max_cache_blocks(n),
+            None => config,
+        };
+        let config = match topdown_config.max_cache_msgs {
+            Some(n) => config.with_max_cache_msgs(n),
+            None => config,
+        };
         let ipc_provider = Arc::new(create_ipc_provider_proxy(&settings)?);
+        // Record what the parent answers, so the top-down messages in the vectors can be replayed.
+        let parent_client: DynParentQueryProxy = match vector_recorder {
+            Some(ref recorder) => recorder.parent_proxy(ipc_provider.clone()),
+            None => ipc_provider.clone(),
+        };
+        let parent_client = Arc::new(parent_client);
         let finality_provider =
-            CachedFinalityProvider::uninitialized(config.clone(), ipc_provider.clone()).await?;
+            CachedFinalityProvider::uninitialized(config.clone(), parent_client.clone()).await?;
         let p = Arc::new(Toggle::enabled(finality_provider));
-        (p, Some((ipc_provider, config)))
+        (p, Some((ipc_provider, parent_client, config)))
     } else {
         info!("topdown finality disabled");
         (Arc::new(Toggle::disabled()), None)
     };
 
+    // Everyone counts the votes, but only validators cast them.
+    if let (Some((client, events, own_subnet_id)), true) =
+        (vote_gossip, parent_finality_provider.is_enabled())
+    {
+        let provider = parent_finality_provider.clone();
+        let subnet_id = own_subnet_id.clone();
+        tokio::spawn(async move { votes::receive_votes(events, provider, subnet_id).await });
+
+        let vote_interval = settings.ipc.topdown_config()?.vote_interval;
+        if let (Some(sk), Some(interval)) = (vote_key, vote_interval) {
+            tracing::info!("starting the parent vote gossip...");
+            let provider = parent_finality_provider.clone();
+            tokio::spawn(async move {
+                votes::publish_votes(provider, client, own_subnet_id, sk, interval).await
+            });
+        }
+    }
+
     // Start a snapshot manager in the background.
     let snapshots = if settings.snapshots.enabled {
         let (manager, client) = SnapshotManager::new(
@@ -192,13 +668,22 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
                 download_dir: settings.snapshots.download_dir(),
                 block_interval: settings.snapshots.block_interval,
                 chunk_size: settings.snapshots.chunk_size_bytes,
+                max_chunks: settings.snapshots.max_chunks,
+                restore_concurrency: settings.snapshots.restore_concurrency,
                 hist_size: settings.snapshots.hist_size,
                 last_access_hold: settings.snapshots.last_access_hold,
                 sync_poll_interval: settings.snapshots.sync_poll_interval,
+                compression: match settings.snapshots.compression {
+                    SnapshotCompressionSettings::None => SnapshotCompression::None,
+                    SnapshotCompressionSettings::Zstd => SnapshotCompression::Zstd,
+                },
+                compression_level: settings.snapshots.compression_level,
             },
         )
         .context("failed to create snapshot manager")?;
 
+        let manager = manager.with_publishers(create_snapshot_publishers(&settings));
+
         tracing::info!("starting the SnapshotManager...");
         let tendermint_client = tendermint_client.clone();
         tokio::spawn(async move { manager.run(tendermint_client).await });
@@ -209,12 +694,56 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         None
     };
 
+    let exporter = if settings.export.enabled {
+        let path = settings.export.path(settings.home_dir());
+        let sink = match settings.export.format {
+            ExportFormat::Csv => ExportSink::Csv {
+                dir: path,
+                rotate_blocks: settings.export.rotate_blocks,
+            },
+            ExportFormat::Socket => ExportSink::Socket { path },
+        };
+        Some(Exporter::new(sink).context("error creating exporter")?)
+    } else {
+        None
+    };
+
+    let halt_height = HaltHeight::new(halt_height.or(settings.abci.halt_height));
+    if let Some(h) = halt_height.get() {
+        tracing::warn!(
+            halt_height = h,
+            "the application will halt after this height"
+        );
+    }
+
     let app: App<_, _, AppStore, _> = App::new(
         AppConfig {
             app_namespace: ns.app,
             state_hist_namespace: ns.state_hist,
             state_hist_size: settings.db.state_hist_size,
+            logs_bloom_namespace: ns.logs_bloom,
+            block_hashes_namespace: ns.block_hashes,
+            eth_block_hashes_namespace: ns.eth_block_hashes,
+            gas_stats_namespace: ns.gas_stats,
+            gas_stats_blocks: settings.abci.gas_stats_blocks,
+            validator_sets_namespace: ns.validator_sets,
+            bottom_up_queue_namespace: ns.bottom_up_queue,
+            topdown_traces_namespace: ns.topdown_traces,
+            bottomup_traces_namespace: ns.bottomup_traces,
+            msg_traces: settings.ipc.msg_traces,
             builtin_actors_bundle: settings.builtin_actors_bundle(),
+            tx_dedup_blocks: settings.abci.tx_dedup_blocks,
+            tracing_enabled: settings.tracing.otlp.enabled,
+            exporter,
+            vector_recorder,
+            halt_height: halt_height.clone(),
+            time_monitor: TimeMonitor::new(
+                Arc::new(SystemClock),
+                settings.ipc.subnet_id.to_string(),
+            ),
+            defer_check: settings.fvm.defer_check,
+            query_budget: to_query_budget(&settings.abci.query_budget),
+            subnet_id: settings.ipc.subnet_id.to_string(),
         },
         db,
         state_store,
@@ -224,14 +753,127 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         snapshots,
     )?;
 
-    if let Some((agent_proxy, config)) = ipc_tuple {
+    let app = match create_genesis_check(&settings) {
+        Some(check) => app.with_parent_genesis_check(check),
+        None => app,
+    };
+
+    let app = if settings.abci.proposal_signature_budget.is_zero() {
+        app
+    } else {
+        app.with_proposal_signature_budget(settings.abci.proposal_signature_budget)
+    };
+
+    let app = match settings.abci.block_signature_threads {
+        0 => app,
+        threads => app.with_block_signature_threads(threads)?,
+    };
+
+    let app = app.with_max_bottom_up_queue_depth(settings.ipc.max_bottom_up_queue_depth);
+
+    let app = if settings.ipc.is_topdown_enabled() {
+        let app = app.with_divergence_action(settings.ipc.topdown_config()?.divergence_action);
+        app.persist_divergence()?;
+        app
+    } else {
+        app
+    };
+
+    let app = match create_event_index(&settings)? {
+        Some(index) => app.with_event_index(index),
+        None => app,
+    };
+
+    let operator_lane = create_operator_lane(&settings);
+    let app = match operator_lane {
+        Some(ref lane) => app.with_operator_lane(lane.clone()),
+        None => app,
+    };
+
+    // Only the operator can evict transactions, through the admin endpoint.
+    let mempool_evictions = settings.admin.enabled.then(MempoolEvictions::default);
+    let app = match mempool_evictions {
+        Some(ref evictions) => app.with_mempool_evictions(evictions.clone())?,
+        None => app,
+    };
+
+    let relayer = match create_relayer_monitor(&settings)? {
+        Some(monitor) => {
+            let status = monitor.status();
+            tokio::spawn(monitor.run());
+            Some(status)
+        }
+        None => None,
+    };
+
+    // The development chain only produces blocks when there are transactions.
+    if settings.watchdog.enabled && dev.is_none() {
+        let watchdog = Watchdog::new(
+            WatchdogConfig {
+                stall_timeout: settings.watchdog.stall_timeout(),
+                check_interval: settings.watchdog.expected_block_time,
+                dump_dir: settings.watchdog.dump_dir(settings.home_dir()),
+                subnet_id: settings.ipc.subnet_id.to_string(),
+            },
+            app.progress(),
+            app.rejected_proposals(),
+            parent_finality_provider.clone(),
+            ipc_tuple.as_ref().map(|(proxy, _, _)| proxy.breaker()),
+            settings.tendermint_rpc_url()?,
+        );
+        tokio::spawn(watchdog.run());
+    }
+
+    if settings.admin.enabled {
+        let listen = settings.admin.listen.clone();
+        let parent = ipc_tuple.as_ref().map(|(proxy, _, _)| proxy.breaker());
+        let divergence = parent_finality_provider.divergence().cloned();
+        // Queries through the admin endpoint come from the operator, who can set different limits.
+        let rpc = fendermint_app::readonly::router(
+            app.clone()
+                .with_query_budget(to_query_budget(&settings.admin.query_budget))
+                .with_admin_queries(true),
+            settings.tendermint_rpc_url()?,
+        );
+        let tendermint_url = settings.tendermint_rpc_url()?;
+        let mempool = mempool_evictions
+            .map(|evictions| MempoolAdmin::new(Arc::new(app.clone()), evictions, tendermint_url));
+        tokio::spawn(async move {
+            if let Err(e) = fendermint_app::admin::serve(
+                listen,
+                halt_height,
+                relayer,
+                parent,
+                divergence,
+                operator_lane,
+                mempool,
+                Some(rpc),
+            )
+            .await
+            {
+                tracing::error!(error = e.to_string(), "admin server failed");
+            }
+        });
+    }
+
+    {
+        let app = app.clone();
+        let client = tendermint_client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = app.backfill_eth_block_hashes(client).await {
+                tracing::error!(error = e.to_string(), "failed to backfill the block hashes");
+            }
+        });
+    }
+
+    if let Some((_, parent_client, config)) = ipc_tuple {
         let app_parent_finality_query = AppParentFinalityQuery::new(app.clone());
         tokio::spawn(async move {
             match launch_polling_syncer(
                 app_parent_finality_query,
                 config,
                 parent_finality_provider,
-                agent_proxy,
+                parent_client,
                 tendermint_client,
             )
             .await
@@ -242,6 +884,18 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
         });
     }
 
+    if let Some(dev) = dev {
+        let url = settings.tendermint_rpc_url()?;
+        let listen = format!("{}:{}", url.host(), url.port());
+        let proposer = validator_key
+            .map(|pk| tendermint::PublicKey::from_raw_secp256k1(&pk.serialize()))
+            .map(|pk| pk.ok_or_else(|| anyhow!("invalid validator public key")))
+            .transpose()?;
+        let dev = DevChainConfig { proposer, ..dev };
+
+        return fendermint_app::devchain::run(listen, app, dev).await;
+    }
+
     let service = ApplicationService(app);
 
     // Split it into components.
@@ -266,23 +920,17 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
     Ok(())
 }
 
-namespaces! {
-    Namespaces {
-        app,
-        state_hist,
-        state_store,
-        bit_store
-    }
-}
-
 /// Open database with all
 fn open_db(settings: &Settings, ns: &Namespaces) -> anyhow::Result<RocksDb> {
     let path = settings.data_dir().join("rocksdb");
+    let config = settings.db.rocksdb.config();
     info!(
         path = path.to_string_lossy().into_owned(),
+        profile = ?settings.db.rocksdb.profile,
+        ?config,
         "opening database"
     );
-    let db = RocksDb::open_cf(path, &RocksDbConfig::default(), ns.values().iter())?;
+    let db = RocksDb::open_cf(path, &config, ns.values().iter())?;
     Ok(db)
 }
 
@@ -366,3 +1014,84 @@ fn to_address(sk: &SecretKey, kind: &AccountKind) -> anyhow::Result<Address> {
         AccountKind::Ethereum => Ok(Address::from(EthAddress::new_secp256k1(&pk)?)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use crate::settings::{InstanceSettings, Settings};
+
+    use super::check_instances;
+
+    fn settings(home_dir: &str, abci_port: u32) -> Settings {
+        let mut settings = Settings::new(Path::new("config"), Path::new(home_dir), "").unwrap();
+        settings.abci.listen.port = abci_port;
+        settings
+    }
+
+    #[test]
+    fn instances_must_not_share_dirs_or_addresses() {
+        let main = settings("/tmp/main", 26658);
+
+        let ok = vec![(false, settings("/tmp/foo", 26668))];
+        assert!(check_instances(&main, &ok).is_ok());
+
+        let same_port = vec![(false, settings("/tmp/foo", 26658))];
+        let err = check_instances(&main, &same_port).unwrap_err();
+        assert!(err.to_string().contains("ABCI address"), "{err}");
+
+        let same_dir = vec![(false, settings("/tmp/main", 26668))];
+        let err = check_instances(&main, &same_dir).unwrap_err();
+        assert!(err.to_string().contains("data directory"), "{err}");
+
+        // The Ethereum API endpoints are only checked for the instances serving them.
+        let eth = vec![
+            (true, settings("/tmp/foo", 26668)),
+            (false, settings("/tmp/bar", 26678)),
+        ];
+        assert!(check_instances(&main, &eth).is_ok());
+        let eth = vec![
+            (true, settings("/tmp/foo", 26668)),
+            (true, settings("/tmp/bar", 26678)),
+        ];
+        let err = check_instances(&main, &eth).unwrap_err();
+        assert!(err.to_string().contains("Ethereum API address"), "{err}");
+
+        // The resolver addresses are only checked for the instances running one,
+        // and random ports can't collide.
+        let with_resolver = |home_dir, abci_port, listen_addr: &str| {
+            let mut s = settings(home_dir, abci_port);
+            s.resolver.subnet_id = SubnetID::from_str("/r314159").unwrap();
+            s.resolver.connection.listen_addr = listen_addr.parse().unwrap();
+            (false, s)
+        };
+        let foo = |listen_addr| with_resolver("/tmp/foo", 26668, listen_addr);
+        let bar = |listen_addr| with_resolver("/tmp/bar", 26678, listen_addr);
+
+        let resolver = vec![foo("/ip4/127.0.0.1/tcp/26655")];
+        assert!(check_instances(&main, &resolver).is_ok());
+        let resolver = vec![
+            foo("/ip4/127.0.0.1/tcp/26655"),
+            bar("/ip4/127.0.0.1/tcp/26655"),
+        ];
+        let err = check_instances(&main, &resolver).unwrap_err();
+        assert!(err.to_string().contains("resolver address"), "{err}");
+        let resolver = vec![foo("/ip4/127.0.0.1/tcp/0"), bar("/ip4/127.0.0.1/tcp/0")];
+        assert!(check_instances(&main, &resolver).is_ok());
+
+        let mut nested = settings("/tmp/foo", 26668);
+        nested.instances.push(InstanceSettings {
+            home_dir: PathBuf::from("bar"),
+            config_dir: PathBuf::from("config"),
+            eth_api: false,
+        });
+        let err = check_instances(&main, &[(false, nested)]).unwrap_err();
+        assert!(
+            err.to_string().contains("cannot host further instances"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::collections::{BTreeMap, HashMap};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::IPLD_RAW;
+
+use crate::BlockHeight;
+
+/// Content identifier of a raw transaction, used to recognise duplicates.
+pub fn tx_cid(tx: &[u8]) -> Cid {
+    Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(tx))
+}
+
+/// Transactions which have been seen recently, either by passing `CheckTx` and
+/// being added to the mempool, or by being included in one of the last few blocks.
+///
+/// It is used to reject duplicates in `CheckTx` which arrive due to gossip races or
+/// clients resubmitting transactions, before they are executed again. This is not
+/// part of the consensus, it's a local optimisation: after a restart the node forgets
+/// what it has seen, and a transaction staying in the mempool for longer than the
+/// configured number of blocks will not be recognised as a duplicate either.
+#[derive(Debug, Default)]
+pub struct RecentTxs {
+    /// Number of blocks to remember transactions for; 0 means deduplication is disabled.
+    num_blocks: u64,
+    /// Last committed block height.
+    block_height: BlockHeight,
+    /// Latest height at which each transaction has been seen.
+    seen: HashMap<Cid, BlockHeight>,
+    /// Transactions by the height they were seen at, to facilitate pruning.
+    by_height: BTreeMap<BlockHeight, Vec<Cid>>,
+    /// Transactions delivered in the block currently being executed.
+    delivered: Vec<Cid>,
+}
+
+impl RecentTxs {
+    pub fn new(num_blocks: u64) -> Self {
+        Self {
+            num_blocks,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.num_blocks > 0
+    }
+
+    /// Check whether the transaction has been seen recently.
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.seen.contains_key(cid)
+    }
+
+    /// Remember a transaction which has been accepted into the mempool.
+    pub fn checked(&mut self, cid: Cid) {
+        if self.is_enabled() {
+            self.insert(cid, self.block_height + 1);
+        }
+    }
+
+    /// Remember a transaction which has been included in the block being executed.
+    pub fn delivered(&mut self, cid: Cid) {
+        if self.is_enabled() {
+            self.delivered.push(cid);
+        }
+    }
+
+    /// Record the delivered transactions at the committed height and forget
+    /// about the ones which have fallen out of the window of recent blocks.
+    pub fn committed(&mut self, block_height: BlockHeight) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.block_height = block_height;
+
+        for cid in std::mem::take(&mut self.delivered) {
+            self.insert(cid, block_height);
+        }
+
+        let min_height = block_height.saturating_sub(self.num_blocks) + 1;
+        let keep = self.by_height.split_off(&min_height);
+        let prune = std::mem::replace(&mut self.by_height, keep);
+
+        for (height, cids) in prune {
+            for cid in cids {
+                // The transaction might have been seen again at a later height.
+                if self.seen.get(&cid) == Some(&height) {
+                    self.seen.remove(&cid);
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, cid: Cid, block_height: BlockHeight) {
+        self.seen.insert(cid, block_height);
+        self.by_height.entry(block_height).or_default().push(cid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tx_cid, RecentTxs};
+
+    #[test]
+    fn recent_txs_window() {
+        let mut txs = RecentTxs::new(2);
+        let a = tx_cid(b"a");
+        let b = tx_cid(b"b");
+
+        // Accepted into the mempool while block 1 is pending.
+        txs.checked(a);
+        assert!(txs.contains(&a));
+
+        // Included in block 2.
+        txs.committed(1);
+        txs.delivered(a);
+        txs.committed(2);
+        assert!(txs.contains(&a));
+
+        txs.checked(b);
+        txs.committed(3);
+        assert!(txs.contains(&a));
+        assert!(txs.contains(&b));
+
+        // Block 2 falls out of the window.
+        txs.committed(4);
+        assert!(!txs.contains(&a));
+        assert!(txs.contains(&b));
+
+        txs.committed(5);
+        assert!(!txs.contains(&b));
+    }
+
+    #[test]
+    fn recent_txs_disabled() {
+        let mut txs = RecentTxs::new(0);
+        let a = tx_cid(b"a");
+        txs.checked(a);
+        txs.committed(1);
+        assert!(!txs.contains(&a));
+    }
+}
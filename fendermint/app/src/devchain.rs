@@ -0,0 +1,1247 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A chain for local development, which runs the application without CometBFT.
+//!
+//! Instead of waiting for consensus, the node seals a block with every transaction it receives,
+//! so the transaction is executed by the time the broadcast returns. Blocks are only produced on
+//! demand, or at a fixed interval if configured, and two extra JSON-RPC methods give tests
+//! control over the chain:
+//! * `dev_mine` with `{"blocks": n}` seals that many empty blocks, up to 10,000 at a time;
+//! * `dev_warp` with `{"seconds": n}` moves the clock of the chain forward, or with
+//!   `{"timestamp": t}` sets the time of the next block.
+//!
+//! The endpoint speaks the subset of the CometBFT JSON-RPC the Fendermint clients and the
+//! Ethereum API use, so they can point at it as if it was CometBFT. There are no websocket
+//! subscriptions, and the blocks are only kept in memory, so after a restart the chain carries
+//! on from the state of the application but the earlier blocks cannot be looked up.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::net::ToSocketAddrs;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context};
+use axum::{body::Bytes, extract::State, response::Response, routing::post, Router};
+use base64::Engine;
+use fendermint_abci::Application;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tendermint::abci::request::CheckTxKind;
+use tendermint::abci::types::CommitInfo;
+use tendermint::abci::{request, response, Event};
+use tendermint::block::{self, parts, Block, Commit, CommitSig, Header, Height, Round};
+use tendermint::{account, validator, AppHash, Hash, PublicKey, Time};
+use tendermint_rpc::query::{Operand, Operation, Query};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::readonly::{
+    abci_info, abci_query, rpc_response, RpcResult, INTERNAL_ERROR, INVALID_PARAMS,
+    METHOD_NOT_FOUND, PARSE_ERROR,
+};
+
+/// Version of the block protocol the headers claim to follow, the same as CometBFT 0.37.
+const BLOCK_PROTOCOL_VERSION: u64 = 11;
+
+/// Number of requests waiting for the sealer before the RPC handlers have to wait.
+const COMMAND_QUEUE_SIZE: usize = 100;
+
+/// Most blocks a single `dev_mine` call can seal, so one request cannot tie up the sealer.
+const MAX_MINE_BLOCKS: u64 = 10_000;
+
+/// Settings of the development chain.
+pub struct DevChainConfig {
+    /// The genesis, as CometBFT would get it.
+    pub genesis: tendermint::Genesis<Value>,
+    /// Key of the validator the blocks are attributed to; the first genesis validator if empty.
+    pub proposer: Option<PublicKey>,
+    /// Seal an empty block when no transaction arrived for this long.
+    pub block_time: Option<Duration>,
+}
+
+/// Run the chain and serve its JSON-RPC endpoint, until the process exits or the application fails.
+pub async fn run<L, A>(listen: L, app: A, config: DevChainConfig) -> anyhow::Result<()>
+where
+    L: ToSocketAddrs,
+    A: Application + Clone + Send + Sync + 'static,
+{
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))?;
+
+    let chain = Arc::new(RwLock::new(Chain::default()));
+    let sealer = Sealer::start(app.clone(), chain.clone(), &config).await?;
+    let (commands_tx, commands_rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+
+    let state = Arc::new(DevState {
+        app,
+        chain,
+        commands: commands_tx,
+        genesis: config.genesis,
+        proposer: config.proposer,
+        listen: addr.to_string(),
+    });
+
+    let router = Router::new()
+        .route("/", post(handle::<A>))
+        .with_state(state);
+
+    tracing::info!(?addr, "serving the development chain");
+
+    let server = axum::Server::bind(&addr).serve(router.into_make_service());
+
+    tokio::select! {
+        res = sealer.run(commands_rx, config.block_time) => res.context("the development chain failed"),
+        res = server => res.context("failed to serve the development chain"),
+    }
+}
+
+/// A block along with the results of executing it.
+struct SealedBlock {
+    block: Block,
+    block_id: block::Id,
+    begin_block: response::BeginBlock,
+    txs_results: Vec<response::DeliverTx>,
+    end_block: response::EndBlock,
+}
+
+/// The blocks sealed since the node started, and what the clients can look up about them.
+#[derive(Default)]
+struct Chain {
+    blocks: BTreeMap<u64, Arc<SealedBlock>>,
+    by_hash: HashMap<Vec<u8>, u64>,
+    /// Height and index of the transactions, by their SHA-256 hash.
+    txs: HashMap<[u8; 32], (u64, usize)>,
+    /// The current validator set.
+    validators: Vec<validator::Info>,
+    /// The app hash after the last commit.
+    app_hash: AppHash,
+}
+
+impl Chain {
+    fn update_validators(&mut self, updates: &[validator::Update]) {
+        for update in updates {
+            let address = account::Id::from(update.pub_key);
+            self.validators.retain(|v| v.address != address);
+            if update.power.value() > 0 {
+                self.validators
+                    .push(validator::Info::new(update.pub_key, update.power));
+            }
+        }
+    }
+
+    fn latest(&self) -> Option<&Arc<SealedBlock>> {
+        self.blocks.values().next_back()
+    }
+
+    /// Look up a block by height, or the latest one if no height is given.
+    fn at(&self, height: Option<u64>) -> Result<Arc<SealedBlock>, RpcError> {
+        let block = match height {
+            None | Some(0) => self.latest(),
+            Some(h) => self.blocks.get(&h),
+        };
+        block.cloned().ok_or_else(|| match height {
+            None | Some(0) => internal(anyhow!("no blocks have been sealed yet")),
+            Some(h) => internal(anyhow!("block {h} is not available")),
+        })
+    }
+
+    fn by_hash(&self, hash: &[u8]) -> Option<Arc<SealedBlock>> {
+        self.by_hash
+            .get(hash)
+            .and_then(|h| self.blocks.get(h))
+            .cloned()
+    }
+}
+
+/// What the sealer is asked to do by the RPC handlers.
+enum Command {
+    Submit {
+        tx: Vec<u8>,
+        reply: oneshot::Sender<anyhow::Result<Submitted>>,
+    },
+    Mine {
+        blocks: u64,
+        reply: oneshot::Sender<anyhow::Result<u64>>,
+    },
+    Warp {
+        warp: Warp,
+        reply: oneshot::Sender<anyhow::Result<i64>>,
+    },
+}
+
+enum Warp {
+    /// Move the clock forward by this many seconds.
+    Seconds(i64),
+    /// Set the Unix timestamp of the next block.
+    Timestamp(i64),
+}
+
+/// The outcome of submitting a transaction.
+struct Submitted {
+    hash: [u8; 32],
+    check_tx: response::CheckTx,
+    /// The height of the block the transaction went into and its result, unless it was rejected.
+    deliver_tx: Option<(u64, response::DeliverTx)>,
+}
+
+/// Drives the application through the ABCI calls CometBFT would make for every block.
+struct Sealer<A> {
+    app: A,
+    chain: Arc<RwLock<Chain>>,
+    chain_id: tendermint::chain::Id,
+    app_version: u64,
+    max_tx_bytes: i64,
+    proposer: Option<account::Id>,
+    /// Height of the last sealed block.
+    height: u64,
+    last_block_id: Option<block::Id>,
+    /// Unix timestamp of the last block.
+    last_time: i64,
+    /// Seconds the clock of the chain is ahead of the system clock.
+    time_offset: i64,
+    /// Unix timestamp of the next block, if it was set explicitly.
+    next_time: Option<i64>,
+}
+
+impl<A> Sealer<A>
+where
+    A: Application + Send + Sync,
+{
+    /// Initialize the chain from the genesis, or carry on from the last committed block.
+    async fn start(
+        app: A,
+        chain: Arc<RwLock<Chain>>,
+        config: &DevChainConfig,
+    ) -> anyhow::Result<Self> {
+        let genesis = &config.genesis;
+
+        let info = app
+            .info(Default::default())
+            .await
+            .map_err(|e| anyhow!("failed to get the application info: {e}"))?;
+
+        let (height, last_time) = if info.last_block_height.value() == 0 {
+            tracing::info!(
+                chain_id = genesis.chain_id.to_string(),
+                "initializing the chain"
+            );
+
+            let res = app
+                .init_chain(request::InitChain {
+                    time: genesis.genesis_time,
+                    chain_id: genesis.chain_id.to_string(),
+                    consensus_params: genesis.consensus_params.clone(),
+                    validators: Vec::new(),
+                    app_state_bytes: serde_json::to_vec(&genesis.app_state)?.into(),
+                    initial_height: Height::try_from(genesis.initial_height)?,
+                })
+                .await
+                .map_err(|e| anyhow!("failed to initialize the chain: {e}"))?;
+
+            let mut chain = chain.write().unwrap();
+            chain.app_hash = res.app_hash;
+            chain.update_validators(&res.validators);
+
+            (0, genesis.genesis_time.unix_timestamp())
+        } else {
+            let height = info.last_block_height.value();
+            tracing::info!(height, "carrying on from the last committed block");
+
+            chain.write().unwrap().app_hash = info.last_block_app_hash;
+
+            (height, 0)
+        };
+
+        Ok(Self {
+            app,
+            chain,
+            chain_id: genesis.chain_id.clone(),
+            app_version: info.app_version,
+            max_tx_bytes: genesis.consensus_params.block.max_bytes as i64,
+            proposer: config.proposer.map(account::Id::from),
+            height,
+            last_block_id: None,
+            last_time,
+            time_offset: 0,
+            next_time: None,
+        })
+    }
+
+    /// Execute the commands of the RPC handlers, sealing empty blocks in between if configured.
+    async fn run(
+        mut self,
+        mut commands: mpsc::Receiver<Command>,
+        block_time: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        loop {
+            let idle = async {
+                match block_time {
+                    Some(d) => tokio::time::sleep(d).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                cmd = commands.recv() => match cmd {
+                    Some(cmd) => self.handle(cmd).await,
+                    None => return Ok(()),
+                },
+                _ = idle => {
+                    if let Err(e) = self.seal(Vec::new()).await {
+                        tracing::error!(error = e.to_string(), "failed to seal empty block");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Execute a command and send the outcome back; failures go to the caller rather than
+    /// stopping the chain, so a test can carry on after a block the application refused.
+    async fn handle(&mut self, cmd: Command) {
+        match cmd {
+            Command::Submit { tx, reply } => {
+                let _ = reply.send(self.submit(tx).await);
+            }
+            Command::Mine { blocks, reply } => {
+                let _ = reply.send(self.mine(blocks).await);
+            }
+            Command::Warp { warp, reply } => {
+                let _ = reply.send(self.warp(warp));
+            }
+        }
+    }
+
+    /// Seal empty blocks and return the height of the last one.
+    async fn mine(&mut self, blocks: u64) -> anyhow::Result<u64> {
+        for _ in 0..blocks {
+            self.seal(Vec::new()).await?;
+        }
+        Ok(self.height)
+    }
+
+    /// Check the transaction and seal it into a block of its own if it passes.
+    async fn submit(&mut self, tx: Vec<u8>) -> anyhow::Result<Submitted> {
+        let hash = tx_hash(&tx);
+
+        let check_tx = self
+            .app
+            .check_tx(request::CheckTx {
+                tx: tx.clone().into(),
+                kind: CheckTxKind::New,
+            })
+            .await
+            .map_err(|e| anyhow!("failed to check transaction: {e}"))?;
+
+        if check_tx.code.is_err() {
+            return Ok(Submitted {
+                hash,
+                check_tx,
+                deliver_tx: None,
+            });
+        }
+
+        let sealed = self.seal(vec![tx]).await?;
+
+        // The proposal could still have left it out, e.g. if it doesn't fit into a block.
+        let deliver_tx = sealed
+            .block
+            .data
+            .iter()
+            .position(|t| tx_hash(t) == hash)
+            .map(|i| (self.height, sealed.txs_results[i].clone()));
+
+        Ok(Submitted {
+            hash,
+            check_tx,
+            deliver_tx,
+        })
+    }
+
+    fn warp(&mut self, warp: Warp) -> anyhow::Result<i64> {
+        match warp {
+            Warp::Seconds(seconds) => {
+                if seconds < 0 {
+                    bail!("cannot warp back in time");
+                }
+                self.time_offset += seconds;
+                if let Some(next_time) = self.next_time.as_mut() {
+                    *next_time += seconds;
+                }
+            }
+            Warp::Timestamp(timestamp) => {
+                if timestamp < self.last_time {
+                    bail!(
+                        "cannot warp to {timestamp}, before the last block at {}",
+                        self.last_time
+                    );
+                }
+                self.time_offset = timestamp - now();
+                self.next_time = Some(timestamp);
+            }
+        }
+        Ok(self.next_timestamp())
+    }
+
+    /// Timestamp of the next block; never before the last one.
+    fn next_timestamp(&self) -> i64 {
+        self.next_time
+            .unwrap_or_else(|| now() + self.time_offset)
+            .max(self.last_time)
+    }
+
+    fn proposer_address(&self) -> account::Id {
+        self.proposer
+            .or_else(|| {
+                let chain = self.chain.read().unwrap();
+                chain.validators.first().map(|v| v.address)
+            })
+            .unwrap_or_else(|| account::Id::new([0; 20]))
+    }
+
+    /// Take the block through the same ABCI calls as CometBFT would, and commit it.
+    async fn seal(&mut self, txs: Vec<Vec<u8>>) -> anyhow::Result<Arc<SealedBlock>> {
+        let height = self.height + 1;
+        let timestamp = self.next_timestamp();
+        let time = Time::from_unix_timestamp(timestamp, 0)?;
+        let proposer_address = self.proposer_address();
+        let app_hash = self.chain.read().unwrap().app_hash.clone();
+
+        let prepared = self
+            .app
+            .prepare_proposal(request::PrepareProposal {
+                max_tx_bytes: self.max_tx_bytes,
+                txs: txs.into_iter().map(Into::into).collect(),
+                local_last_commit: None,
+                misbehavior: Vec::new(),
+                height: Height::try_from(height)?,
+                time,
+                next_validators_hash: Hash::None,
+                proposer_address,
+            })
+            .await
+            .map_err(|e| anyhow!("failed to prepare block {height}: {e}"))?;
+
+        let txs = prepared.txs;
+        let data = txs.iter().map(|tx| tx.to_vec()).collect::<Vec<_>>();
+
+        let header = Header {
+            version: block::header::Version {
+                block: BLOCK_PROTOCOL_VERSION,
+                app: self.app_version,
+            },
+            chain_id: self.chain_id.clone(),
+            height: Height::try_from(height)?,
+            time,
+            last_block_id: self.last_block_id,
+            last_commit_hash: None,
+            data_hash: Some(data_hash(&data)),
+            validators_hash: Hash::None,
+            next_validators_hash: Hash::None,
+            consensus_hash: Hash::None,
+            app_hash,
+            last_results_hash: None,
+            evidence_hash: None,
+            proposer_address,
+        };
+        let hash = header.hash();
+        let block_id = block::Id {
+            hash,
+            part_set_header: parts::Header::new(1, hash)?,
+        };
+
+        let verdict = self
+            .app
+            .process_proposal(request::ProcessProposal {
+                txs: txs.clone(),
+                proposed_last_commit: None,
+                misbehavior: Vec::new(),
+                hash,
+                height: header.height,
+                time,
+                next_validators_hash: Hash::None,
+                proposer_address,
+            })
+            .await
+            .map_err(|e| anyhow!("failed to process block {height}: {e}"))?;
+
+        if !matches!(verdict, response::ProcessProposal::Accept) {
+            bail!("the application rejected block {height}; see the logs for the reason");
+        }
+
+        let begin_block = self
+            .app
+            .begin_block(request::BeginBlock {
+                hash,
+                header: header.clone(),
+                last_commit_info: CommitInfo {
+                    round: Round::default(),
+                    votes: Vec::new(),
+                },
+                byzantine_validators: Vec::new(),
+            })
+            .await
+            .map_err(|e| anyhow!("failed to begin block {height}: {e}"))?;
+
+        let mut txs_results = Vec::with_capacity(txs.len());
+        for tx in txs {
+            let res = self
+                .app
+                .deliver_tx(request::DeliverTx { tx })
+                .await
+                .map_err(|e| anyhow!("failed to deliver transaction in block {height}: {e}"))?;
+            txs_results.push(res);
+        }
+
+        let end_block = self
+            .app
+            .end_block(request::EndBlock {
+                height: height as i64,
+            })
+            .await
+            .map_err(|e| anyhow!("failed to end block {height}: {e}"))?;
+
+        let commit = self
+            .app
+            .commit()
+            .await
+            .map_err(|e| anyhow!("failed to commit block {height}: {e}"))?;
+
+        // The first block doesn't have a previous one to commit to.
+        let last_commit = if height == 1 {
+            None
+        } else {
+            let last_block_id = match self.last_block_id {
+                Some(id) => id,
+                None => empty_block_id()?,
+            };
+            Some(absent_commit(height - 1, last_block_id)?)
+        };
+
+        let tx_hashes = data.iter().map(|tx| tx_hash(tx)).collect::<Vec<_>>();
+        let block = Block::new(header, data, Default::default(), last_commit)?;
+
+        let sealed = Arc::new(SealedBlock {
+            block,
+            block_id,
+            begin_block,
+            txs_results,
+            end_block,
+        });
+
+        {
+            let mut chain = self.chain.write().unwrap();
+            chain.app_hash = AppHash::try_from(commit.data.to_vec())?;
+            chain.update_validators(&sealed.end_block.validator_updates);
+            for (i, h) in tx_hashes.iter().enumerate() {
+                chain.txs.insert(*h, (height, i));
+            }
+            chain.by_hash.insert(hash.as_bytes().to_vec(), height);
+            chain.blocks.insert(height, sealed.clone());
+        }
+
+        self.height = height;
+        self.last_block_id = Some(block_id);
+        self.last_time = timestamp;
+        // An explicit timestamp only applies to one block; the clock carries on from there.
+        self.next_time = None;
+
+        tracing::info!(height, num_txs = tx_hashes.len(), "sealed block");
+
+        Ok(sealed)
+    }
+}
+
+struct DevState<A> {
+    app: A,
+    chain: Arc<RwLock<Chain>>,
+    commands: mpsc::Sender<Command>,
+    genesis: tendermint::Genesis<Value>,
+    proposer: Option<PublicKey>,
+    listen: String,
+}
+
+type RpcError = (i64, anyhow::Error);
+
+fn invalid(e: impl Into<anyhow::Error>) -> RpcError {
+    (INVALID_PARAMS, e.into())
+}
+
+fn internal(e: impl Into<anyhow::Error>) -> RpcError {
+    (INTERNAL_ERROR, e.into())
+}
+
+async fn handle<A>(State(state): State<Arc<DevState<A>>>, body: Bytes) -> Response
+where
+    A: Application + Send + Sync + 'static,
+{
+    let req: Value = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return rpc_response(Value::Null, Err((PARSE_ERROR, anyhow!(e)))),
+    };
+
+    let id = req.get("id").cloned().unwrap_or(Value::Null);
+    let params = req.get("params").cloned().unwrap_or(Value::Null);
+    let method = req
+        .get("method")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default();
+
+    let result = match method {
+        "health" => Ok(json!({})),
+        "status" => status(&state),
+        "net_info" => Ok(json!({
+            "listening": true,
+            "listeners": [state.listen],
+            "n_peers": "0",
+            "peers": []
+        })),
+        "genesis" => Ok(json!({ "genesis": state.genesis })),
+        "abci_info" => abci_info(&state.app).await,
+        "abci_query" => abci_query(&state.app, params).await,
+        "broadcast_tx_async" | "broadcast_tx_sync" => submit(&state, &params).await.map(|s| {
+            json!({
+                "code": s.check_tx.code.value(),
+                "data": base64_encode(&s.check_tx.data),
+                "log": s.check_tx.log,
+                "codespace": s.check_tx.codespace,
+                "hash": hex::encode_upper(s.hash),
+            })
+        }),
+        "broadcast_tx_commit" => submit(&state, &params).await.map(|s| {
+            let (height, deliver_tx) = s.deliver_tx.unwrap_or_default();
+            json!({
+                "check_tx": check_tx_json(&s.check_tx),
+                "deliver_tx": deliver_tx_json(&deliver_tx),
+                "hash": hex::encode_upper(s.hash),
+                "height": height.to_string(),
+            })
+        }),
+        "dev_mine" => mine(&state, &params).await,
+        "dev_warp" => warp(&state, &params).await,
+        "unconfirmed_txs" | "num_unconfirmed_txs" => Ok(json!({
+            "n_txs": "0",
+            "total": "0",
+            "total_bytes": "0",
+            "txs": []
+        })),
+        method => {
+            let chain = state.chain.read().unwrap();
+            match method {
+                "block" => block(&chain, &params),
+                "block_by_hash" => block_by_hash(&chain, &params),
+                "block_results" => block_results(&chain, &params),
+                "header" => header(&chain, &params),
+                "header_by_hash" => header_by_hash(&chain, &params),
+                "commit" => commit(&chain, &params),
+                "validators" => validators(&chain, &params),
+                "consensus_params" => consensus_params(&chain, &state.genesis),
+                "tx" => tx(&chain, &params),
+                "tx_search" => tx_search(&chain, &params),
+                _ => Err((
+                    METHOD_NOT_FOUND,
+                    anyhow!("method {method} is not served by the development chain"),
+                )),
+            }
+        }
+    };
+
+    rpc_response(id, result)
+}
+
+/// Read an optional parameter which can be a string or a number; the Rust and Go clients
+/// send numbers as strings.
+fn param<T: FromStr>(params: &Value, name: &str) -> Result<Option<T>, RpcError> {
+    let value = match params.get(name) {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| invalid(anyhow!("invalid {name}: {value}")))
+}
+
+/// Read a hash parameter, either in hex or in base64.
+fn param_hash(params: &Value, name: &str) -> Result<Vec<u8>, RpcError> {
+    let value = params
+        .get(name)
+        .and_then(|h| h.as_str())
+        .ok_or_else(|| invalid(anyhow!("missing {name}")))?;
+    let value = value.trim_start_matches("0x");
+    if value.len() == 64 {
+        if let Ok(hash) = hex::decode(value) {
+            return Ok(hash);
+        }
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|_| invalid(anyhow!("invalid {name}: {value}")))
+}
+
+fn status<A>(state: &DevState<A>) -> RpcResult {
+    let chain = state.chain.read().unwrap();
+
+    let validator = match state.proposer {
+        Some(pk) => validator::Info::new(pk, Default::default()),
+        None => match chain.validators.first() {
+            Some(v) => v.clone(),
+            None => validator::Info::new(fallback_public_key()?, Default::default()),
+        },
+    };
+
+    let (earliest, latest) = match (chain.blocks.values().next(), chain.latest()) {
+        (Some(earliest), Some(latest)) => (
+            block_info(earliest, &chain.app_hash),
+            block_info(latest, &chain.app_hash),
+        ),
+        _ => {
+            let info = json!({
+                "hash": Hash::None,
+                "app_hash": chain.app_hash,
+                "height": "0",
+                "time": state.genesis.genesis_time,
+            });
+            (info.clone(), info)
+        }
+    };
+
+    Ok(json!({
+        "node_info": {
+            "protocol_version": {
+                "p2p": "8",
+                "block": BLOCK_PROTOCOL_VERSION.to_string(),
+                "app": crate::APP_VERSION.to_string(),
+            },
+            "id": hex::encode(validator.address.as_bytes()),
+            "listen_addr": state.listen,
+            "network": state.genesis.chain_id,
+            "version": "0.37.0",
+            "channels": "",
+            "moniker": "fendermint-dev",
+            "other": {
+                "tx_index": "on",
+                "rpc_address": state.listen,
+            }
+        },
+        "sync_info": {
+            "latest_block_hash": latest["hash"],
+            "latest_app_hash": latest["app_hash"],
+            "latest_block_height": latest["height"],
+            "latest_block_time": latest["time"],
+            "earliest_block_hash": earliest["hash"],
+            "earliest_app_hash": earliest["app_hash"],
+            "earliest_block_height": earliest["height"],
+            "earliest_block_time": earliest["time"],
+            "catching_up": false,
+        },
+        "validator_info": validator,
+    }))
+}
+
+/// What the status reports about a block; the app hash is the one after the latest commit.
+fn block_info(block: &SealedBlock, app_hash: &AppHash) -> Value {
+    json!({
+        "hash": block.block_id.hash,
+        "app_hash": app_hash,
+        "height": block.block.header.height.to_string(),
+        "time": block.block.header.time,
+    })
+}
+
+async fn submit<A>(state: &DevState<A>, params: &Value) -> Result<Submitted, RpcError> {
+    let tx = params
+        .get("tx")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| invalid(anyhow!("missing tx")))?;
+    let tx = base64::engine::general_purpose::STANDARD
+        .decode(tx)
+        .map_err(invalid)?;
+
+    let (reply, rx) = oneshot::channel();
+    send(state, Command::Submit { tx, reply }).await?;
+    rx.await.map_err(|_| stopped())?.map_err(internal)
+}
+
+async fn mine<A>(state: &DevState<A>, params: &Value) -> RpcResult {
+    let blocks = param(params, "blocks")?.unwrap_or(1);
+    if blocks > MAX_MINE_BLOCKS {
+        return Err(invalid(anyhow!(
+            "cannot mine more than {MAX_MINE_BLOCKS} blocks at once"
+        )));
+    }
+
+    let (reply, rx) = oneshot::channel();
+    send(state, Command::Mine { blocks, reply }).await?;
+    let height = rx.await.map_err(|_| stopped())?.map_err(internal)?;
+
+    Ok(json!({ "height": height.to_string() }))
+}
+
+async fn warp<A>(state: &DevState<A>, params: &Value) -> RpcResult {
+    let warp = match (param(params, "seconds")?, param(params, "timestamp")?) {
+        (Some(seconds), None) => Warp::Seconds(seconds),
+        (None, Some(timestamp)) => Warp::Timestamp(timestamp),
+        _ => return Err(invalid(anyhow!("expected either seconds or timestamp"))),
+    };
+
+    let (reply, rx) = oneshot::channel();
+    send(state, Command::Warp { warp, reply }).await?;
+    let next = rx.await.map_err(|_| stopped())?.map_err(invalid)?;
+    let next = Time::from_unix_timestamp(next, 0).map_err(internal)?;
+
+    Ok(json!({ "next_block_time": next }))
+}
+
+async fn send<A>(state: &DevState<A>, cmd: Command) -> Result<(), RpcError> {
+    state.commands.send(cmd).await.map_err(|_| stopped())
+}
+
+fn stopped() -> RpcError {
+    internal(anyhow!("the development chain has stopped"))
+}
+
+fn block(chain: &Chain, params: &Value) -> RpcResult {
+    let b = chain.at(param(params, "height")?)?;
+    Ok(json!({ "block_id": b.block_id, "block": b.block }))
+}
+
+fn block_by_hash(chain: &Chain, params: &Value) -> RpcResult {
+    match chain.by_hash(&param_hash(params, "hash")?) {
+        Some(b) => Ok(json!({ "block_id": b.block_id, "block": b.block })),
+        None => {
+            let block_id = empty_block_id().map_err(internal)?;
+            Ok(json!({ "block_id": block_id, "block": null }))
+        }
+    }
+}
+
+fn header(chain: &Chain, params: &Value) -> RpcResult {
+    let b = chain.at(param(params, "height")?)?;
+    Ok(json!({ "header": b.block.header }))
+}
+
+fn header_by_hash(chain: &Chain, params: &Value) -> RpcResult {
+    let header = chain
+        .by_hash(&param_hash(params, "hash")?)
+        .map(|b| b.block.header.clone());
+    Ok(json!({ "header": header }))
+}
+
+fn commit(chain: &Chain, params: &Value) -> RpcResult {
+    let b = chain.at(param(params, "height")?)?;
+    let commit = absent_commit(b.block.header.height.value(), b.block_id).map_err(internal)?;
+    Ok(json!({
+        "signed_header": { "header": b.block.header, "commit": commit },
+        "canonical": true,
+    }))
+}
+
+fn block_results(chain: &Chain, params: &Value) -> RpcResult {
+    let b = chain.at(param(params, "height")?)?;
+    Ok(json!({
+        "height": b.block.header.height.to_string(),
+        "txs_results": b.txs_results.iter().map(deliver_tx_json).collect::<Vec<_>>(),
+        "begin_block_events": events_json(&b.begin_block.events),
+        "end_block_events": events_json(&b.end_block.events),
+        "validator_updates": b.end_block.validator_updates,
+        "consensus_param_updates": b.end_block.consensus_param_updates,
+    }))
+}
+
+fn validators(chain: &Chain, params: &Value) -> RpcResult {
+    let height = param(params, "height")?
+        .unwrap_or(chain.latest().map_or(0, |b| b.block.header.height.value()));
+    Ok(json!({
+        "block_height": height.to_string(),
+        "validators": chain.validators,
+        "count": chain.validators.len().to_string(),
+        "total": chain.validators.len().to_string(),
+    }))
+}
+
+fn consensus_params(chain: &Chain, genesis: &tendermint::Genesis<Value>) -> RpcResult {
+    let height = chain.latest().map_or(0, |b| b.block.header.height.value());
+    Ok(json!({
+        "block_height": height.to_string(),
+        "consensus_params": genesis.consensus_params,
+    }))
+}
+
+fn tx(chain: &Chain, params: &Value) -> RpcResult {
+    let hash = param_hash(params, "hash")?;
+    let found = <[u8; 32]>::try_from(hash.as_slice())
+        .ok()
+        .and_then(|hash| chain.txs.get(&hash))
+        .and_then(|(height, index)| chain.blocks.get(height).map(|b| (b, *index)));
+
+    match found {
+        Some((b, index)) => Ok(tx_json(b, index)),
+        None => Err(internal(anyhow!(
+            "tx ({}) not found",
+            hex::encode_upper(hash)
+        ))),
+    }
+}
+
+/// Search the transactions of all blocks; there are not supposed to be many on a development chain.
+fn tx_search(chain: &Chain, params: &Value) -> RpcResult {
+    let query: Query = params
+        .get("query")
+        .and_then(|q| q.as_str())
+        .ok_or_else(|| invalid(anyhow!("missing query")))?
+        .parse()
+        .map_err(|e| invalid(anyhow!("invalid query: {e}")))?;
+
+    let page = param::<usize>(params, "page")?.unwrap_or(1).max(1);
+    let per_page = param::<usize>(params, "per_page")?
+        .unwrap_or(30)
+        .clamp(1, 100);
+    let desc = params.get("order_by").and_then(|o| o.as_str()) == Some("desc");
+
+    let mut found = Vec::new();
+    for b in chain.blocks.values() {
+        for index in 0..b.txs_results.len() {
+            if matches_query(&query, b, index) {
+                found.push((b, index));
+            }
+        }
+    }
+    if desc {
+        found.reverse();
+    }
+
+    let txs = found
+        .iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .map(|(b, index)| tx_json(b, *index))
+        .collect::<Vec<_>>();
+
+    Ok(json!({ "txs": txs, "total_count": found.len().to_string() }))
+}
+
+fn matches_query(query: &Query, block: &SealedBlock, index: usize) -> bool {
+    query.conditions.iter().all(|condition| {
+        let values = match condition.key.as_str() {
+            "tx.height" => vec![block.block.header.height.to_string()],
+            "tx.hash" => vec![hex::encode_upper(tx_hash(&block.block.data[index]))],
+            key => block.txs_results[index]
+                .events
+                .iter()
+                .flat_map(|e| {
+                    e.attributes
+                        .iter()
+                        .filter(move |a| key == format!("{}.{}", e.kind, a.key))
+                        .map(|a| a.value.clone())
+                })
+                .collect(),
+        };
+        values
+            .iter()
+            .any(|value| matches_operation(&condition.operation, value))
+    })
+}
+
+fn matches_operation(operation: &Operation, value: &str) -> bool {
+    match operation {
+        Operation::Exists => true,
+        Operation::Contains(s) => value.contains(s.as_str()),
+        Operation::Eq(operand) => {
+            let operand = operand_string(operand);
+            value.eq_ignore_ascii_case(&operand)
+                || compare(value, &operand) == Some(Ordering::Equal)
+        }
+        Operation::Lt(operand) => compare(value, &operand_string(operand)) == Some(Ordering::Less),
+        Operation::Lte(operand) => matches!(
+            compare(value, &operand_string(operand)),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        Operation::Gt(operand) => {
+            compare(value, &operand_string(operand)) == Some(Ordering::Greater)
+        }
+        Operation::Gte(operand) => matches!(
+            compare(value, &operand_string(operand)),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+    }
+}
+
+fn operand_string(operand: &Operand) -> String {
+    match operand {
+        Operand::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compare numerically, like CometBFT does for the ranges.
+fn compare(value: &str, operand: &str) -> Option<Ordering> {
+    let value: f64 = value.parse().ok()?;
+    let operand: f64 = operand.parse().ok()?;
+    value.partial_cmp(&operand)
+}
+
+fn tx_json(block: &SealedBlock, index: usize) -> Value {
+    let tx = &block.block.data[index];
+    json!({
+        "hash": hex::encode_upper(tx_hash(tx)),
+        "height": block.block.header.height.to_string(),
+        "index": index,
+        "tx_result": deliver_tx_json(&block.txs_results[index]),
+        "tx": base64_encode(tx),
+    })
+}
+
+/// The results in the same representation as CometBFT 0.37, with numbers as strings.
+fn deliver_tx_json(res: &response::DeliverTx) -> Value {
+    json!({
+        "code": res.code.value(),
+        "data": base64_encode(&res.data),
+        "log": res.log,
+        "info": res.info,
+        "gas_wanted": res.gas_wanted.to_string(),
+        "gas_used": res.gas_used.to_string(),
+        "events": events_json(&res.events),
+        "codespace": res.codespace,
+    })
+}
+
+fn check_tx_json(res: &response::CheckTx) -> Value {
+    json!({
+        "code": res.code.value(),
+        "data": base64_encode(&res.data),
+        "log": res.log,
+        "info": res.info,
+        "gas_wanted": res.gas_wanted.to_string(),
+        "gas_used": res.gas_used.to_string(),
+        "events": events_json(&res.events),
+        "codespace": res.codespace,
+    })
+}
+
+fn events_json(events: &[Event]) -> Value {
+    events
+        .iter()
+        .map(|e| {
+            json!({
+                "type": e.kind,
+                "attributes": e.attributes.iter().map(|a| json!({
+                    "key": a.key,
+                    "value": a.value,
+                    "index": a.index,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn tx_hash(tx: &[u8]) -> [u8; 32] {
+    Sha256::digest(tx).into()
+}
+
+/// Not the Merkle root CometBFT puts into the header, but it commits to the same transactions.
+fn data_hash(txs: &[Vec<u8>]) -> Hash {
+    let mut hasher = Sha256::new();
+    for tx in txs {
+        hasher.update(tx_hash(tx));
+    }
+    Hash::Sha256(hasher.finalize().into())
+}
+
+fn empty_block_id() -> anyhow::Result<block::Id> {
+    Ok(block::Id {
+        hash: Hash::None,
+        part_set_header: parts::Header::new(0, Hash::None)?,
+    })
+}
+
+/// A commit without signatures; there is nobody to vote on the blocks.
+fn absent_commit(height: u64, block_id: block::Id) -> anyhow::Result<Commit> {
+    Ok(Commit {
+        height: Height::try_from(height)?,
+        round: Round::default(),
+        block_id,
+        signatures: vec![CommitSig::BlockIdFlagAbsent],
+    })
+}
+
+/// A valid key to report as the validator of the node when the chain has none.
+fn fallback_public_key() -> Result<PublicKey, RpcError> {
+    // The base point of Ed25519.
+    let mut bytes = [0x66u8; 32];
+    bytes[0] = 0x58;
+    PublicKey::from_raw_ed25519(&bytes)
+        .ok_or_else(|| internal(anyhow!("invalid fallback public key")))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{Arc, RwLock};
+
+    use async_trait::async_trait;
+    use fendermint_abci::{AbciResult, Application};
+    use serde_json::json;
+    use tendermint::abci::{response, Event, EventAttributeIndexExt};
+    use tendermint_rpc::query::Query;
+    use tokio::sync::oneshot;
+
+    use super::{events_json, matches_operation, now, tx_hash, Chain, Command, Sealer, Warp};
+
+    /// Accepts everything, unless told to reject the proposals, and commits to the block count.
+    #[derive(Clone, Default)]
+    struct TestApp {
+        commits: Arc<AtomicU64>,
+        reject: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Application for TestApp {
+        async fn process_proposal(
+            &self,
+            _request: tendermint::abci::request::ProcessProposal,
+        ) -> AbciResult<response::ProcessProposal> {
+            if self.reject.load(Ordering::Relaxed) {
+                Ok(response::ProcessProposal::Reject)
+            } else {
+                Ok(response::ProcessProposal::Accept)
+            }
+        }
+
+        async fn commit(&self) -> AbciResult<response::Commit> {
+            let n = self.commits.fetch_add(1, Ordering::Relaxed) + 1;
+            Ok(response::Commit {
+                data: n.to_be_bytes().to_vec().into(),
+                retain_height: Default::default(),
+            })
+        }
+    }
+
+    fn sealer(app: TestApp) -> Sealer<TestApp> {
+        Sealer {
+            app,
+            chain: Arc::new(RwLock::new(Chain::default())),
+            chain_id: "test-chain".parse().unwrap(),
+            app_version: 0,
+            max_tx_bytes: 1024 * 1024,
+            proposer: None,
+            height: 0,
+            last_block_id: None,
+            last_time: 0,
+            time_offset: 0,
+            next_time: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn seal_blocks() {
+        let mut sealer = sealer(Default::default());
+
+        let first = sealer.seal(Vec::new()).await.unwrap();
+        let tx = b"hello".to_vec();
+        let submitted = sealer.submit(tx.clone()).await.unwrap();
+
+        assert_eq!(sealer.height, 2);
+        assert_eq!(submitted.hash, tx_hash(&tx));
+        assert_eq!(submitted.deliver_tx.map(|(h, _)| h), Some(2));
+
+        let chain = sealer.chain.read().unwrap();
+        let second = chain.at(None).unwrap();
+        assert_eq!(second.block.header.height.value(), 2);
+        assert_eq!(second.block.header.last_block_id, Some(first.block_id));
+        assert_eq!(second.block.data, vec![tx.clone()]);
+        assert_eq!(chain.txs.get(&tx_hash(&tx)), Some(&(2, 0)));
+        assert_eq!(
+            chain
+                .by_hash(first.block_id.hash.as_bytes())
+                .map(|b| b.block_id),
+            Some(first.block_id)
+        );
+        assert_eq!(chain.app_hash.as_bytes(), 2u64.to_be_bytes().as_slice());
+    }
+
+    #[tokio::test]
+    async fn warp_to_timestamp_applies_once() {
+        let mut sealer = sealer(Default::default());
+
+        let timestamp = now() + 1000;
+        assert_eq!(sealer.warp(Warp::Timestamp(timestamp)).unwrap(), timestamp);
+        assert!(sealer.warp(Warp::Seconds(-1)).is_err());
+
+        let b1 = sealer.seal(Vec::new()).await.unwrap();
+        assert_eq!(b1.block.header.time.unix_timestamp(), timestamp);
+
+        // Later blocks follow the clock from the new time instead of repeating the timestamp.
+        let next = sealer.warp(Warp::Seconds(500)).unwrap();
+        assert!(next >= timestamp + 500);
+
+        let b2 = sealer.seal(Vec::new()).await.unwrap();
+        assert!(b2.block.header.time.unix_timestamp() >= timestamp + 500);
+
+        assert!(sealer.warp(Warp::Timestamp(timestamp)).is_err());
+    }
+
+    #[tokio::test]
+    async fn mine_carries_on_after_failure() {
+        let app = TestApp::default();
+        let mut sealer = sealer(app.clone());
+
+        let (reply, rx) = oneshot::channel();
+        sealer.handle(Command::Mine { blocks: 3, reply }).await;
+        assert_eq!(rx.await.unwrap().unwrap(), 3);
+
+        app.reject.store(true, Ordering::Relaxed);
+        let (reply, rx) = oneshot::channel();
+        sealer.handle(Command::Mine { blocks: 1, reply }).await;
+        assert!(rx.await.unwrap().is_err());
+
+        app.reject.store(false, Ordering::Relaxed);
+        let (reply, rx) = oneshot::channel();
+        sealer.handle(Command::Mine { blocks: 2, reply }).await;
+        assert_eq!(rx.await.unwrap().unwrap(), 5);
+    }
+
+    #[test]
+    fn query_operations() {
+        let query: Query =
+            "message.amount >= 10 AND message.to = 'f0100' AND message.memo CONTAINS 'hi'"
+                .parse()
+                .unwrap();
+
+        let ops = query
+            .conditions
+            .iter()
+            .map(|c| &c.operation)
+            .collect::<Vec<_>>();
+
+        assert!(matches_operation(ops[0], "10"));
+        assert!(matches_operation(ops[0], "11.5"));
+        assert!(!matches_operation(ops[0], "9"));
+        assert!(!matches_operation(ops[0], "ten"));
+        assert!(matches_operation(ops[1], "f0100"));
+        assert!(!matches_operation(ops[1], "f0101"));
+        assert!(matches_operation(ops[2], "oh hi there"));
+        assert!(!matches_operation(ops[2], "hello"));
+
+        // Just to make sure the attributes render the way the clients expect.
+        let event = Event::new("message", [("amount", "10").index()]);
+        assert_eq!(
+            events_json(&[event]),
+            json!([{ "type": "message", "attributes": [{ "key": "amount", "value": "10", "index": true }] }])
+        );
+    }
+}
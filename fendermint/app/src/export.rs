@@ -0,0 +1,291 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Export a record about every executed message at the end of each block,
+//! so analytics pipelines don't have to reconstruct the activity through RPC.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::Context;
+use fendermint_vm_interpreter::fvm::FvmApplyRet;
+use fendermint_vm_message::signed::DomainHash;
+use serde::Serialize;
+use tendermint::crypto::sha256::Sha256;
+
+use crate::BlockHeight;
+
+/// Where to write the records.
+#[derive(Debug, Clone)]
+pub enum ExportSink {
+    /// Append to CSV files in a directory, starting a new file every so many blocks.
+    Csv {
+        dir: PathBuf,
+        rotate_blocks: BlockHeight,
+    },
+    /// Write JSON lines to a Unix socket someone is listening on.
+    Socket { path: PathBuf },
+}
+
+/// A message executed in a block.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ExportRecord {
+    pub height: BlockHeight,
+    /// Index of the transaction in the block.
+    pub index: usize,
+    /// Hash of the transaction, the way CometBFT indexes it.
+    pub tx_hash: String,
+    /// Hash of the transaction in its original domain, e.g. Ethereum, if it has one.
+    pub domain_hash: Option<String>,
+    pub sender: String,
+    pub recipient: String,
+    pub method: u64,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub exit_code: u32,
+    pub events: usize,
+}
+
+impl ExportRecord {
+    /// Create a record from the result of a message; the index is filled in by the [Exporter].
+    pub fn new(
+        height: BlockHeight,
+        tx: &[u8],
+        ret: &FvmApplyRet,
+        domain_hash: Option<&DomainHash>,
+    ) -> Self {
+        let tx_hash = tendermint::crypto::default::Sha256::digest(tx);
+        let receipt = &ret.apply_ret.msg_receipt;
+        Self {
+            height,
+            index: 0,
+            tx_hash: hex::encode_upper(tx_hash),
+            domain_hash: domain_hash.map(|h| match h {
+                DomainHash::Eth(h) => hex::encode(h),
+            }),
+            sender: ret.from.to_string(),
+            recipient: ret.to.to_string(),
+            method: ret.method_num,
+            gas_limit: ret.gas_limit,
+            gas_used: receipt.gas_used,
+            exit_code: receipt.exit_code.value(),
+            events: ret.apply_ret.events.len(),
+        }
+    }
+}
+
+/// Number of committed blocks waiting to be written before we start dropping their records.
+const QUEUE_BLOCKS: usize = 100;
+
+/// How long to wait for the reader of the socket before giving up on a block.
+const SOCKET_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CSV_HEADER: &str =
+    "height,index,tx_hash,domain_hash,sender,recipient,method,gas_limit,gas_used,exit_code,events";
+
+impl ExportRecord {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.height,
+            self.index,
+            self.tx_hash,
+            self.domain_hash.as_deref().unwrap_or_default(),
+            self.sender,
+            self.recipient,
+            self.method,
+            self.gas_limit,
+            self.gas_used,
+            self.exit_code,
+            self.events
+        )
+    }
+}
+
+/// Collects the records of the block being executed and hands them over on commit
+/// to a background thread which writes them out, so a slow disk or reader doesn't hold up
+/// the consensus.
+///
+/// Failing to export is logged but doesn't stop the node; if the writer falls behind by
+/// more than [QUEUE_BLOCKS] blocks, the records of the new blocks are dropped.
+#[derive(Clone)]
+pub struct Exporter {
+    /// Number of transactions delivered in the current block, and the records collected so far.
+    pending: Arc<Mutex<(usize, Vec<ExportRecord>)>>,
+    /// Records of the committed blocks, waiting to be written.
+    queue: SyncSender<(BlockHeight, Vec<ExportRecord>)>,
+}
+
+impl Exporter {
+    pub fn new(sink: ExportSink) -> anyhow::Result<Self> {
+        Self::spawn(sink, QUEUE_BLOCKS).map(|(exporter, _)| exporter)
+    }
+
+    /// Start the writer thread, which stops when all clones of the exporter are dropped.
+    fn spawn(sink: ExportSink, capacity: usize) -> anyhow::Result<(Self, JoinHandle<()>)> {
+        if let ExportSink::Csv { ref dir, .. } = sink {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create export dir {dir:?}"))?;
+        }
+        let (queue, rx) = sync_channel(capacity);
+        let handle = std::thread::Builder::new()
+            .name("exporter".into())
+            .spawn(move || run_writer(sink, rx))
+            .context("failed to start the export writer")?;
+
+        let exporter = Self {
+            pending: Default::default(),
+            queue,
+        };
+        Ok((exporter, handle))
+    }
+
+    /// Count a transaction delivered in the current block, remembering the record
+    /// if it was executed, rather than rejected before it got to the FVM.
+    pub fn delivered(&self, record: Option<ExportRecord>) {
+        let mut guard = self.pending.lock().unwrap();
+        let (ref mut count, ref mut records) = *guard;
+        if let Some(mut record) = record {
+            record.index = *count;
+            records.push(record);
+        }
+        *count += 1;
+    }
+
+    /// Queue the records of the committed block to be written out, without waiting for it.
+    pub fn commit(&self, height: BlockHeight) {
+        let (_, records) = std::mem::take(&mut *self.pending.lock().unwrap());
+        if records.is_empty() {
+            return;
+        }
+        let count = records.len();
+        match self.queue.try_send((height, records)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!(height, count, "export queue full; dropping block records");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                tracing::warn!(
+                    height,
+                    count,
+                    "export writer stopped; dropping block records"
+                );
+            }
+        }
+    }
+}
+
+/// Write the records of the committed blocks as they arrive, until the exporter is dropped.
+fn run_writer(sink: ExportSink, rx: Receiver<(BlockHeight, Vec<ExportRecord>)>) {
+    while let Ok((height, records)) = rx.recv() {
+        if let Err(e) = write(&sink, height, &records) {
+            tracing::warn!(
+                error = e.to_string(),
+                height,
+                count = records.len(),
+                "failed to export block records"
+            );
+        }
+    }
+}
+
+fn write(sink: &ExportSink, height: BlockHeight, records: &[ExportRecord]) -> anyhow::Result<()> {
+    match *sink {
+        ExportSink::Csv {
+            ref dir,
+            rotate_blocks,
+        } => {
+            let first = height - height % rotate_blocks.max(1);
+            let path = dir.join(format!("messages-{first:012}.csv"));
+            let is_new = !path.exists();
+            let mut file: File = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("failed to open {path:?}"))?;
+
+            let mut buf = String::new();
+            if is_new {
+                buf.push_str(CSV_HEADER);
+                buf.push('\n');
+            }
+            for r in records {
+                buf.push_str(&r.to_csv());
+                buf.push('\n');
+            }
+            file.write_all(buf.as_bytes())?;
+        }
+        ExportSink::Socket { ref path } => {
+            let mut stream = UnixStream::connect(path)
+                .with_context(|| format!("failed to connect to {path:?}"))?;
+            stream.set_write_timeout(Some(SOCKET_WRITE_TIMEOUT))?;
+            let mut buf = Vec::new();
+            for r in records {
+                serde_json::to_writer(&mut buf, r)?;
+                buf.push(b'\n');
+            }
+            stream.write_all(&buf)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportRecord, ExportSink, Exporter, CSV_HEADER};
+
+    fn record(height: u64) -> ExportRecord {
+        ExportRecord {
+            height,
+            index: 0,
+            tx_hash: "AB".into(),
+            domain_hash: None,
+            sender: "f01".into(),
+            recipient: "f02".into(),
+            method: 0,
+            gas_limit: 100,
+            gas_used: 50,
+            exit_code: 0,
+            events: 1,
+        }
+    }
+
+    #[test]
+    fn csv_files_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (exporter, writer) = Exporter::spawn(
+            ExportSink::Csv {
+                dir: dir.path().to_path_buf(),
+                rotate_blocks: 10,
+            },
+            10,
+        )
+        .unwrap();
+
+        for height in [8, 9, 10] {
+            exporter.delivered(Some(record(height)));
+            exporter.delivered(None);
+            exporter.delivered(Some(record(height)));
+            exporter.commit(height);
+        }
+
+        // Let the writer finish the queue.
+        drop(exporter);
+        writer.join().unwrap();
+
+        let first = std::fs::read_to_string(dir.path().join("messages-000000000000.csv")).unwrap();
+        let second = std::fs::read_to_string(dir.path().join("messages-000000000010.csv")).unwrap();
+
+        assert_eq!(first.lines().count(), 5);
+        assert_eq!(first.lines().next(), Some(CSV_HEADER));
+        assert_eq!(second.lines().count(), 3);
+        assert_eq!(second.lines().nth(1), Some("10,0,AB,,f01,f02,0,100,50,0,1"));
+        assert_eq!(second.lines().nth(2), Some("10,2,AB,,f01,f02,0,100,50,0,1"));
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Statistics about what uses the gas of the subnet, by class of message and by callee actor.
+//!
+//! The totals of every block are stored in the database for a window of recent blocks,
+//! so that when the throughput degrades, operators can see which contracts are consuming
+//! the capacity of their subnet.
+
+use std::collections::{BTreeMap, HashMap};
+
+use fendermint_vm_actor_interface::{eam, evm, init::builtin_actor_eth_addr, ipc};
+use fendermint_vm_interpreter::fvm::FvmApplyRet;
+use fvm_shared::address::Address;
+use fvm_shared::METHOD_SEND;
+use serde::{Deserialize, Serialize};
+
+use crate::BlockHeight;
+
+/// The kind of work a message does.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageClass {
+    /// Plain value transfer.
+    Transfer,
+    /// Invoking a method of an EVM contract.
+    EvmCall,
+    /// Deploying an EVM contract through the EAM.
+    EvmCreate,
+    /// Messages of the IPC system: top-down messages and calls to the IPC contracts.
+    Ipc,
+    /// Calls to other native actors.
+    Other,
+}
+
+impl MessageClass {
+    /// Classify an executed message; `is_ipc` indicates that it wasn't sent by a user.
+    pub fn of(ret: &FvmApplyRet, is_ipc: bool) -> Self {
+        if is_ipc || is_ipc_contract(&ret.to) {
+            Self::Ipc
+        } else if ret.to == eam::EAM_ACTOR_ADDR {
+            Self::EvmCreate
+        } else if ret.method_num == METHOD_SEND {
+            Self::Transfer
+        } else if ret.method_num == evm::Method::InvokeContract as u64 {
+            Self::EvmCall
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Check whether the address is one of the IPC contracts, by its ID or its delegated address.
+fn is_ipc_contract(addr: &Address) -> bool {
+    [ipc::GATEWAY_ACTOR_ID, ipc::SUBNETREGISTRY_ACTOR_ID]
+        .into_iter()
+        .any(|id| {
+            *addr == Address::new_id(id) || *addr == Address::from(builtin_actor_eth_addr(id))
+        })
+}
+
+/// Number of messages and the gas they used.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GasTotals {
+    pub count: u64,
+    pub gas_used: u64,
+}
+
+impl GasTotals {
+    fn add(&mut self, other: &GasTotals) {
+        self.count += other.count;
+        self.gas_used = self.gas_used.saturating_add(other.gas_used);
+    }
+}
+
+/// Gas used by the transactions of a block.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockGasStats {
+    pub by_class: BTreeMap<MessageClass, GasTotals>,
+    /// Totals by the address of the callee, as it appeared in the message.
+    pub by_actor: BTreeMap<String, GasTotals>,
+}
+
+impl BlockGasStats {
+    /// Add an executed message to the totals.
+    pub fn record(&mut self, ret: &FvmApplyRet, is_ipc: bool) {
+        let totals = GasTotals {
+            count: 1,
+            gas_used: ret.apply_ret.msg_receipt.gas_used,
+        };
+        self.by_class
+            .entry(MessageClass::of(ret, is_ipc))
+            .or_default()
+            .add(&totals);
+        self.by_actor
+            .entry(ret.to.to_string())
+            .or_default()
+            .add(&totals);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_class.is_empty()
+    }
+}
+
+/// Gas used by an actor over a range of blocks.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ActorGas {
+    pub actor: String,
+    #[serde(flatten)]
+    pub totals: GasTotals,
+}
+
+/// Gas used over a range of blocks, with the actors which used the most gas first.
+#[derive(Serialize, Debug, Clone)]
+pub struct GasStatsReport {
+    pub from_height: BlockHeight,
+    pub to_height: BlockHeight,
+    pub by_class: BTreeMap<MessageClass, GasTotals>,
+    pub top_actors: Vec<ActorGas>,
+}
+
+impl GasStatsReport {
+    /// Sum up the statistics of blocks, keeping the `top` actors by gas used.
+    pub fn aggregate(
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        blocks: impl IntoIterator<Item = BlockGasStats>,
+        top: usize,
+    ) -> Self {
+        let mut by_class = BTreeMap::<MessageClass, GasTotals>::new();
+        let mut by_actor = HashMap::<String, GasTotals>::new();
+
+        for block in blocks {
+            for (class, totals) in block.by_class {
+                by_class.entry(class).or_default().add(&totals);
+            }
+            for (actor, totals) in block.by_actor {
+                by_actor.entry(actor).or_default().add(&totals);
+            }
+        }
+
+        let mut top_actors = by_actor
+            .into_iter()
+            .map(|(actor, totals)| ActorGas { actor, totals })
+            .collect::<Vec<_>>();
+
+        top_actors.sort_by(|a, b| {
+            b.totals
+                .gas_used
+                .cmp(&a.totals.gas_used)
+                .then_with(|| a.actor.cmp(&b.actor))
+        });
+        top_actors.truncate(top);
+
+        Self {
+            from_height,
+            to_height,
+            by_class,
+            top_actors,
+        }
+    }
+}
+
+/// Parameters of the gas statistics query.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GasStatsQuery {
+    /// Number of recent blocks to aggregate.
+    pub blocks: u64,
+    /// Number of actors to list.
+    pub top: usize,
+}
+
+impl Default for GasStatsQuery {
+    fn default() -> Self {
+        Self {
+            blocks: 100,
+            top: 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use fendermint_vm_actor_interface::{eam, evm, init::builtin_actor_eth_addr, ipc};
+    use fendermint_vm_interpreter::fvm::FvmApplyRet;
+    use fvm::executor::ApplyRet;
+    use fvm_shared::{
+        address::Address, econ::TokenAmount, error::ExitCode, receipt::Receipt, METHOD_SEND,
+    };
+
+    use super::{BlockGasStats, GasStatsReport, MessageClass};
+
+    fn apply_ret(to: Address, method_num: u64, gas_used: u64) -> FvmApplyRet {
+        FvmApplyRet {
+            apply_ret: ApplyRet {
+                msg_receipt: Receipt {
+                    exit_code: ExitCode::OK,
+                    return_data: Default::default(),
+                    gas_used,
+                    events_root: None,
+                },
+                penalty: TokenAmount::default(),
+                miner_tip: TokenAmount::default(),
+                base_fee_burn: TokenAmount::default(),
+                over_estimation_burn: TokenAmount::default(),
+                refund: TokenAmount::default(),
+                gas_refund: 0,
+                gas_burned: 0,
+                failure_info: None,
+                exec_trace: Vec::new(),
+                events: Vec::new(),
+            },
+            from: Address::new_id(100),
+            to,
+            method_num,
+            gas_limit: 10_000_000,
+            emitters: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn classify_messages() {
+        let invoke = evm::Method::InvokeContract as u64;
+        let gateway = Address::from(builtin_actor_eth_addr(ipc::GATEWAY_ACTOR_ID));
+        let cases = [
+            (
+                Address::new_id(200),
+                METHOD_SEND,
+                false,
+                MessageClass::Transfer,
+            ),
+            (Address::new_id(200), invoke, false, MessageClass::EvmCall),
+            (eam::EAM_ACTOR_ADDR, 4, false, MessageClass::EvmCreate),
+            (gateway, invoke, false, MessageClass::Ipc),
+            (Address::new_id(200), invoke, true, MessageClass::Ipc),
+            (Address::new_id(200), 2, false, MessageClass::Other),
+        ];
+        for (to, method_num, is_ipc, class) in cases {
+            let ret = apply_ret(to, method_num, 0);
+            assert_eq!(MessageClass::of(&ret, is_ipc), class, "{to} {method_num}");
+        }
+    }
+
+    #[test]
+    fn aggregate_top_actors() {
+        let invoke = evm::Method::InvokeContract as u64;
+        let a = Address::new_id(200);
+        let b = Address::new_id(201);
+
+        let mut block1 = BlockGasStats::default();
+        block1.record(&apply_ret(a, invoke, 100), false);
+        block1.record(&apply_ret(b, invoke, 150), false);
+
+        let mut block2 = BlockGasStats::default();
+        block2.record(&apply_ret(a, invoke, 100), false);
+        block2.record(&apply_ret(b, METHOD_SEND, 10), false);
+
+        let report = GasStatsReport::aggregate(1, 2, [block1, block2], 1);
+
+        assert_eq!(report.top_actors.len(), 1);
+        assert_eq!(report.top_actors[0].actor, a.to_string());
+        assert_eq!(report.top_actors[0].totals.gas_used, 200);
+        assert_eq!(report.top_actors[0].totals.count, 2);
+
+        let calls = &report.by_class[&MessageClass::EvmCall];
+        assert_eq!(calls.count, 3);
+        assert_eq!(calls.gas_used, 350);
+        assert_eq!(report.by_class[&MessageClass::Transfer].gas_used, 10);
+    }
+}
@@ -0,0 +1,485 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Offline validation of a genesis file, before anyone starts a chain with it.
+//!
+//! Most mistakes in a genesis only surface when the validators try to initialize the chain,
+//! or worse, after it's running: a duplicate account fails `InitChain` on every node, while a
+//! validator with no power or a multisig nobody can reach the threshold of goes unnoticed. The
+//! checks here don't need anything but the file, so they can gate a subnet launch in CI.
+//!
+//! Problems which make the genesis unusable are errors; things which are allowed but most likely
+//! not intended, like accounts without a balance, are warnings.
+
+use std::collections::HashMap;
+
+use fendermint_vm_actor_interface::eam::EAM_ACTOR_ID;
+use fendermint_vm_genesis::{ActorMeta, Genesis, SignerAddr};
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::econ::TokenAmount;
+use serde::Serialize;
+
+pub use fendermint_vm_genesis::MAX_TOTAL_VOTING_POWER;
+
+/// The total supply of FIL; no genesis can hand out more than this.
+const MAX_TOTAL_BALANCE_FIL: u64 = 2_000_000_000;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the genesis.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Name of the check, stable enough to filter on.
+    pub check: &'static str,
+    /// Where the problem is, e.g. `accounts[3].balance`.
+    pub path: String,
+    pub message: String,
+}
+
+/// The outcome of validating a genesis.
+#[derive(Debug, Clone, Serialize)]
+pub struct GenesisReport {
+    /// Whether there are no errors; there can still be warnings.
+    pub valid: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Sum of the account balances, in FIL.
+    pub total_balance: String,
+    /// Sum of the power of the validators, with the power scale of the genesis.
+    pub total_power: u128,
+    pub findings: Vec<Finding>,
+}
+
+/// Run every check on the genesis.
+pub fn validate(genesis: &Genesis) -> GenesisReport {
+    let mut audit = Audit::default();
+
+    if genesis.chain_name.trim().is_empty() {
+        audit.error("chain_name", "chain_name", "the chain name is empty");
+    }
+    if genesis.base_fee.is_negative() {
+        audit.error("base_fee", "base_fee", "the base fee is negative");
+    }
+
+    let total_balance = check_accounts(&mut audit, genesis);
+    let total_power = check_validators(&mut audit, genesis);
+
+    if let Some(ref ac) = genesis.access_control {
+        audit.signer("address_format", "access_control.governor", &ac.governor);
+        for (list, addrs) in [("deployers", &ac.deployers), ("senders", &ac.senders)] {
+            for (i, a) in addrs.iter().flatten().enumerate() {
+                audit.signer("address_format", format!("access_control.{list}[{i}]"), a);
+            }
+        }
+    }
+
+    if let Some(ref gov) = genesis.governance {
+        audit.signers("governance.members", &gov.members);
+        audit.threshold("governance.threshold", gov.threshold, gov.members.len());
+        if gov.epoch_length == 0 {
+            audit.error(
+                "governance",
+                "governance.epoch_length",
+                "the epoch length must be positive",
+            );
+        }
+    }
+
+    if let Some(ref ipc) = genesis.ipc {
+        let gw = &ipc.gateway;
+        if gw.bottom_up_check_period == 0 {
+            audit.error(
+                "ipc",
+                "ipc.gateway.bottom_up_check_period",
+                "the checkpoint period must be positive",
+            );
+        }
+        if !(51..=100).contains(&gw.majority_percentage) {
+            audit.error(
+                "ipc",
+                "ipc.gateway.majority_percentage",
+                format!(
+                    "the majority must be between 51 and 100 percent, not {}",
+                    gw.majority_percentage
+                ),
+            );
+        }
+        if gw.active_validators_limit == 0 {
+            audit.error(
+                "ipc",
+                "ipc.gateway.active_validators_limit",
+                "the limit on active validators must be positive",
+            );
+        } else if genesis.validators.len() > gw.active_validators_limit as usize {
+            audit.warning(
+                "ipc",
+                "validators",
+                format!(
+                    "there are {} validators, more than the limit of {} active ones",
+                    genesis.validators.len(),
+                    gw.active_validators_limit
+                ),
+            );
+        }
+    }
+
+    if let Some(ref beacon) = genesis.beacon {
+        if beacon.epoch_length < 2 {
+            audit.error(
+                "beacon",
+                "beacon.epoch_length",
+                "the beacon epoch has to be at least 2 blocks long",
+            );
+        }
+    }
+
+    if let Some(ref rewards) = genesis.rewards {
+        if rewards.epoch_length == 0 {
+            audit.error(
+                "rewards",
+                "rewards.epoch_length",
+                "the reward epoch has to be at least 1 block long",
+            );
+        }
+    }
+
+    for (i, call) in genesis.scheduled_calls.iter().enumerate() {
+        if call.start_height == 0 {
+            audit.error(
+                "scheduled_calls",
+                format!("scheduled_calls[{i}].start_height"),
+                format!("the call '{}' has to start at a positive height", call.name),
+            );
+        }
+    }
+
+    audit.report(total_balance, total_power)
+}
+
+fn check_accounts(audit: &mut Audit, genesis: &Genesis) -> TokenAmount {
+    let mut owners: HashMap<&Address, usize> = HashMap::new();
+    let mut total = TokenAmount::from_atto(0);
+
+    for (i, actor) in genesis.accounts.iter().enumerate() {
+        let path = format!("accounts[{i}]");
+
+        if actor.balance.is_negative() {
+            audit.error(
+                "balance",
+                format!("{path}.balance"),
+                format!("the balance is negative: {}", actor.balance),
+            );
+        } else if actor.balance.is_zero() {
+            audit.warning("balance", format!("{path}.balance"), "the balance is zero");
+        }
+        total += actor.balance.clone();
+
+        match actor.meta {
+            ActorMeta::Account(ref acc) => {
+                audit.signer("address_format", format!("{path}.owner"), &acc.owner);
+                if let Some(first) = owners.insert(&acc.owner.0, i) {
+                    audit.error(
+                        "duplicate_account",
+                        path,
+                        format!("{} is already the owner of accounts[{first}]", acc.owner.0),
+                    );
+                }
+            }
+            ActorMeta::Multisig(ref ms) => {
+                audit.signers(&format!("{path}.signers"), &ms.signers);
+                audit.threshold(&format!("{path}.threshold"), ms.threshold, ms.signers.len());
+            }
+        }
+    }
+
+    if total > TokenAmount::from_whole(MAX_TOTAL_BALANCE_FIL) {
+        audit.error(
+            "balance",
+            "accounts",
+            format!("the balances add up to {total} FIL, more than the total supply of FIL"),
+        );
+    }
+
+    total
+}
+
+fn check_validators(audit: &mut Audit, genesis: &Genesis) -> u128 {
+    let mut keys = HashMap::new();
+    let mut total: u128 = 0;
+
+    if genesis.validators.is_empty() {
+        audit.error("validators", "validators", "there are no validators");
+    }
+
+    for (i, v) in genesis.validators.iter().enumerate() {
+        let path = format!("validators[{i}]");
+
+        if let Some(first) = keys.insert(v.public_key.0.serialize(), i) {
+            audit.error(
+                "duplicate_validator",
+                path.clone(),
+                format!("the key is already used by validators[{first}]"),
+            );
+        }
+
+        if v.power.0.is_negative() {
+            audit.error(
+                "power",
+                format!("{path}.power"),
+                "the collateral is negative",
+            );
+            continue;
+        }
+
+        let power = v.power.clone().into_power(genesis.power_scale).0;
+        if power == 0 {
+            audit.error(
+                "power",
+                format!("{path}.power"),
+                format!(
+                    "the collateral of {} FIL is no power with a power scale of {}",
+                    v.power.0, genesis.power_scale
+                ),
+            );
+        }
+        total += power as u128;
+    }
+
+    if total > MAX_TOTAL_VOTING_POWER as u128 {
+        audit.error(
+            "power",
+            "validators",
+            format!(
+                "the total power is {total}, more than the {MAX_TOTAL_VOTING_POWER} Tendermint allows; use a lower power scale"
+            ),
+        );
+    }
+
+    total
+}
+
+#[derive(Default)]
+struct Audit {
+    findings: Vec<Finding>,
+}
+
+impl Audit {
+    fn add(
+        &mut self,
+        severity: Severity,
+        check: &'static str,
+        path: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            check,
+            path: path.into(),
+            message: message.into(),
+        })
+    }
+
+    fn error(&mut self, check: &'static str, path: impl Into<String>, message: impl Into<String>) {
+        self.add(Severity::Error, check, path, message)
+    }
+
+    fn warning(
+        &mut self,
+        check: &'static str,
+        path: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.add(Severity::Warning, check, path, message)
+    }
+
+    /// Check that an address can be the owner of an account created at genesis.
+    fn signer(&mut self, check: &'static str, path: impl Into<String>, addr: &SignerAddr) {
+        if let Err(e) = check_signer_address(&addr.0) {
+            self.error(check, path, format!("{}: {e}", addr.0));
+        }
+    }
+
+    fn signers(&mut self, path: &str, signers: &[SignerAddr]) {
+        let mut seen: HashMap<&Address, usize> = HashMap::new();
+        for (i, s) in signers.iter().enumerate() {
+            let path = format!("{path}[{i}]");
+            self.signer("address_format", path.clone(), s);
+            if let Some(first) = seen.insert(&s.0, i) {
+                self.error(
+                    "duplicate_signer",
+                    path,
+                    format!("{} is the same as signer {first}", s.0),
+                );
+            }
+        }
+    }
+
+    fn threshold(&mut self, path: &str, threshold: u64, signers: usize) {
+        if signers == 0 {
+            self.error("threshold", path, "there are no signers");
+        } else if threshold == 0 {
+            self.error("threshold", path, "the threshold must be positive");
+        } else if threshold > signers as u64 {
+            self.error(
+                "threshold",
+                path,
+                format!("the threshold of {threshold} is more than the {signers} signers"),
+            );
+        }
+    }
+
+    fn report(self, total_balance: TokenAmount, total_power: u128) -> GenesisReport {
+        let errors = self
+            .findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count();
+        let warnings = self.findings.len() - errors;
+        GenesisReport {
+            valid: errors == 0,
+            errors,
+            warnings,
+            total_balance: total_balance.to_string(),
+            total_power,
+            findings: self.findings,
+        }
+    }
+}
+
+/// Accounts are created for `f1` addresses and for Ethereum `f410` addresses.
+fn check_signer_address(addr: &Address) -> Result<(), &'static str> {
+    match addr.payload() {
+        Payload::Secp256k1(_) => Ok(()),
+        Payload::Delegated(d) if d.namespace() != EAM_ACTOR_ID => {
+            Err("delegated addresses have to be in the namespace of the EAM")
+        }
+        Payload::Delegated(d) if d.subaddress().len() != 20 => {
+            Err("Ethereum addresses have to be 20 bytes long")
+        }
+        Payload::Delegated(_) => Ok(()),
+        _ => Err("accounts can only be created for secp256k1 and Ethereum addresses"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_core::Timestamp;
+    use fendermint_vm_genesis::{
+        Account, Actor, ActorMeta, Collateral, Genesis, Multisig, SignerAddr, Validator,
+        ValidatorKey,
+    };
+    use fvm_shared::{address::Address, econ::TokenAmount, version::NetworkVersion};
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    use super::{validate, Severity};
+
+    fn genesis() -> Genesis {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let sk = SecretKey::random(&mut rng);
+        let owner = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        Genesis {
+            chain_name: "test".to_owned(),
+            timestamp: Timestamp(0),
+            network_version: NetworkVersion::V18,
+            base_fee: TokenAmount::from_atto(1000),
+            power_scale: 3,
+            validators: vec![Validator {
+                public_key: ValidatorKey(sk.public_key()),
+                power: Collateral(TokenAmount::from_whole(1)),
+            }],
+            accounts: vec![Actor {
+                meta: ActorMeta::Account(Account {
+                    owner: SignerAddr(owner),
+                }),
+                balance: TokenAmount::from_whole(10),
+            }],
+            ipc: None,
+            access_control: None,
+            governance: None,
+            scheduled_calls: Vec::new(),
+            tx_compression: false,
+            filecoin_signatures: false,
+            system_contracts: Vec::new(),
+            beacon: None,
+            policy: None,
+            rewards: None,
+            exec_digests: false,
+            max_block_interval: None,
+            eth_block_hash_v1: false,
+            canonical_tx_order: false,
+            fast_topdown_finality: false,
+        }
+    }
+
+    fn checks(genesis: &Genesis) -> Vec<(Severity, &'static str)> {
+        validate(genesis)
+            .findings
+            .into_iter()
+            .map(|f| (f.severity, f.check))
+            .collect()
+    }
+
+    #[test]
+    fn valid_genesis() {
+        let report = validate(&genesis());
+        assert!(report.valid, "{report:?}");
+        assert_eq!(report.total_power, 1000);
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut g = genesis();
+        g.accounts.push(g.accounts[0].clone());
+        g.validators.push(g.validators[0].clone());
+        assert_eq!(
+            checks(&g),
+            vec![
+                (Severity::Error, "duplicate_account"),
+                (Severity::Error, "duplicate_validator")
+            ]
+        );
+    }
+
+    #[test]
+    fn balances_and_power() {
+        let mut g = genesis();
+        g.accounts[0].balance = TokenAmount::from_atto(0);
+        g.validators[0].power = Collateral(TokenAmount::from_whole(u64::MAX));
+        assert_eq!(
+            checks(&g),
+            vec![(Severity::Warning, "balance"), (Severity::Error, "power")]
+        );
+    }
+
+    #[test]
+    fn multisig_threshold_and_addresses() {
+        let mut g = genesis();
+        let owner = match g.accounts[0].meta {
+            ActorMeta::Account(ref a) => a.owner.clone(),
+            _ => unreachable!(),
+        };
+        g.accounts.push(Actor {
+            meta: ActorMeta::Multisig(Multisig {
+                signers: vec![owner, SignerAddr(Address::new_id(100))],
+                threshold: 3,
+                vesting_duration: 0,
+                vesting_start: 0,
+            }),
+            balance: TokenAmount::from_whole(1),
+        });
+        assert_eq!(
+            checks(&g),
+            vec![
+                (Severity::Error, "address_format"),
+                (Severity::Error, "threshold")
+            ]
+        );
+    }
+}
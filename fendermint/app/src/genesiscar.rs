@@ -0,0 +1,460 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Distribution of the CAR file the chain is initialised from between the validators.
+//!
+//! Every node needs the same CAR file to execute `InitChain`, and it's too big to pass around
+//! with the genesis JSON. The first validator can serve it over HTTP, along with its SHA-256
+//! checksum, and the others can download it in ranges of a configured size before they start.
+//! An interrupted download is resumed from where it stopped, and the file is only moved into
+//! place once its checksum matches.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use axum::{
+    body::StreamBody,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+/// The path the CAR file is served on; the checksum is served on the same path with `.sha256` added.
+pub const CAR_PATH: &str = "/genesis.car";
+
+/// The largest range served at once, to bound the memory used by a request.
+const MAX_RANGE_LEN: u64 = 64 * 1024 * 1024;
+
+/// Number of times to try downloading a chunk before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// A CAR file being served, with its size and checksum computed up front.
+#[derive(Debug, Clone)]
+struct CarFile {
+    path: PathBuf,
+    size: u64,
+    checksum: String,
+}
+
+/// Serve the CAR file and its checksum until the process exits.
+pub async fn serve<A: ToSocketAddrs>(listen: A, path: PathBuf) -> anyhow::Result<()> {
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))?;
+
+    let app = router(path).await?;
+
+    tracing::info!(?addr, "serving the genesis CAR file");
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .context("failed to serve the genesis CAR file")
+}
+
+async fn router(path: PathBuf) -> anyhow::Result<Router> {
+    let size = tokio::fs::metadata(&path)
+        .await
+        .with_context(|| format!("failed to read the CAR file at {path:?}"))?
+        .len();
+
+    let checksum = file_checksum(&path).await?;
+
+    tracing::info!(?path, size, checksum, "computed the genesis CAR checksum");
+
+    let car = Arc::new(CarFile {
+        path,
+        size,
+        checksum,
+    });
+
+    Ok(Router::new()
+        .route(CAR_PATH, get(get_car))
+        .route(&format!("{CAR_PATH}.sha256"), get(get_checksum))
+        .with_state(car))
+}
+
+async fn get_checksum(State(car): State<Arc<CarFile>>) -> String {
+    format!("{}  genesis.car\n", car.checksum)
+}
+
+async fn get_car(State(car): State<Arc<CarFile>>, headers: HeaderMap) -> Response {
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, car.size));
+
+    let result = match range {
+        None | Some(ByteRange::Ignored) => full_response(&car).await,
+        Some(ByteRange::Partial { start, end }) => range_response(&car, start, end).await,
+        Some(ByteRange::Unsatisfiable) => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", car.size))],
+            )
+                .into_response()
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        tracing::error!(error = e.to_string(), "failed to read the genesis CAR file");
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    })
+}
+
+async fn full_response(car: &CarFile) -> anyhow::Result<Response> {
+    let file = tokio::fs::File::open(&car.path).await?;
+    let mut res = StreamBody::new(ReaderStream::new(file)).into_response();
+    let headers = res.headers_mut();
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(car.size));
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    Ok(res)
+}
+
+async fn range_response(car: &CarFile, start: u64, end: u64) -> anyhow::Result<Response> {
+    let path = car.path.clone();
+    let bytes = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+        let mut file = std::fs::File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = vec![0u8; (end - start + 1) as usize];
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    })
+    .await??;
+
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{}", car.size),
+            ),
+            (header::ACCEPT_RANGES, "bytes".to_owned()),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// The part of the file requested in a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    /// The header isn't something we support, so the whole file is served.
+    Ignored,
+    /// An inclusive range of bytes, limited to [`MAX_RANGE_LEN`].
+    Partial { start: u64, end: u64 },
+    /// The range starts past the end of the file.
+    Unsatisfiable,
+}
+
+/// Parse a single `bytes=<start>-<end>`, `bytes=<start>-` or `bytes=-<suffix>` range.
+fn parse_range(value: &str, size: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Ignored;
+    };
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Ignored;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end),
+        (Ok(start), Err(_)) if end.is_empty() => (start, u64::MAX),
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (size.saturating_sub(suffix), u64::MAX)
+        }
+        _ => return ByteRange::Ignored,
+    };
+
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+
+    let end = end.min(size - 1).min(start + MAX_RANGE_LEN - 1);
+
+    ByteRange::Partial { start, end }
+}
+
+/// Parse the `bytes <start>-<end>/<size>` value of a `Content-Range` header.
+fn parse_content_range(value: &str) -> Option<(u64, u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes ")?;
+    let (range, size) = spec.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    Some((start.parse().ok()?, end.parse().ok()?, size.parse().ok()?))
+}
+
+/// Download the CAR file served by another node, unless there is already one with the right checksum.
+///
+/// The file is downloaded next to the destination with a `.part` extension, in chunks of the
+/// given size, resuming a previous download if there is one. If no checksum is given then it's
+/// fetched from the server as well, which only protects against a corrupted transfer, and an
+/// existing file is kept if the server cannot be reached.
+pub async fn download(
+    url: &str,
+    checksum: Option<&str>,
+    chunk_size: u64,
+    dest: &Path,
+) -> anyhow::Result<()> {
+    if chunk_size == 0 {
+        bail!("the chunk size has to be positive");
+    }
+
+    let client = reqwest::Client::new();
+
+    let expected = match checksum {
+        Some(checksum) => checksum.trim().to_lowercase(),
+        None => match fetch_checksum(&client, url).await {
+            Ok(checksum) => checksum,
+            // Don't prevent a restart because the node serving the file has gone away.
+            Err(e) if dest.exists() => {
+                tracing::warn!(
+                    error = e.to_string(),
+                    ?dest,
+                    "failed to fetch the genesis CAR checksum; using the existing file"
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        },
+    };
+
+    if dest.exists() {
+        if file_checksum(dest).await? == expected {
+            tracing::info!(?dest, "the genesis CAR file is already downloaded");
+            return Ok(());
+        }
+        tracing::warn!(
+            ?dest,
+            "the existing genesis CAR file has a different checksum"
+        );
+    }
+
+    if let Some(dir) = dest.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let part = part_path(dest);
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&part)
+        .await
+        .with_context(|| format!("failed to open {part:?}"))?;
+
+    let mut offset = file.metadata().await?.len();
+
+    if offset > 0 {
+        tracing::info!(?part, offset, "resuming the genesis CAR download");
+    }
+
+    loop {
+        let end = offset + chunk_size - 1;
+        let res = fetch_chunk(&client, url, offset, end).await?;
+
+        match res.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let content_range = res
+                    .headers()
+                    .get(header::CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_content_range)
+                    .ok_or_else(|| anyhow!("invalid Content-Range in the response"))?;
+
+                let (start, _, size) = content_range;
+                if start != offset {
+                    bail!("the server returned a range from {start} instead of {offset}");
+                }
+
+                let bytes = res.bytes().await?;
+                if bytes.is_empty() {
+                    bail!("the server returned an empty range at {offset}");
+                }
+                file.write_all(&bytes).await?;
+                offset += bytes.len() as u64;
+
+                tracing::debug!(offset, size, "downloaded genesis CAR chunk");
+
+                if offset >= size {
+                    break;
+                }
+            }
+            // We already have everything; the checksum will tell if it's right.
+            StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => break,
+            // The server doesn't do ranges, so start over with the full content.
+            StatusCode::OK => {
+                tracing::warn!("the server doesn't support ranges; downloading the whole file");
+                file.set_len(0).await?;
+                let bytes = res.bytes().await?;
+                file.write_all(&bytes).await?;
+                break;
+            }
+            status => bail!("unexpected status downloading the genesis CAR file: {status}"),
+        }
+    }
+
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    let actual = file_checksum(&part).await?;
+    if actual != expected {
+        tokio::fs::remove_file(&part).await?;
+        bail!("the checksum of the downloaded genesis CAR file is {actual} instead of {expected}");
+    }
+
+    tokio::fs::rename(&part, dest)
+        .await
+        .with_context(|| format!("failed to move {part:?} to {dest:?}"))?;
+
+    tracing::info!(
+        ?dest,
+        checksum = expected,
+        "downloaded the genesis CAR file"
+    );
+
+    Ok(())
+}
+
+async fn fetch_checksum(client: &reqwest::Client, url: &str) -> anyhow::Result<String> {
+    let res = client
+        .get(format!("{url}.sha256"))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .context("failed to fetch the genesis CAR checksum")?;
+
+    let text = res.text().await?;
+
+    text.split_whitespace()
+        .next()
+        .map(|c| c.to_lowercase())
+        .ok_or_else(|| anyhow!("empty genesis CAR checksum"))
+}
+
+/// Fetch a range of the file, retrying a few times with an increasing delay.
+async fn fetch_chunk(
+    client: &reqwest::Client,
+    url: &str,
+    start: u64,
+    end: u64,
+) -> anyhow::Result<reqwest::Response> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match client
+            .get(url)
+            .header(header::RANGE, format!("bytes={start}-{end}"))
+            .send()
+            .await
+        {
+            Ok(res) => return Ok(res),
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                tracing::warn!(
+                    error = e.to_string(),
+                    start,
+                    attempt,
+                    "failed to fetch genesis CAR chunk; retrying"
+                );
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+            }
+            Err(e) => return Err(e).context("failed to fetch genesis CAR chunk"),
+        }
+    }
+}
+
+/// The file a download is written to until it's verified.
+fn part_path(dest: &Path) -> PathBuf {
+    let mut part = dest.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Hex encoded SHA-256 digest of a file.
+pub async fn file_checksum(path: &Path) -> anyhow::Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)
+            .with_context(|| format!("failed to open {path:?} to compute its checksum"))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{download, parse_content_range, parse_range, part_path, router, ByteRange};
+
+    #[test]
+    fn parse_ranges() {
+        let p = |start, end| ByteRange::Partial { start, end };
+
+        assert_eq!(parse_range("bytes=0-99", 1000), p(0, 99));
+        assert_eq!(parse_range("bytes=900-1099", 1000), p(900, 999));
+        assert_eq!(parse_range("bytes=100-", 1000), p(100, 999));
+        assert_eq!(parse_range("bytes=-100", 1000), p(900, 999));
+        assert_eq!(parse_range("bytes=-2000", 1000), p(0, 999));
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=10-5", 1000), ByteRange::Ignored);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), ByteRange::Ignored);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Ignored);
+    }
+
+    #[test]
+    fn parse_content_ranges() {
+        assert_eq!(parse_content_range("bytes 0-99/1000"), Some((0, 99, 1000)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+    }
+
+    #[tokio::test]
+    async fn resume_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("bundle.car");
+        let dest = dir.path().join("download").join("bundle.car");
+
+        let content = (0..10_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        std::fs::write(&src, &content).unwrap();
+
+        // Pretend that a previous download was interrupted.
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::write(part_path(&dest), &content[..2500]).unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(src).await.unwrap();
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+        });
+
+        let url = format!("http://{addr}/genesis.car");
+
+        download(&url, None, 1000, &dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+        assert!(!part_path(&dest).exists());
+
+        // A wrong checksum is not accepted, and the partial download is discarded.
+        std::fs::remove_file(&dest).unwrap();
+        let err = download(&url, Some("abcd"), 3000, &dest).await.unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+    }
+}
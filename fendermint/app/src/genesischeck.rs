@@ -0,0 +1,234 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cross-checking the IPC configuration in the genesis against what the parent has registered.
+//!
+//! A subnet whose genesis disagrees with the parent about its own ID or about the checkpointing
+//! parameters starts up just fine, but the parent rejects every checkpoint it submits. The check
+//! compares the gateway parameters of the genesis with the ones the subnet was created with on
+//! the parent, after making sure that the gateway and the registry are deployed there.
+//!
+//! The subnets of this version of IPC are all funded in the native token of the parent, so there
+//! is no supply source in the genesis to compare.
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Context};
+use ethers::providers::{Http, Middleware, Provider};
+use fendermint_vm_genesis::{ipc::GatewayParams, Genesis};
+use fendermint_vm_message::conv::from_fvm::to_eth_address;
+use fvm_shared::address::Address;
+use ipc_provider::config::subnet::{EVMSubnet, SubnetConfig};
+use ipc_provider::IpcProvider;
+use ipc_sdk::subnet_id::SubnetID;
+
+/// A value in the genesis which differs from what was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenesisMismatch {
+    pub field: &'static str,
+    pub genesis: String,
+    pub expected: String,
+}
+
+impl Display for GenesisMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: the genesis has {}, the parent has {}",
+            self.field, self.genesis, self.expected
+        )
+    }
+}
+
+/// Compare the gateway parameters in the genesis with the ones expected by the parent.
+pub fn diff_gateway_params(
+    genesis: &GatewayParams,
+    parent: &GatewayParams,
+) -> Vec<GenesisMismatch> {
+    let mut diffs = Vec::new();
+    let mut diff = |field, genesis: &dyn Display, expected: &dyn Display| {
+        let (genesis, expected) = (genesis.to_string(), expected.to_string());
+        if genesis != expected {
+            diffs.push(GenesisMismatch {
+                field,
+                genesis,
+                expected,
+            })
+        }
+    };
+    diff("subnet_id", &genesis.subnet_id, &parent.subnet_id);
+    diff(
+        "bottom_up_check_period",
+        &genesis.bottom_up_check_period,
+        &parent.bottom_up_check_period,
+    );
+    diff("msg_fee", &genesis.msg_fee.atto(), &parent.msg_fee.atto());
+    diff(
+        "majority_percentage",
+        &genesis.majority_percentage,
+        &parent.majority_percentage,
+    );
+    diff(
+        "min_collateral",
+        &genesis.min_collateral.atto(),
+        &parent.min_collateral.atto(),
+    );
+    diff(
+        "active_validators_limit",
+        &genesis.active_validators_limit,
+        &parent.active_validators_limit,
+    );
+    diffs
+}
+
+/// Checks the genesis of a subnet against its registration on the parent.
+#[derive(Debug, Clone)]
+pub struct ParentGenesisCheck {
+    /// The subnet the genesis is supposed to be for.
+    pub subnet_id: SubnetID,
+    /// The JSON-RPC endpoint of the parent.
+    pub parent_endpoint: String,
+    pub parent_gateway: Address,
+    pub parent_registry: Address,
+}
+
+impl ParentGenesisCheck {
+    /// Compare the genesis with the parent, returning the values which differ.
+    ///
+    /// Fails if the parent cannot be queried, or if it doesn't have the gateway,
+    /// the registry, or the subnet itself.
+    pub async fn check(&self, genesis: &Genesis) -> anyhow::Result<Vec<GenesisMismatch>> {
+        let Some(ipc) = genesis.ipc.as_ref() else {
+            return Ok(vec![GenesisMismatch {
+                field: "ipc",
+                genesis: "no IPC configuration".to_owned(),
+                expected: format!("the child subnet {}", self.subnet_id),
+            }]);
+        };
+
+        self.ensure_deployed("gateway", &self.parent_gateway)
+            .await?;
+        self.ensure_deployed("registry", &self.parent_registry)
+            .await?;
+
+        let parent = self.parent_gateway_params().await?;
+
+        Ok(diff_gateway_params(&ipc.gateway, &parent))
+    }
+
+    /// Check the genesis, failing with the list of differences if there are any.
+    pub async fn verify(&self, genesis: &Genesis) -> anyhow::Result<()> {
+        let diffs = self.check(genesis).await?;
+        if diffs.is_empty() {
+            return Ok(());
+        }
+        let diffs = diffs.iter().map(|d| format!("\n  {d}")).collect::<String>();
+        bail!(
+            "the genesis doesn't match subnet {} on the parent:{diffs}",
+            self.subnet_id
+        )
+    }
+
+    /// The gateway parameters the subnet was created with on the parent.
+    async fn parent_gateway_params(&self) -> anyhow::Result<GatewayParams> {
+        let parent_id = self
+            .subnet_id
+            .parent()
+            .ok_or_else(|| anyhow!("subnet {} is not a child", self.subnet_id))?;
+
+        let provider = IpcProvider::new_with_subnet(
+            None,
+            ipc_provider::config::Subnet {
+                id: parent_id,
+                config: SubnetConfig::Fevm(EVMSubnet {
+                    provider_http: self
+                        .parent_endpoint
+                        .parse()
+                        .context("invalid parent endpoint")?,
+                    auth_token: None,
+                    registry_addr: self.parent_registry,
+                    gateway_addr: self.parent_gateway,
+                }),
+            },
+        )?;
+
+        let info = provider
+            .get_genesis_info(&self.subnet_id)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to get the genesis of subnet {} from the parent; is it registered?",
+                    self.subnet_id
+                )
+            })?;
+
+        Ok(GatewayParams {
+            subnet_id: self.subnet_id.clone(),
+            bottom_up_check_period: info.bottom_up_checkpoint_period,
+            msg_fee: info.msg_fee,
+            majority_percentage: info.majority_percentage,
+            min_collateral: info.min_collateral,
+            active_validators_limit: info.active_validators_limit,
+        })
+    }
+
+    /// Make sure there is a contract at an address on the parent.
+    async fn ensure_deployed(&self, name: &str, addr: &Address) -> anyhow::Result<()> {
+        let eth_addr = to_eth_address(addr)
+            .ok_or_else(|| anyhow!("the parent {name} has to be an Ethereum address"))?;
+
+        let provider = Provider::<Http>::try_from(self.parent_endpoint.as_str())
+            .context("failed to create parent provider")?;
+
+        let code = provider
+            .get_code(eth_addr, None)
+            .await
+            .with_context(|| format!("failed to get the code of the parent {name}"))?;
+
+        if code.is_empty() {
+            bail!("there is no {name} deployed on the parent at {eth_addr:?}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_genesis::ipc::GatewayParams;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use super::diff_gateway_params;
+
+    fn params(subnet_id: SubnetID, bottom_up_check_period: u64) -> GatewayParams {
+        GatewayParams {
+            subnet_id,
+            bottom_up_check_period,
+            msg_fee: TokenAmount::from_atto(10),
+            majority_percentage: 67,
+            min_collateral: TokenAmount::from_whole(1),
+            active_validators_limit: 100,
+        }
+    }
+
+    #[test]
+    fn explicit_diffs() {
+        let subnet = SubnetID::new(314159, vec![Address::new_id(1001)]);
+        let other = SubnetID::new(314159, vec![Address::new_id(1002)]);
+
+        assert!(
+            diff_gateway_params(&params(subnet.clone(), 10), &params(subnet.clone(), 10))
+                .is_empty()
+        );
+
+        let diffs = diff_gateway_params(&params(other.clone(), 10), &params(subnet.clone(), 20));
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].field, "subnet_id");
+        assert_eq!(diffs[0].genesis, other.to_string());
+        assert_eq!(diffs[0].expected, subnet.to_string());
+        assert_eq!(
+            diffs[1].to_string(),
+            "bottom_up_check_period: the genesis has 10, the parent has 20"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Indexing the transactions and the events of the executed blocks.
+//!
+//! The logs blooms and the block hashes kept in RocksDB are always maintained, because the Ethereum
+//! API relies on them; further indexes, e.g. a SQLite database for explorers, can be added to the application.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use ethers_core::types as et;
+use fendermint_storage::{
+    Codec, Encode, KVCollection, KVError, KVRead, KVReadable, KVResult, KVStore, KVWritable,
+    KVWrite,
+};
+use fendermint_vm_message::query::MAX_LOGS_BLOOM_RANGE;
+use fvm_ipld_encoding::RawBytes;
+use tendermint::abci::response;
+
+use crate::tmconv::accrue_logs_bloom;
+use crate::{BlockHash, BlockHeight};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Called on the commit path of the application with everything executed in a block.
+pub trait EventIndex: Send + Sync {
+    /// Start indexing a new block, forgetting anything left over from a block which wasn't committed.
+    fn begin(&self, height: BlockHeight) -> anyhow::Result<()>;
+
+    /// Index a transaction delivered in the current block, in the order they are executed.
+    fn delivered(
+        &self,
+        height: BlockHeight,
+        tx: &[u8],
+        response: &response::DeliverTx,
+    ) -> anyhow::Result<()>;
+
+    /// Persist what has been indexed about the block, once its state has been committed.
+    fn committed(&self, height: BlockHeight) -> anyhow::Result<()>;
+}
+
+/// The logs bloom of each committed block, stored in the database of the application,
+/// to speed up log queries over long ranges.
+///
+/// The blooms are written and pruned in the same transaction as the committed state,
+/// so they are kept for the same blocks as the state history.
+pub struct LogsBloomIndex<DB, S: KVStore> {
+    db: Arc<DB>,
+    blooms: KVCollection<S, BlockHeight, RawBytes>,
+    /// Logs bloom of the block being executed.
+    pending: Mutex<et::Bloom>,
+}
+
+impl<DB, S> LogsBloomIndex<DB, S>
+where
+    S: KVStore + Encode<BlockHeight> + Codec<RawBytes>,
+    DB: KVWritable<S> + KVReadable<S>,
+{
+    pub fn new(db: Arc<DB>, namespace: S::Namespace) -> Self {
+        Self {
+            db,
+            blooms: KVCollection::new(namespace),
+            pending: Mutex::new(et::Bloom::zero()),
+        }
+    }
+
+    /// Start a new block, forgetting the bloom of any block which wasn't committed.
+    pub fn begin(&self) {
+        *self.pending.lock().unwrap() = et::Bloom::zero();
+    }
+
+    /// Add the logs of a transaction delivered in the current block.
+    pub fn delivered(&self, response: &response::DeliverTx) {
+        accrue_logs_bloom(&mut self.pending.lock().unwrap(), &response.events);
+    }
+
+    /// Store the logs bloom of the current block as part of its commit.
+    pub fn put(&self, tx: &mut impl KVWrite<S>, height: BlockHeight) -> KVResult<()> {
+        let bloom = std::mem::take(&mut *self.pending.lock().unwrap());
+        self.blooms
+            .put(tx, &height, &RawBytes::new(bloom.as_bytes().to_vec()))
+    }
+
+    /// Forget the logs bloom of a block which fell out of the state history.
+    pub fn delete(&self, tx: &mut impl KVWrite<S>, height: BlockHeight) -> KVResult<()> {
+        self.blooms.delete(tx, &height)
+    }
+
+    /// Logs blooms of the committed blocks in an inclusive range of heights, limited to
+    /// [`MAX_LOGS_BLOOM_RANGE`]; heights without a bloom (e.g. pruned ones) are left out.
+    pub fn blooms(
+        &self,
+        from: BlockHeight,
+        to: BlockHeight,
+    ) -> anyhow::Result<Vec<(BlockHeight, RawBytes)>> {
+        let to = to.min(from.saturating_add(MAX_LOGS_BLOOM_RANGE - 1));
+        let tx = self.db.read();
+        let mut blooms = Vec::new();
+        for height in from..=to {
+            if let Some(bloom) = self.blooms.get(&tx, &height)? {
+                blooms.push((height, bloom));
+            }
+        }
+        Ok(blooms)
+    }
+}
+
+/// The height of each committed block by its hash, so that blocks can be looked up by hash
+/// without scanning, and so that a node can tell if it has executed a block it doesn't store.
+///
+/// On chains where the Ethereum API exposes hashes derived from the CometBFT ones with
+/// [`fendermint_vm_message::block_hash::EthBlockHashVersion`], those are kept in a second
+/// namespace, written in the same transaction. The entries are never pruned, but a node which
+/// restored its state from a snapshot only has them from the height of the snapshot.
+pub struct BlockHashIndex<DB, S: KVStore> {
+    db: Arc<DB>,
+    heights: KVCollection<S, RawBytes, RawBytes>,
+    eth_heights: KVCollection<S, RawBytes, RawBytes>,
+    /// Hash of the block being executed, and its Ethereum hash if it's derived.
+    pending: Mutex<Option<(BlockHash, Option<BlockHash>)>>,
+}
+
+impl<DB, S> BlockHashIndex<DB, S>
+where
+    S: KVStore + Codec<RawBytes>,
+    DB: KVWritable<S> + KVReadable<S>,
+{
+    pub fn new(db: Arc<DB>, namespace: S::Namespace, eth_namespace: S::Namespace) -> Self {
+        Self {
+            db,
+            heights: KVCollection::new(namespace),
+            eth_heights: KVCollection::new(eth_namespace),
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// Remember the hashes of the block being executed, to be stored when it's committed.
+    pub fn begin(&self, block_hash: BlockHash, eth_block_hash: Option<BlockHash>) {
+        *self.pending.lock().unwrap() = Some((block_hash, eth_block_hash));
+    }
+
+    /// Store the height of the block executed since the last call to [`BlockHashIndex::begin`].
+    pub fn committed(&self, height: BlockHeight) -> anyhow::Result<()> {
+        let Some((block_hash, eth_block_hash)) = self.pending.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let height = RawBytes::serialize(height)?;
+        self.db
+            .with_write(|tx| {
+                put_height(tx, &self.heights, block_hash, &height)?;
+                if let Some(eth_block_hash) = eth_block_hash {
+                    put_height(tx, &self.eth_heights, eth_block_hash, &height)?;
+                }
+                Ok(())
+            })
+            .context("failed to store block hash")
+    }
+
+    /// Store the Ethereum hash of a block committed before the first one in the index,
+    /// e.g. from the blocks CometBFT has, moving the first height back.
+    pub fn backfill_eth(
+        &self,
+        height: BlockHeight,
+        eth_block_hash: BlockHash,
+    ) -> anyhow::Result<()> {
+        let bz = RawBytes::serialize(height)?;
+        self.db
+            .with_write(|tx| {
+                let first_height = self.eth_heights.get(tx, &RawBytes::default())?;
+                let is_earlier = match first_height {
+                    Some(first_height) => {
+                        let first_height: BlockHeight = first_height
+                            .deserialize()
+                            .map_err(|e| KVError::Codec(Box::new(e)))?;
+                        first_height > height
+                    }
+                    None => true,
+                };
+                if is_earlier {
+                    self.eth_heights.put(tx, &RawBytes::default(), &bz)?;
+                }
+                self.eth_heights
+                    .put(tx, &RawBytes::new(eth_block_hash.to_vec()), &bz)
+            })
+            .context("failed to store Ethereum block hash")
+    }
+
+    /// Height of a committed block, if this node executed it.
+    pub fn height(&self, block_hash: &[u8]) -> anyhow::Result<Option<BlockHeight>> {
+        if block_hash.is_empty() {
+            return Ok(None);
+        }
+        self.get(&self.heights, block_hash)
+    }
+
+    /// The height of the first block in the index, which is after the genesis
+    /// unless the node restored its state from a snapshot.
+    pub fn first_height(&self) -> anyhow::Result<Option<BlockHeight>> {
+        self.get(&self.heights, &[])
+    }
+
+    /// Height of a committed block by its derived Ethereum hash, if this node executed it.
+    pub fn eth_height(&self, eth_block_hash: &[u8]) -> anyhow::Result<Option<BlockHeight>> {
+        if eth_block_hash.is_empty() {
+            return Ok(None);
+        }
+        self.get(&self.eth_heights, eth_block_hash)
+    }
+
+    /// The height of the first block with a derived Ethereum hash in the index.
+    pub fn eth_first_height(&self) -> anyhow::Result<Option<BlockHeight>> {
+        self.get(&self.eth_heights, &[])
+    }
+
+    fn get(
+        &self,
+        heights: &KVCollection<S, RawBytes, RawBytes>,
+        key: &[u8],
+    ) -> anyhow::Result<Option<BlockHeight>> {
+        let tx = self.db.read();
+        match heights.get(&tx, &RawBytes::new(key.to_vec()))? {
+            Some(height) => Ok(Some(height.deserialize()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Store the height of a block by its hash, and as the first height if the index is empty.
+fn put_height<S>(
+    tx: &mut (impl KVRead<S> + KVWrite<S>),
+    heights: &KVCollection<S, RawBytes, RawBytes>,
+    block_hash: BlockHash,
+    height: &RawBytes,
+) -> KVResult<()>
+where
+    S: KVStore + Codec<RawBytes>,
+{
+    // The empty key can't be a hash; it holds the first height in the index.
+    if heights.get(tx, &RawBytes::default())?.is_none() {
+        heights.put(tx, &RawBytes::default(), height)?;
+    }
+    heights.put(tx, &RawBytes::new(block_hash.to_vec()), height)
+}
@@ -0,0 +1,319 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Index the transactions and the Ethereum logs of every committed block into a SQLite database,
+//! which explorers can query with SQL while the node is running.
+//!
+//! The database is in WAL mode, so readers don't block the node writing to it. Hashes of the
+//! transactions are upper case hex like in CometBFT, while the Ethereum hashes, addresses and
+//! topics are `0x` prefixed lower case hex like in the Ethereum API.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fendermint_vm_message::conv::from_fvm::to_eth_address;
+use fvm_shared::address::Address;
+use rusqlite::{params, Connection};
+use tendermint::abci::{response, Event};
+use tendermint::crypto::sha256::Sha256;
+
+use super::EventIndex;
+use crate::tmconv::event_topics_and_data;
+use crate::BlockHeight;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS blocks (
+    height INTEGER PRIMARY KEY,
+    tx_count INTEGER NOT NULL,
+    log_count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS txs (
+    height INTEGER NOT NULL,
+    tx_index INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    eth_hash TEXT,
+    sender TEXT,
+    recipient TEXT,
+    code INTEGER NOT NULL,
+    gas_wanted INTEGER NOT NULL,
+    gas_used INTEGER NOT NULL,
+    PRIMARY KEY (height, tx_index)
+);
+CREATE INDEX IF NOT EXISTS txs_hash ON txs (hash);
+CREATE INDEX IF NOT EXISTS txs_eth_hash ON txs (eth_hash);
+CREATE INDEX IF NOT EXISTS txs_sender ON txs (sender);
+CREATE INDEX IF NOT EXISTS txs_recipient ON txs (recipient);
+CREATE TABLE IF NOT EXISTS logs (
+    height INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    tx_index INTEGER NOT NULL,
+    tx_log_index INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    topic0 TEXT,
+    topic1 TEXT,
+    topic2 TEXT,
+    topic3 TEXT,
+    data BLOB NOT NULL,
+    PRIMARY KEY (height, log_index)
+);
+CREATE INDEX IF NOT EXISTS logs_address ON logs (address, height);
+CREATE INDEX IF NOT EXISTS logs_topic0 ON logs (topic0, height);
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TxRow {
+    tx_index: usize,
+    hash: String,
+    eth_hash: Option<String>,
+    sender: Option<String>,
+    recipient: Option<String>,
+    code: u32,
+    gas_wanted: i64,
+    gas_used: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogRow {
+    /// Index of the log in the block, the same way the Ethereum API numbers them.
+    log_index: usize,
+    tx_index: usize,
+    tx_log_index: usize,
+    address: String,
+    topics: Vec<String>,
+    data: Vec<u8>,
+}
+
+/// Rows collected about the block being executed.
+#[derive(Default)]
+struct PendingBlock {
+    txs: Vec<TxRow>,
+    logs: Vec<LogRow>,
+    /// Number of events of the transactions delivered so far, which the log indexes start from.
+    event_count: usize,
+}
+
+pub struct SqliteEventIndex {
+    conn: Mutex<Connection>,
+    pending: Mutex<PendingBlock>,
+}
+
+impl SqliteEventIndex {
+    /// Open or create the database, with the tables it needs.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create directory {dir:?}"))?;
+        }
+        let conn =
+            Connection::open(path).with_context(|| format!("failed to open SQLite DB {path:?}"))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)
+            .context("failed to create SQLite schema")?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            pending: Default::default(),
+        })
+    }
+
+    /// The highest block in the index.
+    pub fn last_height(&self) -> anyhow::Result<Option<BlockHeight>> {
+        let conn = self.conn.lock().unwrap();
+        let height: Option<i64> =
+            conn.query_row("SELECT MAX(height) FROM blocks", [], |row| row.get(0))?;
+        Ok(height.map(|h| h as BlockHeight))
+    }
+}
+
+impl EventIndex for SqliteEventIndex {
+    fn begin(&self, _height: BlockHeight) -> anyhow::Result<()> {
+        *self.pending.lock().unwrap() = Default::default();
+        Ok(())
+    }
+
+    fn delivered(
+        &self,
+        _height: BlockHeight,
+        tx: &[u8],
+        response: &response::DeliverTx,
+    ) -> anyhow::Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+        let tx_index = pending.txs.len();
+        let row = to_tx_row(tx_index, tx, response);
+        let logs = to_log_rows(tx_index, pending.event_count, &response.events);
+        pending.txs.push(row);
+        pending.logs.extend(logs);
+        pending.event_count += response.events.len();
+        Ok(())
+    }
+
+    fn committed(&self, height: BlockHeight) -> anyhow::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut conn = self.conn.lock().unwrap();
+        let height = height as i64;
+
+        let tx = conn.transaction()?;
+        // The block might have been indexed before a crash, and now executed again.
+        tx.execute("DELETE FROM txs WHERE height = ?1", params![height])?;
+        tx.execute("DELETE FROM logs WHERE height = ?1", params![height])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO blocks (height, tx_count, log_count) VALUES (?1, ?2, ?3)",
+            params![height, pending.txs.len() as i64, pending.logs.len() as i64],
+        )?;
+        {
+            let mut insert_tx = tx.prepare_cached(
+                "INSERT INTO txs (height, tx_index, hash, eth_hash, sender, recipient, code, gas_wanted, gas_used) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for r in pending.txs.iter() {
+                insert_tx.execute(params![
+                    height,
+                    r.tx_index as i64,
+                    r.hash,
+                    r.eth_hash,
+                    r.sender,
+                    r.recipient,
+                    r.code,
+                    r.gas_wanted,
+                    r.gas_used
+                ])?;
+            }
+            let mut insert_log = tx.prepare_cached(
+                "INSERT INTO logs (height, log_index, tx_index, tx_log_index, address, topic0, topic1, topic2, topic3, data) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for r in pending.logs.iter() {
+                let topic = |i: usize| r.topics.get(i).cloned();
+                insert_log.execute(params![
+                    height,
+                    r.log_index as i64,
+                    r.tx_index as i64,
+                    r.tx_log_index as i64,
+                    r.address,
+                    topic(0),
+                    topic(1),
+                    topic(2),
+                    topic(3),
+                    r.data
+                ])?;
+            }
+        }
+        tx.commit().context("failed to commit to SQLite")
+    }
+}
+
+fn to_tx_row(tx_index: usize, tx: &[u8], response: &response::DeliverTx) -> TxRow {
+    let attr = |kind: &str, key: &str| {
+        response
+            .events
+            .iter()
+            .filter(|e| e.kind == kind)
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == key)
+            .map(|a| a.value.clone())
+    };
+    TxRow {
+        tx_index,
+        hash: hex::encode_upper(tendermint::crypto::default::Sha256::digest(tx)),
+        eth_hash: attr("eth", "hash").map(|h| format!("0x{h}")),
+        sender: attr("message", "from"),
+        recipient: attr("message", "to"),
+        code: response.code.value(),
+        gas_wanted: response.gas_wanted,
+        gas_used: response.gas_used,
+    }
+}
+
+/// Turn the actor events into logs, leaving out the ones the Ethereum API couldn't show either.
+fn to_log_rows(tx_index: usize, log_index_start: usize, events: &[Event]) -> Vec<LogRow> {
+    events
+        .iter()
+        .filter(|e| e.kind == "event")
+        .enumerate()
+        .filter_map(|(idx, event)| {
+            let address = event_address(event)?;
+            let (topics, data) = event_topics_and_data(event)?;
+            Some(LogRow {
+                log_index: log_index_start + idx,
+                tx_index,
+                tx_log_index: idx,
+                address: format!("{address:?}"),
+                topics: topics.into_iter().map(|t| format!("{t:?}")).collect(),
+                data: data.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// The Ethereum address of the emitter, the same way the Ethereum API derives it.
+fn event_address(event: &Event) -> Option<ethers_core::types::H160> {
+    let attr = |key: &str| event.attributes.iter().find(|a| a.key == key);
+
+    let deleg = attr("emitter.deleg")
+        .and_then(|a| a.value.parse::<Address>().ok())
+        .and_then(|a| to_eth_address(&a));
+
+    let id = attr("emitter.id").and_then(|a| a.value.parse::<u64>().ok())?;
+
+    Some(deleg.unwrap_or_else(|| ethers_core::types::H160::from(EthAddress::from_id(id).0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tendermint::abci::{response, Event, EventAttribute};
+
+    use super::SqliteEventIndex;
+    use crate::index::EventIndex;
+
+    fn attr(key: &str, value: &str) -> EventAttribute {
+        EventAttribute {
+            key: key.to_string(),
+            value: value.to_string(),
+            index: true,
+        }
+    }
+
+    #[test]
+    fn index_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = SqliteEventIndex::open(&dir.path().join("events.sqlite")).unwrap();
+
+        let response = response::DeliverTx {
+            events: vec![
+                Event::new("message", vec![attr("from", "f0100"), attr("to", "f0101")]),
+                Event::new(
+                    "event",
+                    vec![
+                        attr("emitter.id", "101"),
+                        attr("t1", &hex::encode([1u8; 32])),
+                    ],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        for _ in 0..2 {
+            // Executing the same block again replaces the rows.
+            index.begin(10).unwrap();
+            index.delivered(10, b"tx-1", &response).unwrap();
+            index.delivered(10, b"tx-2", &response).unwrap();
+            index.committed(10).unwrap();
+        }
+
+        assert_eq!(index.last_height().unwrap(), Some(10));
+
+        let conn = index.conn.lock().unwrap();
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+
+        assert_eq!(count("SELECT COUNT(*) FROM txs WHERE sender = 'f0100'"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM logs"), 2);
+        // The second log comes after both events of the first transaction.
+        assert_eq!(count("SELECT MAX(log_index) FROM logs"), 3);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM logs WHERE address = '0xff00000000000000000000000000000000000065'"),
+            2
+        );
+    }
+}
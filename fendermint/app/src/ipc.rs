@@ -2,15 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 //! IPC related execution
 
-use crate::app::{AppState, AppStoreKey};
+use crate::app::{AppState, AppStoreKey, CommitJournal};
 use crate::{App, BlockHeight};
 use fendermint_storage::{Codec, Encode, KVReadable, KVStore, KVWritable};
 use fendermint_vm_interpreter::fvm::state::ipc::GatewayCaller;
 use fendermint_vm_interpreter::fvm::state::FvmStateParams;
 use fendermint_vm_interpreter::fvm::store::ReadOnlyBlockstore;
 use fendermint_vm_topdown::sync::ParentFinalityStateQuery;
-use fendermint_vm_topdown::IPCParentFinality;
+use fendermint_vm_topdown::{FinalityMode, IPCParentFinality};
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
 use std::sync::Arc;
 
 /// Queries the LATEST COMMITTED parent finality from the storage
@@ -30,7 +31,9 @@ where
         + Codec<AppState>
         + Encode<AppStoreKey>
         + Encode<BlockHeight>
-        + Codec<FvmStateParams>,
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
     DB: KVWritable<S> + KVReadable<S> + 'static + Clone,
     SS: Blockstore + 'static + Clone,
 {
@@ -48,7 +51,9 @@ where
         + Codec<AppState>
         + Encode<AppStoreKey>
         + Encode<BlockHeight>
-        + Codec<FvmStateParams>,
+        + Codec<FvmStateParams>
+        + Codec<CommitJournal>
+        + Codec<RawBytes>,
     DB: KVWritable<S> + KVReadable<S> + 'static + Clone,
     SS: Blockstore + 'static + Clone,
 {
@@ -66,4 +71,16 @@ where
 
         Ok(finality)
     }
+
+    fn get_finality_mode(&self) -> anyhow::Result<FinalityMode> {
+        let fast = match self.app.new_read_only_exec_state()? {
+            Some(mut exec_state) => exec_state.fast_topdown_finality()?,
+            None => false,
+        };
+        Ok(if fast {
+            FinalityMode::Fast
+        } else {
+            FinalityMode::Safe
+        })
+    }
 }
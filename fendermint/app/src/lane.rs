@@ -0,0 +1,420 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The operator lane: transactions signed by the keys of the node operator skip the queue of the
+//! public mempool, so that emergency actions, e.g. governance, get through even under spam.
+//!
+//! When the mempool of CometBFT is full, it rejects transactions without even showing them to the
+//! application, so the operator can hand them to the lane directly through the admin endpoint;
+//! transactions from the operator keys which made it into the mempool are picked out of it too.
+//!
+//! When this node proposes a block, it puts the transactions of the lane in front of the others,
+//! oldest first, within a per-block budget, so the lane can never crowd out everyone else.
+//! The lane only accepts as many transactions as the budget of the next few proposals covers,
+//! which is what guarantees that each of them is included within that many blocks this node
+//! proposes. Only the proposals a transaction actually made it into count towards that, not the
+//! ones it was cut from. Blocks proposed by validators without the lane are ordered the usual way.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use cid::Cid;
+use fendermint_vm_message::chain::ChainMessage;
+use fendermint_vm_topdown::SubnetLabel;
+use fvm_shared::address::Address;
+use fvm_shared::chainid::ChainID;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+
+use crate::dedup::tx_cid;
+
+lazy_static! {
+    static ref LANE_PENDING: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_operator_lane_pending",
+        "Number of transactions submitted to the operator lane waiting to be included",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref LANE_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_operator_lane_dropped",
+        "Number of transactions dropped from the operator lane without being included",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+#[derive(Debug, Clone)]
+pub struct OperatorLaneConfig {
+    /// Addresses of the operator keys, as they appear as the sender of their messages.
+    pub operators: HashSet<Address>,
+    /// Maximum number of lane transactions in a block.
+    pub max_txs: usize,
+    /// Maximum total size of the lane transactions in a block.
+    pub max_bytes: usize,
+    /// Number of proposals within which the lane has to include every transaction it accepts.
+    pub inclusion_blocks: usize,
+    /// Label of the pending and dropped transaction metrics of the lane.
+    pub subnet_id: SubnetLabel,
+}
+
+/// Why a transaction isn't accepted into the lane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaneError {
+    /// The transaction isn't a signed message.
+    Unsigned,
+    /// The sender isn't one of the operator keys.
+    NotOperator(Address),
+    /// The signature doesn't match the sender.
+    InvalidSignature(String),
+    /// The chain ID to check the signatures against isn't known until the genesis is committed.
+    NotReady,
+    /// The transaction doesn't fit into the budget of a single block.
+    TooLarge { size: usize, max_bytes: usize },
+    /// Accepting more would break the guarantee of inclusion.
+    Full,
+}
+
+impl std::fmt::Display for LaneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "the transaction is not a signed message"),
+            Self::NotOperator(from) => write!(f, "{from} is not an operator key"),
+            Self::InvalidSignature(e) => write!(f, "invalid signature: {e}"),
+            Self::NotReady => write!(f, "the chain ID is not known yet"),
+            Self::TooLarge { size, max_bytes } => write!(
+                f,
+                "the transaction is {size} bytes, more than the {max_bytes} bytes budget of a block"
+            ),
+            Self::Full => write!(f, "the operator lane is full"),
+        }
+    }
+}
+
+impl std::error::Error for LaneError {}
+
+#[derive(Debug)]
+struct LaneTx {
+    cid: Cid,
+    tx: Vec<u8>,
+    /// Number of proposals the transaction has made it into.
+    proposed: usize,
+}
+
+/// Transactions of the operator waiting to be proposed, shared with the admin endpoint.
+#[derive(Debug, Clone)]
+pub struct OperatorLane {
+    config: Arc<OperatorLaneConfig>,
+    /// The chain ID of the committed state, which the signatures are checked against.
+    chain_id: Arc<Mutex<Option<ChainID>>>,
+    pending: Arc<Mutex<VecDeque<LaneTx>>>,
+}
+
+impl OperatorLane {
+    pub fn new(config: OperatorLaneConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            chain_id: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    /// Remember the chain ID of the committed state, to check the signatures of submissions with.
+    pub fn set_chain_id(&self, chain_id: ChainID) {
+        *self.chain_id.lock().unwrap() = Some(chain_id);
+    }
+
+    /// The sender of a transaction, if it's one of the operator keys.
+    fn operator_of(&self, tx: &[u8]) -> Result<Address, LaneError> {
+        // Compressed transactions are only unpacked by the interpreter, so they go the usual way.
+        let from = match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+            Ok(ChainMessage::Signed(msg)) => msg.message().from,
+            _ => return Err(LaneError::Unsigned),
+        };
+        if self.config.operators.contains(&from) {
+            Ok(from)
+        } else {
+            Err(LaneError::NotOperator(from))
+        }
+    }
+
+    /// Accept a transaction of the operator to be put into the next proposals.
+    ///
+    /// Returns the CID the transaction can be tracked by.
+    pub fn submit(&self, tx: Vec<u8>) -> Result<Cid, LaneError> {
+        let from = self.operator_of(&tx)?;
+        let config = &self.config;
+
+        // The transactions in the mempool passed `CheckTx`, but the ones submitted to the lane
+        // don't, so at least make sure they are signed by the operator.
+        let chain_id = self.chain_id.lock().unwrap().ok_or(LaneError::NotReady)?;
+        verify_signature(&tx, &chain_id).map_err(LaneError::InvalidSignature)?;
+
+        if tx.len() > config.max_bytes {
+            return Err(LaneError::TooLarge {
+                size: tx.len(),
+                max_bytes: config.max_bytes,
+            });
+        }
+
+        let cid = tx_cid(&tx);
+        let mut pending = self.pending.lock().unwrap();
+
+        if pending.iter().any(|p| p.cid == cid) {
+            return Ok(cid);
+        }
+
+        let max_txs = config.max_txs.saturating_mul(config.inclusion_blocks);
+        let max_bytes = config.max_bytes.saturating_mul(config.inclusion_blocks);
+        let bytes = pending.iter().map(|p| p.tx.len()).sum::<usize>() + tx.len();
+
+        if pending.len() >= max_txs || bytes > max_bytes {
+            return Err(LaneError::Full);
+        }
+
+        tracing::info!(%from, %cid, "transaction submitted to the operator lane");
+
+        pending.push_back(LaneTx {
+            cid,
+            tx,
+            proposed: 0,
+        });
+        self.set_pending(pending.len());
+
+        Ok(cid)
+    }
+
+    /// Number of transactions waiting in the lane.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Put the transactions of the lane and of the operators in the mempool in front of the rest,
+    /// within the budget of a block.
+    ///
+    /// Call [`OperatorLane::proposed`] with what's left of the proposal after the cuts.
+    pub fn prioritize(&self, txs: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        let config = &self.config;
+        let pending = self.pending.lock().unwrap();
+
+        let mut lane = Vec::new();
+        let mut lane_bytes = 0;
+        let mut selected = HashSet::new();
+
+        // The submitted transactions are accepted such that the budget covers them, oldest first.
+        for p in pending.iter() {
+            if lane.len() == config.max_txs || lane_bytes + p.tx.len() > config.max_bytes {
+                break;
+            }
+            lane_bytes += p.tx.len();
+            lane.push(p.tx.clone());
+            selected.insert(p.cid);
+        }
+
+        let mut rest = Vec::with_capacity(txs.len());
+        for tx in txs {
+            if selected.contains(&tx_cid(&tx)) {
+                continue;
+            }
+            let fits = lane.len() < config.max_txs && lane_bytes + tx.len() <= config.max_bytes;
+            if fits && self.operator_of(&tx).is_ok() {
+                lane_bytes += tx.len();
+                lane.push(tx);
+            } else {
+                rest.push(tx);
+            }
+        }
+
+        lane.extend(rest);
+        lane
+    }
+
+    /// Count the proposals the transactions of the lane made it into.
+    ///
+    /// Transactions which were proposed again and again without being included must be
+    /// invalid, e.g. with a wrong nonce; they would block the lane forever.
+    pub fn proposed<'a>(&self, txs: impl IntoIterator<Item = &'a [u8]>) {
+        let proposed = txs.into_iter().map(tx_cid).collect::<HashSet<_>>();
+        let mut pending = self.pending.lock().unwrap();
+
+        pending.retain_mut(|p| {
+            if !proposed.contains(&p.cid) {
+                return true;
+            }
+            p.proposed += 1;
+            let keep = p.proposed < self.config.inclusion_blocks;
+            if !keep {
+                tracing::warn!(cid = %p.cid, "dropping transaction from the operator lane");
+                LANE_DROPPED
+                    .with_label_values(&[&self.config.subnet_id])
+                    .inc();
+            }
+            keep
+        });
+        self.set_pending(pending.len());
+    }
+
+    fn set_pending(&self, pending: usize) {
+        LANE_PENDING
+            .with_label_values(&[&self.config.subnet_id])
+            .set(pending as i64);
+    }
+
+    /// Forget a transaction once it has been included in a block.
+    pub fn delivered(&self, cid: &Cid) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|p| p.cid != *cid);
+        self.set_pending(pending.len());
+    }
+}
+
+/// Check the signature of a transaction, which can come from an Ethereum or a Filecoin wallet.
+fn verify_signature(tx: &[u8], chain_id: &ChainID) -> Result<(), String> {
+    match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+        Ok(ChainMessage::Signed(msg)) => msg
+            .verify(chain_id)
+            .or_else(|_| msg.verify_filecoin())
+            .map_err(|e| e.to_string()),
+        Ok(_) => Err(LaneError::Unsigned.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_message::chain::ChainMessage;
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{
+        address::Address, chainid::ChainID, crypto::signature::Signature, econ::TokenAmount,
+        message::Message,
+    };
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    use super::{LaneError, OperatorLane, OperatorLaneConfig};
+
+    fn message(from: Address, sequence: u64) -> Message {
+        Message {
+            version: 0,
+            from,
+            to: Address::new_id(100),
+            sequence,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: RawBytes::default(),
+            gas_limit: 1_000_000,
+            gas_fee_cap: TokenAmount::from_atto(100),
+            gas_premium: TokenAmount::from_atto(1),
+        }
+    }
+
+    fn encode(signed: SignedMessage) -> Vec<u8> {
+        fvm_ipld_encoding::to_vec(&ChainMessage::Signed(signed)).unwrap()
+    }
+
+    fn setup(max_txs: usize) -> (OperatorLane, SecretKey, Address) {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let sk = SecretKey::random(&mut rng);
+        let operator = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        let lane = OperatorLane::new(OperatorLaneConfig {
+            operators: HashSet::from([operator]),
+            max_txs,
+            max_bytes: 10_000,
+            inclusion_blocks: 2,
+            subnet_id: String::new(),
+        });
+        lane.set_chain_id(ChainID::from(1));
+        (lane, sk, operator)
+    }
+
+    #[test]
+    fn submit_within_capacity() {
+        let (lane, sk, operator) = setup(1);
+        let chain_id = ChainID::from(1);
+        let signed = |seq| {
+            encode(SignedMessage::new_secp256k1(message(operator, seq), &sk, &chain_id).unwrap())
+        };
+
+        let other = Address::new_id(200);
+        let unsigned = encode(SignedMessage::new_unchecked(
+            message(other, 0),
+            Signature::new_secp256k1(vec![0; 65]),
+        ));
+        assert_eq!(lane.submit(unsigned), Err(LaneError::NotOperator(other)));
+
+        let forged = encode(SignedMessage::new_unchecked(
+            message(operator, 0),
+            Signature::new_secp256k1(vec![0; 65]),
+        ));
+        assert!(matches!(
+            lane.submit(forged),
+            Err(LaneError::InvalidSignature(_))
+        ));
+
+        // The budget of two blocks covers two transactions.
+        lane.submit(signed(0)).unwrap();
+        lane.submit(signed(1)).unwrap();
+        assert_eq!(lane.submit(signed(2)), Err(LaneError::Full));
+        assert_eq!(lane.pending(), 2);
+    }
+
+    #[test]
+    fn prioritize_within_budget() {
+        let (lane, sk, operator) = setup(2);
+        let chain_id = ChainID::from(1);
+
+        let submitted =
+            encode(SignedMessage::new_secp256k1(message(operator, 0), &sk, &chain_id).unwrap());
+        lane.submit(submitted.clone()).unwrap();
+
+        let public = |seq| {
+            encode(SignedMessage::new_unchecked(
+                message(Address::new_id(200), seq),
+                Signature::new_secp256k1(vec![0; 65]),
+            ))
+        };
+        let from_mempool = |seq| {
+            encode(SignedMessage::new_unchecked(
+                message(operator, seq),
+                Signature::new_secp256k1(vec![0; 65]),
+            ))
+        };
+        let mempool = vec![
+            public(0),
+            from_mempool(2),
+            public(1),
+            from_mempool(3),
+            submitted.clone(),
+        ];
+
+        let txs = lane.prioritize(mempool);
+
+        // The submitted transaction leads, and the budget leaves room for
+        // one more transaction of the operator from the mempool.
+        assert_eq!(
+            txs,
+            vec![
+                submitted,
+                from_mempool(2),
+                public(0),
+                public(1),
+                from_mempool(3)
+            ]
+        );
+
+        // Proposals it was cut from don't count.
+        for _ in 0..3 {
+            lane.prioritize(Vec::new());
+            lane.proposed(std::iter::empty());
+        }
+        assert_eq!(lane.pending(), 1);
+
+        // After being proposed as many times as the lane promises to include it, it's dropped.
+        lane.proposed([submitted.as_slice()]);
+        assert_eq!(lane.pending(), 1);
+        lane.proposed([submitted.as_slice()]);
+        assert_eq!(lane.pending(), 0);
+    }
+}
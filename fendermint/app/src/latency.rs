@@ -0,0 +1,246 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Timestamps of the cross-subnet messages at each stage of their way through the pipeline,
+//! so that bridge operators can measure and alert on how long the messages take.
+//!
+//! Top-down messages are observed on the parent by the syncer, proposed in a parent finality,
+//! and executed when the finality is committed. Bottom-up messages are enqueued by the gateway,
+//! included in a checkpoint, and then the checkpoint is signed by a quorum of validators, after
+//! which it's up to the relayer to submit it to the parent.
+//!
+//! The times are taken from the local clock, and the traces are kept in the database of the node,
+//! so they survive restarts. The stages a message went through while the node was down are left
+//! out of its trace, and so are the stages before the node started following the subnet.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fendermint_vm_message::chain::ChainMessage;
+use fendermint_vm_message::ipc::IpcMessage;
+use lazy_static::lazy_static;
+use prometheus::{exponential_buckets, register_histogram_vec, HistogramVec};
+use serde::{Deserialize, Serialize};
+
+use crate::BlockHeight;
+
+lazy_static! {
+    static ref CROSS_MSG_LATENCY: HistogramVec = register_histogram_vec!(
+        "fendermint_cross_msg_latency_secs",
+        "Time between the first stage of a cross-subnet message seen by this node and a later stage",
+        &["subnet", "direction", "stage"],
+        exponential_buckets(0.5, 2.0, 16).unwrap()
+    )
+    .expect("failed to register metric");
+}
+
+/// Which way a cross-subnet message is going.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// From the parent to this subnet.
+    TopDown,
+    /// From this subnet to the parent.
+    BottomUp,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::TopDown => "top_down",
+            Direction::BottomUp => "bottom_up",
+        }
+    }
+}
+
+/// A point a cross-subnet message goes through.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The syncer found the top-down message in a final parent block.
+    Observed,
+    /// A block with a parent finality which includes the top-down message was proposed.
+    Proposed,
+    /// The top-down message was applied, when the parent finality including it was committed or,
+    /// if it was deferred, in a later block; dead-lettered messages don't reach this stage.
+    Executed,
+    /// The bottom-up message appeared among the messages of the gateway.
+    Enqueued,
+    /// The checkpoint including the bottom-up message was created.
+    Checkpointed,
+    /// The checkpoint including the bottom-up message was signed by a quorum of validators.
+    Quorum,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Observed => "observed",
+            Stage::Proposed => "proposed",
+            Stage::Executed => "executed",
+            Stage::Enqueued => "enqueued",
+            Stage::Checkpointed => "checkpointed",
+            Stage::Quorum => "quorum",
+        }
+    }
+}
+
+/// The time a message reached a stage.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StageTime {
+    pub stage: Stage,
+    /// Milliseconds since the Unix epoch, by the local clock.
+    pub timestamp: u64,
+}
+
+/// The stages a cross-subnet message reached so far.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MessageTrace {
+    pub direction: Direction,
+    pub nonce: u64,
+    /// The parent height a top-down message was sent at, or the height of the checkpoint
+    /// a bottom-up message is included in.
+    pub height: BlockHeight,
+    /// The stages in the order they were reached.
+    pub stages: Vec<StageTime>,
+}
+
+impl MessageTrace {
+    pub fn new(direction: Direction, nonce: u64, height: BlockHeight) -> Self {
+        Self {
+            direction,
+            nonce,
+            height,
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn has_reached(&self, stage: Stage) -> bool {
+        self.stages.iter().any(|s| s.stage == stage)
+    }
+
+    /// Record the time a stage was reached, unless it already has been, and the latency
+    /// since the first stage in the metrics of the subnet; returns whether the stage is new.
+    pub fn reach(&mut self, subnet_id: &str, stage: Stage, timestamp: u64) -> bool {
+        if self.has_reached(stage) {
+            return false;
+        }
+        self.stages.push(StageTime { stage, timestamp });
+
+        if self.stages.len() > 1 {
+            if let Some(latency) = self.latency(stage) {
+                CROSS_MSG_LATENCY
+                    .with_label_values(&[subnet_id, self.direction.as_str(), stage.as_str()])
+                    .observe(latency.as_secs_f64());
+            }
+        }
+        true
+    }
+
+    /// Time between the first stage in the trace and a later one.
+    pub fn latency(&self, stage: Stage) -> Option<Duration> {
+        let first = self.stages.first()?;
+        let reached = self.stages.iter().find(|s| s.stage == stage)?;
+        Some(Duration::from_millis(
+            reached.timestamp.saturating_sub(first.timestamp),
+        ))
+    }
+}
+
+/// Parameters of the message trace query.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsgTraceQuery {
+    pub direction: Direction,
+    pub nonce: u64,
+}
+
+/// When the parent finalities proposed since the last one committed were first seen,
+/// by parent height, to tell when the top-down messages they include were proposed.
+#[derive(Debug, Default)]
+pub struct TopDownProposals(BTreeMap<BlockHeight, u64>);
+
+impl TopDownProposals {
+    /// Remember the first time a finality was proposed; proposals are seen again in later rounds.
+    pub fn record(&mut self, height: BlockHeight, timestamp: u64) {
+        self.0.entry(height).or_insert(timestamp);
+    }
+
+    /// When a message sent at a parent height was first proposed, which is in
+    /// the earliest finality proposed above that height.
+    pub fn proposed_at(&self, parent_height: BlockHeight) -> Option<u64> {
+        self.0.range(parent_height + 1..).map(|(_, t)| *t).min()
+    }
+
+    /// Forget the proposals up to the committed finality.
+    pub fn finalized(&mut self, height: BlockHeight) {
+        self.0 = self.0.split_off(&(height + 1));
+    }
+}
+
+/// The parent height of the finality in a proposal, if there is one.
+///
+/// The finality is added after the transactions of the users, so it's looked for from the back.
+pub fn proposed_finality(txs: &[impl AsRef<[u8]>]) -> Option<BlockHeight> {
+    txs.iter().rev().find_map(|tx| {
+        match fvm_ipld_encoding::from_slice::<ChainMessage>(tx.as_ref()) {
+            Ok(ChainMessage::Ipc(IpcMessage::TopDownExec(p))) => Some(p.height as BlockHeight),
+            _ => None,
+        }
+    })
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Direction, MessageTrace, MsgTraceQuery, Stage, TopDownProposals};
+
+    #[test]
+    fn stages_are_reached_once() {
+        let mut trace = MessageTrace::new(Direction::BottomUp, 5, 100);
+        assert!(trace.latency(Stage::Enqueued).is_none());
+
+        assert!(trace.reach("", Stage::Enqueued, 1_000));
+        assert!(trace.reach("", Stage::Checkpointed, 4_500));
+        assert!(!trace.reach("", Stage::Checkpointed, 9_000));
+
+        assert_eq!(trace.latency(Stage::Enqueued), Some(Duration::ZERO));
+        assert_eq!(
+            trace.latency(Stage::Checkpointed),
+            Some(Duration::from_millis(3_500))
+        );
+        assert!(trace.latency(Stage::Quorum).is_none());
+    }
+
+    #[test]
+    fn proposals_by_parent_height() {
+        let mut proposals = TopDownProposals::default();
+        proposals.record(10, 1_000);
+        proposals.record(12, 2_000);
+        // Proposed again in a later round.
+        proposals.record(10, 3_000);
+
+        // The finality of height 10 includes the messages up to height 9.
+        assert_eq!(proposals.proposed_at(9), Some(1_000));
+        assert_eq!(proposals.proposed_at(10), Some(2_000));
+        assert_eq!(proposals.proposed_at(12), None);
+
+        proposals.finalized(10);
+        assert_eq!(proposals.proposed_at(9), Some(2_000));
+    }
+
+    #[test]
+    fn parse_query() {
+        let query: MsgTraceQuery =
+            serde_json::from_str(r#"{"direction": "top_down", "nonce": 3}"#).unwrap();
+        assert_eq!(query.direction, Direction::TopDown);
+        assert_eq!(query.nonce, 3);
+    }
+}
@@ -1,13 +1,37 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+pub mod admin;
 mod app;
+mod blockgas;
+mod bottomup;
+pub mod clock;
+mod dedup;
+pub mod devchain;
+pub mod export;
+pub mod gasstats;
+pub mod genesisaudit;
+pub mod genesiscar;
+pub mod genesischeck;
+pub mod index;
 mod ipc;
+pub mod lane;
+pub mod latency;
+pub mod mempool;
+pub mod metrics;
+mod ordering;
+pub mod proposals;
+pub mod readonly;
+pub mod schema;
 mod store;
 mod tmconv;
+pub mod vectors;
+pub mod votes;
+pub mod watchdog;
 
 pub use app::{App, AppConfig};
 pub use ipc::AppParentFinalityQuery;
 pub use store::{AppStore, BitswapBlockstore};
+pub use tmconv::to_error_msg;
 
 // Different type from `ChainEpoch` just because we might use epoch in a more traditional sense for checkpointing.
 pub type BlockHeight = u64;
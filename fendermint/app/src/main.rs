@@ -10,10 +10,12 @@ use trace4rs::{
     config::{self, Config, Format},
     Handle,
 };
+use tracing_subscriber::layer::SubscriberExt;
 
 pub use fendermint_app_options as options;
+use fendermint_app_options::Commands;
 pub use fendermint_app_settings as settings;
-use fendermint_app_settings::expand_tilde;
+use fendermint_app_settings::{expand_tilde, OtlpSettings};
 
 mod cmd;
 
@@ -21,17 +23,35 @@ mod cmd;
 async fn main() {
     let opts = options::parse();
 
+    // Only the application and the Ethereum API export traces; the other commands don't need settings.
+    let otlp = match opts.command {
+        Commands::Run(_) | Commands::Eth(_) => cmd::settings(&opts)
+            .ok()
+            .map(|s| s.tracing.otlp)
+            .filter(|otlp| otlp.enabled),
+        _ => None,
+    };
+
     if let Some(level) = opts.tracing_level() {
-        create_log(level, opts.log_dir.as_ref()).expect("cannot create logging");
+        create_log(level, opts.log_dir.as_ref(), otlp.as_ref()).expect("cannot create logging");
     }
 
-    if let Err(e) = cmd::exec(&opts).await {
+    let res = cmd::exec(&opts).await;
+
+    // Export whatever traces are still buffered.
+    opentelemetry::global::shutdown_tracer_provider();
+
+    if let Err(e) = res {
         tracing::error!("failed to execute {:?}: {e:?}", opts);
         std::process::exit(1);
     }
 }
 
-fn create_log(level: tracing::Level, log_dir: Option<&PathBuf>) -> anyhow::Result<()> {
+fn create_log(
+    level: tracing::Level,
+    log_dir: Option<&PathBuf>,
+    otlp: Option<&OtlpSettings>,
+) -> anyhow::Result<()> {
     let console_appender = config::Appender::Console;
 
     // default logging output info log to console
@@ -108,12 +128,52 @@ fn create_log(level: tracing::Level, log_dir: Option<&PathBuf>) -> anyhow::Resul
 
     let handle = Arc::new(Handle::try_from(config)?);
 
-    tracing::subscriber::set_global_default(handle.subscriber())
-        .context("setting default subscriber failed")?;
+    match otlp {
+        None => tracing::subscriber::set_global_default(handle.subscriber()),
+        Some(otlp) => {
+            let tracer = create_tracer(otlp)?;
+            let layer = tracing_opentelemetry::layer().with_tracer(tracer);
+            tracing::subscriber::set_global_default(handle.subscriber().with(layer))
+        }
+    }
+    .context("setting default subscriber failed")?;
 
     Ok(())
 }
 
+/// Create a tracer exporting spans to an OpenTelemetry collector over gRPC.
+fn create_tracer(otlp: &OtlpSettings) -> anyhow::Result<opentelemetry_sdk::trace::Tracer> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{
+        trace::{self, Sampler},
+        Resource,
+    };
+
+    // Follow the decision of the parent span, so traces are either sampled in full or not at all.
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(otlp.sample_rate)));
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(otlp.endpoint.clone()),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(sampler)
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    "fendermint",
+                )])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .context("failed to create OTLP tracer")?;
+
+    Ok(tracer)
+}
+
 #[cfg(test)]
 mod tests {
     use cid::Cid;
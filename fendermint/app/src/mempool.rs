@@ -0,0 +1,394 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Operator tools for the local CometBFT mempool: list the transactions waiting in it, checked
+//! against the committed state, and evict the ones which are stuck or malicious.
+//!
+//! CometBFT doesn't let the application remove transactions from its mempool. Instead, evicted
+//! transactions fail `CheckTx` when they are rechecked after the next block, at which point
+//! CometBFT drops them, and they are refused if they are sent again while the eviction lasts.
+//! This relies on `recheck = true` in the `[mempool]` section of the CometBFT config, which is
+//! the default. Evictions only apply to this node; other validators can still include the
+//! transactions in their blocks.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use fendermint_eth_api::{MempoolClient, MempoolHttpClient, MAX_UNCONFIRMED_TXS};
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fendermint_vm_message::chain::{ChainMessage, MAX_DECOMPRESSED_SIZE};
+use fendermint_vm_message::signed::SignedMessage;
+use fvm_shared::address::Address;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tendermint_rpc::Url;
+
+use crate::BlockHeight;
+
+/// Number of blocks an eviction lasts unless the operator asks for something else.
+pub const DEFAULT_EVICTION_BLOCKS: u64 = 10;
+
+/// Checks transactions the same way as `CheckTx`, without affecting the state
+/// the mempool is checked against.
+#[async_trait]
+pub trait MempoolCheck: Send + Sync {
+    /// Check transactions against the committed state, each after the ones before it,
+    /// returning the code and the reason of failure of each.
+    async fn check_txs(&self, txs: &[Vec<u8>]) -> anyhow::Result<Vec<(u32, String)>>;
+}
+
+/// The hash CometBFT knows a transaction by, in upper case hex.
+pub fn tx_hash(tx: &[u8]) -> String {
+    hex::encode_upper(Sha256::digest(tx))
+}
+
+/// Normalize a transaction hash given by the operator.
+fn parse_hash(hash: &str) -> anyhow::Result<String> {
+    let hash = hash.trim_start_matches("0x").to_uppercase();
+    match hex::decode(&hash) {
+        Ok(bz) if bz.len() == 32 => Ok(hash),
+        _ => Err(anyhow!("invalid transaction hash: {hash}")),
+    }
+}
+
+/// Parse a sender given by the operator, either as a Filecoin or an Ethereum address.
+pub fn parse_sender(sender: &str) -> anyhow::Result<Address> {
+    if let Some(hex_addr) = sender.strip_prefix("0x") {
+        let bz = hex::decode(hex_addr).context("invalid Ethereum address")?;
+        let bz: [u8; 20] = bz
+            .try_into()
+            .map_err(|_| anyhow!("Ethereum addresses are 20 bytes"))?;
+        Ok(Address::from(EthAddress(bz)))
+    } else {
+        sender
+            .parse()
+            .with_context(|| format!("invalid address: {sender}"))
+    }
+}
+
+/// The message in a signed transaction, which may be compressed.
+fn decode_signed(tx: &[u8]) -> Option<SignedMessage> {
+    match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+        Ok(ChainMessage::Signed(msg)) => Some(msg),
+        Ok(ChainMessage::Compressed(bz)) => {
+            ChainMessage::decompress(bz.bytes(), MAX_DECOMPRESSED_SIZE).ok()
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Evictions {
+    /// The last committed height.
+    height: BlockHeight,
+    /// The last height the transactions are evicted at, by hash.
+    hashes: HashMap<String, BlockHeight>,
+    /// The last height the transactions of a sender are evicted at.
+    senders: HashMap<Address, BlockHeight>,
+}
+
+/// A transaction or sender evicted from the mempool, as shown to the operator.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    /// Transaction hash or sender address.
+    pub target: String,
+    /// The last block height at which the eviction applies.
+    pub until: BlockHeight,
+}
+
+/// The evictions currently in effect.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct EvictionList {
+    pub hashes: Vec<Eviction>,
+    pub senders: Vec<Eviction>,
+}
+
+/// Transactions evicted by the operator, shared between the [`App`](crate::App),
+/// which rejects them in `CheckTx`, and the admin endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct MempoolEvictions(Arc<Mutex<Evictions>>);
+
+impl MempoolEvictions {
+    /// Evict transactions by hash and by sender for a number of blocks.
+    pub fn evict(&self, hashes: Vec<String>, senders: Vec<Address>, blocks: u64) {
+        let mut evictions = self.0.lock().unwrap();
+        let until = evictions.height.saturating_add(blocks.max(1));
+        for hash in hashes {
+            tracing::warn!(hash, until, "transaction evicted by the operator");
+            evictions.hashes.insert(hash, until);
+        }
+        for sender in senders {
+            tracing::warn!(%sender, until, "sender evicted by the operator");
+            evictions.senders.insert(sender, until);
+        }
+    }
+
+    /// Check whether a transaction is evicted, either by hash or by its sender.
+    pub fn is_evicted(&self, tx: &[u8]) -> bool {
+        let evictions = self.0.lock().unwrap();
+        if evictions.hashes.is_empty() && evictions.senders.is_empty() {
+            return false;
+        }
+        if evictions.hashes.contains_key(&tx_hash(tx)) {
+            return true;
+        }
+        !evictions.senders.is_empty()
+            && decode_signed(tx).map_or(false, |msg| {
+                evictions.senders.contains_key(&msg.message().from)
+            })
+    }
+
+    /// Forget the evictions which ended with a committed block.
+    pub fn committed(&self, height: BlockHeight) {
+        let mut evictions = self.0.lock().unwrap();
+        evictions.height = height;
+        evictions.hashes.retain(|_, until| *until > height);
+        evictions.senders.retain(|_, until| *until > height);
+    }
+
+    /// The evictions in effect, in no particular order.
+    pub fn list(&self) -> EvictionList {
+        let evictions = self.0.lock().unwrap();
+        EvictionList {
+            hashes: evictions
+                .hashes
+                .iter()
+                .map(|(hash, until)| Eviction {
+                    target: hash.clone(),
+                    until: *until,
+                })
+                .collect(),
+            senders: evictions
+                .senders
+                .iter()
+                .map(|(sender, until)| Eviction {
+                    target: sender.to_string(),
+                    until: *until,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What the operator asks to evict.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EvictionRequest {
+    /// Transaction hashes, as shown by CometBFT.
+    #[serde(default)]
+    pub hashes: Vec<String>,
+    /// Senders whose transactions are all evicted, as Filecoin or Ethereum addresses.
+    #[serde(default)]
+    pub senders: Vec<String>,
+    /// Number of blocks the eviction lasts.
+    pub blocks: Option<u64>,
+}
+
+/// A transaction in the mempool, as shown to the operator.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MempoolTx {
+    pub hash: String,
+    pub size: usize,
+    /// `signed` for the transactions of users, compressed or not, `ipc` for the ones added
+    /// by validators, or `unknown` if the transaction can't be decoded.
+    pub kind: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub sequence: Option<u64>,
+    pub method_num: Option<u64>,
+    pub value: Option<String>,
+    pub gas_limit: Option<u64>,
+    pub gas_fee_cap: Option<String>,
+    pub gas_premium: Option<String>,
+    /// Whether the transaction passes `CheckTx` against the committed state,
+    /// after the ones in front of it in the mempool.
+    pub valid: bool,
+    /// The code of the check, zero if it's valid.
+    pub code: u32,
+    /// Why the check failed.
+    pub info: String,
+    /// Whether the transaction is going to be dropped at the next recheck.
+    pub evicted: bool,
+}
+
+impl MempoolTx {
+    fn new(tx: &[u8], (code, info): (u32, String), evicted: bool) -> Self {
+        let mut mtx = Self {
+            hash: tx_hash(tx),
+            size: tx.len(),
+            valid: code == 0,
+            code,
+            info,
+            evicted,
+            ..Default::default()
+        };
+        match decode_signed(tx) {
+            Some(signed) => {
+                let msg = signed.message();
+                mtx.kind = "signed".to_owned();
+                mtx.from = Some(msg.from.to_string());
+                mtx.to = Some(msg.to.to_string());
+                mtx.sequence = Some(msg.sequence);
+                mtx.method_num = Some(msg.method_num);
+                mtx.value = Some(msg.value.to_string());
+                mtx.gas_limit = Some(msg.gas_limit);
+                mtx.gas_fee_cap = Some(msg.gas_fee_cap.to_string());
+                mtx.gas_premium = Some(msg.gas_premium.to_string());
+            }
+            None => match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+                Ok(ChainMessage::Ipc(_)) => mtx.kind = "ipc".to_owned(),
+                _ => mtx.kind = "unknown".to_owned(),
+            },
+        }
+        mtx
+    }
+}
+
+/// The transactions in the mempool, in the order CometBFT would propose them.
+#[derive(Serialize, Debug, Clone)]
+pub struct MempoolContent {
+    /// Number of transactions in the mempool.
+    pub total: u64,
+    /// Whether only the first of them are listed, because CometBFT doesn't return more
+    /// than [MAX_UNCONFIRMED_TXS] at a time.
+    pub truncated: bool,
+    pub txs: Vec<MempoolTx>,
+}
+
+/// The state behind the mempool admin endpoints.
+#[derive(Clone)]
+pub struct MempoolAdmin {
+    check: Arc<dyn MempoolCheck>,
+    evictions: MempoolEvictions,
+    client: MempoolHttpClient,
+}
+
+impl MempoolAdmin {
+    pub fn new(
+        check: Arc<dyn MempoolCheck>,
+        evictions: MempoolEvictions,
+        tendermint_url: Url,
+    ) -> Self {
+        Self {
+            check,
+            evictions,
+            client: MempoolHttpClient::new(tendermint_url),
+        }
+    }
+
+    pub fn evictions(&self) -> &MempoolEvictions {
+        &self.evictions
+    }
+
+    /// List the transactions in the mempool with the outcome of checking them.
+    pub async fn content(&self) -> anyhow::Result<MempoolContent> {
+        let unconfirmed = self.client.unconfirmed_txs(MAX_UNCONFIRMED_TXS).await?;
+        let checks = self.check.check_txs(&unconfirmed.txs).await?;
+        let txs = unconfirmed
+            .txs
+            .iter()
+            .zip(checks)
+            .map(|(tx, check)| MempoolTx::new(tx, check, self.evictions.is_evicted(tx)))
+            .collect();
+
+        let truncated = unconfirmed.total > unconfirmed.txs.len() as u64;
+        if truncated {
+            tracing::warn!(
+                total = unconfirmed.total,
+                listed = unconfirmed.txs.len(),
+                "only the first transactions of the mempool are listed"
+            );
+        }
+
+        Ok(MempoolContent {
+            total: unconfirmed.total,
+            truncated,
+            txs,
+        })
+    }
+
+    /// Evict the requested transactions; nothing is evicted if any of the targets are invalid.
+    pub fn evict(&self, request: EvictionRequest) -> anyhow::Result<EvictionList> {
+        let hashes = request
+            .hashes
+            .iter()
+            .map(|h| parse_hash(h))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let senders = request
+            .senders
+            .iter()
+            .map(|s| parse_sender(s))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.evictions.evict(
+            hashes,
+            senders,
+            request.blocks.unwrap_or(DEFAULT_EVICTION_BLOCKS),
+        );
+
+        Ok(self.evictions.list())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_message::chain::ChainMessage;
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{
+        address::Address, crypto::signature::Signature, econ::TokenAmount, message::Message,
+    };
+
+    use super::{parse_hash, parse_sender, tx_hash, MempoolEvictions};
+
+    fn tx(from: Address, sequence: u64) -> Vec<u8> {
+        let msg = Message {
+            version: 0,
+            from,
+            to: Address::new_id(100),
+            sequence,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: RawBytes::default(),
+            gas_limit: 1_000_000,
+            gas_fee_cap: TokenAmount::from_atto(100),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        let signed = SignedMessage::new_unchecked(msg, Signature::new_secp256k1(vec![0; 65]));
+        fvm_ipld_encoding::to_vec(&ChainMessage::Signed(signed)).unwrap()
+    }
+
+    #[test]
+    fn evict_by_hash_and_sender() {
+        let evictions = MempoolEvictions::default();
+        let (alice, bob) = (Address::new_id(200), Address::new_id(300));
+        evictions.committed(10);
+
+        assert!(!evictions.is_evicted(&tx(alice, 0)));
+
+        evictions.evict(vec![tx_hash(&tx(alice, 0))], vec![bob], 2);
+        assert!(evictions.is_evicted(&tx(alice, 0)));
+        assert!(!evictions.is_evicted(&tx(alice, 1)));
+        assert!(evictions.is_evicted(&tx(bob, 5)));
+
+        // The eviction applies to the next two blocks.
+        evictions.committed(11);
+        assert!(evictions.is_evicted(&tx(bob, 5)));
+        evictions.committed(12);
+        assert!(!evictions.is_evicted(&tx(bob, 5)));
+        assert!(!evictions.is_evicted(&tx(alice, 0)));
+        assert!(evictions.list().hashes.is_empty());
+    }
+
+    #[test]
+    fn parse_targets() {
+        let hash = tx_hash(b"foo");
+        assert_eq!(parse_hash(&hash.to_lowercase()).unwrap(), hash);
+        assert!(parse_hash("0xABCD").is_err());
+
+        assert_eq!(parse_sender("f0100").unwrap(), Address::new_id(100));
+        assert!(parse_sender("0x1234").is_err());
+        assert!(parse_sender(&format!("0x{}", "ab".repeat(20))).is_ok());
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Serve the metrics registered by the different components for Prometheus to scrape.
+
+use std::net::ToSocketAddrs;
+
+use anyhow::{anyhow, Context};
+use axum::{http::StatusCode, routing::get, Router};
+use prometheus::{Encoder, TextEncoder};
+
+/// Serve the `/metrics` endpoint until the process exits.
+pub async fn serve<A: ToSocketAddrs>(listen: A) -> anyhow::Result<()> {
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))?;
+
+    let app = Router::new().route("/metrics", get(metrics));
+
+    tracing::info!(?addr, "serving metrics");
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .context("failed to serve metrics")
+}
+
+async fn metrics() -> (StatusCode, String) {
+    let mut buf = Vec::new();
+    match TextEncoder::new().encode(&prometheus::gather(), &mut buf) {
+        Ok(()) => (StatusCode::OK, String::from_utf8_lossy(&buf).into_owned()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The canonical order of the user transactions in a block.
+//!
+//! CometBFT leaves the order of the transactions up to the proposer, which normally means the
+//! order they arrived in its mempool. When the genesis enables the canonical order, the signed
+//! messages in a block have to be sorted by the bytes of their sender address, then by their nonce:
+//!
+//! * every sender's messages are contiguous, and in the order they have to be executed in;
+//! * anyone looking at the same set of transactions puts them in the same order, so indexers and
+//!   fee markets can't be played by whoever proposes the block;
+//! * a sender and nonce appearing more than once is allowed, as the duplicates fail execution anyway.
+//!
+//! The same account can sign with its public key address, or its ID or delegated address in the
+//! message, so the senders are resolved to their ID addresses in the last committed state, and
+//! only the ones which don't exist yet are ordered by the address they came with.
+//!
+//! Compressed messages are ordered by the signed message they contain, so a sender can mix
+//! them with uncompressed ones. Other transactions, e.g. the IPC messages added by the proposer
+//! or compressed ones which can't be decompressed, keep their positions and aren't ordered.
+//!
+//! The proposer decides which transactions to include first, e.g. putting the operator lane in
+//! front so they survive the cut at the maximum block size, and only then sorts them, so the
+//! order doesn't affect which ones get in. Validators reject proposals which aren't sorted.
+
+use fendermint_vm_message::chain::{ChainMessage, MAX_DECOMPRESSED_SIZE};
+use fendermint_vm_message::signed::SignedMessage;
+use fvm_shared::address::Address;
+
+/// The key the signed messages are sorted by: the bytes of the sender address and the nonce.
+type OrderKey = (Vec<u8>, u64);
+
+fn order_key<R>(tx: &[u8], resolve: &mut R) -> anyhow::Result<Option<OrderKey>>
+where
+    R: FnMut(&Address) -> anyhow::Result<Address>,
+{
+    match decode_signed(tx) {
+        Some(msg) => {
+            let from = resolve(&msg.message.from)?;
+            Ok(Some((from.to_bytes(), msg.message.sequence)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The signed message in a transaction, which may be compressed.
+fn decode_signed(tx: &[u8]) -> Option<SignedMessage> {
+    match fvm_ipld_encoding::from_slice::<ChainMessage>(tx) {
+        Ok(ChainMessage::Signed(msg)) => Some(msg),
+        Ok(ChainMessage::Compressed(bz)) => {
+            ChainMessage::decompress(bz.bytes(), MAX_DECOMPRESSED_SIZE).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Sort the signed messages into the canonical order, leaving the rest where they are.
+///
+/// Compressed messages are sorted along with the rest, but stay compressed.
+///
+/// The senders are mapped with `resolve` before comparing them, e.g. to their ID addresses.
+pub fn sort_canonical<R>(txs: Vec<Vec<u8>>, mut resolve: R) -> anyhow::Result<Vec<Vec<u8>>>
+where
+    R: FnMut(&Address) -> anyhow::Result<Address>,
+{
+    let keys = txs
+        .iter()
+        .map(|tx| order_key(tx, &mut resolve))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut signed = txs
+        .iter()
+        .zip(keys.iter())
+        .filter_map(|(tx, key)| key.as_ref().map(|key| (key.clone(), tx.clone())))
+        .collect::<Vec<_>>();
+
+    // Stable, so exact duplicates stay in the order they came in.
+    signed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut signed = signed.into_iter().map(|(_, tx)| tx);
+
+    let txs = txs
+        .into_iter()
+        .zip(keys)
+        .map(|(tx, key)| match key {
+            Some(_) => signed.next().expect("as many signed messages as keys"),
+            None => tx,
+        })
+        .collect();
+
+    Ok(txs)
+}
+
+/// Find the first signed message which is out of the canonical order, if any.
+pub fn find_non_canonical<R>(txs: &[Vec<u8>], mut resolve: R) -> anyhow::Result<Option<usize>>
+where
+    R: FnMut(&Address) -> anyhow::Result<Address>,
+{
+    let mut last: Option<OrderKey> = None;
+    for (index, tx) in txs.iter().enumerate() {
+        if let Some(key) = order_key(tx, &mut resolve)? {
+            if matches!(last, Some(ref last) if *last > key) {
+                return Ok(Some(index));
+            }
+            last = Some(key);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_message::chain::ChainMessage;
+    use fendermint_vm_message::ipc::{IpcMessage, ParentFinality};
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{
+        address::Address, crypto::signature::Signature, econ::TokenAmount, message::Message,
+    };
+
+    use super::{find_non_canonical, sort_canonical};
+
+    fn as_is(addr: &Address) -> anyhow::Result<Address> {
+        Ok(*addr)
+    }
+
+    fn signed(from: u64, sequence: u64) -> Vec<u8> {
+        signed_by(Address::new_id(from), sequence)
+    }
+
+    fn signed_by(from: Address, sequence: u64) -> Vec<u8> {
+        fvm_ipld_encoding::to_vec(&ChainMessage::Signed(signed_msg(from, sequence))).unwrap()
+    }
+
+    fn compressed(from: u64, sequence: u64) -> Vec<u8> {
+        let msg = ChainMessage::compress(&signed_msg(Address::new_id(from), sequence)).unwrap();
+        fvm_ipld_encoding::to_vec(&msg).unwrap()
+    }
+
+    fn signed_msg(from: Address, sequence: u64) -> SignedMessage {
+        let message = Message {
+            version: 0,
+            from,
+            to: Address::new_id(100),
+            sequence,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: RawBytes::default(),
+            gas_limit: 1_000_000,
+            gas_fee_cap: TokenAmount::from_atto(100),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        SignedMessage::new_unchecked(message, Signature::new_secp256k1(vec![]))
+    }
+
+    fn ipc() -> Vec<u8> {
+        let msg = ChainMessage::Ipc(IpcMessage::TopDownExec(ParentFinality {
+            height: 1,
+            block_hash: vec![0; 32],
+        }));
+        fvm_ipld_encoding::to_vec(&msg).unwrap()
+    }
+
+    #[test]
+    fn sorts_signed_messages_in_place() {
+        let txs = vec![
+            signed(2, 0),
+            ipc(),
+            signed(1, 1),
+            signed(1, 0),
+            signed(2, 1),
+        ];
+
+        assert_eq!(find_non_canonical(&txs, as_is).unwrap(), Some(2));
+
+        let sorted = sort_canonical(txs, as_is).unwrap();
+
+        assert_eq!(
+            sorted,
+            vec![
+                signed(1, 0),
+                ipc(),
+                signed(1, 1),
+                signed(2, 0),
+                signed(2, 1)
+            ]
+        );
+        assert_eq!(find_non_canonical(&sorted, as_is).unwrap(), None);
+    }
+
+    #[test]
+    fn sorts_compressed_messages_with_the_rest() {
+        let txs = vec![
+            compressed(1, 1),
+            signed(2, 0),
+            ipc(),
+            signed(1, 0),
+            compressed(1, 2),
+        ];
+
+        assert_eq!(find_non_canonical(&txs, as_is).unwrap(), Some(3));
+
+        let sorted = sort_canonical(txs, as_is).unwrap();
+
+        assert_eq!(
+            sorted,
+            vec![
+                signed(1, 0),
+                compressed(1, 1),
+                ipc(),
+                compressed(1, 2),
+                signed(2, 0)
+            ]
+        );
+        assert_eq!(find_non_canonical(&sorted, as_is).unwrap(), None);
+    }
+
+    #[test]
+    fn allows_duplicates() {
+        let txs = vec![signed(1, 0), signed(1, 0), signed(1, 1)];
+        assert_eq!(find_non_canonical(&txs, as_is).unwrap(), None);
+    }
+
+    #[test]
+    fn orders_by_resolved_sender() {
+        let key = Address::new_secp256k1(&[1; 65]).unwrap();
+        let delegated = Address::new_delegated(10, &[2; 20]).unwrap();
+
+        // The public key address of account 101, and the delegated address of account 100.
+        let resolve = |addr: &Address| -> anyhow::Result<Address> {
+            Ok(if *addr == key {
+                Address::new_id(101)
+            } else if *addr == delegated {
+                Address::new_id(100)
+            } else {
+                *addr
+            })
+        };
+
+        // Account 101 signing with its key and its ID is still one sender, in nonce order.
+        let txs = vec![signed_by(delegated, 0), signed_by(key, 0), signed(101, 1)];
+        assert_eq!(find_non_canonical(&txs, resolve).unwrap(), None);
+
+        // Unresolved, the key address would sort before the delegated one.
+        assert_eq!(find_non_canonical(&txs, as_is).unwrap(), Some(1));
+
+        let txs = vec![signed(101, 1), signed_by(key, 0), signed(100, 1)];
+        assert_eq!(find_non_canonical(&txs, resolve).unwrap(), Some(1));
+        assert_eq!(
+            sort_canonical(txs, resolve).unwrap(),
+            vec![signed(100, 1), signed_by(key, 0), signed(101, 1)]
+        );
+    }
+}
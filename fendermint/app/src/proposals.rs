@@ -0,0 +1,224 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Telemetry about the blocks proposed to this node, and why it rejected some of them.
+//!
+//! To CometBFT a rejected proposal just looks like a round which didn't make it, so the cause is
+//! only visible on the validators which voted against it. They log it, count it in the metrics,
+//! and keep the recent rejections for the operator to query through the admin endpoint.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+use fendermint_vm_interpreter::ProposalRejection;
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, HistogramVec,
+    IntCounterVec,
+};
+use serde::Serialize;
+
+use crate::BlockHeight;
+
+lazy_static! {
+    static ref PROPOSAL_TXS: HistogramVec = register_histogram_vec!(
+        "fendermint_proposal_txs",
+        "Number of transactions in the processed proposals",
+        &["subnet"],
+        exponential_buckets(1.0, 2.0, 14).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref PROPOSAL_BYTES: HistogramVec = register_histogram_vec!(
+        "fendermint_proposal_bytes",
+        "Total size of the transactions in the processed proposals",
+        &["subnet"],
+        exponential_buckets(256.0, 2.0, 18).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref PROPOSAL_TX_BYTES: HistogramVec = register_histogram_vec!(
+        "fendermint_proposal_tx_bytes",
+        "Size of the individual transactions in the processed proposals",
+        &["subnet"],
+        exponential_buckets(64.0, 2.0, 16).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref PROPOSALS_REJECTED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_proposals_rejected",
+        "Number of proposals this node voted against, by reason",
+        &["subnet", "reason"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Number of recent rejections to remember.
+const MAX_REJECTIONS: usize = 100;
+
+/// Why the application rejected a proposal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The application halted before the height of the proposal.
+    Halted { halt_height: BlockHeight },
+    /// The time of the proposal is before or too far after the previous block.
+    BlockTime,
+    /// One of the transactions cannot be included.
+    Tx(ProposalRejection),
+}
+
+impl RejectReason {
+    /// Short name of the reason, e.g. to label metrics with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Halted { .. } => "halted",
+            Self::BlockTime => "block_time",
+            Self::Tx(r) => r.kind(),
+        }
+    }
+
+    /// Index of the offending transaction, if the reason is a transaction.
+    pub fn tx_index(&self) -> Option<usize> {
+        match self {
+            Self::Tx(r) => Some(r.index()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Halted { halt_height } => write!(f, "the application halted at {halt_height}"),
+            Self::BlockTime => write!(
+                f,
+                "the block time is before or too far after the previous block"
+            ),
+            Self::Tx(r) => write!(f, "{r}"),
+        }
+    }
+}
+
+/// A proposal this node voted against.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RejectedProposal {
+    pub height: BlockHeight,
+    pub time: String,
+    /// Consensus address of the proposer.
+    pub proposer: String,
+    /// Number of transactions in the proposal.
+    pub txs: usize,
+    /// Total size of the transactions in the proposal.
+    pub bytes: usize,
+    pub kind: &'static str,
+    /// Index of the offending transaction, if the reason is a transaction.
+    pub tx_index: Option<usize>,
+    pub reason: String,
+}
+
+impl RejectedProposal {
+    pub fn new(
+        height: BlockHeight,
+        time: String,
+        proposer: String,
+        txs: usize,
+        bytes: usize,
+        reason: RejectReason,
+    ) -> Self {
+        Self {
+            height,
+            time,
+            proposer,
+            txs,
+            bytes,
+            kind: reason.kind(),
+            tx_index: reason.tx_index(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Record the size of a proposal in the metrics, returning the total size of its transactions.
+pub fn observe_proposal(subnet_id: &str, txs: &[Vec<u8>]) -> usize {
+    let tx_bytes = PROPOSAL_TX_BYTES.with_label_values(&[subnet_id]);
+    let mut total = 0;
+    for tx in txs {
+        tx_bytes.observe(tx.len() as f64);
+        total += tx.len();
+    }
+    PROPOSAL_TXS
+        .with_label_values(&[subnet_id])
+        .observe(txs.len() as f64);
+    PROPOSAL_BYTES
+        .with_label_values(&[subnet_id])
+        .observe(total as f64);
+    total
+}
+
+/// The most recent proposals this node voted against.
+#[derive(Clone, Debug, Default)]
+pub struct RecentRejections(Arc<Mutex<VecDeque<RejectedProposal>>>);
+
+impl RecentRejections {
+    /// Log and remember a rejected proposal, forgetting the oldest one if there are too many,
+    /// and count it in the metrics of the subnet.
+    pub fn record(&self, subnet_id: &str, rejection: RejectedProposal) {
+        tracing::warn!(
+            height = rejection.height,
+            proposer = rejection.proposer,
+            txs = rejection.txs,
+            bytes = rejection.bytes,
+            tx_index = rejection.tx_index,
+            reason = rejection.reason,
+            "rejected proposal"
+        );
+        PROPOSALS_REJECTED
+            .with_label_values(&[subnet_id, rejection.kind])
+            .inc();
+
+        let mut rejections = self.0.lock().unwrap();
+        if rejections.len() == MAX_REJECTIONS {
+            rejections.pop_front();
+        }
+        rejections.push_back(rejection);
+    }
+
+    /// The remembered rejections, the latest first.
+    pub fn list(&self) -> Vec<RejectedProposal> {
+        self.0.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_interpreter::ProposalRejection;
+
+    use super::{RecentRejections, RejectReason, RejectedProposal, MAX_REJECTIONS};
+
+    #[test]
+    fn keeps_latest_rejections() {
+        let reason = RejectReason::Tx(ProposalRejection::InvalidFinality {
+            index: 1,
+            height: 100,
+        });
+
+        let rejections = RecentRejections::default();
+        for height in 0..MAX_REJECTIONS as u64 + 5 {
+            let r =
+                RejectedProposal::new(height, String::new(), String::new(), 2, 30, reason.clone());
+            rejections.record("", r);
+        }
+
+        let list = rejections.list();
+        assert_eq!(list.len(), MAX_REJECTIONS);
+        assert_eq!(list[0].height, MAX_REJECTIONS as u64 + 4);
+        assert_eq!(list[MAX_REJECTIONS - 1].height, 5);
+
+        let r = &list[0];
+        assert_eq!(r.bytes, 30);
+        assert_eq!(r.kind, "invalid_finality");
+        assert_eq!(r.tx_index, Some(1));
+        assert_eq!(
+            r.reason,
+            "transaction 1 proposes parent finality at height 100, which isn't final"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A CometBFT compatible JSON-RPC endpoint which answers ABCI queries from an
+//! application running on a read-only secondary instance of the node's database,
+//! and forwards every other request to the node's CometBFT.
+//!
+//! Clients such as the Ethereum API can point at this endpoint instead of CometBFT,
+//! so that expensive queries run in a separate process from the validator.
+
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use base64::Engine;
+use fendermint_abci::Application;
+use serde_json::{json, Value};
+use tendermint::abci::request;
+use tendermint_rpc::{endpoint::abci_query, Url};
+
+/// JSON-RPC error code for failures inside the application.
+pub(crate) const INTERNAL_ERROR: i64 = -32603;
+/// JSON-RPC error code for malformed parameters.
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+/// JSON-RPC error code for methods which aren't served.
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code for requests which aren't valid JSON.
+pub(crate) const PARSE_ERROR: i64 = -32700;
+
+/// The outcome of a JSON-RPC call: the result, or the error code with the reason.
+pub(crate) type RpcResult = Result<Value, (i64, anyhow::Error)>;
+
+struct ReadOnlyState<A> {
+    app: A,
+    tendermint_url: Url,
+    http_client: reqwest::Client,
+}
+
+/// Serve the endpoint until the process exits:
+/// * `abci_query` and `abci_info` are handled by the application
+/// * anything else is forwarded to the CometBFT RPC at `tendermint_url`
+pub async fn serve<L, A>(listen: L, app: A, tendermint_url: Url) -> anyhow::Result<()>
+where
+    L: ToSocketAddrs,
+    A: Application + Send + Sync + 'static,
+{
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))?;
+
+    let router = router(app, tendermint_url);
+
+    tracing::info!(?addr, "serving read-only queries");
+
+    axum::Server::bind(&addr)
+        .serve(router.into_make_service())
+        .await
+        .context("failed to serve read-only queries")
+}
+
+/// The routes of the endpoint, so they can also be served along with others.
+pub fn router<A>(app: A, tendermint_url: Url) -> Router
+where
+    A: Application + Send + Sync + 'static,
+{
+    let state = Arc::new(ReadOnlyState {
+        app,
+        tendermint_url,
+        http_client: reqwest::Client::new(),
+    });
+
+    Router::new()
+        .route("/", post(handle::<A>))
+        .with_state(state)
+}
+
+async fn handle<A>(State(state): State<Arc<ReadOnlyState<A>>>, body: Bytes) -> Response
+where
+    A: Application + Send + Sync + 'static,
+{
+    let req: Value = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        // Let CometBFT produce the parse error, so clients get the response they expect.
+        Err(_) => return forward(&state, body).await,
+    };
+
+    let id = req.get("id").cloned().unwrap_or(Value::Null);
+    let params = req.get("params").cloned().unwrap_or(Value::Null);
+
+    let result = match req.get("method").and_then(|m| m.as_str()) {
+        Some("abci_query") => abci_query(&state.app, params).await,
+        Some("abci_info") => abci_info(&state.app).await,
+        _ => return forward(&state, body).await,
+    };
+
+    rpc_response(id, result)
+}
+
+/// Wrap the outcome of a call into a JSON-RPC response.
+pub(crate) fn rpc_response(id: Value, result: RpcResult) -> Response {
+    let res = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, e)) => {
+            let message = match code {
+                INVALID_PARAMS => "Invalid params",
+                METHOD_NOT_FOUND => "Method not found",
+                PARSE_ERROR => "Parse error",
+                _ => "Internal error",
+            };
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message, "data": format!("{e:#}") }
+            })
+        }
+    };
+
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        res.to_string(),
+    )
+        .into_response()
+}
+
+pub(crate) async fn abci_query<A: Application>(app: &A, params: Value) -> RpcResult {
+    let params: abci_query::Request =
+        serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, anyhow!(e)))?;
+
+    let request = request::Query {
+        data: params.data.into(),
+        path: params.path.unwrap_or_default(),
+        height: params.height.unwrap_or_default(),
+        prove: params.prove,
+    };
+
+    let res = app
+        .query(request)
+        .await
+        .map_err(|e| (INTERNAL_ERROR, anyhow!("{e}")))?;
+
+    let res = abci_query::Response {
+        response: abci_query::AbciQuery {
+            code: res.code,
+            log: res.log,
+            info: res.info,
+            index: res.index,
+            key: res.key.to_vec(),
+            value: res.value.to_vec(),
+            proof: res.proof,
+            height: res.height,
+            codespace: res.codespace,
+        },
+    };
+
+    serde_json::to_value(res).map_err(|e| (INTERNAL_ERROR, anyhow!(e)))
+}
+
+pub(crate) async fn abci_info<A: Application>(app: &A) -> RpcResult {
+    let res = app
+        .info(Default::default())
+        .await
+        .map_err(|e| (INTERNAL_ERROR, anyhow!("{e}")))?;
+
+    // Same representation as CometBFT, with numbers as strings.
+    Ok(json!({
+        "response": {
+            "data": res.data,
+            "version": res.version,
+            "app_version": res.app_version.to_string(),
+            "last_block_height": res.last_block_height.to_string(),
+            "last_block_app_hash": base64::engine::general_purpose::STANDARD
+                .encode(res.last_block_app_hash.as_bytes()),
+        }
+    }))
+}
+
+/// Send the request to CometBFT as it is and relay its response.
+async fn forward<A>(state: &ReadOnlyState<A>, body: Bytes) -> Response {
+    let res = state
+        .http_client
+        .post(state.tendermint_url.to_string())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await;
+
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            tracing::warn!(
+                error = e.to_string(),
+                "failed to forward request to CometBFT"
+            );
+            return (StatusCode::BAD_GATEWAY, e.to_string()).into_response();
+        }
+    };
+
+    let status = StatusCode::from_u16(res.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    match res.bytes().await {
+        Ok(body) => (status, [(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,1108 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Schemas of the actor states, to show them as JSON with named fields instead of raw IPLD.
+//!
+//! The builtin actors are identified by their name in the manifest of the actor bundle, which,
+//! unlike their code CID, doesn't change between bundle versions. The actors whose state is kept
+//! by the interpreter all have the placeholder code, so they are told apart by their ID, and so
+//! are the IPC actors, which are EVM contracts deployed at fixed IDs.
+//!
+//! Each schema decodes the state into the type it's declared with, to make sure it's the right
+//! one, and then names the fields of the tuples it's encoded as. Anything the schema doesn't
+//! describe, e.g. fields added in a later version, is shown as is, with bytes in hex.
+
+use anyhow::{anyhow, Context};
+use cid::Cid;
+use fendermint_vm_actor_interface::{
+    accesscontrol, account, beacon, blocktime, chainmetadata, checkpointarchive, contractbook,
+    cron, deadletter, diamondlayout, evm, execdigests, governance, init, ipc, multisig, policy,
+    rewardpool, scheduler, system, topdownnonces, txcompression, validators,
+};
+use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+use libipld::Ipld;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+/// Name of the actor all the actors with state kept by the interpreter are created with.
+const PLACEHOLDER: &str = "placeholder";
+
+/// How to show a value.
+pub enum Kind {
+    /// Shown as it is in the IPLD.
+    Any,
+    /// An address encoded as bytes.
+    Address,
+    /// A token amount encoded as bytes, shown in atto.
+    TokenAmount,
+    /// A tuple with named fields.
+    Tuple(&'static [Field]),
+    /// A list of values of the same kind.
+    List(&'static Kind),
+}
+
+/// A named field of a tuple.
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+const fn field(name: &'static str, kind: Kind) -> Field {
+    Field { name, kind }
+}
+
+/// The layout of the state of an actor.
+pub struct Schema {
+    /// Name of the actor, which is what the schema can be looked up by.
+    pub name: &'static str,
+    /// Name of the code of the actor in the manifest.
+    pub code: &'static str,
+    /// The ID of the actor, for the ones which share their code with others.
+    pub id: Option<ActorID>,
+    /// The fields of the state tuple.
+    pub fields: &'static [Field],
+    /// Decode the state into the type it's declared with.
+    check: fn(&[u8]) -> anyhow::Result<()>,
+}
+
+impl Schema {
+    /// Decode the state of the actor into JSON with named fields.
+    pub fn decode(&self, state: &[u8]) -> anyhow::Result<Value> {
+        (self.check)(state)
+            .with_context(|| format!("the state doesn't match the {} schema", self.name))?;
+
+        let ipld = fvm_ipld_encoding::from_slice::<Ipld>(state).context("the state is not IPLD")?;
+
+        Ok(render(&ipld, &Kind::Tuple(self.fields)))
+    }
+}
+
+fn check<T: DeserializeOwned>(state: &[u8]) -> anyhow::Result<()> {
+    fvm_ipld_encoding::from_slice::<T>(state)?;
+    Ok(())
+}
+
+const fn builtin(
+    name: &'static str,
+    fields: &'static [Field],
+    check: fn(&[u8]) -> anyhow::Result<()>,
+) -> Schema {
+    Schema {
+        name,
+        code: name,
+        id: None,
+        fields,
+        check,
+    }
+}
+
+const fn singleton(
+    name: &'static str,
+    code: &'static str,
+    id: ActorID,
+    fields: &'static [Field],
+    check: fn(&[u8]) -> anyhow::Result<()>,
+) -> Schema {
+    Schema {
+        name,
+        code,
+        id: Some(id),
+        fields,
+        check,
+    }
+}
+
+const ADDRESSES: Kind = Kind::List(&Kind::Address);
+
+const EVM: &[Field] = &[
+    field("bytecode", Kind::Any),
+    field("bytecode_hash", Kind::Any),
+    field("contract_state", Kind::Any),
+    field("nonce", Kind::Any),
+    field(
+        "tombstone",
+        Kind::Tuple(&[field("origin", Kind::Any), field("nonce", Kind::Any)]),
+    ),
+];
+
+const CHAIN_PARAMS: Kind = Kind::Tuple(&[
+    field("base_fee_floor", Kind::TokenAmount),
+    field("block_gas_limit", Kind::Any),
+    field("topdown_max_msgs", Kind::Any),
+]);
+
+const TOPDOWN_FILTER: Kind =
+    Kind::Tuple(&[field("blocked", ADDRESSES), field("allowed", ADDRESSES)]);
+
+/// All the schemas known to the application.
+pub static SCHEMAS: &[Schema] = &[
+    builtin(
+        "system",
+        &[field("builtin_actors", Kind::Any)],
+        check::<system::State>,
+    ),
+    builtin(
+        "init",
+        &[
+            field("address_map", Kind::Any),
+            field("next_id", Kind::Any),
+            field("network_name", Kind::Any),
+        ],
+        check::<init::State>,
+    ),
+    builtin(
+        "cron",
+        &[field(
+            "entries",
+            Kind::List(&Kind::Tuple(&[
+                field("receiver", Kind::Address),
+                field("method_num", Kind::Any),
+            ])),
+        )],
+        check::<cron::State>,
+    ),
+    builtin(
+        "account",
+        &[field("address", Kind::Address)],
+        check::<account::State>,
+    ),
+    builtin(
+        "multisig",
+        &[
+            field("signers", ADDRESSES),
+            field("num_approvals_threshold", Kind::Any),
+            field("next_tx_id", Kind::Any),
+            field("initial_balance", Kind::TokenAmount),
+            field("start_epoch", Kind::Any),
+            field("unlock_duration", Kind::Any),
+            field("pending_txs", Kind::Any),
+        ],
+        check::<multisig::State>,
+    ),
+    builtin("evm", EVM, check::<evm::State>),
+    singleton(
+        "gateway",
+        "evm",
+        ipc::GATEWAY_ACTOR_ID,
+        EVM,
+        check::<evm::State>,
+    ),
+    singleton(
+        "subnet_registry",
+        "evm",
+        ipc::SUBNETREGISTRY_ACTOR_ID,
+        EVM,
+        check::<evm::State>,
+    ),
+    singleton(
+        "access_control",
+        PLACEHOLDER,
+        accesscontrol::ACCESS_CONTROL_ACTOR_ID,
+        &[
+            field("governor", Kind::Address),
+            field("deployers", ADDRESSES),
+            field("senders", ADDRESSES),
+        ],
+        check::<accesscontrol::State>,
+    ),
+    singleton(
+        "governance",
+        PLACEHOLDER,
+        governance::GOVERNANCE_ACTOR_ID,
+        &[
+            field("members", ADDRESSES),
+            field("threshold", Kind::Any),
+            field("epoch_length", Kind::Any),
+            field("params", CHAIN_PARAMS),
+            field("next_id", Kind::Any),
+            field(
+                "proposals",
+                Kind::List(&Kind::Tuple(&[
+                    field("id", Kind::Any),
+                    field("proposer", Kind::Address),
+                    field("height", Kind::Any),
+                    field("params", CHAIN_PARAMS),
+                    field("approvals", ADDRESSES),
+                    field("shutdown", Kind::Any),
+                    field("topdown_filter", TOPDOWN_FILTER),
+                ])),
+            ),
+            field(
+                "scheduled",
+                Kind::Tuple(&[
+                    field("proposal_id", Kind::Any),
+                    field("params", CHAIN_PARAMS),
+                    field("shutdown", Kind::Any),
+                ]),
+            ),
+            field("deferred_topdown_msgs", Kind::Any),
+            field(
+                "shutdown",
+                Kind::Tuple(&[
+                    field("since", Kind::Any),
+                    field("final_checkpoint_height", Kind::Any),
+                    field("terminal_state_root", Kind::Any),
+                ]),
+            ),
+            field("topdown_filter", TOPDOWN_FILTER),
+        ],
+        check::<governance::State>,
+    ),
+    singleton(
+        "checkpoint_archive",
+        PLACEHOLDER,
+        checkpointarchive::CHECKPOINT_ARCHIVE_ACTOR_ID,
+        &[
+            field("retention", Kind::Any),
+            field(
+                "archives",
+                Kind::List(&Kind::Tuple(&[
+                    field("from_height", Kind::Any),
+                    field("to_height", Kind::Any),
+                    field("root", Kind::Any),
+                ])),
+            ),
+        ],
+        check::<checkpointarchive::State>,
+    ),
+    singleton(
+        "topdown_nonces",
+        PLACEHOLDER,
+        topdownnonces::TOPDOWN_NONCES_ACTOR_ID,
+        &[
+            field("next_nonce", Kind::Any),
+            field("last_height", Kind::Any),
+        ],
+        check::<topdownnonces::State>,
+    ),
+    singleton(
+        "scheduler",
+        PLACEHOLDER,
+        scheduler::SCHEDULER_ACTOR_ID,
+        &[field(
+            "calls",
+            Kind::List(&Kind::Tuple(&[
+                field("name", Kind::Any),
+                field("to", Kind::Address),
+                field("method_num", Kind::Any),
+                field("params", Kind::Any),
+                field("start_height", Kind::Any),
+                field("interval", Kind::Any),
+            ])),
+        )],
+        check::<scheduler::State>,
+    ),
+    singleton(
+        "tx_compression",
+        PLACEHOLDER,
+        txcompression::TX_COMPRESSION_ACTOR_ID,
+        &[field("max_decompressed_size", Kind::Any)],
+        check::<txcompression::State>,
+    ),
+    singleton(
+        "block_time",
+        PLACEHOLDER,
+        blocktime::BLOCK_TIME_ACTOR_ID,
+        &[field("max_interval", Kind::Any)],
+        check::<blocktime::State>,
+    ),
+    singleton(
+        "beacon",
+        PLACEHOLDER,
+        beacon::BEACON_ACTOR_ID,
+        &[
+            field("epoch_length", Kind::Any),
+            field("lookback_len", Kind::Any),
+            field(
+                "round",
+                Kind::Tuple(&[
+                    field("epoch", Kind::Any),
+                    field(
+                        "contributions",
+                        Kind::List(&Kind::Tuple(&[
+                            field("sender", Kind::Address),
+                            field("commitment", Kind::Any),
+                            field("secret", Kind::Any),
+                        ])),
+                    ),
+                ]),
+            ),
+            field(
+                "beacons",
+                Kind::List(&Kind::Tuple(&[
+                    field("epoch", Kind::Any),
+                    field("height", Kind::Any),
+                    field("value", Kind::Any),
+                    field("contributors", Kind::Any),
+                ])),
+            ),
+            field(
+                "suspensions",
+                Kind::List(&Kind::Tuple(&[
+                    field("sender", Kind::Address),
+                    field("until_epoch", Kind::Any),
+                ])),
+            ),
+        ],
+        check::<beacon::State>,
+    ),
+    singleton(
+        "reward_pool",
+        PLACEHOLDER,
+        rewardpool::REWARD_POOL_ACTOR_ID,
+        &[
+            field("epoch_length", Kind::Any),
+            field("inflation_per_epoch", Kind::TokenAmount),
+            field("collect_base_fees", Kind::Any),
+            field("epoch", Kind::Any),
+            field("total_claimable", Kind::TokenAmount),
+            field(
+                "claimable",
+                Kind::List(&Kind::Tuple(&[
+                    field("public_key", Kind::Any),
+                    field("amount", Kind::TokenAmount),
+                ])),
+            ),
+        ],
+        check::<rewardpool::State>,
+    ),
+    singleton(
+        "policy",
+        PLACEHOLDER,
+        policy::POLICY_ACTOR_ID,
+        &[field("module", Kind::Any), field("fuel_limit", Kind::Any)],
+        check::<policy::State>,
+    ),
+    singleton(
+        "validators",
+        PLACEHOLDER,
+        validators::VALIDATORS_ACTOR_ID,
+        &[field(
+            "validators",
+            Kind::List(&Kind::Tuple(&[
+                field("public_key", Kind::Any),
+                field("power", Kind::Any),
+                field("joined_at", Kind::Any),
+                field("updated_at", Kind::Any),
+            ])),
+        )],
+        check::<validators::State>,
+    ),
+    singleton(
+        "chain_metadata",
+        PLACEHOLDER,
+        chainmetadata::CHAIN_METADATA_ACTOR_ID,
+        &[
+            field("lookback_len", Kind::Any),
+            field(
+                "blocks",
+                Kind::List(&Kind::Tuple(&[
+                    field("height", Kind::Any),
+                    field("block_cid", Kind::Any),
+                ])),
+            ),
+        ],
+        check::<chainmetadata::State>,
+    ),
+    singleton(
+        "contract_book",
+        PLACEHOLDER,
+        contractbook::CONTRACT_BOOK_ACTOR_ID,
+        &[field(
+            "contracts",
+            Kind::List(&Kind::Tuple(&[
+                field("fqn", Kind::Any),
+                field("actor_id", Kind::Any),
+                field("eth_address", Kind::Any),
+                field("library", Kind::Any),
+            ])),
+        )],
+        check::<contractbook::State>,
+    ),
+    singleton(
+        "diamond_layout",
+        PLACEHOLDER,
+        diamondlayout::DIAMOND_LAYOUT_ACTOR_ID,
+        &[field(
+            "diamonds",
+            Kind::List(&Kind::Tuple(&[
+                field("name", Kind::Any),
+                field("actor_id", Kind::Any),
+                field(
+                    "facets",
+                    Kind::List(&Kind::Tuple(&[
+                        field("name", Kind::Any),
+                        field("address", Kind::Any),
+                        field("selectors", Kind::Any),
+                    ])),
+                ),
+            ])),
+        )],
+        check::<diamondlayout::State>,
+    ),
+    singleton(
+        "dead_letters",
+        PLACEHOLDER,
+        deadletter::DEADLETTER_ACTOR_ID,
+        &[
+            field("next_id", Kind::Any),
+            field("first_id", Kind::Any),
+            field("count", Kind::Any),
+            field("letters", Kind::Any),
+        ],
+        check::<deadletter::State>,
+    ),
+    singleton(
+        "exec_digests",
+        PLACEHOLDER,
+        execdigests::EXEC_DIGESTS_ACTOR_ID,
+        &[
+            field("lookback_len", Kind::Any),
+            field(
+                "blocks",
+                Kind::List(&Kind::Tuple(&[
+                    field("height", Kind::Any),
+                    field("tx_count", Kind::Any),
+                    field("receipts_root", Kind::Any),
+                    field("events_root", Kind::Any),
+                    field("receipts", Kind::Any),
+                ])),
+            ),
+        ],
+        check::<execdigests::State>,
+    ),
+];
+
+/// Look up a schema by the name of the actor.
+pub fn by_name(name: &str) -> Option<&'static Schema> {
+    SCHEMAS.iter().find(|s| s.name == name)
+}
+
+/// Look up the schema of an actor by the name of its code in the manifest and its ID.
+pub fn lookup(code: &str, id: ActorID) -> Option<&'static Schema> {
+    let mut candidates = SCHEMAS.iter().filter(|s| s.code == code);
+    candidates
+        .clone()
+        .find(|s| s.id == Some(id))
+        .or_else(|| candidates.find(|s| s.id.is_none()))
+}
+
+/// Find the name of an actor code in the data of the builtin actor manifest,
+/// which is the `builtin_actors` CID in the state of the system actor.
+pub fn code_name(manifest_data: &[u8], code: &Cid) -> anyhow::Result<Option<String>> {
+    let entries: Vec<(String, Cid)> = fvm_ipld_encoding::from_slice(manifest_data)
+        .map_err(|e| anyhow!("failed to decode the actor manifest: {e}"))?;
+
+    Ok(entries
+        .into_iter()
+        .find(|(_, c)| c == code)
+        .map(|(name, _)| name))
+}
+
+/// Show a value with the names of the fields the kind has, if it matches.
+fn render(ipld: &Ipld, kind: &Kind) -> Value {
+    match (kind, ipld) {
+        (Kind::Address, Ipld::Bytes(bz)) => match Address::from_bytes(bz) {
+            Ok(addr) => json!(addr.to_string()),
+            Err(_) => render_any(ipld),
+        },
+        (Kind::TokenAmount, Ipld::Bytes(_)) => match typed::<TokenAmount>(ipld) {
+            Some(amount) => json!(amount.atto().to_string()),
+            None => render_any(ipld),
+        },
+        (Kind::Tuple(fields), Ipld::List(items)) => {
+            let mut obj = Map::new();
+            for (i, item) in items.iter().enumerate() {
+                match fields.get(i) {
+                    Some(f) => obj.insert(f.name.to_owned(), render(item, &f.kind)),
+                    None => obj.insert(format!("_{i}"), render_any(item)),
+                };
+            }
+            Value::Object(obj)
+        }
+        (Kind::List(kind), Ipld::List(items)) => {
+            Value::Array(items.iter().map(|item| render(item, kind)).collect())
+        }
+        _ => render_any(ipld),
+    }
+}
+
+/// Show a value without a schema.
+fn render_any(ipld: &Ipld) -> Value {
+    match ipld {
+        Ipld::Null => Value::Null,
+        Ipld::Bool(b) => json!(b),
+        Ipld::Integer(i) => match i64::try_from(*i) {
+            Ok(i) => json!(i),
+            Err(_) => json!(i.to_string()),
+        },
+        Ipld::Float(f) => json!(f),
+        Ipld::String(s) => json!(s),
+        Ipld::Bytes(bz) => json!(format!("0x{}", hex::encode(bz))),
+        Ipld::List(items) => Value::Array(items.iter().map(render_any).collect()),
+        Ipld::Map(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(k, v)| (k.clone(), render_any(v)))
+                .collect(),
+        ),
+        Ipld::Link(cid) => json!(cid.to_string()),
+    }
+}
+
+/// Decode a value into a type with its own encoding.
+fn typed<T: DeserializeOwned>(ipld: &Ipld) -> Option<T> {
+    fvm_ipld_encoding::to_vec(ipld)
+        .ok()
+        .and_then(|bz| fvm_ipld_encoding::from_slice(&bz).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+    use fendermint_vm_actor_interface::{
+        accesscontrol, account, beacon, blocktime, chainmetadata, checkpointarchive, contractbook,
+        cron, deadletter, diamondlayout, eam::EthAddress, evm, execdigests, governance, init, ipc,
+        multisig, policy, rewardpool, scheduler, system, topdownnonces, txcompression, validators,
+    };
+    use fvm_ipld_encoding::{RawBytes, IPLD_RAW};
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use libipld::Ipld;
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::{by_name, lookup, Field, Kind, SCHEMAS};
+
+    /// Build a value out of all of its fields and check that the fields of the schema at the
+    /// path have the same names, in the order the value is encoded in.
+    ///
+    /// The fields have to be given different values, which is what tells them apart in the encoding.
+    macro_rules! assert_fields {
+        ($name:expr, [$($step:literal),*], $($ty:ident)::+ { $($field:ident: $value:expr),* $(,)? }) => {{
+            let value = $($ty)::+ { $($field: $value),* };
+            let path: &[&str] = &[$($step),*];
+            assert_fields(
+                &format!("{} {:?}", $name, path),
+                fields_of($name, path),
+                &to_ipld(&value),
+                &[$((stringify!($field), to_ipld(&value.$field))),*],
+            );
+            if path.is_empty() {
+                let bz = fvm_ipld_encoding::to_vec(&value).unwrap();
+                by_name($name).unwrap().decode(&bz).unwrap();
+            }
+        }};
+    }
+
+    fn assert_fields(at: &str, fields: &[Field], encoded: &Ipld, values: &[(&str, Ipld)]) {
+        for (i, (a, x)) in values.iter().enumerate() {
+            for (b, y) in &values[i + 1..] {
+                assert_ne!(x, y, "{at}: give {a} and {b} different values");
+            }
+        }
+        let items = match encoded {
+            Ipld::List(items) => items,
+            other => panic!("{at}: not encoded as a tuple: {other:?}"),
+        };
+        assert_eq!(fields.len(), items.len(), "{at}: number of fields");
+        for (f, item) in fields.iter().zip(items) {
+            let value = values
+                .iter()
+                .find(|(name, _)| *name == f.name)
+                .map(|(_, value)| value)
+                .unwrap_or_else(|| panic!("{at}: there is no {} field", f.name));
+            assert_eq!(item, value, "{at}: {} is in the wrong place", f.name);
+        }
+    }
+
+    /// The fields of a schema, or of the tuples nested in it, following the names through lists.
+    fn fields_of(name: &str, path: &[&str]) -> &'static [Field] {
+        let mut fields = by_name(name).unwrap().fields;
+        for step in path {
+            let mut kind = &fields
+                .iter()
+                .find(|f| f.name == *step)
+                .unwrap_or_else(|| panic!("{name} has no {step} field"))
+                .kind;
+            while let Kind::List(inner) = kind {
+                kind = *inner;
+            }
+            fields = match kind {
+                Kind::Tuple(nested) => *nested,
+                _ => panic!("{name}: {step} is not a tuple"),
+            };
+        }
+        fields
+    }
+
+    fn to_ipld<T: Serialize>(value: &T) -> Ipld {
+        fvm_ipld_encoding::from_slice(&fvm_ipld_encoding::to_vec(value).unwrap()).unwrap()
+    }
+
+    fn cid(seed: u8) -> Cid {
+        Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(&[seed]))
+    }
+
+    fn addr(id: u64) -> Address {
+        Address::new_id(id)
+    }
+
+    fn atto(amount: u64) -> TokenAmount {
+        TokenAmount::from_atto(amount)
+    }
+
+    #[test]
+    fn decode_with_field_names() {
+        let mut state = rewardpool::State::new(10, TokenAmount::from_atto(1000), true);
+        state.distribute(&TokenAmount::from_atto(60), &[(vec![1, 2], 1)]);
+        let bz = fvm_ipld_encoding::to_vec(&state).unwrap();
+
+        let decoded = by_name("reward_pool").unwrap().decode(&bz).unwrap();
+        assert_eq!(
+            decoded,
+            json!({
+                "epoch_length": 10,
+                "inflation_per_epoch": "1000",
+                "collect_base_fees": true,
+                "epoch": 0,
+                "total_claimable": "60",
+                "claimable": [{ "public_key": "0x0102", "amount": "60" }]
+            })
+        );
+
+        // The state of another actor doesn't fit.
+        assert!(by_name("validators").unwrap().decode(&bz).is_err());
+    }
+
+    #[test]
+    fn lookup_by_code_and_id() {
+        let name = |code, id| lookup(code, id).map(|s| s.name);
+
+        assert_eq!(
+            name("placeholder", validators::VALIDATORS_ACTOR_ID),
+            Some("validators")
+        );
+        assert_eq!(name("placeholder", 1000), None);
+        assert_eq!(name("evm", ipc::GATEWAY_ACTOR_ID), Some("gateway"));
+        assert_eq!(name("evm", 1000), Some("evm"));
+        assert_eq!(name("account", 1000), Some("account"));
+    }
+
+    #[test]
+    fn field_names_match_the_types() {
+        assert_fields!(
+            "system",
+            [],
+            system::State {
+                builtin_actors: cid(1)
+            }
+        );
+        assert_fields!(
+            "init",
+            [],
+            init::State {
+                address_map: cid(1),
+                next_id: 2,
+                network_name: "test".to_owned(),
+            }
+        );
+        assert_fields!(
+            "cron",
+            [],
+            cron::State {
+                entries: Vec::new()
+            }
+        );
+        assert_fields!(
+            "cron",
+            ["entries"],
+            cron::Entry {
+                receiver: addr(1),
+                method_num: 2,
+            }
+        );
+        assert_fields!("account", [], account::State { address: addr(1) });
+        assert_fields!(
+            "multisig",
+            [],
+            multisig::State {
+                signers: vec![addr(1)],
+                num_approvals_threshold: 2,
+                next_tx_id: multisig::TxnID(3),
+                initial_balance: atto(4),
+                start_epoch: 5,
+                unlock_duration: 6,
+                pending_txs: cid(7),
+            }
+        );
+        for name in ["evm", "gateway", "subnet_registry"] {
+            assert_fields!(
+                name,
+                [],
+                evm::State {
+                    bytecode: cid(1),
+                    bytecode_hash: vec![2],
+                    contract_state: cid(3),
+                    nonce: 4,
+                    tombstone: Some(evm::Tombstone {
+                        origin: 5,
+                        nonce: 6
+                    }),
+                }
+            );
+            assert_fields!(
+                name,
+                ["tombstone"],
+                evm::Tombstone {
+                    origin: 1,
+                    nonce: 2
+                }
+            );
+        }
+        assert_fields!(
+            "access_control",
+            [],
+            accesscontrol::State {
+                governor: addr(1),
+                deployers: Some(vec![addr(2)]),
+                senders: Some(vec![addr(3)]),
+            }
+        );
+
+        let params = governance::ChainParams {
+            base_fee_floor: atto(1),
+            block_gas_limit: 2,
+            topdown_max_msgs: 3,
+        };
+        assert_fields!(
+            "governance",
+            [],
+            governance::State {
+                members: vec![addr(1)],
+                threshold: 2,
+                epoch_length: 3,
+                params: params.clone(),
+                next_id: 4,
+                proposals: vec![governance::Proposal {
+                    id: 5,
+                    proposer: addr(6),
+                    height: 7,
+                    params: params.clone(),
+                    approvals: Vec::new(),
+                    shutdown: false,
+                    topdown_filter: None,
+                }],
+                scheduled: Some(governance::ScheduledChange {
+                    proposal_id: 8,
+                    params: params.clone(),
+                    shutdown: true,
+                }),
+                deferred_topdown_msgs: Vec::new(),
+                shutdown: Some(governance::Shutdown {
+                    since: 9,
+                    final_checkpoint_height: None,
+                    terminal_state_root: None,
+                }),
+                topdown_filter: governance::TopdownFilter::default(),
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["params"],
+            governance::ChainParams {
+                base_fee_floor: atto(1),
+                block_gas_limit: 2,
+                topdown_max_msgs: 3,
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["proposals"],
+            governance::Proposal {
+                id: 1,
+                proposer: addr(2),
+                height: 3,
+                params: params.clone(),
+                approvals: vec![addr(4)],
+                shutdown: true,
+                topdown_filter: Some(governance::TopdownFilter::default()),
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["proposals", "topdown_filter"],
+            governance::TopdownFilter {
+                blocked: vec![addr(1)],
+                allowed: Some(vec![addr(2)]),
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["scheduled"],
+            governance::ScheduledChange {
+                proposal_id: 1,
+                params,
+                shutdown: true,
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["shutdown"],
+            governance::Shutdown {
+                since: 1,
+                final_checkpoint_height: Some(2),
+                terminal_state_root: Some(cid(3)),
+            }
+        );
+        assert_fields!(
+            "governance",
+            ["topdown_filter"],
+            governance::TopdownFilter {
+                blocked: vec![addr(1)],
+                allowed: Some(vec![addr(2)]),
+            }
+        );
+
+        assert_fields!(
+            "checkpoint_archive",
+            [],
+            checkpointarchive::State {
+                retention: 1,
+                archives: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "checkpoint_archive",
+            ["archives"],
+            checkpointarchive::Archive {
+                from_height: 1,
+                to_height: 2,
+                root: cid(3),
+            }
+        );
+        assert_fields!(
+            "topdown_nonces",
+            [],
+            topdownnonces::State {
+                next_nonce: 1,
+                last_height: 2,
+            }
+        );
+        assert_fields!("scheduler", [], scheduler::State { calls: Vec::new() });
+        assert_fields!(
+            "scheduler",
+            ["calls"],
+            scheduler::ScheduledCall {
+                name: "test".to_owned(),
+                to: addr(1),
+                method_num: 2,
+                params: RawBytes::new(vec![3]),
+                start_height: 4,
+                interval: 5,
+            }
+        );
+        assert_fields!(
+            "tx_compression",
+            [],
+            txcompression::State {
+                max_decompressed_size: 1
+            }
+        );
+        assert_fields!("block_time", [], blocktime::State { max_interval: 1 });
+
+        assert_fields!(
+            "beacon",
+            [],
+            beacon::State {
+                epoch_length: 1,
+                lookback_len: 2,
+                round: beacon::Round::default(),
+                beacons: vec![beacon::BeaconEntry {
+                    epoch: 3,
+                    height: 4,
+                    value: vec![5],
+                    contributors: 6,
+                }],
+                suspensions: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "beacon",
+            ["round"],
+            beacon::Round {
+                epoch: 1,
+                contributions: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "beacon",
+            ["round", "contributions"],
+            beacon::Contribution {
+                sender: addr(1),
+                commitment: vec![2],
+                secret: vec![3],
+            }
+        );
+        assert_fields!(
+            "beacon",
+            ["beacons"],
+            beacon::BeaconEntry {
+                epoch: 1,
+                height: 2,
+                value: vec![3],
+                contributors: 4,
+            }
+        );
+        assert_fields!(
+            "beacon",
+            ["suspensions"],
+            beacon::Suspension {
+                sender: addr(1),
+                until_epoch: 2,
+            }
+        );
+
+        assert_fields!(
+            "reward_pool",
+            [],
+            rewardpool::State {
+                epoch_length: 1,
+                inflation_per_epoch: atto(2),
+                collect_base_fees: true,
+                epoch: 3,
+                total_claimable: atto(4),
+                claimable: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "reward_pool",
+            ["claimable"],
+            rewardpool::Claimable {
+                public_key: vec![1],
+                amount: atto(2),
+            }
+        );
+        assert_fields!(
+            "policy",
+            [],
+            policy::State {
+                module: cid(1),
+                fuel_limit: 2,
+            }
+        );
+        assert_fields!(
+            "validators",
+            [],
+            validators::State {
+                validators: Vec::new()
+            }
+        );
+        assert_fields!(
+            "validators",
+            ["validators"],
+            validators::ValidatorEntry {
+                public_key: vec![1],
+                power: 2,
+                joined_at: 3,
+                updated_at: 4,
+            }
+        );
+        assert_fields!(
+            "chain_metadata",
+            [],
+            chainmetadata::State {
+                lookback_len: 1,
+                blocks: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "chain_metadata",
+            ["blocks"],
+            chainmetadata::BlockMetadata {
+                height: 1,
+                block_cid: cid(2),
+            }
+        );
+        assert_fields!(
+            "contract_book",
+            [],
+            contractbook::State {
+                contracts: Vec::new()
+            }
+        );
+        assert_fields!(
+            "contract_book",
+            ["contracts"],
+            contractbook::ContractEntry {
+                fqn: "test".to_owned(),
+                actor_id: 1,
+                eth_address: EthAddress([2; 20]),
+                library: true,
+            }
+        );
+        assert_fields!(
+            "diamond_layout",
+            [],
+            diamondlayout::State {
+                diamonds: Vec::new()
+            }
+        );
+        assert_fields!(
+            "diamond_layout",
+            ["diamonds"],
+            diamondlayout::DiamondEntry {
+                name: "test".to_owned(),
+                actor_id: 1,
+                facets: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "diamond_layout",
+            ["diamonds", "facets"],
+            diamondlayout::FacetEntry {
+                name: "test".to_owned(),
+                address: EthAddress([1; 20]),
+                selectors: vec![[2; 4]],
+            }
+        );
+        assert_fields!(
+            "dead_letters",
+            [],
+            deadletter::State {
+                next_id: 1,
+                first_id: 2,
+                count: 3,
+                letters: cid(4),
+            }
+        );
+        assert_fields!(
+            "exec_digests",
+            [],
+            execdigests::State {
+                lookback_len: 1,
+                blocks: Vec::new(),
+            }
+        );
+        assert_fields!(
+            "exec_digests",
+            ["blocks"],
+            execdigests::BlockDigest {
+                height: 1,
+                tx_count: 2,
+                receipts_root: [3; 32],
+                events_root: [4; 32],
+                receipts: cid(5),
+            }
+        );
+    }
+
+    #[test]
+    fn unique_names() {
+        for (i, s) in SCHEMAS.iter().enumerate() {
+            assert!(SCHEMAS[i + 1..].iter().all(|o| o.name != s.name));
+        }
+    }
+}
@@ -2,26 +2,44 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 //! Conversions to Tendermint data types.
 use anyhow::{anyhow, bail, Context};
+use ethers_core::types as et;
 use fendermint_vm_core::Timestamp;
 use fendermint_vm_genesis::{Power, Validator};
 use fendermint_vm_interpreter::fvm::{
     state::{BlockHash, FvmStateParams},
-    FvmApplyRet, FvmCheckRet, FvmQueryRet,
+    FvmApplyRet, FvmBeginRet, FvmCheckRet, FvmQueryRet,
+};
+use fendermint_vm_message::ipc::ParentFinality;
+use fendermint_vm_message::logs::{self, EventEntry};
+use fendermint_vm_message::query::{
+    accrue_bloom, AppHashComponent, AppHashReport, Capabilities, TopDownMessage, ValidatorSet,
 };
 use fendermint_vm_message::signed::DomainHash;
-use fendermint_vm_snapshot::{SnapshotItem, SnapshotManifest};
+use fendermint_vm_snapshot::{
+    DownloadProgress, SnapshotCompression, SnapshotItem, SnapshotManifest,
+};
+use fvm_ipld_encoding::RawBytes;
 use fvm_shared::{address::Address, error::ExitCode, event::StampedEvent, ActorID};
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, num::NonZeroU32};
 use tendermint::abci::{response, Code, Event, EventAttribute};
 
+use crate::gasstats::GasStatsReport;
+use crate::latency::MessageTrace;
+use crate::proposals::RejectedProposal;
 use crate::{app::AppError, BlockHeight};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct SnapshotMetadata {
     size: u64,
     state_params: FvmStateParams,
+    /// Missing from the metadata of peers which don't compress their snapshots.
+    #[serde(default)]
+    compression: SnapshotCompression,
+    /// Missing from the metadata of peers which don't record their chunk size.
+    #[serde(default)]
+    chunk_size: u64,
 }
 
 /// IPLD encoding of data types we know we must be able to encode.
@@ -148,8 +166,9 @@ pub fn to_end_block(power_table: Vec<Validator<Power>>) -> anyhow::Result<respon
 }
 
 /// Map the return values from cron operations.
-pub fn to_begin_block(ret: FvmApplyRet) -> response::BeginBlock {
-    let events = to_events("event", ret.apply_ret.events, ret.emitters);
+pub fn to_begin_block(ret: FvmBeginRet) -> response::BeginBlock {
+    let mut events = to_events("event", ret.cron.apply_ret.events, ret.cron.emitters);
+    events.extend(to_events("event", ret.events, ret.emitters));
 
     response::BeginBlock { events }
 }
@@ -239,15 +258,238 @@ pub fn to_message_event(from: Address, to: Address) -> Event {
     )
 }
 
+/// Add the addresses and the event topics of a delivered transaction to the logs bloom of the block,
+/// the way it's described at [`LOGS_BLOOM_PATH`](fendermint_vm_message::query::LOGS_BLOOM_PATH).
+pub fn accrue_logs_bloom(bloom: &mut et::Bloom, events: &[Event]) {
+    for event in events {
+        for attr in event.attributes.iter() {
+            let input = match (event.kind.as_str(), attr.key.as_str()) {
+                ("event", "emitter.id") => attr
+                    .value
+                    .parse::<ActorID>()
+                    .ok()
+                    .map(|id| Address::new_id(id).to_bytes()),
+                ("event", "emitter.deleg") | ("message", "from" | "to") => {
+                    attr.value.parse::<Address>().ok().map(|a| a.to_bytes())
+                }
+                _ => None,
+            };
+            if let Some(input) = input {
+                accrue_bloom(bloom, &input);
+            }
+        }
+        if event.kind == "event" {
+            let topics = event_topics_and_data(event)
+                .map(|(topics, _)| topics)
+                .unwrap_or_default();
+            for topic in topics {
+                accrue_bloom(bloom, topic.as_bytes());
+            }
+        }
+    }
+}
+
+/// The Ethereum topics and data the API derives from an actor event, or none if it's malformed.
+pub fn event_topics_and_data(event: &Event) -> Option<(Vec<et::H256>, et::Bytes)> {
+    let values = event
+        .attributes
+        .iter()
+        .filter(|a| !a.key.starts_with("emitter."))
+        .map(|a| hex::decode(&a.value).map(|v| (a, v)))
+        .collect::<Result<Vec<_>, _>>();
+
+    let values = values.ok()?;
+
+    let entries = values
+        .iter()
+        .map(|(a, value)| EventEntry {
+            key: &a.key,
+            value,
+            indexed: a.index,
+        })
+        .collect::<Vec<_>>();
+
+    logs::to_topics_and_data(&entries).ok()
+}
+
+/// Respond to the logs bloom query.
+pub fn to_logs_blooms(
+    blooms: Vec<(BlockHeight, RawBytes)>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(blooms);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the rejected proposals query.
+pub fn to_rejected_proposals(
+    rejections: Vec<RejectedProposal>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value =
+        serde_json::to_vec(&rejections).context("failed to serialize rejected proposals")?;
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the validator set query.
+pub fn to_validator_set(
+    set: Option<ValidatorSet>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(set);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the block height by hash query.
+pub fn to_block_height_by_hash(
+    height: Option<BlockHeight>,
+    first_height: Option<BlockHeight>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!((height, first_height));
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+pub fn to_parent_finality(
+    finality: Option<ParentFinality>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(finality);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+pub fn to_topdown_msgs(
+    msgs: Vec<TopDownMessage>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(msgs);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Break down the app hash of the state committed at a height into its components.
+pub fn to_app_hash_report(height: BlockHeight, state_params: &FvmStateParams) -> AppHashReport {
+    let component = |name: &str, value: String| AppHashComponent {
+        name: name.to_owned(),
+        value,
+    };
+    AppHashReport {
+        height,
+        app_hash: to_app_hash(state_params).to_string(),
+        components: vec![
+            component("state_root", state_params.state_root.to_string()),
+            component("timestamp", state_params.timestamp.0.to_string()),
+            component(
+                "network_version",
+                u32::from(state_params.network_version).to_string(),
+            ),
+            component("base_fee", state_params.base_fee.atto().to_string()),
+            component("circ_supply", state_params.circ_supply.atto().to_string()),
+            component("chain_id", state_params.chain_id.to_string()),
+            component("power_scale", state_params.power_scale.to_string()),
+        ],
+    }
+}
+
+/// Respond to the app hash components query.
+pub fn to_app_hash_query(
+    report: Option<AppHashReport>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(report);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the gas statistics query.
+pub fn to_gas_stats(
+    report: GasStatsReport,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = serde_json::to_vec(&report).context("failed to serialize gas statistics")?;
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the message trace query.
+pub fn to_msg_trace(
+    trace: Option<MessageTrace>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = serde_json::to_vec(&trace).context("failed to serialize message trace")?;
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
 /// Map to query results.
 pub fn to_query(ret: FvmQueryRet, block_height: BlockHeight) -> anyhow::Result<response::Query> {
     let exit_code = match ret {
-        FvmQueryRet::Ipld(None) | FvmQueryRet::ActorState(None) => ExitCode::USR_NOT_FOUND,
-        FvmQueryRet::Ipld(_) | FvmQueryRet::ActorState(_) => ExitCode::OK,
+        FvmQueryRet::Ipld(None)
+        | FvmQueryRet::ActorState(None)
+        | FvmQueryRet::ArchivedCheckpoint(None)
+        | FvmQueryRet::ReceiptProof(None) => ExitCode::USR_NOT_FOUND,
+        FvmQueryRet::Ipld(_)
+        | FvmQueryRet::ActorState(_)
+        | FvmQueryRet::ArchivedCheckpoint(_)
+        | FvmQueryRet::ReceiptProof(_) => ExitCode::OK,
         // For calls and estimates, the caller needs to look into the `value` field to see the real exit code;
         // the query itself is successful, even if the value represents a failure.
-        FvmQueryRet::Call(_) | FvmQueryRet::EstimateGas(_) => ExitCode::OK,
-        FvmQueryRet::StateParams(_) => ExitCode::OK,
+        FvmQueryRet::Call(_) | FvmQueryRet::EstimateGas(_) | FvmQueryRet::AccessList(_) => {
+            ExitCode::OK
+        }
+        FvmQueryRet::StateParams(_)
+        | FvmQueryRet::StakingSimulation(_)
+        | FvmQueryRet::DeadLetters(_) => ExitCode::OK,
     };
 
     // The return value has a `key` field which is supposed to be set to the data matched.
@@ -255,7 +497,10 @@ pub fn to_query(ret: FvmQueryRet, block_height: BlockHeight) -> anyhow::Result<r
     // but I assume the query sender has. Rather than repeat everything, I'll add the key
     // where it gives some extra information, like the actor ID, just to keep this option visible.
     let (key, value) = match ret {
-        FvmQueryRet::Ipld(None) | FvmQueryRet::ActorState(None) => (Vec::new(), Vec::new()),
+        FvmQueryRet::Ipld(None)
+        | FvmQueryRet::ActorState(None)
+        | FvmQueryRet::ArchivedCheckpoint(None)
+        | FvmQueryRet::ReceiptProof(None) => (Vec::new(), Vec::new()),
         FvmQueryRet::Ipld(Some(bz)) => (Vec::new(), bz),
         FvmQueryRet::ActorState(Some(x)) => {
             let (id, st) = *x;
@@ -280,10 +525,30 @@ pub fn to_query(ret: FvmQueryRet, block_height: BlockHeight) -> anyhow::Result<r
             let v = ipld_encode!(est);
             (Vec::new(), v)
         }
+        FvmQueryRet::AccessList(al) => {
+            let v = ipld_encode!(al);
+            (Vec::new(), v)
+        }
         FvmQueryRet::StateParams(sp) => {
             let v = ipld_encode!(sp);
             (Vec::new(), v)
         }
+        FvmQueryRet::ArchivedCheckpoint(Some(cp)) => {
+            let v = ipld_encode!(cp);
+            (Vec::new(), v)
+        }
+        FvmQueryRet::StakingSimulation(sim) => {
+            let v = ipld_encode!(sim);
+            (Vec::new(), v)
+        }
+        FvmQueryRet::ReceiptProof(Some(proof)) => {
+            let v = ipld_encode!(proof);
+            (Vec::new(), v)
+        }
+        FvmQueryRet::DeadLetters(letters) => {
+            let v = ipld_encode!(letters);
+            (Vec::new(), v)
+        }
     };
 
     // The height here is the height of the block that was committed, not in which the app hash appeared.
@@ -301,6 +566,45 @@ pub fn to_query(ret: FvmQueryRet, block_height: BlockHeight) -> anyhow::Result<r
     Ok(res)
 }
 
+/// Respond to the state-sync status query with the progress of the ongoing snapshot download, if any.
+///
+/// The value is JSON, because it's meant to be looked at by operators through the CometBFT RPC.
+pub fn to_state_sync_status(
+    progress: Option<DownloadProgress>,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let exit_code = if progress.is_some() {
+        ExitCode::OK
+    } else {
+        ExitCode::USR_NOT_FOUND
+    };
+    let value = serde_json::to_vec(&progress).context("failed to serialize download progress")?;
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        code: to_code(exit_code),
+        info: to_error_msg(exit_code).to_owned(),
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
+/// Respond to the capabilities query.
+pub fn to_capabilities(
+    capabilities: Capabilities,
+    block_height: BlockHeight,
+) -> anyhow::Result<response::Query> {
+    let value = ipld_encode!(capabilities);
+    let height = tendermint::block::Height::try_from(block_height).context("height too big")?;
+
+    Ok(response::Query {
+        value: value.into(),
+        height,
+        ..Default::default()
+    })
+}
+
 /// Project Genesis validators to Tendermint.
 pub fn to_validator_updates(
     validators: Vec<Validator<Power>>,
@@ -375,6 +679,8 @@ pub fn to_snapshot(snapshot: SnapshotItem) -> anyhow::Result<tendermint::abci::t
     let metadata = SnapshotMetadata {
         size: snapshot.manifest.size,
         state_params: snapshot.manifest.state_params,
+        compression: snapshot.manifest.compression,
+        chunk_size: snapshot.manifest.chunk_size,
     };
 
     Ok(tendermint::abci::types::Snapshot {
@@ -417,6 +723,8 @@ pub fn from_snapshot(
         checksum,
         state_params: metadata.state_params,
         version: offer.snapshot.format,
+        compression: metadata.compression,
+        chunk_size: metadata.chunk_size,
     };
 
     Ok(manifest)
@@ -448,13 +756,23 @@ pub fn to_app_hash(state_params: &FvmStateParams) -> tendermint::hash::AppHash {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use ethers_core::types as et;
+    use ethers_core::utils::keccak256;
+    use fendermint_vm_message::query::bloom_contains;
     use fendermint_vm_snapshot::SnapshotItem;
+    use fvm_ipld_encoding::IPLD_RAW;
+    use fvm_shared::address::Address;
     use fvm_shared::error::ExitCode;
+    use fvm_shared::event::{ActorEvent, Entry, Flags, StampedEvent};
     use tendermint::abci::request;
 
     use crate::tmconv::to_error_msg;
 
-    use super::{from_snapshot, to_app_hash, to_snapshot};
+    use super::{
+        accrue_logs_bloom, from_snapshot, to_app_hash, to_app_hash_report, to_events, to_snapshot,
+    };
 
     #[test]
     fn code_error_message() {
@@ -465,6 +783,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn logs_bloom_has_emitters_and_topics() {
+        let topic = [1u8; 32];
+        let event = StampedEvent::new(
+            100,
+            ActorEvent {
+                entries: vec![Entry {
+                    flags: Flags::FLAG_INDEXED_ALL,
+                    key: "t1".to_owned(),
+                    codec: IPLD_RAW,
+                    value: topic.to_vec(),
+                }],
+            },
+        );
+        let deleg = Address::new_delegated(10, &[2u8; 20]).unwrap();
+        let events = to_events("event", vec![event], HashMap::from([(100, deleg)]));
+
+        let mut bloom = et::Bloom::zero();
+        accrue_logs_bloom(&mut bloom, &events);
+
+        assert!(bloom_contains(&bloom, &Address::new_id(100).to_bytes()));
+        assert!(bloom_contains(&bloom, &deleg.to_bytes()));
+        assert!(bloom_contains(&bloom, &topic));
+        assert!(!bloom_contains(&bloom, &Address::new_id(101).to_bytes()));
+    }
+
+    #[test]
+    fn logs_bloom_has_native_event_topics() {
+        let event = StampedEvent::new(
+            100,
+            ActorEvent {
+                entries: vec![Entry {
+                    flags: Flags::FLAG_INDEXED_ALL,
+                    key: "governance".to_owned(),
+                    codec: IPLD_RAW,
+                    value: b"terminated".to_vec(),
+                }],
+            },
+        );
+        let events = to_events("event", vec![event], HashMap::new());
+
+        let mut bloom = et::Bloom::zero();
+        accrue_logs_bloom(&mut bloom, &events);
+
+        let mut name = [0u8; 32];
+        name[32 - 10..].copy_from_slice(b"terminated");
+
+        assert!(bloom_contains(&bloom, &keccak256("governance()")));
+        assert!(bloom_contains(&bloom, &name));
+    }
+
     #[quickcheck_macros::quickcheck]
     fn abci_snapshot_metadata(snapshot: SnapshotItem) {
         let abci_snapshot = to_snapshot(snapshot.clone()).unwrap();
@@ -475,4 +844,17 @@ mod tests {
         let manifest = from_snapshot(abci_offer).unwrap();
         assert_eq!(manifest, snapshot.manifest)
     }
+
+    #[quickcheck_macros::quickcheck]
+    fn app_hash_report_components(snapshot: SnapshotItem) {
+        let params = snapshot.manifest.state_params;
+        let report = to_app_hash_report(10, &params);
+        assert_eq!(report.app_hash, to_app_hash(&params).to_string());
+        assert_eq!(report.components[0].name, "state_root");
+        assert_eq!(report.components[0].value, params.state_root.to_string());
+
+        // Every field of the state parameters is accounted for.
+        let fields = serde_json::to_value(&params).unwrap();
+        assert_eq!(report.components.len(), fields.as_object().unwrap().len());
+    }
 }
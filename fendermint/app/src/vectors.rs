@@ -0,0 +1,305 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Record the blocks executed by the node as test vectors for the interpreter,
+//! in the format described in [fendermint_vm_interpreter::vectors].
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use cid::Cid;
+use fendermint_vm_genesis::{Power, Validator};
+use fendermint_vm_interpreter::fvm::state::{snapshot::Snapshot, FvmStateParams};
+use fendermint_vm_interpreter::vectors::{
+    BlockHashVector, BlockVector, ParentVector, PayloadVector, ReceiptVector, BLOCKS_FILE,
+    GENESIS_FILE, PARENT_FILE,
+};
+use fendermint_vm_topdown::breaker::ParentErrorKind;
+use fendermint_vm_topdown::proxy::{DynParentQueryProxy, ParentQueryProxy};
+use fvm_ipld_blockstore::Blockstore;
+use ipc_provider::manager::{GetBlockHashResult, TopDownQueryPayload};
+use ipc_sdk::cross::CrossMsg;
+use ipc_sdk::staking::StakingChangeRequest;
+use serde::Serialize;
+
+use crate::BlockHeight;
+
+/// Collects the transactions and receipts of the block being executed and appends
+/// the block to the vector on commit.
+///
+/// The files are written by a background thread, so the commit never waits for the disk.
+/// A vector is only useful if it's complete, so recording only starts if the node
+/// executes the genesis, and stops for good if anything fails to be written.
+#[derive(Clone)]
+pub struct VectorRecorder {
+    dir: PathBuf,
+    /// Stop after so many blocks; 0 means no limit.
+    max_blocks: u64,
+    /// Whether the answers of the parent go into a new vector.
+    record_parent: bool,
+    recording: Arc<Mutex<Recording>>,
+    lines: Sender<Line>,
+}
+
+#[derive(Default)]
+struct Recording {
+    started: bool,
+    stopped: bool,
+    recorded: u64,
+    block: Option<BlockVector>,
+}
+
+/// A line to append to one of the files of the vector.
+enum Line {
+    Block(BlockVector),
+    Parent(ParentVector),
+}
+
+impl VectorRecorder {
+    pub fn new(dir: PathBuf, max_blocks: u64) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create test vector dir {dir:?}"))?;
+
+        let record_parent = if dir.join(GENESIS_FILE).exists() {
+            tracing::warn!(
+                dir = dir.to_string_lossy().into_owned(),
+                "test vector exists; blocks are only recorded from genesis"
+            );
+            false
+        } else {
+            // The parent is queried before the genesis is executed, so this is where
+            // the answers left over from an earlier network are truncated.
+            File::create(dir.join(PARENT_FILE))?;
+            true
+        };
+
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        let (tx, rx) = mpsc::channel();
+
+        {
+            let dir = dir.clone();
+            let recording = recording.clone();
+            std::thread::Builder::new()
+                .name("test-vectors".to_owned())
+                .spawn(move || run_writer(dir, rx, recording))
+                .context("failed to spawn test vector writer")?;
+        }
+
+        Ok(Self {
+            dir,
+            max_blocks,
+            record_parent,
+            recording,
+            lines: tx,
+        })
+    }
+
+    /// Wrap the proxy to the parent so that its answers are recorded as well,
+    /// which is what lets the top-down messages of the blocks be replayed.
+    pub fn parent_proxy(&self, inner: DynParentQueryProxy) -> DynParentQueryProxy {
+        if !self.record_parent {
+            return inner;
+        }
+        Arc::new(RecordingParentProxy {
+            inner,
+            recording: self.recording.clone(),
+            lines: self.lines.clone(),
+        })
+    }
+
+    /// Write the state after genesis and start recording blocks.
+    pub async fn genesis<BS>(
+        &self,
+        store: BS,
+        state_params: FvmStateParams,
+        height: BlockHeight,
+    ) -> anyhow::Result<()>
+    where
+        BS: Blockstore + Send + 'static,
+    {
+        Snapshot::new(store, state_params, height)?
+            .write_car(self.dir.join(GENESIS_FILE))
+            .await
+            .context("failed to write genesis snapshot")?;
+
+        // Truncate any blocks left over from an earlier network.
+        File::create(self.dir.join(BLOCKS_FILE))?;
+
+        self.recording.lock().unwrap().started = true;
+
+        Ok(())
+    }
+
+    /// Start collecting a new block.
+    pub fn begin(&self, height: BlockHeight, timestamp: u64, block_hash: &[u8]) {
+        let mut guard = self.recording.lock().unwrap();
+        if !guard.started || guard.stopped {
+            return;
+        }
+        guard.block = Some(BlockVector {
+            height,
+            timestamp,
+            block_hash: hex::encode(block_hash),
+            txs: Vec::new(),
+            receipts: Vec::new(),
+            power_updates: Vec::new(),
+            state_root: Cid::default(),
+        });
+    }
+
+    /// Add a delivered transaction to the current block.
+    pub fn delivered(&self, tx: &[u8], receipt: Option<ReceiptVector>) {
+        if let Some(ref mut block) = self.recording.lock().unwrap().block {
+            block.txs.push(hex::encode(tx));
+            block.receipts.push(receipt);
+        }
+    }
+
+    /// Remember the power updates at the end of the current block.
+    pub fn ended(&self, power_updates: &[Validator<Power>]) {
+        if let Some(ref mut block) = self.recording.lock().unwrap().block {
+            block.power_updates = power_updates.to_vec();
+        }
+    }
+
+    /// Hand the committed block over to be appended to the vector.
+    pub fn commit(&self, state_root: Cid) {
+        let mut guard = self.recording.lock().unwrap();
+        let mut block = match guard.block.take() {
+            Some(block) => block,
+            None => return,
+        };
+        block.state_root = state_root;
+
+        guard.recorded += 1;
+        if self.max_blocks > 0 && guard.recorded >= self.max_blocks {
+            guard.stopped = true;
+        }
+
+        // If the writer is gone, it has already stopped the recording.
+        let _ = self.lines.send(Line::Block(block));
+    }
+}
+
+/// Append the lines to the files of the vector until a write fails or the recorder is dropped.
+fn run_writer(dir: PathBuf, lines: Receiver<Line>, recording: Arc<Mutex<Recording>>) {
+    for line in lines {
+        let res = match line {
+            Line::Block(block) => append(&dir.join(BLOCKS_FILE), &block),
+            Line::Parent(_) if recording.lock().unwrap().stopped => continue,
+            Line::Parent(answer) => append(&dir.join(PARENT_FILE), &answer),
+        };
+
+        if let Err(e) = res {
+            tracing::warn!(
+                error = format!("{e:#}"),
+                "failed to record test vector; recording stopped"
+            );
+            recording.lock().unwrap().stopped = true;
+            return;
+        }
+    }
+}
+
+fn append<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {path:?}"))?;
+
+    let mut buf = serde_json::to_vec(value)?;
+    buf.push(b'\n');
+    file.write_all(&buf)?;
+
+    Ok(())
+}
+
+/// Passes the queries on to the parent and records what it answered.
+struct RecordingParentProxy {
+    inner: DynParentQueryProxy,
+    recording: Arc<Mutex<Recording>>,
+    lines: Sender<Line>,
+}
+
+impl RecordingParentProxy {
+    /// Record a result or a null round; any other error isn't an answer, the query is just retried.
+    fn record<T, V>(
+        &self,
+        res: &anyhow::Result<T>,
+        to_vector: impl FnOnce(&T) -> anyhow::Result<V>,
+        to_answer: impl FnOnce(Option<V>) -> ParentVector,
+    ) {
+        if self.recording.lock().unwrap().stopped {
+            return;
+        }
+        let result = match res {
+            Ok(value) => match to_vector(value) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    tracing::warn!(
+                        error = format!("{e:#}"),
+                        "failed to encode parent answer for the test vector"
+                    );
+                    return;
+                }
+            },
+            Err(e) if ParentErrorKind::of(e) == ParentErrorKind::NullRound => None,
+            Err(_) => return,
+        };
+        let _ = self.lines.send(Line::Parent(to_answer(result)));
+    }
+}
+
+#[async_trait]
+impl ParentQueryProxy for RecordingParentProxy {
+    async fn get_chain_head_height(&self) -> anyhow::Result<BlockHeight> {
+        self.inner.get_chain_head_height().await
+    }
+
+    async fn get_genesis_epoch(&self) -> anyhow::Result<BlockHeight> {
+        let res = self.inner.get_genesis_epoch().await;
+        if let Ok(height) = res {
+            let _ = self
+                .lines
+                .send(Line::Parent(ParentVector::GenesisEpoch { height }));
+        }
+        res
+    }
+
+    async fn get_block_hash(&self, height: BlockHeight) -> anyhow::Result<GetBlockHashResult> {
+        let res = self.inner.get_block_hash(height).await;
+        self.record(
+            &res,
+            |r| Ok(BlockHashVector::from(r)),
+            |result| ParentVector::BlockHash { height, result },
+        );
+        res
+    }
+
+    async fn get_top_down_msgs(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<CrossMsg>>> {
+        let res = self.inner.get_top_down_msgs(height).await;
+        self.record(&res, PayloadVector::new, |result| {
+            ParentVector::TopDownMsgs { height, result }
+        });
+        res
+    }
+
+    async fn get_validator_changes(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<StakingChangeRequest>>> {
+        let res = self.inner.get_validator_changes(height).await;
+        self.record(&res, PayloadVector::new, |result| {
+            ParentVector::ValidatorChanges { height, result }
+        });
+        res
+    }
+}
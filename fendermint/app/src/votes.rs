@@ -0,0 +1,170 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Gossip of the parent blocks the validators have seen as final, through the IPLD Resolver,
+//! so that the proposer can pick a parent finality the others are going to accept.
+//!
+//! See [fendermint_vm_topdown::voting] for how the votes are counted.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use async_stm::atomically;
+use fendermint_crypto::SecretKey;
+use fendermint_vm_interpreter::chain::TopDownFinalityProvider;
+use fendermint_vm_topdown::voting::{SignedVote, VoteTally};
+use ipc_ipld_resolver::{Client as ResolverClient, Event as ResolverEvent};
+use ipc_sdk::subnet_id::SubnetID;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// Publish the latest parent block we have seen as final at regular intervals, counting our own vote too.
+///
+/// The vote is published again even if it didn't change, for the validators who weren't listening yet.
+pub async fn publish_votes(
+    provider: TopDownFinalityProvider,
+    client: ResolverClient,
+    subnet_id: SubnetID,
+    sk: SecretKey,
+    interval: Duration,
+) {
+    let votes = match provider.vote_tally() {
+        Some(votes) => votes,
+        None => return,
+    };
+    let subnet = subnet_id.to_string();
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let latest = atomically(|| match provider.latest_height_in_cache()? {
+            Some(height) => Ok(provider.block_hash(height)?.map(|hash| (height, hash))),
+            None => Ok(None),
+        })
+        .await;
+
+        // Nothing seen yet, or a null round, which can't be proposed anyway.
+        let (height, block_hash) = match latest {
+            Some(latest) => latest,
+            None => continue,
+        };
+
+        let vote = match SignedVote::sign(&sk, &subnet, height, block_hash) {
+            Ok(vote) => vote,
+            Err(e) => {
+                tracing::error!(error = format!("{e:#}"), "failed to sign parent vote");
+                continue;
+            }
+        };
+
+        votes
+            .receive_vote(
+                vote.public_key.clone(),
+                vote.height,
+                vote.block_hash.clone(),
+            )
+            .await;
+
+        let published = fvm_ipld_encoding::to_vec(&vote)
+            .context("failed to encode parent vote")
+            .and_then(|bz| client.publish_preemptive(subnet_id.clone(), bz));
+
+        if let Err(e) = published {
+            tracing::warn!(
+                height,
+                error = format!("{e:#}"),
+                "failed to publish parent vote"
+            );
+        }
+    }
+}
+
+/// Count the votes of the other validators as they arrive.
+pub async fn receive_votes(
+    mut events: Receiver<ResolverEvent>,
+    provider: TopDownFinalityProvider,
+    subnet_id: SubnetID,
+) {
+    let votes = match provider.vote_tally() {
+        Some(votes) => votes,
+        None => return,
+    };
+    let subnet = subnet_id.to_string();
+
+    loop {
+        match events.recv().await {
+            Ok(ResolverEvent::ReceivedPreemptive(from, data)) if from == subnet_id => {
+                if let Err(e) = receive_vote(votes, &subnet, &data).await {
+                    tracing::debug!(error = format!("{e:#}"), "ignoring parent vote");
+                }
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "parent vote gossip fell behind");
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Check the signature of a vote and add it to the tally.
+async fn receive_vote(votes: &VoteTally, subnet: &str, data: &[u8]) -> anyhow::Result<()> {
+    let vote: SignedVote =
+        fvm_ipld_encoding::from_slice(data).context("failed to decode parent vote")?;
+
+    vote.verify(subnet)?;
+
+    votes
+        .receive_vote(vote.public_key, vote.height, vote.block_hash)
+        .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_stm::atomically;
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_topdown::voting::{SignedVote, VoteTally};
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    use super::receive_vote;
+
+    #[tokio::test]
+    async fn counts_signed_votes_of_the_subnet() {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let sks = (0..3)
+            .map(|_| SecretKey::random(&mut rng))
+            .collect::<Vec<_>>();
+
+        let votes = VoteTally::new("");
+        let power_table = sks
+            .iter()
+            .map(|sk| (sk.public_key().serialize().to_vec(), 1))
+            .collect::<Vec<_>>();
+        atomically(|| votes.set_power_table(power_table.clone())).await;
+
+        let vote = |sk: &SecretKey, subnet: &str| {
+            let vote = SignedVote::sign(sk, subnet, 10, vec![1; 32]).unwrap();
+            fvm_ipld_encoding::to_vec(&vote).unwrap()
+        };
+
+        // Garbage and votes meant for another subnet are ignored.
+        assert!(receive_vote(&votes, "/r1", b"garbage").await.is_err());
+        for sk in sks.iter() {
+            assert!(receive_vote(&votes, "/r1", &vote(sk, "/r2")).await.is_err());
+        }
+        assert_eq!(
+            atomically(|| votes.find_quorum(10, |_, _| Ok(true))).await,
+            None
+        );
+
+        for sk in sks.iter() {
+            receive_vote(&votes, "/r1", &vote(sk, "/r1")).await.unwrap();
+        }
+        assert_eq!(
+            atomically(|| votes.find_quorum(10, |_, _| Ok(true))).await,
+            Some((10, vec![1; 32]))
+        );
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Notice when the chain stops committing blocks, and dump what the node knows at that moment,
+//! so that the operator doesn't have to catch the stall in the act to find out why it happened.
+//!
+//! Rust can't capture the stack of another thread or task, so instead of stack traces, the
+//! [`App`](crate::App) marks what it's doing while handling the consensus requests, and the dump
+//! lists these activities with how long they have been running; a stuck one stands out.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use async_stm::atomically;
+use fendermint_vm_interpreter::chain::TopDownFinalityProvider;
+use fendermint_vm_topdown::breaker::{CircuitBreaker, ParentHealth};
+use fendermint_vm_topdown::{IPCParentFinality, SubnetLabel};
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::Serialize;
+use tendermint_rpc::Url;
+
+use crate::proposals::{RecentRejections, RejectedProposal};
+use crate::BlockHeight;
+
+/// How long to wait for CometBFT to answer while collecting the diagnostics.
+const COMETBFT_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref STALLS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_watchdog_stalls_total",
+        "Number of times block production stalled long enough for the watchdog to dump diagnostics",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// The last committed block, and what the application is busy with.
+///
+/// Shared between the [`App`](crate::App), which updates it, and the [`Watchdog`].
+#[derive(Clone, Debug, Default)]
+pub struct BlockProgress(Arc<Mutex<ProgressState>>);
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    /// Height, app hash and time of the last commit.
+    last_commit: Option<(BlockHeight, String, Instant)>,
+    /// The activities in progress, by a sequence number.
+    activities: BTreeMap<u64, (&'static str, Option<BlockHeight>, Instant)>,
+    next_id: u64,
+}
+
+impl BlockProgress {
+    /// Record that a block has been committed.
+    pub fn committed(&self, height: BlockHeight, app_hash: String) {
+        self.0.lock().unwrap().last_commit = Some((height, app_hash, Instant::now()));
+    }
+
+    /// Mark the start of an activity, which lasts until the returned guard is dropped.
+    pub fn enter(&self, name: &'static str, height: Option<BlockHeight>) -> Activity {
+        let mut state = self.0.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.activities.insert(id, (name, height, Instant::now()));
+        Activity {
+            progress: self.clone(),
+            id,
+        }
+    }
+
+    /// Height of the last committed block and the time since it was committed.
+    pub fn last_commit(&self) -> Option<(BlockHeight, Duration)> {
+        self.0
+            .lock()
+            .unwrap()
+            .last_commit
+            .as_ref()
+            .map(|(h, _, t)| (*h, t.elapsed()))
+    }
+
+    fn snapshot(&self) -> (Option<CommitInfo>, Vec<ActivityInfo>) {
+        let state = self.0.lock().unwrap();
+        let last_commit = state
+            .last_commit
+            .as_ref()
+            .map(|(height, app_hash, t)| CommitInfo {
+                height: *height,
+                app_hash: app_hash.clone(),
+                secs_ago: t.elapsed().as_secs(),
+            });
+        let activities = state
+            .activities
+            .values()
+            .map(|(name, height, t)| ActivityInfo {
+                name,
+                height: *height,
+                running_secs: t.elapsed().as_secs(),
+            })
+            .collect();
+        (last_commit, activities)
+    }
+}
+
+/// An activity in progress, tracked until dropped.
+pub struct Activity {
+    progress: BlockProgress,
+    id: u64,
+}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        self.progress.0.lock().unwrap().activities.remove(&self.id);
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CommitInfo {
+    pub height: BlockHeight,
+    pub app_hash: String,
+    pub secs_ago: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ActivityInfo {
+    pub name: &'static str,
+    pub height: Option<BlockHeight>,
+    pub running_secs: u64,
+}
+
+/// The view of the parent chain used for the top-down finality.
+#[derive(Serialize, Debug, Clone)]
+pub struct TopDownInfo {
+    pub last_committed_finality: Option<IPCParentFinality>,
+    pub latest_cached_height: Option<BlockHeight>,
+    pub cached_blocks: BlockHeight,
+    pub parent_health: Option<ParentHealth>,
+}
+
+/// Everything the watchdog could find out about the node when block production stalled.
+#[derive(Serialize, Debug, Clone)]
+pub struct Diagnostics {
+    /// Seconds since the Unix epoch when the diagnostics were collected.
+    pub timestamp: u64,
+    /// Seconds since the last commit.
+    pub stalled_secs: u64,
+    /// The last block committed by the application.
+    pub last_commit: Option<CommitInfo>,
+    /// What the application is doing, and for how long.
+    pub activities: Vec<ActivityInfo>,
+    /// The latest proposals this node voted against, the latest first.
+    pub rejected_proposals: Vec<RejectedProposal>,
+    /// Top-down finality status, if it's enabled.
+    pub topdown: Option<TopDownInfo>,
+    /// The `status` of CometBFT, including its sync info.
+    pub cometbft_status: Option<serde_json::Value>,
+    /// The size of the CometBFT mempool.
+    pub mempool: Option<serde_json::Value>,
+    /// Anything that couldn't be collected, and why.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// Dump diagnostics if no block has been committed for this long.
+    pub stall_timeout: Duration,
+    /// How often to check for progress.
+    pub check_interval: Duration,
+    /// Directory to write the diagnostics to.
+    pub dump_dir: PathBuf,
+    /// Label of the stall metric.
+    pub subnet_id: SubnetLabel,
+}
+
+/// Watch the progress of the application, and dump diagnostics once per stall.
+pub struct Watchdog {
+    config: WatchdogConfig,
+    progress: BlockProgress,
+    rejections: RecentRejections,
+    topdown: TopDownFinalityProvider,
+    parent: Option<CircuitBreaker>,
+    cometbft_url: Url,
+    /// The height at which diagnostics were last dumped, so a stall is only dumped once.
+    dumped_at: Option<BlockHeight>,
+}
+
+impl Watchdog {
+    pub fn new(
+        config: WatchdogConfig,
+        progress: BlockProgress,
+        rejections: RecentRejections,
+        topdown: TopDownFinalityProvider,
+        parent: Option<CircuitBreaker>,
+        cometbft_url: Url,
+    ) -> Self {
+        Self {
+            config,
+            progress,
+            rejections,
+            topdown,
+            parent,
+            cometbft_url,
+            dumped_at: None,
+        }
+    }
+
+    /// Check the progress periodically, forever.
+    ///
+    /// The watch starts at the first commit, so that a node which is still
+    /// syncing its state from a snapshot isn't reported as stalled.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.config.check_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Some(height) = self.stalled_at() {
+                self.dumped_at = Some(height);
+                self.dump().await;
+            }
+        }
+    }
+
+    /// The height the chain is stuck at, if it stalled and it hasn't been dumped yet.
+    fn stalled_at(&self) -> Option<BlockHeight> {
+        let (height, elapsed) = self.progress.last_commit()?;
+        let is_stalled = elapsed >= self.config.stall_timeout && self.dumped_at != Some(height);
+        is_stalled.then_some(height)
+    }
+
+    async fn dump(&self) {
+        STALLS.with_label_values(&[&self.config.subnet_id]).inc();
+        let diagnostics = self.collect().await;
+
+        let path = self.config.dump_dir.join(format!(
+            "stall-{}-{}.json",
+            diagnostics
+                .last_commit
+                .as_ref()
+                .map(|c| c.height)
+                .unwrap_or_default(),
+            diagnostics.timestamp
+        ));
+
+        let written = write_json(&path, &diagnostics);
+
+        tracing::error!(
+            last_height = diagnostics.last_commit.as_ref().map(|c| c.height),
+            stalled_secs = diagnostics.stalled_secs,
+            activities = ?diagnostics.activities,
+            rejected_proposals = diagnostics.rejected_proposals.len(),
+            path = path.to_string_lossy().into_owned(),
+            write_error = written.err().map(|e| format!("{e:#}")),
+            "block production stalled; dumped diagnostics"
+        );
+    }
+
+    /// Gather the diagnostics, carrying on if some parts aren't available.
+    pub async fn collect(&self) -> Diagnostics {
+        let (last_commit, activities) = self.progress.snapshot();
+        let mut errors = Vec::new();
+
+        let topdown = if self.topdown.is_enabled() {
+            let (last_committed_finality, latest_cached_height, cached_blocks) = atomically(|| {
+                Ok((
+                    self.topdown.last_committed_finality()?,
+                    self.topdown.latest_height_in_cache()?,
+                    self.topdown.cached_blocks()?,
+                ))
+            })
+            .await;
+            Some(TopDownInfo {
+                last_committed_finality,
+                latest_cached_height,
+                cached_blocks,
+                parent_health: self.parent.as_ref().map(|b| b.health()),
+            })
+        } else {
+            None
+        };
+
+        let mut cometbft = |method: &str, res: anyhow::Result<serde_json::Value>| match res {
+            Ok(v) => Some(v),
+            Err(e) => {
+                errors.push(format!("failed to get CometBFT {method}: {e:#}"));
+                None
+            }
+        };
+        let cometbft_status = cometbft("status", self.cometbft_get("status").await);
+        let mempool = cometbft(
+            "num_unconfirmed_txs",
+            self.cometbft_get("num_unconfirmed_txs").await,
+        );
+
+        Diagnostics {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            stalled_secs: last_commit.as_ref().map(|c| c.secs_ago).unwrap_or_default(),
+            last_commit,
+            activities,
+            rejected_proposals: self.rejections.list(),
+            topdown,
+            cometbft_status,
+            mempool,
+            errors,
+        }
+    }
+
+    /// Call a CometBFT RPC method without parameters, returning its result.
+    ///
+    /// CometBFT itself might be what's stuck, so the call has a timeout.
+    async fn cometbft_get(&self, method: &str) -> anyhow::Result<serde_json::Value> {
+        let url = format!(
+            "{}/{method}",
+            self.cometbft_url.to_string().trim_end_matches('/')
+        );
+        let mut res: serde_json::Value = reqwest::Client::new()
+            .get(url)
+            .timeout(COMETBFT_TIMEOUT)
+            .send()
+            .await
+            .context("failed to send request")?
+            .json()
+            .await
+            .context("failed to parse response")?;
+
+        match res.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(anyhow::anyhow!("no result in response: {res}")),
+        }
+    }
+}
+
+fn write_json(path: &std::path::Path, diagnostics: &Diagnostics) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {dir:?}"))?;
+    }
+    let json = serde_json::to_vec_pretty(diagnostics).context("failed to serialize")?;
+    std::fs::write(path, json).with_context(|| format!("failed to write {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::BlockProgress;
+
+    #[test]
+    fn activities_end_when_dropped() {
+        let progress = BlockProgress::default();
+        assert!(progress.last_commit().is_none());
+
+        let begin = progress.enter("begin_block", Some(2));
+        {
+            let _query = progress.enter("query", None);
+            assert_eq!(progress.snapshot().1.len(), 2);
+        }
+        let (_, activities) = progress.snapshot();
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].name, "begin_block");
+        drop(begin);
+        assert!(progress.snapshot().1.is_empty());
+
+        progress.committed(2, "ABCD".to_owned());
+        let (height, elapsed) = progress.last_commit().unwrap();
+        assert_eq!(height, 2);
+        assert!(elapsed < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use fendermint_rpc::client::{FendermintClient, TendermintClient};
+use fendermint_rpc::query::{QueryClient, QueryResponse};
+use fendermint_rpc::response::decode_fevm_invoke;
+use fendermint_vm_message::query::{feature, FvmQueryHeight, STATE_SYNC_STATUS_PATH};
+use fvm_shared::address::Address;
+use fvm_shared::error::ExitCode;
+use fvm_shared::message::Message;
+use tendermint_rpc::Client as TendermintRpcClient;
+
+use crate::codec;
+use crate::types::{
+    ActorInfo, Capabilities, CheckpointStatus, SnapshotStatus, StateParams, TopDownStatus,
+};
+
+/// Typed client for the Fendermint specific queries.
+///
+/// Every method returns the height at which the query was performed along with the result.
+#[derive(Clone)]
+pub struct Client<C> {
+    inner: FendermintClient<C>,
+}
+
+impl<C> Client<C> {
+    pub fn new(inner: FendermintClient<C>) -> Self {
+        Self { inner }
+    }
+
+    /// The underlying query client, for anything which isn't covered here.
+    pub fn inner(&self) -> &FendermintClient<C> {
+        &self.inner
+    }
+}
+
+impl<C> Client<C>
+where
+    C: TendermintRpcClient + Sync + Send,
+{
+    /// Versions and optional features of the node, if it's new enough to report them.
+    pub async fn capabilities(&self) -> anyhow::Result<Option<Capabilities>> {
+        self.inner.capabilities().await
+    }
+
+    /// Slowly changing state parameters, such as the state root and the base fee.
+    pub async fn state_params(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<StateParams>> {
+        self.inner.state_params(height).await
+    }
+
+    /// State of an actor, if it exists.
+    pub async fn actor_state(
+        &self,
+        address: &Address,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<ActorInfo>>> {
+        let res = self.inner.actor_state(address, height).await?;
+        Ok(QueryResponse {
+            height: res.height,
+            value: res.value.map(|(id, state)| ActorInfo { id, state }),
+        })
+    }
+
+    /// The last parent block finalized in the subnet.
+    pub async fn topdown_status(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<TopDownStatus>> {
+        let res = self
+            .gateway_call(codec::topdown_status_call()?, height)
+            .await?;
+        Ok(QueryResponse {
+            height: res.height,
+            value: codec::decode_topdown_status(&res.value)?,
+        })
+    }
+
+    /// The checkpoint period and the checkpoints still waiting for signatures.
+    pub async fn checkpoints(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<CheckpointStatus>> {
+        let res = self
+            .gateway_call(codec::checkpoint_period_call()?, height)
+            .await?;
+        let period = codec::decode_checkpoint_period(&res.value)?;
+
+        // Query the rest at the same height, so the results are consistent.
+        let height = res.height;
+        let res = self
+            .gateway_call(
+                codec::incomplete_checkpoints_call()?,
+                FvmQueryHeight::from(height.value()),
+            )
+            .await?;
+        let incomplete = codec::decode_incomplete_checkpoints(&res.value)?;
+
+        Ok(QueryResponse {
+            height,
+            value: CheckpointStatus { period, incomplete },
+        })
+    }
+
+    /// Whether the node serves snapshots, and the progress of the state sync if it's restoring one.
+    ///
+    /// Nodes which don't report their capabilities are assumed to have snapshots disabled.
+    pub async fn snapshot_status(&self) -> anyhow::Result<SnapshotStatus> {
+        let features = self
+            .capabilities()
+            .await?
+            .map(|c| c.features)
+            .unwrap_or_default();
+
+        let has_feature = |f: &str| features.iter().any(|x| x == f);
+
+        let state_sync = if has_feature(feature::STATE_SYNC_STATUS) {
+            let res = self
+                .inner
+                .underlying()
+                .abci_query(
+                    Some(STATE_SYNC_STATUS_PATH.to_owned()),
+                    Vec::new(),
+                    None,
+                    false,
+                )
+                .await
+                .context("failed to query state sync status")?;
+
+            if res.code.value() == ExitCode::USR_NOT_FOUND.value() {
+                None
+            } else if res.code.is_err() {
+                return Err(anyhow!(
+                    "state sync status returned non-zero exit code: {}",
+                    res.code.value()
+                ));
+            } else {
+                Some(codec::decode_state_sync_progress(&res.value)?)
+            }
+        } else {
+            None
+        };
+
+        Ok(SnapshotStatus {
+            enabled: has_feature(feature::SNAPSHOTS),
+            state_sync,
+        })
+    }
+
+    /// Run a read-only call on the IPC gateway and return the EVM return data.
+    async fn gateway_call(
+        &self,
+        msg: Message,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Vec<u8>>> {
+        let res = self.inner.call(msg, height).await?;
+
+        if res.value.code.is_err() {
+            return Err(anyhow!(
+                "gateway call failed with exit code {}: {}",
+                res.value.code.value(),
+                res.value.info
+            ));
+        }
+
+        let value = decode_fevm_invoke(&res.value).context("failed to decode gateway return")?;
+
+        Ok(QueryResponse {
+            height: res.height,
+            value,
+        })
+    }
+}
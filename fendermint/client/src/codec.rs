@@ -0,0 +1,145 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Encoding of the queries and decoding of their results, independent of how they are sent.
+//!
+//! Every query is sent to the CometBFT `abci_query` endpoint; the functions here produce
+//! the `data` argument and parse the `key` and `value` fields of the response. The `path`
+//! is empty, except for the [`STATE_SYNC_STATUS_PATH`], which takes no `data`.
+
+use anyhow::{anyhow, Context};
+use ethers::abi::{AbiDecode, AbiEncode};
+use fendermint_vm_actor_interface::{evm, ipc::GATEWAY_ACTOR_ID, system::SYSTEM_ACTOR_ADDR};
+use fendermint_vm_message::query::FvmQuery;
+use fvm_ipld_encoding::{BytesDe, BytesSer, RawBytes};
+use fvm_shared::{address::Address, message::Message, ActorID};
+use ipc_actors_abis::gateway_getter_facet as getter;
+
+use crate::types::{
+    ActorInfo, ActorState, Capabilities, Checkpoint, StateParams, StateSyncProgress, TopDownStatus,
+};
+
+pub use fendermint_vm_message::query::STATE_SYNC_STATUS_PATH;
+
+/// Encode a query into the `data` argument of `abci_query`.
+pub fn encode_query(query: &FvmQuery) -> anyhow::Result<Vec<u8>> {
+    fvm_ipld_encoding::to_vec(query).context("failed to encode query")
+}
+
+/// A read-only message calling a method of the IPC gateway contract, to be sent as [`FvmQuery::Call`].
+pub fn gateway_call<C: AbiEncode>(call: C) -> anyhow::Result<Message> {
+    let calldata = RawBytes::serialize(BytesSer(&call.encode()))?;
+    Ok(Message {
+        version: Default::default(),
+        // The system actor is allowed to call anything, and doesn't need a balance.
+        from: SYSTEM_ACTOR_ADDR,
+        to: Address::new_id(GATEWAY_ACTOR_ID),
+        sequence: 0,
+        value: Default::default(),
+        method_num: evm::Method::InvokeContract as u64,
+        params: calldata,
+        // Zero means the block gas limit.
+        gas_limit: 0,
+        gas_fee_cap: Default::default(),
+        gas_premium: Default::default(),
+    })
+}
+
+/// Call to get the last parent block finalized by the gateway.
+pub fn topdown_status_call() -> anyhow::Result<Message> {
+    gateway_call(getter::GetLatestParentFinalityCall)
+}
+
+/// Call to get the checkpoint period of the subnet.
+pub fn checkpoint_period_call() -> anyhow::Result<Message> {
+    gateway_call(getter::BottomUpCheckPeriodCall)
+}
+
+/// Call to get the checkpoints which are still collecting signatures.
+pub fn incomplete_checkpoints_call() -> anyhow::Result<Message> {
+    gateway_call(getter::GetIncompleteCheckpointsCall)
+}
+
+/// Parse the return data of the [`topdown_status_call`] call.
+pub fn decode_topdown_status(return_data: &[u8]) -> anyhow::Result<TopDownStatus> {
+    let ret = getter::GetLatestParentFinalityReturn::decode(return_data)
+        .context("failed to decode parent finality")?;
+    Ok(ret.0.into())
+}
+
+/// Parse the return data of the [`checkpoint_period_call`] call.
+pub fn decode_checkpoint_period(return_data: &[u8]) -> anyhow::Result<u64> {
+    let ret = getter::BottomUpCheckPeriodReturn::decode(return_data)
+        .context("failed to decode checkpoint period")?;
+    Ok(ret.0)
+}
+
+/// Parse the return data of the [`incomplete_checkpoints_call`] call.
+pub fn decode_incomplete_checkpoints(return_data: &[u8]) -> anyhow::Result<Vec<Checkpoint>> {
+    let ret = getter::GetIncompleteCheckpointsReturn::decode(return_data)
+        .context("failed to decode incomplete checkpoints")?;
+    Ok(ret.0.into_iter().map(Checkpoint::from).collect())
+}
+
+/// Parse the `value` of a [`FvmQuery::StateParams`] response.
+pub fn decode_state_params(value: &[u8]) -> anyhow::Result<StateParams> {
+    fvm_ipld_encoding::from_slice(value).context("failed to decode state params")
+}
+
+/// Parse the `value` of a [`FvmQuery::Capabilities`] response.
+pub fn decode_capabilities(value: &[u8]) -> anyhow::Result<Capabilities> {
+    fvm_ipld_encoding::from_slice(value).context("failed to decode capabilities")
+}
+
+/// Parse the `key` and `value` of a [`FvmQuery::ActorState`] response.
+pub fn decode_actor_state(key: &[u8], value: &[u8]) -> anyhow::Result<ActorInfo> {
+    let id: ActorID = fvm_ipld_encoding::from_slice(key).context("failed to decode actor ID")?;
+    let state: ActorState =
+        fvm_ipld_encoding::from_slice(value).context("failed to decode actor state")?;
+    Ok(ActorInfo { id, state })
+}
+
+/// Parse the `value` of a response to the [`STATE_SYNC_STATUS_PATH`].
+pub fn decode_state_sync_progress(value: &[u8]) -> anyhow::Result<StateSyncProgress> {
+    serde_json::from_slice(value).context("failed to decode state sync progress")
+}
+
+/// Extract the EVM return data from the base64 decoded `data` field of a call result,
+/// which is IPLD encoded bytes.
+pub fn decode_return_data(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    fvm_ipld_encoding::from_slice::<BytesDe>(data)
+        .map(|bz| bz.0)
+        .map_err(|e| anyhow!("failed to decode return data: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+    use ethers::types as et;
+    use ipc_actors_abis::gateway_getter_facet as getter;
+
+    use super::{decode_checkpoint_period, decode_topdown_status};
+
+    #[test]
+    fn decode_gateway_returns() {
+        let finality = getter::ParentFinality {
+            height: et::U256::from(1234),
+            block_hash: [1u8; 32],
+        };
+        let bz = getter::GetLatestParentFinalityReturn(finality).encode();
+        let status = decode_topdown_status(&bz).unwrap();
+        assert_eq!(status.height, 1234);
+        assert_eq!(status.block_hash, et::H256([1u8; 32]));
+
+        // The JSON form is what SDKs get to see.
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["height"], 1234);
+        assert_eq!(json["block_hash"], format!("0x{}", hex::encode([1u8; 32])));
+
+        let bz = getter::BottomUpCheckPeriodReturn(10).encode();
+        assert_eq!(decode_checkpoint_period(&bz).unwrap(), 10);
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Typed access to the parts of Fendermint which the Ethereum API doesn't cover,
+//! such as the IPC specific state of the subnet and the status of the node.
+//!
+//! The results are plain structs which serialize to JSON in a stable form, so they can
+//! be passed on to SDKs and dashboards as-is.
+//!
+//! The [`codec`] module has no dependency on the transport, and compiles to `wasm32`
+//! with the default features turned off.
+
+pub mod codec;
+pub mod types;
+
+#[cfg(feature = "client")]
+mod client;
+
+#[cfg(feature = "client")]
+pub use client::Client;
@@ -0,0 +1,102 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Results of the queries in a form that can be serialized to JSON as-is,
+//! with numbers that fit into JavaScript and hashes and addresses in hex.
+
+use ethers::types as et;
+use fvm_shared::ActorID;
+use ipc_actors_abis::gateway_getter_facet as getter;
+use serde::{Deserialize, Serialize};
+
+pub use fendermint_vm_message::query::{ActorState, Capabilities, StateParams};
+
+/// The state of an actor along with its ID.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ActorInfo {
+    pub id: ActorID,
+    #[serde(flatten)]
+    pub state: ActorState,
+}
+
+/// The latest finalized block of the parent subnet, as recorded by the gateway of the child.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TopDownStatus {
+    /// Height of the parent block which was last finalized.
+    pub height: u64,
+    /// Hash of the parent block which was last finalized.
+    pub block_hash: et::H256,
+}
+
+impl From<getter::ParentFinality> for TopDownStatus {
+    fn from(value: getter::ParentFinality) -> Self {
+        Self {
+            height: value.height.as_u64(),
+            block_hash: et::H256(value.block_hash),
+        }
+    }
+}
+
+/// Subnet ID in the form the IPC contracts use.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubnetId {
+    /// Chain ID of the root network.
+    pub root: u64,
+    /// Addresses of the subnet actors on the path from the root.
+    pub route: Vec<et::Address>,
+}
+
+/// A bottom-up checkpoint created by the subnet, to be signed by the validators.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub subnet_id: SubnetId,
+    pub block_height: u64,
+    pub block_hash: et::H256,
+    /// Configuration number of the validator set which will sign the next checkpoint.
+    pub next_configuration_number: u64,
+    pub cross_messages_hash: et::H256,
+}
+
+impl From<getter::BottomUpCheckpoint> for Checkpoint {
+    fn from(value: getter::BottomUpCheckpoint) -> Self {
+        Self {
+            subnet_id: SubnetId {
+                root: value.subnet_id.root,
+                route: value.subnet_id.route,
+            },
+            block_height: value.block_height,
+            block_hash: et::H256(value.block_hash),
+            next_configuration_number: value.next_configuration_number,
+            cross_messages_hash: et::H256(value.cross_messages_hash),
+        }
+    }
+}
+
+/// The checkpointing status of the subnet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointStatus {
+    /// Number of blocks between checkpoints.
+    pub period: u64,
+    /// Checkpoints which haven't collected enough signatures yet.
+    pub incomplete: Vec<Checkpoint>,
+}
+
+/// Progress of the snapshot download while the node is catching up through state sync.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateSyncProgress {
+    /// Height of the snapshot being downloaded.
+    pub block_height: u64,
+    pub chunks_received: u32,
+    pub chunks_total: u32,
+    pub bytes_received: u64,
+    pub bytes_total: u64,
+}
+
+/// Snapshot related status of the node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotStatus {
+    /// Whether the node offers snapshots to its peers.
+    pub enabled: bool,
+    /// The ongoing download, if the node is restoring from a snapshot.
+    pub state_sync: Option<StateSyncProgress>,
+}
@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.19;
+
+/// @title Create2Deployer
+/// @notice Deploy contracts with CREATE2, so they get the same address on every subnet
+///         which has this deployer, regardless of the nonce of the account deploying them.
+contract Create2Deployer {
+    event Deployed(address indexed addr, bytes32 indexed salt);
+
+    /// @notice Deploy the contract with the given init code, forwarding any value sent.
+    function deploy(bytes32 salt, bytes calldata initCode) external payable returns (address addr) {
+        bytes memory code = initCode;
+        assembly {
+            addr := create2(callvalue(), add(code, 0x20), mload(code), salt)
+        }
+        require(addr != address(0), "Create2Deployer: deployment failed");
+        emit Deployed(addr, salt);
+    }
+
+    /// @notice The address a contract would be deployed at with the given salt and init code hash.
+    function computeAddress(bytes32 salt, bytes32 initCodeHash) external view returns (address) {
+        bytes32 hash = keccak256(abi.encodePacked(bytes1(0xff), address(this), salt, initCodeHash));
+        return address(uint160(uint256(hash)));
+    }
+}
@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.19;
+
+/// @title Multicall3
+/// @notice Aggregate the results of multiple calls into one, e.g. to read many values at the same height.
+/// @dev A subset of https://github.com/mds1/multicall with the same ABI for the functions it has.
+contract Multicall3 {
+    struct Call3 {
+        address target;
+        bool allowFailure;
+        bytes callData;
+    }
+
+    struct Call3Value {
+        address target;
+        bool allowFailure;
+        uint256 value;
+        bytes callData;
+    }
+
+    struct Result {
+        bool success;
+        bytes returnData;
+    }
+
+    /// @notice Call each target in order, reverting if a call which doesn't allow failure fails.
+    function aggregate3(Call3[] calldata calls) public payable returns (Result[] memory returnData) {
+        uint256 length = calls.length;
+        returnData = new Result[](length);
+        for (uint256 i = 0; i < length; i++) {
+            Call3 calldata call = calls[i];
+            Result memory result = returnData[i];
+            (result.success, result.returnData) = call.target.call(call.callData);
+            require(call.allowFailure || result.success, "Multicall3: call failed");
+        }
+    }
+
+    /// @notice Like `aggregate3`, sending a value with each call; the values must add up to the one sent.
+    function aggregate3Value(Call3Value[] calldata calls) public payable returns (Result[] memory returnData) {
+        uint256 length = calls.length;
+        uint256 valAccumulator;
+        returnData = new Result[](length);
+        for (uint256 i = 0; i < length; i++) {
+            Call3Value calldata call = calls[i];
+            Result memory result = returnData[i];
+            valAccumulator += call.value;
+            (result.success, result.returnData) = call.target.call{value: call.value}(call.callData);
+            require(call.allowFailure || result.success, "Multicall3: call failed");
+        }
+        require(msg.value == valAccumulator, "Multicall3: value mismatch");
+    }
+
+    function getBlockNumber() public view returns (uint256 blockNumber) {
+        blockNumber = block.number;
+    }
+
+    function getCurrentBlockTimestamp() public view returns (uint256 timestamp) {
+        timestamp = block.timestamp;
+    }
+
+    function getChainId() public view returns (uint256 chainid) {
+        chainid = block.chainid;
+    }
+
+    function getEthBalance(address addr) public view returns (uint256 balance) {
+        balance = addr.balance;
+    }
+}
@@ -15,6 +15,11 @@ pub fn normalize_public_key(pk: PublicKey) -> PublicKey {
     PublicKey::try_from(aff).unwrap()
 }
 
+/// Verify a signature over a 32 byte digest, made with [SecretKey::sign].
+pub fn verify(bz: &[u8; 32], signature: &Signature, public_key: &PublicKey) -> bool {
+    libsecp256k1::verify(&libsecp256k1::Message::parse(bz), signature, public_key)
+}
+
 /// Wrapper around a [libsecp256k1::SecretKey] that implements [Zeroize].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SecretKey(libsecp256k1::SecretKey);
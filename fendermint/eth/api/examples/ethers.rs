@@ -398,6 +398,24 @@ where
         |b| b.is_some() && b.as_ref().map(|b| b.parent_hash) == Some(bh),
     )?;
 
+    // Blocks are final as soon as they are committed, so the tags point at the same block,
+    // unless a new one was committed in between.
+    let finalized = request(
+        "eth_getBlockByNumber @ finalized",
+        provider
+            .get_block(BlockId::Number(BlockNumber::Finalized))
+            .await,
+        |b| b.is_some() && b.as_ref().map(|b| b.number).flatten().is_some(),
+    )?;
+
+    let finalized = finalized.unwrap().number;
+
+    request(
+        "eth_getBlockByNumber @ safe",
+        provider.get_block(BlockId::Number(BlockNumber::Safe)).await,
+        |b| b.as_ref().and_then(|b| b.number) >= finalized,
+    )?;
+
     let base_fee = request("eth_gasPrice", provider.get_gas_price().await, |id| {
         !id.is_zero()
     })?;
@@ -531,6 +549,12 @@ where
         |gas: &U256| !gas.is_zero(),
     )?;
 
+    request(
+        "eth_createAccessList",
+        provider.create_access_list(&probe_tx, None).await,
+        |al| !al.gas_used.is_zero(),
+    )?;
+
     request(
         "eth_maxPriorityFeePerGas",
         provider.request("eth_maxPriorityFeePerGas", ()).await,
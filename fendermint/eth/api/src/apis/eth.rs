@@ -6,7 +6,7 @@
 // * https://github.com/filecoin-project/lotus/blob/v1.23.1-rc2/api/api_full.go#L783
 // * https://github.com/filecoin-project/lotus/blob/v1.23.1-rc2/node/impl/full/eth.go
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Context;
 use ethers_core::types as et;
@@ -18,7 +18,7 @@ use fendermint_rpc::response::{decode_fevm_invoke, decode_fevm_return_data};
 use fendermint_vm_actor_interface::eam::{EthAddress, EAM_ACTOR_ADDR};
 use fendermint_vm_actor_interface::evm;
 use fendermint_vm_message::chain::ChainMessage;
-use fendermint_vm_message::query::FvmQueryHeight;
+use fendermint_vm_message::query::{feature, FvmQueryHeight, MAX_LOGS_BLOOM_RANGE};
 use fendermint_vm_message::signed::SignedMessage;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
@@ -36,13 +36,14 @@ use tendermint_rpc::{
 
 use crate::conv::from_eth::to_fvm_message;
 use crate::conv::from_tm::{self, msg_hash, to_chain_message, to_cumulative, to_eth_block_zero};
-use crate::error::error_with_data;
-use crate::filters::{matches_topics, FilterId, FilterKind, FilterRecords};
+use crate::error::{error_with_data, limit_exceeded};
+use crate::filters::{matches_bloom, matches_topics, FilterId, FilterKind, FilterRecords};
+use crate::paging::Cursor;
 use crate::{
     conv::{
         from_eth::to_fvm_address,
-        from_fvm::to_eth_tokens,
-        from_tm::{to_eth_receipt, to_eth_transaction},
+        from_fvm::{to_eth_address, to_eth_tokens},
+        from_tm::{resolve_eth_transaction, to_eth_receipt, to_eth_transaction},
     },
     error, JsonRpcData, JsonRpcResult,
 };
@@ -137,8 +138,7 @@ where
             let txs_results = block_results.txs_results.unwrap_or_default();
 
             for (tx, txres) in block.data().iter().zip(txs_results) {
-                let msg = fvm_ipld_encoding::from_slice::<ChainMessage>(tx)
-                    .context("failed to decode tx as ChainMessage")?;
+                let msg = to_chain_message(tx, data.max_decompressed_size().await?)?;
 
                 if let ChainMessage::Signed(msg) = msg {
                     let premium = crate::gas::effective_gas_premium(&msg.message, base_fee);
@@ -237,8 +237,7 @@ where
 
             let mut premiums = Vec::new();
             for (tx, txres) in block.data().iter().zip(txs_results) {
-                let msg = fvm_ipld_encoding::from_slice::<ChainMessage>(tx)
-                    .context("failed to decode tx as ChainMessage")?;
+                let msg = to_chain_message(tx, data.max_decompressed_size().await?)?;
 
                 if let ChainMessage::Signed(msg) = msg {
                     let premium = crate::gas::effective_gas_premium(&msg.message, base_fee);
@@ -323,8 +322,14 @@ where
     C: Client + Sync + Send,
 {
     match data.block_by_hash_opt(block_hash).await? {
-        Some(block) if from_tm::is_block_zero(&block) => Ok(Some(to_eth_block_zero(block)?)),
-        Some(block) => data.enrich_block(block, full_tx).await.map(Some),
+        Some(block) if from_tm::is_block_zero(&block) => Ok(Some(to_eth_block_zero(
+            block,
+            data.eth_block_hash_version().await?,
+        )?)),
+        Some(block) => {
+            let bundle = data.block_bundle(block).await?;
+            data.enrich_block(&bundle, full_tx).await.map(Some)
+        }
         None => Ok(None),
     }
 }
@@ -337,13 +342,17 @@ pub async fn get_block_by_number<C>(
 where
     C: Client + Sync + Send,
 {
-    match data.block_by_height(block_number).await? {
-        block if block.header().height.value() > 0 => {
-            data.enrich_block(block, full_tx).await.map(Some)
+    if let et::BlockNumber::Number(height) = block_number {
+        if height.is_zero() {
+            return Ok(Some(to_eth_block_zero(
+                from_tm::BLOCK_ZERO.clone(),
+                data.eth_block_hash_version().await?,
+            )?));
         }
-        block if from_tm::is_block_zero(&block) => Ok(Some(to_eth_block_zero(block)?)),
-        _ => Ok(None),
     }
+    let height = data.block_height(block_number).await?;
+    let bundle = data.block_bundle_by_height(height).await?;
+    data.enrich_block(&bundle, full_tx).await.map(Some)
 }
 
 /// Returns the number of transactions in a block matching the given block number.
@@ -410,7 +419,7 @@ where
     C: Client + Sync + Send,
 {
     if let Some(res) = data.tx_by_hash(tx_hash).await? {
-        let msg = to_chain_message(&res.tx)?;
+        let msg = to_chain_message(&res.tx, data.max_decompressed_size().await?)?;
 
         if let ChainMessage::Signed(msg) = msg {
             let header: header::Response = data.tm().header(res.height).await?;
@@ -420,9 +429,14 @@ where
                 .await?;
             let chain_id = ChainID::from(sp.value.chain_id);
             let hash = msg_hash(&res.tx_result.events, &res.tx);
+            let fvm_msg = msg.message.clone();
             let mut tx = to_eth_transaction(msg, chain_id, hash)?;
+            resolve_eth_transaction(&data.addr_cache, &fvm_msg, &mut tx).await?;
             tx.transaction_index = Some(et::U64::from(res.index));
-            tx.block_hash = Some(et::H256::from_slice(header.header.hash().as_bytes()));
+            tx.block_hash = Some(from_tm::to_eth_block_hash(
+                data.eth_block_hash_version().await?,
+                &header.header.hash(),
+            ));
             tx.block_number = Some(et::U64::from(res.height.value()));
             Ok(Some(tx))
         } else {
@@ -472,14 +486,17 @@ where
             .client
             .state_params(FvmQueryHeight::Height(header.header.height.value()))
             .await?;
-        let msg = to_chain_message(&res.tx)?;
+        let msg = to_chain_message(&res.tx, data.max_decompressed_size().await?)?;
         if let ChainMessage::Signed(msg) = msg {
             let receipt = to_eth_receipt(
+                &data.addr_cache,
                 &msg,
                 &res,
                 &cumulative,
                 &header.header,
                 &state_params.value.base_fee,
+                !data.compat.strict_receipts,
+                data.eth_block_hash_version().await?,
             )
             .await
             .context("failed to convert to receipt")?;
@@ -501,42 +518,43 @@ pub async fn get_block_receipts<C>(
 where
     C: Client + Sync + Send,
 {
-    let block = data.block_by_height(block_number).await?;
-    if from_tm::is_block_zero(&block) {
-        return Ok(Vec::new());
+    if let et::BlockNumber::Number(height) = block_number {
+        if height.is_zero() {
+            return Ok(Vec::new());
+        }
     }
-    let height = block.header.height;
-    let state_params = data
-        .client
-        .state_params(FvmQueryHeight::Height(height.value()))
-        .await?;
-    let block_results: block_results::Response = data.tm().block_results(height).await?;
-    let cumulative = to_cumulative(&block_results);
+    let height = data.block_height(block_number).await?;
+    let bundle = data.block_bundle_by_height(height).await?;
+    let cumulative = to_cumulative(&bundle.results);
     let mut receipts = Vec::new();
 
-    for (index, (tx, tx_result)) in block
+    for (index, (tx, tx_result)) in bundle
+        .block
         .data
-        .into_iter()
-        .zip(block_results.txs_results.unwrap_or_default())
+        .iter()
+        .zip(bundle.results.txs_results.iter().flatten())
         .enumerate()
     {
-        let msg = to_chain_message(&tx)?;
+        let msg = to_chain_message(tx, data.max_decompressed_size().await?)?;
         if let ChainMessage::Signed(msg) = msg {
             let result = endpoint::tx::Response {
                 hash: Default::default(), // Shouldn't use this anyway.
                 height,
                 index: index as u32,
-                tx_result,
-                tx,
+                tx_result: tx_result.clone(),
+                tx: tx.clone(),
                 proof: None,
             };
 
             let receipt = to_eth_receipt(
+                &data.addr_cache,
                 &msg,
                 &result,
                 &cumulative,
-                &block.header,
-                &state_params.value.base_fee,
+                &bundle.block.header,
+                &bundle.base_fee,
+                !data.compat.strict_receipts,
+                data.eth_block_hash_version().await?,
             )
             .await?;
             receipts.push(receipt)
@@ -547,11 +565,16 @@ where
 
 /// Returns the number of uncles in a block from a block matching the given block hash.
 ///
-/// It will always return 0 since Tendermint doesn't have uncles.
+/// It will always return 0 since Tendermint doesn't have uncles, but it
+/// fails the same way as the other methods if the block is not available.
 pub async fn get_uncle_count_by_block_hash<C>(
-    _data: JsonRpcData<C>,
-    _params: Params<(et::H256,)>,
-) -> JsonRpcResult<et::U256> {
+    data: JsonRpcData<C>,
+    Params((block_hash,)): Params<(et::H256,)>,
+) -> JsonRpcResult<et::U256>
+where
+    C: Client + Sync + Send,
+{
+    data.header_by_hash_opt(block_hash).await?;
     Ok(et::U256::zero())
 }
 
@@ -567,11 +590,16 @@ pub async fn get_uncle_count_by_block_number<C>(
 
 /// Returns information about a uncle of a block by hash and uncle index position.
 ///
-/// It will always return None since Tendermint doesn't have uncles.
+/// It will always return None since Tendermint doesn't have uncles, but it
+/// fails the same way as the other methods if the block is not available.
 pub async fn get_uncle_by_block_hash_and_index<C>(
-    _data: JsonRpcData<C>,
-    _params: Params<(et::H256, et::U64)>,
-) -> JsonRpcResult<Option<et::Block<et::H256>>> {
+    data: JsonRpcData<C>,
+    Params((block_hash, _)): Params<(et::H256, et::U64)>,
+) -> JsonRpcResult<Option<et::Block<et::H256>>>
+where
+    C: Client + Sync + Send,
+{
+    data.header_by_hash_opt(block_hash).await?;
     Ok(None)
 }
 
@@ -711,6 +739,79 @@ where
     }
 }
 
+/// Generates an access list for a transaction, along with the gas it would use.
+///
+/// The list is collected from the execution trace, and contains the actors the
+/// transaction calls into, apart from its sender, with the storage slots of the contracts
+/// it touched; like Ethereum, the recipient is only listed if its storage was accessed.
+pub async fn create_access_list<C>(
+    data: JsonRpcData<C>,
+    Params(params): Params<EstimateGasParams>,
+) -> JsonRpcResult<et::transaction::eip2930::AccessListWithGasUsed>
+where
+    C: Client + Sync + Send,
+{
+    let (tx, block_id) = match params {
+        EstimateGasParams::One((tx,)) => (tx, et::BlockId::Number(et::BlockNumber::Latest)),
+        EstimateGasParams::Two((tx, block_id)) => (tx, block_id),
+    };
+
+    let can_trace = data
+        .capabilities()
+        .await?
+        .map(|c| c.has_feature(feature::ACCESS_LIST))
+        .unwrap_or_default();
+
+    if !can_trace {
+        return error(
+            ExitCode::USR_UNSUPPORTED_METHOD,
+            "the node doesn't support access lists",
+        );
+    }
+
+    let msg = to_fvm_message(tx.into(), true).context("failed to convert to FVM message")?;
+
+    let height = data
+        .query_height(block_id)
+        .await
+        .context("failed to get height")?;
+
+    let response = data
+        .client
+        .access_list(msg, height)
+        .await
+        .context("failed to call access list query")?;
+
+    let al = response.value;
+    let estimate = al.estimate;
+
+    if !estimate.exit_code.is_success() {
+        let msg = format!("failed to create access list: {}", estimate.info);
+        let (msg, data) = match decode_fevm_return_data(estimate.return_data).map(hex::encode) {
+            Ok(h) => (msg, h),
+            Err(e) => (format!("{msg}\n{e:#}"), "".to_string()),
+        };
+
+        return error_with_data(estimate.exit_code, msg, data);
+    }
+
+    let access_list = al
+        .actors
+        .iter()
+        .filter_map(|actor| {
+            to_eth_address(&actor.address).map(|address| et::transaction::eip2930::AccessListItem {
+                address,
+                storage_keys: actor.storage_keys.iter().map(et::H256::from).collect(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(et::transaction::eip2930::AccessListWithGasUsed {
+        access_list: et::transaction::eip2930::AccessList(access_list),
+        gas_used: estimate.gas_limit.into(),
+    })
+}
+
 /// Returns the value from a storage position at a given address.
 ///
 /// The return value is a hex encoded U256.
@@ -788,6 +889,24 @@ where
     let status = if !info.catching_up {
         et::SyncingStatus::IsFalse
     } else {
+        // Older nodes don't report on the snapshot download, in which case leave it empty.
+        let can_report_states = data
+            .capabilities()
+            .await?
+            .map(|c| c.has_feature(feature::STATE_SYNC_STATUS))
+            .unwrap_or_default();
+
+        let (pulled_states, known_states) = if can_report_states {
+            match data.state_sync_progress().await? {
+                Some((received, total)) => {
+                    (Some(et::U64::from(received)), Some(et::U64::from(total)))
+                }
+                None => (None, None),
+            }
+        } else {
+            (None, None)
+        };
+
         let progress = et::SyncProgress {
             // This would be the block we executed.
             current_block: et::U64::from(info.latest_block_height.value()),
@@ -795,8 +914,8 @@ where
             highest_block: et::U64::from(info.latest_block_height.value()),
             // This would be the block we started syncing from.
             starting_block: Default::default(),
-            pulled_states: None,
-            known_states: None,
+            pulled_states,
+            known_states,
             healed_bytecode_bytes: None,
             healed_bytecodes: None,
             healed_trienode_bytes: None,
@@ -817,6 +936,9 @@ where
 }
 
 /// Returns an array of all logs matching a given filter object.
+///
+/// Fails if the range is too wide, or there are more logs than the configured limit,
+/// in which case the error suggests a range that would fit, like Infura does.
 pub async fn get_logs<C>(
     data: JsonRpcData<C>,
     Params((filter,)): Params<(et::Filter,)>,
@@ -824,7 +946,48 @@ pub async fn get_logs<C>(
 where
     C: Client + Sync + Send,
 {
-    let (from_height, to_height) = match filter.block_option {
+    let (from_height, to_height) = log_range(&data, &filter).await?;
+    let from = from_height.value();
+    let max_logs = data.limits.max_logs;
+    let max_block_range = data.limits.max_block_range;
+
+    if max_block_range > 0 && to_height.value().saturating_sub(from) >= max_block_range {
+        return limit_exceeded(
+            format!("block range is too wide; the maximum is {max_block_range} blocks"),
+            Some(range_hint(from, from + max_block_range - 1)),
+        );
+    }
+
+    let (logs, next) = find_logs(&data, &filter, from_height, to_height, 0, max_logs).await?;
+
+    if let Some(next) = next {
+        // Suggest the blocks which could be returned in full.
+        return limit_exceeded(
+            format!("query returned more than {max_logs} results"),
+            Some(range_hint(from, next.height.saturating_sub(1).max(from))),
+        );
+    }
+
+    Ok(logs)
+}
+
+/// Range of blocks suggested to the client in a limit error.
+fn range_hint(from: u64, to: u64) -> serde_json::Value {
+    serde_json::json!({
+        "from": et::U64::from(from),
+        "to": et::U64::from(to),
+    })
+}
+
+/// Resolve the range of block heights a log filter is looking at.
+pub(super) async fn log_range<C>(
+    data: &JsonRpcData<C>,
+    filter: &et::Filter,
+) -> JsonRpcResult<(tendermint::block::Height, tendermint::block::Height)>
+where
+    C: Client + Sync + Send,
+{
+    match filter.block_option {
         et::FilterBlockOption::Range {
             from_block,
             to_block,
@@ -837,14 +1000,30 @@ where
             } else {
                 data.header_by_height(from_block).await?
             };
-            (from_header.height, to_header.height)
+            Ok((from_header.height, to_header.height))
         }
         et::FilterBlockOption::AtBlockHash(block_hash) => {
             let header = data.header_by_hash(block_hash).await?;
-            (header.height, header.height)
+            Ok((header.height, header.height))
         }
-    };
+    }
+}
 
+/// Collect the logs matching a filter between two heights, starting from a log index
+/// at the first height, until there are `limit` of them.
+///
+/// If there are more, it returns the cursor pointing at the first log which didn't fit.
+pub(super) async fn find_logs<C>(
+    data: &JsonRpcData<C>,
+    filter: &et::Filter,
+    from_height: tendermint::block::Height,
+    to_height: tendermint::block::Height,
+    from_index: u64,
+    limit: usize,
+) -> JsonRpcResult<(Vec<et::Log>, Option<Cursor>)>
+where
+    C: Client + Sync + Send,
+{
     let addrs = match &filter.address {
         Some(et::ValueOrArray::Value(addr)) => vec![*addr],
         Some(et::ValueOrArray::Array(addrs)) => addrs.clone(),
@@ -855,10 +1034,40 @@ where
         .map(|addr| Address::from(EthAddress(addr.0)))
         .collect::<HashSet<_>>();
 
+    // Older nodes don't have blooms to skip blocks with.
+    let has_blooms = data
+        .capabilities()
+        .await?
+        .map(|c| c.has_feature(feature::LOGS_BLOOM))
+        .unwrap_or_default();
+
+    let version = data.eth_block_hash_version().await?;
+    let max_decompressed_size = data.max_decompressed_size().await?;
+
     let mut height = from_height;
     let mut logs = Vec::new();
+    let mut blooms = HashMap::new();
+    // Last height covered by the blooms fetched so far.
+    let mut blooms_to = None;
 
     while height <= to_height {
+        if has_blooms && blooms_to.map_or(true, |h| h < height.value()) {
+            blooms = data
+                .logs_blooms(height, to_height)
+                .await?
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            // The node returns blooms for at most this many heights in one go.
+            blooms_to = Some(height.value() + MAX_LOGS_BLOOM_RANGE - 1);
+        }
+
+        if let Some(bloom) = blooms.get(&height) {
+            if !matches_bloom(bloom, &addrs, filter) {
+                height = height.increment();
+                continue;
+            }
+        }
+
         if let Ok(block_results) = data.tm().block_results(height).await {
             if let Some(tx_results) = block_results.txs_results {
                 let block_number = et::U64::from(height.value());
@@ -867,11 +1076,16 @@ where
                     .block_by_height(et::BlockNumber::Number(block_number))
                     .await?;
 
-                let block_hash = et::H256::from_slice(block.header().hash().as_bytes());
+                let block_hash = from_tm::to_eth_block_hash(version, &block.header().hash());
 
+                // Log indexes count the events of every transaction in the block,
+                // as they do in the receipts, so that they don't depend on the filter.
                 let mut log_index_start = 0usize;
                 for ((tx_idx, tx_result), tx) in tx_results.iter().enumerate().zip(block.data()) {
-                    let msg = match to_chain_message(tx) {
+                    let tx_log_index_start = log_index_start;
+                    log_index_start += tx_result.events.len();
+
+                    let msg = match to_chain_message(tx, max_decompressed_size) {
                         Ok(ChainMessage::Signed(msg)) => msg,
                         _ => continue,
                     };
@@ -896,15 +1110,22 @@ where
                         block_number,
                         tx_hash,
                         tx_idx,
-                        log_index_start,
+                        tx_log_index_start,
                     )?;
 
-                    // Filter by topic.
-                    tx_logs.retain(|log| matches_topics(&filter, log));
+                    // Filter by topic, and skip what was on the previous page.
+                    tx_logs.retain(|log| {
+                        matches_topics(filter, log)
+                            && (height != from_height || log_index(log) >= from_index)
+                    });
 
                     logs.append(&mut tx_logs);
+                }
 
-                    log_index_start += tx_result.events.len();
+                if logs.len() > limit {
+                    let next = Cursor::new(height.value(), log_index(&logs[limit]));
+                    logs.truncate(limit);
+                    return Ok((logs, Some(next)));
                 }
             }
         } else {
@@ -913,7 +1134,11 @@ where
         height = height.increment()
     }
 
-    Ok(logs)
+    Ok((logs, None))
+}
+
+fn log_index(log: &et::Log) -> u64 {
+    log.log_index.map(|i| i.as_u64()).unwrap_or_default()
 }
 
 /// Creates a filter object, based on filter options, to notify when the state changes (logs).
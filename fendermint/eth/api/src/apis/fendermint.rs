@@ -0,0 +1,248 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Methods which are specific to Fendermint, rather than part of the Ethereum API.
+
+use anyhow::Context;
+use ethers_core::types as et;
+use fendermint_rpc::query::{
+    self, ContractAddresses, DiamondFacets, QueryClient, ValidatorAddresses,
+};
+use fendermint_vm_message::query::FvmQueryHeight;
+use jsonrpc_v2::Params;
+use serde::Serialize;
+use tendermint::block::Height;
+use tendermint_rpc::Client;
+
+use crate::conv::from_fvm::to_eth_tokens;
+use crate::paging::{page_size, Cursor, Page, PagedParams};
+use crate::{JsonRpcData, JsonRpcResult};
+
+use super::eth::{find_logs, log_range};
+
+/// List the validators which have been part of the power table, with their consensus,
+/// FVM and Ethereum addresses, so missed blocks and signatures can be attributed to them.
+pub async fn validators<C>(
+    data: JsonRpcData<C>,
+    Params((block_id,)): Params<(et::BlockId,)>,
+) -> JsonRpcResult<Vec<ValidatorAddresses>>
+where
+    C: Client + Sync + Send,
+{
+    let height = data.query_height(block_id).await?;
+    let res = data.client.validators(height).await?;
+    Ok(res.value)
+}
+
+/// List the libraries and contracts deployed at genesis with their addresses, by their
+/// fully qualified names, so they can be linked against without recomputing the addresses.
+pub async fn contracts<C>(
+    data: JsonRpcData<C>,
+    Params((block_id,)): Params<(et::BlockId,)>,
+) -> JsonRpcResult<Vec<ContractAddresses>>
+where
+    C: Client + Sync + Send,
+{
+    let height = data.query_height(block_id).await?;
+    let res = data.client.contracts(height).await?;
+    Ok(res.value)
+}
+
+/// List the facets and function selectors the IPC diamonds were cut with at genesis,
+/// so that facet upgrades can be checked against the layout the chain started with.
+pub async fn diamonds<C>(
+    data: JsonRpcData<C>,
+    Params((block_id,)): Params<(et::BlockId,)>,
+) -> JsonRpcResult<Vec<DiamondFacets>>
+where
+    C: Client + Sync + Send,
+{
+    let height = data.query_height(block_id).await?;
+    let res = data.client.diamonds(height).await?;
+    Ok(res.value)
+}
+
+/// Like `eth_getLogs`, but returns the logs a page at a time, instead of failing if there are too many.
+///
+/// Every page scans a limited number of blocks, so it can have fewer items than asked for,
+/// or even none, while there is still a cursor to continue with.
+pub async fn get_logs<C>(
+    data: JsonRpcData<C>,
+    Params(params): Params<PagedParams<et::Filter>>,
+) -> JsonRpcResult<Page<et::Log>>
+where
+    C: Client + Sync + Send,
+{
+    let (filter, page) = params.into_parts();
+    let limit = page_size(page.limit, data.limits.max_page_size);
+
+    let (from_height, to_height) = log_range(&data, &filter).await?;
+
+    let (from_height, from_index) = match page.cursor {
+        Some(cursor) => (
+            Height::try_from(cursor.height).context("invalid cursor height")?,
+            cursor.index,
+        ),
+        None => (from_height, 0),
+    };
+
+    let max_block_range = data.limits.max_block_range;
+    let page_to_height = if max_block_range > 0 {
+        let h = from_height.value().saturating_add(max_block_range - 1);
+        Height::try_from(h.min(to_height.value())).context("invalid height")?
+    } else {
+        to_height
+    };
+
+    let (logs, next_cursor) = find_logs(
+        &data,
+        &filter,
+        from_height,
+        page_to_height,
+        from_index,
+        limit,
+    )
+    .await?;
+
+    let next_cursor = next_cursor.or_else(|| {
+        (page_to_height < to_height).then(|| Cursor::new(page_to_height.value() + 1, 0))
+    });
+
+    Ok(Page {
+        items: logs,
+        next_cursor,
+    })
+}
+
+/// A batch of bottom-up checkpoints pruned from the gateway.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointArchive {
+    pub from_height: et::U64,
+    pub to_height: et::U64,
+    /// CID of the content of the archive.
+    pub root: String,
+    /// Name of the CAR file the archive is exported to.
+    pub file_name: String,
+}
+
+/// List the archives of bottom-up checkpoints pruned from the gateway, a page at a time.
+///
+/// The cursor points at the checkpoint height the next archive starts at.
+pub async fn checkpoint_archives<C>(
+    data: JsonRpcData<C>,
+    Params(params): Params<PagedParams<et::BlockId>>,
+) -> JsonRpcResult<Page<CheckpointArchive>>
+where
+    C: Client + Sync + Send,
+{
+    let (block_id, page) = params.into_parts();
+    let limit = page_size(page.limit, data.limits.max_page_size);
+    let from_height = page.cursor.map(|c| c.height).unwrap_or_default();
+
+    let height = data.query_height(block_id).await?;
+    let res = data.client.checkpoint_archives(height).await?;
+
+    let mut archives = res
+        .value
+        .map(|state| state.archives)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| a.from_height >= from_height)
+        .take(limit + 1)
+        .collect::<Vec<_>>();
+
+    let next_cursor = if archives.len() > limit {
+        archives.pop().map(|a| Cursor::new(a.from_height, 0))
+    } else {
+        None
+    };
+
+    let items = archives
+        .into_iter()
+        .map(|a| CheckpointArchive {
+            from_height: et::U64::from(a.from_height),
+            to_height: et::U64::from(a.to_height),
+            root: a.root.to_string(),
+            file_name: a.file_name(),
+        })
+        .collect();
+
+    Ok(Page { items, next_cursor })
+}
+
+/// The latest parent block finalized by the subnet.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParentFinality {
+    /// Height of the block in the parent.
+    pub height: et::U64,
+    pub block_hash: et::Bytes,
+}
+
+/// Get the parent finality committed in the state at a block, which is the latest parent
+/// height whose top-down messages have been executed; `null` if the subnet doesn't have IPC.
+pub async fn get_parent_finality<C>(
+    data: JsonRpcData<C>,
+    Params((block_id,)): Params<(et::BlockId,)>,
+) -> JsonRpcResult<Option<ParentFinality>>
+where
+    C: Client + Sync + Send,
+{
+    let height = data.query_height(block_id).await?;
+    let height = match height {
+        FvmQueryHeight::Pending => FvmQueryHeight::Committed,
+        h => h,
+    };
+    let res = query::parent_finality(data.tm(), u64::from(height)).await?;
+
+    Ok(res.value.map(|f| ParentFinality {
+        height: et::U64::from(f.height as u64),
+        block_hash: et::Bytes::from(f.block_hash),
+    }))
+}
+
+/// A message sent by the parent to the subnet.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopDownMessage {
+    pub nonce: et::U64,
+    /// The sender in the parent, as an FVM address.
+    pub from: Option<String>,
+    /// The recipient in the subnet, as an FVM address.
+    pub to: Option<String>,
+    pub method: et::U64,
+    pub params: et::Bytes,
+    pub value: et::U256,
+}
+
+/// List the top-down messages sent by the parent at the parent heights in an inclusive range.
+///
+/// Only finalized parent heights are included, and only up to a limited number of them at a time,
+/// so the range can come back shorter; the caller can follow up from the latest parent finality.
+pub async fn get_top_down_messages<C>(
+    data: JsonRpcData<C>,
+    Params((from, to)): Params<(et::U64, et::U64)>,
+) -> JsonRpcResult<Vec<TopDownMessage>>
+where
+    C: Client + Sync + Send,
+{
+    let res = query::topdown_msgs(data.tm(), from.as_u64(), to.as_u64()).await?;
+
+    let msgs = res
+        .value
+        .into_iter()
+        .map(|msg| {
+            Ok(TopDownMessage {
+                nonce: et::U64::from(msg.nonce),
+                from: msg.from.map(|a| a.to_string()),
+                to: msg.to.map(|a| a.to_string()),
+                method: et::U64::from(msg.method_num),
+                params: et::Bytes::from(msg.params.to_vec()),
+                value: to_eth_tokens(&msg.value)?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(msgs)
+}
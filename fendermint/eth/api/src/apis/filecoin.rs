@@ -0,0 +1,41 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Methods of the Lotus API, so that Filecoin wallets and tools can submit transactions.
+// See https://lotus.filecoin.io/reference/lotus/mpool/
+
+use fendermint_rpc::message::MessageFactory;
+use fendermint_vm_message::chain::ChainMessage;
+use fvm_shared::error::ExitCode;
+use jsonrpc_v2::Params;
+use tendermint_rpc::{endpoint::broadcast::tx_sync, Client};
+
+use crate::conv::from_lotus::{to_lotus_cid, to_signed_message, LotusCid, LotusSignedMessage};
+use crate::{error, JsonRpcData, JsonRpcResult};
+
+/// Submit a message signed by a Filecoin wallet to the mempool, returning its CID.
+///
+/// Such wallets sign the CID of the message without the chain ID, so the message
+/// only passes the checks if the genesis enabled Filecoin signatures.
+pub async fn mpool_push<C>(
+    data: JsonRpcData<C>,
+    Params((msg,)): Params<(LotusSignedMessage,)>,
+) -> JsonRpcResult<LotusCid>
+where
+    C: Client + Sync + Send,
+{
+    let msg = to_signed_message(msg)?;
+    let cid = to_lotus_cid(&msg)?;
+    tracing::debug!(?cid, ?msg, "received Filecoin message");
+
+    let msg = ChainMessage::Signed(msg);
+    let bz: Vec<u8> = MessageFactory::serialize(&msg)?;
+
+    // Same as `eth_sendRawTransaction`, wait for the checks but not for the execution.
+    let res: tx_sync::Response = data.tm().broadcast_tx_sync(bz).await?;
+    if res.code.is_ok() {
+        Ok(LotusCid::from(cid))
+    } else {
+        error(ExitCode::new(res.code.value()), res.log)
+    }
+}
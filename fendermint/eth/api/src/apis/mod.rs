@@ -9,7 +9,10 @@ use jsonrpc_v2::{MapRouter, ServerBuilder};
 use paste::paste;
 
 mod eth;
+mod fendermint;
+mod filecoin;
 mod net;
+mod txpool;
 mod web3;
 
 macro_rules! with_methods {
@@ -33,6 +36,7 @@ pub fn register_methods(server: ServerBuilder<MapRouter>) -> ServerBuilder<MapRo
         blockNumber,
         call,
         chainId,
+        createAccessList,
         // eth_coinbase
         // eth_compileLLL
         // eth_compileSerpent
@@ -86,11 +90,29 @@ pub fn register_methods(server: ServerBuilder<MapRouter>) -> ServerBuilder<MapRo
         sha3
     });
 
-    with_methods!(server, net, {
+    let server = with_methods!(server, net, {
         version,
         listening,
         peerCount
-    })
+    });
+
+    let server = with_methods!(server, txpool, {
+        content,
+        status
+    });
+
+    let server = with_methods!(server, fendermint, {
+        validators,
+        contracts,
+        diamonds,
+        getLogs,
+        checkpointArchives,
+        getParentFinality,
+        getTopDownMessages
+    });
+
+    // Lotus methods don't follow the naming convention of the others.
+    server.with_method("Filecoin.MpoolPush", filecoin::mpool_push::<HybridClient>)
 }
 
 /// Indicate whether a method requires a WebSocket connection.
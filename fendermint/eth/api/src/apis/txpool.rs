@@ -0,0 +1,208 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Methods to inspect the mempool, with the same response shape as `geth`.
+// See https://geth.ethereum.org/docs/interacting-with-geth/rpc/ns-txpool
+
+use std::collections::BTreeMap;
+
+use ethers_core::types as et;
+use ethers_core::utils::to_checksum;
+use fendermint_rpc::query::QueryClient;
+use fendermint_vm_message::chain::ChainMessage;
+use fendermint_vm_message::query::FvmQueryHeight;
+use fvm_shared::chainid::ChainID;
+use serde::Serialize;
+use tendermint_rpc::Client;
+
+use crate::client::{MempoolClient, MAX_UNCONFIRMED_TXS};
+use crate::conv::from_eth::to_fvm_address;
+use crate::conv::from_tm::{
+    resolve_eth_transaction, to_chain_message, to_eth_transaction, to_eth_tx_hash,
+};
+use crate::{JsonRpcData, JsonRpcResult};
+
+/// Transactions by sender and nonce.
+type TxsBySender = BTreeMap<String, BTreeMap<String, et::Transaction>>;
+
+#[derive(Serialize, Default)]
+pub struct TxPoolContent {
+    /// Transactions which can be included in the next block.
+    pub pending: TxsBySender,
+    /// Transactions which have to wait for a gap in the sender's nonces to be filled.
+    pub queued: TxsBySender,
+}
+
+#[derive(Serialize, Default)]
+pub struct TxPoolStatus {
+    pub pending: et::U64,
+    pub queued: et::U64,
+}
+
+/// Returns the transactions waiting in the mempool, grouped by sender and nonce.
+///
+/// CometBFT doesn't return more than [MAX_UNCONFIRMED_TXS] transactions, so only
+/// the first of them are listed if there are more.
+pub async fn content<C>(data: JsonRpcData<C>) -> JsonRpcResult<TxPoolContent>
+where
+    C: Client + MempoolClient + Sync + Send,
+{
+    let (content, unlisted) = pool_content(&data).await?;
+    if unlisted > 0 {
+        tracing::warn!(
+            unlisted,
+            "only the first transactions of the mempool are listed"
+        );
+    }
+    Ok(content)
+}
+
+/// Returns the number of pending and queued transactions in the mempool.
+///
+/// The transactions which CometBFT didn't list can't be told apart, so they are counted as queued.
+pub async fn status<C>(data: JsonRpcData<C>) -> JsonRpcResult<TxPoolStatus>
+where
+    C: Client + MempoolClient + Sync + Send,
+{
+    let (content, unlisted) = pool_content(&data).await?;
+    Ok(pool_status(&content, unlisted))
+}
+
+fn pool_status(content: &TxPoolContent, unlisted: u64) -> TxPoolStatus {
+    let count = |txs: &TxsBySender| txs.values().map(|m| m.len() as u64).sum::<u64>();
+    TxPoolStatus {
+        pending: et::U64::from(count(&content.pending)),
+        queued: et::U64::from(count(&content.queued) + unlisted),
+    }
+}
+
+/// Group the transactions in the mempool, along with the number of the ones which weren't listed.
+async fn pool_content<C>(data: &JsonRpcData<C>) -> JsonRpcResult<(TxPoolContent, u64)>
+where
+    C: Client + MempoolClient + Sync + Send,
+{
+    let res = data.tm().unconfirmed_txs(MAX_UNCONFIRMED_TXS).await?;
+    let unlisted = res.total.saturating_sub(res.txs.len() as u64);
+
+    let sp = data.client.state_params(FvmQueryHeight::default()).await?;
+    let chain_id = ChainID::from(sp.value.chain_id);
+    let max_decompressed_size = data.max_decompressed_size().await?;
+
+    let mut by_sender: BTreeMap<et::Address, Vec<et::Transaction>> = BTreeMap::new();
+
+    for tx in res.txs {
+        let msg = match to_chain_message(&tx, max_decompressed_size) {
+            Ok(ChainMessage::Signed(msg)) => msg,
+            // Only user transactions are of interest to Ethereum tools.
+            _ => continue,
+        };
+        let hash = to_eth_tx_hash(&msg, &chain_id, &[], &tx);
+        let fvm_msg = msg.message.clone();
+        match to_eth_transaction(msg, chain_id, hash) {
+            Ok(mut tx) => {
+                resolve_eth_transaction(&data.addr_cache, &fvm_msg, &mut tx).await?;
+                by_sender.entry(tx.from).or_default().push(tx)
+            }
+            Err(e) => {
+                tracing::debug!(error = e.to_string(), "skipping non-Ethereum transaction");
+            }
+        }
+    }
+
+    let mut content = TxPoolContent::default();
+
+    for (sender, txs) in by_sender {
+        let res = data
+            .client
+            .actor_state(&to_fvm_address(sender), FvmQueryHeight::Committed)
+            .await?;
+        let sequence = res
+            .value
+            .map(|(_, state)| state.sequence)
+            .unwrap_or_default();
+
+        let (pending, queued) = split_by_nonce(sequence, txs);
+        let key = to_checksum(&sender, None);
+
+        if !pending.is_empty() {
+            content.pending.insert(key.clone(), pending);
+        }
+        if !queued.is_empty() {
+            content.queued.insert(key, queued);
+        }
+    }
+
+    Ok((content, unlisted))
+}
+
+/// Separate the transactions of a sender which follow on from its current nonce
+/// from the ones which come after a gap.
+fn split_by_nonce(
+    sequence: u64,
+    mut txs: Vec<et::Transaction>,
+) -> (
+    BTreeMap<String, et::Transaction>,
+    BTreeMap<String, et::Transaction>,
+) {
+    txs.sort_by_key(|tx| tx.nonce);
+
+    let mut pending = BTreeMap::new();
+    let mut queued = BTreeMap::new();
+    let mut next = et::U256::from(sequence);
+
+    for tx in txs {
+        let key = tx.nonce.to_string();
+        if tx.nonce == next {
+            next += et::U256::one();
+            pending.insert(key, tx);
+        } else if tx.nonce > next {
+            queued.insert(key, tx);
+        }
+        // Lower nonces are already executed and about to be removed from the mempool.
+    }
+
+    (pending, queued)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types as et;
+
+    use super::{pool_status, split_by_nonce, TxPoolContent};
+
+    #[test]
+    fn split_pending_and_queued() {
+        let txs = [7u64, 5, 3, 4, 9]
+            .into_iter()
+            .map(|n| et::Transaction {
+                nonce: et::U256::from(n),
+                ..Default::default()
+            })
+            .collect();
+
+        let (pending, queued) = split_by_nonce(4, txs);
+
+        assert_eq!(pending.keys().collect::<Vec<_>>(), vec!["4", "5"]);
+        assert_eq!(queued.keys().collect::<Vec<_>>(), vec!["7", "9"]);
+    }
+
+    #[test]
+    fn status_counts_unlisted_as_queued() {
+        let tx = || (String::new(), et::Transaction::default());
+        let mut content = TxPoolContent::default();
+        content
+            .pending
+            .insert("a".into(), [tx()].into_iter().collect());
+        content
+            .queued
+            .insert("b".into(), [tx()].into_iter().collect());
+
+        let status = pool_status(&content, 0);
+        assert_eq!(status.pending, et::U64::from(1));
+        assert_eq!(status.queued, et::U64::from(1));
+
+        let status = pool_status(&content, 5);
+        assert_eq!(status.pending, et::U64::from(1));
+        assert_eq!(status.queued, et::U64::from(6));
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Optional authentication of the sensitive methods, the same way the Engine API of Ethereum
+//! clients does it: the caller signs a JWT with HS256 using a secret shared with the node,
+//! puts the time of signing into the `iat` claim, and sends it in the `Authorization: Bearer`
+//! header. HTTP requests are authenticated one by one, WebSocket connections when they are opened.
+//!
+//! Only the configured namespaces and methods require authentication, so the rest of the API
+//! stays open to the public on the same port.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::error::JsonRpcError;
+
+/// The error code of requests to protected methods without valid credentials.
+pub const UNAUTHORIZED_CODE: i64 = -32000;
+
+/// Matches every namespace.
+const ALL_NAMESPACES: &str = "*";
+
+#[derive(Debug, Clone)]
+pub struct AuthOpt {
+    /// Whether to require authentication for the protected methods.
+    pub enabled: bool,
+    /// The secret shared with the callers to sign the tokens with.
+    pub secret: Vec<u8>,
+    /// Namespaces of the protected methods, e.g. `txpool` for `txpool_*`, or `*` for all.
+    pub namespaces: Vec<String>,
+    /// Individual protected methods, e.g. `eth_sendRawTransaction`.
+    pub methods: Vec<String>,
+    /// Maximum difference between the `iat` claim of a token and the local clock.
+    pub max_iat_drift: Duration,
+}
+
+/// The outcome of checking the credentials of a request or a connection.
+pub type Authentication = Result<(), String>;
+
+#[derive(Deserialize)]
+struct Claims {
+    iat: u64,
+}
+
+/// Checks the credentials of the callers of protected methods.
+#[derive(Clone)]
+pub struct Auth {
+    opt: Arc<AuthOpt>,
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl Auth {
+    pub fn new(opt: AuthOpt) -> Self {
+        let key = DecodingKey::from_secret(&opt.secret);
+        // Only the issuance time is mandatory, the expiry is checked if present.
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims.clear();
+        Self {
+            opt: Arc::new(opt),
+            key,
+            validation,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.opt.enabled
+    }
+
+    /// Whether calling a method requires authentication.
+    pub fn is_protected(&self, method: &str) -> bool {
+        if !self.opt.enabled {
+            return false;
+        }
+        // The Lotus methods are namespaced like `Filecoin.MpoolPush`.
+        let namespace = method.split(['_', '.']).next().unwrap_or_default();
+        self.opt.methods.iter().any(|m| m == method)
+            || self
+                .opt
+                .namespaces
+                .iter()
+                .any(|n| n == namespace || n == ALL_NAMESPACES)
+    }
+
+    /// Check the token in the headers of a request.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Authentication {
+        if !self.opt.enabled {
+            return Ok(());
+        }
+        let token = headers
+            .get(AUTHORIZATION)
+            .ok_or_else(|| "missing token".to_owned())?
+            .to_str()
+            .map_err(|_| "invalid authorization header".to_owned())?
+            .strip_prefix("Bearer ")
+            .ok_or_else(|| "expected a bearer token".to_owned())?;
+
+        self.verify_token(token.trim())
+    }
+
+    fn verify_token(&self, token: &str) -> Authentication {
+        let data = jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|e| format!("invalid token: {e}"))?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        if now.abs_diff(data.claims.iat) > self.opt.max_iat_drift.as_secs() {
+            return Err("stale token".to_owned());
+        }
+        Ok(())
+    }
+
+    /// Check whether a method can be called with the given credentials.
+    pub fn check(&self, method: &str, authn: &Authentication) -> Result<(), JsonRpcError> {
+        match authn {
+            Err(e) if self.is_protected(method) => Err(JsonRpcError {
+                code: UNAUTHORIZED_CODE,
+                message: format!("unauthorized: {e}"),
+                data: None,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use axum::http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    use super::{Auth, AuthOpt, UNAUTHORIZED_CODE};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn auth() -> Auth {
+        Auth::new(AuthOpt {
+            enabled: true,
+            secret: SECRET.to_vec(),
+            namespaces: vec!["txpool".to_owned()],
+            methods: vec!["fendermint_getLogs".to_owned()],
+            max_iat_drift: Duration::from_secs(60),
+        })
+    }
+
+    fn headers(secret: &[u8], iat: u64) -> HeaderMap {
+        claims_headers(secret, json!({ "iat": iat }))
+    }
+
+    fn claims_headers(secret: &[u8], claims: serde_json::Value) -> HeaderMap {
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn protected_methods() {
+        let auth = auth();
+        assert!(auth.is_protected("txpool_content"));
+        assert!(auth.is_protected("fendermint_getLogs"));
+        assert!(!auth.is_protected("fendermint_validators"));
+        assert!(!auth.is_protected("eth_blockNumber"));
+        assert!(!auth.is_protected("Filecoin.MpoolPush"));
+    }
+
+    #[test]
+    fn authenticate_tokens() {
+        let auth = auth();
+        assert!(auth.authenticate(&headers(SECRET, now())).is_ok());
+        assert!(auth.authenticate(&headers(SECRET, now() - 30)).is_ok());
+        assert_eq!(
+            auth.authenticate(&headers(SECRET, now() - 120)),
+            Err("stale token".to_owned())
+        );
+        assert!(auth.authenticate(&headers(b"other", now())).is_err());
+
+        let expired = json!({ "iat": now(), "exp": now() - 120 });
+        assert!(auth.authenticate(&claims_headers(SECRET, expired)).is_err());
+        let valid = json!({ "iat": now(), "exp": now() + 120 });
+        assert!(auth.authenticate(&claims_headers(SECRET, valid)).is_ok());
+
+        let missing = auth.authenticate(&HeaderMap::new());
+        assert!(auth.check("eth_blockNumber", &missing).is_ok());
+        let err = auth.check("txpool_status", &missing).unwrap_err();
+        assert_eq!(err.code, UNAUTHORIZED_CODE);
+        assert_eq!(err.message, "unauthorized: missing token");
+    }
+}
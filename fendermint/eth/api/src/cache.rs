@@ -4,6 +4,7 @@
 use std::sync::{Arc, Mutex};
 
 use anyhow::Context;
+use async_trait::async_trait;
 use fendermint_rpc::client::FendermintClient;
 use fendermint_rpc::query::QueryClient;
 use fendermint_vm_message::query::FvmQueryHeight;
@@ -14,6 +15,13 @@ use fvm_shared::{
 use lru_time_cache::LruCache;
 use tendermint_rpc::Client;
 
+/// Resolve FVM addresses to the delegated addresses the actors are known by in Ethereum.
+#[async_trait]
+pub trait CanResolveAddress {
+    /// Look up the delegated address of an actor, if it has one.
+    async fn resolve_delegated(&self, addr: &Address) -> anyhow::Result<Option<Address>>;
+}
+
 /// Facilitate Ethereum address <-> Actor ID lookups.
 #[derive(Clone)]
 pub struct AddressCache<C> {
@@ -108,3 +116,19 @@ where
         c.insert(id, addr);
     }
 }
+
+#[async_trait]
+impl<C> CanResolveAddress for AddressCache<C>
+where
+    C: Client + Sync + Send,
+{
+    async fn resolve_delegated(&self, addr: &Address) -> anyhow::Result<Option<Address>> {
+        if let Payload::Delegated(_) = addr.payload() {
+            return Ok(Some(*addr));
+        }
+        match self.lookup_id(addr).await? {
+            Some(id) => self.lookup_addr(&id).await,
+            None => Ok(None),
+        }
+    }
+}
@@ -3,10 +3,11 @@
 
 use std::{pin::Pin, time::Duration};
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
 use fendermint_rpc::client::{http_client, ws_client};
 use futures::Future;
+use serde::Deserialize;
 use tendermint_rpc::{
     error::ErrorDetail, query::Query, Client, Error, HttpClient, SimpleRequest, Subscription,
     SubscriptionClient, Url, WebSocketClient, WebSocketClientDriver, WebSocketClientUrl,
@@ -19,12 +20,13 @@ use tendermint_rpc::{
 /// The WebSocket client is expected to lose connection with CometBFT,
 /// in which case it will be re-established in the background.
 ///
-/// Existing subscriptions should receive an error and they can try
-/// re-subscribing through the Ethereum API facade, which should create
+/// Existing subscriptions should receive an error, upon which the filters
+/// in the Ethereum API facade try re-subscribing, which should create
 /// new subscriptions through a fresh CometBFT client.
 #[derive(Clone)]
 pub struct HybridClient {
     http_client: HttpClient,
+    mempool_client: MempoolHttpClient,
     cmd_tx: tokio::sync::mpsc::UnboundedSender<DriverCommand>,
 }
 
@@ -50,11 +52,12 @@ impl HybridClient {
         retry_delay: Duration,
     ) -> anyhow::Result<(Self, HybridClientDriver)> {
         let http_client =
-            http_client(http_url, None).context("failed to create Tendermint client")?;
+            http_client(http_url.clone(), None).context("failed to create Tendermint client")?;
 
         let (cmd_tx, cmd_rx) = tokio::sync::mpsc::unbounded_channel();
 
         let client = Self {
+            mempool_client: MempoolHttpClient::new(http_url),
             http_client,
             cmd_tx,
         };
@@ -79,6 +82,88 @@ impl Client for HybridClient {
     }
 }
 
+/// The maximum number of transactions CometBFT returns from its mempool, whatever the limit asked for.
+pub const MAX_UNCONFIRMED_TXS: usize = 100;
+
+/// Transactions waiting in the CometBFT mempool.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnconfirmedTxs {
+    /// Number of transactions returned.
+    #[serde(with = "tendermint::serializers::from_str")]
+    pub n_txs: u64,
+    /// Number of transactions in the mempool.
+    #[serde(with = "tendermint::serializers::from_str")]
+    pub total: u64,
+    /// The raw transactions.
+    #[serde(with = "tendermint::serializers::txs")]
+    pub txs: Vec<Vec<u8>>,
+}
+
+/// CometBFT mempool endpoints which the [Client] doesn't support.
+#[async_trait]
+pub trait MempoolClient {
+    /// Get the transactions in the mempool, up to `limit`, which CometBFT caps at [MAX_UNCONFIRMED_TXS].
+    async fn unconfirmed_txs(&self, limit: usize) -> anyhow::Result<UnconfirmedTxs>;
+}
+
+#[async_trait]
+impl MempoolClient for HybridClient {
+    async fn unconfirmed_txs(&self, limit: usize) -> anyhow::Result<UnconfirmedTxs> {
+        self.mempool_client.unconfirmed_txs(limit).await
+    }
+}
+
+/// Calls the [MempoolClient] endpoints of CometBFT over HTTP.
+#[derive(Clone)]
+pub struct MempoolHttpClient {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl MempoolHttpClient {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl MempoolClient for MempoolHttpClient {
+    async fn unconfirmed_txs(&self, limit: usize) -> anyhow::Result<UnconfirmedTxs> {
+        #[derive(Deserialize)]
+        struct RpcResponse {
+            result: Option<UnconfirmedTxs>,
+            error: Option<serde_json::Value>,
+        }
+
+        let req = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "unconfirmed_txs",
+            "params": { "limit": limit.to_string() }
+        });
+
+        let res: RpcResponse = self
+            .client
+            .post(self.url.to_string())
+            .json(&req)
+            .send()
+            .await
+            .context("failed to send unconfirmed_txs request")?
+            .json()
+            .await
+            .context("failed to parse unconfirmed_txs response")?;
+
+        match (res.result, res.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(e)) => Err(anyhow!("unconfirmed_txs failed: {e}")),
+            (None, None) => Err(anyhow!("unconfirmed_txs returned no result")),
+        }
+    }
+}
+
 #[async_trait]
 impl SubscriptionClient for HybridClient {
     async fn subscribe(&self, query: Query) -> Result<Subscription, Error> {
@@ -0,0 +1,222 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Helper methods to convert messages in the JSON format of the Lotus API, so that
+//! Filecoin wallets such as Lotus or Glif can submit them.
+//!
+//! See https://github.com/filecoin-project/lotus/blob/v1.25.0/chain/types/message.go
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use cid::Cid;
+use fendermint_vm_message::signed::SignedMessage;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::crypto::signature::{Signature, SignatureType};
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// An unsigned message; addresses and token amounts are strings, the parameters are base64 encoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LotusMessage {
+    pub version: u64,
+    pub to: String,
+    pub from: String,
+    pub nonce: u64,
+    pub value: String,
+    pub gas_limit: u64,
+    pub gas_fee_cap: String,
+    pub gas_premium: String,
+    pub method: u64,
+    /// Lotus sends `null` if there are no parameters.
+    #[serde(default)]
+    pub params: Option<String>,
+}
+
+/// A signature with its type: 1 for Secp256k1 and 2 for BLS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LotusSignature {
+    #[serde(rename = "Type")]
+    pub sig_type: u8,
+    /// The base64 encoded signature.
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LotusSignedMessage {
+    pub message: LotusMessage,
+    pub signature: LotusSignature,
+}
+
+/// A CID in the JSON format of IPLD, e.g. `{"/": "bafy2bza..."}`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LotusCid {
+    #[serde(rename = "/")]
+    pub cid: String,
+}
+
+impl From<Cid> for LotusCid {
+    fn from(value: Cid) -> Self {
+        Self {
+            cid: value.to_string(),
+        }
+    }
+}
+
+/// Convert a message signed by a Filecoin wallet into a [SignedMessage].
+///
+/// The signature isn't verified here.
+pub fn to_signed_message(msg: LotusSignedMessage) -> anyhow::Result<SignedMessage> {
+    let LotusSignedMessage { message, signature } = msg;
+
+    let signature = match signature.sig_type {
+        t if t == SignatureType::Secp256k1 as u8 => {
+            Signature::new_secp256k1(decode_b64(&signature.data, "signature")?)
+        }
+        t if t == SignatureType::BLS as u8 => {
+            Signature::new_bls(decode_b64(&signature.data, "signature")?)
+        }
+        t => bail!(
+            "unsupported signature type {t}; Ethereum accounts have to use eth_sendRawTransaction"
+        ),
+    };
+
+    Ok(SignedMessage::new_unchecked(
+        to_fvm_message(message)?,
+        signature,
+    ))
+}
+
+/// Convert the unsigned part of a Lotus message into an FVM [Message].
+pub fn to_fvm_message(msg: LotusMessage) -> anyhow::Result<Message> {
+    let params = match msg.params {
+        Some(params) => decode_b64(&params, "params")?,
+        None => Vec::new(),
+    };
+
+    Ok(Message {
+        version: msg.version,
+        from: parse_address(&msg.from, "from")?,
+        to: parse_address(&msg.to, "to")?,
+        sequence: msg.nonce,
+        value: parse_tokens(&msg.value, "value")?,
+        method_num: msg.method,
+        params: RawBytes::new(params),
+        gas_limit: msg.gas_limit,
+        gas_fee_cap: parse_tokens(&msg.gas_fee_cap, "gas fee cap")?,
+        gas_premium: parse_tokens(&msg.gas_premium, "gas premium")?,
+    })
+}
+
+/// The CID by which Lotus identifies a signed message: BLS messages are identified
+/// by the CID of the unsigned message, because their signatures are aggregated in blocks.
+pub fn to_lotus_cid(msg: &SignedMessage) -> anyhow::Result<Cid> {
+    let cid = if msg.is_bls() {
+        fendermint_vm_message::cid(msg.message())
+    } else {
+        fendermint_vm_message::cid(msg)
+    };
+    cid.context("failed to compute message CID")
+}
+
+fn decode_b64(value: &str, what: &str) -> anyhow::Result<Vec<u8>> {
+    B64.decode(value)
+        .with_context(|| format!("invalid base64 in {what}"))
+}
+
+/// Parse an address of either the mainnet or the testnet; the prefix isn't part of the checksum.
+fn parse_address(value: &str, what: &str) -> anyhow::Result<Address> {
+    Address::from_str(value)
+        .or_else(|e| {
+            let other = match value.get(..1) {
+                Some("f") => "t",
+                Some("t") => "f",
+                _ => return Err(e),
+            };
+            Address::from_str(&format!("{other}{}", &value[1..]))
+        })
+        .map_err(|e| anyhow!("invalid address in {what}: {e}"))
+}
+
+fn parse_tokens(value: &str, what: &str) -> anyhow::Result<TokenAmount> {
+    let atto = BigInt::from_str(value).map_err(|e| anyhow!("invalid amount in {what}: {e}"))?;
+    Ok(TokenAmount::from_atto(atto))
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::{address::Address, crypto::signature::SignatureType, econ::TokenAmount};
+
+    use super::{to_lotus_cid, to_signed_message, LotusSignedMessage};
+
+    const SIGNED_MESSAGE: &str = r#"{
+        "Message": {
+            "Version": 0,
+            "To": "f01234",
+            "From": "f1ys5qqiciehcml3sp764ymbbytfn3qoar5fo3iwy",
+            "Nonce": 5,
+            "Value": "1000000000000000000",
+            "GasLimit": 1000000,
+            "GasFeeCap": "100",
+            "GasPremium": "10",
+            "Method": 0,
+            "Params": null,
+            "CID": { "/": "bafy2bzaceb3dnpmrlsfoatwozgd4nvdcktoekf7zmmvrvbfrmkvv6qqdnfxhw" }
+        },
+        "Signature": {
+            "Type": 1,
+            "Data": "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P0A="
+        }
+    }"#;
+
+    #[test]
+    fn convert_signed_message() {
+        let msg: LotusSignedMessage = serde_json::from_str(SIGNED_MESSAGE).unwrap();
+        let signed = to_signed_message(msg).unwrap();
+
+        let m = signed.message();
+        assert_eq!(m.to, Address::new_id(1234));
+        assert_eq!(m.sequence, 5);
+        assert_eq!(m.value, TokenAmount::from_whole(1));
+        assert_eq!(m.gas_limit, 1000000);
+        assert_eq!(m.gas_fee_cap, TokenAmount::from_atto(100));
+        assert_eq!(m.gas_premium, TokenAmount::from_atto(10));
+        assert!(m.params.is_empty());
+
+        assert_eq!(
+            signed.signature().signature_type(),
+            SignatureType::Secp256k1
+        );
+        assert_eq!(signed.signature().bytes().len(), 65);
+
+        // Secp256k1 messages are identified by the CID of the message with the signature.
+        let cid = to_lotus_cid(&signed).unwrap();
+        assert_ne!(cid, fendermint_vm_message::cid(signed.message()).unwrap());
+    }
+
+    #[test]
+    fn accept_testnet_addresses() {
+        let json = SIGNED_MESSAGE.replace(r#""From": "f1"#, r#""From": "t1"#);
+        let msg: LotusSignedMessage = serde_json::from_str(&json).unwrap();
+        let signed = to_signed_message(msg).unwrap();
+        assert_eq!(
+            signed.message().from.to_string()[1..],
+            "1ys5qqiciehcml3sp764ymbbytfn3qoar5fo3iwy"
+        );
+    }
+
+    #[test]
+    fn reject_delegated_signatures() {
+        let json = SIGNED_MESSAGE.replace(r#""Type": 1"#, r#""Type": 3"#);
+        let msg: LotusSignedMessage = serde_json::from_str(&json).unwrap();
+        assert!(to_signed_message(msg).is_err());
+    }
+}
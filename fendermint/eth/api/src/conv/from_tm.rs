@@ -9,9 +9,14 @@ use std::str::FromStr;
 use anyhow::{anyhow, Context};
 use ethers_core::types::{self as et};
 use fendermint_vm_actor_interface::eam::EthAddress;
+use fendermint_vm_message::block_hash::EthBlockHashVersion;
 use fendermint_vm_message::conv::from_fvm::to_eth_transaction_request;
-use fendermint_vm_message::{chain::ChainMessage, signed::SignedMessage};
-use fvm_shared::address::Address;
+use fendermint_vm_message::{
+    chain::ChainMessage,
+    logs::{self, EventEntry},
+    signed::{DomainHash, SignedMessage},
+};
+use fvm_shared::address::{Address, Payload};
 use fvm_shared::bigint::Zero;
 use fvm_shared::chainid::ChainID;
 use fvm_shared::{bigint::BigInt, econ::TokenAmount};
@@ -22,6 +27,7 @@ use tendermint::crypto::sha256::Sha256;
 use tendermint_rpc::endpoint;
 
 use super::from_fvm::{to_eth_address, to_eth_signature, to_eth_tokens};
+use crate::cache::CanResolveAddress;
 
 // Values taken from https://github.com/filecoin-project/lotus/blob/6e7dc9532abdb3171427347710df4c860f1957a2/chain/types/ethtypes/eth_types.go#L199
 
@@ -97,27 +103,47 @@ pub fn is_block_zero(block: &tendermint::Block) -> bool {
     block.header().hash() == tendermint::Hash::Sha256(*BLOCK_ZERO_HASH)
 }
 
+/// The hash the Ethereum API exposes for a block with the given CometBFT hash.
+pub fn to_eth_block_hash(version: EthBlockHashVersion, block_hash: &tendermint::Hash) -> et::H256 {
+    match block_hash {
+        tendermint::Hash::Sha256(h) => et::H256(version.to_eth_block_hash(h)),
+        tendermint::Hash::None => et::H256::default(),
+    }
+}
+
+/// Check if the hash is what the Ethereum API exposes for [`BLOCK_ZERO`].
+pub fn is_block_zero_hash(version: EthBlockHashVersion, block_hash: &et::H256) -> bool {
+    block_hash.0 == version.to_eth_block_hash(&BLOCK_ZERO_HASH)
+}
+
+/// Name of the extra field in the Ethereum blocks with the CometBFT hash of the block,
+/// so that explorers can correlate the two.
+pub const TENDERMINT_HASH_FIELD: &str = "tendermintHash";
+
 /// Convert a Tendermint block to Ethereum with only the block hashes in the body.
 pub fn to_eth_block(
     block: tendermint::Block,
     block_results: tendermint_rpc::endpoint::block_results::Response,
     base_fee: TokenAmount,
     chain_id: ChainID,
+    version: EthBlockHashVersion,
+    max_decompressed_size: usize,
 ) -> anyhow::Result<et::Block<et::Transaction>> {
     // Based on https://github.com/evmos/ethermint/blob/07cf2bd2b1ce9bdb2e44ec42a39e7239292a14af/rpc/types/utils.go#L113
     //          https://github.com/evmos/ethermint/blob/07cf2bd2b1ce9bdb2e44ec42a39e7239292a14af/rpc/backend/blocks.go#L365
     //          https://github.com/filecoin-project/lotus/blob/6cc506f5cf751215be6badc94a960251c6453202/node/impl/full/eth.go#L1883
 
-    let hash = et::H256::from_slice(block.header().hash().as_ref());
+    let tm_hash = block.header().hash();
+    let hash = to_eth_block_hash(version, &tm_hash);
 
     let parent_hash = if block.header.height.value() == 1 {
         // Just in case the client tool wants to compare hashes.
-        et::H256::from_slice(BLOCK_ZERO_HASH.as_ref())
+        to_eth_block_hash(version, &tendermint::Hash::Sha256(*BLOCK_ZERO_HASH))
     } else {
         block
             .header()
             .last_block_id
-            .map(|id| et::H256::from_slice(id.hash.as_bytes()))
+            .map(|id| to_eth_block_hash(version, &id.hash))
             .unwrap_or_default()
     };
 
@@ -145,25 +171,29 @@ pub fn to_eth_block(
     // potentially through multiple hops. Let's leave that for the future and for now
     // assume that all we have is signed transactions.
     for (idx, data) in block.data().iter().enumerate() {
-        let result = match transaction_results.get(idx) {
-            Some(result) => result,
-            None => continue,
-        };
+        // The results can be missing if the block hasn't been executed yet, or if they have been
+        // pruned from the node we are backfilling from. We still want to return the transaction
+        // bodies in that case; only the gas figures will be incomplete.
+        let result = transaction_results.get(idx);
 
         size += et::U256::from(data.len());
-        gas_used += et::U256::from(result.gas_used);
-        gas_limit += et::U256::from(result.gas_wanted);
 
-        let msg = to_chain_message(data)?;
+        if let Some(result) = result {
+            gas_used += et::U256::from(result.gas_used);
+            gas_limit += et::U256::from(result.gas_wanted);
+        }
+
+        let msg = to_chain_message(data, max_decompressed_size)?;
 
         if let ChainMessage::Signed(msg) = msg {
-            let hash = msg_hash(&result.events, data);
+            let events = result.map(|r| r.events.as_slice()).unwrap_or_default();
+            let hash = to_eth_tx_hash(&msg, &chain_id, events, data);
 
             let mut tx = to_eth_transaction(msg, chain_id, hash)
                 .context("failed to convert to eth transaction")?;
 
             tx.transaction_index = Some(et::U64::from(idx));
-            tx.block_hash = Some(et::H256::from_slice(block.header.hash().as_bytes()));
+            tx.block_hash = Some(hash);
             tx.block_number = Some(et::U64::from(block.header.height.value()));
 
             transactions.push(tx);
@@ -191,7 +221,7 @@ pub fn to_eth_block(
         withdrawals_root: None,
         withdrawals: None,
         seal_fields: Vec::new(),
-        other: Default::default(),
+        other: tendermint_hash_field(&tm_hash),
         transactions,
         size: Some(size),
         gas_limit,
@@ -204,6 +234,16 @@ pub fn to_eth_block(
     Ok(block)
 }
 
+/// The CometBFT hash of a block as an extra field of the Ethereum block.
+fn tendermint_hash_field(block_hash: &tendermint::Hash) -> et::OtherFields {
+    let mut other = et::OtherFields::default();
+    other.insert(
+        TENDERMINT_HASH_FIELD.to_owned(),
+        serde_json::Value::String(format!("0x{}", hex::encode(block_hash.as_bytes()))),
+    );
+    other
+}
+
 pub fn to_eth_transaction(
     msg: SignedMessage,
     chain_id: ChainID,
@@ -248,6 +288,43 @@ pub fn to_eth_transaction(
     Ok(tx)
 }
 
+/// Convert an FVM address to the Ethereum address the actor is known by: its delegated address
+/// if it has one, e.g. an account or contract created through the EAM which is referred to by
+/// its ID, otherwise whatever [`to_eth_address`] makes of it.
+pub async fn to_eth_address_resolved<R: CanResolveAddress>(
+    resolver: &R,
+    addr: &Address,
+) -> anyhow::Result<Option<et::H160>> {
+    let eth_addr = to_eth_address(addr);
+
+    // Nothing to resolve for deployments, delegated addresses, or addresses without an Ethereum form.
+    if eth_addr.is_none() || matches!(addr.payload(), Payload::Delegated(_)) {
+        return Ok(eth_addr);
+    }
+
+    match resolver
+        .resolve_delegated(addr)
+        .await
+        .context("failed to resolve delegated address")?
+    {
+        Some(delegated) => Ok(to_eth_address(&delegated).or(eth_addr)),
+        None => Ok(eth_addr),
+    }
+}
+
+/// Replace the `from` and `to` of a transaction with the addresses the actors are known by in Ethereum.
+pub async fn resolve_eth_transaction<R: CanResolveAddress>(
+    resolver: &R,
+    msg: &fvm_shared::message::Message,
+    tx: &mut et::Transaction,
+) -> anyhow::Result<()> {
+    if let Some(from) = to_eth_address_resolved(resolver, &msg.from).await? {
+        tx.from = from;
+    }
+    tx.to = to_eth_address_resolved(resolver, &msg.to).await?;
+    Ok(())
+}
+
 /// Helper function to produce cumulative gas used after the execution of each transaction in a block,
 /// along with cumulative event log count.
 pub fn to_cumulative(block_results: &endpoint::block_results::Response) -> Vec<(et::U256, usize)> {
@@ -266,14 +343,17 @@ pub fn to_cumulative(block_results: &endpoint::block_results::Response) -> Vec<(
 
 // https://github.com/filecoin-project/lotus/blob/6cc506f5cf751215be6badc94a960251c6453202/node/impl/full/eth.go#L2174
 // https://github.com/evmos/ethermint/blob/07cf2bd2b1ce9bdb2e44ec42a39e7239292a14af/rpc/backend/tx_info.go#L147
-pub async fn to_eth_receipt(
+pub async fn to_eth_receipt<R: CanResolveAddress>(
+    resolver: &R,
     msg: &SignedMessage,
     result: &endpoint::tx::Response,
     cumulative: &[(et::U256, usize)],
     header: &tendermint::block::Header,
     base_fee: &TokenAmount,
+    fvm_fields: bool,
+    version: EthBlockHashVersion,
 ) -> anyhow::Result<et::TransactionReceipt> {
-    let block_hash = et::H256::from_slice(header.hash().as_bytes());
+    let block_hash = to_eth_block_hash(version, &header.hash());
     let block_number = et::U64::from(result.height.value());
     let transaction_index = et::U64::from(result.index);
     let transaction_hash = msg_hash(&result.tx_result.events, &result.tx);
@@ -319,8 +399,10 @@ pub async fn to_eth_receipt(
         transaction_index,
         block_hash: Some(block_hash),
         block_number: Some(block_number),
-        from: to_eth_address(&msg.from).unwrap_or_default(),
-        to: to_eth_address(&msg.to),
+        from: to_eth_address_resolved(resolver, &msg.from)
+            .await?
+            .unwrap_or_default(),
+        to: to_eth_address_resolved(resolver, &msg.to).await?,
         cumulative_gas_used,
         gas_used: Some(et::U256::from(result.tx_result.gas_used)),
         contract_address,
@@ -334,11 +416,34 @@ pub async fn to_eth_receipt(
         logs_bloom: et::Bloom::from_slice(&*EMPTY_ETH_BLOOM),
         transaction_type: Some(et::U64::from(2)), // Value used by Lotus.
         effective_gas_price: Some(to_eth_tokens(&effective_gas_price)?),
-        other: Default::default(),
+        other: if fvm_fields {
+            to_fvm_receipt_fields(&result.tx_result)
+        } else {
+            Default::default()
+        },
     };
     Ok(receipt)
 }
 
+/// Non-standard receipt fields explaining why a transaction failed, which the `status` alone doesn't.
+///
+/// The application puts the exit code of the FVM into the code of the transaction result,
+/// and the failure info, e.g. the revert reason or the actor error message, into the info.
+pub fn to_fvm_receipt_fields(tx_result: &DeliverTx) -> et::OtherFields {
+    let mut other = et::OtherFields::default();
+    other.insert(
+        "fvmExitCode".to_owned(),
+        serde_json::Value::from(tx_result.code.value()),
+    );
+    if tx_result.code.is_err() && !tx_result.info.is_empty() {
+        other.insert(
+            "fvmFailureInfo".to_owned(),
+            serde_json::Value::from(tx_result.info.clone()),
+        );
+    }
+    other
+}
+
 /// Change the type of transactions in a block by mapping a function over them.
 pub fn map_rpc_block_txs<F, A, B, E>(block: et::Block<A>, f: F) -> Result<et::Block<B>, E>
 where
@@ -440,7 +545,10 @@ fn maybe_contract_address(deliver_tx: &DeliverTx) -> Option<EthAddress> {
 }
 
 /// Artificial block-zero.
-pub fn to_eth_block_zero(block: tendermint::Block) -> anyhow::Result<et::Block<serde_json::Value>> {
+pub fn to_eth_block_zero(
+    block: tendermint::Block,
+    version: EthBlockHashVersion,
+) -> anyhow::Result<et::Block<serde_json::Value>> {
     let block_results = tendermint_rpc::endpoint::block_results::Response {
         height: block.header.height,
         txs_results: None,
@@ -449,8 +557,15 @@ pub fn to_eth_block_zero(block: tendermint::Block) -> anyhow::Result<et::Block<s
         validator_updates: Vec::new(),
         consensus_param_updates: None,
     };
-    let block = to_eth_block(block, block_results, TokenAmount::zero(), ChainID::from(0))
-        .context("failed to map block zero to eth")?;
+    let block = to_eth_block(
+        block,
+        block_results,
+        TokenAmount::zero(),
+        ChainID::from(0),
+        version,
+        0,
+    )
+    .context("failed to map block zero to eth")?;
     let block =
         map_rpc_block_txs(block, serde_json::to_value).context("failed to convert to JSON")?;
     Ok(block)
@@ -513,39 +628,40 @@ pub fn to_logs(
 }
 
 // Find the Ethereum topics (up to 4) and the data in the event attributes.
-fn to_topics_and_data(attrs: &Vec<EventAttribute>) -> anyhow::Result<(Vec<et::H256>, et::Bytes)> {
-    // Based on https://github.com/filecoin-project/lotus/blob/6cc506f5cf751215be6badc94a960251c6453202/node/impl/full/eth.go#L1534
-    let mut topics = Vec::new();
-    let mut data = None;
-    for attr in attrs {
-        let decode_value = || {
-            hex::decode(&attr.value)
-                .with_context(|| format!("failed to decode attr value as hex: {}", &attr.value))
-        };
-
-        match attr.key.as_str() {
-            "t1" | "t2" | "t3" | "t4" => {
-                let bz = decode_value()?;
-                if bz.len() != 32 {
-                    return Err(anyhow!("unexpected topic value: {attr:?}"));
-                }
-                let h = et::H256::from_slice(&bz);
-                let i = attr.key[1..].parse::<usize>().unwrap().saturating_sub(1);
-                while topics.len() <= i {
-                    topics.push(et::H256::default())
-                }
-                topics[i] = h;
-            }
-            "d" => data = Some(et::Bytes::from(decode_value()?)),
-            _ => {} // e.g. "emitter.*"
-        }
+//
+// See [fendermint_vm_message::logs] for how they are derived for events of non-EVM actors.
+fn to_topics_and_data(attrs: &[EventAttribute]) -> anyhow::Result<(Vec<et::H256>, et::Bytes)> {
+    let mut values = Vec::new();
+    for attr in attrs.iter().filter(|a| !a.key.starts_with("emitter.")) {
+        let value = hex::decode(&attr.value)
+            .with_context(|| format!("failed to decode attr value as hex: {}", &attr.value))?;
+        values.push((attr, value));
     }
-    Ok((topics, data.unwrap_or_default()))
+    let entries = values
+        .iter()
+        .map(|(attr, value)| EventEntry {
+            key: &attr.key,
+            value,
+            indexed: attr.index,
+        })
+        .collect::<Vec<_>>();
+
+    logs::to_topics_and_data(&entries)
 }
 
-/// Decode the transaction payload as a [ChainMessage].
-pub fn to_chain_message(tx: &[u8]) -> anyhow::Result<ChainMessage> {
-    fvm_ipld_encoding::from_slice::<ChainMessage>(tx).context("failed to decode tx as ChainMessage")
+/// Decode a transaction, unwrapping the signed message from its compressed envelope if it has one.
+///
+/// The envelope is only opened up to the size the chain allows compressed transactions to expand to.
+pub fn to_chain_message(tx: &[u8], max_decompressed_size: usize) -> anyhow::Result<ChainMessage> {
+    match fvm_ipld_encoding::from_slice::<ChainMessage>(tx)
+        .context("failed to decode tx as ChainMessage")?
+    {
+        ChainMessage::Compressed(bz) => {
+            let msg = ChainMessage::decompress(bz.bytes(), max_decompressed_size)?;
+            Ok(ChainMessage::Signed(msg))
+        }
+        msg => Ok(msg),
+    }
 }
 
 /// Hash the transaction payload the way Tendermint does,
@@ -580,6 +696,26 @@ pub fn msg_hash(events: &[Event], tx: &[u8]) -> et::TxHash {
     }
 }
 
+/// Calculate the hash of a signed message, preferably one the tools expect.
+///
+/// Unlike [msg_hash], this doesn't need the events emitted during execution to be
+/// available to return the Ethereum hash, because it can recalculate it from the
+/// message itself. The events are still consulted first, so that the two agree.
+pub fn to_eth_tx_hash(
+    msg: &SignedMessage,
+    chain_id: &ChainID,
+    events: &[Event],
+    tx: &[u8],
+) -> et::TxHash {
+    if let Some(h) = find_hash_event("eth", events) {
+        h
+    } else if let Ok(Some(DomainHash::Eth(h))) = msg.domain_hash(chain_id) {
+        et::TxHash::from(h)
+    } else {
+        et::TxHash::from_slice(tx_hash(tx).as_bytes())
+    }
+}
+
 /// Collect and parse all `emitter.deleg` or `emitter.id` in the events.
 pub fn collect_emitters(events: &[abci::Event]) -> HashSet<Address> {
     let mut emitters = HashSet::new();
@@ -608,9 +744,72 @@ pub fn collect_emitters(events: &[abci::Event]) -> HashSet<Address> {
 
 #[cfg(test)]
 mod tests {
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers_core::types::{self as et, transaction::eip2718::TypedTransaction};
+    use ethers_core::utils::rlp;
+    use fendermint_vm_actor_interface::eam::{EthAddress, EAM_ACTOR_ADDR};
+    use fendermint_vm_message::block_hash::EthBlockHashVersion;
+    use fendermint_vm_message::chain::ChainMessage;
+    use fendermint_vm_message::conv::from_eth::to_fvm_message;
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_shared::address::Address;
+    use fvm_shared::bigint::Zero;
+    use fvm_shared::crypto::signature::Signature;
+    use fvm_shared::{chainid::ChainID, econ::TokenAmount, ActorID};
+    use quickcheck_macros::quickcheck;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::cache::CanResolveAddress;
     use crate::conv::from_tm::is_block_zero;
 
-    use super::{to_eth_block_zero, BLOCK_ZERO};
+    use super::{
+        to_eth_address_resolved, to_eth_block, to_eth_block_hash, to_eth_block_zero,
+        to_eth_transaction, BLOCK_ZERO, BLOCK_ZERO_HASH, TENDERMINT_HASH_FIELD,
+    };
+
+    /// Resolves a single ID to a delegated address.
+    struct OneDelegated(ActorID, Address);
+
+    #[async_trait::async_trait]
+    impl CanResolveAddress for OneDelegated {
+        async fn resolve_delegated(&self, addr: &Address) -> anyhow::Result<Option<Address>> {
+            Ok((addr.id().ok() == Some(self.0)).then_some(self.1))
+        }
+    }
+
+    /// Sign a random transaction with an Ethereum wallet and turn it into a [SignedMessage]
+    /// the same way `eth_sendRawTransaction` does, returning the sender and the hash as well.
+    fn signed_eth_message(seed: u64, chain_id: u64) -> (et::Address, et::TxHash, SignedMessage) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let wallet = LocalWallet::new(&mut rng).with_chain_id(chain_id);
+
+        let tx: TypedTransaction = et::Eip1559TransactionRequest::new()
+            .chain_id(chain_id)
+            .from(wallet.address())
+            .to(et::H160::from(rng.gen::<[u8; 20]>()))
+            .nonce(rng.gen::<u32>())
+            .gas(rng.gen::<u32>())
+            .max_fee_per_gas(rng.gen::<u64>())
+            .max_priority_fee_per_gas(rng.gen::<u32>())
+            .value(rng.gen_range(1..u64::MAX))
+            .data(et::Bytes::from(rng.gen::<[u8; 16]>().to_vec()))
+            .into();
+
+        let sig = wallet.sign_transaction_sync(&tx).expect("failed to sign");
+        let bz = tx.rlp_signed(&sig);
+        let hash = et::TxHash::from(ethers_core::utils::keccak256(&bz));
+
+        let rlp = rlp::Rlp::new(bz.as_ref());
+        let (tx, sig) = TypedTransaction::decode_signed(&rlp).expect("failed to decode RLP");
+        let tx = tx.as_eip1559_ref().expect("not an eip1559 transaction");
+
+        let msg = SignedMessage {
+            message: to_fvm_message(tx).expect("failed to convert to FVM"),
+            signature: Signature::new_secp256k1(sig.to_vec()),
+        };
+
+        (wallet.address(), hash, msg)
+    }
 
     #[test]
     fn block_zero_can_be_created() {
@@ -619,6 +818,100 @@ mod tests {
 
     #[test]
     fn block_zero_can_be_turned_into_eth() {
-        let _ = to_eth_block_zero(BLOCK_ZERO.clone()).unwrap();
+        let _ = to_eth_block_zero(BLOCK_ZERO.clone(), EthBlockHashVersion::V1).unwrap();
+    }
+
+    /// Check that the transaction we return has all the fields populated to reproduce
+    /// the original Ethereum hash and to recover the sender from the signature.
+    #[quickcheck]
+    fn prop_to_eth_transaction_roundtrip(seed: u64, chain_id: u32) {
+        let chain_id = u64::from(chain_id);
+        let (from, hash, msg) = signed_eth_message(seed, chain_id);
+
+        let tx = to_eth_transaction(msg, ChainID::from(chain_id), hash)
+            .expect("failed to convert to eth transaction");
+
+        assert_eq!(tx.from, from);
+        assert_eq!(tx.chain_id, Some(et::U256::from(chain_id)));
+        assert_eq!(tx.hash(), hash, "RLP should reproduce the hash");
+        assert_eq!(tx.recover_from().expect("failed to recover"), from);
+    }
+
+    /// Check that the transactions are returned even if the results aren't available.
+    #[quickcheck]
+    fn prop_to_eth_block_without_results(seed: u64, chain_id: u32) {
+        let chain_id = u64::from(chain_id);
+        let (from, hash, msg) = signed_eth_message(seed, chain_id);
+        let bz = fvm_ipld_encoding::to_vec(&ChainMessage::Signed(msg)).unwrap();
+
+        let mut block = BLOCK_ZERO.clone();
+        block.header.height = tendermint::block::Height::try_from(1u64).unwrap();
+        block.data = vec![bz];
+        let block_hash = block.header.hash();
+
+        let block_results = tendermint_rpc::endpoint::block_results::Response {
+            height: block.header.height,
+            txs_results: None,
+            begin_block_events: None,
+            end_block_events: None,
+            validator_updates: Vec::new(),
+            consensus_param_updates: None,
+        };
+
+        let block = to_eth_block(
+            block,
+            block_results,
+            TokenAmount::zero(),
+            ChainID::from(chain_id),
+            EthBlockHashVersion::V1,
+            0,
+        )
+        .expect("failed to convert block");
+
+        let tm_hash = format!("0x{}", hex::encode(block_hash.as_bytes()));
+        assert_eq!(
+            block.hash,
+            Some(to_eth_block_hash(EthBlockHashVersion::V1, &block_hash))
+        );
+        assert_eq!(
+            block.parent_hash.0,
+            EthBlockHashVersion::V1.to_eth_block_hash(&BLOCK_ZERO_HASH)
+        );
+        assert_eq!(
+            block.other.get(TENDERMINT_HASH_FIELD),
+            Some(&serde_json::Value::String(tm_hash))
+        );
+
+        assert_eq!(block.transactions.len(), 1);
+        let tx = &block.transactions[0];
+        assert_eq!(tx.hash, hash);
+        assert_eq!(tx.from, from);
+        assert_eq!(tx.transaction_index, Some(et::U64::zero()));
+        assert_eq!(tx.block_number, Some(et::U64::one()));
+        assert_eq!(tx.block_hash, block.hash);
+    }
+
+    #[tokio::test]
+    async fn to_eth_address_prefers_delegated() {
+        let eth_addr = et::H160::from([1u8; 20]);
+        let delegated = Address::from(EthAddress(eth_addr.0));
+        let resolver = OneDelegated(100, delegated);
+
+        let resolve = |addr| {
+            let resolver = &resolver;
+            async move { to_eth_address_resolved(resolver, &addr).await.unwrap() }
+        };
+
+        // An actor created through the EAM, referred to by ID.
+        assert_eq!(resolve(Address::new_id(100)).await, Some(eth_addr));
+        // An actor without a delegated address is shown by its masked ID.
+        assert_eq!(
+            resolve(Address::new_id(101)).await,
+            Some(et::H160::from(EthAddress::from_id(101).0))
+        );
+        // Delegated addresses are returned as they are.
+        assert_eq!(resolve(delegated).await, Some(eth_addr));
+        // Deployments have no recipient.
+        assert_eq!(resolve(EAM_ACTOR_ADDR).await, None);
     }
 }
@@ -3,4 +3,5 @@
 
 pub mod from_eth;
 pub mod from_fvm;
+pub mod from_lotus;
 pub mod from_tm;
@@ -0,0 +1,94 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Let browsers on the configured origins call the API, e.g. wallets and dApps.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD,
+            ORIGIN, VARY,
+        },
+        HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Matches every origin.
+const ANY_ORIGIN: &str = "*";
+
+#[derive(Debug, Clone, Default)]
+pub struct CorsOpt {
+    /// Origins allowed to call the API from a browser, e.g. `https://app.example.com`,
+    /// or `*` for any; empty means cross-origin requests are not allowed.
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Cors {
+    opt: Arc<CorsOpt>,
+}
+
+impl Cors {
+    pub fn new(opt: CorsOpt) -> Self {
+        Self { opt: Arc::new(opt) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.opt.allowed_origins.is_empty()
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        self.opt
+            .allowed_origins
+            .iter()
+            .any(|o| o == origin || o == ANY_ORIGIN)
+    }
+}
+
+/// Answer the preflight requests, and tell the browser that the origin can read the response.
+///
+/// Requests from origins which aren't allowed are still served, it's the browser which
+/// keeps the response from them, so the limits apply the same way to every caller.
+pub async fn handle(State(cors): State<Cors>, req: Request<Body>, next: Next<Body>) -> Response {
+    let origin = req
+        .headers()
+        .get(ORIGIN)
+        .filter(|o| o.to_str().map(|o| cors.is_allowed(o)).unwrap_or_default())
+        .cloned();
+
+    let is_preflight = req.method() == Method::OPTIONS
+        && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = if is_preflight {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        next.run(req).await
+    };
+
+    let headers = response.headers_mut();
+    headers.append(VARY, HeaderValue::from_static("origin"));
+
+    if let Some(origin) = origin {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if is_preflight {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static("GET, POST, OPTIONS"),
+            );
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_static("authorization, content-type"),
+            );
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static("3600"));
+        }
+    }
+
+    response
+}
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use fvm_shared::error::ExitCode;
+use opentelemetry::trace::TraceContextExt;
 use serde::Serialize;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 #[derive(Debug, Clone)]
 pub struct JsonRpcError {
@@ -33,10 +35,11 @@ impl From<tendermint_rpc::Error> for JsonRpcError {
 
 impl From<JsonRpcError> for jsonrpc_v2::Error {
     fn from(value: JsonRpcError) -> Self {
+        let data = with_trace_id(value.data);
         Self::Full {
             code: value.code,
             message: value.message,
-            data: value.data.map(|d| {
+            data: data.map(|d| {
                 let d: Box<dyn erased_serde::Serialize + Send> = Box::new(d);
                 d
             }),
@@ -44,6 +47,65 @@ impl From<JsonRpcError> for jsonrpc_v2::Error {
     }
 }
 
+/// The ID of the trace the current request is part of, if traces are exported.
+pub fn current_trace_id() -> Option<String> {
+    let cx = tracing::Span::current().context();
+    let span = cx.span();
+    let sc = span.span_context();
+    if sc.is_valid() {
+        Some(sc.trace_id().to_string())
+    } else {
+        None
+    }
+}
+
+/// Add the trace ID to the error data, so the error can be correlated with the traces of the request.
+fn with_trace_id(data: Option<serde_json::Value>) -> Option<serde_json::Value> {
+    let trace_id = match current_trace_id() {
+        Some(id) => serde_json::Value::String(id),
+        None => return data,
+    };
+    match data {
+        None => Some(serde_json::json!({ "trace_id": trace_id })),
+        Some(serde_json::Value::Object(mut obj)) => {
+            obj.insert("trace_id".to_owned(), trace_id);
+            Some(serde_json::Value::Object(obj))
+        }
+        // Not changing the shape of existing data, e.g. the revert data of a failed call.
+        Some(data) => Some(data),
+    }
+}
+
+/// The error code Infura and Alchemy use when a query would return too much,
+/// so that clients know to split it into smaller ones.
+pub const LIMIT_EXCEEDED_CODE: i64 = -32005;
+
+pub fn limit_exceeded<T>(
+    msg: impl ToString,
+    data: Option<serde_json::Value>,
+) -> Result<T, JsonRpcError> {
+    Err(JsonRpcError {
+        code: LIMIT_EXCEEDED_CODE,
+        message: msg.to_string(),
+        data,
+    })
+}
+
+/// The error code of EIP-1474 for a resource which is not available, e.g. blocks
+/// the node doesn't have because it restored its state from a snapshot.
+pub const RESOURCE_UNAVAILABLE_CODE: i64 = -32002;
+
+pub fn resource_unavailable<T>(
+    msg: impl ToString,
+    data: Option<serde_json::Value>,
+) -> Result<T, JsonRpcError> {
+    Err(JsonRpcError {
+        code: RESOURCE_UNAVAILABLE_CODE,
+        message: msg.to_string(),
+        data,
+    })
+}
+
 pub fn error<T>(exit_code: ExitCode, msg: impl ToString) -> Result<T, JsonRpcError> {
     Err(JsonRpcError {
         code: exit_code.value().into(),
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     pin::Pin,
     sync::Arc,
     time::{Duration, Instant},
@@ -12,15 +12,21 @@ use anyhow::{anyhow, Context};
 use ethers_core::types as et;
 use fendermint_rpc::{client::FendermintClient, query::QueryClient};
 use fendermint_vm_actor_interface::eam::EthAddress;
-use fendermint_vm_message::{chain::ChainMessage, query::FvmQueryHeight, signed::DomainHash};
+use fendermint_vm_message::{
+    block_hash::EthBlockHashVersion,
+    chain::ChainMessage,
+    query::{bloom_contains, FvmQueryHeight},
+    signed::DomainHash,
+};
 use futures::{Future, StreamExt};
 use fvm_shared::{address::Address, chainid::ChainID, error::ExitCode};
 use lru_time_cache::LruCache;
 use serde::Serialize;
+use tendermint::block::Height;
 use tendermint_rpc::{
-    event::{Event, EventData},
+    event::{Event, EventData, TxInfo, TxResult},
     query::{EventType, Query},
-    Client, Subscription,
+    Client, Order, Subscription, SubscriptionClient,
 };
 use tokio::sync::{
     mpsc::{Receiver, Sender},
@@ -28,12 +34,19 @@ use tokio::sync::{
 };
 
 use crate::{
-    conv::from_tm::{self, find_hash_event, map_rpc_block_txs, msg_hash, tx_hash},
+    conv::from_tm::{
+        self, find_hash_event, map_rpc_block_txs, msg_hash, to_chain_message, tx_hash,
+    },
     error::JsonRpcError,
     handlers::ws::{MethodNotification, Notification},
-    state::{enrich_block, WebSocketSender},
+    metrics::{self, GaugeGuard},
+    state::{enrich_block, max_decompressed_size, WebSocketSender},
+    SubscriptionOpt,
 };
 
+/// Number of transactions to fetch at a time when re-fetching missed heights.
+const BACKFILL_PAGE_SIZE: u8 = 100;
+
 /// Check whether to keep a log according to the topic filter.
 ///
 /// A note on specifying topic filters: Topics are order-dependent.
@@ -60,6 +73,29 @@ pub fn matches_topics(filter: &et::Filter, log: &et::Log) -> bool {
     true
 }
 
+/// Check whether a block with the given logs bloom can contain logs matching the addresses and topics of a filter.
+///
+/// The addresses have to be in the same form as they are matched against transactions in `eth_getLogs`.
+pub fn matches_bloom(bloom: &et::Bloom, addrs: &HashSet<Address>, filter: &et::Filter) -> bool {
+    if !addrs.is_empty() && !addrs.iter().any(|a| bloom_contains(bloom, &a.to_bytes())) {
+        return false;
+    }
+    for topics in filter.topics.iter().flatten() {
+        let matches = match topics {
+            et::ValueOrArray::Value(Some(t)) => bloom_contains(bloom, t.as_bytes()),
+            et::ValueOrArray::Array(ts) => ts
+                .iter()
+                .flatten()
+                .any(|t| bloom_contains(bloom, t.as_bytes())),
+            _ => true,
+        };
+        if !matches {
+            return false;
+        }
+    }
+    true
+}
+
 pub type FilterId = et::U256;
 pub type FilterMap = Arc<RwLock<HashMap<FilterId, Sender<FilterCommand>>>>;
 
@@ -83,6 +119,11 @@ pub enum FilterKind {
 }
 
 impl FilterKind {
+    /// Indicate whether the filter subscribes to transaction events, as opposed to new blocks.
+    pub fn is_tx_query(&self) -> bool {
+        matches!(self, FilterKind::Logs(_))
+    }
+
     /// Convert an Ethereum filter to potentially multiple Tendermint queries.
     ///
     /// One limitation with Tendermint is that it only handles AND condition
@@ -233,6 +274,8 @@ where
         event: Event,
         to_block: F,
         chain_id: &ChainID,
+        version: EthBlockHashVersion,
+        max_decompressed_size: usize,
         filter: &Option<et::Filter>,
     ) -> anyhow::Result<()>
     where
@@ -255,7 +298,9 @@ where
                 },
             ) => {
                 for tx in &block.data {
-                    if let Ok(ChainMessage::Signed(msg)) = fvm_ipld_encoding::from_slice(tx) {
+                    if let Ok(ChainMessage::Signed(msg)) =
+                        to_chain_message(tx, max_decompressed_size)
+                    {
                         if let Ok(Some(DomainHash::Eth(h))) = msg.domain_hash(chain_id) {
                             hashes.push(et::TxHash::from(h))
                         }
@@ -307,8 +352,9 @@ where
                 // but other than that our only option is to query the Tendermint API. If we do that we should have caching,
                 // otherwise all the transactions in a block hammering the node will act like a DoS attack.
                 // Or we can add it to the indexed fields.
-                let block_hash =
-                    find_hash_event("block", &tx_result.result.events).unwrap_or_default();
+                let block_hash = find_hash_event("block", &tx_result.result.events)
+                    .map(|h| et::H256(version.to_eth_block_hash(&h.0)))
+                    .unwrap_or_default();
 
                 let block_number = et::U64::from(tx_result.height);
 
@@ -356,6 +402,7 @@ pub struct FilterDriver {
     kind: FilterKind,
     state: FilterState,
     rx: Receiver<FilterCommand>,
+    _active: GaugeGuard,
 }
 
 enum FilterState {
@@ -387,6 +434,8 @@ impl FilterDriver {
     ) -> (Self, Sender<FilterCommand>) {
         let (tx, rx) = tokio::sync::mpsc::channel(10);
 
+        let active = metrics::active_filter(&kind, ws_sender.is_some());
+
         let state = match ws_sender {
             Some(ws_sender) => FilterState::Subscription(SubscriptionState { ws_sender }),
             None => FilterState::Poll(PollState {
@@ -402,6 +451,7 @@ impl FilterDriver {
             kind,
             state,
             rx,
+            _active: active,
         };
 
         (r, tx)
@@ -428,6 +478,24 @@ impl FilterDriver {
             .await
             .map(|state_params| ChainID::from(state_params.value.chain_id));
 
+        // Neither does the derivation of the block hashes.
+        let version = client
+            .capabilities()
+            .await
+            .ok()
+            .flatten()
+            .map(|c| c.eth_block_hash_version())
+            .unwrap_or_default();
+
+        // Nor the size compressed transactions can expand to, which is set in the genesis.
+        let max_decompressed_size = match max_decompressed_size(&client).await {
+            Ok(size) => size,
+            Err(e) => {
+                tracing::warn!(?id, error = ?e, "failed to get the compression limit");
+                0
+            }
+        };
+
         // Logs need to be filtered by topics.
         let filter = if let FilterKind::Logs(ref filter) = self.kind {
             Some(filter.as_ref().to_owned())
@@ -474,12 +542,15 @@ impl FilterDriver {
                                             event,
                                             |block| {
                                                 Box::pin(async move {
-                                                    Ok(et::H256::from_slice(
-                                                        block.header().hash().as_bytes(),
+                                                    Ok(from_tm::to_eth_block_hash(
+                                                        version,
+                                                        &block.header().hash(),
                                                     ))
                                                 })
                                             },
                                             chain_id,
+                                            version,
+                                            max_decompressed_size,
                                             &filter,
                                         )
                                         .await
@@ -526,13 +597,21 @@ impl FilterDriver {
                                         |block| {
                                             let client = client.clone();
                                             Box::pin(async move {
-                                                let block = enrich_block(&client, block).await?;
+                                                let block = enrich_block(
+                                                    &client,
+                                                    block,
+                                                    version,
+                                                    max_decompressed_size,
+                                                )
+                                                .await?;
                                                 let block: anyhow::Result<et::Block<et::TxHash>> =
                                                     map_rpc_block_txs(block, |tx| Ok(tx.hash()));
                                                 block
                                             })
                                         },
                                         chain_id,
+                                        version,
+                                        max_decompressed_size,
                                         &filter,
                                     )
                                     .await
@@ -682,48 +761,442 @@ impl PollState {
 /// Spawn a Tendermint subscription handler in a new task.
 ///
 /// The subscription sends [Event] records to the driver over a channel.
-pub async fn run_subscription(id: FilterId, mut sub: Subscription, tx: Sender<FilterCommand>) {
-    let query = sub.query().to_string();
-    tracing::debug!(?id, query, "polling filter subscription");
-    while let Some(result) = sub.next().await {
-        match result {
-            Ok(event) => {
-                if tx.send(FilterCommand::Update(event)).await.is_err() {
-                    // Filter has been uninstalled.
+///
+/// If the subscription fails, for example because CometBFT was restarted, it tries to
+/// re-subscribe with the same query, and re-fetches the heights it might have missed
+/// in the meantime, so consumers don't silently lose blocks or logs. Sending to the
+/// driver is done over a bounded channel, so a slow consumer slows down the re-fetching
+/// as well, rather than everything getting buffered in memory.
+pub async fn run_subscription<C>(
+    id: FilterId,
+    mut sub: Subscription,
+    client: C,
+    tx: Sender<FilterCommand>,
+    is_tx_query: bool,
+    opt: SubscriptionOpt,
+) where
+    C: Client + SubscriptionClient + Send + Sync,
+{
+    let query = sub.query().clone();
+    let query_str = query.to_string();
+
+    // The highest block height we have seen events from, or the one we subscribed at,
+    // so that a gap before the first event is re-fetched as well.
+    let mut last_height: Option<u64> = match latest_height(&client).await {
+        Ok(height) => Some(height),
+        Err(e) => {
+            tracing::warn!(
+                ?id,
+                query = query_str,
+                error = ?e,
+                "failed to get the height the filter subscribed at"
+            );
+            None
+        }
+    };
+    // Events at or below this height have already been re-fetched after a gap.
+    let mut backfilled_height: u64 = 0;
+
+    loop {
+        tracing::debug!(?id, query = query_str, "polling filter subscription");
+        while let Some(result) = sub.next().await {
+            match result {
+                Ok(event) => {
+                    if let Some(height) = event_height(&event) {
+                        if height <= backfilled_height {
+                            continue;
+                        }
+                        last_height = Some(last_height.map_or(height, |h| h.max(height)));
+                    }
+                    if tx.send(FilterCommand::Update(event)).await.is_err() {
+                        // Filter has been uninstalled.
+                        tracing::debug!(
+                            ?id,
+                            query = query_str,
+                            "filter no longer listening, quiting subscription"
+                        );
+                        return;
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        ?id,
+                        query = query_str,
+                        error = ?err,
+                        "filter subscription error"
+                    );
+                    break;
+                }
+            }
+        }
+
+        // Dropping the `Subscription` should cause the client to unsubscribe,
+        // if this was the last one interested in that query; we don't have to
+        // call the unsubscribe method explicitly.
+        // See https://docs.rs/tendermint-rpc/0.31.1/tendermint_rpc/client/struct.WebSocketClient.html
+
+        sub = match resubscribe(id, &client, &query, &tx, &opt).await {
+            Some(sub) => sub,
+            None => return,
+        };
+
+        if let Some(from_height) = last_height {
+            let backfilled = match latest_height(&client).await {
+                Ok(to_height) => {
+                    backfill(
+                        &client,
+                        &query,
+                        is_tx_query,
+                        from_height,
+                        to_height,
+                        &tx,
+                        &opt,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            match backfilled {
+                Ok(Some(to_height)) => {
                     tracing::debug!(
                         ?id,
-                        query,
+                        query = query_str,
+                        from_height,
+                        to_height,
+                        "re-fetched missed heights"
+                    );
+                    backfilled_height = to_height;
+                    last_height = Some(to_height.max(from_height));
+                }
+                Ok(None) => {
+                    tracing::debug!(
+                        ?id,
+                        query = query_str,
                         "filter no longer listening, quiting subscription"
                     );
                     return;
                 }
+                Err(e) => {
+                    tracing::error!(
+                        ?id,
+                        query = query_str,
+                        error = ?e,
+                        "failed to re-fetch missed heights"
+                    );
+                    let err = tendermint_rpc::Error::client_internal(format!(
+                        "failed to re-fetch missed heights: {e:#}"
+                    ));
+                    let _ = tx.send(FilterCommand::Finish(Some(err))).await;
+                    return;
+                }
             }
-            Err(err) => {
-                tracing::error!(
+        }
+    }
+}
+
+/// Try to subscribe to the query again until it succeeds, or the filter is uninstalled.
+async fn resubscribe<C>(
+    id: FilterId,
+    client: &C,
+    query: &Query,
+    tx: &Sender<FilterCommand>,
+    opt: &SubscriptionOpt,
+) -> Option<Subscription>
+where
+    C: SubscriptionClient + Sync,
+{
+    loop {
+        if tx.is_closed() {
+            return None;
+        }
+
+        tokio::time::sleep(opt.resubscribe_delay).await;
+
+        match client.subscribe(query.clone()).await {
+            Ok(sub) => {
+                tracing::info!(?id, query = query.to_string(), "re-subscribed filter");
+                return Some(sub);
+            }
+            Err(e) => {
+                tracing::warn!(
                     ?id,
-                    query,
-                    error = ?err,
-                    "filter subscription error"
+                    query = query.to_string(),
+                    error = ?e,
+                    "failed to re-subscribe filter; retrying in {}s...",
+                    opt.resubscribe_delay.as_secs()
                 );
-                let _ = tx.send(FilterCommand::Finish(Some(err))).await;
-                return;
             }
         }
     }
-    tracing::debug!(?id, query, "filter subscription finished");
-    let _ = tx.send(FilterCommand::Finish(None)).await;
+}
+
+/// The latest block height known to CometBFT.
+async fn latest_height<C>(client: &C) -> anyhow::Result<u64>
+where
+    C: Client + Sync,
+{
+    let status = client
+        .status()
+        .await
+        .context("failed to get CometBFT status")?;
+
+    Ok(status.sync_info.latest_block_height.value())
+}
+
+/// Fetch the events from the heights after `from_height` up to `to_height`,
+/// which we might have missed while the subscription was down, and send them to the driver.
+///
+/// Returns the height up to which events have been sent, or `None` if the driver is gone.
+async fn backfill<C>(
+    client: &C,
+    query: &Query,
+    is_tx_query: bool,
+    from_height: u64,
+    to_height: u64,
+    tx: &Sender<FilterCommand>,
+    opt: &SubscriptionOpt,
+) -> anyhow::Result<Option<u64>>
+where
+    C: Client + Sync,
+{
+    if to_height <= from_height {
+        return Ok(Some(from_height));
+    }
+
+    let gap = to_height - from_height;
+    if gap > opt.max_gap {
+        return Err(anyhow!(
+            "missed {gap} heights, which is more than the maximum of {}",
+            opt.max_gap
+        ));
+    }
+
+    if is_tx_query {
+        let query = query
+            .clone()
+            .and_gt("tx.height", from_height)
+            .and_lte("tx.height", to_height);
+
+        let mut page = 1;
+        let mut sent = 0;
+        loop {
+            let res = client
+                .tx_search(
+                    query.clone(),
+                    false,
+                    page,
+                    BACKFILL_PAGE_SIZE,
+                    Order::Ascending,
+                )
+                .await
+                .context("failed to search transactions")?;
+
+            let num_txs = res.txs.len();
+
+            for tx_res in res.txs {
+                let event = Event {
+                    query: query.to_string(),
+                    data: EventData::Tx {
+                        tx_result: TxInfo {
+                            height: tx_res.height.value() as i64,
+                            index: Some(tx_res.index as i64),
+                            tx: tx_res.tx,
+                            result: TxResult {
+                                log: Some(tx_res.tx_result.log),
+                                gas_wanted: Some(tx_res.tx_result.gas_wanted.to_string()),
+                                gas_used: Some(tx_res.tx_result.gas_used.to_string()),
+                                events: tx_res.tx_result.events,
+                            },
+                        },
+                    },
+                    events: None,
+                };
+                if tx.send(FilterCommand::Update(event)).await.is_err() {
+                    return Ok(None);
+                }
+            }
+
+            sent += num_txs;
+            if num_txs == 0 || sent >= res.total_count as usize {
+                break;
+            }
+            page += 1;
+        }
+    } else {
+        for height in (from_height + 1)..=to_height {
+            let height = Height::try_from(height).context("invalid height")?;
+            let res = client
+                .block(height)
+                .await
+                .context("failed to fetch block")?;
+
+            let event = Event {
+                query: query.to_string(),
+                data: EventData::NewBlock {
+                    block: Some(res.block),
+                    result_begin_block: None,
+                    result_end_block: None,
+                },
+                events: None,
+            };
+            if tx.send(FilterCommand::Update(event)).await.is_err() {
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(Some(to_height))
+}
 
-    // Dropping the `Subscription` should cause the client to unsubscribe,
-    // if this was the last one interested in that query; we don't have to
-    // call the unsubscribe method explicitly.
-    // See https://docs.rs/tendermint-rpc/0.31.1/tendermint_rpc/client/struct.WebSocketClient.html
+/// The block height an event belongs to, if it's one we know how to get it from.
+fn event_height(event: &Event) -> Option<u64> {
+    match event.data {
+        EventData::NewBlock {
+            block: Some(ref block),
+            ..
+        } => Some(block.header().height.value()),
+        EventData::Tx { ref tx_result } => u64::try_from(tx_result.height).ok(),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use ethers_core::types as et;
+    use tendermint_rpc::{
+        event::EventData,
+        query::{EventType, Query},
+        Method, MockClient, MockRequestMethodMatcher, SubscriptionClient,
+    };
+
+    use super::{backfill, event_height, resubscribe, FilterCommand, FilterId, FilterKind};
+    use crate::SubscriptionOpt;
+
+    fn subscription_opt(max_gap: u64) -> SubscriptionOpt {
+        SubscriptionOpt {
+            resubscribe_delay: Duration::from_millis(10),
+            max_gap,
+        }
+    }
 
-    use super::FilterKind;
+    /// A `tx_search` response with a single transaction at the given height.
+    fn tx_search_response(height: u64) -> String {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "",
+            "result": {
+                "txs": [{
+                    "hash": "A".repeat(64),
+                    "height": height.to_string(),
+                    "index": 0,
+                    "tx_result": {
+                        "gas_wanted": "100",
+                        "gas_used": "50",
+                    },
+                    "tx": "AQID",
+                    "proof": null,
+                }],
+                "total_count": "1",
+            }
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn backfill_sends_missed_transactions() {
+        let matcher =
+            MockRequestMethodMatcher::default().map(Method::TxSearch, Ok(tx_search_response(7)));
+        let (client, _driver) = MockClient::new(matcher);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let query = Query::from(EventType::Tx);
+
+        let res = backfill(&client, &query, true, 5, 8, &tx, &subscription_opt(10))
+            .await
+            .unwrap();
+
+        assert_eq!(res, Some(8));
+        match rx.try_recv() {
+            Ok(FilterCommand::Update(event)) => {
+                assert!(matches!(event.data, EventData::Tx { .. }));
+                assert_eq!(event_height(&event), Some(7));
+            }
+            _ => panic!("expected a transaction event"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn backfill_skips_heights_already_seen() {
+        // Nothing is mapped, so any request to the client would fail.
+        let (client, _driver) = MockClient::new(MockRequestMethodMatcher::default());
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let query = Query::from(EventType::NewBlock);
+
+        for is_tx_query in [true, false] {
+            let res = backfill(
+                &client,
+                &query,
+                is_tx_query,
+                8,
+                8,
+                &tx,
+                &subscription_opt(10),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(res, Some(8));
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn backfill_fails_if_the_gap_is_too_large() {
+        let (client, _driver) = MockClient::new(MockRequestMethodMatcher::default());
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let query = Query::from(EventType::NewBlock);
+
+        let res = backfill(&client, &query, false, 5, 20, &tx, &subscription_opt(10)).await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn backfill_stops_if_the_filter_is_uninstalled() {
+        let matcher =
+            MockRequestMethodMatcher::default().map(Method::TxSearch, Ok(tx_search_response(7)));
+        let (client, _driver) = MockClient::new(matcher);
+        let (tx, rx) = tokio::sync::mpsc::channel(10);
+        let query = Query::from(EventType::Tx);
+        drop(rx);
+
+        let res = backfill(&client, &query, true, 5, 8, &tx, &subscription_opt(10))
+            .await
+            .unwrap();
+
+        assert_eq!(res, None);
+    }
+
+    #[tokio::test]
+    async fn resubscribe_until_the_filter_is_uninstalled() {
+        let (client, driver) = MockClient::new(MockRequestMethodMatcher::default());
+        let driver_handle = tokio::spawn(async move { driver.run().await });
+        let (tx, rx) = tokio::sync::mpsc::channel(10);
+        let query = Query::from(EventType::NewBlock);
+        let opt = subscription_opt(10);
+
+        let sub = resubscribe(FilterId::zero(), &client, &query, &tx, &opt).await;
+        assert!(sub.is_some());
+
+        drop(rx);
+        let sub = resubscribe(FilterId::zero(), &client, &query, &tx, &opt).await;
+        assert!(sub.is_none());
+
+        client.close();
+        let _ = driver_handle.await;
+    }
 
     #[test]
     fn default_filter_to_query() {
@@ -0,0 +1,23 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+
+use crate::AppState;
+
+/// Report whether the node is ready to serve requests, for load balancers and orchestrators.
+///
+/// Responds with `503 Service Unavailable` while the node is syncing, if the sync guard is enabled.
+pub async fn handle(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> impl IntoResponse {
+    let guard = &state.rpc_state.sync_guard;
+    let status = guard.status();
+    let code = if !guard.is_enabled() || status.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, axum::Json(status))
+}
@@ -4,11 +4,16 @@
 
 // Based on https://github.com/ChainSafe/forest/blob/v0.8.2/node/rpc/src/rpc_http_handler.rs
 
+use std::net::SocketAddr;
+
+use axum::extract::ConnectInfo;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use jsonrpc_v2::{RequestObject, ResponseObjects};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
+use super::{error_response, handle_call, RequestHead};
 use crate::{apis, AppState};
 
 type ResponseHeaders = [(&'static str, &'static str); 1];
@@ -27,18 +32,56 @@ pub enum RequestKind {
 }
 
 /// Handle JSON-RPC calls.
+///
+/// The body is parsed here rather than by an extractor, so that the IDs of requests
+/// rejected by the policies of the endpoint or the sync guard can be looked up for the error responses.
 pub async fn handle(
-    _headers: HeaderMap,
+    headers: HeaderMap,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     axum::extract::State(state): axum::extract::State<AppState>,
-    axum::Json(request): axum::Json<RequestKind>,
+    body: String,
 ) -> impl IntoResponse {
-    // NOTE: Any authorization can come here.
-    let response = match request {
+    let request = match serde_json::from_str::<RequestKind>(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                RESPONSE_HEADERS,
+                format!("failed to parse JSON-RPC request: {e}"),
+            )
+        }
+    };
+    let guard = &state.rpc_state.sync_guard;
+    let authn = state.auth.authenticate(&headers);
+    let check = |method: &str| {
+        state
+            .methods
+            .check(method)
+            .and_then(|()| state.rate_limiter.check(remote_addr.ip()))
+            .and_then(|()| state.auth.check(method, &authn))
+            .and_then(|()| guard.check(method))
+    };
+
+    match request {
         RequestKind::One(request) => {
             if let Err(response) = check_request(&request) {
                 return response;
             }
-            state.rpc_server.handle(request).await
+            if let Err(e) = check(request.method_ref()) {
+                let id = serde_json::from_str::<RequestHead>(&body)
+                    .map(|h| h.id)
+                    .unwrap_or_default();
+                return json_response(&error_response(id, e));
+            }
+            let span = tracing::info_span!("eth_rpc", method = request.method_ref());
+            match handle_call(&state.rpc_server, request)
+                .instrument(span)
+                .await
+            {
+                Ok(Some(json)) => (StatusCode::OK, RESPONSE_HEADERS, json),
+                Ok(None) => json_response(&ResponseObjects::Empty),
+                Err(e) => json_error(e),
+            }
         }
         RequestKind::Many(requests) => {
             for request in requests.iter() {
@@ -46,44 +89,71 @@ pub async fn handle(
                     return response;
                 }
             }
-            state.rpc_server.handle(requests).await
-        }
-    };
-    debug_response(&response);
-    json_response(&response)
-}
 
-fn debug_response(response: &ResponseObjects) {
-    let debug = |r| {
-        tracing::debug!(
-            response = serde_json::to_string(r).unwrap_or_else(|e| e.to_string()),
-            "RPC response"
-        );
-    };
-    match response {
-        ResponseObjects::Empty => {}
-        ResponseObjects::One(r) => {
-            debug(r);
-        }
-        ResponseObjects::Many(rs) => {
-            for r in rs {
-                debug(r);
+            let mut rejected = Vec::new();
+            let mut accepted = Vec::new();
+            let mut heads: Option<Vec<RequestHead>> = None;
+
+            for (i, request) in requests.into_iter().enumerate() {
+                match check(request.method_ref()) {
+                    Ok(()) => accepted.push(request),
+                    Err(e) => {
+                        let heads = heads
+                            .get_or_insert_with(|| serde_json::from_str(&body).unwrap_or_default());
+                        let id = heads.get(i).map(|h| h.id.clone()).unwrap_or_default();
+                        rejected.push(error_response(id, e));
+                    }
+                }
+            }
+
+            // The calls are handled one by one, rather than as a batch, so each is measured on its own.
+            let span = tracing::info_span!("eth_rpc_batch", size = accepted.len());
+            let calls = accepted
+                .into_iter()
+                .map(|request| handle_call(&state.rpc_server, request));
+            let handled = futures::future::join_all(calls).instrument(span).await;
+
+            let mut responses = Vec::new();
+            for response in handled {
+                match response {
+                    Ok(Some(json)) => responses.push(json),
+                    Ok(None) => {}
+                    Err(e) => return json_error(e),
+                }
+            }
+            for response in rejected {
+                match serde_json::to_string(&response) {
+                    Ok(json) => responses.push(json),
+                    Err(e) => return json_error(e),
+                }
+            }
+
+            if responses.is_empty() {
+                json_response(&ResponseObjects::Empty)
+            } else {
+                (
+                    StatusCode::OK,
+                    RESPONSE_HEADERS,
+                    format!("[{}]", responses.join(",")),
+                )
             }
         }
     }
 }
 
-fn json_response(response: &ResponseObjects) -> (StatusCode, ResponseHeaders, std::string::String) {
+fn json_response<T: Serialize>(response: &T) -> (StatusCode, ResponseHeaders, std::string::String) {
     match serde_json::to_string(response) {
         Ok(json) => (StatusCode::OK, RESPONSE_HEADERS, json),
-        Err(err) => {
-            let msg = err.to_string();
-            tracing::error!(error = msg, "RPC to JSON failure");
-            (StatusCode::INTERNAL_SERVER_ERROR, RESPONSE_HEADERS, msg)
-        }
+        Err(err) => json_error(err),
     }
 }
 
+fn json_error(err: serde_json::Error) -> (StatusCode, ResponseHeaders, std::string::String) {
+    let msg = err.to_string();
+    tracing::error!(error = msg, "RPC to JSON failure");
+    (StatusCode::INTERNAL_SERVER_ERROR, RESPONSE_HEADERS, msg)
+}
+
 fn check_request(
     request: &RequestObject,
 ) -> Result<(), (StatusCode, ResponseHeaders, std::string::String)> {
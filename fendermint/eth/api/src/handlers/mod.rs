@@ -1,5 +1,62 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::time::Instant;
+
+use jsonrpc_v2::{RequestObject, ResponseObjects, V2};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{error::JsonRpcError, metrics, JsonRpcServer};
+
+pub mod health;
 pub mod http;
 pub mod ws;
+
+/// The parts of a JSON-RPC request we look at before handing it to the server.
+///
+/// The [jsonrpc_v2::RequestObject] doesn't give access to the ID, which we need
+/// to be able to respond without involving the server.
+#[derive(Deserialize, Debug)]
+pub struct RequestHead {
+    #[serde(default)]
+    pub id: serde_json::Value,
+    pub method: String,
+}
+
+/// Handle a single call and serialize the response, recording the metrics of the method.
+///
+/// Returns `None` if the call was a notification, which doesn't get a response.
+pub async fn handle_call(
+    server: &JsonRpcServer,
+    request: RequestObject,
+) -> serde_json::Result<Option<String>> {
+    let method = request.method_ref().to_owned();
+    let started = Instant::now();
+
+    let response = server.handle(request).await;
+
+    if let ResponseObjects::Empty = response {
+        return Ok(None);
+    }
+
+    let json = serde_json::to_string(&response)?;
+
+    tracing::debug!(method, response = json, "RPC response");
+    metrics::observe_call(&method, started.elapsed(), &response, json.len());
+
+    Ok(Some(json))
+}
+
+/// Construct an error response to a request which the server isn't allowed to handle.
+pub fn error_response(id: serde_json::Value, err: JsonRpcError) -> serde_json::Value {
+    json!({
+        "jsonrpc": V2,
+        "error": {
+            "code": err.code,
+            "message": err.message,
+            "data": err.data,
+        },
+        "id": id
+    })
+}
@@ -4,19 +4,23 @@
 
 // Based on https://github.com/ChainSafe/forest/blob/v0.8.2/node/rpc/src/rpc_ws_handler.rs
 
+use std::net::SocketAddr;
+
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        ConnectInfo, WebSocketUpgrade,
     },
     http::HeaderMap,
     response::IntoResponse,
 };
 use futures::{stream::SplitSink, SinkExt, StreamExt};
-use jsonrpc_v2::{RequestObject, ResponseObject, ResponseObjects, V2};
+use jsonrpc_v2::{RequestObject, V2};
+use serde::Serialize;
 use serde_json::json;
 
-use crate::{apis, state::WebSocketId, AppState, JsonRpcServer};
+use super::{error_response, handle_call, RequestHead};
+use crate::{apis, auth::Authentication, metrics, state::WebSocketId, AppState, JsonRpcServer};
 
 /// Mirroring [ethers_providers::rpc::transports::ws::types::Notification], which is what the library
 /// expects for non-request-response payloads in [PubSubItem::deserialize].
@@ -33,20 +37,31 @@ pub struct MethodNotification {
     pub notification: Notification,
 }
 
+/// The connection is authenticated once, with the headers of the upgrade request.
 pub async fn handle(
-    _headers: HeaderMap,
+    headers: HeaderMap,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     axum::extract::State(state): axum::extract::State<AppState>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| async { rpc_ws_handler_inner(state, socket).await })
+    let authn = state.auth.authenticate(&headers);
+    ws.on_upgrade(move |socket| async move {
+        rpc_ws_handler_inner(state, authn, remote_addr, socket).await
+    })
 }
 
 /// Handle requests in a loop, interpreting each message as a JSON-RPC request.
 ///
 /// Messages are evaluated one by one. We could spawn tasks like Forest,
-/// but there should be some rate limiting applied to avoid DoS attacks.
-async fn rpc_ws_handler_inner(state: AppState, socket: WebSocket) {
+/// but the rate limit of the endpoint would have to account for them.
+async fn rpc_ws_handler_inner(
+    state: AppState,
+    authn: Authentication,
+    remote_addr: SocketAddr,
+    socket: WebSocket,
+) {
     tracing::debug!("Accepted WS connection!");
+    let _connection = metrics::ws_connection();
     let (mut sender, mut receiver) = socket.split();
 
     // Create a channel over which the application can send messages to this socket.
@@ -57,7 +72,7 @@ async fn rpc_ws_handler_inner(state: AppState, socket: WebSocket) {
     loop {
         let keep = tokio::select! {
             Some(Ok(message)) = receiver.next() => {
-                handle_incoming(web_socket_id, &state.rpc_server, &mut sender, message).await
+                handle_incoming(web_socket_id, &state, &authn, remote_addr, &mut sender, message).await
             },
             Some(notif) = notif_rx.recv() => {
                 handle_outgoing(web_socket_id, &mut sender, notif).await
@@ -78,7 +93,9 @@ async fn rpc_ws_handler_inner(state: AppState, socket: WebSocket) {
 /// Handle an incoming request.
 async fn handle_incoming(
     web_socket_id: WebSocketId,
-    rpc_server: &JsonRpcServer,
+    state: &AppState,
+    authn: &Authentication,
+    remote_addr: SocketAddr,
     sender: &mut SplitSink<WebSocket, Message>,
     message: Message,
 ) -> bool {
@@ -86,13 +103,26 @@ async fn handle_incoming(
         if !request_text.is_empty() {
             tracing::debug!(web_socket_id, request = request_text, "WS Request Received");
 
+            if let Ok(head) = serde_json::from_str::<RequestHead>(&request_text) {
+                let checked = state
+                    .methods
+                    .check(&head.method)
+                    .and_then(|()| state.rate_limiter.check(remote_addr.ip()))
+                    .and_then(|()| state.auth.check(&head.method, authn))
+                    .and_then(|()| state.rpc_state.sync_guard.check(&head.method));
+                if let Err(e) = checked {
+                    let response = error_response(head.id, e);
+                    return send_response(web_socket_id, sender, response).await;
+                }
+            }
+
             // We have to deserialize-add-reserialize becuase `JsonRpcRequest` can
             // only be parsed with `from_str`, not `from_value`.
             request_text = maybe_add_web_socket_id(request_text, web_socket_id);
 
             match serde_json::from_str::<RequestObject>(&request_text) {
                 Ok(req) => {
-                    return send_call_result(web_socket_id, rpc_server, sender, req).await;
+                    return send_call_result(web_socket_id, &state.rpc_server, sender, req).await;
                 }
                 Err(e) => {
                     deserialization_error("RequestObject", e);
@@ -190,39 +220,40 @@ async fn send_call_result(
 
     tracing::debug!("RPC WS called method: {}", method);
 
-    match server.handle(request).await {
-        ResponseObjects::Empty => true,
-        ResponseObjects::One(response) => send_response(web_socket_id, sender, response).await,
-        ResponseObjects::Many(responses) => {
-            for response in responses {
-                if !send_response(web_socket_id, sender, response).await {
-                    return false;
-                }
-            }
+    match handle_call(server, request).await {
+        Ok(None) => true,
+        Ok(Some(json)) => send_json(web_socket_id, sender, json).await,
+        Err(e) => {
+            tracing::error!(error=?e, "failed to serialize response to JSON");
             true
         }
     }
 }
 
-async fn send_response(
+async fn send_response<T: Serialize>(
     web_socket_id: WebSocketId,
     sender: &mut SplitSink<WebSocket, Message>,
-    response: ResponseObject,
+    response: T,
 ) -> bool {
-    let response = serde_json::to_string(&response);
-
-    match response {
+    match serde_json::to_string(&response) {
         Err(e) => {
             tracing::error!(error=?e, "failed to serialize response to JSON");
+            true
         }
-        Ok(json) => {
-            tracing::debug!(web_socket_id, json, "sending response to WS");
-            if let Err(e) = sender.send(Message::Text(json)).await {
-                tracing::warn!(web_socket_id, error=?e, "failed to send response to WS");
-                if is_closed_connection(e) {
-                    return false;
-                }
-            }
+        Ok(json) => send_json(web_socket_id, sender, json).await,
+    }
+}
+
+async fn send_json(
+    web_socket_id: WebSocketId,
+    sender: &mut SplitSink<WebSocket, Message>,
+    json: String,
+) -> bool {
+    tracing::debug!(web_socket_id, json, "sending response to WS");
+    if let Err(e) = sender.send(Message::Text(json)).await {
+        tracing::warn!(web_socket_id, error=?e, "failed to send response to WS");
+        if is_closed_connection(e) {
+            return false;
         }
     }
     true
@@ -1,26 +1,47 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use axum::routing::{get, post};
 use fvm_shared::econ::TokenAmount;
 use jsonrpc_v2::Data;
-use std::{net::ToSocketAddrs, sync::Arc, time::Duration};
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
 
 mod apis;
+mod auth;
 mod cache;
 mod client;
 mod conv;
+mod cors;
 mod error;
 mod filters;
 mod gas;
 mod handlers;
+mod metrics;
+mod paging;
+mod policy;
 mod state;
+mod sync;
 
-pub use client::{HybridClient, HybridClientDriver};
+pub use auth::AuthOpt;
+pub use client::{
+    HybridClient, HybridClientDriver, MempoolClient, MempoolHttpClient, UnconfirmedTxs,
+    MAX_UNCONFIRMED_TXS,
+};
+pub use cors::CorsOpt;
+pub use policy::{MethodsOpt, RateLimitOpt};
+pub use sync::SyncGuardOpt;
 
+use auth::Auth;
+use cors::Cors;
 use error::{error, JsonRpcError};
+use policy::{MethodPolicy, RateLimiter};
 use state::JsonRpcState;
+use sync::SyncGuard;
 
 /// This is passed to every method handler. It's generic in the client type to facilitate testing with mocks.
 type JsonRpcData<C> = Data<JsonRpcState<C>>;
@@ -28,10 +49,27 @@ type JsonRpcServer = Arc<jsonrpc_v2::Server<jsonrpc_v2::MapRouter>>;
 type JsonRpcResult<T> = Result<T, JsonRpcError>;
 
 /// This is the state we will pass to [axum] so that we can extract it in handlers.
+///
+/// The server and its state are shared by the endpoints, the policies are their own.
 #[derive(Clone)]
 pub struct AppState {
     pub rpc_server: JsonRpcServer,
     pub rpc_state: Arc<JsonRpcState<HybridClient>>,
+    pub auth: Auth,
+    pub methods: MethodPolicy,
+    pub rate_limiter: RateLimiter,
+}
+
+/// An address to serve the API on, with the policies applied to its callers.
+#[derive(Debug, Clone)]
+pub struct EndpointOpt {
+    /// Name of the endpoint in the logs.
+    pub name: String,
+    pub listen_addr: SocketAddr,
+    pub methods: MethodsOpt,
+    pub cors: CorsOpt,
+    pub rate_limit: RateLimitOpt,
+    pub auth: AuthOpt,
 }
 
 #[derive(Debug, Clone)]
@@ -41,35 +79,106 @@ pub struct GasOpt {
     pub max_fee_hist_size: u64,
 }
 
-/// Start listening to JSON-RPC requests.
-pub async fn listen<A: ToSocketAddrs>(
-    listen_addr: A,
+/// Options for keeping the CometBFT subscriptions behind filters alive.
+#[derive(Debug, Clone)]
+pub struct SubscriptionOpt {
+    /// Time to wait before trying to re-subscribe after a subscription failed.
+    pub resubscribe_delay: Duration,
+    /// Maximum number of block heights to re-fetch after re-subscribing;
+    /// if more were missed, the subscription is ended with an error.
+    pub max_gap: u64,
+}
+
+/// Limits on the size of responses, to protect the node from queries which would take too long.
+#[derive(Debug, Clone)]
+pub struct LimitsOpt {
+    /// Maximum number of logs `eth_getLogs` can return before failing.
+    pub max_logs: usize,
+    /// Maximum number of blocks a single log query can scan; 0 means no limit.
+    pub max_block_range: u64,
+    /// Maximum number of items on a page of the paginated `fendermint_*` methods.
+    pub max_page_size: usize,
+}
+
+/// Deviations from the standard Ethereum API which can be turned off for tools that reject them.
+#[derive(Debug, Clone, Default)]
+pub struct CompatOpt {
+    /// Leave out the `fvmExitCode` and `fvmFailureInfo` fields from transaction receipts.
+    pub strict_receipts: bool,
+}
+
+/// Start listening to JSON-RPC requests on all the endpoints.
+#[allow(clippy::too_many_arguments)]
+pub async fn listen(
+    endpoints: Vec<EndpointOpt>,
     client: HybridClient,
     filter_timeout: Duration,
     cache_capacity: usize,
     gas_opt: GasOpt,
+    sub_opt: SubscriptionOpt,
+    sync_opt: SyncGuardOpt,
+    limits: LimitsOpt,
+    compat: CompatOpt,
 ) -> anyhow::Result<()> {
-    if let Some(listen_addr) = listen_addr.to_socket_addrs()?.next() {
-        let rpc_state = Arc::new(JsonRpcState::new(
-            client,
-            filter_timeout,
-            cache_capacity,
-            gas_opt,
-        ));
-        let rpc_server = make_server(rpc_state.clone());
+    if endpoints.is_empty() {
+        return Err(anyhow!("no endpoints to serve the Ethereum API on"));
+    }
+
+    let sync_guard = SyncGuard::new(sync_opt);
+    tokio::spawn(sync_guard.clone().run(client.clone()));
+
+    let rpc_state = Arc::new(JsonRpcState::new(
+        client,
+        filter_timeout,
+        cache_capacity,
+        gas_opt,
+        sub_opt,
+        sync_guard,
+        limits,
+        compat,
+    ));
+    let rpc_server = make_server(rpc_state.clone());
+
+    let mut servers = Vec::new();
+
+    for endpoint in endpoints {
+        let name = endpoint.name;
+        let listen_addr = endpoint.listen_addr;
+
+        let auth = Auth::new(endpoint.auth);
+        if auth.is_enabled() {
+            tracing::info!(
+                endpoint = name,
+                "authentication of the protected Ethereum API methods enabled"
+            );
+        }
         let app_state = AppState {
-            rpc_server,
-            rpc_state,
+            rpc_server: rpc_server.clone(),
+            rpc_state: rpc_state.clone(),
+            auth,
+            methods: MethodPolicy::new(endpoint.methods),
+            rate_limiter: RateLimiter::new(endpoint.rate_limit),
         };
-        let router = make_router(app_state);
-        let server = axum::Server::try_bind(&listen_addr)?.serve(router.into_make_service());
+        let router = make_router(app_state, Cors::new(endpoint.cors));
+        // The address of the caller is needed for rate limiting.
+        let server = axum::Server::try_bind(&listen_addr)
+            .with_context(|| format!("failed to bind the {name} endpoint to {listen_addr}"))?
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>());
 
-        tracing::info!(?listen_addr, "bound Ethereum API");
-        server.await?;
-        Ok(())
-    } else {
-        Err(anyhow!("failed to convert to any socket address"))
+        tracing::info!(endpoint = name, ?listen_addr, "bound Ethereum API");
+        servers.push(server);
     }
+
+    futures::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+/// Resolve the address of an endpoint.
+pub fn to_socket_addr<A: ToSocketAddrs>(listen_addr: A) -> anyhow::Result<SocketAddr> {
+    listen_addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("failed to convert to any socket address"))
 }
 
 /// Register method handlers with the JSON-RPC server construct.
@@ -80,9 +189,16 @@ fn make_server(state: Arc<JsonRpcState<HybridClient>>) -> JsonRpcServer {
 }
 
 /// Register routes in the `axum` HTTP router to handle JSON-RPC and WebSocket calls.
-fn make_router(state: AppState) -> axum::Router {
-    axum::Router::new()
+fn make_router(state: AppState, cors: Cors) -> axum::Router {
+    let router = axum::Router::new()
         .route("/", post(handlers::http::handle))
         .route("/", get(handlers::ws::handle))
-        .with_state(state)
+        .route("/health", get(handlers::health::handle))
+        .with_state(state);
+
+    if cors.is_enabled() {
+        router.layer(axum::middleware::from_fn_with_state(cors, cors::handle))
+    } else {
+        router
+    }
 }
@@ -0,0 +1,133 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Metrics about the JSON-RPC calls and the WebSocket subscriptions served by the facade.
+//!
+//! They are registered with the default registry, so they are served on the metrics endpoint
+//! of the node along with everything else, and can be used to build dashboards of the public
+//! API without having to put a proxy in front of it.
+
+use std::time::Duration;
+
+use jsonrpc_v2::{ResponseObject, ResponseObjects};
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+use crate::filters::FilterKind;
+
+lazy_static! {
+    static ref RPC_LATENCY: HistogramVec = register_histogram_vec!(
+        "fendermint_eth_rpc_latency_seconds",
+        "Time it took to handle JSON-RPC calls, by method and outcome",
+        &["method", "outcome"],
+        exponential_buckets(0.001, 2.0, 16).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref RPC_RESPONSE_BYTES: HistogramVec = register_histogram_vec!(
+        "fendermint_eth_rpc_response_bytes",
+        "Size of the JSON-RPC responses, by method",
+        &["method"],
+        exponential_buckets(128.0, 4.0, 10).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref RPC_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_eth_rpc_errors_total",
+        "Number of JSON-RPC calls which returned an error, by method and error code",
+        &["method", "code"]
+    )
+    .expect("failed to register metric");
+    static ref WS_CONNECTIONS: IntGauge = register_int_gauge!(
+        "fendermint_eth_ws_connections",
+        "Number of open WebSocket connections"
+    )
+    .expect("failed to register metric");
+    static ref ACTIVE_FILTERS: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_eth_active_filters",
+        "Number of installed filters and WebSocket subscriptions, by kind and delivery",
+        &["kind", "delivery"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Error code of calls to methods which don't exist.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Record the outcome of a call once it has been handled and its response serialized.
+///
+/// Calls to methods which don't exist are all labeled as `unknown`, so that anyone
+/// sending junk cannot create new time series at will.
+pub fn observe_call(method: &str, elapsed: Duration, response: &ResponseObjects, size: usize) {
+    let code = match response {
+        ResponseObjects::One(ResponseObject::Error { error, .. }) => Some(error_code(error)),
+        _ => None,
+    };
+
+    let method = match code {
+        Some(METHOD_NOT_FOUND) => "unknown",
+        _ => method,
+    };
+
+    let outcome = if code.is_some() { "error" } else { "ok" };
+
+    RPC_LATENCY
+        .with_label_values(&[method, outcome])
+        .observe(elapsed.as_secs_f64());
+
+    RPC_RESPONSE_BYTES
+        .with_label_values(&[method])
+        .observe(size as f64);
+
+    if let Some(code) = code {
+        RPC_ERRORS
+            .with_label_values(&[method, &code.to_string()])
+            .inc();
+    }
+}
+
+/// The error type of [jsonrpc_v2] is only meant to be serialized, so that's how we get its code.
+fn error_code(error: &jsonrpc_v2::Error) -> i64 {
+    serde_json::to_value(error)
+        .ok()
+        .and_then(|e| e.get("code").and_then(|c| c.as_i64()))
+        .unwrap_or_default()
+}
+
+/// Count a WebSocket connection as open until the guard is dropped.
+pub fn ws_connection() -> GaugeGuard {
+    GaugeGuard::new(WS_CONNECTIONS.clone())
+}
+
+/// Count a filter as active until the guard is dropped.
+pub fn active_filter(kind: &FilterKind, is_subscription: bool) -> GaugeGuard {
+    let kind = match kind {
+        FilterKind::NewBlocks => "new_blocks",
+        FilterKind::PendingTransactions => "pending_transactions",
+        FilterKind::Logs(_) => "logs",
+    };
+    let delivery = if is_subscription {
+        "subscription"
+    } else {
+        "poll"
+    };
+    GaugeGuard::new(ACTIVE_FILTERS.with_label_values(&[kind, delivery]))
+}
+
+/// Increments a gauge when created and decrements it when dropped,
+/// so that it's kept accurate however a task ends.
+pub struct GaugeGuard(IntGauge);
+
+impl GaugeGuard {
+    fn new(gauge: IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
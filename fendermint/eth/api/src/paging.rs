@@ -0,0 +1,136 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cursor based pagination for the methods which can return more items than fit into a response.
+//!
+//! The cursor points at the first item of the next page with the block height and the index
+//! of the item within that height, e.g. the log index, so it stays valid as new blocks are
+//! added to the chain, and it's the same for every node.
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Position of an item in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub height: u64,
+    pub index: u64,
+}
+
+impl Cursor {
+    pub fn new(height: u64, index: u64) -> Self {
+        Self { height, index }
+    }
+}
+
+/// Cursors are opaque to clients; they are sent as 16 bytes in hexadecimal.
+impl Serialize for Cursor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut bz = [0u8; 16];
+        bz[..8].copy_from_slice(&self.height.to_be_bytes());
+        bz[8..].copy_from_slice(&self.index.to_be_bytes());
+        serializer.serialize_str(&format!("0x{}", hex::encode(bz)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let bz = hex::decode(s.trim_start_matches("0x"))
+            .map_err(|e| D::Error::custom(format!("invalid cursor: {e}")))?;
+        if bz.len() != 16 {
+            return Err(D::Error::custom("invalid cursor: unexpected length"));
+        }
+        let height = u64::from_be_bytes(bz[..8].try_into().unwrap());
+        let index = u64::from_be_bytes(bz[8..].try_into().unwrap());
+        Ok(Self { height, index })
+    }
+}
+
+/// A page of results, with the cursor to pass to get the next page, if there are more.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Where a page starts and how many items it should have at most; both are optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    pub cursor: Option<Cursor>,
+    pub limit: Option<usize>,
+}
+
+/// Parameters of a paginated method: the query, optionally followed by the [PageRequest].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PagedParams<T> {
+    One((T,)),
+    Two((T, PageRequest)),
+}
+
+impl<T> PagedParams<T> {
+    pub fn into_parts(self) -> (T, PageRequest) {
+        match self {
+            Self::One((query,)) => (query, PageRequest::default()),
+            Self::Two((query, page)) => (query, page),
+        }
+    }
+}
+
+/// Number of items to return on a page: what the client asked for, up to the configured maximum.
+pub fn page_size(requested: Option<usize>, max_page_size: usize) -> usize {
+    requested
+        .unwrap_or(max_page_size)
+        .clamp(1, max_page_size.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page_size, Cursor, PagedParams};
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = Cursor::new(1234, 56);
+        let json = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(json, "\"0x00000000000004d20000000000000038\"");
+        let back: Cursor = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, cursor);
+
+        assert!(serde_json::from_str::<Cursor>("\"0x1234\"").is_err());
+        assert!(serde_json::from_str::<Cursor>("\"not hex\"").is_err());
+    }
+
+    #[test]
+    fn paged_params_with_or_without_page() {
+        let params: PagedParams<u64> = serde_json::from_str("[1]").unwrap();
+        let (query, page) = params.into_parts();
+        assert_eq!(query, 1);
+        assert!(page.cursor.is_none());
+        assert!(page.limit.is_none());
+
+        let params: PagedParams<u64> = serde_json::from_str(
+            r#"[2, {"cursor": "0x00000000000000010000000000000002", "limit": 10}]"#,
+        )
+        .unwrap();
+        let (query, page) = params.into_parts();
+        assert_eq!(query, 2);
+        assert_eq!(page.cursor, Some(Cursor::new(1, 2)));
+        assert_eq!(page.limit, Some(10));
+    }
+
+    #[test]
+    fn page_size_is_capped() {
+        assert_eq!(page_size(None, 100), 100);
+        assert_eq!(page_size(Some(10), 100), 10);
+        assert_eq!(page_size(Some(1000), 100), 100);
+        assert_eq!(page_size(Some(0), 100), 1);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Which methods an endpoint serves, and how often its callers can call them.
+//!
+//! The same process can serve the API on several addresses, e.g. a restricted set of methods to the
+//! public, and everything including the debug methods to internal callers, each with its own policy.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::error::JsonRpcError;
+
+/// The error code of calls to methods which the endpoint doesn't serve.
+pub const METHOD_NOT_AVAILABLE_CODE: i64 = -32601;
+
+/// The error code of calls beyond the rate limit, the same as Infura uses.
+pub const RATE_LIMITED_CODE: i64 = -32005;
+
+/// Matches every namespace.
+const ALL_NAMESPACES: &str = "*";
+
+/// Above this many clients, the ones which are back to a full bucket are forgotten.
+const MAX_IDLE_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct MethodsOpt {
+    /// Namespaces, e.g. `eth`, or methods, e.g. `debug_traceTransaction`, which are served;
+    /// empty means all of them.
+    pub allow: Vec<String>,
+    /// Namespaces or methods which are not served, even if they are allowed.
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitOpt {
+    /// Number of calls per second each client IP address can make; 0 means no limit.
+    pub calls_per_second: u32,
+    /// Number of calls a client can make at once after being idle; 0 means the same as the rate.
+    pub burst: u32,
+}
+
+/// Decides which methods an endpoint serves.
+#[derive(Clone)]
+pub struct MethodPolicy {
+    opt: Arc<MethodsOpt>,
+}
+
+impl MethodPolicy {
+    pub fn new(opt: MethodsOpt) -> Self {
+        Self { opt: Arc::new(opt) }
+    }
+
+    pub fn is_allowed(&self, method: &str) -> bool {
+        (self.opt.allow.is_empty() || matches_any(&self.opt.allow, method))
+            && !matches_any(&self.opt.deny, method)
+    }
+
+    /// Check whether a method can be called on this endpoint.
+    pub fn check(&self, method: &str) -> Result<(), JsonRpcError> {
+        if self.is_allowed(method) {
+            Ok(())
+        } else {
+            Err(JsonRpcError {
+                code: METHOD_NOT_AVAILABLE_CODE,
+                message: format!("the method {method} is not available on this endpoint"),
+                data: None,
+            })
+        }
+    }
+}
+
+fn matches_any(patterns: &[String], method: &str) -> bool {
+    // The Lotus methods are namespaced like `Filecoin.MpoolPush`.
+    let namespace = method.split(['_', '.']).next().unwrap_or_default();
+    patterns
+        .iter()
+        .any(|p| p == method || p == namespace || p == ALL_NAMESPACES)
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Limits the rate of calls by the IP address of the caller, with a token bucket for each.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(opt: RateLimitOpt) -> Self {
+        let burst = if opt.burst == 0 {
+            opt.calls_per_second
+        } else {
+            opt.burst
+        };
+        Self {
+            rate: opt.calls_per_second as f64,
+            capacity: burst as f64,
+            buckets: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Take a token from the bucket of the client, if there is one.
+    fn acquire(&self, ip: IpAddr, now: Instant) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_IDLE_CLIENTS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, b| self.refill(b, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.capacity)
+    }
+
+    /// Check whether the client can make another call.
+    pub fn check(&self, ip: IpAddr) -> Result<(), JsonRpcError> {
+        if self.acquire(ip, Instant::now()) {
+            Ok(())
+        } else {
+            Err(JsonRpcError {
+                code: RATE_LIMITED_CODE,
+                message: "rate limit exceeded".to_owned(),
+                data: None,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    use super::{MethodPolicy, MethodsOpt, RateLimitOpt, RateLimiter};
+
+    #[test]
+    fn allowed_methods() {
+        let policy = MethodPolicy::new(MethodsOpt::default());
+        assert!(policy.is_allowed("debug_traceTransaction"));
+
+        let policy = MethodPolicy::new(MethodsOpt {
+            allow: vec!["eth".to_owned(), "net".to_owned(), "Filecoin".to_owned()],
+            deny: vec!["eth_sendRawTransaction".to_owned()],
+        });
+        assert!(policy.is_allowed("eth_blockNumber"));
+        assert!(policy.is_allowed("Filecoin.EthGetBlockByNumber"));
+        assert!(!policy.is_allowed("eth_sendRawTransaction"));
+        assert!(!policy.is_allowed("txpool_content"));
+
+        let policy = MethodPolicy::new(MethodsOpt {
+            allow: Vec::new(),
+            deny: vec!["*".to_owned()],
+        });
+        assert!(!policy.is_allowed("eth_chainId"));
+    }
+
+    #[test]
+    fn rate_limit_per_client() {
+        let limiter = RateLimiter::new(RateLimitOpt {
+            calls_per_second: 2,
+            burst: 3,
+        });
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.acquire(a, now));
+        }
+        assert!(!limiter.acquire(a, now));
+        assert!(limiter.acquire(b, now));
+
+        // Two calls per second means another one after half a second.
+        assert!(limiter.acquire(a, now + Duration::from_millis(500)));
+        assert!(!limiter.acquire(a, now + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn no_rate_limit() {
+        let limiter = RateLimiter::new(RateLimitOpt::default());
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.acquire(ip, now)));
+    }
+}
@@ -5,18 +5,26 @@
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context};
 use ethers_core::types::{self as et};
 use fendermint_rpc::client::{FendermintClient, TendermintClient};
 use fendermint_rpc::query::QueryClient;
-use fendermint_vm_actor_interface::{evm, system};
-use fendermint_vm_message::query::FvmQueryHeight;
+use fendermint_vm_actor_interface::{evm, system, txcompression};
+use fendermint_vm_message::block_hash::EthBlockHashVersion;
+use fendermint_vm_message::query::{
+    feature, Capabilities, FvmQueryHeight, BLOCK_HEIGHT_BY_HASH_PATH,
+    ETH_BLOCK_HEIGHT_BY_HASH_PATH, LOGS_BLOOM_PATH, STATE_SYNC_STATUS_PATH,
+};
 use fendermint_vm_message::signed::DomainHash;
 use fendermint_vm_message::{chain::ChainMessage, conv::from_eth::to_fvm_address};
 use fvm_ipld_encoding::{de::DeserializeOwned, RawBytes};
-use fvm_shared::{chainid::ChainID, econ::TokenAmount, error::ExitCode, message::Message};
+use fvm_shared::{
+    address::Address, chainid::ChainID, econ::TokenAmount, error::ExitCode, message::Message,
+};
+use lru_time_cache::LruCache;
 use rand::Rng;
 use tendermint::block::Height;
 use tendermint_rpc::query::Query;
@@ -26,24 +34,56 @@ use tendermint_rpc::{
 };
 use tendermint_rpc::{Order, Subscription, SubscriptionClient};
 use tokio::sync::mpsc::{Sender, UnboundedSender};
-use tokio::sync::RwLock;
+use tokio::sync::{OnceCell, RwLock};
 
 use crate::cache::AddressCache;
 use crate::conv::from_tm;
+use crate::error::resource_unavailable;
 use crate::filters::{
     run_subscription, BlockHash, FilterCommand, FilterDriver, FilterId, FilterKind, FilterMap,
     FilterRecords,
 };
 use crate::handlers::ws::MethodNotification;
-use crate::GasOpt;
+use crate::sync::SyncGuard;
 use crate::{
-    conv::from_tm::{map_rpc_block_txs, to_chain_message, to_eth_block, to_eth_transaction},
-    error, JsonRpcResult,
+    conv::from_tm::{
+        map_rpc_block_txs, resolve_eth_transaction, to_chain_message, to_eth_block,
+        to_eth_transaction,
+    },
+    error, JsonRpcError, JsonRpcResult,
 };
+use crate::{CompatOpt, GasOpt, LimitsOpt, SubscriptionOpt};
 
 pub type WebSocketId = usize;
 pub type WebSocketSender = UnboundedSender<MethodNotification>;
 
+/// What the block hash index of the application knows about a hash.
+enum IndexedHeight {
+    /// The node executed the block at this height.
+    Found(Height),
+    /// The node executed every block since the genesis, and this wasn't one of them.
+    NotFound,
+    /// The node only executed blocks since this height, so the block might be an earlier one.
+    BeforeIndex(Height),
+    /// The node doesn't have an index.
+    NoIndex,
+}
+
+/// Number of recent blocks to keep with their results, for explorers asking for the same ones over and over.
+const BLOCK_CACHE_CAPACITY: usize = 128;
+
+/// How long the features of the node are remembered for, so that an upgrade is noticed without a restart.
+const CAPABILITIES_TTL: Duration = Duration::from_secs(60);
+
+/// A block with its results and the parameters it was executed with,
+/// which is everything it takes to present it, or its receipts, in Ethereum format.
+pub struct BlockBundle {
+    pub block: tendermint::Block,
+    pub results: block_results::Response,
+    pub base_fee: TokenAmount,
+    pub chain_id: ChainID,
+}
+
 // Made generic in the client type so we can mock it if we want to test API
 // methods without having to spin up a server. In those tests the methods
 // below would not be used, so those aren't generic; we'd directly invoke
@@ -56,6 +96,17 @@ pub struct JsonRpcState<C> {
     next_web_socket_id: AtomicUsize,
     web_sockets: RwLock<HashMap<WebSocketId, WebSocketSender>>,
     pub gas_opt: GasOpt,
+    sub_opt: SubscriptionOpt,
+    /// Features of the node, with the time they were queried at, refreshed after [CAPABILITIES_TTL];
+    /// `None` if the node predates the query.
+    capabilities: RwLock<Option<(Instant, Option<Capabilities>)>>,
+    /// The size compressed transactions can expand to, queried once on first use, as it's set in the genesis.
+    max_decompressed_size: OnceCell<usize>,
+    pub sync_guard: SyncGuard,
+    pub limits: LimitsOpt,
+    pub compat: CompatOpt,
+    /// Committed blocks never change, so once fetched they can be kept until evicted.
+    block_cache: Mutex<LruCache<u64, Arc<BlockBundle>>>,
 }
 
 impl<C> JsonRpcState<C>
@@ -67,6 +118,10 @@ where
         filter_timeout: Duration,
         cache_capacity: usize,
         gas_opt: GasOpt,
+        sub_opt: SubscriptionOpt,
+        sync_guard: SyncGuard,
+        limits: LimitsOpt,
+        compat: CompatOpt,
     ) -> Self {
         let client = FendermintClient::new(client);
         let addr_cache = AddressCache::new(client.clone(), cache_capacity);
@@ -78,6 +133,13 @@ where
             next_web_socket_id: Default::default(),
             web_sockets: Default::default(),
             gas_opt,
+            sub_opt,
+            capabilities: Default::default(),
+            max_decompressed_size: OnceCell::new(),
+            sync_guard,
+            limits,
+            compat,
+            block_cache: Mutex::new(LruCache::with_capacity(BLOCK_CACHE_CAPACITY)),
         }
     }
 }
@@ -116,39 +178,220 @@ impl<C> JsonRpcState<C>
 where
     C: Client + Sync + Send,
 {
-    /// Get the Tendermint block at a specific height.
-    pub async fn block_by_height(
-        &self,
-        block_number: et::BlockNumber,
-    ) -> JsonRpcResult<tendermint::Block> {
-        let block = match block_number {
-            et::BlockNumber::Number(height) if height == et::U64::from(0) => {
-                from_tm::BLOCK_ZERO.clone()
+    /// Versions and optional features of the node we are talking to.
+    ///
+    /// They are cached for a while, but they can change when the node is upgraded.
+    pub async fn capabilities(&self) -> JsonRpcResult<Option<Capabilities>> {
+        if let Some((queried_at, ref caps)) = *self.capabilities.read().await {
+            if queried_at.elapsed() < CAPABILITIES_TTL {
+                return Ok(caps.clone());
             }
+        }
+        let caps = self.client.capabilities().await?;
+        *self.capabilities.write().await = Some((Instant::now(), caps.clone()));
+        Ok(caps)
+    }
+
+    /// The size compressed transactions can expand to on this chain; 0 if it doesn't accept them.
+    pub async fn max_decompressed_size(&self) -> JsonRpcResult<usize> {
+        let size = self
+            .max_decompressed_size
+            .get_or_try_init(|| max_decompressed_size(&self.client))
+            .await?;
+        Ok(*size)
+    }
+
+    /// The derivation of the block hashes to expose, which is the latest the node indexes.
+    pub async fn eth_block_hash_version(&self) -> JsonRpcResult<EthBlockHashVersion> {
+        Ok(self
+            .capabilities()
+            .await?
+            .map(|c| c.eth_block_hash_version())
+            .unwrap_or_default())
+    }
+
+    /// Number of snapshot chunks received and expected, if the node is restoring from a snapshot.
+    pub async fn state_sync_progress(&self) -> JsonRpcResult<Option<(u64, u64)>> {
+        let res = self
+            .tm()
+            .abci_query(
+                Some(STATE_SYNC_STATUS_PATH.to_owned()),
+                Vec::new(),
+                None,
+                false,
+            )
+            .await
+            .context("failed to query state-sync status")?;
+
+        if res.code.is_err() {
+            return Ok(None);
+        }
+
+        let progress: serde_json::Value =
+            serde_json::from_slice(&res.value).context("failed to parse state-sync status")?;
+
+        let received = progress.get("chunks_received").and_then(|v| v.as_u64());
+        let total = progress.get("chunks_total").and_then(|v| v.as_u64());
+
+        Ok(received.zip(total))
+    }
+
+    /// Logs blooms of the blocks in an inclusive range of heights; the node may return
+    /// fewer heights than asked for, and leaves out those it has no bloom for.
+    pub async fn logs_blooms(
+        &self,
+        from: Height,
+        to: Height,
+    ) -> JsonRpcResult<Vec<(Height, et::Bloom)>> {
+        let data = fvm_ipld_encoding::to_vec(&(from.value(), to.value()))
+            .context("failed to encode logs bloom range")?;
+
+        let res = self
+            .tm()
+            .abci_query(Some(LOGS_BLOOM_PATH.to_owned()), data, None, false)
+            .await
+            .context("failed to query logs blooms")?;
+
+        if res.code.is_err() {
+            return error(ExitCode::new(res.code.value()), res.info);
+        }
+
+        let blooms: Vec<(u64, RawBytes)> =
+            fvm_ipld_encoding::from_slice(&res.value).context("failed to decode logs blooms")?;
+
+        blooms
+            .into_iter()
+            .map(|(h, bz)| {
+                let h = Height::try_from(h).context("invalid height")?;
+                if bz.bytes().len() != et::Bloom::len_bytes() {
+                    return error(ExitCode::USR_SERIALIZATION, "invalid logs bloom length");
+                }
+                Ok((h, et::Bloom::from_slice(bz.bytes())))
+            })
+            .collect()
+    }
+
+    /// The latest height which is final and whose results are available.
+    ///
+    /// Blocks are final as soon as CometBFT commits them, so there are no reorgs, but the
+    /// latest committed block might still be executed by the application; the one before it
+    /// has definitely been executed.
+    pub async fn finalized_height(&self) -> JsonRpcResult<Height> {
+        let commit: commit::Response = self.tm().latest_commit().await?;
+        let height = commit.signed_header.header.height.value();
+        let height = Height::try_from(height.saturating_sub(1).max(1))
+            .context("failed to convert to height")?;
+        Ok(height)
+    }
+
+    /// The height of the block a block number refers to.
+    pub async fn block_height(&self, block_number: et::BlockNumber) -> JsonRpcResult<Height> {
+        match block_number {
             et::BlockNumber::Number(height) => {
                 let height =
                     Height::try_from(height.as_u64()).context("failed to convert to height")?;
-                let res: block::Response = self.tm().block(height).await?;
-                res.block
+                Ok(height)
             }
             et::BlockNumber::Finalized
             | et::BlockNumber::Latest
             | et::BlockNumber::Safe
             | et::BlockNumber::Pending => {
-                // Using 1 block less than latest so if this is followed up by `block_results` then we don't get an error.
-                let commit: commit::Response = self.tm().latest_commit().await?;
-                let height = commit.signed_header.header.height.value();
-                let height = Height::try_from((height.saturating_sub(1)).max(1))
-                    .context("failed to convert to height")?;
-                let res: block::Response = self.tm().block(height).await?;
-                res.block
+                // Using the finalized height rather than the latest, so if this is followed up by `block_results` then we don't get an error.
+                self.finalized_height().await
             }
-            et::BlockNumber::Earliest => {
-                let res: block::Response = self.tm().block(Height::from(1u32)).await?;
-                res.block
+            et::BlockNumber::Earliest => Ok(Height::from(1u32)),
+        }
+    }
+
+    /// Get the Tendermint block at a specific height.
+    pub async fn block_by_height(
+        &self,
+        block_number: et::BlockNumber,
+    ) -> JsonRpcResult<tendermint::Block> {
+        if let et::BlockNumber::Number(height) = block_number {
+            if height.is_zero() {
+                return Ok(from_tm::BLOCK_ZERO.clone());
             }
+        }
+        let height = self.block_height(block_number).await?;
+        let res: block::Response = self.tm().block(height).await?;
+        Ok(res.block)
+    }
+
+    /// Get a block along with its results and parameters.
+    ///
+    /// Rather than asking for them one after the other, the queries are sent at the same time,
+    /// and the answers are cached, so a block asked for repeatedly is only fetched once.
+    pub async fn block_bundle_by_height(&self, height: Height) -> JsonRpcResult<Arc<BlockBundle>> {
+        if let Some(bundle) = self.cached_block(height) {
+            return Ok(bundle);
+        }
+
+        let fetch_block = async {
+            let res: block::Response = self.tm().block(height).await?;
+            Ok::<_, JsonRpcError>(res.block)
         };
-        Ok(block)
+
+        let (block, results, (base_fee, chain_id)) = tokio::try_join!(
+            fetch_block,
+            self.fetch_block_results(height),
+            self.fetch_block_params(height)
+        )?;
+
+        Ok(self.cache_block(BlockBundle {
+            block,
+            results,
+            base_fee,
+            chain_id,
+        }))
+    }
+
+    /// Get the results and the parameters of a block which has already been fetched, e.g. by its hash.
+    pub async fn block_bundle(&self, block: tendermint::Block) -> JsonRpcResult<Arc<BlockBundle>> {
+        let height = block.header().height;
+
+        if let Some(bundle) = self.cached_block(height) {
+            return Ok(bundle);
+        }
+
+        let (results, (base_fee, chain_id)) = tokio::try_join!(
+            self.fetch_block_results(height),
+            self.fetch_block_params(height)
+        )?;
+
+        Ok(self.cache_block(BlockBundle {
+            block,
+            results,
+            base_fee,
+            chain_id,
+        }))
+    }
+
+    async fn fetch_block_results(&self, height: Height) -> JsonRpcResult<block_results::Response> {
+        let res: block_results::Response = self.tm().block_results(height).await?;
+        Ok(res)
+    }
+
+    /// The base fee and the chain ID the block was executed with.
+    async fn fetch_block_params(&self, height: Height) -> JsonRpcResult<(TokenAmount, ChainID)> {
+        let res = self
+            .client
+            .state_params(FvmQueryHeight::Height(height.value()))
+            .await?;
+        Ok((res.value.base_fee, ChainID::from(res.value.chain_id)))
+    }
+
+    fn cached_block(&self, height: Height) -> Option<Arc<BlockBundle>> {
+        let mut guard = self.block_cache.lock().expect("block cache poisoned");
+        guard.get(&height.value()).cloned()
+    }
+
+    fn cache_block(&self, bundle: BlockBundle) -> Arc<BlockBundle> {
+        let height = bundle.block.header().height.value();
+        let bundle = Arc::new(bundle);
+        let mut guard = self.block_cache.lock().expect("block cache poisoned");
+        guard.insert(height, bundle.clone());
+        bundle
     }
 
     /// Get the Tendermint header at a specific height.
@@ -166,10 +409,13 @@ where
                 let res: header::Response = self.tm().header(height).await?;
                 res.header
             }
-            et::BlockNumber::Finalized
-            | et::BlockNumber::Latest
-            | et::BlockNumber::Safe
-            | et::BlockNumber::Pending => {
+            et::BlockNumber::Finalized | et::BlockNumber::Safe => {
+                // Same as the block, so the tags point at the same height everywhere.
+                let height = self.finalized_height().await?;
+                let res: header::Response = self.tm().header(height).await?;
+                res.header
+            }
+            et::BlockNumber::Latest | et::BlockNumber::Pending => {
                 // `.latest_commit()` actually points at the block before the last one,
                 // because the commit is attached to the next block.
                 // Not using `.latest_block().header` because this is a lighter query.
@@ -201,9 +447,11 @@ where
         match block_id {
             et::BlockId::Number(bn) => match bn {
                 et::BlockNumber::Number(height) => Ok(FvmQueryHeight::from(height.as_u64())),
-                et::BlockNumber::Finalized | et::BlockNumber::Latest | et::BlockNumber::Safe => {
-                    Ok(FvmQueryHeight::Committed)
+                et::BlockNumber::Finalized | et::BlockNumber::Safe => {
+                    let height = self.finalized_height().await?;
+                    Ok(FvmQueryHeight::Height(height.value()))
                 }
+                et::BlockNumber::Latest => Ok(FvmQueryHeight::Committed),
                 et::BlockNumber::Pending => Ok(FvmQueryHeight::Pending),
                 et::BlockNumber::Earliest => Ok(FvmQueryHeight::Height(1)),
             },
@@ -215,29 +463,156 @@ where
     }
 
     /// Get a Tendermint block by hash, if it exists.
+    ///
+    /// Fails if the node doesn't have the history the block could be in.
     pub async fn block_by_hash_opt(
         &self,
         block_hash: et::H256,
     ) -> JsonRpcResult<Option<tendermint::block::Block>> {
-        if block_hash.0 == *from_tm::BLOCK_ZERO_HASH {
+        let version = self.eth_block_hash_version().await?;
+        if from_tm::is_block_zero_hash(version, &block_hash) {
             return Ok(Some(from_tm::BLOCK_ZERO.clone()));
         }
-        let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
-        let res: block_by_hash::Response = self.tm().block_by_hash(hash).await?;
-        Ok(res.block)
+        match self.indexed_block_height(block_hash, version).await? {
+            IndexedHeight::Found(height) => match self.tm().block(height).await {
+                Ok(res) => Ok(Some(res.block)),
+                Err(e) => {
+                    self.ensure_block_available(block_hash, height).await?;
+                    Err(e.into())
+                }
+            },
+            IndexedHeight::NotFound => Ok(None),
+            // CometBFT doesn't know about the derived hashes.
+            IndexedHeight::BeforeIndex(first_height) if version != EthBlockHashVersion::V0 => {
+                history_unavailable(block_hash, first_height)
+            }
+            IndexedHeight::BeforeIndex(first_height) => {
+                let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
+                let res: block_by_hash::Response = self.tm().block_by_hash(hash).await?;
+                match res.block {
+                    Some(block) => Ok(Some(block)),
+                    None => history_unavailable(block_hash, first_height),
+                }
+            }
+            IndexedHeight::NoIndex => {
+                let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
+                let res: block_by_hash::Response = self.tm().block_by_hash(hash).await?;
+                Ok(res.block)
+            }
+        }
     }
 
     /// Get a Tendermint height by hash, if it exists.
+    ///
+    /// Fails if the node doesn't have the history the block could be in.
     pub async fn header_by_hash_opt(
         &self,
         block_hash: et::H256,
     ) -> JsonRpcResult<Option<tendermint::block::Header>> {
-        if block_hash.0 == *from_tm::BLOCK_ZERO_HASH {
+        let version = self.eth_block_hash_version().await?;
+        if from_tm::is_block_zero_hash(version, &block_hash) {
             return Ok(Some(from_tm::BLOCK_ZERO.header.clone()));
         }
-        let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
-        let res: header_by_hash::Response = self.tm().header_by_hash(hash).await?;
-        Ok(res.header)
+        match self.indexed_block_height(block_hash, version).await? {
+            IndexedHeight::Found(height) => match self.tm().header(height).await {
+                Ok(res) => Ok(Some(res.header)),
+                Err(e) => {
+                    self.ensure_block_available(block_hash, height).await?;
+                    Err(e.into())
+                }
+            },
+            IndexedHeight::NotFound => Ok(None),
+            // CometBFT doesn't know about the derived hashes.
+            IndexedHeight::BeforeIndex(first_height) if version != EthBlockHashVersion::V0 => {
+                history_unavailable(block_hash, first_height)
+            }
+            IndexedHeight::BeforeIndex(first_height) => {
+                let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
+                let res: header_by_hash::Response = self.tm().header_by_hash(hash).await?;
+                match res.header {
+                    Some(header) => Ok(Some(header)),
+                    None => history_unavailable(block_hash, first_height),
+                }
+            }
+            IndexedHeight::NoIndex => {
+                let hash = tendermint::Hash::Sha256(*block_hash.as_fixed_bytes());
+                let res: header_by_hash::Response = self.tm().header_by_hash(hash).await?;
+                Ok(res.header)
+            }
+        }
+    }
+
+    /// Look up the height of a block in the block hash index of the application
+    /// which has the hashes of the given version.
+    async fn indexed_block_height(
+        &self,
+        block_hash: et::H256,
+        version: EthBlockHashVersion,
+    ) -> JsonRpcResult<IndexedHeight> {
+        let path = match version {
+            EthBlockHashVersion::V0 => {
+                // Older nodes don't have the index.
+                let has_index = self
+                    .capabilities()
+                    .await?
+                    .map(|c| c.has_feature(feature::BLOCK_HASH_INDEX))
+                    .unwrap_or_default();
+
+                if !has_index {
+                    return Ok(IndexedHeight::NoIndex);
+                }
+                BLOCK_HEIGHT_BY_HASH_PATH
+            }
+            EthBlockHashVersion::V1 => ETH_BLOCK_HEIGHT_BY_HASH_PATH,
+        };
+
+        let res = self
+            .tm()
+            .abci_query(
+                Some(path.to_owned()),
+                block_hash.as_bytes().to_vec(),
+                None,
+                false,
+            )
+            .await
+            .context("failed to query block height by hash")?;
+
+        if res.code.is_err() {
+            return error(ExitCode::new(res.code.value()), res.info);
+        }
+
+        let (height, first_height): (Option<u64>, Option<u64>) =
+            fvm_ipld_encoding::from_slice(&res.value).context("failed to decode block height")?;
+
+        let to_height = |h: u64| Height::try_from(h).context("invalid height");
+
+        match (height, first_height) {
+            (Some(h), _) => Ok(IndexedHeight::Found(to_height(h)?)),
+            (None, Some(f)) if f > 1 => Ok(IndexedHeight::BeforeIndex(to_height(f)?)),
+            // Before the first block is committed there is nothing to find.
+            (None, _) => Ok(IndexedHeight::NotFound),
+        }
+    }
+
+    /// Fail with a defined error if CometBFT doesn't have a block the node executed,
+    /// because it restored from a snapshot or pruned its blocks since.
+    async fn ensure_block_available(
+        &self,
+        block_hash: et::H256,
+        height: Height,
+    ) -> JsonRpcResult<()> {
+        let status = self.tm().status().await?;
+        let earliest = status.sync_info.earliest_block_height;
+        if height < earliest {
+            return resource_unavailable(
+                format!("block {block_hash} at height {height} is not available on this node; the earliest block is at {earliest}"),
+                Some(serde_json::json!({
+                    "height": height.value(),
+                    "earliest_height": earliest.value(),
+                })),
+            );
+        }
+        Ok(())
     }
 
     /// Get a Tendermint header by hash.
@@ -254,16 +629,47 @@ where
         }
     }
 
-    /// Fetch transaction results to produce the full block.
+    /// Produce the full block from a bundle, resolving the addresses in the transactions if needed.
     pub async fn enrich_block(
         &self,
-        block: tendermint::Block,
+        bundle: &BlockBundle,
         full_tx: bool,
     ) -> JsonRpcResult<et::Block<serde_json::Value>>
     where
         C: Client + Sync + Send,
     {
-        let block = enrich_block(&self.client, block).await?;
+        let max_decompressed_size = self.max_decompressed_size().await?;
+
+        // The same messages `to_eth_block` turns into transactions, in the same order.
+        let msgs = bundle
+            .block
+            .data()
+            .iter()
+            .filter_map(|tx| match to_chain_message(tx, max_decompressed_size) {
+                Ok(ChainMessage::Signed(msg)) => Some(msg.message),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut block = to_eth_block(
+            bundle.block.clone(),
+            bundle.results.clone(),
+            bundle.base_fee.clone(),
+            bundle.chain_id,
+            self.eth_block_hash_version().await?,
+            max_decompressed_size,
+        )
+        .context("failed to convert to eth block")?;
+
+        if full_tx {
+            let resolved = block
+                .transactions
+                .iter_mut()
+                .zip(msgs.iter())
+                .map(|(tx, msg)| resolve_eth_transaction(&self.addr_cache, msg, tx));
+
+            futures::future::try_join_all(resolved).await?;
+        }
 
         let block = if full_tx {
             map_rpc_block_txs(block, serde_json::to_value).context("failed to convert to JSON")?
@@ -282,12 +688,12 @@ where
         index: et::U64,
     ) -> JsonRpcResult<Option<et::Transaction>> {
         if let Some(msg) = block.data().get(index.as_usize()) {
-            let msg = to_chain_message(msg)?;
+            let msg = to_chain_message(msg, self.max_decompressed_size().await?)?;
 
             if let ChainMessage::Signed(msg) = msg {
                 let sp = self
                     .client
-                    .state_params(FvmQueryHeight::from(index.as_u64()))
+                    .state_params(FvmQueryHeight::Height(block.header.height.value()))
                     .await?;
 
                 let chain_id = ChainID::from(sp.value.chain_id);
@@ -298,10 +704,15 @@ where
                     return error(ExitCode::USR_ILLEGAL_ARGUMENT, "incompatible transaction");
                 };
 
+                let fvm_msg = msg.message.clone();
                 let mut tx = to_eth_transaction(msg, chain_id, hash)
                     .context("failed to convert to eth transaction")?;
+                resolve_eth_transaction(&self.addr_cache, &fvm_msg, &mut tx).await?;
                 tx.transaction_index = Some(index);
-                tx.block_hash = Some(et::H256::from_slice(block.header.hash().as_bytes()));
+                tx.block_hash = Some(from_tm::to_eth_block_hash(
+                    self.eth_block_hash_version().await?,
+                    &block.header.hash(),
+                ));
                 tx.block_number = Some(et::U64::from(block.header.height.value()));
                 Ok(Some(tx))
             } else {
@@ -439,6 +850,7 @@ where
             subs.push(sub);
         }
 
+        let is_tx_query = kind.is_tx_query();
         let (state, tx) = self.insert_filter_driver(kind, ws_sender).await;
         let id = state.id();
         let filters = self.filters.clone();
@@ -448,7 +860,9 @@ where
 
         for sub in subs {
             let tx = tx.clone();
-            tokio::spawn(async move { run_subscription(id, sub, tx).await });
+            let tm = self.tm().clone();
+            let opt = self.sub_opt.clone();
+            tokio::spawn(async move { run_subscription(id, sub, tm, tx, is_tx_query, opt).await });
         }
 
         Ok(id)
@@ -509,6 +923,8 @@ impl<C> JsonRpcState<C> {
 pub async fn enrich_block<C>(
     client: &FendermintClient<C>,
     block: tendermint::Block,
+    version: EthBlockHashVersion,
+    max_decompressed_size: usize,
 ) -> JsonRpcResult<et::Block<et::Transaction>>
 where
     C: Client + Sync + Send,
@@ -524,8 +940,55 @@ where
 
     let block_results: block_results::Response = client.underlying().block_results(height).await?;
 
-    let block = to_eth_block(block, block_results, base_fee, chain_id)
-        .context("failed to convert to eth block")?;
+    let block = to_eth_block(
+        block,
+        block_results,
+        base_fee,
+        chain_id,
+        version,
+        max_decompressed_size,
+    )
+    .context("failed to convert to eth block")?;
 
     Ok(block)
 }
+
+/// Look up the size compressed transactions can expand to in the state of the chain;
+/// 0 if the genesis didn't enable them.
+pub async fn max_decompressed_size<C>(client: &FendermintClient<C>) -> anyhow::Result<usize>
+where
+    C: Client + Sync + Send,
+{
+    let res = client
+        .actor_state(
+            &Address::new_id(txcompression::TX_COMPRESSION_ACTOR_ID),
+            FvmQueryHeight::Committed,
+        )
+        .await?;
+
+    let state = match res.value {
+        Some((_, state)) => state,
+        None => return Ok(0),
+    };
+
+    let bz = client
+        .ipld(&state.state, FvmQueryHeight::Committed)
+        .await?
+        .ok_or_else(|| anyhow!("transaction compression state not found"))?;
+
+    let state: txcompression::State =
+        fvm_ipld_encoding::from_slice(&bz).context("failed to decode compression state")?;
+
+    Ok(state.max_decompressed_size as usize)
+}
+
+/// Fail with a defined error for a block hash the node can't tell anything about,
+/// because it only has the blocks since it restored its state from a snapshot.
+fn history_unavailable<T>(block_hash: et::H256, first_height: Height) -> JsonRpcResult<T> {
+    resource_unavailable(
+        format!(
+            "block {block_hash} not found; this node only has blocks since height {first_height}"
+        ),
+        Some(serde_json::json!({ "earliest_height": first_height.value() })),
+    )
+}
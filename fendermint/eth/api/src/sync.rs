@@ -0,0 +1,207 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Keep track of whether the node has caught up with the rest of the network,
+//! so that we don't serve misleading data while it's restoring a snapshot or
+//! replaying blocks.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tendermint_rpc::{endpoint::status, Client};
+
+use crate::error::JsonRpcError;
+
+/// The error code other Ethereum clients use when they can't serve a request yet.
+pub const NODE_SYNCING_CODE: i64 = -32002;
+
+/// Methods which are meaningful even when the node is still syncing.
+const EXEMPT_METHODS: &[&str] = &[
+    "eth_chainId",
+    "eth_syncing",
+    "net_listening",
+    "net_peerCount",
+    "net_version",
+    "web3_clientVersion",
+    "web3_sha3",
+];
+
+#[derive(Debug, Clone)]
+pub struct SyncGuardOpt {
+    /// Whether to reject requests while the node is syncing.
+    pub enabled: bool,
+    /// Maximum age of the latest block while CometBFT is catching up,
+    /// for the node to still be considered ready to serve requests.
+    pub max_lag: Duration,
+    /// Time between checking the status of CometBFT.
+    pub check_interval: Duration,
+}
+
+/// The last observed sync status, also returned by the health endpoint.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncStatus {
+    pub ready: bool,
+    pub catching_up: bool,
+    pub latest_block_height: u64,
+    /// Seconds since the latest block was produced.
+    pub lag_secs: Option<u64>,
+    /// The reason the status couldn't be checked.
+    pub error: Option<String>,
+}
+
+/// Shared view of the sync status, updated by a background task.
+#[derive(Clone)]
+pub struct SyncGuard {
+    opt: SyncGuardOpt,
+    status: Arc<RwLock<SyncStatus>>,
+}
+
+impl SyncGuard {
+    pub fn new(opt: SyncGuardOpt) -> Self {
+        // Until the first check, assume the node is syncing if the guard is enabled.
+        let status = SyncStatus {
+            ready: !opt.enabled,
+            ..Default::default()
+        };
+        Self {
+            opt,
+            status: Arc::new(RwLock::new(status)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.opt.enabled
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Check whether a method can be called in the current sync status.
+    pub fn check(&self, method: &str) -> Result<(), JsonRpcError> {
+        if !self.opt.enabled || EXEMPT_METHODS.contains(&method) {
+            return Ok(());
+        }
+        let status = self.status();
+        if status.ready {
+            return Ok(());
+        }
+        Err(JsonRpcError {
+            code: NODE_SYNCING_CODE,
+            message: "node is syncing".to_owned(),
+            data: serde_json::to_value(status).ok(),
+        })
+    }
+
+    /// Poll the status of CometBFT until the process exits.
+    pub async fn run<C>(self, client: C)
+    where
+        C: Client + Send + Sync,
+    {
+        if !self.opt.enabled {
+            return;
+        }
+        loop {
+            let status = match client.status().await {
+                Ok(status) => self.to_sync_status(status),
+                Err(e) => {
+                    tracing::debug!(error = e.to_string(), "failed to check the sync status");
+                    SyncStatus {
+                        ready: false,
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    }
+                }
+            };
+
+            let was_ready = self.status.read().unwrap().ready;
+            if was_ready != status.ready {
+                tracing::info!(
+                    ready = status.ready,
+                    height = status.latest_block_height,
+                    "sync status changed"
+                );
+            }
+            *self.status.write().unwrap() = status;
+
+            tokio::time::sleep(self.opt.check_interval).await;
+        }
+    }
+
+    fn to_sync_status(&self, status: status::Response) -> SyncStatus {
+        let info = status.sync_info;
+        let latest_block_height = info.latest_block_height.value();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        let lag_secs = if latest_block_height == 0 {
+            None
+        } else {
+            Some(
+                now.saturating_sub(info.latest_block_time.unix_timestamp())
+                    .max(0) as u64,
+            )
+        };
+
+        SyncStatus {
+            ready: is_ready(
+                info.catching_up,
+                latest_block_height,
+                lag_secs,
+                self.opt.max_lag,
+            ),
+            catching_up: info.catching_up,
+            latest_block_height,
+            lag_secs,
+            error: None,
+        }
+    }
+}
+
+/// A node which is catching up is still considered ready if it's only slightly behind;
+/// one which doesn't have any blocks yet, e.g. because it's restoring a snapshot, is not.
+fn is_ready(catching_up: bool, height: u64, lag_secs: Option<u64>, max_lag: Duration) -> bool {
+    if height == 0 {
+        return false;
+    }
+    if !catching_up {
+        return true;
+    }
+    match lag_secs {
+        Some(lag) => lag <= max_lag.as_secs(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{is_ready, SyncGuard, SyncGuardOpt, NODE_SYNCING_CODE};
+
+    #[test]
+    fn readiness() {
+        let max_lag = Duration::from_secs(30);
+        assert!(!is_ready(true, 0, None, max_lag));
+        assert!(!is_ready(false, 0, None, max_lag));
+        assert!(is_ready(false, 10, Some(1000), max_lag));
+        assert!(is_ready(true, 10, Some(10), max_lag));
+        assert!(!is_ready(true, 10, Some(100), max_lag));
+    }
+
+    #[test]
+    fn syncing_until_checked() {
+        let guard = SyncGuard::new(SyncGuardOpt {
+            enabled: true,
+            max_lag: Duration::from_secs(30),
+            check_interval: Duration::from_secs(1),
+        });
+        let err = guard.check("eth_getBalance").unwrap_err();
+        assert_eq!(err.code, NODE_SYNCING_CODE);
+        assert!(guard.check("eth_syncing").is_ok());
+    }
+}
@@ -2,11 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use anyhow::{anyhow, bail, Context};
+use ethers_core::abi::Abi;
 use ethers_core::types as et;
 use serde::Deserialize;
 use std::{
     cmp::Ord,
-    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     hash::Hash,
     path::{Path, PathBuf},
 };
@@ -96,6 +97,38 @@ impl Hardhat {
         Ok(bytecode)
     }
 
+    /// Read the ABI of the contract from the build artifact.
+    pub fn abi(&self, contract_src: impl AsRef<Path>, contract_name: &str) -> anyhow::Result<Abi> {
+        let artifact = self.artifact(contract_src.as_ref(), contract_name)?;
+        Ok(artifact.abi)
+    }
+
+    /// Check that the ABI in the build artifact of a contract is the same as the one we expect,
+    /// typically coming from the generated bindings, so that drift between the contracts we deploy
+    /// and the methods we call on them is detected up front rather than as reverts at runtime.
+    pub fn check_abi(
+        &self,
+        contract_src: impl AsRef<Path>,
+        contract_name: &str,
+        expected: &Abi,
+    ) -> anyhow::Result<()> {
+        let fqn = self.fqn(contract_src.as_ref(), contract_name);
+        let actual = self
+            .abi(&contract_src, contract_name)
+            .with_context(|| format!("failed to load ABI of {fqn}"))?;
+
+        let diff = AbiDiff::new(expected, &actual);
+
+        if !diff.is_empty() {
+            bail!(
+                "the ABI of {fqn} in the build artifacts differs from the bindings; missing: {:?}, unexpected: {:?}",
+                diff.missing,
+                diff.unexpected
+            )
+        }
+        Ok(())
+    }
+
     /// Traverse the linked references and return the library contracts to be deployed in topological order.
     ///
     /// The result will include the top contracts as well, and it's up to the caller to filter them out if
@@ -170,8 +203,49 @@ impl Hardhat {
     }
 }
 
+/// Difference between the function and event signatures of two versions of an ABI.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AbiDiff {
+    /// Signatures we expect but the other ABI doesn't have.
+    pub missing: Vec<String>,
+    /// Signatures the other ABI has but we don't expect.
+    pub unexpected: Vec<String>,
+}
+
+impl AbiDiff {
+    pub fn new(expected: &Abi, actual: &Abi) -> Self {
+        let expected = abi_signatures(expected);
+        let actual = abi_signatures(actual);
+        Self {
+            missing: expected.difference(&actual).cloned().collect(),
+            unexpected: actual.difference(&expected).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Human readable signatures of all functions and events in an ABI, ordered for stable reporting.
+fn abi_signatures(abi: &Abi) -> BTreeSet<String> {
+    let functions = abi
+        .functions()
+        .map(|f| format!("function {}", f.signature()));
+    let events = abi.events().map(|e| {
+        let params = e
+            .inputs
+            .iter()
+            .map(|p| p.kind.to_string())
+            .collect::<Vec<_>>();
+        format!("event {}({})", e.name, params.join(","))
+    });
+    functions.chain(events).collect()
+}
+
 #[derive(Deserialize)]
 struct Artifact {
+    pub abi: Abi,
     pub bytecode: Bytecode,
 }
 
@@ -254,7 +328,7 @@ mod tests {
     use std::path::{Path, PathBuf};
     use std::str::FromStr;
 
-    use crate::{topo_sort, DependencyTree};
+    use crate::{topo_sort, AbiDiff, DependencyTree};
 
     use super::Hardhat;
 
@@ -405,4 +479,28 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn abi_diffing() {
+        let parse = |json: &str| serde_json::from_str::<ethers_core::abi::Abi>(json).unwrap();
+
+        let expected = parse(
+            r#"[
+              {"type": "function", "name": "foo", "inputs": [{"name": "a", "type": "uint256"}], "outputs": [], "stateMutability": "nonpayable"},
+              {"type": "event", "name": "Bar", "inputs": [{"name": "b", "type": "address", "indexed": true}], "anonymous": false}
+            ]"#,
+        );
+        // `foo` changed its parameter type, which changes its selector.
+        let actual = parse(
+            r#"[
+              {"type": "function", "name": "foo", "inputs": [{"name": "a", "type": "uint64"}], "outputs": [], "stateMutability": "nonpayable"},
+              {"type": "event", "name": "Bar", "inputs": [{"name": "b", "type": "address", "indexed": true}], "anonymous": false}
+            ]"#,
+        );
+
+        assert!(AbiDiff::new(&expected, &expected).is_empty());
+
+        let diff = AbiDiff::new(&expected, &actual);
+        assert_eq!(diff.missing, vec!["function foo(uint256)".to_owned()]);
+        assert_eq!(diff.unexpected, vec!["function foo(uint64)".to_owned()]);
+    }
 }
@@ -5,9 +5,12 @@ use std::sync::Arc;
 use anyhow::anyhow;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
-use rocksdb::{BoundColumnFamily, OptimisticTransactionDB, WriteBatchWithTransaction};
+use rocksdb::{
+    BoundColumnFamily, DBWithThreadMode, MultiThreaded, OptimisticTransactionDB,
+    WriteBatchWithTransaction,
+};
 
-use crate::RocksDb;
+use crate::{RocksDb, RocksDbSecondary};
 
 impl Blockstore for RocksDb {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
@@ -94,3 +97,36 @@ impl Blockstore for NamespaceBlockstore {
         Ok(self.db.write(batch)?)
     }
 }
+
+/// A read-only [`Blockstore`] over a namespace of a secondary database instance.
+#[derive(Clone)]
+pub struct SecondaryBlockstore {
+    db: Arc<DBWithThreadMode<MultiThreaded>>,
+    ns: String,
+}
+
+impl SecondaryBlockstore {
+    pub fn new(db: RocksDbSecondary, ns: String) -> anyhow::Result<Self> {
+        if !db.has_cf_handle(&ns) {
+            Err(anyhow!("namespace {ns} does not exist!"))
+        } else {
+            Ok(Self { db: db.db, ns })
+        }
+    }
+
+    fn cf(&self) -> anyhow::Result<Arc<BoundColumnFamily>> {
+        self.db
+            .cf_handle(&self.ns)
+            .ok_or_else(|| anyhow!("namespace {} does not exist!", self.ns))
+    }
+}
+
+impl Blockstore for SecondaryBlockstore {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.db.get_cf(&self.cf()?, k.to_bytes())?)
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        Err(anyhow!("the secondary database instance is read-only"))
+    }
+}
@@ -9,7 +9,9 @@ use fendermint_storage::KVWritable;
 use fendermint_storage::KVWrite;
 use fendermint_storage::{KVError, KVRead, KVReadable, KVStore};
 use rocksdb::BoundColumnFamily;
+use rocksdb::DBWithThreadMode;
 use rocksdb::ErrorKind;
+use rocksdb::MultiThreaded;
 use rocksdb::OptimisticTransactionDB;
 use rocksdb::SnapshotWithThreadMode;
 use rocksdb::Transaction;
@@ -19,16 +21,33 @@ use std::mem::ManuallyDrop;
 use std::sync::Arc;
 use std::thread;
 
-use crate::RocksDb;
+use crate::{RocksDb, RocksDbSecondary};
+
+/// Column family lookup, which the primary and the secondary databases both support.
+trait ColumnFamilies {
+    fn cf_handle(&self, name: &str) -> Option<Arc<BoundColumnFamily<'_>>>;
+}
+
+impl ColumnFamilies for OptimisticTransactionDB {
+    fn cf_handle(&self, name: &str) -> Option<Arc<BoundColumnFamily<'_>>> {
+        OptimisticTransactionDB::cf_handle(self, name)
+    }
+}
+
+impl ColumnFamilies for DBWithThreadMode<MultiThreaded> {
+    fn cf_handle(&self, name: &str) -> Option<Arc<BoundColumnFamily<'_>>> {
+        DBWithThreadMode::<MultiThreaded>::cf_handle(self, name)
+    }
+}
 
 /// Cache column families to avoid further cloning on each access.
-struct ColumnFamilyCache<'a> {
-    db: &'a OptimisticTransactionDB,
+struct ColumnFamilyCache<'a, D = OptimisticTransactionDB> {
+    db: &'a D,
     cfs: RefCell<BTreeMap<String, Arc<BoundColumnFamily<'a>>>>,
 }
 
-impl<'a> ColumnFamilyCache<'a> {
-    fn new(db: &'a OptimisticTransactionDB) -> Self {
+impl<'a, D: ColumnFamilies> ColumnFamilyCache<'a, D> {
+    fn new(db: &'a D) -> Self {
         Self {
             db,
             cfs: Default::default(),
@@ -72,6 +91,15 @@ pub struct RocksDbWriteTx<'a> {
     tx: ManuallyDrop<Transaction<'a, OptimisticTransactionDB>>,
 }
 
+/// Transaction on a secondary instance.
+///
+/// Secondary instances don't support snapshots, but their view of the data only
+/// changes when they catch up with the primary, so reads are consistent in between.
+/// Writes are rejected, and so there is nothing to commit or roll back.
+pub struct RocksDbSecondaryTx<'a> {
+    cache: ColumnFamilyCache<'a, DBWithThreadMode<MultiThreaded>>,
+}
+
 impl<'a> RocksDbWriteTx<'a> {
     // This method takes the transaction without running the panicky destructor.
     fn take_tx(self) -> Transaction<'a, OptimisticTransactionDB> {
@@ -140,6 +168,94 @@ where
     }
 }
 
+impl<S> KVReadable<S> for RocksDbSecondary
+where
+    S: KVStore<Repr = Vec<u8>>,
+    S::Namespace: AsRef<str>,
+{
+    type Tx<'a> = RocksDbSecondaryTx<'a>
+    where
+        Self: 'a;
+
+    fn read(&self) -> Self::Tx<'_> {
+        RocksDbSecondaryTx {
+            cache: ColumnFamilyCache::new(&self.db),
+        }
+    }
+}
+
+impl<S> KVWritable<S> for RocksDbSecondary
+where
+    S: KVStore<Repr = Vec<u8>>,
+    S::Namespace: AsRef<str>,
+{
+    type Tx<'a> = RocksDbSecondaryTx<'a>
+    where
+        Self: 'a;
+
+    fn write(&self) -> Self::Tx<'_> {
+        RocksDbSecondaryTx {
+            cache: ColumnFamilyCache::new(&self.db),
+        }
+    }
+}
+
+impl<'a, S> KVRead<S> for RocksDbSecondaryTx<'a>
+where
+    S: KVStore<Repr = Vec<u8>>,
+    S::Namespace: AsRef<str>,
+{
+    fn get<K, V>(&self, ns: &S::Namespace, k: &K) -> KVResult<Option<V>>
+    where
+        S: Encode<K> + Decode<V>,
+    {
+        self.cache.with_cf_handle(ns.as_ref(), |cf| {
+            let key = S::to_repr(k)?;
+
+            let res = self
+                .cache
+                .db
+                .get_cf(cf, key.as_ref())
+                .map_err(to_kv_error)?;
+
+            match res {
+                Some(bz) => Ok(Some(S::from_repr(&bz)?)),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+impl<'a, S> KVWrite<S> for RocksDbSecondaryTx<'a>
+where
+    S: KVStore<Repr = Vec<u8>>,
+    S::Namespace: AsRef<str>,
+{
+    fn put<K, V>(&mut self, _ns: &S::Namespace, _k: &K, _v: &V) -> KVResult<()>
+    where
+        S: Encode<K> + Encode<V>,
+    {
+        Err(read_only_error())
+    }
+
+    fn delete<K>(&mut self, _ns: &S::Namespace, _k: &K) -> KVResult<()>
+    where
+        S: Encode<K>,
+    {
+        Err(read_only_error())
+    }
+}
+
+impl<'a> KVTransaction for RocksDbSecondaryTx<'a> {
+    fn commit(self) -> KVResult<()> {
+        Ok(())
+    }
+
+    fn rollback(self) -> KVResult<()> {
+        Ok(())
+    }
+}
+
 impl<'a, S> KVRead<S> for RocksDbWriteTx<'a>
 where
     S: KVStore<Repr = Vec<u8>>,
@@ -217,6 +333,10 @@ impl<'a> Drop for RocksDbWriteTx<'a> {
     }
 }
 
+fn read_only_error() -> KVError {
+    KVError::Unexpected(anyhow!("the secondary database instance is read-only").into())
+}
+
 fn to_kv_error(e: rocksdb::Error) -> KVError {
     if e.kind() == ErrorKind::Busy {
         KVError::Conflict
@@ -26,7 +26,78 @@ pub struct RocksDbConfig {
     pub stats_dump_period_sec: u32,
     pub log_level: String,
     pub optimize_filters_for_hits: bool,
+    /// Size of the block cache in MiB, unless `block_cache_size` is set; negative to leave the table options alone.
     pub optimize_for_point_lookup: i32,
+    /// Size of the block cache in bytes.
+    #[serde(default)]
+    pub block_cache_size: Option<usize>,
+    /// Number of `memtable`s to fill up before writes stall while they are flushed.
+    #[serde(default)]
+    pub max_write_buffer_number: Option<i32>,
+    /// Compression of each level, from L0 down; `compression_type` applies to all levels if empty.
+    #[serde(default)]
+    pub compression_per_level: Vec<String>,
+    /// Bits per key of the bloom filters on the blocks; 0 disables them.
+    #[serde(default = "default_bloom_filter_bits")]
+    pub bloom_filter_bits: u32,
+}
+
+fn default_bloom_filter_bits() -> u32 {
+    10
+}
+
+/// Presets for the kind of workload the node has.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RocksDbProfile {
+    /// Keeps up with the chain with moderate memory, writing the state of every block
+    /// and pruning the history.
+    #[default]
+    Validator,
+    /// Serves lots of queries, so it trades memory for a large block cache.
+    RpcHeavy,
+    /// Keeps the full history, so it favours write throughput and stronger compression of the cold levels.
+    Archive,
+}
+
+impl RocksDbConfig {
+    /// The options of a profile, on top of the defaults.
+    pub fn for_profile(profile: RocksDbProfile) -> Self {
+        const MIB: usize = 1024 * 1024;
+        let parallelism = num_cpus::get() as i32;
+        let base = Self {
+            compaction_style: "level".into(),
+            ..Default::default()
+        };
+        match profile {
+            RocksDbProfile::Validator => Self {
+                write_buffer_size: 256 * MIB,
+                max_write_buffer_number: Some(4),
+                block_cache_size: Some(512 * MIB),
+                max_background_jobs: Some(parallelism.clamp(2, 4)),
+                ..base
+            },
+            RocksDbProfile::RpcHeavy => Self {
+                write_buffer_size: 128 * MIB,
+                max_write_buffer_number: Some(3),
+                block_cache_size: Some(4096 * MIB),
+                max_background_jobs: Some(parallelism.clamp(2, 6)),
+                ..base
+            },
+            RocksDbProfile::Archive => Self {
+                write_buffer_size: 512 * MIB,
+                max_write_buffer_number: Some(6),
+                block_cache_size: Some(1024 * MIB),
+                max_background_jobs: Some(parallelism.clamp(2, 8)),
+                // The top levels are rewritten often, so don't spend time compressing them.
+                compression_per_level: ["none", "none", "lz4", "lz4", "lz4", "lz4hc", "lz4hc"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                ..base
+            },
+        }
+    }
 }
 
 impl Default for RocksDbConfig {
@@ -44,6 +115,10 @@ impl Default for RocksDbConfig {
             log_level: "warn".into(),
             optimize_filters_for_hits: true,
             optimize_for_point_lookup: 8,
+            block_cache_size: None,
+            max_write_buffer_number: None,
+            compression_per_level: Vec::new(),
+            bloom_filter_bits: default_bloom_filter_bits(),
         }
     }
 }
@@ -56,6 +131,10 @@ impl From<&RocksDbConfig> for Options {
         db_opts.set_write_buffer_size(config.write_buffer_size);
         db_opts.set_max_open_files(config.max_open_files);
 
+        if let Some(max_write_buffer_number) = config.max_write_buffer_number {
+            db_opts.set_max_write_buffer_number(max_write_buffer_number);
+        }
+
         if let Some(max_background_jobs) = config.max_background_jobs {
             db_opts.set_max_background_jobs(max_background_jobs);
         }
@@ -67,6 +146,14 @@ impl From<&RocksDbConfig> for Options {
             db_opts.set_disable_auto_compactions(true);
         }
         db_opts.set_compression_type(compression_type_from_str(&config.compression_type).unwrap());
+        if !config.compression_per_level.is_empty() {
+            let levels = config
+                .compression_per_level
+                .iter()
+                .map(|c| compression_type_from_str(c).unwrap())
+                .collect::<Vec<_>>();
+            db_opts.set_compression_per_level(&levels);
+        }
         if config.enable_statistics {
             db_opts.set_stats_dump_period_sec(config.stats_dump_period_sec);
             db_opts.enable_statistics();
@@ -75,14 +162,18 @@ impl From<&RocksDbConfig> for Options {
         db_opts.set_optimize_filters_for_hits(config.optimize_filters_for_hits);
         // Comes from https://github.com/facebook/rocksdb/blob/main/options/options.cc#L606
         // Only modified to upgrade format to v5
-        if !config.optimize_for_point_lookup.is_negative() {
-            let cache_size = config.optimize_for_point_lookup as usize;
+        if !config.optimize_for_point_lookup.is_negative() || config.block_cache_size.is_some() {
+            let cache_size = config
+                .block_cache_size
+                .unwrap_or(config.optimize_for_point_lookup.max(0) as usize * 1024 * 1024);
             let mut opts = BlockBasedOptions::default();
             opts.set_format_version(5);
             opts.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
             opts.set_data_block_hash_ratio(0.75);
-            opts.set_bloom_filter(10.0, false);
-            let cache = Cache::new_lru_cache(cache_size * 1024 * 1024);
+            if config.bloom_filter_bits > 0 {
+                opts.set_bloom_filter(config.bloom_filter_bits as f64, false);
+            }
+            let cache = Cache::new_lru_cache(cache_size);
             opts.set_block_cache(&cache);
             db_opts.set_block_based_table_factory(&opts);
             db_opts.set_memtable_prefix_bloom_ratio(0.02);
@@ -177,6 +268,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn profiles_open() {
+        for profile in [
+            RocksDbProfile::Validator,
+            RocksDbProfile::RpcHeavy,
+            RocksDbProfile::Archive,
+        ] {
+            let config = RocksDbConfig::for_profile(profile);
+            let dir = tempfile::tempdir().unwrap();
+            let opts = Options::from(&config);
+            rocksdb::DB::open(&opts, dir.path()).unwrap();
+        }
+    }
+
     #[test]
     fn compression_style_from_str_test() {
         let test_cases = vec![
@@ -9,9 +9,11 @@ use std::{path::Path, sync::Arc};
 
 mod config;
 mod error;
+mod secondary;
 
-pub use config::RocksDbConfig;
+pub use config::{RocksDbConfig, RocksDbProfile};
 pub use error::Error;
+pub use secondary::RocksDbSecondary;
 
 #[derive(Clone)]
 pub struct RocksDb {
@@ -0,0 +1,65 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use rocksdb::{DBWithThreadMode, MultiThreaded, Options};
+use std::{path::Path, sync::Arc};
+
+use super::{Error, RocksDbConfig};
+
+/// Read-only view of a database which is open for writing by another process.
+///
+/// RocksDB only allows one process to open a database as primary, but any number
+/// of others can open it as secondary instances, which see the writes of the primary
+/// as of the last time they caught up with its write-ahead log. This is what lets
+/// heavy queries run outside the process that has to keep up with consensus.
+///
+/// Usage:
+/// ```no_run
+/// use fendermint_rocksdb::{RocksDbConfig, RocksDbSecondary};
+///
+/// let db = RocksDbSecondary::open("test_db", "test_db_secondary", &RocksDbConfig::default()).unwrap();
+/// db.catch_up().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct RocksDbSecondary {
+    pub db: Arc<DBWithThreadMode<MultiThreaded>>,
+}
+
+impl RocksDbSecondary {
+    /// Open all the column families of the primary database at `path`.
+    ///
+    /// The secondary instance keeps its own logs in `secondary_path`, which must
+    /// not be shared with any other instance.
+    pub fn open<P, S>(path: P, secondary_path: S, config: &RocksDbConfig) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        S: AsRef<Path>,
+    {
+        let mut db_opts: Options = config.into();
+        // The primary has to exist; a secondary can't create anything.
+        db_opts.create_if_missing(false);
+        // Secondary instances have to keep all files open, otherwise they can miss deletions by the primary.
+        db_opts.set_max_open_files(-1);
+
+        let cfs = DBWithThreadMode::<MultiThreaded>::list_cf(&db_opts, &path)?;
+
+        let db = DBWithThreadMode::<MultiThreaded>::open_cf_as_secondary(
+            &db_opts,
+            path.as_ref(),
+            secondary_path.as_ref(),
+            cfs,
+        )?;
+
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Replay whatever the primary has written since the last time we caught up.
+    pub fn catch_up(&self) -> Result<(), Error> {
+        Ok(self.db.try_catch_up_with_primary()?)
+    }
+
+    /// Check if a column family exists
+    pub fn has_cf_handle(&self, name: &str) -> bool {
+        self.db.cf_handle(name).is_some()
+    }
+}
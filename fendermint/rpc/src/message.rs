@@ -25,6 +25,8 @@ pub struct MessageFactory {
     addr: Address,
     sequence: u64,
     chain_id: ChainID,
+    /// Whether to send transactions in compressed envelopes.
+    compress: bool,
 }
 
 impl MessageFactory {
@@ -35,6 +37,7 @@ impl MessageFactory {
             addr,
             sequence,
             chain_id,
+            compress: false,
         }
     }
 
@@ -60,6 +63,11 @@ impl MessageFactory {
         Ok(fvm_ipld_encoding::to_vec(message)?)
     }
 
+    /// Compress the transactions created from now on, which the chain must have enabled in its genesis.
+    pub fn set_compression(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
     /// Actor address.
     pub fn address(&self) -> &Address {
         &self.addr
@@ -89,7 +97,26 @@ impl MessageFactory {
         value: TokenAmount,
         gas_params: GasParams,
     ) -> anyhow::Result<ChainMessage> {
-        let message = fvm_shared::message::Message {
+        let message = self.message(to, method_num, params, value, gas_params);
+        let signed = SignedMessage::new_secp256k1(message, &self.sk, &self.chain_id)?;
+        let chain = if self.compress {
+            ChainMessage::compress(&signed)?
+        } else {
+            ChainMessage::Signed(signed)
+        };
+        Ok(chain)
+    }
+
+    /// Create an unsigned message and increment the sequence.
+    fn message(
+        &mut self,
+        to: Address,
+        method_num: MethodNum,
+        params: RawBytes,
+        value: TokenAmount,
+        gas_params: GasParams,
+    ) -> Message {
+        let message = Message {
             version: Default::default(), // TODO: What does this do?
             from: self.addr,
             to,
@@ -102,9 +129,7 @@ impl MessageFactory {
             gas_premium: gas_params.gas_premium,
         };
         self.sequence += 1;
-        let signed = SignedMessage::new_secp256k1(message, &self.sk, &self.chain_id)?;
-        let chain = ChainMessage::Signed(signed);
-        Ok(chain)
+        message
     }
 
     /// Deploy a FEVM contract.
@@ -154,13 +179,14 @@ impl MessageFactory {
         value: TokenAmount,
         gas_params: GasParams,
     ) -> anyhow::Result<Message> {
-        let msg = self.fevm_invoke(contract, calldata, value, gas_params)?;
-
-        let msg = if let ChainMessage::Signed(signed) = msg {
-            signed.into_message()
-        } else {
-            panic!("unexpected message type: {msg:?}");
-        };
+        let calldata = RawBytes::serialize(BytesSer(&calldata))?;
+        let msg = self.message(
+            contract,
+            evm::Method::InvokeContract as u64,
+            calldata,
+            value,
+            gas_params,
+        );
 
         // Roll back the sequence, we don't really want to invoke anything.
         self.set_sequence(msg.sequence);
@@ -10,13 +10,31 @@ use tendermint::abci::response::DeliverTx;
 use tendermint::block::Height;
 use tendermint::v0_37::abci::response;
 use tendermint_rpc::endpoint::abci_query::AbciQuery;
+use tendermint_rpc::Client;
 
 use cid::Cid;
 use fvm_shared::ActorID;
-use fvm_shared::{address::Address, error::ExitCode};
+use fvm_shared::{address::Address, clock::ChainEpoch, econ::TokenAmount, error::ExitCode};
 
+use fendermint_vm_actor_interface::beacon::{self, BEACON_ACTOR_ADDR};
+use fendermint_vm_actor_interface::checkpointarchive::{
+    self, ArchivedCheckpoint, CHECKPOINT_ARCHIVE_ACTOR_ADDR,
+};
+use fendermint_vm_actor_interface::contractbook::{self, ContractEntry, CONTRACT_BOOK_ACTOR_ADDR};
+use fendermint_vm_actor_interface::deadletter::DeadLetter;
+use fendermint_vm_actor_interface::diamondlayout::{
+    self, DiamondEntry, FacetEntry, DIAMOND_LAYOUT_ACTOR_ADDR,
+};
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fendermint_vm_actor_interface::execdigests::ReceiptProof;
+use fendermint_vm_actor_interface::governance::{self, GOVERNANCE_ACTOR_ADDR};
+use fendermint_vm_actor_interface::rewardpool::{self, REWARD_POOL_ACTOR_ADDR};
+use fendermint_vm_actor_interface::validators::{self, ValidatorEntry, VALIDATORS_ACTOR_ADDR};
+use fendermint_vm_message::ipc::ParentFinality;
 use fendermint_vm_message::query::{
-    ActorState, FvmQuery, FvmQueryHeight, GasEstimate, StateParams,
+    AccessList, ActorState, AppHashReport, Capabilities, FvmQuery, FvmQueryHeight, GasEstimate,
+    StakingSimulation, StakingSimulationParams, StateParams, TopDownMessage, ValidatorSet,
+    APP_HASH_PATH, PARENT_FINALITY_PATH, TOPDOWN_MSGS_PATH, VALIDATOR_SET_PATH,
 };
 
 use crate::response::encode_data;
@@ -28,6 +46,163 @@ pub struct QueryResponse<T> {
     pub value: T,
 }
 
+/// The different identities of a validator, derived from its public key.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorAddresses {
+    /// Hex encoded uncompressed secp256k1 public key.
+    pub public_key: String,
+    /// The address CometBFT uses to identify the validator in blocks, votes and evidence.
+    pub consensus_address: String,
+    /// The `f1` address of the key.
+    pub fvm_address: String,
+    /// The Ethereum address of the key, which the validator uses to stake in the subnet actor
+    /// and to sign checkpoints.
+    pub eth_address: String,
+    /// The `f410` address the Ethereum address is known by in the FVM.
+    pub delegated_address: String,
+    /// Power in the CometBFT scale; 0 if the validator has left.
+    pub power: u64,
+    /// Height where the validator first appeared in the power table.
+    pub joined_at: ChainEpoch,
+    /// Height of the last change in power.
+    pub updated_at: ChainEpoch,
+}
+
+impl TryFrom<&ValidatorEntry> for ValidatorAddresses {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &ValidatorEntry) -> Result<Self, Self::Error> {
+        let pk = &value.public_key;
+        let consensus_key = tendermint::PublicKey::from_raw_secp256k1(pk)
+            .ok_or_else(|| anyhow!("invalid validator public key"))?;
+        let eth_address = EthAddress::new_secp256k1(pk)?;
+
+        Ok(Self {
+            public_key: hex::encode(pk),
+            consensus_address: tendermint::account::Id::from(consensus_key).to_string(),
+            fvm_address: Address::new_secp256k1(pk)?.to_string(),
+            eth_address: format!("{eth_address:?}"),
+            delegated_address: Address::from(eth_address).to_string(),
+            power: value.power,
+            joined_at: value.joined_at,
+            updated_at: value.updated_at,
+        })
+    }
+}
+
+/// The power table which was in effect at some height.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorSetAddresses {
+    /// Height of the block which changed the power table; it took effect two blocks later.
+    pub height: u64,
+    /// Height of the previous change, if there was any.
+    pub prev_height: Option<u64>,
+    pub validators: Vec<ValidatorAddresses>,
+}
+
+impl TryFrom<&ValidatorSet> for ValidatorSetAddresses {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &ValidatorSet) -> Result<Self, Self::Error> {
+        Ok(Self {
+            height: value.height,
+            prev_height: value.prev_height,
+            validators: value
+                .validators
+                .iter()
+                .map(ValidatorAddresses::try_from)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        })
+    }
+}
+
+/// The state of the reward pool along with the balance of the actor, which is the sum
+/// of the claimable rewards and the ones not distributed yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewardPool {
+    pub state: rewardpool::State,
+    pub balance: TokenAmount,
+}
+
+impl RewardPool {
+    /// The fees and inflation to be distributed at the end of the current epoch.
+    pub fn undistributed(&self) -> TokenAmount {
+        self.state.undistributed(&self.balance)
+    }
+}
+
+/// The addresses of a library or contract deployed at genesis.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ContractAddresses {
+    /// Fully qualified name of the contract, e.g. `Gateway.sol:Gateway`.
+    pub fqn: String,
+    pub actor_id: ActorID,
+    /// The Ethereum address the contract was deployed with.
+    pub eth_address: String,
+    /// The masked ID address, which is what libraries are linked with.
+    pub id_address: String,
+    /// The `f410` address of the contract.
+    pub delegated_address: String,
+    /// Whether the contract is a library, or one of the top-level contracts.
+    pub library: bool,
+}
+
+impl From<&ContractEntry> for ContractAddresses {
+    fn from(value: &ContractEntry) -> Self {
+        Self {
+            fqn: value.fqn.clone(),
+            actor_id: value.actor_id,
+            eth_address: format!("{:?}", value.eth_address),
+            id_address: format!("{:?}", value.link_address()),
+            delegated_address: Address::from(value.eth_address).to_string(),
+            library: value.library,
+        }
+    }
+}
+
+/// The facets a diamond was cut with at genesis.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiamondFacets {
+    /// Name of the diamond contract, e.g. `GatewayDiamond`.
+    pub name: String,
+    pub actor_id: ActorID,
+    pub facets: Vec<FacetSelectors>,
+}
+
+/// A facet of a diamond with the function selectors routed to it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FacetSelectors {
+    pub name: String,
+    /// The masked ID address of the library the facet was cut with.
+    pub address: String,
+    /// Function selectors as `0x` prefixed hexadecimal strings.
+    pub selectors: Vec<String>,
+}
+
+impl From<&FacetEntry> for FacetSelectors {
+    fn from(value: &FacetEntry) -> Self {
+        Self {
+            name: value.name.clone(),
+            address: format!("{:?}", value.address),
+            selectors: value
+                .selectors
+                .iter()
+                .map(|s| format!("0x{}", hex::encode(s)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&DiamondEntry> for DiamondFacets {
+    fn from(value: &DiamondEntry) -> Self {
+        Self {
+            name: value.name.clone(),
+            actor_id: value.actor_id,
+            facets: value.facets.iter().map(FacetSelectors::from).collect(),
+        }
+    }
+}
+
 /// Fendermint client for submitting queries.
 #[async_trait]
 pub trait QueryClient: Sync {
@@ -83,6 +258,26 @@ pub trait QueryClient: Sync {
         Ok(QueryResponse { height, value })
     }
 
+    /// Estimate the gas limit of a message and list the actors it calls.
+    async fn access_list(
+        &self,
+        mut message: Message,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<AccessList>> {
+        // Using 0 sequence so estimation doesn't get tripped over by nonce mismatch.
+        message.sequence = 0;
+
+        let res = self
+            .perform(FvmQuery::AccessList(Box::new(message)), height)
+            .await?;
+        let height = res.height;
+        let value = extract(res, |res| {
+            fvm_ipld_encoding::from_slice(&res.value)
+                .context("failed to decode AccessList from query")
+        })?;
+        Ok(QueryResponse { height, value })
+    }
+
     /// Slowly changing state parameters.
     async fn state_params(
         &self,
@@ -97,10 +292,358 @@ pub trait QueryClient: Sync {
         Ok(QueryResponse { height, value })
     }
 
+    /// List the top-down messages which failed to be applied.
+    async fn dead_letters(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Vec<DeadLetter>>> {
+        let res = self.perform(FvmQuery::DeadLetters, height).await?;
+        let height = res.height;
+        let value = extract(res, |res| {
+            fvm_ipld_encoding::from_slice(&res.value).context("failed to decode dead letters")
+        })?;
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Get the governance state, if the chain has governance.
+    async fn governance(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<governance::State>>> {
+        let res = self.actor_state(&GOVERNANCE_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => None,
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("governance state not found"))?;
+                let state = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode governance state")?;
+                Some(state)
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Get the state of the randomness beacon, if the chain has one.
+    async fn beacon(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<beacon::State>>> {
+        let res = self.actor_state(&BEACON_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => None,
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("beacon state not found"))?;
+                let state =
+                    fvm_ipld_encoding::from_slice(&bz).context("failed to decode beacon state")?;
+                Some(state)
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Get the state of the reward pool, if the chain pays rewards to the validators.
+    async fn reward_pool(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<RewardPool>>> {
+        let res = self.actor_state(&REWARD_POOL_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => None,
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("reward pool state not found"))?;
+                let state = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode reward pool state")?;
+                Some(RewardPool {
+                    state,
+                    balance: actor.balance,
+                })
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// List the validators which have been part of the power table, along with their addresses.
+    ///
+    /// Returns an empty list if the chain was started without the validator address book.
+    async fn validators(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Vec<ValidatorAddresses>>> {
+        let res = self.actor_state(&VALIDATORS_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => Vec::new(),
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("validator address book state not found"))?;
+                let state: validators::State = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode validator address book")?;
+                state
+                    .validators
+                    .iter()
+                    .map(ValidatorAddresses::try_from)
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// List the libraries and contracts deployed at genesis, in the order they were deployed.
+    ///
+    /// Returns an empty list if the chain was started without the contract address book.
+    async fn contracts(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Vec<ContractAddresses>>> {
+        let res = self.actor_state(&CONTRACT_BOOK_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => Vec::new(),
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("contract address book state not found"))?;
+                let state: contractbook::State = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode contract address book")?;
+                state
+                    .contracts
+                    .iter()
+                    .map(ContractAddresses::from)
+                    .collect()
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// List the facets the IPC diamonds were cut with at genesis, with their function selectors,
+    /// as the baseline to check facet upgrades against.
+    ///
+    /// Returns an empty list if the chain was started without the diamond layout.
+    async fn diamonds(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Vec<DiamondFacets>>> {
+        let res = self.actor_state(&DIAMOND_LAYOUT_ACTOR_ADDR, height).await?;
+        let height = res.height;
+        let value = match res.value {
+            None => Vec::new(),
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("diamond layout state not found"))?;
+                let state: diamondlayout::State = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode diamond layout")?;
+                state.diamonds.iter().map(DiamondFacets::from).collect()
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Get the state of the checkpoint archive, if the chain prunes bottom-up checkpoints.
+    async fn checkpoint_archives(
+        &self,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<checkpointarchive::State>>> {
+        let res = self
+            .actor_state(&CHECKPOINT_ARCHIVE_ACTOR_ADDR, height)
+            .await?;
+        let height = res.height;
+        let value = match res.value {
+            None => None,
+            Some((_, actor)) => {
+                let bz = self
+                    .ipld(&actor.state, FvmQueryHeight::from(height.value()))
+                    .await?
+                    .ok_or_else(|| anyhow!("checkpoint archive state not found"))?;
+                let state = fvm_ipld_encoding::from_slice(&bz)
+                    .context("failed to decode checkpoint archive state")?;
+                Some(state)
+            }
+        };
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Look up a bottom-up checkpoint which has been pruned from the gateway.
+    ///
+    /// Returns `None` if the checkpoint hasn't been archived, or the node doesn't have the archive file.
+    async fn archived_checkpoint(
+        &self,
+        checkpoint_height: u64,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<ArchivedCheckpoint>>> {
+        let res = self
+            .perform(FvmQuery::ArchivedCheckpoint(checkpoint_height), height)
+            .await?;
+        let height = res.height;
+        let value = extract_opt(res, |res| {
+            fvm_ipld_encoding::from_slice(&res.value)
+                .context("failed to decode ArchivedCheckpoint from query")
+        })?;
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Get the receipt of a transaction with the proof that it's in the execution digests of its block.
+    ///
+    /// Returns `None` if the chain doesn't record execution digests, the block is no longer in their
+    /// lookback window at the queried height, or it doesn't have a transaction at the index.
+    async fn receipt_proof(
+        &self,
+        block_height: u64,
+        index: u64,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<Option<ReceiptProof>>> {
+        let res = self
+            .perform(FvmQuery::ReceiptProof(block_height, index), height)
+            .await?;
+        let height = res.height;
+        let value = extract_opt(res, |res| {
+            fvm_ipld_encoding::from_slice(&res.value)
+                .context("failed to decode ReceiptProof from query")
+        })?;
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Simulate applying staking changes made on the parent, and check the resulting power table
+    /// against the constraints in the parameters, without changing anything in the state.
+    async fn simulate_staking_changes(
+        &self,
+        params: StakingSimulationParams,
+        height: FvmQueryHeight,
+    ) -> anyhow::Result<QueryResponse<StakingSimulation>> {
+        let res = self
+            .perform(FvmQuery::SimulateStakingChanges(Box::new(params)), height)
+            .await?;
+        let height = res.height;
+        let value = extract(res, |res| {
+            fvm_ipld_encoding::from_slice(&res.value)
+                .context("failed to decode StakingSimulation from query")
+        })?;
+        Ok(QueryResponse { height, value })
+    }
+
+    /// Versions and optional features of the node.
+    ///
+    /// Returns `None` if the node is too old to understand the query,
+    /// in which case the client should only rely on the basic features.
+    async fn capabilities(&self) -> anyhow::Result<Option<Capabilities>> {
+        let res = self
+            .perform(FvmQuery::Capabilities, FvmQueryHeight::Committed)
+            .await?;
+        if res.code.is_err() {
+            return Ok(None);
+        }
+        let value = fvm_ipld_encoding::from_slice(&res.value)
+            .context("failed to decode Capabilities from query")?;
+        Ok(Some(value))
+    }
+
     /// Run an ABCI query.
     async fn perform(&self, query: FvmQuery, height: FvmQueryHeight) -> anyhow::Result<AbciQuery>;
 }
 
+/// Get the power table which was in effect at a height from the history kept by the node,
+/// which, unlike the state, isn't pruned; a `height` of 0 means the latest.
+///
+/// Returns `None` if the history of the node doesn't go back that far.
+pub async fn validator_set<C>(
+    client: &C,
+    height: u64,
+) -> anyhow::Result<QueryResponse<Option<ValidatorSet>>>
+where
+    C: Client + Sync + Send,
+{
+    let data = fvm_ipld_encoding::to_vec(&height).context("failed to encode height")?;
+    let res = client
+        .abci_query(Some(VALIDATOR_SET_PATH.to_owned()), data, None, false)
+        .await?;
+    let height = res.height;
+    let value = extract(res, |res| {
+        fvm_ipld_encoding::from_slice(&res.value).context("failed to decode validator set")
+    })?;
+    Ok(QueryResponse { height, value })
+}
+
+/// Query the components of the app hash of the state committed at a height, 0 meaning the latest.
+///
+/// Returns `None` if the node no longer has the state of that height.
+pub async fn app_hash_report<C>(
+    client: &C,
+    height: u64,
+) -> anyhow::Result<QueryResponse<Option<AppHashReport>>>
+where
+    C: Client + Sync + Send,
+{
+    let data = fvm_ipld_encoding::to_vec(&height).context("failed to encode height")?;
+    let res = client
+        .abci_query(Some(APP_HASH_PATH.to_owned()), data, None, false)
+        .await?;
+    let height = res.height;
+    let value = extract(res, |res| {
+        fvm_ipld_encoding::from_slice(&res.value).context("failed to decode app hash report")
+    })?;
+    Ok(QueryResponse { height, value })
+}
+
+/// Get the parent finality committed in the state at a height, 0 meaning the latest.
+///
+/// Returns `None` if the subnet doesn't have IPC enabled.
+pub async fn parent_finality<C>(
+    client: &C,
+    height: u64,
+) -> anyhow::Result<QueryResponse<Option<ParentFinality>>>
+where
+    C: Client + Sync + Send,
+{
+    let data = fvm_ipld_encoding::to_vec(&height).context("failed to encode height")?;
+    let res = client
+        .abci_query(Some(PARENT_FINALITY_PATH.to_owned()), data, None, false)
+        .await?;
+    let height = res.height;
+    let value = extract(res, |res| {
+        fvm_ipld_encoding::from_slice(&res.value).context("failed to decode parent finality")
+    })?;
+    Ok(QueryResponse { height, value })
+}
+
+/// Get the top-down messages sent by the parent at the heights in an inclusive range.
+///
+/// The range is cut short at the latest committed parent finality, so the caller
+/// can continue from the height after the finality to get the rest later.
+pub async fn topdown_msgs<C>(
+    client: &C,
+    from: u64,
+    to: u64,
+) -> anyhow::Result<QueryResponse<Vec<TopDownMessage>>>
+where
+    C: Client + Sync + Send,
+{
+    let data = fvm_ipld_encoding::to_vec(&(from, to)).context("failed to encode range")?;
+    let res = client
+        .abci_query(Some(TOPDOWN_MSGS_PATH.to_owned()), data, None, false)
+        .await?;
+    let height = res.height;
+    let value = extract(res, |res| {
+        fvm_ipld_encoding::from_slice(&res.value).context("failed to decode top-down messages")
+    })?;
+    Ok(QueryResponse { height, value })
+}
+
 /// Extract some value from the query result, unless it's not found or other error.
 fn extract_opt<T, F>(res: AbciQuery, f: F) -> anyhow::Result<Option<T>>
 where
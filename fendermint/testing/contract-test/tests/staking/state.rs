@@ -547,6 +547,7 @@ impl arbitrary::Arbitrary<'_> for StakingState {
                 min_collateral,
                 active_validators_limit: 1 + u.choose_index(100)? as u16,
             },
+            checkpoint_retention: 0,
         };
 
         // We cannot actually use this value because the real ID will only be
@@ -565,6 +566,20 @@ impl arbitrary::Arbitrary<'_> for StakingState {
             validators: parent_validators,
             accounts: parent_actors,
             ipc: Some(parent_ipc),
+            access_control: None,
+            governance: None,
+            scheduled_calls: Vec::new(),
+            tx_compression: false,
+            filecoin_signatures: false,
+            system_contracts: Vec::new(),
+            beacon: None,
+            policy: None,
+            rewards: None,
+            exec_digests: false,
+            max_block_interval: None,
+            eth_block_hash_v1: false,
+            canonical_tx_order: false,
+            fast_topdown_finality: false,
         };
 
         let child_ipc = IpcParams {
@@ -578,6 +593,7 @@ impl arbitrary::Arbitrary<'_> for StakingState {
                     .max(TokenAmount::from_atto(1)),
                 active_validators_limit: num_max_validators as u16,
             },
+            checkpoint_retention: 0,
         };
 
         let child_genesis = Genesis {
@@ -589,6 +605,20 @@ impl arbitrary::Arbitrary<'_> for StakingState {
             validators: current_configuration,
             accounts: Vec::new(),
             ipc: Some(child_ipc),
+            access_control: None,
+            governance: None,
+            scheduled_calls: Vec::new(),
+            tx_compression: false,
+            filecoin_signatures: false,
+            system_contracts: Vec::new(),
+            beacon: None,
+            policy: None,
+            rewards: None,
+            exec_digests: false,
+            max_block_interval: None,
+            eth_block_hash_v1: false,
+            canonical_tx_order: false,
+            fast_topdown_finality: false,
         };
 
         Ok(StakingState::new(accounts, parent_genesis, child_genesis))
@@ -0,0 +1,320 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Run an Ethereum API compatibility suite against a pair of Fendermint and Tendermint
+//! docker containers running locally, in the spirit of the `execution-apis` tests used by `hive`.
+//!
+//! Example:
+//!
+//! ```text
+//! cd fendermint/testing/ethapi-test
+//! cargo make
+//! ```
+//!
+//! Make sure you installed cargo-make by running `cargo install cargo-make` first.
+//!
+//! The test vectors are JSON files in the `vectors` directory, each containing an array
+//! of [Vector]s. Because the chain is started from scratch, the actual values such as
+//! hashes and timestamps are not known in advance, so the expected results describe the
+//! _shape_ of the response, which is what wallets and client libraries tend to trip over:
+//!
+//! * objects have to have exactly the same keys, no missing and no extra ones
+//! * an array with a single element means every item has to match that element
+//! * an empty array means the response has to be empty
+//! * strings in angle brackets are placeholders, e.g. `"<quantity>"` or `"<hash>"`;
+//!   a `?` suffix, e.g. `"<address?>"`, means the value can also be `null`
+//! * anything else has to match literally
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A single request with the expected response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Vector {
+    /// Short description of what is being tested.
+    pub name: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Vec<Value>,
+    #[serde(flatten)]
+    pub expect: Expectation,
+}
+
+/// What we expect the API to respond with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// Successful response, with the template of the result.
+    Result(Value),
+    /// Error response with a specific code; the message is not compared.
+    Error { code: i64 },
+}
+
+/// Describe where and why the response differs from what was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// JSON path to the offending value, e.g. `$.transactions[0].hash`.
+    pub path: String,
+    pub reason: String,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Check that the actual value matches the expected template.
+pub fn check_shape(expected: &Value, actual: &Value) -> Result<(), Mismatch> {
+    check_at("$".to_owned(), expected, actual)
+}
+
+fn check_at(path: String, expected: &Value, actual: &Value) -> Result<(), Mismatch> {
+    let mismatch = |reason: String| {
+        Err(Mismatch {
+            path: path.clone(),
+            reason,
+        })
+    };
+
+    match expected {
+        Value::String(s) if s.starts_with('<') && s.ends_with('>') => {
+            let placeholder = &s[1..s.len() - 1];
+            let (placeholder, nullable) = match placeholder.strip_suffix('?') {
+                Some(p) => (p, true),
+                None => (placeholder, false),
+            };
+            if actual.is_null() {
+                return if nullable {
+                    Ok(())
+                } else {
+                    mismatch(format!("expected {s}, got null"))
+                };
+            }
+            match check_placeholder(placeholder, actual) {
+                Ok(()) => Ok(()),
+                Err(e) => mismatch(format!("expected {s}, got {actual}: {e}")),
+            }
+        }
+        Value::Object(exp) => {
+            let act = match actual.as_object() {
+                Some(act) => act,
+                None => return mismatch(format!("expected an object, got {actual}")),
+            };
+            let missing = exp
+                .keys()
+                .filter(|k| !act.contains_key(*k))
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return mismatch(format!("missing keys: {missing:?}"));
+            }
+            let extra = act
+                .keys()
+                .filter(|k| !exp.contains_key(*k))
+                .collect::<Vec<_>>();
+            if !extra.is_empty() {
+                return mismatch(format!("unexpected keys: {extra:?}"));
+            }
+            for (k, e) in exp {
+                check_at(format!("{path}.{k}"), e, &act[k])?;
+            }
+            Ok(())
+        }
+        Value::Array(exp) => {
+            let act = match actual.as_array() {
+                Some(act) => act,
+                None => return mismatch(format!("expected an array, got {actual}")),
+            };
+            match exp.as_slice() {
+                [] if act.is_empty() => Ok(()),
+                [] => mismatch(format!("expected an empty array, got {} items", act.len())),
+                [item] => {
+                    for (i, a) in act.iter().enumerate() {
+                        check_at(format!("{path}[{i}]"), item, a)?;
+                    }
+                    Ok(())
+                }
+                _ if exp.len() != act.len() => {
+                    mismatch(format!("expected {} items, got {}", exp.len(), act.len()))
+                }
+                _ => {
+                    for (i, (e, a)) in exp.iter().zip(act.iter()).enumerate() {
+                        check_at(format!("{path}[{i}]"), e, a)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+        _ if expected == actual => Ok(()),
+        _ => mismatch(format!("expected {expected}, got {actual}")),
+    }
+}
+
+fn check_placeholder(placeholder: &str, actual: &Value) -> Result<(), String> {
+    match placeholder {
+        "any" => Ok(()),
+        "bool" if actual.is_boolean() => Ok(()),
+        "string" if actual.is_string() => Ok(()),
+        "number" if actual.is_number() => Ok(()),
+        "quantity" => check_quantity(actual),
+        "data" => check_data(actual, None),
+        "hash" => check_data(actual, Some(32)),
+        "address" => check_data(actual, Some(20)),
+        "bloom" => check_data(actual, Some(256)),
+        "nonce" => check_data(actual, Some(8)),
+        "bool" | "string" | "number" => Err("wrong type".to_owned()),
+        other => Err(format!("unknown placeholder: {other}")),
+    }
+}
+
+/// Quantities are hex encoded without leading zeroes, e.g. `0x0` or `0x400`.
+fn check_quantity(actual: &Value) -> Result<(), String> {
+    let s = actual.as_str().ok_or("not a string")?;
+    let hex = s.strip_prefix("0x").ok_or("missing 0x prefix")?;
+    if hex.is_empty() {
+        return Err("empty quantity".to_owned());
+    }
+    if hex.len() > 1 && hex.starts_with('0') {
+        return Err("leading zeroes".to_owned());
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("not hex".to_owned());
+    }
+    Ok(())
+}
+
+/// Unformatted data is hex encoded with two digits per byte, optionally of a fixed size.
+fn check_data(actual: &Value, size: Option<usize>) -> Result<(), String> {
+    let s = actual.as_str().ok_or("not a string")?;
+    let hex = s.strip_prefix("0x").ok_or("missing 0x prefix")?;
+    if hex.len() % 2 != 0 {
+        return Err("odd number of digits".to_owned());
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("not hex".to_owned());
+    }
+    match size {
+        Some(size) if hex.len() != size * 2 => {
+            Err(format!("expected {size} bytes, got {}", hex.len() / 2))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Results of the vectors executed against a node, grouped by method.
+#[derive(Debug, Default)]
+pub struct Report {
+    methods: BTreeMap<String, MethodReport>,
+}
+
+#[derive(Debug, Default)]
+struct MethodReport {
+    passed: usize,
+    failures: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn passed(&mut self, vector: &Vector) {
+        self.method(vector).passed += 1;
+    }
+
+    pub fn failed(&mut self, vector: &Vector, reason: impl Display) {
+        let name = vector.name.clone();
+        self.method(vector)
+            .failures
+            .push((name, reason.to_string()));
+    }
+
+    /// Indicate whether all vectors passed.
+    pub fn is_success(&self) -> bool {
+        self.methods.values().all(|m| m.failures.is_empty())
+    }
+
+    fn method(&mut self, vector: &Vector) -> &mut MethodReport {
+        self.methods.entry(vector.method.clone()).or_default()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut total = 0;
+        let mut conformant = 0;
+        for (method, report) in &self.methods {
+            let count = report.passed + report.failures.len();
+            let status = if report.failures.is_empty() {
+                "ok"
+            } else {
+                "FAILED"
+            };
+            writeln!(f, "{method}: {}/{count} {status}", report.passed)?;
+            for (name, reason) in &report.failures {
+                writeln!(f, "  - {name}: {reason}")?;
+            }
+            total += 1;
+            if report.failures.is_empty() {
+                conformant += 1;
+            }
+        }
+        writeln!(f, "{conformant}/{total} methods conformant")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::check_shape;
+
+    #[test]
+    fn placeholders() {
+        assert!(check_shape(&json!("<quantity>"), &json!("0x0")).is_ok());
+        assert!(check_shape(&json!("<quantity>"), &json!("0x400")).is_ok());
+        assert!(check_shape(&json!("<quantity>"), &json!("0x0400")).is_err());
+        assert!(check_shape(&json!("<quantity>"), &json!(1024)).is_err());
+        assert!(check_shape(&json!("<data>"), &json!("0x")).is_ok());
+        assert!(check_shape(&json!("<data>"), &json!("0x123")).is_err());
+        assert!(check_shape(
+            &json!("<address>"),
+            &json!(format!("0x{}", "ab".repeat(20)))
+        )
+        .is_ok());
+        assert!(check_shape(
+            &json!("<address>"),
+            &json!(format!("0x{}", "ab".repeat(32)))
+        )
+        .is_err());
+        assert!(check_shape(&json!("<address?>"), &json!(null)).is_ok());
+        assert!(check_shape(&json!("<address>"), &json!(null)).is_err());
+    }
+
+    #[test]
+    fn objects_and_arrays() {
+        let expected = json!({"number": "<quantity>", "transactions": ["<hash>"]});
+        let hash = format!("0x{}", "00".repeat(32));
+
+        assert!(check_shape(&expected, &json!({"number": "0x1", "transactions": []})).is_ok());
+        assert!(check_shape(
+            &expected,
+            &json!({"number": "0x1", "transactions": [hash, hash]})
+        )
+        .is_ok());
+
+        let err = check_shape(
+            &expected,
+            &json!({"number": "0x1", "transactions": ["0x1"]}),
+        )
+        .unwrap_err();
+        assert_eq!(err.path, "$.transactions[0]");
+
+        let err = check_shape(&expected, &json!({"number": "0x1"})).unwrap_err();
+        assert_eq!(err.path, "$");
+
+        let err = check_shape(
+            &expected,
+            &json!({"number": "0x1", "transactions": [], "extra": 1}),
+        )
+        .unwrap_err();
+        assert!(err.reason.contains("extra"));
+    }
+}
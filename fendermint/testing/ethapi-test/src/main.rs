@@ -0,0 +1,109 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Execute the compatibility vectors against a running Ethereum API facade.
+//!
+//! ```text
+//! cargo run -p ethapi-test --release -- \
+//!   --url http://127.0.0.1:8545 \
+//!   --vectors-dir fendermint/testing/ethapi-test/vectors
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use ethapi_test::{check_shape, Expectation, Report, Vector};
+use ethers::providers::{Http, HttpClientError, JsonRpcClient};
+use serde_json::Value;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// The URL of the Ethereum API facade.
+    #[arg(long, default_value = "http://127.0.0.1:8545")]
+    pub url: url::Url,
+
+    /// Directory with the JSON files containing the test vectors.
+    #[arg(long)]
+    pub vectors_dir: PathBuf,
+
+    /// Only run the vectors of the given method.
+    #[arg(long)]
+    pub method: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opts = Options::parse();
+
+    let client = Http::new(opts.url.clone());
+
+    let mut vectors = read_vectors(&opts.vectors_dir)?;
+
+    if let Some(ref method) = opts.method {
+        vectors.retain(|v| v.method == *method);
+    }
+
+    let mut report = Report::default();
+
+    for vector in vectors {
+        let res: Result<Value, HttpClientError> =
+            client.request(&vector.method, &vector.params).await;
+
+        match (&vector.expect, res) {
+            (Expectation::Result(expected), Ok(actual)) => match check_shape(expected, &actual) {
+                Ok(()) => report.passed(&vector),
+                Err(mismatch) => report.failed(&vector, mismatch),
+            },
+            (Expectation::Result(_), Err(e)) => {
+                report.failed(&vector, format!("unexpected error: {e}"))
+            }
+            (Expectation::Error { code }, Err(HttpClientError::JsonRpcError(e))) => {
+                if e.code == *code {
+                    report.passed(&vector)
+                } else {
+                    report.failed(
+                        &vector,
+                        format!("expected error code {code}, got {}: {}", e.code, e.message),
+                    )
+                }
+            }
+            (Expectation::Error { code }, Err(e)) => {
+                report.failed(&vector, format!("expected error code {code}, got: {e}"))
+            }
+            (Expectation::Error { code }, Ok(actual)) => report.failed(
+                &vector,
+                format!("expected error code {code}, got result: {actual}"),
+            ),
+        }
+    }
+
+    println!("{report}");
+
+    if report.is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("the Ethereum API is not conformant"))
+    }
+}
+
+/// Read all the `*.json` files in the directory, in alphabetical order.
+fn read_vectors(dir: &Path) -> anyhow::Result<Vec<Vector>> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read vectors dir {dir:?}"))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.retain(|p| p.extension().map(|e| e == "json").unwrap_or_default());
+    paths.sort();
+
+    let mut vectors = Vec::new();
+    for path in paths {
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read vectors from {path:?}"))?;
+        let vs: Vec<Vector> = serde_json::from_str(&json)
+            .with_context(|| format!("failed to parse vectors from {path:?}"))?;
+        vectors.extend(vs);
+    }
+    Ok(vectors)
+}
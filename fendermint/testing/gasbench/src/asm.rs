@@ -0,0 +1,200 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Just enough of an EVM assembler to write the loops of the workloads without a Solidity compiler,
+//! so that the bytecode does exactly what is being measured and nothing else.
+
+use std::collections::BTreeMap;
+
+pub const STOP: u8 = 0x00;
+pub const ADD: u8 = 0x01;
+pub const MUL: u8 = 0x02;
+pub const SUB: u8 = 0x03;
+pub const MOD: u8 = 0x06;
+pub const ISZERO: u8 = 0x15;
+pub const KECCAK256: u8 = 0x20;
+pub const ADDRESS: u8 = 0x30;
+pub const CALLDATALOAD: u8 = 0x35;
+pub const CODECOPY: u8 = 0x39;
+pub const POP: u8 = 0x50;
+pub const MSTORE: u8 = 0x52;
+pub const SLOAD: u8 = 0x54;
+pub const SSTORE: u8 = 0x55;
+pub const JUMP: u8 = 0x56;
+pub const JUMPI: u8 = 0x57;
+pub const GAS: u8 = 0x5a;
+pub const JUMPDEST: u8 = 0x5b;
+pub const PUSH1: u8 = 0x60;
+pub const PUSH2: u8 = 0x61;
+pub const DUP1: u8 = 0x80;
+pub const DUP2: u8 = 0x81;
+pub const SWAP1: u8 = 0x90;
+pub const CREATE: u8 = 0xf0;
+pub const CALL: u8 = 0xf1;
+pub const RETURN: u8 = 0xf3;
+
+enum Item {
+    Op(u8),
+    Push(Vec<u8>),
+    Label(&'static str),
+    /// Push the offset of a label, which is only known once everything is in place.
+    PushLabel(&'static str),
+}
+
+impl Item {
+    fn len(&self) -> usize {
+        match self {
+            Item::Op(_) => 1,
+            Item::Push(bz) => 1 + bz.len(),
+            Item::Label(_) => 1,
+            Item::PushLabel(_) => 3,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Asm {
+    items: Vec<Item>,
+}
+
+impl Asm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append opcodes without immediate arguments.
+    pub fn ops(mut self, ops: &[u8]) -> Self {
+        self.items.extend(ops.iter().map(|op| Item::Op(*op)));
+        self
+    }
+
+    /// Push a number with the shortest `PUSH` instruction that fits it.
+    pub fn push(mut self, value: u64) -> Self {
+        let bz = value.to_be_bytes();
+        let skip = bz.iter().take_while(|b| **b == 0).count().min(7);
+        self.items.push(Item::Push(bz[skip..].to_vec()));
+        self
+    }
+
+    /// Mark a jump destination.
+    pub fn label(mut self, name: &'static str) -> Self {
+        self.items.push(Item::Label(name));
+        self
+    }
+
+    /// Push the offset of a jump destination, which can be defined before or after.
+    pub fn push_label(mut self, name: &'static str) -> Self {
+        self.items.push(Item::PushLabel(name));
+        self
+    }
+
+    /// Repeat the `body` as many times as the first word of the call data says,
+    /// with the remaining number of iterations on the top of the stack.
+    ///
+    /// The body has to leave the stack the way it found it.
+    pub fn repeat(self, body: &[u8]) -> Self {
+        self.push(0)
+            .ops(&[CALLDATALOAD])
+            .label("loop")
+            .ops(&[DUP1, ISZERO])
+            .push_label("end")
+            .ops(&[JUMPI])
+            .ops(body)
+            .push(1)
+            .ops(&[SWAP1, SUB])
+            .push_label("loop")
+            .ops(&[JUMP])
+            .label("end")
+            .ops(&[STOP])
+    }
+
+    pub fn assemble(&self) -> Vec<u8> {
+        let mut labels = BTreeMap::new();
+        let mut offset = 0;
+        for item in self.items.iter() {
+            if let Item::Label(name) = item {
+                let prev = labels.insert(*name, offset as u16);
+                assert!(prev.is_none(), "duplicate label: {name}");
+            }
+            offset += item.len();
+        }
+
+        let mut code = Vec::with_capacity(offset);
+        for item in self.items.iter() {
+            match item {
+                Item::Op(op) => code.push(*op),
+                Item::Push(bz) => {
+                    code.push(PUSH1 + bz.len() as u8 - 1);
+                    code.extend_from_slice(bz);
+                }
+                Item::Label(_) => code.push(JUMPDEST),
+                Item::PushLabel(name) => {
+                    let offset = labels
+                        .get(name)
+                        .unwrap_or_else(|| panic!("undefined label: {name}"));
+                    code.push(PUSH2);
+                    code.extend_from_slice(&offset.to_be_bytes());
+                }
+            }
+        }
+        code
+    }
+}
+
+/// Wrap the runtime bytecode into an initcode which returns it from the constructor.
+pub fn initcode(runtime: &[u8]) -> Vec<u8> {
+    let len = runtime.len() as u64;
+    // The runtime is copied from right after the constructor.
+    let prefix = |offset: u64| {
+        Asm::new()
+            .push(len)
+            .push(offset)
+            .push(0)
+            .ops(&[CODECOPY])
+            .push(len)
+            .push(0)
+            .ops(&[RETURN])
+            .assemble()
+    };
+    // The offset is short enough to fit a `PUSH1` either way, so the length doesn't change.
+    let mut code = prefix(0);
+    code = prefix(code.len() as u64);
+    code.extend_from_slice(runtime);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_resolve_to_jumpdests() {
+        let code = Asm::new().repeat(&[DUP1, POP]).assemble();
+
+        // PUSH1 0, CALLDATALOAD, then the loop starts.
+        assert_eq!(code[3], JUMPDEST);
+        // DUP1, ISZERO, PUSH2 <end>
+        assert_eq!(code[6], PUSH2);
+        let end = u16::from_be_bytes([code[7], code[8]]) as usize;
+        assert_eq!(code[end], JUMPDEST);
+        assert_eq!(code[end + 1], STOP);
+        assert_eq!(end + 2, code.len());
+    }
+
+    #[test]
+    fn push_uses_shortest_instruction() {
+        assert_eq!(Asm::new().push(0).assemble(), vec![PUSH1, 0]);
+        assert_eq!(Asm::new().push(300).assemble(), vec![PUSH2, 1, 44]);
+    }
+
+    #[test]
+    fn initcode_copies_runtime() {
+        let runtime = vec![STOP; 300];
+        let code = initcode(&runtime);
+        let prefix = code.len() - runtime.len();
+        // PUSH2 <len> PUSH1 <offset> ...
+        assert_eq!(&code[1..3], &300u16.to_be_bytes());
+        assert_eq!(code[4] as usize, prefix);
+        assert_eq!(&code[prefix..], runtime.as_slice());
+    }
+}
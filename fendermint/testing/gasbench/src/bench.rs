@@ -0,0 +1,231 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context};
+use fendermint_crypto::SecretKey;
+use fendermint_vm_actor_interface::{eam, evm};
+use fendermint_vm_core::Timestamp;
+use fendermint_vm_genesis::{
+    Account, Actor, ActorMeta, Collateral, Genesis, SignerAddr, Validator, ValidatorKey,
+};
+use fendermint_vm_interpreter::{
+    fvm::{
+        bundle::{bundle_path, contracts_path},
+        state::{FvmExecState, FvmGenesisState},
+        store::memory::MemoryBlockstore,
+        FvmMessageInterpreter,
+    },
+    GenesisInterpreter,
+};
+use fvm::engine::MultiEngine;
+use fvm_ipld_encoding::{BytesDe, BytesSer, RawBytes};
+use fvm_shared::{
+    address::Address, econ::TokenAmount, message::Message, version::NetworkVersion, BLOCK_GAS_LIMIT,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::asm;
+use crate::report::Measurement;
+use crate::workload::Workload;
+
+/// Executes messages on a chain which only exists in memory, with a single account deploying
+/// and calling the contracts of the workloads.
+pub struct Bench {
+    state: FvmExecState<MemoryBlockstore>,
+    sender: Address,
+    sequence: u64,
+    /// Incremented with every message to make the storage writes go to new slots.
+    salt: u64,
+}
+
+impl Bench {
+    pub async fn new() -> anyhow::Result<Self> {
+        let sk = SecretKey::random(&mut StdRng::seed_from_u64(0));
+        let pk = sk.public_key();
+        let sender = Address::from(eam::EthAddress::from(pk));
+
+        let genesis = Genesis {
+            chain_name: "gasbench".to_string(),
+            timestamp: Timestamp(1),
+            network_version: NetworkVersion::V20,
+            base_fee: TokenAmount::from_atto(0),
+            power_scale: 0,
+            validators: vec![Validator {
+                public_key: ValidatorKey(pk),
+                power: Collateral(TokenAmount::from_whole(1)),
+            }],
+            accounts: vec![Actor {
+                meta: ActorMeta::Account(Account {
+                    owner: SignerAddr(sender),
+                }),
+                balance: TokenAmount::from_whole(1_000_000),
+            }],
+            ipc: None,
+            access_control: None,
+            governance: None,
+            scheduled_calls: Vec::new(),
+            tx_compression: false,
+            filecoin_signatures: false,
+            system_contracts: Vec::new(),
+            beacon: None,
+            policy: None,
+            rewards: None,
+            exec_digests: false,
+            max_block_interval: None,
+            eth_block_hash_v1: false,
+            canonical_tx_order: false,
+            fast_topdown_finality: false,
+        };
+
+        let bundle = std::fs::read(bundle_path()).context("failed to read bundle")?;
+        let multi_engine = Arc::new(MultiEngine::new(1));
+        let state = FvmGenesisState::new(MemoryBlockstore::new(), multi_engine, &bundle)
+            .await
+            .context("failed to create state")?;
+
+        let (client, _) =
+            tendermint_rpc::MockClient::new(tendermint_rpc::MockRequestMethodMatcher::default());
+
+        let interpreter =
+            FvmMessageInterpreter::new(client, None, contracts_path(), 1.05, 1.05, false);
+
+        let (state, _) = interpreter
+            .init(state, genesis)
+            .await
+            .context("failed to create actors")?;
+
+        let state = state
+            .into_exec_state()
+            .map_err(|_| anyhow!("should be in exec stage"))?;
+
+        Ok(Self {
+            state,
+            sender,
+            sequence: 0,
+            salt: 0,
+        })
+    }
+
+    /// Execute a message from the account, the same way as a transaction in a block.
+    fn execute(
+        &mut self,
+        to: Address,
+        method_num: u64,
+        params: RawBytes,
+    ) -> anyhow::Result<(RawBytes, u64, Duration)> {
+        let msg = Message {
+            version: Default::default(),
+            from: self.sender,
+            to,
+            sequence: self.sequence,
+            value: TokenAmount::from_atto(0),
+            method_num,
+            params,
+            gas_limit: BLOCK_GAS_LIMIT,
+            gas_fee_cap: TokenAmount::from_atto(0),
+            gas_premium: TokenAmount::from_atto(0),
+        };
+
+        let start = Instant::now();
+        let (ret, _) = self
+            .state
+            .execute_explicit(msg)
+            .context("failed to execute message")?;
+        let elapsed = start.elapsed();
+
+        self.sequence += 1;
+
+        let receipt = ret.msg_receipt;
+        if !receipt.exit_code.is_success() {
+            let data = receipt
+                .return_data
+                .deserialize::<BytesDe>()
+                .map(|bz| hex::encode(bz.0))
+                .unwrap_or_default();
+            bail!(
+                "message failed with exit code {}: {:?} {data}",
+                receipt.exit_code,
+                ret.failure_info
+            );
+        }
+
+        Ok((receipt.return_data, receipt.gas_used, elapsed))
+    }
+
+    /// Deploy the contract of a workload.
+    pub fn deploy(&mut self, workload: Workload) -> anyhow::Result<Address> {
+        let initcode = asm::initcode(&workload.runtime());
+        let params = RawBytes::serialize(BytesSer(&initcode))?;
+
+        let (ret, _, _) = self
+            .execute(
+                eam::EAM_ACTOR_ADDR,
+                eam::Method::CreateExternal as u64,
+                params,
+            )
+            .with_context(|| format!("failed to deploy the {workload} contract"))?;
+
+        let ret = ret
+            .deserialize::<eam::CreateReturn>()
+            .context("failed to decode the contract address")?;
+
+        Ok(Address::new_id(ret.actor_id))
+    }
+
+    /// Call a workload contract to run a number of iterations.
+    fn invoke(&mut self, contract: Address, iterations: u64) -> anyhow::Result<(u64, Duration)> {
+        self.salt += 1;
+        let calldata = Workload::calldata(iterations, self.salt);
+        let params = RawBytes::serialize(BytesSer(&calldata))?;
+
+        let (_, gas_used, elapsed) =
+            self.execute(contract, evm::Method::InvokeContract as u64, params)?;
+
+        Ok((gas_used, elapsed))
+    }
+
+    /// Measure the gas and the time of the iterations of a workload, without the cost of the
+    /// message itself, by subtracting the cost of a message which does no iterations.
+    ///
+    /// The messages are repeated a number of times, and the medians are taken,
+    /// after a first run to warm up the caches, e.g. of the compiled actor code.
+    pub fn measure(
+        &mut self,
+        workload: Workload,
+        iterations: u64,
+        repeat: usize,
+    ) -> anyhow::Result<Measurement> {
+        let contract = self.deploy(workload)?;
+
+        self.invoke(contract, iterations)
+            .with_context(|| format!("failed to warm up the {workload} workload"))?;
+
+        let mut gas = Vec::with_capacity(repeat);
+        let mut nanos = Vec::with_capacity(repeat);
+
+        for _ in 0..repeat.max(1) {
+            let (empty_gas, empty_time) = self.invoke(contract, 0)?;
+            let (full_gas, full_time) = self
+                .invoke(contract, iterations)
+                .with_context(|| format!("failed to run the {workload} workload"))?;
+
+            gas.push(full_gas.saturating_sub(empty_gas));
+            nanos.push(full_time.saturating_sub(empty_time).as_nanos() as u64);
+        }
+
+        Ok(Measurement {
+            workload,
+            iterations,
+            gas_used: median(&mut gas),
+            elapsed_nanos: median(&mut nanos),
+        })
+    }
+}
+
+fn median(xs: &mut [u64]) -> u64 {
+    xs.sort_unstable();
+    xs.get(xs.len() / 2).cloned().unwrap_or_default()
+}
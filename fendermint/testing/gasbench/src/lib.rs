@@ -0,0 +1,23 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Run representative EVM workloads through the FVM and compare the gas they are charged with
+//! the time it takes to execute them, to tell which classes of operations are priced out of line
+//! with the rest, and how long a block full of each would take to execute.
+//!
+//! Example:
+//!
+//! ```text
+//! cargo run -p fendermint_gasbench --release -- --repeat 10 --max-divergence 3
+//! ```
+//!
+//! Each workload is a contract written in EVM assembly, which repeats an operation as many times
+//! as the call data says. The contracts are deployed on a chain which only exists in memory, and
+//! called by an account the same way as transactions in a block. The cost of the message itself
+//! is subtracted by also calling the contract without iterations, and the medians are reported.
+//!
+//! The workloads are compared by the nanoseconds they take per gas, to the median of all of them;
+//! the ones which diverge too much are flagged either as underpriced or as overpriced.
+pub mod asm;
+pub mod bench;
+pub mod report;
+pub mod workload;
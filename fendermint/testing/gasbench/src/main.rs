@@ -0,0 +1,101 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Compare the FVM gas of EVM workloads with the time it takes to execute them.
+//!
+//! ```text
+//! cargo run -p fendermint_gasbench --release -- \
+//!   --workload storage_write,keccak,call --repeat 10 \
+//!   --report-file gasbench.json --fail-on-divergence
+//! ```
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use fendermint_gasbench::bench::Bench;
+use fendermint_gasbench::report::Report;
+use fendermint_gasbench::workload::Workload;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Workloads to measure, out of `arithmetic`, `storage_write`, `storage_read`, `keccak`,
+    /// `call` and `create`; all of them by default.
+    #[arg(long, value_delimiter = ',')]
+    pub workload: Vec<Workload>,
+
+    /// Multiply the default number of iterations of each workload by this much.
+    #[arg(long, default_value_t = 1.0)]
+    pub scale: f64,
+
+    /// Number of times to measure each workload, to take the median of.
+    #[arg(long, default_value_t = 5)]
+    pub repeat: usize,
+
+    /// Flag the workloads whose time per gas is more than this many times
+    /// higher or lower than the median.
+    #[arg(long, default_value_t = 3.0)]
+    pub max_divergence: f64,
+
+    /// Write the report to a JSON file.
+    #[arg(long)]
+    pub report_file: Option<PathBuf>,
+
+    /// Fail if any of the workloads is flagged.
+    #[arg(long)]
+    pub fail_on_divergence: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opts = Options::parse();
+
+    let workloads = if opts.workload.is_empty() {
+        Workload::ALL.to_vec()
+    } else {
+        opts.workload.clone()
+    };
+
+    if cfg!(debug_assertions) {
+        eprintln!(
+            "WARNING: not running in --release mode; the timings are going to be meaningless"
+        );
+    }
+
+    let mut bench = Bench::new().await.context("failed to create the chain")?;
+
+    let mut measurements = Vec::new();
+    for workload in workloads {
+        let iterations = ((workload.default_iterations() as f64 * opts.scale) as u64).max(1);
+        let m = bench.measure(workload, iterations, opts.repeat)?;
+        eprintln!(
+            "{workload}: {} gas in {:?}",
+            m.gas_used,
+            std::time::Duration::from_nanos(m.elapsed_nanos)
+        );
+        measurements.push(m);
+    }
+
+    let report = Report::new(measurements, opts.max_divergence);
+
+    println!("{report}");
+
+    if let Some(ref path) = opts.report_file {
+        let json = serde_json::to_string_pretty(&report)?;
+        std::fs::write(path, json).with_context(|| format!("failed to write {path:?}"))?;
+    }
+
+    let flagged = report
+        .flagged()
+        .map(|c| c.measurement.workload.as_str())
+        .collect::<Vec<_>>();
+
+    if opts.fail_on_divergence && !flagged.is_empty() {
+        return Err(anyhow!(
+            "the gas pricing diverges from the execution time of: {}",
+            flagged.join(", ")
+        ));
+    }
+
+    Ok(())
+}
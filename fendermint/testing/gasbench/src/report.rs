@@ -0,0 +1,196 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt::Display;
+
+use fvm_shared::BLOCK_GAS_LIMIT;
+use serde::Serialize;
+
+use crate::workload::Workload;
+
+/// Gas and time of the iterations of a workload, without the overhead of the message.
+#[derive(Debug, Clone, Serialize)]
+pub struct Measurement {
+    pub workload: Workload,
+    pub iterations: u64,
+    pub gas_used: u64,
+    pub elapsed_nanos: u64,
+}
+
+impl Measurement {
+    pub fn nanos_per_gas(&self) -> f64 {
+        if self.gas_used == 0 {
+            0.0
+        } else {
+            self.elapsed_nanos as f64 / self.gas_used as f64
+        }
+    }
+}
+
+/// How the execution time of a workload compares to what its gas suggests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pricing {
+    /// Takes longer than the gas suggests, so a block full of it would take longer than expected.
+    Underpriced,
+    /// Takes less time than the gas suggests, so it's more expensive for users than it should be.
+    Overpriced,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassReport {
+    #[serde(flatten)]
+    pub measurement: Measurement,
+    pub gas_per_iteration: f64,
+    pub nanos_per_gas: f64,
+    /// Seconds it would take to execute a block using all of its gas limit on this workload.
+    pub block_secs: f64,
+    /// Nanoseconds per gas relative to the median of the workloads.
+    pub divergence: f64,
+    pub flag: Option<Pricing>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// The median nanoseconds per gas of the workloads, which they are compared to.
+    pub median_nanos_per_gas: f64,
+    /// The divergence beyond which a workload is flagged, in either direction.
+    pub max_divergence: f64,
+    pub classes: Vec<ClassReport>,
+}
+
+impl Report {
+    pub fn new(measurements: Vec<Measurement>, max_divergence: f64) -> Self {
+        let mut rates = measurements
+            .iter()
+            .map(|m| m.nanos_per_gas())
+            .collect::<Vec<_>>();
+        rates.sort_by(|a, b| a.total_cmp(b));
+
+        let median = match rates.len() {
+            0 => 0.0,
+            n if n % 2 == 0 => (rates[n / 2 - 1] + rates[n / 2]) / 2.0,
+            n => rates[n / 2],
+        };
+
+        let classes = measurements
+            .into_iter()
+            .map(|m| {
+                let nanos_per_gas = m.nanos_per_gas();
+                let divergence = if median > 0.0 {
+                    nanos_per_gas / median
+                } else {
+                    1.0
+                };
+                let flag = if divergence > max_divergence {
+                    Some(Pricing::Underpriced)
+                } else if divergence * max_divergence < 1.0 {
+                    Some(Pricing::Overpriced)
+                } else {
+                    None
+                };
+                ClassReport {
+                    gas_per_iteration: m.gas_used as f64 / m.iterations.max(1) as f64,
+                    nanos_per_gas,
+                    block_secs: nanos_per_gas * BLOCK_GAS_LIMIT as f64 / 1e9,
+                    divergence,
+                    flag,
+                    measurement: m,
+                }
+            })
+            .collect();
+
+        Self {
+            median_nanos_per_gas: median,
+            max_divergence,
+            classes,
+        }
+    }
+
+    pub fn flagged(&self) -> impl Iterator<Item = &ClassReport> {
+        self.classes.iter().filter(|c| c.flag.is_some())
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<14} {:>10} {:>14} {:>12} {:>10} {:>10} {:>10}  flag",
+            "workload", "iterations", "gas", "gas/iter", "ns/gas", "block s", "divergence"
+        )?;
+        for c in self.classes.iter() {
+            let flag = match c.flag {
+                Some(Pricing::Underpriced) => "UNDERPRICED",
+                Some(Pricing::Overpriced) => "OVERPRICED",
+                None => "",
+            };
+            writeln!(
+                f,
+                "{:<14} {:>10} {:>14} {:>12.1} {:>10.4} {:>10.2} {:>10.2}  {flag}",
+                c.measurement.workload.as_str(),
+                c.measurement.iterations,
+                c.measurement.gas_used,
+                c.gas_per_iteration,
+                c.nanos_per_gas,
+                c.block_secs,
+                c.divergence,
+            )?;
+        }
+        write!(
+            f,
+            "median: {:.4} ns/gas; flagged beyond {}x either way",
+            self.median_nanos_per_gas, self.max_divergence
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workload::Workload;
+
+    use super::{Measurement, Pricing, Report};
+
+    fn measurement(workload: Workload, gas_used: u64, elapsed_nanos: u64) -> Measurement {
+        Measurement {
+            workload,
+            iterations: 100,
+            gas_used,
+            elapsed_nanos,
+        }
+    }
+
+    #[test]
+    fn flag_divergent_classes() {
+        let report = Report::new(
+            vec![
+                measurement(Workload::Arithmetic, 1000, 1000),
+                measurement(Workload::Keccak, 1000, 1200),
+                measurement(Workload::StorageRead, 1000, 900),
+                measurement(Workload::StorageWrite, 1000, 5000),
+                measurement(Workload::Create, 1000, 100),
+            ],
+            3.0,
+        );
+
+        assert_eq!(report.median_nanos_per_gas, 1.0);
+
+        let flags = report
+            .classes
+            .iter()
+            .map(|c| (c.measurement.workload, c.flag))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flags,
+            vec![
+                (Workload::Arithmetic, None),
+                (Workload::Keccak, None),
+                (Workload::StorageRead, None),
+                (Workload::StorageWrite, Some(Pricing::Underpriced)),
+                (Workload::Create, Some(Pricing::Overpriced)),
+            ]
+        );
+        assert_eq!(report.flagged().count(), 2);
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::asm::*;
+
+/// A class of EVM operations, measured by a contract which repeats them in a loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Workload {
+    /// Arithmetic on the stack, the cheapest kind of work a contract does.
+    Arithmetic,
+    /// Write new storage slots.
+    StorageWrite,
+    /// Read storage slots which were never written.
+    StorageRead,
+    /// Hash 64 bytes of memory.
+    Keccak,
+    /// Call the contract itself, which returns straight away.
+    Call,
+    /// Deploy an empty contract.
+    Create,
+}
+
+impl Workload {
+    pub const ALL: [Workload; 6] = [
+        Workload::Arithmetic,
+        Workload::StorageWrite,
+        Workload::StorageRead,
+        Workload::Keccak,
+        Workload::Call,
+        Workload::Create,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Workload::Arithmetic => "arithmetic",
+            Workload::StorageWrite => "storage_write",
+            Workload::StorageRead => "storage_read",
+            Workload::Keccak => "keccak",
+            Workload::Call => "call",
+            Workload::Create => "create",
+        }
+    }
+
+    /// Iterations per message, so that the workloads use gas of roughly the same order of magnitude.
+    pub fn default_iterations(&self) -> u64 {
+        match self {
+            Workload::Arithmetic => 20_000,
+            Workload::StorageWrite => 200,
+            Workload::StorageRead => 500,
+            Workload::Keccak => 5_000,
+            Workload::Call => 200,
+            Workload::Create => 20,
+        }
+    }
+
+    /// Bytecode of the contract repeating the operation as many times as the call data says.
+    ///
+    /// The call data is two words: the number of iterations, and a salt which
+    /// the storage writes add to the slot keys, so that every message writes new slots.
+    pub fn runtime(&self) -> Vec<u8> {
+        let body: &[u8] = match self {
+            // 3 % (i * i + 7)
+            Workload::Arithmetic => &[DUP1, DUP1, MUL, PUSH1, 7, ADD, PUSH1, 3, MOD, POP],
+            // sstore(i + salt, i)
+            Workload::StorageWrite => &[DUP1, DUP1, PUSH1, 32, CALLDATALOAD, ADD, SSTORE],
+            // sload(i)
+            Workload::StorageRead => &[DUP1, SLOAD, POP],
+            // mstore(0, i); mstore(32, keccak256(0, 64))
+            Workload::Keccak => &[
+                DUP1, PUSH1, 0, MSTORE, PUSH1, 64, PUSH1, 0, KECCAK256, PUSH1, 32, MSTORE,
+            ],
+            // call(gas(), address(), 0, 0, 0, 0, 0), which finds zero iterations in the call data.
+            Workload::Call => &[
+                PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, ADDRESS, GAS, CALL, POP,
+            ],
+            // create(0, 0, 1), with the initcode being a single STOP from the empty memory.
+            Workload::Create => &[PUSH1, 1, PUSH1, 0, PUSH1, 0, CREATE, POP],
+        };
+        Asm::new().repeat(body).assemble()
+    }
+
+    pub fn calldata(iterations: u64, salt: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        data[24..32].copy_from_slice(&iterations.to_be_bytes());
+        // Put the salt high up so the slots don't overlap with the ones written by other messages.
+        data[32..40].copy_from_slice(&salt.to_be_bytes());
+        data
+    }
+}
+
+impl Display for Workload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Workload {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Workload::ALL
+            .into_iter()
+            .find(|w| w.as_str() == s)
+            .ok_or_else(|| anyhow!("unknown workload: {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Workload;
+
+    #[test]
+    fn parse_workloads() {
+        for w in Workload::ALL {
+            assert_eq!(w.as_str().parse::<Workload>().unwrap(), w);
+        }
+        assert!("sha256".parse::<Workload>().is_err());
+    }
+}
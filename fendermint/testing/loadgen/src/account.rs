@@ -0,0 +1,49 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::signers::Wallet;
+use ethers::types::H160;
+use ethers::utils::keccak256;
+use fendermint_crypto::SecretKey;
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fvm_shared::address::Address;
+
+/// An account sending transactions, with its addresses in both the Ethereum and the FVM format.
+pub struct Account {
+    pub secret_key: SecretKey,
+    /// Signs the Ethereum transactions.
+    pub wallet: Wallet<SigningKey>,
+    /// The `f410` address of the key, used through the Ethereum API.
+    pub eth_addr: H160,
+    /// The `f1` address of the key, used through the Tendermint RPC.
+    pub f1_addr: Address,
+}
+
+impl Account {
+    pub fn new(secret_key: SecretKey) -> anyhow::Result<Self> {
+        let pk = secret_key.public_key();
+        let wallet = Wallet::from_bytes(secret_key.serialize().as_ref())?;
+        let eth_addr = H160::from_slice(&EthAddress::from(pk).0);
+        let f1_addr = Address::new_secp256k1(&pk.serialize())?;
+        Ok(Self {
+            secret_key,
+            wallet,
+            eth_addr,
+            f1_addr,
+        })
+    }
+
+    /// Derive the key of the account with the given index from a seed, so that the same
+    /// accounts can be reused across runs without having to fund them again.
+    pub fn derive(seed: u64, index: u64) -> anyhow::Result<Self> {
+        let preimage = [
+            b"fendermint-loadgen".as_slice(),
+            &seed.to_be_bytes(),
+            &index.to_be_bytes(),
+        ]
+        .concat();
+        let secret_key = SecretKey::try_from(keccak256(preimage).to_vec())?;
+        Self::new(secret_key)
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Generate transaction load against a running Fendermint node, through either the Ethereum API
+//! or the Tendermint RPC, and report the throughput and the latency of inclusion as it goes.
+//!
+//! Example:
+//!
+//! ```text
+//! cargo run -p fendermint_loadgen --release -- \
+//!   --secret-key test-network/keys/alice.sk \
+//!   --target eth --url http://127.0.0.1:8545 \
+//!   --keys 50 --duration 60 --workload transfer:8,erc20:2
+//! ```
+//!
+//! The load is sent from accounts derived from a seed, which are funded by the account of the
+//! secret key before the run. Each account keeps track of its own nonce, and the transactions are
+//! considered included when they appear in a block, which is also when their latency is measured.
+use ethers::types::H256;
+
+pub mod account;
+pub mod runner;
+pub mod stats;
+pub mod target;
+pub mod workload;
+
+/// Hash of a transaction, as the target reports it in the blocks.
+pub type TxHash = H256;
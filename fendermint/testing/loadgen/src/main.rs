@@ -0,0 +1,184 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Send a configurable load to a running node and report the throughput and the latency.
+//!
+//! ```text
+//! cargo run -p fendermint_loadgen --release -- \
+//!   --secret-key test-network/keys/alice.sk \
+//!   --target eth --url http://127.0.0.1:8545 \
+//!   --keys 50 --duration 60 --workload transfer:8,erc20:2 \
+//!   --report-file loadgen.json --min-tps 100
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use clap::{Parser, ValueEnum};
+use ethers::types::U256;
+use fendermint_loadgen::account::Account;
+use fendermint_loadgen::runner::{self, RunConfig};
+use fendermint_loadgen::target::{EthTarget, Fees, Target, TendermintTarget};
+use fendermint_loadgen::workload::Workload;
+use fendermint_rpc::message::MessageFactory;
+use tendermint_rpc::Url;
+use tracing::Level;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TargetKind {
+    /// Send Ethereum transactions through the Ethereum API.
+    Eth,
+    /// Send FVM messages through the Tendermint RPC.
+    Tendermint,
+}
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// The API to send the transactions through.
+    #[arg(long, value_enum, default_value_t = TargetKind::Eth)]
+    pub target: TargetKind,
+
+    /// The URL of the Ethereum API or the Tendermint RPC, depending on the target.
+    #[arg(long, default_value = "http://127.0.0.1:8545")]
+    pub url: Url,
+
+    /// Path to the secret key of the account funding the load, in Base64 format.
+    ///
+    /// With the `eth` target it has to be an Ethereum account. If it's missing,
+    /// the accounts are expected to have been funded already, e.g. by an earlier run.
+    #[arg(long, short)]
+    pub secret_key: Option<PathBuf>,
+
+    /// Number of accounts to send transactions from at the same time.
+    #[arg(long, default_value_t = 10)]
+    pub keys: u64,
+
+    /// Seed to derive the keys of the accounts from, and of the random choices.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Whole tokens to send to each account before the run.
+    #[arg(long, default_value_t = 100)]
+    pub fund: u64,
+
+    /// Kinds of transactions to send with their weights, out of `transfer`, `erc20` and `deploy`.
+    #[arg(long, default_value = "transfer")]
+    pub workload: Workload,
+
+    /// Seconds to send transactions for.
+    #[arg(long, default_value_t = 60)]
+    pub duration: u64,
+
+    /// Transactions per second to send across all accounts; 0 means as fast as possible.
+    #[arg(long, default_value_t = 0.0)]
+    pub rate: f64,
+
+    /// Maximum number of transactions per account waiting to be included.
+    #[arg(long, default_value_t = 16)]
+    pub max_pending: usize,
+
+    /// Seconds after which a transaction not included in a block is considered dropped.
+    #[arg(long, default_value_t = 60)]
+    pub tx_timeout: u64,
+
+    /// Seconds between the reports of the throughput and the latency.
+    #[arg(long, default_value_t = 5)]
+    pub report_interval: u64,
+
+    /// Milliseconds between looking for new blocks.
+    #[arg(long, default_value_t = 250)]
+    pub poll_interval: u64,
+
+    /// Maximum fee per gas, in atto.
+    #[arg(long, default_value_t = 1_000_000_000)]
+    pub gas_fee_cap: u64,
+
+    /// Priority fee per gas, in atto.
+    #[arg(long, default_value_t = 1)]
+    pub gas_premium: u64,
+
+    /// Write the summary of the run to a JSON file.
+    #[arg(long)]
+    pub report_file: Option<PathBuf>,
+
+    /// Fail if the throughput of the run is less than this many transactions per second.
+    #[arg(long)]
+    pub min_tps: Option<f64>,
+
+    /// Enable DEBUG logs.
+    #[arg(long, short)]
+    pub verbose: bool,
+}
+
+impl Options {
+    pub fn log_level(&self) -> Level {
+        if self.verbose {
+            Level::DEBUG
+        } else {
+            Level::INFO
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opts = Options::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(opts.log_level())
+        .init();
+
+    let fees = Fees {
+        gas_fee_cap: opts.gas_fee_cap,
+        gas_premium: opts.gas_premium,
+    };
+
+    let target: Arc<dyn Target> = match opts.target {
+        TargetKind::Eth => Arc::new(EthTarget::new(opts.url.clone(), fees).await?),
+        TargetKind::Tendermint => Arc::new(TendermintTarget::new(opts.url.clone(), fees).await?),
+    };
+
+    let accounts = (0..opts.keys)
+        .map(|i| Account::derive(opts.seed, i))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .context("failed to derive accounts")?;
+
+    let faucet = match opts.secret_key {
+        Some(ref sk) => {
+            let sk = MessageFactory::read_secret_key(sk).context("failed to read secret key")?;
+            let amount = U256::from(opts.fund) * U256::exp10(18);
+            Some((Account::new(sk)?, amount))
+        }
+        None => None,
+    };
+
+    let config = RunConfig {
+        workload: opts.workload.clone(),
+        duration: Duration::from_secs(opts.duration),
+        rate: opts.rate,
+        max_pending: opts.max_pending.max(1),
+        tx_timeout: Duration::from_secs(opts.tx_timeout),
+        report_interval: Duration::from_secs(opts.report_interval.max(1)),
+        poll_interval: Duration::from_millis(opts.poll_interval.max(10)),
+        seed: opts.seed,
+    };
+
+    let summary = runner::run(target, faucet, accounts, config).await?;
+
+    println!("{summary}");
+
+    if let Some(ref path) = opts.report_file {
+        let json = serde_json::to_string_pretty(&summary)?;
+        std::fs::write(path, json).with_context(|| format!("failed to write {path:?}"))?;
+    }
+
+    match opts.min_tps {
+        Some(min_tps) if summary.tps < min_tps => Err(anyhow!(
+            "the throughput of {:.1} TPS is below the minimum of {min_tps}",
+            summary.tps
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Send the load from many accounts at the same time, and follow the blocks to see when it gets included.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use ethers::types::{H160, U256};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::account::Account;
+use crate::stats::{Stats, Summary};
+use crate::target::Target;
+use crate::workload::{send_coin_calldata, Tx, TxKind, Workload, SIMPLE_COIN};
+use crate::TxHash;
+
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    pub workload: Workload,
+    /// How long to send transactions for.
+    pub duration: Duration,
+    /// Transactions to send per second across all accounts; 0 means as fast as possible.
+    pub rate: f64,
+    /// Maximum number of transactions of an account waiting to be included.
+    pub max_pending: usize,
+    /// Consider a transaction dropped if it's not included in this long.
+    pub tx_timeout: Duration,
+    /// How often to print the throughput and the latency.
+    pub report_interval: Duration,
+    /// How often to look for new blocks.
+    pub poll_interval: Duration,
+    /// Seed of the random choices of the workload.
+    pub seed: u64,
+}
+
+/// A transaction waiting to be included.
+struct PendingTx {
+    sent_at: Instant,
+    account: usize,
+    /// Whether the transaction is part of the load, or just setting it up.
+    measured: bool,
+}
+
+/// Transactions waiting to be included, and the stats of the ones which were.
+#[derive(Default)]
+struct Tracker {
+    pending: HashMap<TxHash, PendingTx>,
+    in_flight: HashMap<usize, usize>,
+    stats: Stats,
+}
+
+impl Tracker {
+    fn sent(&mut self, hash: TxHash, account: usize, sent_at: Instant, measured: bool) {
+        self.pending.insert(
+            hash,
+            PendingTx {
+                sent_at,
+                account,
+                measured,
+            },
+        );
+        *self.in_flight.entry(account).or_default() += 1;
+        if measured {
+            self.stats.sent += 1;
+        }
+    }
+
+    fn included(&mut self, hashes: &[TxHash], now: Instant) {
+        for hash in hashes {
+            if let Some(tx) = self.remove(hash) {
+                if tx.measured {
+                    self.stats.included(now.duration_since(tx.sent_at));
+                }
+            }
+        }
+    }
+
+    /// Give up on the transactions which have been waiting for too long.
+    fn expire(&mut self, timeout: Duration, now: Instant) {
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, tx)| now.duration_since(tx.sent_at) > timeout)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+
+        for hash in expired {
+            if let Some(tx) = self.remove(&hash) {
+                tracing::debug!(?hash, account = tx.account, "transaction dropped");
+                if tx.measured {
+                    self.stats.dropped += 1;
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, hash: &TxHash) -> Option<PendingTx> {
+        let tx = self.pending.remove(hash)?;
+        if let Some(n) = self.in_flight.get_mut(&tx.account) {
+            *n -= 1;
+        }
+        Some(tx)
+    }
+
+    fn in_flight(&self, account: usize) -> usize {
+        self.in_flight.get(&account).copied().unwrap_or_default()
+    }
+}
+
+type SharedTracker = Arc<Mutex<Tracker>>;
+
+/// Fund the accounts, set up the contracts the workload needs, then send the load for the
+/// configured duration, printing a report periodically, and return the summary of the run.
+pub async fn run(
+    target: Arc<dyn Target>,
+    faucet: Option<(Account, U256)>,
+    accounts: Vec<Account>,
+    config: RunConfig,
+) -> anyhow::Result<Summary> {
+    if accounts.is_empty() {
+        return Err(anyhow!("there are no accounts to send the load from"));
+    }
+    let accounts = Arc::new(accounts);
+    let tracker = SharedTracker::default();
+
+    let from_height = target.latest_height().await? + 1;
+    let watcher = tokio::spawn(watch_blocks(
+        target.clone(),
+        tracker.clone(),
+        from_height,
+        config.clone(),
+    ));
+
+    if let Some((faucet, amount)) = faucet {
+        fund(&target, &tracker, &faucet, &accounts, amount, &config).await?;
+    }
+
+    let coins = if config.workload.contains(TxKind::Erc20) {
+        deploy_coins(&target, &accounts).await?
+    } else {
+        Vec::new()
+    };
+    let coins = Arc::new(coins);
+
+    tracing::info!(accounts = accounts.len(), "starting the load");
+
+    let start = Instant::now();
+    let deadline = start + config.duration;
+
+    let reporter = tokio::spawn(report(tracker.clone(), start, config.report_interval));
+
+    let mut workers = Vec::new();
+    for idx in 0..accounts.len() {
+        workers.push(tokio::spawn(send_load(
+            idx,
+            accounts.clone(),
+            coins.clone(),
+            target.clone(),
+            tracker.clone(),
+            config.clone(),
+            deadline,
+        )));
+    }
+    for worker in workers {
+        if let Err(e) = worker.await? {
+            tracing::error!(error = format!("{e:#}"), "account stopped sending");
+        }
+    }
+
+    // Wait for the transactions in flight; the watcher gives up on them after the timeout.
+    while !tracker.lock().unwrap().pending.is_empty() {
+        tokio::time::sleep(config.poll_interval).await;
+    }
+
+    watcher.abort();
+    reporter.abort();
+
+    let summary = tracker.lock().unwrap().stats.summary(start.elapsed());
+    Ok(summary)
+}
+
+/// Send some tokens from the faucet to each account, and wait until they are included.
+async fn fund(
+    target: &Arc<dyn Target>,
+    tracker: &SharedTracker,
+    faucet: &Account,
+    accounts: &[Account],
+    amount: U256,
+    config: &RunConfig,
+) -> anyhow::Result<()> {
+    let faucet_idx = usize::MAX;
+    let mut nonce = target.nonce(faucet).await?;
+
+    tracing::info!(accounts = accounts.len(), %amount, "funding accounts");
+
+    for account in accounts {
+        let tx = Tx::Transfer {
+            to: target.recipient(account),
+            value: amount,
+        };
+        let sent_at = Instant::now();
+        let hash = target
+            .submit(faucet, nonce, tx)
+            .await
+            .context("failed to fund account")?;
+        tracker
+            .lock()
+            .unwrap()
+            .sent(hash, faucet_idx, sent_at, false);
+        nonce += 1;
+
+        // Don't flood the mempool with transactions from the same sender.
+        while tracker.lock().unwrap().in_flight(faucet_idx) >= config.max_pending {
+            tokio::time::sleep(config.poll_interval).await;
+        }
+    }
+
+    while tracker.lock().unwrap().in_flight(faucet_idx) > 0 {
+        tokio::time::sleep(config.poll_interval).await;
+    }
+    Ok(())
+}
+
+/// Deploy a token contract for each account, to send the tokens of.
+async fn deploy_coins(
+    target: &Arc<dyn Target>,
+    accounts: &Arc<Vec<Account>>,
+) -> anyhow::Result<Vec<H160>> {
+    tracing::info!(accounts = accounts.len(), "deploying token contracts");
+
+    let mut deployments = Vec::new();
+    for idx in 0..accounts.len() {
+        let target = target.clone();
+        let accounts = accounts.clone();
+        deployments.push(tokio::spawn(async move {
+            let account = &accounts[idx];
+            let nonce = target.nonce(account).await?;
+            target.deploy(account, nonce, SIMPLE_COIN.clone()).await
+        }));
+    }
+
+    let mut coins = Vec::new();
+    for deployment in deployments {
+        coins.push(
+            deployment
+                .await?
+                .context("failed to deploy token contract")?,
+        );
+    }
+    Ok(coins)
+}
+
+/// Send transactions from an account until the deadline.
+async fn send_load(
+    idx: usize,
+    accounts: Arc<Vec<Account>>,
+    coins: Arc<Vec<H160>>,
+    target: Arc<dyn Target>,
+    tracker: SharedTracker,
+    config: RunConfig,
+    deadline: Instant,
+) -> anyhow::Result<()> {
+    let account = &accounts[idx];
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(idx as u64));
+    let mut nonce = target.nonce(account).await?;
+
+    // Spread the rate evenly over the accounts.
+    let pause =
+        (config.rate > 0.0).then(|| Duration::from_secs_f64(accounts.len() as f64 / config.rate));
+    let mut next_send = Instant::now();
+
+    while Instant::now() < deadline {
+        if tracker.lock().unwrap().in_flight(idx) >= config.max_pending {
+            tokio::time::sleep(config.poll_interval).await;
+            continue;
+        }
+        if let Some(pause) = pause {
+            tokio::time::sleep_until(next_send.into()).await;
+            // Catch up after waiting for the pending transactions, but not in a burst.
+            next_send += pause;
+            if let Some(floor) = Instant::now().checked_sub(pause) {
+                next_send = next_send.max(floor);
+            }
+        }
+
+        let other = &accounts[rng.gen_range(0..accounts.len())];
+        let tx = match config.workload.pick(&mut rng) {
+            TxKind::Transfer => Tx::Transfer {
+                to: target.recipient(other),
+                value: U256::one(),
+            },
+            TxKind::Erc20 => Tx::Invoke {
+                contract: coins[idx],
+                calldata: send_coin_calldata(other.eth_addr, 1),
+            },
+            TxKind::Deploy => Tx::Deploy {
+                bytecode: SIMPLE_COIN.clone(),
+            },
+        };
+
+        let sent_at = Instant::now();
+        match target.submit(account, nonce, tx).await {
+            Ok(hash) => {
+                tracker.lock().unwrap().sent(hash, idx, sent_at, true);
+                nonce += 1;
+            }
+            Err(e) => {
+                tracing::debug!(
+                    account = idx,
+                    nonce,
+                    error = format!("{e:#}"),
+                    "failed to send transaction"
+                );
+                tracker.lock().unwrap().stats.failed += 1;
+                // The nonce might be off; wait for what's in flight, then ask the node.
+                while tracker.lock().unwrap().in_flight(idx) > 0 {
+                    tokio::time::sleep(config.poll_interval).await;
+                }
+                nonce = target.nonce(account).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follow the new blocks, marking the transactions in them as included.
+async fn watch_blocks(
+    target: Arc<dyn Target>,
+    tracker: SharedTracker,
+    mut next_height: u64,
+    config: RunConfig,
+) {
+    loop {
+        match target.latest_height().await {
+            Ok(latest) => {
+                while next_height <= latest {
+                    match target.block_txs(next_height).await {
+                        Ok(hashes) => {
+                            tracker.lock().unwrap().included(&hashes, Instant::now());
+                            next_height += 1;
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                height = next_height,
+                                error = format!("{e:#}"),
+                                "failed to get block"
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = format!("{e:#}"), "failed to get the latest height")
+            }
+        }
+        tracker
+            .lock()
+            .unwrap()
+            .expire(config.tx_timeout, Instant::now());
+
+        tokio::time::sleep(config.poll_interval).await;
+    }
+}
+
+/// Print the throughput and the latency of every interval.
+async fn report(tracker: SharedTracker, start: Instant, interval: Duration) {
+    let mut earlier = (start, Stats::default());
+    loop {
+        tokio::time::sleep(interval).await;
+        let now = Instant::now();
+        let (summary, stats, pending) = {
+            let tracker = tracker.lock().unwrap();
+            let summary = tracker
+                .stats
+                .summary_since(&earlier.1, now.duration_since(earlier.0));
+            (summary, tracker.stats.clone(), tracker.pending.len())
+        };
+        println!(
+            "{summary} pending={pending} total_included={}",
+            stats.included_count()
+        );
+        earlier = (now, stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use ethers::types::H256;
+
+    use super::Tracker;
+
+    #[test]
+    fn track_inclusion() {
+        let mut tracker = Tracker::default();
+        let t0 = Instant::now();
+        let (a, b, c) = (
+            H256::repeat_byte(1),
+            H256::repeat_byte(2),
+            H256::repeat_byte(3),
+        );
+
+        tracker.sent(a, 0, t0, true);
+        tracker.sent(b, 0, t0, true);
+        tracker.sent(c, 1, t0, false);
+        assert_eq!(tracker.in_flight(0), 2);
+
+        tracker.included(&[a, c, H256::zero()], t0 + Duration::from_secs(2));
+        assert_eq!(tracker.in_flight(0), 1);
+        assert_eq!(tracker.in_flight(1), 0);
+        // Setup transactions are not measured.
+        assert_eq!(tracker.stats.included_count(), 1);
+
+        tracker.expire(Duration::from_secs(5), t0 + Duration::from_secs(10));
+        assert!(tracker.pending.is_empty());
+        assert_eq!(tracker.stats.dropped, 1);
+        assert_eq!(tracker.stats.sent, 2);
+    }
+}
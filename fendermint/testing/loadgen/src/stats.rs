@@ -0,0 +1,154 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Counts and latencies of the transactions sent during the run.
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    /// Transactions accepted by the node.
+    pub sent: u64,
+    /// Transactions the node refused to accept.
+    pub failed: u64,
+    /// Transactions accepted but not included in a block within the timeout.
+    pub dropped: u64,
+    /// Time from sending to inclusion of each included transaction.
+    latencies: Vec<Duration>,
+}
+
+impl Stats {
+    pub fn included(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    pub fn included_count(&self) -> u64 {
+        self.latencies.len() as u64
+    }
+
+    /// Summarize the transactions so far, over the time they took.
+    pub fn summary(&self, elapsed: Duration) -> Summary {
+        self.summary_since(&Stats::default(), elapsed)
+    }
+
+    /// Summarize the transactions since an earlier snapshot of the stats.
+    pub fn summary_since(&self, earlier: &Stats, elapsed: Duration) -> Summary {
+        let mut latencies = self.latencies[earlier.latencies.len().min(self.latencies.len())..]
+            .iter()
+            .map(|d| d.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+
+        let included = latencies.len() as u64;
+        let secs = elapsed.as_secs_f64();
+
+        Summary {
+            elapsed_secs: secs,
+            sent: self.sent - earlier.sent,
+            included,
+            failed: self.failed - earlier.failed,
+            dropped: self.dropped - earlier.dropped,
+            tps: if secs > 0.0 {
+                included as f64 / secs
+            } else {
+                0.0
+            },
+            latency_ms: Latency {
+                p50: percentile(&latencies, 50.0),
+                p95: percentile(&latencies, 95.0),
+                p99: percentile(&latencies, 99.0),
+                max: latencies.last().copied().unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// Throughput and latency over a period, which is what gets reported.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub elapsed_secs: f64,
+    pub sent: u64,
+    pub included: u64,
+    pub failed: u64,
+    pub dropped: u64,
+    /// Transactions included per second.
+    pub tps: f64,
+    pub latency_ms: Latency,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Latency {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>7.1}s sent={} included={} failed={} dropped={} tps={:.1} latency p50={:.0}ms p95={:.0}ms p99={:.0}ms max={:.0}ms",
+            self.elapsed_secs,
+            self.sent,
+            self.included,
+            self.failed,
+            self.dropped,
+            self.tps,
+            self.latency_ms.p50,
+            self.latency_ms.p95,
+            self.latency_ms.p99,
+            self.latency_ms.max
+        )
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{percentile, Stats};
+
+    #[test]
+    fn nearest_rank() {
+        let xs = (1..=100).map(|x| x as f64).collect::<Vec<_>>();
+        assert_eq!(percentile(&xs, 50.0), 50.0);
+        assert_eq!(percentile(&xs, 99.0), 99.0);
+        assert_eq!(percentile(&[7.0], 95.0), 7.0);
+        assert_eq!(percentile(&[], 95.0), 0.0);
+    }
+
+    #[test]
+    fn summary_of_interval() {
+        let mut stats = Stats {
+            sent: 3,
+            ..Default::default()
+        };
+        stats.included(Duration::from_millis(100));
+        let earlier = stats.clone();
+        stats.sent = 5;
+        stats.included(Duration::from_millis(300));
+        stats.included(Duration::from_millis(200));
+
+        let s = stats.summary_since(&earlier, Duration::from_secs(2));
+        assert_eq!(s.sent, 2);
+        assert_eq!(s.included, 2);
+        assert_eq!(s.tps, 1.0);
+        assert_eq!(s.latency_ms.p50, 200.0);
+
+        let s = stats.summary(Duration::from_secs(3));
+        assert_eq!(s.included, 3);
+        assert_eq!(s.latency_ms.max, 300.0);
+    }
+}
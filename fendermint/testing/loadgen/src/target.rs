@@ -0,0 +1,283 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The APIs the load can be sent through.
+
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{BlockNumber, Bytes, Eip1559TransactionRequest, H160, H256};
+use fendermint_rpc::client::{FendermintClient, TendermintClient};
+use fendermint_rpc::message::{GasParams, MessageFactory};
+use fendermint_rpc::query::QueryClient;
+use fendermint_rpc::response::decode_fevm_create;
+use fendermint_vm_actor_interface::eam;
+use fendermint_vm_message::query::FvmQueryHeight;
+use fvm_shared::address::Address;
+use fvm_shared::chainid::ChainID;
+use fvm_shared::econ::TokenAmount;
+use tendermint::crypto::sha256::Sha256;
+use tendermint_rpc::{Client, HttpClient, Url};
+
+use crate::account::Account;
+use crate::workload::{Recipient, Tx};
+use crate::TxHash;
+
+/// Fees to send the transactions with, in atto.
+#[derive(Debug, Clone)]
+pub struct Fees {
+    pub gas_fee_cap: u64,
+    pub gas_premium: u64,
+}
+
+/// Where the transactions are sent.
+#[async_trait]
+pub trait Target: Send + Sync {
+    /// The recipient of transfers to an account, in the address format of the target.
+    fn recipient(&self, account: &Account) -> Recipient;
+
+    /// The nonce of the next transaction of an account, including the ones in the mempool.
+    async fn nonce(&self, account: &Account) -> anyhow::Result<u64>;
+
+    /// Sign and send a transaction without waiting for it to be included.
+    async fn submit(&self, account: &Account, nonce: u64, tx: Tx) -> anyhow::Result<TxHash>;
+
+    /// Sign and send a contract deployment, and wait for the address of the contract.
+    async fn deploy(
+        &self,
+        account: &Account,
+        nonce: u64,
+        bytecode: Vec<u8>,
+    ) -> anyhow::Result<H160>;
+
+    /// The height of the latest block.
+    async fn latest_height(&self) -> anyhow::Result<u64>;
+
+    /// The hashes of the transactions in a block.
+    async fn block_txs(&self, height: u64) -> anyhow::Result<Vec<TxHash>>;
+}
+
+/// Send transactions through the Ethereum API, signed by the `f410` addresses of the accounts.
+pub struct EthTarget {
+    provider: Provider<Http>,
+    chain_id: u64,
+    fees: Fees,
+}
+
+impl EthTarget {
+    pub async fn new(url: Url, fees: Fees) -> anyhow::Result<Self> {
+        let provider = Provider::<Http>::try_from(url.to_string())?;
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .context("failed to get the chain ID")?
+            .as_u64();
+        Ok(Self {
+            provider,
+            chain_id,
+            fees,
+        })
+    }
+
+    fn sign(&self, account: &Account, nonce: u64, tx: Tx) -> anyhow::Result<Bytes> {
+        let gas_limit = tx.kind().gas_limit();
+        let mut req = Eip1559TransactionRequest::new()
+            .from(account.eth_addr)
+            .nonce(nonce)
+            .gas(gas_limit)
+            .max_fee_per_gas(self.fees.gas_fee_cap)
+            .max_priority_fee_per_gas(self.fees.gas_premium)
+            .chain_id(self.chain_id);
+
+        req = match tx {
+            Tx::Transfer { to, value } => match to {
+                Recipient::Eth(to) => req.to(to).value(value),
+                Recipient::Fvm(to) => bail!("cannot send to {to} through the Ethereum API"),
+            },
+            Tx::Invoke { contract, calldata } => req.to(contract).data(calldata),
+            Tx::Deploy { bytecode } => req.data(bytecode),
+        };
+
+        let tx = TypedTransaction::Eip1559(req);
+        // The chain ID of the transaction takes precedence over the one of the wallet.
+        let sig = account.wallet.sign_transaction_sync(&tx)?;
+
+        Ok(tx.rlp_signed(&sig))
+    }
+}
+
+#[async_trait]
+impl Target for EthTarget {
+    fn recipient(&self, account: &Account) -> Recipient {
+        Recipient::Eth(account.eth_addr)
+    }
+
+    async fn nonce(&self, account: &Account) -> anyhow::Result<u64> {
+        let nonce = self
+            .provider
+            .get_transaction_count(account.eth_addr, Some(BlockNumber::Pending.into()))
+            .await?;
+        Ok(nonce.as_u64())
+    }
+
+    async fn submit(&self, account: &Account, nonce: u64, tx: Tx) -> anyhow::Result<TxHash> {
+        let rlp = self.sign(account, nonce, tx)?;
+        let pending = self.provider.send_raw_transaction(rlp).await?;
+        Ok(pending.tx_hash())
+    }
+
+    async fn deploy(
+        &self,
+        account: &Account,
+        nonce: u64,
+        bytecode: Vec<u8>,
+    ) -> anyhow::Result<H160> {
+        let rlp = self.sign(account, nonce, Tx::Deploy { bytecode })?;
+        let receipt = self
+            .provider
+            .send_raw_transaction(rlp)
+            .await?
+            .await?
+            .ok_or_else(|| anyhow!("no receipt for the deployment"))?;
+
+        if receipt.status != Some(1.into()) {
+            bail!("deployment failed: {receipt:?}");
+        }
+        receipt
+            .contract_address
+            .ok_or_else(|| anyhow!("no contract address in the receipt"))
+    }
+
+    async fn latest_height(&self) -> anyhow::Result<u64> {
+        Ok(self.provider.get_block_number().await?.as_u64())
+    }
+
+    async fn block_txs(&self, height: u64) -> anyhow::Result<Vec<TxHash>> {
+        let block = self.provider.get_block(height).await?;
+        Ok(block.map(|b| b.transactions).unwrap_or_default())
+    }
+}
+
+/// Send transactions through the Tendermint RPC, signed by the `f1` addresses of the accounts.
+pub struct TendermintTarget {
+    client: FendermintClient,
+    chain_id: ChainID,
+    fees: Fees,
+}
+
+impl TendermintTarget {
+    pub async fn new(url: Url, fees: Fees) -> anyhow::Result<Self> {
+        let client = FendermintClient::new_http(url, None)?;
+        let chain_id = client
+            .state_params(FvmQueryHeight::default())
+            .await
+            .context("failed to get the state params")?
+            .value
+            .chain_id;
+        Ok(Self {
+            client,
+            chain_id: ChainID::from(chain_id),
+            fees,
+        })
+    }
+
+    fn sign(&self, account: &Account, nonce: u64, tx: Tx) -> anyhow::Result<Vec<u8>> {
+        let mut mf =
+            MessageFactory::new_secp256k1(account.secret_key.clone(), nonce, self.chain_id);
+        let gas_params = GasParams {
+            gas_limit: tx.kind().gas_limit(),
+            gas_fee_cap: TokenAmount::from_atto(self.fees.gas_fee_cap),
+            gas_premium: TokenAmount::from_atto(self.fees.gas_premium),
+        };
+        let msg = match tx {
+            Tx::Transfer { to, value } => {
+                let to = match to {
+                    Recipient::Fvm(to) => to,
+                    Recipient::Eth(to) => Address::new_delegated(eam::EAM_ACTOR_ID, &to.0)
+                        .context("invalid address")?,
+                };
+                mf.transfer(to, TokenAmount::from_atto(value.as_u128()), gas_params)?
+            }
+            Tx::Invoke { contract, calldata } => {
+                let contract = Address::new_delegated(eam::EAM_ACTOR_ID, &contract.0)
+                    .context("invalid contract address")?;
+                mf.fevm_invoke(
+                    contract,
+                    calldata.into(),
+                    TokenAmount::default(),
+                    gas_params,
+                )?
+            }
+            Tx::Deploy { bytecode } => mf.fevm_create(
+                bytecode.into(),
+                Default::default(),
+                TokenAmount::default(),
+                gas_params,
+            )?,
+        };
+        MessageFactory::serialize(&msg)
+    }
+
+    fn tendermint(&self) -> &HttpClient {
+        self.client.underlying()
+    }
+}
+
+#[async_trait]
+impl Target for TendermintTarget {
+    fn recipient(&self, account: &Account) -> Recipient {
+        Recipient::Fvm(account.f1_addr)
+    }
+
+    async fn nonce(&self, account: &Account) -> anyhow::Result<u64> {
+        let state = self
+            .client
+            .actor_state(&account.f1_addr, FvmQueryHeight::Pending)
+            .await?;
+        Ok(state.value.map(|(_, s)| s.sequence).unwrap_or_default())
+    }
+
+    async fn submit(&self, account: &Account, nonce: u64, tx: Tx) -> anyhow::Result<TxHash> {
+        let tx = self.sign(account, nonce, tx)?;
+        let res = self.tendermint().broadcast_tx_sync(tx).await?;
+        if res.code.is_err() {
+            bail!("check failed with code {}: {}", res.code.value(), res.log);
+        }
+        Ok(H256::from_slice(res.hash.as_bytes()))
+    }
+
+    async fn deploy(
+        &self,
+        account: &Account,
+        nonce: u64,
+        bytecode: Vec<u8>,
+    ) -> anyhow::Result<H160> {
+        let tx = self.sign(account, nonce, Tx::Deploy { bytecode })?;
+        let res = self.tendermint().broadcast_tx_commit(tx).await?;
+        if res.check_tx.code.is_err() {
+            bail!("deployment check failed: {}", res.check_tx.log);
+        }
+        if res.deliver_tx.code.is_err() {
+            bail!("deployment failed: {}", res.deliver_tx.log);
+        }
+        let ret = decode_fevm_create(&res.deliver_tx)?;
+        Ok(H160::from_slice(&ret.eth_address.0))
+    }
+
+    async fn latest_height(&self) -> anyhow::Result<u64> {
+        let status = self.tendermint().status().await?;
+        Ok(status.sync_info.latest_block_height.value())
+    }
+
+    async fn block_txs(&self, height: u64) -> anyhow::Result<Vec<TxHash>> {
+        let height = tendermint::block::Height::try_from(height)?;
+        let res = self.tendermint().block(height).await?;
+        Ok(res
+            .block
+            .data
+            .iter()
+            .map(|tx| H256::from(tendermint::crypto::default::Sha256::digest(tx)))
+            .collect())
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use ethers::abi::Token;
+use ethers::types::{H160, U256};
+use lazy_static::lazy_static;
+use rand::Rng;
+
+lazy_static! {
+    /// The token contract deployed by the `deploy` and `erc20` workloads.
+    pub static ref SIMPLE_COIN: Vec<u8> =
+        hex::decode(include_str!("../../contracts/SimpleCoin.bin").trim())
+            .expect("SimpleCoin.bin is hex");
+}
+
+/// The kinds of transactions the load can be made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxKind {
+    /// Native token transfers between the accounts.
+    Transfer,
+    /// Token transfers between the accounts in a contract with an ERC-20 like `transfer`.
+    ///
+    /// Every account deploys its own contract before the run, which mints it 10000 coins;
+    /// once they are spent, the transfers keep costing the same but don't move any coins.
+    Erc20,
+    /// Deployments of a new contract.
+    Deploy,
+}
+
+impl TxKind {
+    /// Gas limit to send the transactions with, generous enough not to run out.
+    pub fn gas_limit(&self) -> u64 {
+        match self {
+            TxKind::Transfer => 10_000_000,
+            TxKind::Erc20 => 50_000_000,
+            TxKind::Deploy => 500_000_000,
+        }
+    }
+}
+
+impl Display for TxKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxKind::Transfer => write!(f, "transfer"),
+            TxKind::Erc20 => write!(f, "erc20"),
+            TxKind::Deploy => write!(f, "deploy"),
+        }
+    }
+}
+
+impl FromStr for TxKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transfer" => Ok(TxKind::Transfer),
+            "erc20" => Ok(TxKind::Erc20),
+            "deploy" => Ok(TxKind::Deploy),
+            other => Err(anyhow!("unknown workload: {other}")),
+        }
+    }
+}
+
+/// Weighted mix of transaction kinds, e.g. `transfer:8,erc20:2`; the weight defaults to 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workload(Vec<(TxKind, u32)>);
+
+impl Workload {
+    /// Pick the kind of the next transaction at random, according to the weights.
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> TxKind {
+        let total: u32 = self.0.iter().map(|(_, w)| w).sum();
+        let mut r = rng.gen_range(0..total);
+        for (kind, weight) in self.0.iter() {
+            if r < *weight {
+                return *kind;
+            }
+            r -= weight;
+        }
+        unreachable!("the weights add up to the total")
+    }
+
+    pub fn contains(&self, kind: TxKind) -> bool {
+        self.0.iter().any(|(k, _)| *k == kind)
+    }
+}
+
+impl FromStr for Workload {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = Vec::new();
+        for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let (kind, weight) = match part.split_once(':') {
+                Some((kind, weight)) => (
+                    kind,
+                    weight
+                        .parse()
+                        .with_context(|| format!("invalid weight in {part}"))?,
+                ),
+                None => (part, 1),
+            };
+            if weight > 0 {
+                mix.push((kind.parse()?, weight));
+            }
+        }
+        if mix.is_empty() {
+            bail!("the workload is empty");
+        }
+        Ok(Self(mix))
+    }
+}
+
+/// A transaction to send, which the target turns into its own format.
+#[derive(Debug, Clone)]
+pub enum Tx {
+    /// Send some native tokens.
+    Transfer { to: Recipient, value: U256 },
+    /// Call a contract with some calldata.
+    Invoke { contract: H160, calldata: Vec<u8> },
+    /// Deploy a contract.
+    Deploy { bytecode: Vec<u8> },
+}
+
+/// Recipient of a transfer, in the format of the target.
+#[derive(Debug, Clone)]
+pub enum Recipient {
+    Eth(H160),
+    Fvm(fvm_shared::address::Address),
+}
+
+impl Tx {
+    pub fn kind(&self) -> TxKind {
+        match self {
+            Tx::Transfer { .. } => TxKind::Transfer,
+            Tx::Invoke { .. } => TxKind::Erc20,
+            Tx::Deploy { .. } => TxKind::Deploy,
+        }
+    }
+}
+
+/// Calldata of `sendCoin(address,uint256)` on the SimpleCoin contract.
+pub fn send_coin_calldata(receiver: H160, amount: u64) -> Vec<u8> {
+    let selector = &ethers::utils::id("sendCoin(address,uint256)")[..];
+    let args = ethers::abi::encode(&[Token::Address(receiver), Token::Uint(U256::from(amount))]);
+    [selector, &args].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{send_coin_calldata, TxKind, Workload};
+
+    #[test]
+    fn parse_workload() {
+        let w: Workload = "transfer:8, erc20:2,deploy".parse().unwrap();
+        assert_eq!(
+            w,
+            Workload(vec![
+                (TxKind::Transfer, 8),
+                (TxKind::Erc20, 2),
+                (TxKind::Deploy, 1)
+            ])
+        );
+        assert!("transfer:0".parse::<Workload>().is_err());
+        assert!("mint".parse::<Workload>().is_err());
+        assert!("transfer:x".parse::<Workload>().is_err());
+    }
+
+    #[test]
+    fn pick_by_weight() {
+        let w: Workload = "transfer:3,deploy:1".parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        let transfers = (0..4000)
+            .filter(|_| w.pick(&mut rng) == TxKind::Transfer)
+            .count();
+        assert!((2800..3200).contains(&transfers), "{transfers}");
+    }
+
+    #[test]
+    fn send_coin_selector() {
+        let calldata = send_coin_calldata(Default::default(), 1);
+        assert_eq!(hex::encode(&calldata[..4]), "90b98a11");
+        assert_eq!(calldata.len(), 4 + 32 * 2);
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The access control actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it, keeps the allow-lists in its state, and handles the
+//! messages the governor sends to it natively, before they would reach the FVM.
+use fendermint_vm_genesis::{AccessControl, SignerAddr};
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+
+define_id!(ACCESS_CONTROL { id: 90 });
+
+/// Methods the governor can call to update the access control lists.
+#[repr(u64)]
+pub enum Method {
+    SetGovernor = 2,
+    SetDeployers = 3,
+    SetSenders = 4,
+}
+
+/// Access control state; an empty list means nobody but the governor is allowed,
+/// while a missing list means there are no restrictions.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub governor: Address,
+    pub deployers: Option<Vec<Address>>,
+    pub senders: Option<Vec<Address>>,
+}
+
+impl State {
+    /// Check whether an account is allowed to send transactions.
+    pub fn can_send(&self, addr: &Address) -> bool {
+        *addr == self.governor || is_allowed(&self.senders, addr)
+    }
+
+    /// Check whether an account is allowed to deploy contracts.
+    pub fn can_deploy(&self, addr: &Address) -> bool {
+        *addr == self.governor || is_allowed(&self.deployers, addr)
+    }
+}
+
+impl From<AccessControl> for State {
+    fn from(value: AccessControl) -> Self {
+        let to_addrs = |list: Option<Vec<SignerAddr>>| -> Option<Vec<Address>> {
+            list.map(|xs| xs.into_iter().map(|a| a.0).collect())
+        };
+        Self {
+            governor: value.governor.0,
+            deployers: to_addrs(value.deployers),
+            senders: to_addrs(value.senders),
+        }
+    }
+}
+
+fn is_allowed(list: &Option<Vec<Address>>, addr: &Address) -> bool {
+    list.as_ref().map(|xs| xs.contains(addr)).unwrap_or(true)
+}
+
+/// Parameters of [Method::SetGovernor].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct SetGovernorParams {
+    pub governor: Address,
+}
+
+/// Parameters of [Method::SetDeployers] and [Method::SetSenders].
+///
+/// Setting `None` lifts the restriction altogether.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct SetAllowListParams {
+    pub addresses: Option<Vec<Address>>,
+}
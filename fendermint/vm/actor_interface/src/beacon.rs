@@ -0,0 +1,446 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The beacon actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and collects the randomness the validators contribute
+//! in commit-reveal rounds, one round per epoch. In the first half of an epoch
+//! the validators send a commitment to a secret, in the second half they reveal
+//! it, and at the end of the epoch the revealed secrets are combined with the
+//! previous value into the next beacon value, which contracts can draw on as
+//! beacon randomness, e.g. with the `PREVRANDAO` opcode.
+//!
+//! Committing before revealing means nobody can choose their secret knowing the
+//! others', although the last validator to reveal can still decide to withhold
+//! theirs. Whoever commits and doesn't reveal is suspended from the beacon for
+//! [WITHHOLDING_SUSPENSION_EPOCHS], so each validator can only skew the value that
+//! way once in a while; a single honest validator is enough for it to be unpredictable.
+use cid::multihash::{Code, MultihashDigest};
+use fvm_ipld_encoding::{strict_bytes, tuple::*};
+use fvm_shared::{address::Address, clock::ChainEpoch};
+
+define_id!(BEACON { id: 88 });
+
+/// Number of beacon values to remember by default.
+pub const DEFAULT_LOOKBACK_LEN: u64 = 256;
+
+/// Number of epochs a validator who committed but didn't reveal can't contribute for.
+pub const WITHHOLDING_SUSPENSION_EPOCHS: u64 = 10;
+
+/// Methods the validators call to contribute to the beacon.
+#[repr(u64)]
+pub enum Method {
+    /// Commit to a secret during the first half of the epoch.
+    Commit = 2,
+    /// Reveal the secret during the second half of the epoch.
+    Reveal = 3,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct CommitParams {
+    pub epoch: u64,
+    /// See [commitment].
+    #[serde(with = "strict_bytes")]
+    pub commitment: Vec<u8>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct RevealParams {
+    pub epoch: u64,
+    #[serde(with = "strict_bytes")]
+    pub secret: Vec<u8>,
+}
+
+/// The part of the epoch a height falls into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Commit,
+    Reveal,
+}
+
+/// What a validator contributed to the current round.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Contribution {
+    pub sender: Address,
+    #[serde(with = "strict_bytes")]
+    pub commitment: Vec<u8>,
+    /// Empty until revealed.
+    #[serde(with = "strict_bytes")]
+    pub secret: Vec<u8>,
+}
+
+/// The contributions collected in the current epoch.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Round {
+    pub epoch: u64,
+    pub contributions: Vec<Contribution>,
+}
+
+/// A validator barred from the beacon for withholding its secret.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Suspension {
+    pub sender: Address,
+    /// The first epoch the validator can commit in again.
+    pub until_epoch: u64,
+}
+
+/// A beacon value produced at the end of an epoch.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct BeaconEntry {
+    pub epoch: u64,
+    /// Height of the block at the beginning of which the round was closed.
+    pub height: ChainEpoch,
+    #[serde(with = "strict_bytes")]
+    pub value: Vec<u8>,
+    /// Number of validators whose secrets went into the value.
+    pub contributors: u64,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    /// Number of blocks in an epoch.
+    pub epoch_length: u64,
+    pub lookback_len: u64,
+    pub round: Round,
+    /// The most recent values, in ascending order of epoch.
+    pub beacons: Vec<BeaconEntry>,
+    pub suspensions: Vec<Suspension>,
+}
+
+impl State {
+    pub fn new(epoch_length: u64, lookback_len: u64) -> Self {
+        Self {
+            epoch_length: epoch_length.max(2),
+            lookback_len,
+            round: Round::default(),
+            beacons: Vec::new(),
+            suspensions: Vec::new(),
+        }
+    }
+
+    /// The epoch a height belongs to.
+    pub fn epoch(&self, height: ChainEpoch) -> u64 {
+        height as u64 / self.epoch_length
+    }
+
+    /// The epoch and the phase of the round at a height.
+    pub fn phase(&self, height: ChainEpoch) -> (u64, Phase) {
+        let offset = height as u64 % self.epoch_length;
+        let phase = if offset < self.epoch_length / 2 {
+            Phase::Commit
+        } else {
+            Phase::Reveal
+        };
+        (self.epoch(height), phase)
+    }
+
+    /// Record the commitment of a validator.
+    pub fn commit(
+        &mut self,
+        height: ChainEpoch,
+        sender: Address,
+        params: CommitParams,
+    ) -> Result<(), String> {
+        self.check_phase(height, params.epoch, Phase::Commit)?;
+        if params.commitment.len() != 32 {
+            return Err("the commitment has to be 32 bytes".into());
+        }
+        if let Some(s) = self.suspension(&sender, params.epoch) {
+            return Err(format!(
+                "{sender} is suspended until epoch {} for withholding its secret",
+                s.until_epoch
+            ));
+        }
+        if self.contribution(&sender).is_some() {
+            return Err(format!(
+                "{sender} already committed in epoch {}",
+                params.epoch
+            ));
+        }
+        self.round.contributions.push(Contribution {
+            sender,
+            commitment: params.commitment,
+            secret: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Record the secret of a validator, if it matches its commitment.
+    pub fn reveal(
+        &mut self,
+        height: ChainEpoch,
+        sender: Address,
+        params: RevealParams,
+    ) -> Result<(), String> {
+        self.check_phase(height, params.epoch, Phase::Reveal)?;
+        let epoch = params.epoch;
+        let c = self
+            .round
+            .contributions
+            .iter_mut()
+            .find(|c| c.sender == sender)
+            .ok_or_else(|| format!("{sender} didn't commit in epoch {epoch}"))?;
+        if !c.secret.is_empty() {
+            return Err(format!("{sender} already revealed in epoch {epoch}"));
+        }
+        if c.commitment != commitment(&params.secret, epoch, &sender) {
+            return Err(format!(
+                "the secret doesn't match the commitment of {sender}"
+            ));
+        }
+        c.secret = params.secret;
+        Ok(())
+    }
+
+    /// Close the round if the height is in a later epoch, and start a new one.
+    /// The validators who committed but didn't reveal are suspended.
+    ///
+    /// Returns the new beacon value, unless nobody revealed their secret,
+    /// in which case the previous value stays in effect.
+    pub fn finalize(&mut self, height: ChainEpoch) -> Option<&BeaconEntry> {
+        let epoch = self.epoch(height);
+        if epoch <= self.round.epoch {
+            return None;
+        }
+        let round = std::mem::replace(
+            &mut self.round,
+            Round {
+                epoch,
+                contributions: Vec::new(),
+            },
+        );
+
+        self.suspensions.retain(|s| s.until_epoch > epoch);
+        for c in round.contributions.iter().filter(|c| c.secret.is_empty()) {
+            self.suspensions.push(Suspension {
+                sender: c.sender,
+                until_epoch: epoch + WITHHOLDING_SUSPENSION_EPOCHS,
+            });
+        }
+
+        let mut secrets = round
+            .contributions
+            .into_iter()
+            .filter(|c| !c.secret.is_empty())
+            .map(|c| (c.sender.to_bytes(), c.secret))
+            .collect::<Vec<_>>();
+
+        if secrets.is_empty() {
+            return None;
+        }
+        // The order of the reveals in the blocks should not matter.
+        secrets.sort();
+
+        let mut data = self.latest().map(|b| b.value.clone()).unwrap_or_default();
+        data.extend_from_slice(&round.epoch.to_be_bytes());
+        for (_, secret) in secrets.iter() {
+            data.extend_from_slice(secret);
+        }
+
+        self.beacons.push(BeaconEntry {
+            epoch: round.epoch,
+            height,
+            value: blake2b_256(&data).to_vec(),
+            contributors: secrets.len() as u64,
+        });
+
+        let excess = self
+            .beacons
+            .len()
+            .saturating_sub(self.lookback_len as usize);
+        self.beacons.drain(..excess);
+
+        self.beacons.last()
+    }
+
+    /// The latest beacon value.
+    pub fn latest(&self) -> Option<&BeaconEntry> {
+        self.beacons.last()
+    }
+
+    /// The beacon value in effect at a height, if it's still within the lookback window.
+    pub fn at_height(&self, height: ChainEpoch) -> Option<&BeaconEntry> {
+        self.beacons.iter().rev().find(|b| b.height <= height)
+    }
+
+    /// The beacon value produced in an epoch, if there was one.
+    pub fn get(&self, epoch: u64) -> Option<&BeaconEntry> {
+        self.beacons
+            .binary_search_by_key(&epoch, |b| b.epoch)
+            .ok()
+            .map(|i| &self.beacons[i])
+    }
+
+    /// The suspension of a validator, if it can't commit in the epoch.
+    pub fn suspension(&self, sender: &Address, epoch: u64) -> Option<&Suspension> {
+        self.suspensions
+            .iter()
+            .find(|s| s.sender == *sender && s.until_epoch > epoch)
+    }
+
+    fn contribution(&self, sender: &Address) -> Option<&Contribution> {
+        self.round
+            .contributions
+            .iter()
+            .find(|c| c.sender == *sender)
+    }
+
+    fn check_phase(&self, height: ChainEpoch, epoch: u64, expected: Phase) -> Result<(), String> {
+        let (current, phase) = self.phase(height);
+        if epoch != current {
+            Err(format!("epoch {epoch} is not the current epoch {current}"))
+        } else if phase != expected {
+            Err(format!("epoch {epoch} is in the {phase:?} phase"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The commitment to a secret, bound to the epoch and the sender,
+/// so that nobody can copy someone else's commitment and reveal.
+pub fn commitment(secret: &[u8], epoch: u64, sender: &Address) -> Vec<u8> {
+    let mut data = secret.to_vec();
+    data.extend_from_slice(&epoch.to_be_bytes());
+    data.extend_from_slice(&sender.to_bytes());
+    blake2b_256(&data).to_vec()
+}
+
+fn blake2b_256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(Code::Blake2b256.digest(data).digest());
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::Address;
+
+    use super::{
+        commitment, CommitParams, Phase, RevealParams, State, WITHHOLDING_SUSPENSION_EPOCHS,
+    };
+
+    fn commit(state: &mut State, height: i64, sender: Address, secret: &[u8]) {
+        let epoch = state.epoch(height);
+        let commitment = commitment(secret, epoch, &sender);
+        state
+            .commit(height, sender, CommitParams { epoch, commitment })
+            .unwrap();
+    }
+
+    fn reveal(state: &mut State, height: i64, sender: Address, secret: &[u8]) {
+        let epoch = state.epoch(height);
+        let secret = secret.to_vec();
+        state
+            .reveal(height, sender, RevealParams { epoch, secret })
+            .unwrap();
+    }
+
+    #[test]
+    fn phases() {
+        let state = State::new(10, 5);
+        assert_eq!(state.phase(0), (0, Phase::Commit));
+        assert_eq!(state.phase(4), (0, Phase::Commit));
+        assert_eq!(state.phase(5), (0, Phase::Reveal));
+        assert_eq!(state.phase(9), (0, Phase::Reveal));
+        assert_eq!(state.phase(10), (1, Phase::Commit));
+    }
+
+    #[test]
+    fn commit_reveal_round() {
+        let (a, b, c) = (Address::new_id(1), Address::new_id(2), Address::new_id(3));
+        let mut state = State::new(10, 5);
+
+        commit(&mut state, 1, a, &[1; 32]);
+        commit(&mut state, 2, b, &[2; 32]);
+        commit(&mut state, 3, c, &[3; 32]);
+
+        // Too early to reveal, and too late to commit.
+        assert!(state
+            .reveal(
+                4,
+                a,
+                RevealParams {
+                    epoch: 0,
+                    secret: vec![1; 32]
+                }
+            )
+            .is_err());
+        assert!(state
+            .commit(
+                5,
+                Address::new_id(4),
+                CommitParams {
+                    epoch: 0,
+                    commitment: vec![0; 32]
+                }
+            )
+            .is_err());
+
+        // A secret which doesn't match the commitment.
+        assert!(state
+            .reveal(
+                6,
+                a,
+                RevealParams {
+                    epoch: 0,
+                    secret: vec![2; 32]
+                }
+            )
+            .is_err());
+
+        // The order of the reveals doesn't matter; `c` never reveals.
+        let mut other = state.clone();
+        reveal(&mut state, 6, a, &[1; 32]);
+        reveal(&mut state, 7, b, &[2; 32]);
+        reveal(&mut other, 6, b, &[2; 32]);
+        reveal(&mut other, 7, a, &[1; 32]);
+
+        assert!(state.finalize(9).is_none());
+        let beacon = state.finalize(10).cloned().unwrap();
+        assert_eq!(beacon.epoch, 0);
+        assert_eq!(beacon.height, 10);
+        assert_eq!(beacon.contributors, 2);
+        assert_eq!(other.finalize(10), Some(&beacon));
+
+        assert_eq!(state.round.epoch, 1);
+        assert!(state.round.contributions.is_empty());
+
+        // `c` withheld its secret, so it sits out the next epochs.
+        assert_eq!(state.suspensions.len(), 1);
+        assert!(state.suspension(&c, 1).is_some());
+        assert!(state.suspension(&a, 1).is_none());
+        assert!(state
+            .commit(
+                11,
+                c,
+                CommitParams {
+                    epoch: 1,
+                    commitment: vec![0; 32]
+                }
+            )
+            .is_err());
+        let until = 1 + WITHHOLDING_SUSPENSION_EPOCHS;
+        assert!(state.suspension(&c, until).is_none());
+        assert_eq!(state.at_height(9), None);
+        assert_eq!(state.at_height(15), Some(&beacon));
+        assert_eq!(state.get(0), Some(&beacon));
+    }
+
+    #[test]
+    fn epochs_without_reveals() {
+        let (a, b) = (Address::new_id(1), Address::new_id(2));
+        let mut state = State::new(10, 2);
+
+        commit(&mut state, 1, b, &[1; 32]);
+        assert!(state.finalize(10).is_none());
+
+        for epoch in 1..5 {
+            let height = epoch * 10;
+            commit(&mut state, height + 1, a, &[epoch as u8; 32]);
+            reveal(&mut state, height + 5, a, &[epoch as u8; 32]);
+            assert!(state.finalize(height + 10).is_some());
+        }
+        assert_eq!(state.beacons.len(), 2);
+        assert_eq!(state.latest().unwrap().epoch, 4);
+        assert_eq!(state.get(1), None);
+    }
+}
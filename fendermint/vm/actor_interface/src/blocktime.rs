@@ -0,0 +1,14 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The block time actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it if the genesis limited how far the time can advance
+//! between blocks, and validators reject proposals which go beyond the limit.
+use fvm_ipld_encoding::tuple::*;
+
+define_id!(BLOCK_TIME { id: 82 });
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    /// Largest number of seconds a block time can be ahead of the previous one.
+    pub max_interval: u64,
+}
@@ -0,0 +1,93 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The chain metadata actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and records the hash of each block in its state, so that
+//! the FVM externs can look up ancestors at historical heights using values which
+//! are part of the consensus.
+//!
+//! The FVM only asks the externs for the tipset CIDs of ancestors; the timestamp and
+//! base fee of the current block come from the machine context, and there are no
+//! syscalls for those of earlier blocks, so they aren't recorded.
+use cid::Cid;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::clock::ChainEpoch;
+
+define_id!(CHAIN_METADATA { id: 91 });
+
+/// Number of recent blocks to remember by default.
+///
+/// The EVM `BLOCKHASH` opcode can look back at most 256 blocks.
+pub const DEFAULT_LOOKBACK_LEN: u64 = 256;
+
+/// Information about a block which has been executed.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct BlockMetadata {
+    pub height: ChainEpoch,
+    /// The Tendermint block hash, wrapped in a CID, which is what the FVM expects tipsets to be.
+    pub block_cid: Cid,
+}
+
+/// The most recent blocks, in ascending order of height.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub lookback_len: u64,
+    pub blocks: Vec<BlockMetadata>,
+}
+
+impl State {
+    pub fn new(lookback_len: u64) -> Self {
+        Self {
+            lookback_len,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Record a new block, forgetting about the ones which fell out of the lookback window.
+    pub fn push(&mut self, block: BlockMetadata) {
+        // In case the same height is executed again, which shouldn't happen.
+        self.blocks.retain(|b| b.height < block.height);
+        self.blocks.push(block);
+
+        let excess = self.blocks.len().saturating_sub(self.lookback_len as usize);
+        self.blocks.drain(..excess);
+    }
+
+    /// Look up a block by height, if it's still within the lookback window.
+    pub fn get(&self, height: ChainEpoch) -> Option<&BlockMetadata> {
+        self.blocks
+            .binary_search_by_key(&height, |b| b.height)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+    use fvm_ipld_encoding::IPLD_RAW;
+
+    use super::{BlockMetadata, State};
+
+    fn block(height: i64) -> BlockMetadata {
+        BlockMetadata {
+            height,
+            block_cid: Cid::new_v1(IPLD_RAW, Code::Sha2_256.digest(&height.to_be_bytes())),
+        }
+    }
+
+    #[test]
+    fn lookback_window() {
+        let mut state = State::new(3);
+        for h in 1..=5 {
+            state.push(block(h));
+        }
+        assert_eq!(state.blocks.len(), 3);
+        assert!(state.get(2).is_none());
+        assert_eq!(state.get(3), Some(&block(3)));
+        assert_eq!(state.get(5), Some(&block(5)));
+        assert!(state.get(6).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The checkpoint archive actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it and records the root CID of every batch
+//! of bottom-up checkpoints it pruned from the gateway. The checkpoints
+//! themselves, with their signatures, are exported to CAR files outside the
+//! state, so the link is dangling in the state tree, but anyone holding an
+//! archive can prove it's the one the validators agreed on.
+use cid::Cid;
+use fvm_ipld_encoding::{strict_bytes, tuple::*, RawBytes};
+
+use crate::eam::EthAddress;
+
+define_id!(CHECKPOINT_ARCHIVE { id: 95 });
+
+/// A bottom-up checkpoint with everything needed to verify it, as it was in the gateway before pruning.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedCheckpoint {
+    pub height: u64,
+    /// ABI encoded `BottomUpCheckpoint`, which is what the signatures are over.
+    #[serde(with = "strict_bytes")]
+    pub checkpoint: Vec<u8>,
+    /// ABI encoded `QuorumInfo`, with the root hash of the power table which could sign the checkpoint.
+    #[serde(with = "strict_bytes")]
+    pub quorum_info: Vec<u8>,
+    /// Validators who signed the checkpoint.
+    pub signatories: Vec<EthAddress>,
+    /// Signatures in the same order as the signatories.
+    pub signatures: Vec<RawBytes>,
+}
+
+/// Checkpoints exported into the same CAR file, in ascending order of height.
+///
+/// The root of the CAR file is the CID of this list.
+pub type ArchiveContent = Vec<ArchivedCheckpoint>;
+
+/// A batch of checkpoints which has been pruned from the gateway.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Archive {
+    /// Height of the first checkpoint in the archive.
+    pub from_height: u64,
+    /// Height of the last checkpoint in the archive.
+    pub to_height: u64,
+    /// CID of the [ArchiveContent]; it's not in the state store.
+    pub root: Cid,
+}
+
+impl Archive {
+    /// Name of the CAR file the archive is exported to.
+    pub fn file_name(&self) -> String {
+        format!("checkpoints-{}-{}.car", self.from_height, self.to_height)
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    /// Number of checkpoints to keep in the gateway; 0 means they are never pruned.
+    pub retention: u64,
+    /// Archives in ascending order of height.
+    pub archives: Vec<Archive>,
+}
+
+impl State {
+    pub fn new(retention: u64) -> Self {
+        Self {
+            retention,
+            archives: Vec::new(),
+        }
+    }
+
+    /// Height of the last checkpoint which has been archived, if any.
+    pub fn archived_height(&self) -> Option<u64> {
+        self.archives.last().map(|a| a.to_height)
+    }
+
+    /// Find the archive which contains a checkpoint height.
+    pub fn find(&self, height: u64) -> Option<&Archive> {
+        self.archives
+            .iter()
+            .find(|a| a.from_height <= height && height <= a.to_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+
+    use super::{Archive, State};
+
+    #[test]
+    fn find_archive() {
+        let mut state = State::new(2);
+        assert_eq!(state.archived_height(), None);
+
+        for (from_height, to_height) in [(10, 10), (20, 40)] {
+            state.archives.push(Archive {
+                from_height,
+                to_height,
+                root: Cid::default(),
+            });
+        }
+
+        assert_eq!(state.archived_height(), Some(40));
+        assert!(state.find(5).is_none());
+        assert_eq!(state.find(10).unwrap().from_height, 10);
+        assert_eq!(state.find(30).unwrap().from_height, 20);
+        assert!(state.find(50).is_none());
+    }
+}
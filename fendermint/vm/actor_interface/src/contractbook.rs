@@ -0,0 +1,44 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The contract address book actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it and records the Solidity libraries and
+//! contracts it deployed at genesis by their fully qualified names, so that
+//! upgrades and external tools can link against the exact library addresses
+//! on the chain, rather than recomputing them from the deployment order.
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::ActorID;
+
+use crate::eam::EthAddress;
+
+define_id!(CONTRACT_BOOK { id: 98 });
+
+/// A library or contract deployed at genesis.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ContractEntry {
+    /// Fully qualified name of the contract, e.g. `Gateway.sol:Gateway`.
+    pub fqn: String,
+    pub actor_id: ActorID,
+    /// The delegated address of the actor; libraries are linked by their masked ID address instead.
+    pub eth_address: EthAddress,
+    /// Whether the contract is a library, or one of the top-level contracts.
+    pub library: bool,
+}
+
+impl ContractEntry {
+    /// The address the contract is linked with in the bytecode of the contracts which use it.
+    pub fn link_address(&self) -> EthAddress {
+        EthAddress::from_id(self.actor_id)
+    }
+}
+
+/// Contracts in the order they were deployed.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    pub contracts: Vec<ContractEntry>,
+}
+
+impl State {
+    pub fn get(&self, fqn: &str) -> Option<&ContractEntry> {
+        self.contracts.iter().find(|c| c.fqn == fqn)
+    }
+}
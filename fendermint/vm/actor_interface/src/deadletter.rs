@@ -0,0 +1,263 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The dead-letter actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and keeps the top-down messages which failed to be
+//! applied in its state, so they are visible to everyone and can be retried
+//! later, instead of being dropped or halting the chain.
+//!
+//! The letters are kept in a HAMT keyed by their ID, and the queue is bounded:
+//! once it's full, the oldest letter is evicted to make room for a new one.
+use anyhow::anyhow;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::{clock::ChainEpoch, HAMT_BIT_WIDTH};
+use ipc_sdk::cross::CrossMsg;
+
+define_id!(DEADLETTER { id: 92 });
+
+/// Methods that can be called on the dead-letter actor.
+#[repr(u64)]
+pub enum Method {
+    /// Try to apply a dead letter again.
+    ///
+    /// Only governance can call it: a member of the governance multisig, or the
+    /// access control governor on chains without one.
+    Retry = 2,
+}
+
+/// Maximum number of letters kept in the queue.
+pub const MAX_DEAD_LETTERS: u64 = 1000;
+
+type LetterMap<BS> = Hamt<BS, DeadLetter, BytesKey>;
+
+/// A top-down message which could not be applied.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub id: u64,
+    /// Height of the block where the message failed for the last time.
+    pub height: ChainEpoch,
+    pub msg: CrossMsg,
+    /// Why the message failed the last time.
+    pub reason: String,
+    /// Number of times the message has been retried.
+    pub retries: u64,
+}
+
+/// The dead letters, by ID, which is the order they arrived in.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub next_id: u64,
+    /// Lowest ID which can still be in the queue; the ones before it have been removed.
+    pub first_id: u64,
+    /// Number of letters in the queue.
+    pub count: u64,
+    /// HAMT of [DeadLetter] by ID.
+    pub letters: Cid,
+}
+
+impl State {
+    pub fn new<BS: Blockstore>(store: &BS) -> anyhow::Result<Self> {
+        let letters = LetterMap::new_with_bit_width(store, HAMT_BIT_WIDTH).flush()?;
+        Ok(Self {
+            next_id: 0,
+            first_id: 0,
+            count: 0,
+            letters,
+        })
+    }
+
+    /// Add a new dead letter and return its ID, along with the letter evicted
+    /// to make room for it, if the queue was full.
+    pub fn push<BS: Blockstore>(
+        &mut self,
+        store: &BS,
+        height: ChainEpoch,
+        msg: CrossMsg,
+        reason: String,
+    ) -> anyhow::Result<(u64, Option<DeadLetter>)> {
+        let mut letters = self.load(store)?;
+
+        let mut evicted = None;
+        if self.count >= MAX_DEAD_LETTERS {
+            // IDs removed by retries leave gaps; skip over them to find the oldest letter.
+            while self.first_id < self.next_id && evicted.is_none() {
+                evicted = letters.delete(&key(self.first_id))?.map(|(_, l)| l);
+                self.first_id += 1;
+            }
+            self.count -= 1;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.count += 1;
+
+        letters.set(
+            key(id),
+            DeadLetter {
+                id,
+                height,
+                msg,
+                reason,
+                retries: 0,
+            },
+        )?;
+
+        self.letters = letters.flush()?;
+        Ok((id, evicted))
+    }
+
+    pub fn get<BS: Blockstore>(&self, store: &BS, id: u64) -> anyhow::Result<Option<DeadLetter>> {
+        Ok(self.load(store)?.get(&key(id))?.cloned())
+    }
+
+    /// Record another failed attempt.
+    pub fn failed<BS: Blockstore>(
+        &mut self,
+        store: &BS,
+        id: u64,
+        height: ChainEpoch,
+        reason: String,
+    ) -> anyhow::Result<()> {
+        let mut letters = self.load(store)?;
+        if let Some(mut l) = letters.get(&key(id))?.cloned() {
+            l.height = height;
+            l.reason = reason;
+            l.retries += 1;
+            letters.set(key(id), l)?;
+            self.letters = letters.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Forget about a letter after it has been applied successfully.
+    pub fn remove<BS: Blockstore>(
+        &mut self,
+        store: &BS,
+        id: u64,
+    ) -> anyhow::Result<Option<DeadLetter>> {
+        let mut letters = self.load(store)?;
+        let removed = letters.delete(&key(id))?.map(|(_, l)| l);
+        if removed.is_some() {
+            self.count -= 1;
+            self.letters = letters.flush()?;
+        }
+        Ok(removed)
+    }
+
+    /// All the letters in the queue, in the order they arrived.
+    pub fn letters<BS: Blockstore>(&self, store: &BS) -> anyhow::Result<Vec<DeadLetter>> {
+        let mut letters = Vec::new();
+        self.load(store)?.for_each(|_, l| {
+            letters.push(l.clone());
+            Ok(())
+        })?;
+        letters.sort_by_key(|l| l.id);
+        Ok(letters)
+    }
+
+    fn load<BS: Blockstore>(&self, store: BS) -> anyhow::Result<LetterMap<BS>> {
+        LetterMap::load_with_bit_width(&self.letters, store, HAMT_BIT_WIDTH)
+            .map_err(|e| anyhow!("failed to load dead letters: {e}"))
+    }
+}
+
+fn key(id: u64) -> BytesKey {
+    BytesKey::from(id.to_be_bytes().to_vec())
+}
+
+/// Parameters of [Method::Retry].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct RetryParams {
+    pub id: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use ipc_sdk::cross::{CrossMsg, StorableMsg};
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use super::{State, MAX_DEAD_LETTERS};
+
+    fn cross_msg(nonce: u64) -> CrossMsg {
+        let subnet_id = SubnetID::new(10, vec![Address::new_id(1000)]);
+        let mut msg = StorableMsg::new_fund_msg(
+            &subnet_id,
+            &Address::new_id(1),
+            &Address::new_id(2),
+            TokenAmount::from_atto(100),
+        )
+        .unwrap();
+        msg.nonce = nonce;
+        CrossMsg {
+            msg,
+            wrapped: false,
+        }
+    }
+
+    #[test]
+    fn push_retry_remove() {
+        let store = MemoryBlockstore::new();
+        let mut state = State::new(&store).unwrap();
+        let (a, _) = state
+            .push(&store, 1, cross_msg(0), "reverted".into())
+            .unwrap();
+        let (b, _) = state
+            .push(&store, 1, cross_msg(1), "reverted".into())
+            .unwrap();
+        assert_ne!(a, b);
+
+        state.failed(&store, a, 5, "reverted again".into()).unwrap();
+        let l = state.get(&store, a).unwrap().unwrap();
+        assert_eq!(l.retries, 1);
+        assert_eq!(l.height, 5);
+        assert_eq!(l.reason, "reverted again");
+
+        assert!(state.remove(&store, a).unwrap().is_some());
+        assert!(state.get(&store, a).unwrap().is_none());
+        assert!(state.get(&store, b).unwrap().is_some());
+        assert_eq!(state.count, 1);
+
+        // IDs are not reused.
+        let (c, _) = state
+            .push(&store, 6, cross_msg(0), "reverted".into())
+            .unwrap();
+        assert!(c > b);
+
+        let ids = state
+            .letters(&store)
+            .unwrap()
+            .into_iter()
+            .map(|l| l.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![b, c]);
+    }
+
+    #[test]
+    fn evicts_oldest_when_full() {
+        let store = MemoryBlockstore::new();
+        let mut state = State::new(&store).unwrap();
+        for i in 0..MAX_DEAD_LETTERS {
+            let (_, evicted) = state
+                .push(&store, 1, cross_msg(i), "reverted".into())
+                .unwrap();
+            assert!(evicted.is_none());
+        }
+        // Leave a gap at the front, which the eviction has to skip.
+        state.remove(&store, 0).unwrap();
+        state
+            .push(&store, 2, cross_msg(0), "reverted".into())
+            .unwrap();
+
+        let (_, evicted) = state
+            .push(&store, 3, cross_msg(0), "reverted".into())
+            .unwrap();
+        assert_eq!(evicted.map(|l| l.id), Some(1));
+        assert_eq!(state.count, MAX_DEAD_LETTERS);
+        assert!(state.get(&store, 1).unwrap().is_none());
+        assert!(state.get(&store, 2).unwrap().is_some());
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The diamond layout actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and records which facets the IPC diamonds were cut
+//! with at genesis, and which function selectors each facet serves, so that
+//! later facet upgrades can be checked against the baseline the chain started
+//! with, instead of against whatever artifacts happen to be at hand.
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::ActorID;
+
+use crate::eam::EthAddress;
+
+define_id!(DIAMOND_LAYOUT { id: 84 });
+
+/// Function selector, the first 4 bytes of the Keccak hash of the function signature.
+pub type Selector = [u8; 4];
+
+/// A facet added to a diamond, with the selectors routed to it.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct FacetEntry {
+    /// Name of the facet contract, e.g. `GatewayGetterFacet`.
+    pub name: String,
+    /// The address the facet was cut with, which is the masked ID of the library actor.
+    pub address: EthAddress,
+    pub selectors: Vec<Selector>,
+}
+
+/// The facets of a diamond contract deployed at genesis.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct DiamondEntry {
+    /// Name of the diamond contract, e.g. `GatewayDiamond`.
+    pub name: String,
+    pub actor_id: ActorID,
+    /// Facets in the order they were cut.
+    pub facets: Vec<FacetEntry>,
+}
+
+impl DiamondEntry {
+    /// The facet a function selector is routed to.
+    pub fn facet_of(&self, selector: &Selector) -> Option<&FacetEntry> {
+        self.facets.iter().find(|f| f.selectors.contains(selector))
+    }
+}
+
+/// Diamonds in the order they were deployed.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    pub diamonds: Vec<DiamondEntry>,
+}
+
+impl State {
+    pub fn get(&self, name: &str) -> Option<&DiamondEntry> {
+        self.diamonds.iter().find(|d| d.name == name)
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The Ethereum block hash actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it if the genesis chose to expose block hashes
+//! derived from the CometBFT ones in the Ethereum API, rather than the CometBFT
+//! hashes as they are. Chains started without it keep the CometBFT hashes, so
+//! the hashes clients have already stored never change.
+//!
+//! The actor has no state, its existence is the flag.
+
+define_id!(ETH_BLOCK_HASH { id: 81 });
@@ -2,8 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use cid::Cid;
-use fvm_ipld_encoding::RawBytes;
-use fvm_shared::METHOD_CONSTRUCTOR;
+use fvm_ipld_encoding::{strict_bytes, RawBytes};
+use fvm_shared::{ActorID, METHOD_CONSTRUCTOR};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
 
 pub use fil_actors_evm_shared::uints;
@@ -46,6 +46,28 @@ pub struct GetStorageAtReturn {
     pub storage: uints::U256,
 }
 
+/// Marks an EVM actor which self-destructed, by the message that destroyed it.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+    pub origin: ActorID,
+    pub nonce: u64,
+}
+
+/// State of an EVM actor, copied from the builtin actor.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    /// The EVM bytecode of the contract.
+    pub bytecode: Cid,
+    /// Keccak256 hash of the bytecode.
+    #[serde(with = "strict_bytes")]
+    pub bytecode_hash: Vec<u8>,
+    /// HAMT of the contract storage.
+    pub contract_state: Cid,
+    /// Incremented every time the contract creates another one.
+    pub nonce: u64,
+    pub tombstone: Option<Tombstone>,
+}
+
 #[derive(Serialize_tuple, Deserialize_tuple)]
 pub struct ConstructorParams {
     /// The actor's "creator" (specified by the EAM).
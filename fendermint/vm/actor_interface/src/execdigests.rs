@@ -0,0 +1,307 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The execution digests actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it if the genesis enabled the digests, and
+//! records the Merkle roots of the receipts and the events of every block in
+//! its state, next to a link to the receipts themselves.
+//!
+//! The state goes into the app hash, which appears in the header of the next
+//! block, so a light client which trusts the headers can check that a
+//! transaction executed with a given result from a single receipt and its
+//! Merkle proof, without replaying the block.
+//!
+//! The trees are built the same way as the ones in the CometBFT headers,
+//! following RFC 6962: leaves and inner nodes are hashed with SHA-256
+//! with a different prefix, and the split is at the largest power of two.
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::{tuple::*, RawBytes};
+use fvm_shared::{clock::ChainEpoch, error::ExitCode};
+
+define_id!(EXEC_DIGESTS { id: 83 });
+
+/// Number of recent blocks to remember by default.
+pub const DEFAULT_LOOKBACK_LEN: u64 = 256;
+
+const LEAF_PREFIX: u8 = 0;
+const INNER_PREFIX: u8 = 1;
+
+/// SHA-256 hash of a leaf or the root of a tree.
+pub type Hash = [u8; 32];
+
+/// The outcome of a transaction in a block, which the receipts root commits to.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct TxReceipt {
+    /// SHA-256 hash of the transaction bytes, which is also how CometBFT identifies it.
+    pub tx_hash: Hash,
+    /// Exit code of the message, or `None` if the transaction was rejected without being
+    /// executed, e.g. because it couldn't be decoded or its signature was invalid.
+    pub exit_code: Option<ExitCode>,
+    pub gas_used: u64,
+    pub return_data: RawBytes,
+    /// Root of the tree of the events emitted by the message, in the order they were emitted;
+    /// the leaves are the CBOR encoded `StampedEvent`s.
+    pub events_root: Hash,
+}
+
+impl TxReceipt {
+    /// The CBOR encoding of the receipt, which is the leaf in the receipts tree.
+    pub fn leaf(&self) -> Vec<u8> {
+        fvm_ipld_encoding::to_vec(self).expect("receipt can be serialized")
+    }
+}
+
+/// The digests of the execution of a block.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct BlockDigest {
+    pub height: ChainEpoch,
+    /// Number of transactions in the block, which is the number of leaves in the receipts tree.
+    pub tx_count: u64,
+    /// Root of the tree of the receipts of the transactions, in the order they are in the block.
+    pub receipts_root: Hash,
+    /// Root of the tree of all events emitted by the transactions, in the order they were emitted.
+    pub events_root: Hash,
+    /// Link to the CBOR encoded list of [`TxReceipt`], to build the proofs from.
+    pub receipts: Cid,
+}
+
+/// The most recent blocks, in ascending order of height.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub lookback_len: u64,
+    pub blocks: Vec<BlockDigest>,
+}
+
+impl State {
+    pub fn new(lookback_len: u64) -> Self {
+        Self {
+            lookback_len,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Record a new block, forgetting about the ones which fell out of the lookback window.
+    pub fn push(&mut self, block: BlockDigest) {
+        self.blocks.retain(|b| b.height < block.height);
+        self.blocks.push(block);
+
+        let excess = self.blocks.len().saturating_sub(self.lookback_len as usize);
+        self.blocks.drain(..excess);
+    }
+
+    /// Look up a block by height, if it's still within the lookback window.
+    pub fn get(&self, height: ChainEpoch) -> Option<&BlockDigest> {
+        self.blocks
+            .binary_search_by_key(&height, |b| b.height)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+}
+
+/// The path from a leaf to the root of a tree.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    /// Index of the leaf.
+    pub index: u64,
+    /// Number of leaves in the tree.
+    pub total: u64,
+    /// Roots of the sibling subtrees, from the leaf up.
+    pub aunts: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Check that the leaf is in the tree with the given root, at the index of the proof.
+    pub fn verify(&self, root: &Hash, leaf: &[u8]) -> bool {
+        if self.index >= self.total {
+            return false;
+        }
+        let mut aunts = self.aunts.iter().rev();
+        let hash = root_from_aunts(
+            self.index as usize,
+            self.total as usize,
+            leaf_hash(leaf),
+            &mut aunts,
+        );
+        hash.as_ref() == Some(root) && aunts.next().is_none()
+    }
+}
+
+/// A receipt with the proof that it's in a block, as returned by the query.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ReceiptProof {
+    pub digest: BlockDigest,
+    pub receipt: TxReceipt,
+    pub proof: MerkleProof,
+    /// The IPLD blocks on the path from the app hash to the digest: the CBOR encoded state
+    /// parameters, whose CID is the app hash, then the state root, the nodes of the actor
+    /// HAMT down to the execution digests actor, and finally the state of the actor.
+    pub state_blocks: Vec<RawBytes>,
+}
+
+impl ReceiptProof {
+    /// Check that the receipt is in the receipts root of the digest.
+    ///
+    /// The digest itself is in the state of the block, which the caller has to check
+    /// against the app hash with the `state_blocks`; the interpreter does both in
+    /// `verify_receipt_proof`.
+    pub fn verify(&self) -> bool {
+        self.proof.total == self.digest.tx_count
+            && self
+                .proof
+                .verify(&self.digest.receipts_root, &self.receipt.leaf())
+    }
+}
+
+fn sha256(parts: &[&[u8]]) -> Hash {
+    let mh = Code::Sha2_256.digest(&parts.concat());
+    let mut hash = Hash::default();
+    hash.copy_from_slice(mh.digest());
+    hash
+}
+
+/// The hash of a transaction, the same as CometBFT shows.
+pub fn tx_hash(tx: &[u8]) -> Hash {
+    sha256(&[tx])
+}
+
+pub fn leaf_hash(leaf: &[u8]) -> Hash {
+    sha256(&[&[LEAF_PREFIX], leaf])
+}
+
+fn inner_hash(left: &Hash, right: &Hash) -> Hash {
+    sha256(&[&[INNER_PREFIX], left, right])
+}
+
+/// The largest power of two smaller than `n`, where a tree of `n > 1` leaves is split.
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k * 2 < n {
+        k *= 2;
+    }
+    k
+}
+
+fn root_of(hashes: &[Hash]) -> Hash {
+    match hashes.len() {
+        0 => sha256(&[]),
+        1 => hashes[0],
+        n => {
+            let k = split_point(n);
+            inner_hash(&root_of(&hashes[..k]), &root_of(&hashes[k..]))
+        }
+    }
+}
+
+fn aunts_of(hashes: &[Hash], index: usize) -> Vec<Hash> {
+    if hashes.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(hashes.len());
+    let (mut aunts, aunt) = if index < k {
+        (aunts_of(&hashes[..k], index), root_of(&hashes[k..]))
+    } else {
+        (aunts_of(&hashes[k..], index - k), root_of(&hashes[..k]))
+    };
+    aunts.push(aunt);
+    aunts
+}
+
+/// Recompute the root from the leaf hash, taking the aunts from the root down.
+fn root_from_aunts<'a>(
+    index: usize,
+    total: usize,
+    leaf: Hash,
+    aunts: &mut impl Iterator<Item = &'a Hash>,
+) -> Option<Hash> {
+    if total <= 1 {
+        return Some(leaf);
+    }
+    let k = split_point(total);
+    let aunt = aunts.next()?;
+    if index < k {
+        let left = root_from_aunts(index, k, leaf, aunts)?;
+        Some(inner_hash(&left, aunt))
+    } else {
+        let right = root_from_aunts(index - k, total - k, leaf, aunts)?;
+        Some(inner_hash(aunt, &right))
+    }
+}
+
+/// Root of the tree of the leaves; the hash of nothing if there are none.
+pub fn merkle_root<T: AsRef<[u8]>>(leaves: &[T]) -> Hash {
+    let hashes = leaves
+        .iter()
+        .map(|l| leaf_hash(l.as_ref()))
+        .collect::<Vec<_>>();
+    root_of(&hashes)
+}
+
+/// Proof that the leaf at an index is in the tree of the leaves, if the index is in range.
+pub fn merkle_proof<T: AsRef<[u8]>>(leaves: &[T], index: usize) -> Option<MerkleProof> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let hashes = leaves
+        .iter()
+        .map(|l| leaf_hash(l.as_ref()))
+        .collect::<Vec<_>>();
+    Some(MerkleProof {
+        index: index as u64,
+        total: leaves.len() as u64,
+        aunts: aunts_of(&hashes, index),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::error::ExitCode;
+
+    use super::{merkle_proof, merkle_root, TxReceipt};
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for n in 1..=9usize {
+            let leaves = (0..n).map(|i| vec![i as u8; i + 1]).collect::<Vec<_>>();
+            let root = merkle_root(&leaves);
+
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, i).unwrap();
+                assert!(proof.verify(&root, leaf), "n = {n}, i = {i}");
+                assert!(!proof.verify(&root, b"not a leaf"), "n = {n}, i = {i}");
+            }
+            assert!(merkle_proof(&leaves, n).is_none());
+        }
+    }
+
+    #[test]
+    fn proof_fails_at_other_index() {
+        let leaves = (0..5u8).map(|i| vec![i]).collect::<Vec<_>>();
+        let root = merkle_root(&leaves);
+
+        let mut proof = merkle_proof(&leaves, 1).unwrap();
+        proof.index = 2;
+        assert!(!proof.verify(&root, &leaves[1]));
+    }
+
+    #[test]
+    fn receipt_changes_root() {
+        let receipt = TxReceipt {
+            tx_hash: [1; 32],
+            exit_code: Some(ExitCode::OK),
+            gas_used: 100,
+            return_data: RawBytes::default(),
+            events_root: merkle_root::<Vec<u8>>(&[]),
+        };
+        let failed = TxReceipt {
+            exit_code: Some(ExitCode::USR_ASSERTION_FAILED),
+            ..receipt.clone()
+        };
+        assert_ne!(
+            merkle_root(&[receipt.leaf()]),
+            merkle_root(&[failed.leaf()])
+        );
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The fast finality actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it if the genesis chose to follow the parent in the fast
+//! top-down finality mode, which is only meant for local development. Since the
+//! mode decides which proposals the validators vote for, they all read it from
+//! the chain rather than from their own settings.
+//!
+//! The actor has no state, its existence is the flag.
+
+define_id!(FAST_FINALITY { id: 79 });
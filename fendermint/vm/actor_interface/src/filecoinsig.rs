@@ -0,0 +1,10 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The Filecoin signatures actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it if the genesis enabled messages signed
+//! the way Filecoin wallets such as Lotus sign them, over the CID alone, and
+//! only accepts those signatures if the actor exists.
+//!
+//! The actor has no state, its existence is the flag.
+
+define_id!(FILECOIN_SIG { id: 89 });
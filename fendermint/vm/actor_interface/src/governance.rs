@@ -0,0 +1,512 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The governance actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and keeps the adjustable chain parameters in its state,
+//! along with the open proposals to change them. Members of the governance
+//! multisig send messages to the actor, which are handled natively.
+//!
+//! Governance can also decide to shut the subnet down: from the next epoch
+//! boundary no user transactions or top-down messages are accepted, the next
+//! checkpoint is the final one, carrying all outstanding bottom-up messages,
+//! and the state root it commits to is recorded as the terminal one, for the
+//! parent to verify.
+//!
+//! Proposals which don't get enough approvals within [PROPOSAL_LIFETIME_EPOCHS]
+//! epochs expire, so that old ones can't be brought back when the membership
+//! or the circumstances have changed.
+//!
+//! Top-down messages are only executed if their sender on the parent passes the
+//! filter kept here. Any single member can block a sender at once, as an emergency
+//! brake, but replacing the filter takes an approved proposal.
+use cid::Cid;
+use fendermint_vm_genesis::Governance;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::{address::Address, clock::ChainEpoch, econ::TokenAmount};
+use ipc_sdk::cross::CrossMsg;
+
+define_id!(GOVERNANCE { id: 93 });
+
+/// Number of epochs an open proposal can collect approvals for.
+pub const PROPOSAL_LIFETIME_EPOCHS: u64 = 10;
+
+/// Methods the members can call to change the chain parameters.
+#[repr(u64)]
+pub enum Method {
+    /// Propose new values for the parameters; counts as an approval by the proposer.
+    Propose = 2,
+    /// Approve an open proposal.
+    Approve = 3,
+    /// Propose to shut the subnet down; counts as an approval by the proposer.
+    ProposeShutdown = 4,
+    /// Block a top-down message sender with immediate effect; needs no approvals.
+    BlockTopdownSender = 5,
+    /// Propose to replace the top-down sender filter; counts as an approval by the proposer.
+    ProposeTopdownFilter = 6,
+}
+
+/// Chain parameters which can be adjusted by governance.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct ChainParams {
+    pub base_fee_floor: TokenAmount,
+    /// 0 means unlimited.
+    pub block_gas_limit: u64,
+    /// 0 means unlimited.
+    pub topdown_max_msgs: u64,
+}
+
+/// Which senders on the parent can send top-down messages into the subnet.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct TopdownFilter {
+    /// Senders whose messages are never executed.
+    pub blocked: Vec<Address>,
+    /// If set, only these senders' messages are executed.
+    pub allowed: Option<Vec<Address>>,
+}
+
+impl TopdownFilter {
+    pub fn is_allowed(&self, sender: &Address) -> bool {
+        !self.blocked.contains(sender)
+            && self
+                .allowed
+                .as_ref()
+                .map(|allowed| allowed.contains(sender))
+                .unwrap_or(true)
+    }
+}
+
+/// An open proposal to change the chain parameters.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Address,
+    /// Height of the block where the proposal was made.
+    pub height: ChainEpoch,
+    pub params: ChainParams,
+    pub approvals: Vec<Address>,
+    /// The proposal is to shut the subnet down, rather than to change the parameters.
+    pub shutdown: bool,
+    /// The proposal is to replace the top-down filter, rather than to change the parameters.
+    ///
+    /// The filter doesn't affect the execution of transactions, so it's replaced
+    /// as soon as the proposal is approved, without waiting for the next epoch.
+    pub topdown_filter: Option<TopdownFilter>,
+}
+
+/// A proposal which has been approved, waiting for the next epoch boundary.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledChange {
+    pub proposal_id: u64,
+    pub params: ChainParams,
+    pub shutdown: bool,
+}
+
+/// Progress of the subnet shutdown.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Shutdown {
+    /// Height of the block from which user transactions are rejected.
+    pub since: ChainEpoch,
+    /// Height of the final bottom-up checkpoint, once it has been created.
+    pub final_checkpoint_height: Option<u64>,
+    /// Committed state root the block with the final checkpoint started from, which is the app hash
+    /// in the header of that block, so the checkpoint commits to it through its block hash.
+    pub terminal_state_root: Option<Cid>,
+}
+
+/// Governance state.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub members: Vec<Address>,
+    pub threshold: u64,
+    pub epoch_length: u64,
+    /// The parameters currently in effect.
+    pub params: ChainParams,
+    pub next_id: u64,
+    pub proposals: Vec<Proposal>,
+    /// The latest approved change; it supersedes any earlier one not yet in effect.
+    pub scheduled: Option<ScheduledChange>,
+    /// Top-down messages over the limit of their block, waiting to be executed.
+    pub deferred_topdown_msgs: Vec<CrossMsg>,
+    /// Set once a shutdown has been put in effect.
+    pub shutdown: Option<Shutdown>,
+    /// Filter applied to the senders of top-down messages.
+    pub topdown_filter: TopdownFilter,
+}
+
+impl State {
+    pub fn is_member(&self, addr: &Address) -> bool {
+        self.members.contains(addr)
+    }
+
+    /// User transactions are not accepted once the shutdown is in effect.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    /// Changes scheduled during an epoch take effect at the first block of the next one.
+    pub fn is_epoch_boundary(&self, height: ChainEpoch) -> bool {
+        self.epoch_length > 0 && height % self.epoch_length as ChainEpoch == 0
+    }
+
+    /// Open a new proposal and return its ID, scheduling it if no other approvals are needed.
+    pub fn propose(&mut self, height: ChainEpoch, proposer: Address, params: ChainParams) -> u64 {
+        self.open(height, proposer, params, false, None)
+    }
+
+    /// Open a proposal to shut the subnet down and return its ID.
+    pub fn propose_shutdown(&mut self, height: ChainEpoch, proposer: Address) -> u64 {
+        self.open(height, proposer, self.params.clone(), true, None)
+    }
+
+    /// Open a proposal to replace the top-down filter and return its ID.
+    pub fn propose_topdown_filter(
+        &mut self,
+        height: ChainEpoch,
+        proposer: Address,
+        filter: TopdownFilter,
+    ) -> u64 {
+        self.open(height, proposer, self.params.clone(), false, Some(filter))
+    }
+
+    /// Block a top-down message sender.
+    ///
+    /// Returns `false` if it was already blocked.
+    pub fn block_topdown_sender(&mut self, sender: Address) -> bool {
+        if self.topdown_filter.blocked.contains(&sender) {
+            return false;
+        }
+        self.topdown_filter.blocked.push(sender);
+        true
+    }
+
+    /// Add the approval of a member to a proposal.
+    ///
+    /// Returns `true` if the proposal reached the threshold and got scheduled.
+    pub fn approve(
+        &mut self,
+        id: u64,
+        member: Address,
+        height: ChainEpoch,
+    ) -> Result<bool, String> {
+        let expires_at = self.expires_at(height);
+        let proposal = self
+            .proposals
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("proposal {id} not found"))?;
+
+        if proposal.height <= expires_at {
+            return Err(format!("proposal {id} expired"));
+        }
+
+        if proposal.approvals.contains(&member) {
+            return Err(format!("proposal {id} already approved by {member}"));
+        }
+        proposal.approvals.push(member);
+
+        Ok(self.maybe_schedule(id))
+    }
+
+    /// Remove the open proposals which expired by a height, returning their IDs.
+    pub fn expire_proposals(&mut self, height: ChainEpoch) -> Vec<u64> {
+        let expires_at = self.expires_at(height);
+        let mut expired = Vec::new();
+        self.proposals.retain(|p| {
+            if p.height <= expires_at {
+                expired.push(p.id);
+                false
+            } else {
+                true
+            }
+        });
+        expired
+    }
+
+    /// Proposals made at or before the returned height have expired by the given one.
+    fn expires_at(&self, height: ChainEpoch) -> ChainEpoch {
+        let lifetime = PROPOSAL_LIFETIME_EPOCHS * self.epoch_length.max(1);
+        height - lifetime as ChainEpoch
+    }
+
+    /// Take the scheduled change, if any, and put it into effect at a height.
+    pub fn apply_scheduled(&mut self, height: ChainEpoch) -> Option<ScheduledChange> {
+        let change = self.scheduled.take()?;
+        if change.shutdown {
+            self.shutdown = Some(Shutdown {
+                since: height,
+                final_checkpoint_height: None,
+                terminal_state_root: None,
+            });
+        } else {
+            self.params = change.params.clone();
+        }
+        Some(change)
+    }
+
+    fn open(
+        &mut self,
+        height: ChainEpoch,
+        proposer: Address,
+        params: ChainParams,
+        shutdown: bool,
+        topdown_filter: Option<TopdownFilter>,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.proposals.push(Proposal {
+            id,
+            proposer,
+            height,
+            params,
+            approvals: vec![proposer],
+            shutdown,
+            topdown_filter,
+        });
+        self.maybe_schedule(id);
+        id
+    }
+
+    fn maybe_schedule(&mut self, id: u64) -> bool {
+        let idx = match self.proposals.iter().position(|p| p.id == id) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        if (self.proposals[idx].approvals.len() as u64) < self.threshold {
+            return false;
+        }
+        if self.proposals[idx].topdown_filter.is_some() {
+            let proposal = self.proposals.remove(idx);
+            self.topdown_filter = proposal.topdown_filter.unwrap_or_default();
+            return true;
+        }
+        // A scheduled shutdown is not superseded by a parameter change; that stays open.
+        if self
+            .scheduled
+            .as_ref()
+            .map(|s| s.shutdown)
+            .unwrap_or_default()
+            && !self.proposals[idx].shutdown
+        {
+            return false;
+        }
+        let proposal = self.proposals.remove(idx);
+        self.scheduled = Some(ScheduledChange {
+            proposal_id: proposal.id,
+            params: proposal.params,
+            shutdown: proposal.shutdown,
+        });
+        true
+    }
+}
+
+impl From<fendermint_vm_genesis::ChainParams> for ChainParams {
+    fn from(value: fendermint_vm_genesis::ChainParams) -> Self {
+        Self {
+            base_fee_floor: value.base_fee_floor,
+            block_gas_limit: value.block_gas_limit,
+            topdown_max_msgs: value.topdown_max_msgs,
+        }
+    }
+}
+
+impl From<Governance> for State {
+    fn from(value: Governance) -> Self {
+        Self {
+            members: value.members.into_iter().map(|a| a.0).collect(),
+            threshold: value.threshold,
+            epoch_length: value.epoch_length,
+            params: value.params.into(),
+            next_id: 0,
+            proposals: Vec::new(),
+            scheduled: None,
+            deferred_topdown_msgs: Vec::new(),
+            shutdown: None,
+            topdown_filter: TopdownFilter::default(),
+        }
+    }
+}
+
+/// Parameters of [Method::Propose].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ProposeParams {
+    pub params: ChainParams,
+}
+
+/// Parameters of [Method::BlockTopdownSender].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct BlockTopdownSenderParams {
+    pub sender: Address,
+}
+
+/// Parameters of [Method::ProposeTopdownFilter].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ProposeTopdownFilterParams {
+    pub filter: TopdownFilter,
+}
+
+/// Parameters of [Method::Approve].
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ApproveParams {
+    pub id: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::Address;
+
+    use super::{ChainParams, State, TopdownFilter};
+
+    #[test]
+    fn propose_approve_apply() {
+        let alice = Address::new_id(1000);
+        let bob = Address::new_id(1001);
+        let charlie = Address::new_id(1002);
+
+        let mut state = State {
+            members: vec![alice, bob, charlie],
+            threshold: 2,
+            epoch_length: 10,
+            params: ChainParams::default(),
+            next_id: 0,
+            proposals: Vec::new(),
+            scheduled: None,
+            deferred_topdown_msgs: Vec::new(),
+            shutdown: None,
+            topdown_filter: TopdownFilter::default(),
+        };
+
+        let params = ChainParams {
+            block_gas_limit: 1000,
+            ..Default::default()
+        };
+
+        let id = state.propose(1, alice, params.clone());
+        assert!(state.scheduled.is_none());
+        assert!(state.approve(id, alice, 2).is_err());
+        assert!(state.approve(id + 1, bob, 2).is_err());
+        assert_eq!(state.approve(id, bob, 2), Ok(true));
+        assert!(state.proposals.is_empty());
+
+        assert!(!state.is_epoch_boundary(15));
+        assert!(state.is_epoch_boundary(20));
+
+        let change = state.apply_scheduled(20).unwrap();
+        assert_eq!(change.proposal_id, id);
+        assert_eq!(state.params, params);
+        assert!(state.apply_scheduled(20).is_none());
+        assert!(!state.is_shutting_down());
+    }
+
+    #[test]
+    fn shutdown_is_not_superseded() {
+        let alice = Address::new_id(1000);
+        let bob = Address::new_id(1001);
+
+        let mut state = State {
+            members: vec![alice, bob],
+            threshold: 1,
+            epoch_length: 10,
+            params: ChainParams::default(),
+            next_id: 0,
+            proposals: Vec::new(),
+            scheduled: None,
+            deferred_topdown_msgs: Vec::new(),
+            shutdown: None,
+            topdown_filter: TopdownFilter::default(),
+        };
+
+        let shutdown_id = state.propose_shutdown(1, alice);
+        let params = ChainParams {
+            block_gas_limit: 1000,
+            ..Default::default()
+        };
+        let params_id = state.propose(2, bob, params);
+
+        assert_eq!(state.scheduled.as_ref().unwrap().proposal_id, shutdown_id);
+        assert_eq!(state.proposals.len(), 1);
+        assert_eq!(state.proposals[0].id, params_id);
+
+        state.apply_scheduled(10);
+        assert_eq!(state.params, ChainParams::default());
+        assert_eq!(state.shutdown.as_ref().unwrap().since, 10);
+    }
+
+    #[test]
+    fn topdown_filter() {
+        let alice = Address::new_id(1000);
+        let bob = Address::new_id(1001);
+        let mallory = Address::new_id(2000);
+        let sender = Address::new_id(2001);
+
+        let mut state = State {
+            members: vec![alice, bob],
+            threshold: 2,
+            epoch_length: 10,
+            params: ChainParams::default(),
+            next_id: 0,
+            proposals: Vec::new(),
+            scheduled: None,
+            deferred_topdown_msgs: Vec::new(),
+            shutdown: None,
+            topdown_filter: TopdownFilter::default(),
+        };
+
+        assert!(state.topdown_filter.is_allowed(&mallory));
+        assert!(state.block_topdown_sender(mallory));
+        assert!(!state.block_topdown_sender(mallory));
+        assert!(!state.topdown_filter.is_allowed(&mallory));
+        assert!(state.topdown_filter.is_allowed(&sender));
+
+        let filter = TopdownFilter {
+            blocked: Vec::new(),
+            allowed: Some(vec![sender]),
+        };
+        let id = state.propose_topdown_filter(1, alice, filter.clone());
+        assert_eq!(state.topdown_filter.blocked, vec![mallory]);
+
+        // Takes effect as soon as it's approved, without scheduling.
+        assert_eq!(state.approve(id, bob, 2), Ok(true));
+        assert!(state.scheduled.is_none());
+        assert_eq!(state.topdown_filter, filter);
+        assert!(state.topdown_filter.is_allowed(&sender));
+        assert!(!state.topdown_filter.is_allowed(&mallory));
+        assert!(!state.topdown_filter.is_allowed(&alice));
+    }
+
+    #[test]
+    fn proposals_expire() {
+        let alice = Address::new_id(1000);
+        let bob = Address::new_id(1001);
+
+        let mut state = State {
+            members: vec![alice, bob],
+            threshold: 2,
+            epoch_length: 10,
+            params: ChainParams::default(),
+            next_id: 0,
+            proposals: Vec::new(),
+            scheduled: None,
+            deferred_topdown_msgs: Vec::new(),
+            shutdown: None,
+            topdown_filter: TopdownFilter::default(),
+        };
+
+        let old = state.propose(5, alice, ChainParams::default());
+        let new = state.propose(50, alice, ChainParams::default());
+
+        // 10 epochs of 10 blocks after the proposal.
+        assert!(state.expire_proposals(104).is_empty());
+        assert_eq!(
+            state.approve(old, bob, 105),
+            Err(format!("proposal {old} expired"))
+        );
+        assert_eq!(state.expire_proposals(105), vec![old]);
+        assert_eq!(state.proposals.len(), 1);
+
+        assert_eq!(
+            state.approve(old, bob, 106),
+            Err(format!("proposal {old} not found"))
+        );
+        assert_eq!(state.approve(new, bob, 106), Ok(true));
+        assert_eq!(state.scheduled.as_ref().unwrap().proposal_id, new);
+    }
+}
@@ -335,6 +335,23 @@ pub mod gateway {
         U256::from_big_endian(&value.atto().to_bytes_be().1)
     }
 
+    /// Check whether the calldata of a gateway call adds a message to the bottom-up queue,
+    /// i.e. it's releasing funds to the parent or sending a cross-message.
+    pub fn is_bottom_up_call(calldata: &[u8]) -> bool {
+        use ethers::contract::EthCall;
+        use ipc_actors_abis::{gateway_manager_facet, gateway_messenger_facet};
+
+        if calldata.len() < 4 {
+            return false;
+        }
+        [
+            gateway_manager_facet::ReleaseCall::selector(),
+            gateway_messenger_facet::SendCrossMessageCall::selector(),
+        ]
+        .iter()
+        .any(|selector| calldata[..4] == *selector)
+    }
+
     #[cfg(test)]
     mod tests {
         use ethers::core::types::{Selector, U256};
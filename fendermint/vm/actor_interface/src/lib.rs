@@ -42,16 +42,37 @@ macro_rules! define_singleton {
     };
 }
 
+pub mod accesscontrol;
 pub mod account;
+pub mod beacon;
+pub mod blocktime;
 pub mod burntfunds;
+pub mod chainmetadata;
+pub mod checkpointarchive;
+pub mod contractbook;
 pub mod cron;
+pub mod deadletter;
 pub mod diamond;
+pub mod diamondlayout;
 pub mod eam;
 pub mod ethaccount;
 pub mod evm;
+pub mod ethblockhash;
+pub mod fastfinality;
+pub mod execdigests;
+pub mod filecoinsig;
+pub mod governance;
 pub mod init;
 pub mod ipc;
 pub mod multisig;
 pub mod placeholder;
+pub mod policy;
 pub mod reward;
+pub mod rewardpool;
+pub mod scheduler;
+pub mod syscontracts;
 pub mod system;
+pub mod topdownnonces;
+pub mod txcompression;
+pub mod txorder;
+pub mod validators;
@@ -0,0 +1,28 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The policy actor doesn't have Wasm code of its own. The interpreter reserves
+//! an ID for it if the genesis names a policy module, and consults the module
+//! about every user message if the actor exists. The state pins the module, so
+//! that every validator has to run the same one.
+use cid::Cid;
+use fendermint_vm_genesis::Policy;
+use fvm_ipld_encoding::tuple::*;
+
+define_id!(POLICY { id: 87 });
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    /// CID of the raw bytes of the Wasm module.
+    pub module: Cid,
+    /// Fuel a single call of the module can consume.
+    pub fuel_limit: u64,
+}
+
+impl From<Policy> for State {
+    fn from(value: Policy) -> Self {
+        Self {
+            module: value.module,
+            fuel_limit: value.fuel_limit,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The reward pool actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and collects the fees of every block into its balance,
+//! along with the inflation minted at the end of every epoch. At the epoch
+//! boundaries the pool is shared among the validators in proportion to their
+//! power, and the shares are kept in the state until the validators claim them.
+//!
+//! The balance of the actor is the sum of the claimable shares and the part of
+//! the pool which hasn't been distributed yet, e.g. the fees of the current epoch.
+use fvm_ipld_encoding::{strict_bytes, tuple::*};
+use fvm_shared::{address::Address, bigint::BigInt, econ::TokenAmount};
+
+use crate::eam::EthAddress;
+
+define_id!(REWARD_POOL { id: 85 });
+
+/// Methods the validators call on the reward pool.
+#[repr(u64)]
+pub enum Method {
+    /// Transfer all the rewards of the validator to the sender.
+    Claim = 2,
+}
+
+/// Rewards distributed to a validator but not claimed yet.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct Claimable {
+    /// Uncompressed secp256k1 public key of the validator, as in the validator address book.
+    #[serde(with = "strict_bytes")]
+    pub public_key: Vec<u8>,
+    pub amount: TokenAmount,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    /// Number of blocks in an epoch.
+    pub epoch_length: u64,
+    /// Tokens minted into the pool at the end of every epoch.
+    pub inflation_per_epoch: TokenAmount,
+    /// Whether the base fees, which are otherwise burnt, are collected as well as the tips.
+    pub collect_base_fees: bool,
+    /// The epoch in progress, which is distributed when the next one begins.
+    pub epoch: u64,
+    /// Sum of the claimable amounts.
+    pub total_claimable: TokenAmount,
+    /// The validators with unclaimed rewards, in the order they were first rewarded.
+    pub claimable: Vec<Claimable>,
+}
+
+impl State {
+    pub fn new(
+        epoch_length: u64,
+        inflation_per_epoch: TokenAmount,
+        collect_base_fees: bool,
+    ) -> Self {
+        Self {
+            epoch_length: epoch_length.max(1),
+            inflation_per_epoch,
+            collect_base_fees,
+            epoch: 0,
+            total_claimable: TokenAmount::default(),
+            claimable: Vec::new(),
+        }
+    }
+
+    /// The epoch a height belongs to.
+    pub fn epoch(&self, height: i64) -> u64 {
+        height as u64 / self.epoch_length
+    }
+
+    /// The part of a balance which hasn't been distributed yet.
+    pub fn undistributed(&self, balance: &TokenAmount) -> TokenAmount {
+        if *balance > self.total_claimable {
+            balance - &self.total_claimable
+        } else {
+            TokenAmount::default()
+        }
+    }
+
+    /// Share an amount among the validators in proportion to their power.
+    ///
+    /// Validators are given by their public key and power; the ones without power get nothing.
+    /// Returns the shares; what's left over from rounding down is not distributed.
+    pub fn distribute(
+        &mut self,
+        amount: &TokenAmount,
+        validators: &[(Vec<u8>, u64)],
+    ) -> Vec<(Vec<u8>, TokenAmount)> {
+        let total_power = validators.iter().map(|(_, p)| *p as u128).sum::<u128>();
+        if total_power == 0 {
+            return Vec::new();
+        }
+        let total_power = BigInt::from(total_power);
+
+        let mut shares = Vec::new();
+        for (public_key, power) in validators.iter().filter(|(_, p)| *p > 0) {
+            let share = TokenAmount::from_atto(amount.atto() * BigInt::from(*power) / &total_power);
+            if share == TokenAmount::default() {
+                continue;
+            }
+            self.credit(public_key, &share);
+            shares.push((public_key.clone(), share));
+        }
+        shares
+    }
+
+    fn credit(&mut self, public_key: &[u8], amount: &TokenAmount) {
+        self.total_claimable += amount;
+        match self
+            .claimable
+            .iter_mut()
+            .find(|c| c.public_key == public_key)
+        {
+            Some(c) => c.amount += amount,
+            None => self.claimable.push(Claimable {
+                public_key: public_key.to_vec(),
+                amount: amount.clone(),
+            }),
+        }
+    }
+
+    /// The rewards claimable by an address, which can be the `f1` address of the validator key
+    /// or the `f410` address of its Ethereum address.
+    pub fn get(&self, addr: &Address) -> Option<&Claimable> {
+        self.claimable
+            .iter()
+            .find(|c| is_key_address(&c.public_key, addr))
+    }
+
+    /// Remove the rewards of the validator behind an address, to be transferred to it.
+    pub fn claim(&mut self, addr: &Address) -> Option<Claimable> {
+        let i = self
+            .claimable
+            .iter()
+            .position(|c| is_key_address(&c.public_key, addr))?;
+        let c = self.claimable.remove(i);
+        self.total_claimable -= &c.amount;
+        Some(c)
+    }
+}
+
+fn is_key_address(public_key: &[u8], addr: &Address) -> bool {
+    Address::new_secp256k1(public_key).ok().as_ref() == Some(addr)
+        || EthAddress::new_secp256k1(public_key)
+            .map(Address::from)
+            .ok()
+            .as_ref()
+            == Some(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::State;
+    use crate::eam::EthAddress;
+
+    fn atto(n: u64) -> TokenAmount {
+        TokenAmount::from_atto(n)
+    }
+
+    #[test]
+    fn distribute_by_power() {
+        let mut state = State::new(10, atto(0), false);
+        let validators = vec![(vec![1], 1), (vec![2], 2), (vec![3], 0)];
+
+        let shares = state.distribute(&atto(100), &validators);
+        assert_eq!(shares, vec![(vec![1], atto(33)), (vec![2], atto(66))]);
+        assert_eq!(state.total_claimable, atto(99));
+
+        // The remainder stays in the pool, to be distributed with the next epoch.
+        assert_eq!(state.undistributed(&atto(100)), atto(1));
+
+        state.distribute(&atto(31), &validators);
+        assert_eq!(state.claimable.len(), 2);
+        assert_eq!(state.claimable[0].amount, atto(43));
+        assert_eq!(state.claimable[1].amount, atto(86));
+        assert_eq!(state.total_claimable, atto(129));
+    }
+
+    #[test]
+    fn distribute_without_power() {
+        let mut state = State::new(10, atto(0), false);
+        assert!(state.distribute(&atto(100), &[]).is_empty());
+        assert!(state.distribute(&atto(100), &[(vec![1], 0)]).is_empty());
+        assert_eq!(state.total_claimable, atto(0));
+    }
+
+    #[test]
+    fn claim_by_either_address() {
+        let sk = SecretKey::random(&mut StdRng::seed_from_u64(42));
+        let pk = sk.public_key().serialize().to_vec();
+        let f1 = Address::new_secp256k1(&pk).unwrap();
+        let f410 = Address::from(EthAddress::new_secp256k1(&pk).unwrap());
+
+        let mut state = State::new(10, atto(0), false);
+        state.distribute(&atto(100), &[(pk, 1)]);
+
+        assert_eq!(state.get(&f410).map(|c| c.amount.clone()), Some(atto(100)));
+        assert!(state.claim(&Address::new_id(100)).is_none());
+        assert_eq!(state.claim(&f1).map(|c| c.amount), Some(atto(100)));
+        assert!(state.claim(&f410).is_none());
+        assert_eq!(state.total_claimable, atto(0));
+    }
+}
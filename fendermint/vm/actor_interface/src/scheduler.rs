@@ -0,0 +1,106 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The scheduler actor doesn't have Wasm code of its own. The interpreter
+//! reserves an ID for it and keeps the system messages registered in genesis
+//! in its state; at the beginning of every block the due ones are sent by the
+//! system actor, the same way as the cron tick.
+use fvm_ipld_encoding::{tuple::*, RawBytes};
+use fvm_shared::{address::Address, MethodNum};
+
+define_id!(SCHEDULER { id: 96 });
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledCall {
+    pub name: String,
+    pub to: Address,
+    pub method_num: MethodNum,
+    pub params: RawBytes,
+    pub start_height: u64,
+    /// Number of blocks between runs; 0 means the call only runs once.
+    pub interval: u64,
+}
+
+impl ScheduledCall {
+    pub fn is_due(&self, height: u64) -> bool {
+        if height < self.start_height {
+            false
+        } else if self.interval == 0 {
+            height == self.start_height
+        } else {
+            (height - self.start_height) % self.interval == 0
+        }
+    }
+}
+
+impl From<fendermint_vm_genesis::ScheduledCall> for ScheduledCall {
+    fn from(value: fendermint_vm_genesis::ScheduledCall) -> Self {
+        Self {
+            name: value.name,
+            to: value.to,
+            method_num: value.method_num,
+            params: RawBytes::new(value.params),
+            start_height: value.start_height,
+            interval: value.interval,
+        }
+    }
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    /// Calls in the order they are executed when due at the same height.
+    pub calls: Vec<ScheduledCall>,
+}
+
+impl State {
+    /// Calls which have to run at a given height, in registration order.
+    pub fn due(&self, height: u64) -> impl Iterator<Item = &ScheduledCall> {
+        self.calls.iter().filter(move |c| c.is_due(height))
+    }
+}
+
+impl From<Vec<fendermint_vm_genesis::ScheduledCall>> for State {
+    fn from(value: Vec<fendermint_vm_genesis::ScheduledCall>) -> Self {
+        Self {
+            calls: value.into_iter().map(ScheduledCall::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::Address;
+
+    use super::{ScheduledCall, State};
+
+    fn call(name: &str, start_height: u64, interval: u64) -> ScheduledCall {
+        ScheduledCall {
+            name: name.to_owned(),
+            to: Address::new_id(100),
+            method_num: 2,
+            params: Default::default(),
+            start_height,
+            interval,
+        }
+    }
+
+    #[test]
+    fn due_calls() {
+        let state = State {
+            calls: vec![call("once", 5, 0), call("every-3", 2, 3)],
+        };
+
+        let due = |height| {
+            state
+                .due(height)
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(due(1).is_empty());
+        assert_eq!(due(2), vec!["every-3"]);
+        assert!(due(4).is_empty());
+        assert_eq!(due(5), vec!["once", "every-3"]);
+        assert_eq!(due(8), vec!["every-3"]);
+        assert!(due(10).is_empty());
+    }
+}
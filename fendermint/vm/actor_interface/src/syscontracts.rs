@@ -0,0 +1,128 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Utility contracts which can be deployed during genesis with well-known IDs.
+// The Solidity sources are in `fendermint/contracts`; the ABIs here have to
+// match the compiled artifacts, which `ContractDeployer::deploy_contract`
+// checks before the deployment.
+//
+// The contracts don't declare constructors, but the genesis deployment
+// encodes the init code through the ABI, so an empty one is listed.
+
+use ethers::core::abi::{parse_abi, Abi};
+use fendermint_vm_genesis::SystemContract;
+use lazy_static::lazy_static;
+
+use crate::diamond::{EthContract, EthContractMap};
+
+define_id!(MULTICALL { id: 66 });
+define_id!(CREATE2_DEPLOYER { id: 67 });
+define_id!(WFIL { id: 68 });
+
+pub mod multicall {
+    pub const CONTRACT_NAME: &str = "Multicall3";
+}
+
+pub mod deployer {
+    pub const CONTRACT_NAME: &str = "Create2Deployer";
+}
+
+pub mod wfil {
+    pub const CONTRACT_NAME: &str = "WFIL";
+}
+
+lazy_static! {
+    static ref MULTICALL_ABI: Abi = parse_abi(&[
+        "constructor()",
+        "function aggregate3((address,bool,bytes)[] calls) payable returns ((bool,bytes)[] returnData)",
+        "function aggregate3Value((address,bool,uint256,bytes)[] calls) payable returns ((bool,bytes)[] returnData)",
+        "function getBlockNumber() view returns (uint256 blockNumber)",
+        "function getCurrentBlockTimestamp() view returns (uint256 timestamp)",
+        "function getChainId() view returns (uint256 chainid)",
+        "function getEthBalance(address addr) view returns (uint256 balance)",
+    ])
+    .expect("valid Multicall3 ABI");
+
+    static ref DEPLOYER_ABI: Abi = parse_abi(&[
+        "constructor()",
+        "event Deployed(address indexed addr, bytes32 indexed salt)",
+        "function deploy(bytes32 salt, bytes initCode) payable returns (address addr)",
+        "function computeAddress(bytes32 salt, bytes32 initCodeHash) view returns (address)",
+    ])
+    .expect("valid Create2Deployer ABI");
+
+    static ref WFIL_ABI: Abi = parse_abi(&[
+        "constructor()",
+        "event Approval(address indexed src, address indexed guy, uint256 wad)",
+        "event Transfer(address indexed src, address indexed dst, uint256 wad)",
+        "event Deposit(address indexed dst, uint256 wad)",
+        "event Withdrawal(address indexed src, uint256 wad)",
+        "function name() view returns (string)",
+        "function symbol() view returns (string)",
+        "function decimals() view returns (uint8)",
+        "function balanceOf(address) view returns (uint256)",
+        "function allowance(address, address) view returns (uint256)",
+        "function totalSupply() view returns (uint256)",
+        "function deposit() payable",
+        "function withdraw(uint256 wad)",
+        "function approve(address guy, uint256 wad) returns (bool)",
+        "function transfer(address dst, uint256 wad) returns (bool)",
+        "function transferFrom(address src, address dst, uint256 wad) returns (bool)",
+    ])
+    .expect("valid WFIL ABI");
+}
+
+/// The contract to deploy for a system contract enabled in the genesis, with its name.
+pub fn system_contract(kind: SystemContract) -> (&'static str, EthContract) {
+    let (name, actor_id, abi) = match kind {
+        SystemContract::Multicall => (
+            multicall::CONTRACT_NAME,
+            MULTICALL_ACTOR_ID,
+            &*MULTICALL_ABI,
+        ),
+        SystemContract::Deployer => (
+            deployer::CONTRACT_NAME,
+            CREATE2_DEPLOYER_ACTOR_ID,
+            &*DEPLOYER_ABI,
+        ),
+        SystemContract::Wfil => (wfil::CONTRACT_NAME, WFIL_ACTOR_ID, &*WFIL_ABI),
+    };
+    let contract = EthContract {
+        actor_id,
+        abi: abi.clone(),
+        facets: Vec::new(),
+    };
+    (name, contract)
+}
+
+/// The contracts to deploy for the system contracts enabled in the genesis.
+pub fn system_contracts(kinds: &[SystemContract]) -> EthContractMap {
+    kinds.iter().map(|k| system_contract(*k)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_genesis::SystemContract;
+
+    use super::system_contracts;
+
+    #[test]
+    fn system_contract_abis_parse() {
+        let contracts = system_contracts(&[
+            SystemContract::Multicall,
+            SystemContract::Deployer,
+            SystemContract::Wfil,
+        ]);
+        assert_eq!(contracts.len(), 3);
+        for (name, contract) in contracts {
+            assert!(
+                contract.abi.functions().count() > 0,
+                "{name} has no functions"
+            );
+            assert!(
+                contract.abi.constructor().is_some(),
+                "{name} has no constructor"
+            );
+        }
+    }
+}
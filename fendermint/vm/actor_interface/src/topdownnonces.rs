@@ -0,0 +1,40 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The top-down nonces actor doesn't have Wasm code of its own. The interpreter
+//! creates it on chains with a parent, either at genesis or when the first top-down
+//! messages arrive, and records the nonces of the top-down messages as the gateway
+//! applies them, so that no message is ever applied twice or skipped, whatever the
+//! proposers do. Being in the state, the record survives restarts and comes along
+//! with the snapshots.
+use fvm_ipld_encoding::tuple::*;
+
+define_id!(TOPDOWN_NONCES { id: 86 });
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    /// Nonce the next top-down message has to have; it follows the nonce of the gateway.
+    pub next_nonce: u64,
+    /// Block height where the last message was applied.
+    pub last_height: u64,
+}
+
+impl State {
+    /// Start recording from the nonce the gateway expects next.
+    pub fn new(next_nonce: u64) -> Self {
+        Self {
+            next_nonce,
+            last_height: 0,
+        }
+    }
+
+    /// Check whether a message with this nonce has been applied already.
+    pub fn is_applied(&self, nonce: u64) -> bool {
+        nonce < self.next_nonce
+    }
+
+    /// Record a message as applied.
+    pub fn applied(&mut self, nonce: u64, height: u64) {
+        self.next_nonce = nonce + 1;
+        self.last_height = height;
+    }
+}
@@ -0,0 +1,14 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The transaction compression actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it if the genesis enabled compressed
+//! transactions, and only accepts them if the actor exists.
+use fvm_ipld_encoding::tuple::*;
+
+define_id!(TX_COMPRESSION { id: 97 });
+
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    /// Largest size a compressed transaction is allowed to expand to.
+    pub max_decompressed_size: u64,
+}
@@ -0,0 +1,11 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The transaction order actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it if the genesis requires the signed
+//! messages of every block to be in the canonical order, by sender and nonce.
+//! Since it decides which proposals the validators vote for, it's part of the
+//! chain rather than a setting of the nodes.
+//!
+//! The actor has no state, its existence is the flag.
+
+define_id!(TX_ORDER { id: 80 });
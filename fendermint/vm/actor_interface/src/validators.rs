@@ -0,0 +1,93 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! The validator address book actor doesn't have Wasm code of its own. The
+//! interpreter reserves an ID for it and records the public key of every
+//! validator which has been part of the power table, starting with the ones
+//! in the genesis, so monitoring tools can tell which on-chain identity is
+//! behind a consensus address, even after a validator has left.
+use fvm_ipld_encoding::{strict_bytes, tuple::*};
+use fvm_shared::{address::Address, clock::ChainEpoch};
+
+use crate::eam::EthAddress;
+
+define_id!(VALIDATORS { id: 94 });
+
+/// A validator which has been part of the power table at some point.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorEntry {
+    /// Uncompressed secp256k1 public key, which all the addresses can be derived from.
+    #[serde(with = "strict_bytes")]
+    pub public_key: Vec<u8>,
+    /// Power in the CometBFT scale; 0 if the validator has left.
+    pub power: u64,
+    /// Height where the validator first appeared in the power table.
+    pub joined_at: ChainEpoch,
+    /// Height of the last change in power.
+    pub updated_at: ChainEpoch,
+}
+
+/// Validators in the order they joined.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct State {
+    pub validators: Vec<ValidatorEntry>,
+}
+
+impl State {
+    /// Record a change in the power of a validator, adding it if it's new.
+    pub fn update(&mut self, height: ChainEpoch, public_key: Vec<u8>, power: u64) {
+        match self
+            .validators
+            .iter_mut()
+            .find(|v| v.public_key == public_key)
+        {
+            Some(v) => {
+                v.power = power;
+                v.updated_at = height;
+            }
+            None => self.validators.push(ValidatorEntry {
+                public_key,
+                power,
+                joined_at: height,
+                updated_at: height,
+            }),
+        }
+    }
+
+    pub fn get(&self, public_key: &[u8]) -> Option<&ValidatorEntry> {
+        self.validators.iter().find(|v| v.public_key == public_key)
+    }
+
+    /// Check whether an address belongs to a validator currently in the power table,
+    /// either as the `f1` address of its key or as the `f410` address of its Ethereum address.
+    pub fn is_active(&self, addr: &Address) -> bool {
+        self.validators.iter().filter(|v| v.power > 0).any(|v| {
+            Address::new_secp256k1(&v.public_key).ok().as_ref() == Some(addr)
+                || EthAddress::new_secp256k1(&v.public_key)
+                    .map(Address::from)
+                    .ok()
+                    .as_ref()
+                    == Some(addr)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::State;
+
+    #[test]
+    fn update_keeps_leavers() {
+        let mut state = State::default();
+        state.update(0, vec![1], 10);
+        state.update(0, vec![2], 20);
+        state.update(5, vec![1], 0);
+        state.update(7, vec![3], 30);
+
+        assert_eq!(state.validators.len(), 3);
+        let v = state.get(&[1]).unwrap();
+        assert_eq!(v.power, 0);
+        assert_eq!(v.joined_at, 0);
+        assert_eq!(v.updated_at, 5);
+        assert_eq!(state.get(&[3]).unwrap().joined_at, 7);
+    }
+}
@@ -1,8 +1,8 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 use crate::{
-    ipc, Account, Actor, ActorMeta, Collateral, Genesis, Multisig, Power, SignerAddr, Validator,
-    ValidatorKey,
+    ipc, AccessControl, Account, Actor, ActorMeta, ChainParams, Collateral, Genesis, Governance,
+    Multisig, Power, Rewards, ScheduledCall, SignerAddr, Validator, ValidatorKey,
 };
 use cid::multihash::MultihashDigest;
 use fendermint_crypto::SecretKey;
@@ -32,13 +32,7 @@ impl Arbitrary for ActorMeta {
             })
         } else {
             let n = u64::arbitrary(g) % 4 + 2;
-            let signers = (0..n)
-                .map(|_| {
-                    let pk = ValidatorKey::arbitrary(g).0;
-                    let addr = Address::new_secp256k1(&pk.serialize()).unwrap();
-                    SignerAddr(addr)
-                })
-                .collect();
+            let signers = (0..n).map(|_| SignerAddr::arbitrary(g)).collect();
             let threshold = u64::arbitrary(g) % n + 1;
             ActorMeta::Multisig(Multisig {
                 signers,
@@ -113,6 +107,118 @@ impl Arbitrary for Genesis {
             } else {
                 None
             },
+            access_control: if bool::arbitrary(g) {
+                Some(AccessControl::arbitrary(g))
+            } else {
+                None
+            },
+            governance: if bool::arbitrary(g) {
+                Some(Governance::arbitrary(g))
+            } else {
+                None
+            },
+            scheduled_calls: (0..usize::arbitrary(g) % 3)
+                .map(|_| ScheduledCall::arbitrary(g))
+                .collect(),
+            tx_compression: bool::arbitrary(g),
+            filecoin_signatures: bool::arbitrary(g),
+            // Deploying them needs the Foundry artifacts, which aren't built by default.
+            system_contracts: Vec::new(),
+            // A beacon changes what every block executes, which the tests built on an arbitrary
+            // genesis don't expect; the values are still drawn so that the rest of the genesis stays the same.
+            beacon: {
+                if bool::arbitrary(g) {
+                    let _ = u64::arbitrary(g);
+                }
+                None
+            },
+            // A policy needs a module the nodes can load, which random bytes are not;
+            // the values are still drawn so that the rest of the genesis stays the same.
+            policy: {
+                if bool::arbitrary(g) {
+                    let _ = (Vec::<u8>::arbitrary(g), u64::arbitrary(g));
+                }
+                None
+            },
+            rewards: if bool::arbitrary(g) {
+                Some(Rewards {
+                    epoch_length: u64::arbitrary(g) % 100 + 1,
+                    inflation_per_epoch: TokenAmount::from_atto(u64::arbitrary(g)),
+                    collect_base_fees: bool::arbitrary(g),
+                })
+            } else {
+                None
+            },
+            exec_digests: bool::arbitrary(g),
+            max_block_interval: if bool::arbitrary(g) {
+                Some(u64::arbitrary(g) % 3600 + 1)
+            } else {
+                None
+            },
+            eth_block_hash_v1: bool::arbitrary(g),
+            canonical_tx_order: bool::arbitrary(g),
+            fast_topdown_finality: bool::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for SignerAddr {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let pk = ValidatorKey::arbitrary(g).0;
+        let addr = Address::new_secp256k1(&pk.serialize()).unwrap();
+        SignerAddr(addr)
+    }
+}
+
+impl Arbitrary for AccessControl {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let arb_list = |g: &mut Gen| -> Option<Vec<SignerAddr>> {
+            if bool::arbitrary(g) {
+                let n = usize::arbitrary(g) % 5;
+                Some((0..n).map(|_| SignerAddr::arbitrary(g)).collect())
+            } else {
+                None
+            }
+        };
+        Self {
+            governor: SignerAddr::arbitrary(g),
+            deployers: arb_list(g),
+            senders: arb_list(g),
+        }
+    }
+}
+
+impl Arbitrary for Governance {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let n = usize::arbitrary(g) % 5 + 1;
+        Self {
+            members: (0..n).map(|_| SignerAddr::arbitrary(g)).collect(),
+            threshold: u64::arbitrary(g) % n as u64 + 1,
+            epoch_length: u64::arbitrary(g) % 100 + 1,
+            params: ChainParams::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for ScheduledCall {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            name: String::arbitrary(g),
+            to: Address::new_id(u64::arbitrary(g) % 1000),
+            method_num: u64::arbitrary(g),
+            params: Vec::arbitrary(g),
+            start_height: u64::arbitrary(g) % 100 + 1,
+            interval: u64::arbitrary(g) % 10,
+        }
+    }
+}
+
+impl Arbitrary for ChainParams {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            base_fee_floor: ArbTokenAmount::arbitrary(g).0,
+            block_gas_limit: u64::arbitrary(g),
+            topdown_max_msgs: u64::arbitrary(g),
         }
     }
 }
@@ -138,6 +244,7 @@ impl Arbitrary for ipc::IpcParams {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
         Self {
             gateway: ipc::GatewayParams::arbitrary(g),
+            checkpoint_retention: u64::arbitrary(g) % 10,
         }
     }
 }
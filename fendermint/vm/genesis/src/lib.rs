@@ -4,6 +4,8 @@
 //! in Lotus, which is used to [initialize](https://github.com/filecoin-project/lotus/blob/v1.20.4/chain/gen/genesis/genesis.go) the state tree.
 
 use anyhow::anyhow;
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
 use fvm_shared::bigint::{BigInt, Integer};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -44,6 +46,63 @@ pub struct Genesis {
     /// IPC related configuration, if enabled.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ipc: Option<ipc::IpcParams>,
+    /// Chain-level access control for permissioned subnets, if enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_control: Option<AccessControl>,
+    /// Multisig which can change chain parameters through on-chain proposals, if enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<Governance>,
+    /// System messages executed by the interpreter at the beginning of blocks, at fixed heights.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scheduled_calls: Vec<ScheduledCall>,
+    /// Whether transactions can be sent compressed, to fit large ones into blocks.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tx_compression: bool,
+    /// Whether messages signed by Filecoin wallets over their CID alone are accepted,
+    /// which means they aren't protected against replay from other chains.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub filecoin_signatures: bool,
+    /// Utility contracts to deploy at their well-known addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_contracts: Vec<SystemContract>,
+    /// Randomness beacon the validators contribute to with commit-reveal rounds, if enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beacon: Option<Beacon>,
+    /// Wasm module consulted about every user message, to enforce custom policies, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    /// Rewards paid to the validators out of the fees and an inflation schedule, if enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewards: Option<Rewards>,
+    /// Whether the digests of the receipts and events of every block are recorded in the state,
+    /// so light clients can verify the result of a transaction with a Merkle proof.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exec_digests: bool,
+    /// Largest number of seconds the time of a block can be ahead of the previous one, if limited.
+    ///
+    /// Validators reject proposals beyond it, which protects time dependent contracts from a
+    /// proposer jumping ahead, but it also means the chain can't resume after stopping for longer
+    /// than this, so it has to be well above any pause the operators expect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_block_interval: Option<u64>,
+    /// Whether the Ethereum API exposes block hashes derived from the CometBFT ones with the
+    /// `v1` derivation in `fendermint_vm_message::block_hash`, rather than the CometBFT hashes
+    /// as they are.
+    ///
+    /// It can only be chosen at genesis, so that the hashes clients store never change.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub eth_block_hash_v1: bool,
+    /// Whether the signed messages in a block have to be sorted by sender and nonce.
+    ///
+    /// Validators reject proposals out of this order, so it's part of the chain and can only be
+    /// chosen at genesis, rather than risking the validators disagreeing about it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canonical_tx_order: bool,
+    /// Whether the validators follow the parent in the fast top-down finality mode, proposing
+    /// parent heights without waiting for them to be final and voting for heights they haven't
+    /// seen yet. It's only meant for local development.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fast_topdown_finality: bool,
 }
 
 /// Wrapper around [`Address`] to provide human readable serialization in JSON format.
@@ -83,11 +142,154 @@ pub struct Actor {
     pub balance: TokenAmount,
 }
 
+/// Allow-lists restricting who can deploy contracts and who can send transactions.
+///
+/// The lists are stored in the state of the access control actor, where the
+/// governor can update them after genesis by sending messages to the actor.
+///
+/// Addresses are compared to the sender of the messages, so they should be
+/// given in the form the accounts sign with, e.g. `f1` or `f410` addresses.
+/// The governor is always allowed to send transactions and deploy contracts.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessControl {
+    /// The account which can update the allow-lists.
+    pub governor: SignerAddr,
+    /// Accounts allowed to deploy contracts through the EAM; `None` means anyone can.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployers: Option<Vec<SignerAddr>>,
+    /// Accounts allowed to send any transaction; `None` means anyone can.
+    ///
+    /// Note that validators have to be on this list to be able to submit checkpoint signatures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub senders: Option<Vec<SignerAddr>>,
+}
+
+/// Members who can change the chain parameters after genesis, by proposing new values
+/// and approving each other's proposals with messages sent to the governance actor.
+///
+/// Approved changes take effect at the next epoch boundary, so that every block in
+/// an epoch is executed with the same parameters.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Governance {
+    pub members: Vec<SignerAddr>,
+    /// Number of approvals a proposal needs, including the proposer's.
+    pub threshold: u64,
+    /// Number of blocks in an epoch.
+    pub epoch_length: u64,
+    /// Initial values of the parameters.
+    #[serde(default)]
+    pub params: ChainParams,
+}
+
+/// Chain parameters which can be adjusted by governance.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct ChainParams {
+    /// Minimum base fee; if the base fee is lower, it's raised to this value.
+    #[serde_as(as = "IsHumanReadable")]
+    pub base_fee_floor: TokenAmount,
+    /// Maximum sum of the gas limits of the transactions in a block; 0 means unlimited.
+    pub block_gas_limit: u64,
+    /// Maximum number of top-down messages executed in a block; 0 means unlimited.
+    ///
+    /// Messages over the limit are executed in later blocks, in their original order.
+    pub topdown_max_msgs: u64,
+}
+
+/// A randomness beacon produced by the validators, without relying on an external oracle.
+///
+/// Every epoch is split in half: in the first half the validators commit to a secret,
+/// in the second half they reveal it, and at the end of the epoch the revealed secrets
+/// are combined with the previous beacon value into the new one.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Beacon {
+    /// Number of blocks in an epoch; at least 2.
+    pub epoch_length: u64,
+}
+
+/// The fees of the blocks, and optionally newly minted tokens, are collected into a pool
+/// which is shared among the validators in proportion to their power at the end of every
+/// epoch. The validators claim their shares by sending a message to the reward pool actor.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Rewards {
+    /// Number of blocks in an epoch.
+    pub epoch_length: u64,
+    /// Tokens minted into the pool at the end of every epoch.
+    #[serde_as(as = "IsHumanReadable")]
+    #[serde(default)]
+    pub inflation_per_epoch: TokenAmount,
+    /// Whether the base fees are collected too, instead of being burnt; the tips always are.
+    #[serde(default)]
+    pub collect_base_fees: bool,
+}
+
+/// A Wasm module the validators consult about every user message, which can reject them,
+/// so that subnets can enforce compliance rules of their own without forking the node.
+///
+/// The module itself is not part of the genesis, only its CID; the validators load it from
+/// their own disk, and refuse to execute messages if it's not the same.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Policy {
+    /// CID of the raw bytes of the module, with a Blake2b-256 hash.
+    #[serde_as(as = "IsHumanReadable")]
+    pub module: Cid,
+    /// Fuel a single call of the module can consume before the message is rejected.
+    pub fuel_limit: u64,
+}
+
+impl Policy {
+    /// Calculate the CID a module is pinned by.
+    pub fn module_cid(bytes: &[u8]) -> Cid {
+        // IPLD_RAW, as the module is not IPLD data.
+        Cid::new_v1(0x55, Code::Blake2b256.digest(bytes))
+    }
+}
+
+/// Utility contracts which can be deployed at genesis, so that subnets have the same baseline tooling.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemContract {
+    /// Batch multiple calls into one, compatible with Multicall3.
+    Multicall,
+    /// Deploy contracts with `CREATE2` to get the same address on every subnet.
+    Deployer,
+    /// Wrapped FIL, an ERC-20 token backed by the native coin.
+    Wfil,
+}
+
+/// A message sent by the system actor at the beginning of a block, when the height is due.
+///
+/// Use it for periodic chain maintenance, e.g. adjusting the base fee or distributing
+/// rewards, so that the logic lives in an actor rather than the interpreter.
+/// The messages don't cost gas, and failures are recorded but don't halt the chain.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduledCall {
+    /// Name used in logs and events.
+    pub name: String,
+    #[serde_as(as = "IsHumanReadable")]
+    pub to: Address,
+    pub method_num: u64,
+    /// IPLD encoded parameters of the method.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<u8>,
+    /// First height at which the message is sent; must be greater than zero.
+    pub start_height: u64,
+    /// Number of blocks between consecutive sends; 0 means the message is only sent once.
+    #[serde(default)]
+    pub interval: u64,
+}
+
 /// Total amount of tokens delegated to a validator.
 #[serde_as]
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Collateral(#[serde_as(as = "IsHumanReadable")] pub TokenAmount);
 
+/// Tendermint rejects validator sets with more total power than this.
+pub const MAX_TOTAL_VOTING_POWER: u64 = (i64::MAX / 8) as u64;
+
 /// Total voting power of a validator.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Copy)]
 pub struct Power(pub u64);
@@ -210,6 +412,10 @@ pub mod ipc {
     #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
     pub struct IpcParams {
         pub gateway: GatewayParams,
+        /// Number of bottom-up checkpoints to keep in the gateway; the older ones are exported
+        /// to archive files and pruned. 0 means they are kept forever.
+        #[serde(default)]
+        pub checkpoint_retention: u64,
     }
 
     #[serde_as]
@@ -3,14 +3,19 @@
 use anyhow::{anyhow, Context};
 use async_trait::async_trait;
 use cid::Cid;
+use fendermint_vm_actor_interface::execdigests::{self, TxReceipt};
 use fendermint_vm_genesis::Genesis;
 use fendermint_vm_message::chain::ChainMessage;
-use fvm_ipld_encoding::Error as IpldError;
+use fvm_ipld_encoding::{CodecProtocol, Error as IpldError};
+use rayon::prelude::*;
 
 use crate::{
+    cache::{tx_cid, MessageCache},
     chain::{ChainMessageApplyRet, ChainMessageCheckRes},
     fvm::{FvmQuery, FvmQueryRet},
-    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, QueryInterpreter,
+    signed::BlockSignatureCheck,
+    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, ProposalRejection,
+    ProposalVerdict, QueryInterpreter, VerifyBlockInterpreter,
 };
 
 pub type BytesMessageApplyRes = Result<ChainMessageApplyRet, IpldError>;
@@ -20,6 +25,22 @@ pub type BytesMessageQueryRes = Result<FvmQueryRet, IpldError>;
 /// Close to what the ABCI sends: (Path, Bytes).
 pub type BytesMessageQuery = (String, Vec<u8>);
 
+/// States which know whether the chain accepts compressed transactions.
+pub trait HasTxCompression {
+    /// The size compressed transactions can expand to, or `None` if they aren't accepted.
+    fn max_decompressed_size(&mut self) -> anyhow::Result<Option<usize>>;
+}
+
+/// States which record the digests of the execution of blocks.
+pub trait HasExecDigests {
+    /// Whether the chain records the digests; if not, the receipts aren't collected.
+    fn records_exec_digests(&mut self) -> anyhow::Result<bool>;
+    /// Collect the receipt of a delivered transaction, with the CBOR encoded events it emitted.
+    fn push_tx_receipt(&mut self, receipt: TxReceipt, events: Vec<Vec<u8>>);
+    /// Record the digests of the transactions delivered in the block.
+    fn record_exec_digest(&mut self) -> anyhow::Result<()>;
+}
+
 /// Behavour of proposal preparation. It's an optimisation to cut down needless serialization
 /// when we know we aren't doing anything with the messages.
 #[derive(Debug, Default, Clone)]
@@ -41,6 +62,12 @@ pub struct BytesMessageInterpreter<I> {
     prepare_mode: ProposalPrepareMode,
     /// Should we reject proposals with transactions we cannot parse.
     reject_malformed_proposal: bool,
+    /// Messages decoded during proposal processing, so they don't have to be decoded again during delivery.
+    decoded: MessageCache<Cid, ChainMessage>,
+    /// Messages which passed the check when they were added to the mempool, so they don't have
+    /// to be decoded again every time CometBFT rechecks them after a block is committed.
+    /// They are forgotten once they fail a recheck, at which point CometBFT drops them.
+    checked: MessageCache<Cid, ChainMessage>,
 }
 
 impl<I> BytesMessageInterpreter<I> {
@@ -48,12 +75,127 @@ impl<I> BytesMessageInterpreter<I> {
         inner: I,
         prepare_mode: ProposalPrepareMode,
         reject_malformed_proposal: bool,
+        decode_cache_size: usize,
     ) -> Self {
         Self {
             inner,
             prepare_mode,
             reject_malformed_proposal,
+            decoded: MessageCache::new(decode_cache_size),
+            checked: MessageCache::new(decode_cache_size),
+        }
+    }
+
+    /// Decode a message, unless it has already been decoded while the proposal was processed.
+    ///
+    /// A delivered message is also no longer in the mempool, so it won't be rechecked.
+    fn decode(&self, msg: &[u8]) -> Result<ChainMessage, IpldError> {
+        if self.decoded.is_enabled() {
+            let cid = tx_cid(msg);
+            self.checked.remove(&cid);
+            if let Some(msg) = self.decoded.remove(&cid) {
+                return Ok(msg);
+            }
+        }
+        fvm_ipld_encoding::from_slice::<ChainMessage>(msg)
+    }
+}
+
+/// The receipt of a delivered transaction for the execution digests, with the CBOR encoded events it emitted.
+fn tx_receipt(tx: &[u8], ret: &BytesMessageApplyRes) -> anyhow::Result<(TxReceipt, Vec<Vec<u8>>)> {
+    let apply_ret = match ret {
+        Ok(ChainMessageApplyRet::Signed(Ok(ret))) => Some(&ret.fvm.apply_ret),
+        Ok(ChainMessageApplyRet::Ipc(ret)) => Some(&ret.apply_ret),
+        Ok(ChainMessageApplyRet::Signed(Err(_))) | Err(_) => None,
+    };
+
+    let events = match apply_ret {
+        Some(ret) => ret
+            .events
+            .iter()
+            .map(fvm_ipld_encoding::to_vec)
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to encode events")?,
+        None => Vec::new(),
+    };
+
+    let receipt = TxReceipt {
+        tx_hash: execdigests::tx_hash(tx),
+        exit_code: apply_ret.map(|r| r.msg_receipt.exit_code),
+        gas_used: apply_ret
+            .map(|r| r.msg_receipt.gas_used)
+            .unwrap_or_default(),
+        return_data: apply_ret
+            .map(|r| r.msg_receipt.return_data.clone())
+            .unwrap_or_default(),
+        events_root: execdigests::merkle_root(&events),
+    };
+
+    Ok((receipt, events))
+}
+
+/// Unwrap the message from its compressed envelope, if it has one.
+///
+/// Messages are kept compressed in the caches, so this is done for each check and delivery,
+/// which is when the state is at hand to tell whether the chain accepts them at all.
+fn decompress<S>(
+    state: &mut S,
+    msg: ChainMessage,
+) -> anyhow::Result<Result<ChainMessage, IpldError>>
+where
+    S: HasTxCompression,
+{
+    let bz = match msg {
+        ChainMessage::Compressed(bz) => bz,
+        msg => return Ok(Ok(msg)),
+    };
+
+    let invalid = |description: String| IpldError {
+        description,
+        protocol: CodecProtocol::Cbor,
+    };
+
+    let max_size = match state.max_decompressed_size()? {
+        Some(max_size) => max_size,
+        None => {
+            return Ok(Err(invalid(
+                "compressed transactions are not enabled on this chain".to_owned(),
+            )))
         }
+    };
+
+    match ChainMessage::decompress(bz.bytes(), max_size) {
+        Ok(msg) => Ok(Ok(ChainMessage::Signed(msg))),
+        Err(e) => Ok(Err(invalid(format!("{e:#}")))),
+    }
+}
+
+impl<I> VerifyBlockInterpreter for BytesMessageInterpreter<I>
+where
+    I: VerifyBlockInterpreter<Message = ChainMessage>,
+{
+    type Message = Vec<u8>;
+
+    /// Decode the messages in parallel as well, and remember them for the delivery, unless they
+    /// have already been decoded while the proposal was processed. Compressed messages are left
+    /// as they are, since whether they are accepted depends on the state.
+    fn verify_block(&self, check: &BlockSignatureCheck, msgs: &[Self::Message]) -> usize {
+        let chain_msgs = msgs
+            .par_iter()
+            .filter_map(|msg| {
+                let cid = self.decoded.is_enabled().then(|| tx_cid(msg));
+                if let Some(msg) = cid.and_then(|cid| self.decoded.get(&cid)) {
+                    return Some(msg);
+                }
+                let msg = fvm_ipld_encoding::from_slice::<ChainMessage>(msg).ok()?;
+                if let Some(cid) = cid {
+                    self.decoded.insert(cid, msg.clone());
+                }
+                Some(msg)
+            })
+            .collect::<Vec<_>>();
+
+        self.inner.verify_block(check, &chain_msgs)
     }
 }
 
@@ -110,10 +252,23 @@ where
     }
 
     /// Parse messages in the block, reject if unknown format. Pass the rest to the inner `ChainMessage` interpreter.
-    async fn process(&self, state: Self::State, msgs: Vec<Self::Message>) -> anyhow::Result<bool> {
+    async fn process(
+        &self,
+        state: Self::State,
+        msgs: Vec<Self::Message>,
+    ) -> anyhow::Result<ProposalVerdict> {
         let mut chain_msgs = Vec::new();
+        // Index of each decoded message in the proposal, to report rejections by.
+        let mut indexes = Vec::new();
 
-        for msg in msgs {
+        for (index, msg) in msgs.into_iter().enumerate() {
+            // The same proposal can be processed more than once, if there are multiple rounds at the same height.
+            let cid = self.decoded.is_enabled().then(|| tx_cid(&msg));
+            if let Some(msg) = cid.and_then(|cid| self.decoded.get(&cid)) {
+                chain_msgs.push(msg);
+                indexes.push(index);
+                continue;
+            }
             match fvm_ipld_encoding::from_slice::<ChainMessage>(&msg) {
                 Err(e) => {
                     // If we cannot parse a message, then either:
@@ -130,14 +285,29 @@ where
                         "failed to decode message in proposal as ChainMessage"
                     );
                     if self.reject_malformed_proposal {
-                        return Ok(false);
+                        return Ok(ProposalVerdict::Reject(ProposalRejection::MalformedTx {
+                            index,
+                            error: e.to_string(),
+                        }));
+                    }
+                }
+                Ok(msg) => {
+                    if let Some(cid) = cid {
+                        self.decoded.insert(cid, msg.clone());
                     }
+                    chain_msgs.push(msg);
+                    indexes.push(index);
                 }
-                Ok(msg) => chain_msgs.push(msg),
             }
         }
 
-        self.inner.process(state, chain_msgs).await
+        match self.inner.process(state, chain_msgs).await? {
+            ProposalVerdict::Reject(r) => {
+                let index = indexes[r.index()];
+                Ok(ProposalVerdict::Reject(r.with_index(index)))
+            }
+            accept => Ok(accept),
+        }
     }
 }
 
@@ -145,6 +315,7 @@ where
 impl<I> ExecInterpreter for BytesMessageInterpreter<I>
 where
     I: ExecInterpreter<Message = ChainMessage, DeliverOutput = ChainMessageApplyRet>,
+    I::State: HasTxCompression + HasExecDigests,
 {
     type State = I::State;
     type Message = Vec<u8>;
@@ -154,10 +325,14 @@ where
 
     async fn deliver(
         &self,
-        state: Self::State,
+        mut state: Self::State,
         msg: Self::Message,
     ) -> anyhow::Result<(Self::State, Self::DeliverOutput)> {
-        match fvm_ipld_encoding::from_slice::<ChainMessage>(&msg) {
+        let decoded = match self.decode(&msg) {
+            Ok(msg) => decompress(&mut state, msg)?,
+            Err(e) => Err(e),
+        };
+        let (mut state, ret) = match decoded {
             Err(e) =>
             // TODO: Punish the validator for including rubbish.
             // There is always the possibility that our codebase is incompatible,
@@ -171,20 +346,31 @@ where
                         "failed to decode delivered message as ChainMessage; we did not vote for it, maybe our node is buggy?"
                     );
                 }
-                Ok((state, Err(e)))
+                (state, Err(e))
             }
             Ok(msg) => {
                 let (state, ret) = self.inner.deliver(state, msg).await?;
-                Ok((state, Ok(ret)))
+                (state, Ok(ret))
             }
+        };
+
+        // Rejected transactions get a receipt too, so the index of a receipt is the index in the block.
+        if state.records_exec_digests()? {
+            let (receipt, events) = tx_receipt(&msg, &ret)?;
+            state.push_tx_receipt(receipt, events);
         }
+
+        Ok((state, ret))
     }
 
     async fn begin(&self, state: Self::State) -> anyhow::Result<(Self::State, Self::BeginOutput)> {
         self.inner.begin(state).await
     }
 
-    async fn end(&self, state: Self::State) -> anyhow::Result<(Self::State, Self::EndOutput)> {
+    async fn end(&self, mut state: Self::State) -> anyhow::Result<(Self::State, Self::EndOutput)> {
+        state
+            .record_exec_digest()
+            .context("failed to record execution digests")?;
         self.inner.end(state).await
     }
 }
@@ -193,6 +379,7 @@ where
 impl<I> CheckInterpreter for BytesMessageInterpreter<I>
 where
     I: CheckInterpreter<Message = ChainMessage, Output = ChainMessageCheckRes>,
+    I::State: HasTxCompression,
 {
     type State = I::State;
     type Message = Vec<u8>;
@@ -200,24 +387,60 @@ where
 
     async fn check(
         &self,
-        state: Self::State,
+        mut state: Self::State,
         msg: Self::Message,
         is_recheck: bool,
     ) -> anyhow::Result<(Self::State, Self::Output)> {
-        match fvm_ipld_encoding::from_slice::<ChainMessage>(&msg) {
+        let cid = self.checked.is_enabled().then(|| tx_cid(&msg));
+
+        // Messages being rechecked have been decoded when they were first checked.
+        let cached = match cid {
+            Some(ref cid) if is_recheck => self.checked.get(cid),
+            _ => None,
+        };
+
+        let decoded = match cached {
+            Some(msg) => Ok(msg),
+            None => fvm_ipld_encoding::from_slice::<ChainMessage>(&msg),
+        };
+
+        match decoded {
             Err(e) =>
             // The user sent us an invalid message, all we can do is discard it and block the source.
             {
                 Ok((state, Err(e)))
             }
             Ok(msg) => {
-                let (state, ret) = self.inner.check(state, msg, is_recheck).await?;
-                Ok((state, Ok(ret)))
+                let decoded = (cid.is_some() && !is_recheck).then(|| msg.clone());
+
+                let (state, ret) = match decompress(&mut state, msg)? {
+                    Ok(msg) => {
+                        let (state, ret) = self.inner.check(state, msg, is_recheck).await?;
+                        (state, Ok(ret))
+                    }
+                    Err(e) => (state, Err(e)),
+                };
+
+                // Only the messages which stay in the mempool are going to be rechecked.
+                if let Some(cid) = cid {
+                    if !is_passed(&ret) {
+                        self.checked.remove(&cid);
+                    } else if let Some(msg) = decoded {
+                        self.checked.insert(cid, msg);
+                    }
+                }
+
+                Ok((state, ret))
             }
         }
     }
 }
 
+/// Whether the message passed all the checks, and is kept in the mempool.
+fn is_passed(ret: &BytesMessageCheckRes) -> bool {
+    matches!(ret, Ok(Ok(Ok(ret))) if ret.exit_code.is_success())
+}
+
 #[async_trait]
 impl<I> QueryInterpreter for BytesMessageInterpreter<I>
 where
@@ -276,7 +499,7 @@ where
 }
 
 /// Parse the initial genesis either as JSON or CBOR.
-fn parse_genesis(bytes: &[u8]) -> anyhow::Result<Genesis> {
+pub fn parse_genesis(bytes: &[u8]) -> anyhow::Result<Genesis> {
     try_parse_genesis_json(bytes).or_else(|e1| {
         try_parse_genesis_cbor(bytes)
             .map_err(|e2| anyhow!("failed to deserialize genesis as JSON or CBOR: {e1}; {e2}"))
@@ -293,3 +516,161 @@ fn try_parse_genesis_cbor(bytes: &[u8]) -> anyhow::Result<Genesis> {
     let genesis = fvm_ipld_encoding::from_slice(bytes)?;
     Ok(genesis)
 }
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_genesis::Genesis;
+    use fendermint_vm_message::{chain::ChainMessage, signed::SignedMessage};
+    use fvm::engine::MultiEngine;
+    use fvm_shared::chainid::ChainID;
+    use fvm_shared::crypto::signature::Signature;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+
+    use super::{is_passed, ProposalPrepareMode};
+    use crate::cache::tx_cid;
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::{memory::MemoryBlockstore, ReadOnlyBlockstore};
+    use crate::fvm::testing::{self, interpreter};
+    use crate::fvm::FvmMessage;
+    use crate::stack::InterpreterBuilder;
+    use crate::CheckInterpreter;
+
+    /// Create the genesis state of a chain with accounts for the secret keys.
+    async fn genesis_state(
+        tx_compression: bool,
+        sks: &[SecretKey],
+    ) -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(Genesis {
+            accounts: sks
+                .iter()
+                .map(|sk| testing::account(sender(sk), TokenAmount::from_whole(1)))
+                .collect(),
+            tx_compression,
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    fn sender(sk: &SecretKey) -> Address {
+        Address::new_secp256k1(&sk.public_key().serialize()).unwrap()
+    }
+
+    fn message(from: Address, sequence: u64) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from,
+            to: Address::new_id(101),
+            sequence,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 1000000,
+            gas_fee_cap: TokenAmount::from_atto(2000),
+            gas_premium: TokenAmount::from_atto(1),
+        }
+    }
+
+    /// A signed transaction, encoded the way CometBFT hands it over.
+    fn signed_tx(sk: &SecretKey, chain_id: &ChainID, sequence: u64) -> Vec<u8> {
+        let msg = SignedMessage::new_secp256k1(message(sender(sk), sequence), sk, chain_id)
+            .expect("failed to sign");
+        fvm_ipld_encoding::to_vec(&ChainMessage::Signed(msg)).expect("failed to encode")
+    }
+
+    /// A compressed transaction; whether its signature is valid doesn't matter here.
+    fn compressed_tx() -> Vec<u8> {
+        let msg = SignedMessage::new_unchecked(
+            message(Address::new_id(100), 0),
+            Signature::new_secp256k1(vec![0; 65]),
+        );
+        let msg = ChainMessage::compress(&msg).expect("failed to compress");
+        fvm_ipld_encoding::to_vec(&msg).expect("failed to encode")
+    }
+
+    #[tokio::test]
+    async fn compressed_txs_are_only_accepted_if_enabled() {
+        let interpreter = InterpreterBuilder::new(interpreter())
+            .signed(0)
+            .chain()
+            .bytes(ProposalPrepareMode::AppendOnly, false, 0)
+            .build();
+
+        let multi_engine = MultiEngine::default();
+
+        for enabled in [false, true] {
+            let (store, params) = genesis_state(enabled, &[]).await;
+            let state = FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params)
+                .unwrap();
+
+            let (_, res) = interpreter
+                .check(state, compressed_tx(), false)
+                .await
+                .expect("check failed");
+
+            match res {
+                Ok(_) => assert!(enabled, "accepted a compressed transaction"),
+                Err(e) => {
+                    assert!(!enabled, "failed to decompress transaction: {e}");
+                    assert!(e.description.contains("not enabled"));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rechecks_only_reuse_passed_checks() {
+        let interpreter = InterpreterBuilder::new(interpreter())
+            .signed(0)
+            .chain()
+            .bytes(ProposalPrepareMode::AppendOnly, false, 10)
+            .build();
+
+        let multi_engine = MultiEngine::default();
+        let sk = SecretKey::try_from(vec![1u8; 32]).unwrap();
+
+        let (store, params) = genesis_state(false, std::slice::from_ref(&sk)).await;
+        let chain_id = ChainID::from(params.chain_id);
+        let state = || {
+            FvmExecState::new(
+                ReadOnlyBlockstore::new(store.clone()),
+                &multi_engine,
+                1,
+                params.clone(),
+            )
+            .unwrap()
+        };
+
+        let valid = signed_tx(&sk, &chain_id, 0);
+        let (_, res) = interpreter
+            .check(state(), valid.clone(), false)
+            .await
+            .unwrap();
+        assert!(is_passed(&res));
+        assert!(interpreter.checked.get(&tx_cid(&valid)).is_some());
+
+        // Failed checks aren't kept, the transaction is not going to be rechecked.
+        let invalid = signed_tx(&sk, &chain_id, 5);
+        let (_, res) = interpreter
+            .check(state(), invalid.clone(), false)
+            .await
+            .unwrap();
+        assert!(!is_passed(&res));
+        assert!(interpreter.checked.get(&tx_cid(&invalid)).is_none());
+
+        let (_, res) = interpreter
+            .check(state(), valid.clone(), true)
+            .await
+            .unwrap();
+        assert!(is_passed(&res));
+        assert!(interpreter.checked.get(&tx_cid(&valid)).is_some());
+
+        // Once a recheck fails, here because the sender doesn't exist, it's forgotten as well.
+        let (store, params) = genesis_state(false, &[]).await;
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params).unwrap();
+        let (_, res) = interpreter.check(state, valid.clone(), true).await.unwrap();
+        assert!(!is_passed(&res));
+        assert!(interpreter.checked.get(&tx_cid(&valid)).is_none());
+    }
+}
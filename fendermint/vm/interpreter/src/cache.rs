@@ -0,0 +1,139 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::IPLD_RAW;
+
+/// Content identifier of a raw transaction, to look up the results of earlier work on it.
+pub fn tx_cid(tx: &[u8]) -> Cid {
+    Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(tx))
+}
+
+/// A bounded cache shared between the different ABCI calls, so the work done on a
+/// transaction in one of them doesn't have to be repeated in a later one, e.g. the
+/// decoding in `ProcessProposal` and the signature check in `CheckTx` can be reused
+/// in `DeliverTx`.
+///
+/// Items are expected to be taken out when they are used; the ones which never are,
+/// for example because a proposal got rejected, are evicted in insertion order once
+/// the capacity is reached. A capacity of 0 disables the cache.
+pub struct MessageCache<K, V> {
+    capacity: usize,
+    inner: Arc<Mutex<Inner<K, V>>>,
+}
+
+struct Inner<K, V> {
+    items: HashMap<K, V>,
+    order: VecDeque<K>,
+}
+
+impl<K, V> Clone for MessageCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: self.capacity,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K, V> MessageCache<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Arc::new(Mutex::new(Inner {
+                items: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.items.insert(key.clone(), value).is_none() {
+            inner.order.push_back(key);
+        }
+
+        while inner.items.len() > self.capacity {
+            match inner.order.pop_front() {
+                Some(k) => {
+                    inner.items.remove(&k);
+                }
+                None => break,
+            }
+        }
+
+        // Keys taken out by `remove` are left in the queue; clean them up once in a while.
+        if inner.order.len() > 2 * self.capacity {
+            let Inner { items, order } = &mut *inner;
+            order.retain(|k| items.contains_key(k));
+        }
+    }
+
+    /// Take an item out of the cache.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.inner.lock().unwrap().items.remove(key)
+    }
+
+    /// Get a copy of an item, leaving it in the cache.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.inner.lock().unwrap().items.get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageCache;
+
+    #[test]
+    fn evicts_oldest() {
+        let cache = MessageCache::new(2);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some("b"));
+        assert_eq!(cache.remove(&3), Some("c"));
+        assert_eq!(cache.remove(&3), None);
+
+        // Removed keys don't count towards the capacity.
+        for i in 4..100 {
+            cache.insert(i, "x");
+            cache.remove(&i);
+        }
+        assert_eq!(cache.get(&2), Some("b"));
+        assert!(cache.inner.lock().unwrap().order.len() <= 4);
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = MessageCache::new(0);
+        cache.insert(1, "a");
+        assert_eq!(cache.get(&1), None);
+    }
+}
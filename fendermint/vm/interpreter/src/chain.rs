@@ -1,26 +1,34 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 use crate::fvm::state::ipc::GatewayCaller;
-use crate::fvm::{topdown, FvmApplyRet};
+use crate::fvm::{governance, topdown, FvmApplyRet};
 use crate::{
+    bytes::{HasExecDigests, HasTxCompression},
     fvm::state::FvmExecState,
     fvm::FvmMessage,
-    signed::{SignedMessageApplyRes, SignedMessageCheckRes, SyntheticMessage, VerifiableMessage},
-    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, QueryInterpreter,
+    signed::{
+        BlockSignatureCheck, ProposalSignatureCheck, SignedMessageApplyRes, SignedMessageCheckRes,
+        SyntheticMessage, VerifiableMessage, VerifySignatures,
+    },
+    CheckInterpreter, ExecInterpreter, GenesisInterpreter, ProposalInterpreter, ProposalRejection,
+    ProposalVerdict, QueryInterpreter, VerifyBlockInterpreter,
 };
 use anyhow::{bail, Context};
 use async_stm::atomically;
 use async_trait::async_trait;
+use fendermint_vm_actor_interface::execdigests::TxReceipt;
 use fendermint_vm_actor_interface::ipc;
 use fendermint_vm_message::ipc::ParentFinality;
+use fendermint_vm_message::signed::SignedMessageError;
 use fendermint_vm_message::{
     chain::ChainMessage,
     ipc::{BottomUpCheckpoint, CertifiedMessage, IpcMessage, SignedRelayedMessage},
 };
 use fendermint_vm_resolver::pool::{ResolveKey, ResolvePool};
-use fendermint_vm_topdown::proxy::IPCProviderProxy;
+use fendermint_vm_topdown::proxy::DynParentQueryProxy;
 use fendermint_vm_topdown::{
-    CachedFinalityProvider, IPCParentFinality, ParentFinalityProvider, ParentViewProvider, Toggle,
+    BlockHeight, CachedFinalityProvider, IPCParentFinality, ParentFinalityProvider,
+    ParentViewProvider, Toggle,
 };
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::RawBytes;
@@ -28,10 +36,11 @@ use fvm_shared::clock::ChainEpoch;
 use fvm_shared::econ::TokenAmount;
 use num_traits::Zero;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// A resolution pool for bottom-up and top-down checkpoints.
 pub type CheckpointPool = ResolvePool<CheckpointPoolItem>;
-pub type TopDownFinalityProvider = Arc<Toggle<CachedFinalityProvider<IPCProviderProxy>>>;
+pub type TopDownFinalityProvider = Arc<Toggle<CachedFinalityProvider<DynParentQueryProxy>>>;
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub enum CheckpointPoolItem {
@@ -55,6 +64,26 @@ impl From<&CheckpointPoolItem> for ResolveKey {
 /// A user sent a transaction which they are not allowed to do.
 pub struct IllegalMessage;
 
+impl<S: HasTxCompression> HasTxCompression for (CheckpointPool, TopDownFinalityProvider, S) {
+    fn max_decompressed_size(&mut self) -> anyhow::Result<Option<usize>> {
+        self.2.max_decompressed_size()
+    }
+}
+
+impl<S: HasExecDigests> HasExecDigests for (CheckpointPool, TopDownFinalityProvider, S) {
+    fn records_exec_digests(&mut self) -> anyhow::Result<bool> {
+        self.2.records_exec_digests()
+    }
+
+    fn push_tx_receipt(&mut self, receipt: TxReceipt, events: Vec<Vec<u8>>) {
+        self.2.push_tx_receipt(receipt, events)
+    }
+
+    fn record_exec_digest(&mut self) -> anyhow::Result<()> {
+        self.2.record_exec_digest()
+    }
+}
+
 // For now this is the only option, later we can expand.
 pub enum ChainMessageApplyRet {
     Signed(SignedMessageApplyRes),
@@ -86,9 +115,16 @@ impl<I, DB> ChainMessageInterpreter<I, DB> {
 impl<I, DB> ProposalInterpreter for ChainMessageInterpreter<I, DB>
 where
     DB: Blockstore + Clone + 'static + Send + Sync,
-    I: Sync + Send,
+    I: VerifySignatures + Sync + Send,
 {
-    type State = (CheckpointPool, TopDownFinalityProvider);
+    /// The signatures in the proposals are only verified if there is a signature check,
+    /// and the nonces of the top-down messages if the nonce the next one has to have is known.
+    type State = (
+        CheckpointPool,
+        TopDownFinalityProvider,
+        Option<ProposalSignatureCheck>,
+        Option<u64>,
+    );
     type Message = ChainMessage;
 
     /// Check whether there are any "ready" messages in the IPLD resolution mempool which can be appended to the proposal.
@@ -97,7 +133,7 @@ where
     /// account the transactions which are part of top-down or bottom-up checkpoints, to stay within gas limits.
     async fn prepare(
         &self,
-        (pool, finality_provider): Self::State,
+        (pool, finality_provider, _, topdown_nonce): Self::State,
         mut msgs: Vec<Self::Message>,
     ) -> anyhow::Result<Vec<Self::Message>> {
         // Collect resolved CIDs ready to be proposed from the pool.
@@ -108,8 +144,35 @@ where
             CheckpointPoolItem::BottomUp(ckpt) => ChainMessage::Ipc(IpcMessage::BottomUpExec(ckpt)),
         });
 
-        // Prepare top down proposals
-        if let Some(proposal) = atomically(|| finality_provider.next_proposal()).await {
+        // Prepare top down proposals, unless the other validators would reject them.
+        let proposal = atomically(|| finality_provider.next_proposal()).await;
+        let proposal = match (proposal, topdown_nonce) {
+            (Some(proposal), Some(next_nonce)) => {
+                match find_topdown_nonce_gap(&finality_provider, next_nonce, proposal.height).await
+                {
+                    Ok(None) => Some(proposal),
+                    Ok(Some(nonce)) => {
+                        tracing::warn!(
+                            height = proposal.height,
+                            nonce,
+                            next_nonce,
+                            "not proposing parent finality with a gap in the top-down nonces"
+                        );
+                        None
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            height = proposal.height,
+                            error = format!("{e:#}"),
+                            "failed to check the top-down nonces of the proposal"
+                        );
+                        None
+                    }
+                }
+            }
+            (proposal, _) => proposal,
+        };
+        if let Some(proposal) = proposal {
             msgs.push(ChainMessage::Ipc(IpcMessage::TopDownExec(ParentFinality {
                 height: proposal.height as ChainEpoch,
                 block_hash: proposal.block_hash,
@@ -122,13 +185,44 @@ where
     }
 
     /// Perform finality checks on top-down transactions and availability checks on bottom-up transactions.
+    ///
+    /// With a signature check, the signatures of the user transactions and relayed checkpoints are
+    /// verified as well, so a block with a badly signed message is rejected instead of executed.
     async fn process(
         &self,
-        (pool, finality_provider): Self::State,
+        (pool, finality_provider, signature_check, topdown_nonce): Self::State,
         msgs: Vec<Self::Message>,
-    ) -> anyhow::Result<bool> {
-        for msg in msgs {
+    ) -> anyhow::Result<ProposalVerdict> {
+        let mut signatures = signature_check.map(SignatureBudget::new);
+
+        for (index, msg) in msgs.into_iter().enumerate() {
             match msg {
+                ChainMessage::Signed(msg) => {
+                    if let Some(ref mut signatures) = signatures {
+                        let msg = VerifiableMessage::Signed(msg);
+                        if let Some(r) = signatures.verify(&self.inner, index, &msg) {
+                            return Ok(ProposalVerdict::Reject(r));
+                        }
+                    }
+                }
+                ChainMessage::Ipc(IpcMessage::BottomUpResolve(msg)) => {
+                    if let Some(ref mut signatures) = signatures {
+                        let msg = match relayed_bottom_up_ckpt_to_fvm(&msg) {
+                            Ok(msg) => VerifiableMessage::Synthetic(msg),
+                            Err(e) => {
+                                return Ok(ProposalVerdict::Reject(
+                                    ProposalRejection::MalformedTx {
+                                        index,
+                                        error: format!("{e:#}"),
+                                    },
+                                ))
+                            }
+                        };
+                        if let Some(r) = signatures.verify(&self.inner, index, &msg) {
+                            return Ok(ProposalVerdict::Reject(r));
+                        }
+                    }
+                }
                 ChainMessage::Ipc(IpcMessage::BottomUpExec(msg)) => {
                     let item = CheckpointPoolItem::BottomUp(msg);
 
@@ -137,14 +231,23 @@ where
                     // We don't have to validate the checkpoint here, because
                     // 1) we validated it when it was relayed, and
                     // 2) if a validator proposes something invalid, we can make them pay during execution.
+                    //
+                    // If the content isn't available, the block is rejected, because we couldn't execute it.
+                    // The proposer has the content though, so ask our own subnet for it as well,
+                    // which also brings back items which expired from our pool before we could resolve them.
                     let is_resolved = atomically(|| match pool.get_status(&item)? {
-                        None => Ok(false),
-                        Some(status) => status.is_resolved(),
+                        Some(status) if status.is_resolved()? => Ok(true),
+                        _ => {
+                            pool.add(item.clone(), true)?;
+                            Ok(false)
+                        }
                     })
                     .await;
 
                     if !is_resolved {
-                        return Ok(false);
+                        return Ok(ProposalVerdict::Reject(
+                            ProposalRejection::UnresolvedCheckpoint { index },
+                        ));
                     }
                 }
                 ChainMessage::Ipc(IpcMessage::TopDownExec(ParentFinality {
@@ -157,13 +260,136 @@ where
                     };
                     let is_final = atomically(|| finality_provider.check_proposal(&prop)).await;
                     if !is_final {
-                        return Ok(false);
+                        return Ok(ProposalVerdict::Reject(
+                            ProposalRejection::InvalidFinality {
+                                index,
+                                height: prop.height,
+                            },
+                        ));
+                    }
+                    // A gap would leave the messages after it undelivered, so vote against it.
+                    // If the messages can't be fetched, the execution would fail just the same.
+                    if let Some(next_nonce) = topdown_nonce {
+                        let rejection = match find_topdown_nonce_gap(
+                            &finality_provider,
+                            next_nonce,
+                            prop.height,
+                        )
+                        .await
+                        {
+                            Ok(None) => None,
+                            Ok(Some(nonce)) => {
+                                Some(ProposalRejection::TopDownNonceGap { index, nonce })
+                            }
+                            Err(e) => {
+                                tracing::warn!(
+                                    height = prop.height,
+                                    error = format!("{e:#}"),
+                                    "failed to fetch the top-down messages of the proposal"
+                                );
+                                Some(ProposalRejection::InvalidFinality {
+                                    index,
+                                    height: prop.height,
+                                })
+                            }
+                        };
+                        if let Some(r) = rejection {
+                            return Ok(ProposalVerdict::Reject(r));
+                        }
                     }
                 }
                 _ => {}
             };
         }
-        Ok(true)
+        Ok(ProposalVerdict::Accept)
+    }
+}
+
+impl<I, DB> VerifyBlockInterpreter for ChainMessageInterpreter<I, DB>
+where
+    DB: Blockstore + Clone + 'static + Send + Sync,
+    I: VerifySignatures + Sync + Send,
+{
+    type Message = ChainMessage;
+
+    /// Only the user transactions are verified ahead; the relayed checkpoints are few and far between.
+    fn verify_block(&self, check: &BlockSignatureCheck, msgs: &[Self::Message]) -> usize {
+        let msgs = msgs
+            .iter()
+            .filter_map(|msg| match msg {
+                ChainMessage::Signed(msg) => Some(VerifiableMessage::Signed(msg.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        self.inner.verify_many(&msgs, check)
+    }
+}
+
+/// Find the first nonce which would skip top-down messages if the parent finality at `height`
+/// were committed, given the nonce the next message has to have; the messages are the ones
+/// the execution would fetch, i.e. from the last committed finality up to the one before it.
+async fn find_topdown_nonce_gap(
+    finality_provider: &TopDownFinalityProvider,
+    next_nonce: u64,
+    height: BlockHeight,
+) -> anyhow::Result<Option<u64>> {
+    let from = match atomically(|| finality_provider.last_committed_finality()).await {
+        Some(finality) => finality.height,
+        None => finality_provider.genesis_epoch()?,
+    };
+    if height <= from {
+        return Ok(None);
+    }
+    let msgs = finality_provider
+        .top_down_msgs_from(from, height - 1)
+        .await?;
+    Ok(topdown::find_nonce_gap(next_nonce, &msgs))
+}
+
+/// Verifies the signatures in a proposal until the time allowed for it runs out.
+///
+/// The rest are verified during delivery, so a slow validator may vote for a proposal which a
+/// faster one rejects; that only costs a round, and no invalid message gets executed either way.
+struct SignatureBudget {
+    check: ProposalSignatureCheck,
+    deadline: Instant,
+    exhausted: bool,
+}
+
+impl SignatureBudget {
+    fn new(check: ProposalSignatureCheck) -> Self {
+        Self {
+            deadline: Instant::now() + check.budget,
+            check,
+            exhausted: false,
+        }
+    }
+
+    /// Verify the signature of a message, returning the reason to reject the proposal if it's invalid.
+    fn verify<V: VerifySignatures>(
+        &mut self,
+        verifier: &V,
+        index: usize,
+        msg: &VerifiableMessage,
+    ) -> Option<ProposalRejection> {
+        if self.exhausted {
+            return None;
+        }
+        if Instant::now() > self.deadline {
+            tracing::warn!(
+                index,
+                "ran out of time verifying the signatures in the proposal; leaving the rest to delivery"
+            );
+            self.exhausted = true;
+            return None;
+        }
+        let error = match verifier.verify_ahead(msg, &self.check) {
+            Ok(()) => return None,
+            Err(SignedMessageError::Ethereum(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        Some(ProposalRejection::InvalidSignature { index, error })
     }
 }
 
@@ -240,6 +466,17 @@ where
                         bail!("cannot execute IPC top-down message: parent provider disabled");
                     }
 
+                    // Nothing comes into the subnet once it's shutting down; the finality isn't
+                    // committed either, so the parent's view stays where the subnet stopped.
+                    if let Some(reason) = governance::check_shutdown(&mut state)? {
+                        tracing::debug!(
+                            height = p.height,
+                            "skipping top-down proposal during shutdown"
+                        );
+                        let ret = topdown::rejected(&self.gateway_caller, reason);
+                        return Ok(((pool, provider, state), ChainMessageApplyRet::Ipc(ret)));
+                    }
+
                     // commit parent finality first
                     let finality = IPCParentFinality::new(p.height, p.block_hash);
                     tracing::debug!(
@@ -299,6 +536,9 @@ where
                         "chain interpreter received topdown msgs",
                     );
 
+                    let msgs = governance::limit_topdown_msgs(&mut state, msgs)
+                        .context("failed to apply the top-down batch limit")?;
+
                     let ret = topdown::execute_topdown_msgs(&self.gateway_caller, &mut state, msgs)
                         .await
                         .context("failed to execute top down messages")?;
@@ -317,6 +557,9 @@ where
                     Ok(((pool, provider, state), ChainMessageApplyRet::Ipc(ret)))
                 }
             },
+            ChainMessage::Compressed(_) => {
+                bail!("compressed messages should have been unwrapped before delivery")
+            }
         }
     }
 
@@ -324,6 +567,21 @@ where
         &self,
         (pool, provider, state): Self::State,
     ) -> anyhow::Result<(Self::State, Self::BeginOutput)> {
+        // Drop the checkpoints which couldn't be resolved in time, and let the waiting ones take their place.
+        let maintenance = atomically(|| pool.maintain()).await;
+        if maintenance.expired > 0 {
+            tracing::warn!(
+                expired = maintenance.expired,
+                "dropped bottom-up checkpoints which couldn't be resolved in time"
+            );
+        }
+        if maintenance.released > 0 {
+            tracing::debug!(
+                released = maintenance.released,
+                "released bottom-up checkpoints for resolution"
+            );
+        }
+
         let (state, out) = self.inner.begin(state).await?;
         Ok(((pool, provider, state), out))
     }
@@ -382,6 +640,10 @@ where
                     }
                 }
             }
+            ChainMessage::Compressed(_) => {
+                // The envelope should have been removed by the caller, who can tell whether it's allowed.
+                Ok((state, Err(IllegalMessage)))
+            }
         }
     }
 }
@@ -453,3 +715,80 @@ fn relayed_bottom_up_ckpt_to_fvm(
 
     Ok(msg)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use fendermint_vm_message::signed::SignedMessageError;
+    use fvm_shared::{address::Address, chainid::ChainID, econ::TokenAmount};
+
+    use super::SignatureBudget;
+    use crate::fvm::FvmMessage;
+    use crate::signed::{
+        BlockSignatureCheck, ProposalSignatureCheck, VerifiableMessage, VerifySignatures,
+    };
+    use crate::ProposalRejection;
+
+    struct RejectAll;
+
+    impl VerifySignatures for RejectAll {
+        fn verify_ahead(
+            &self,
+            _msg: &VerifiableMessage,
+            _check: &ProposalSignatureCheck,
+        ) -> Result<(), SignedMessageError> {
+            Err(SignedMessageError::InvalidSignature("bad".to_owned()))
+        }
+
+        fn verify_many(&self, _msgs: &[VerifiableMessage], _check: &BlockSignatureCheck) -> usize {
+            0
+        }
+    }
+
+    fn message() -> VerifiableMessage {
+        VerifiableMessage::NotVerify(FvmMessage {
+            version: 0,
+            from: Address::new_id(100),
+            to: Address::new_id(200),
+            sequence: 0,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 1000,
+            gas_fee_cap: TokenAmount::from_atto(1),
+            gas_premium: TokenAmount::from_atto(1),
+        })
+    }
+
+    fn budget(budget: Duration) -> SignatureBudget {
+        SignatureBudget::new(ProposalSignatureCheck {
+            chain_id: ChainID::from(1),
+            filecoin_signatures: false,
+            budget,
+        })
+    }
+
+    #[test]
+    fn rejects_invalid_signature() {
+        let mut b = budget(Duration::from_secs(60));
+        let r = b.verify(&RejectAll, 3, &message());
+        assert_eq!(
+            r,
+            Some(ProposalRejection::InvalidSignature {
+                index: 3,
+                error: "invalid signature: bad".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn leaves_the_rest_to_delivery() {
+        let mut b = budget(Duration::from_secs(60));
+        b.deadline = Instant::now() - Duration::from_millis(1);
+        assert_eq!(b.verify(&RejectAll, 0, &message()), None);
+        assert!(b.exhausted);
+        b.deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(b.verify(&RejectAll, 1, &message()), None);
+    }
+}
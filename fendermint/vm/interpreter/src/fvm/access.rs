@@ -0,0 +1,283 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::multihash::Code;
+use fendermint_vm_actor_interface::{
+    accesscontrol::{self, SetAllowListParams, SetGovernorParams, ACCESS_CONTROL_ACTOR_ID},
+    eam,
+};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{error::ExitCode, METHOD_SEND};
+
+use super::{
+    state::{ExecResult, FvmExecState},
+    FvmMessage,
+};
+
+/// Load the access control state, if the chain was started with one.
+///
+/// Returns `None` if the chain is permissionless.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<accesscontrol::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(ACCESS_CONTROL_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let acl = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("access control state not found"))?;
+            Ok(Some(acl))
+        }
+    }
+}
+
+/// Check whether the message is allowed by the access control lists.
+///
+/// Returns the reason for rejection if it is not.
+pub fn check_access(acl: &accesscontrol::State, msg: &FvmMessage) -> Option<String> {
+    if !acl.can_send(&msg.from) {
+        return Some(format!(
+            "sender {} is not allowed to send transactions",
+            msg.from
+        ));
+    }
+    if is_deployment(msg) && !acl.can_deploy(&msg.from) {
+        return Some(format!(
+            "sender {} is not allowed to deploy contracts",
+            msg.from
+        ));
+    }
+    if is_update(msg) && msg.from != acl.governor {
+        return Some(format!(
+            "sender {} is not allowed to update access control",
+            msg.from
+        ));
+    }
+    None
+}
+
+/// Execute an explicit message, subject to access control.
+///
+/// Messages not passing the checks, or carrying invalid updates, are rejected
+/// but still charged for gas, with their nonce incremented, like failed messages.
+/// Updates sent by the governor are applied to the access control state,
+/// while the message itself is executed as a simple send, so that the
+/// governor is charged for gas and its nonce is incremented.
+pub fn execute_explicit<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    let acl = match get_state(state)? {
+        None => return state.execute_explicit(msg),
+        Some(acl) => acl,
+    };
+
+    if let Some(reason) = check_access(&acl, &msg) {
+        return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+    }
+
+    if !is_update(&msg) {
+        return state.execute_explicit(msg);
+    }
+
+    let acl = match apply_update(acl, &msg) {
+        Ok(acl) => acl,
+        Err(e) => {
+            let reason = format!("invalid access control update: {e:#}");
+            return state.execute_rejected(msg, ExitCode::USR_ILLEGAL_ARGUMENT, reason);
+        }
+    };
+
+    let send = FvmMessage {
+        method_num: METHOD_SEND,
+        params: RawBytes::default(),
+        ..msg
+    };
+
+    let (apply_ret, emitters) = state.execute_explicit(send)?;
+
+    if apply_ret.msg_receipt.exit_code.is_success() {
+        set_state(state, &acl).context("failed to update access control state")?;
+    }
+
+    Ok((apply_ret, emitters))
+}
+
+/// Contracts are deployed by calling one of the creation methods of the EAM.
+fn is_deployment(msg: &FvmMessage) -> bool {
+    msg.to == eam::EAM_ACTOR_ADDR
+        && (msg.method_num == eam::Method::Create as u64
+            || msg.method_num == eam::Method::Create2 as u64
+            || msg.method_num == eam::Method::CreateExternal as u64)
+}
+
+/// Anything other than a simple send to the access control actor is an update.
+fn is_update(msg: &FvmMessage) -> bool {
+    msg.to == accesscontrol::ACCESS_CONTROL_ACTOR_ADDR && msg.method_num != METHOD_SEND
+}
+
+fn apply_update(
+    mut acl: accesscontrol::State,
+    msg: &FvmMessage,
+) -> anyhow::Result<accesscontrol::State> {
+    use accesscontrol::Method;
+    match msg.method_num {
+        m if m == Method::SetGovernor as u64 => {
+            let params: SetGovernorParams = msg.params.deserialize()?;
+            acl.governor = params.governor;
+        }
+        m if m == Method::SetDeployers as u64 => {
+            let params: SetAllowListParams = msg.params.deserialize()?;
+            acl.deployers = params.addresses;
+        }
+        m if m == Method::SetSenders as u64 => {
+            let params: SetAllowListParams = msg.params.deserialize()?;
+            acl.senders = params.addresses;
+        }
+        m => return Err(anyhow!("unknown method: {m}")),
+    }
+    Ok(acl)
+}
+
+fn set_state<DB>(state: &mut FvmExecState<DB>, acl: &accesscontrol::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let mut actor = state_tree
+        .get_actor(ACCESS_CONTROL_ACTOR_ID)?
+        .ok_or_else(|| anyhow!("access control actor not found"))?;
+    actor.state = state_tree.store().put_cbor(acl, Code::Blake2b256)?;
+    state_tree.set_actor(ACCESS_CONTROL_ACTOR_ID, actor);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_actor_interface::{accesscontrol, burntfunds::BURNT_FUNDS_ACTOR_ADDR, eam};
+    use fendermint_vm_genesis::{AccessControl, Genesis, SignerAddr};
+    use fvm::engine::MultiEngine;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::{address::Address, METHOD_SEND};
+
+    use super::{check_access, execute_explicit};
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::{testing, FvmMessage};
+
+    /// Create the genesis state of a chain where only the governor can send transactions,
+    /// with an account for the sender as well.
+    async fn genesis_state(
+        governor: Address,
+        sender: Address,
+    ) -> (MemoryBlockstore, FvmStateParams) {
+        let balance = TokenAmount::from_whole(1);
+        testing::genesis_state(Genesis {
+            accounts: vec![
+                testing::account(governor, balance.clone()),
+                testing::account(sender, balance),
+            ],
+            access_control: Some(AccessControl {
+                governor: SignerAddr(governor),
+                deployers: None,
+                senders: Some(Vec::new()),
+            }),
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    fn message(from: Address, to: Address, method_num: u64) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from,
+            to,
+            sequence: 0,
+            value: Default::default(),
+            method_num,
+            params: Default::default(),
+            gas_limit: 0,
+            gas_fee_cap: Default::default(),
+            gas_premium: Default::default(),
+        }
+    }
+
+    #[test]
+    fn access_control_lists() {
+        let governor = Address::new_id(1000);
+        let alice = Address::new_id(1001);
+        let bob = Address::new_id(1002);
+        let other = Address::new_id(2000);
+
+        let acl = accesscontrol::State {
+            governor,
+            deployers: Some(vec![alice]),
+            senders: Some(vec![alice, bob]),
+        };
+
+        let deploy = eam::Method::CreateExternal as u64;
+        let update = accesscontrol::Method::SetSenders as u64;
+        let acl_addr = accesscontrol::ACCESS_CONTROL_ACTOR_ADDR;
+
+        assert!(check_access(&acl, &message(alice, other, METHOD_SEND)).is_none());
+        assert!(check_access(&acl, &message(bob, other, METHOD_SEND)).is_none());
+        assert!(check_access(&acl, &message(other, bob, METHOD_SEND)).is_some());
+
+        assert!(check_access(&acl, &message(alice, eam::EAM_ACTOR_ADDR, deploy)).is_none());
+        assert!(check_access(&acl, &message(bob, eam::EAM_ACTOR_ADDR, deploy)).is_some());
+        assert!(check_access(&acl, &message(governor, eam::EAM_ACTOR_ADDR, deploy)).is_none());
+
+        assert!(check_access(&acl, &message(governor, acl_addr, update)).is_none());
+        assert!(check_access(&acl, &message(alice, acl_addr, update)).is_some());
+        assert!(check_access(&acl, &message(alice, acl_addr, METHOD_SEND)).is_none());
+
+        let acl = accesscontrol::State {
+            governor,
+            deployers: None,
+            senders: None,
+        };
+
+        assert!(check_access(&acl, &message(other, eam::EAM_ACTOR_ADDR, deploy)).is_none());
+    }
+
+    #[tokio::test]
+    async fn rejected_messages_are_charged() {
+        let addr = |seed: u8| {
+            let sk = SecretKey::try_from(vec![seed; 32]).unwrap();
+            Address::new_secp256k1(&sk.public_key().serialize()).unwrap()
+        };
+        let (governor, sender) = (addr(1), addr(2));
+
+        let (store, params) = genesis_state(governor, sender).await;
+        let multi_engine = MultiEngine::default();
+        let mut state =
+            FvmExecState::new(store, &multi_engine, 1, params).expect("failed to create state");
+
+        let msg = |method_num| FvmMessage {
+            sequence: 0,
+            gas_limit: 10_000_000,
+            gas_fee_cap: TokenAmount::from_atto(10_000),
+            ..message(sender, BURNT_FUNDS_ACTOR_ADDR, method_num)
+        };
+
+        let (ret, _) = execute_explicit(&mut state, msg(METHOD_SEND)).unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::USR_FORBIDDEN);
+        assert!(ret.msg_receipt.gas_used > 0);
+
+        let state_tree = state.state_tree_mut();
+        let id = state_tree
+            .lookup_id(&sender)
+            .unwrap()
+            .expect("sender exists");
+        let actor = state_tree.get_actor(id).unwrap().expect("sender exists");
+        assert_eq!(actor.sequence, 1);
+        assert!(actor.balance < TokenAmount::from_whole(1));
+    }
+}
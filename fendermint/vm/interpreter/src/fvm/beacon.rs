@@ -0,0 +1,512 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context};
+use cid::multihash::{Code, MultihashDigest};
+use fendermint_crypto::SecretKey;
+use fendermint_vm_actor_interface::beacon::{
+    self, BeaconEntry, CommitParams, Phase, RevealParams, BEACON_ACTOR_ADDR, BEACON_ACTOR_ID,
+};
+use fendermint_vm_actor_interface::validators::{self, VALIDATORS_ACTOR_ID};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{
+    address::Address, chainid::ChainID, error::ExitCode, event::StampedEvent, MethodNum,
+    METHOD_SEND,
+};
+
+use super::{
+    access, events,
+    state::{ExecResult, FvmExecState},
+    FvmMessage,
+};
+
+/// Gas limit of the commit and reveal messages, which are executed as simple sends.
+pub const BEACON_GAS_LIMIT: u64 = 10_000_000;
+
+/// Load the beacon state, if the chain was started with one.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<beacon::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(BEACON_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let state = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("beacon state not found"))?;
+            Ok(Some(state))
+        }
+    }
+}
+
+/// Anything other than a simple send to the beacon actor is a commitment or a reveal.
+pub fn is_beacon(msg: &FvmMessage) -> bool {
+    msg.to == BEACON_ACTOR_ADDR && msg.method_num != METHOD_SEND
+}
+
+/// Check whether the sender is allowed to contribute to the beacon.
+///
+/// Returns the reason for rejection if it is not.
+pub fn check_validator<DB>(
+    state: &mut FvmExecState<DB>,
+    msg: &FvmMessage,
+) -> anyhow::Result<Option<String>>
+where
+    DB: Blockstore + 'static,
+{
+    if is_active_validator(state, &msg.from)? {
+        Ok(None)
+    } else {
+        Ok(Some(format!(
+            "sender {} is not an active validator",
+            msg.from
+        )))
+    }
+}
+
+fn is_active_validator<DB>(state: &mut FvmExecState<DB>, addr: &Address) -> anyhow::Result<bool>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let book: validators::State = match state_tree.get_actor(VALIDATORS_ACTOR_ID)? {
+        None => return Ok(false),
+        Some(actor) => state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("validator address book state not found"))?,
+    };
+    Ok(book.is_active(addr))
+}
+
+/// Close the round at the end of an epoch, producing the next beacon value,
+/// which the messages of this block can already draw on.
+///
+/// Returns the event to emit about the new value, if there is one.
+pub fn begin_block<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<StampedEvent>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut beacon = match get_state(state)? {
+        None => return Ok(Vec::new()),
+        Some(beacon) => beacon,
+    };
+
+    let height = state.block_height();
+    let round = beacon.round.epoch;
+
+    if beacon.epoch(height) <= round {
+        return Ok(Vec::new());
+    }
+
+    let withheld = beacon
+        .round
+        .contributions
+        .iter()
+        .filter(|c| c.secret.is_empty())
+        .map(|c| c.sender.to_string())
+        .collect::<Vec<_>>();
+
+    if !withheld.is_empty() {
+        tracing::warn!(
+            height,
+            epoch = round,
+            withheld = withheld.join(","),
+            "validators withheld their beacon secrets; suspending them"
+        );
+    }
+
+    let mut events = Vec::new();
+    match beacon.finalize(height) {
+        Some(entry) => {
+            tracing::info!(
+                height,
+                epoch = entry.epoch,
+                contributors = entry.contributors,
+                value = hex::encode(&entry.value),
+                "new beacon value"
+            );
+            events.push(event(entry));
+        }
+        None => {
+            tracing::warn!(
+                height,
+                epoch = round,
+                "no beacon contributions were revealed; keeping the previous value"
+            );
+        }
+    }
+    set_state(state, &beacon).context("failed to update beacon state")?;
+    state.externs().set_beacon(beacon);
+
+    Ok(events)
+}
+
+/// Handle a commitment or a reveal sent by a validator.
+///
+/// The message itself is executed as a simple send, so that the validator is charged
+/// for gas and its nonce is incremented, whether the contribution is accepted or not.
+pub fn execute<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    let mut beacon = match get_state(state)? {
+        None => return access::execute_explicit(state, msg),
+        Some(beacon) => beacon,
+    };
+
+    if let Some(acl) = access::get_state(state)? {
+        if let Some(reason) = access::check_access(&acl, &msg) {
+            return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+        }
+    }
+
+    if let Some(reason) = check_validator(state, &msg)? {
+        return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+    }
+
+    if let Err(e) = apply(&mut beacon, state.block_height(), &msg) {
+        return state.execute_rejected(
+            msg,
+            ExitCode::USR_ILLEGAL_ARGUMENT,
+            format!("invalid beacon message: {e}"),
+        );
+    }
+
+    let send = FvmMessage {
+        method_num: METHOD_SEND,
+        params: RawBytes::default(),
+        ..msg
+    };
+
+    let (apply_ret, emitters) = state.execute_explicit(send)?;
+
+    if apply_ret.msg_receipt.exit_code.is_success() {
+        set_state(state, &beacon).context("failed to update beacon state")?;
+    }
+
+    Ok((apply_ret, emitters))
+}
+
+fn apply(beacon: &mut beacon::State, height: i64, msg: &FvmMessage) -> Result<(), String> {
+    use beacon::Method;
+    match msg.method_num {
+        m if m == Method::Commit as u64 => {
+            let params: CommitParams = msg.params.deserialize().map_err(|e| e.to_string())?;
+            beacon.commit(height, msg.from, params)
+        }
+        m if m == Method::Reveal as u64 => {
+            let params: RevealParams = msg.params.deserialize().map_err(|e| e.to_string())?;
+            beacon.reveal(height, msg.from, params)
+        }
+        m => Err(format!("unknown method: {m}")),
+    }
+}
+
+/// The phase this validator last sent a message in, so it's only sent once.
+#[derive(Clone, Default)]
+pub struct BeaconProgress(Arc<Mutex<Option<(u64, Phase)>>>);
+
+/// The message a validator should send to the beacon next, if any, as a method and its parameters.
+///
+/// The secret is derived from the validator key, the chain and the epoch, so it doesn't have to be
+/// remembered across restarts, and the validator cannot try different ones to bias the outcome.
+/// The phase is looked up for the next block, which is the earliest the message can be included in.
+pub fn next_contribution<DB>(
+    state: &mut FvmExecState<DB>,
+    progress: &BeaconProgress,
+    secret_key: &SecretKey,
+    sender: &Address,
+) -> anyhow::Result<Option<(MethodNum, RawBytes)>>
+where
+    DB: Blockstore + 'static,
+{
+    let beacon = match get_state(state)? {
+        None => return Ok(None),
+        Some(beacon) => beacon,
+    };
+
+    if !is_active_validator(state, sender)? {
+        return Ok(None);
+    }
+
+    let (epoch, phase) = beacon.phase(state.block_height() + 1);
+
+    // Any commitment would be rejected, after paying for it.
+    if beacon.suspension(sender, epoch).is_some() {
+        return Ok(None);
+    }
+
+    let mut last = progress.0.lock().unwrap();
+    if *last == Some((epoch, phase)) {
+        return Ok(None);
+    }
+
+    let contribution = if beacon.round.epoch == epoch {
+        beacon
+            .round
+            .contributions
+            .iter()
+            .find(|c| c.sender == *sender)
+    } else {
+        None
+    };
+
+    let secret = derive_secret(secret_key, &state.chain_id(), epoch);
+
+    let msg = match (phase, contribution) {
+        (Phase::Commit, None) => {
+            let commitment = beacon::commitment(&secret, epoch, sender);
+            let params = RawBytes::serialize(CommitParams { epoch, commitment })?;
+            (beacon::Method::Commit as u64, params)
+        }
+        (Phase::Reveal, Some(c)) if c.secret.is_empty() => {
+            let params = RawBytes::serialize(RevealParams { epoch, secret })?;
+            (beacon::Method::Reveal as u64, params)
+        }
+        _ => return Ok(None),
+    };
+
+    *last = Some((epoch, phase));
+
+    Ok(Some(msg))
+}
+
+fn derive_secret(secret_key: &SecretKey, chain_id: &ChainID, epoch: u64) -> Vec<u8> {
+    let mut data = b"fendermint-beacon".to_vec();
+    data.extend_from_slice(secret_key.serialize().as_ref());
+    data.extend_from_slice(&u64::from(*chain_id).to_be_bytes());
+    data.extend_from_slice(&epoch.to_be_bytes());
+    Code::Blake2b256.digest(&data).digest().to_vec()
+}
+
+/// An event emitted by the beacon actor about a new value.
+fn event(entry: &BeaconEntry) -> StampedEvent {
+    events::event(
+        BEACON_ACTOR_ID,
+        vec![
+            events::entry("beacon", entry.epoch.to_be_bytes()),
+            events::entry("value", entry.value.clone()),
+            events::entry("contributors", entry.contributors.to_be_bytes()),
+        ],
+    )
+}
+
+fn set_state<DB>(state: &mut FvmExecState<DB>, beacon: &beacon::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let mut actor = state_tree
+        .get_actor(BEACON_ACTOR_ID)?
+        .ok_or_else(|| anyhow!("beacon actor not found"))?;
+    actor.state = state_tree.store().put_cbor(beacon, Code::Blake2b256)?;
+    state_tree.set_actor(BEACON_ACTOR_ID, actor);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_actor_interface::beacon::{
+        self, CommitParams, RevealParams, BEACON_ACTOR_ADDR,
+    };
+    use fendermint_vm_genesis::{Beacon, Collateral, Genesis, Validator, ValidatorKey};
+    use fvm::engine::MultiEngine;
+    use fvm::externs::Rand;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{
+        begin_block, execute, get_state, next_contribution, BeaconProgress, BEACON_GAS_LIMIT,
+    };
+    use crate::fvm::externs::FendermintExterns;
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::{testing, FvmMessage};
+
+    fn keys(n: u8) -> Vec<SecretKey> {
+        (1..=n)
+            .map(|i| SecretKey::try_from(vec![i; 32]).unwrap())
+            .collect()
+    }
+
+    fn addr(sk: &SecretKey) -> Address {
+        Address::new_secp256k1(&sk.public_key().serialize()).unwrap()
+    }
+
+    /// Create the genesis state of a chain with a beacon and an account for every key,
+    /// where the first few keys are validators.
+    async fn genesis_state(
+        sks: &[SecretKey],
+        validators: usize,
+    ) -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(Genesis {
+            validators: sks[..validators]
+                .iter()
+                .map(|sk| Validator {
+                    public_key: ValidatorKey::new(sk.public_key()),
+                    power: Collateral(TokenAmount::from_whole(1)),
+                })
+                .collect(),
+            accounts: sks
+                .iter()
+                .map(|sk| testing::account(addr(sk), TokenAmount::from_whole(1)))
+                .collect(),
+            beacon: Some(Beacon { epoch_length: 10 }),
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    /// Commit the state and start a block at a later height.
+    fn next_block(
+        state: FvmExecState<MemoryBlockstore>,
+        store: &MemoryBlockstore,
+        multi_engine: &MultiEngine,
+        params: &mut FvmStateParams,
+        height: i64,
+    ) -> FvmExecState<MemoryBlockstore> {
+        let (state_root, _, _) = state.commit().expect("failed to commit");
+        params.state_root = state_root;
+        FvmExecState::new(store.clone(), multi_engine, height, params.clone())
+            .expect("failed to create state")
+    }
+
+    fn contribution(
+        sk: &SecretKey,
+        sequence: u64,
+        method: beacon::Method,
+        params: RawBytes,
+    ) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from: addr(sk),
+            to: BEACON_ACTOR_ADDR,
+            sequence,
+            value: TokenAmount::default(),
+            method_num: method as u64,
+            params,
+            gas_limit: BEACON_GAS_LIMIT,
+            gas_fee_cap: TokenAmount::from_atto(10_000),
+            gas_premium: TokenAmount::default(),
+        }
+    }
+
+    fn commit(sk: &SecretKey, sequence: u64, epoch: u64, secret: &[u8]) -> FvmMessage {
+        let commitment = beacon::commitment(secret, epoch, &addr(sk));
+        let params = RawBytes::serialize(CommitParams { epoch, commitment }).unwrap();
+        contribution(sk, sequence, beacon::Method::Commit, params)
+    }
+
+    fn reveal(sk: &SecretKey, sequence: u64, epoch: u64, secret: &[u8]) -> FvmMessage {
+        let secret = secret.to_vec();
+        let params = RawBytes::serialize(RevealParams { epoch, secret }).unwrap();
+        contribution(sk, sequence, beacon::Method::Reveal, params)
+    }
+
+    /// Execute a contribution, checking that the sender paid for it either way.
+    fn exit_code(state: &mut FvmExecState<MemoryBlockstore>, msg: FvmMessage) -> ExitCode {
+        let (ret, _) = execute(state, msg).expect("failed to execute");
+        assert!(ret.msg_receipt.gas_used > 0, "gas is charged");
+        ret.msg_receipt.exit_code
+    }
+
+    fn sequence(state: &mut FvmExecState<MemoryBlockstore>, sk: &SecretKey) -> u64 {
+        let id = state
+            .state_tree_mut()
+            .lookup_id(&addr(sk))
+            .unwrap()
+            .expect("account exists");
+        state
+            .state_tree_mut()
+            .get_actor(id)
+            .unwrap()
+            .expect("actor exists")
+            .sequence
+    }
+
+    #[tokio::test]
+    async fn rejected_contributions_are_charged_gas() {
+        let sks = keys(2);
+        let (store, params) = genesis_state(&sks, 1).await;
+        let multi_engine = MultiEngine::default();
+        let mut state = FvmExecState::new(store, &multi_engine, 1, params).unwrap();
+
+        // Not a validator.
+        let code = exit_code(&mut state, commit(&sks[1], 0, 0, &[1; 32]));
+        assert_eq!(code, ExitCode::USR_FORBIDDEN);
+
+        // Too early to reveal.
+        let code = exit_code(&mut state, reveal(&sks[0], 0, 0, &[1; 32]));
+        assert_eq!(code, ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        // The nonces moved on, so the rejected messages can't be included again.
+        assert_eq!(sequence(&mut state, &sks[0]), 1);
+        assert_eq!(sequence(&mut state, &sks[1]), 1);
+
+        let code = exit_code(&mut state, commit(&sks[0], 1, 0, &[1; 32]));
+        assert_eq!(code, ExitCode::OK);
+    }
+
+    #[tokio::test]
+    async fn new_values_are_drawn_on_in_their_block_and_withholders_are_suspended() {
+        let sks = keys(2);
+        let (store, mut params) = genesis_state(&sks, 2).await;
+        let multi_engine = MultiEngine::default();
+
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 1, params.clone()).unwrap();
+        assert_eq!(
+            exit_code(&mut state, commit(&sks[0], 0, 0, &[1; 32])),
+            ExitCode::OK
+        );
+        assert_eq!(
+            exit_code(&mut state, commit(&sks[1], 0, 0, &[2; 32])),
+            ExitCode::OK
+        );
+
+        // Only the first validator reveals its secret.
+        let mut state = next_block(state, &store, &multi_engine, &mut params, 5);
+        assert_eq!(
+            exit_code(&mut state, reveal(&sks[0], 1, 0, &[1; 32])),
+            ExitCode::OK
+        );
+
+        // Without a beacon value yet, the randomness falls back on the block hashes, which aren't recorded here.
+        let mut state = next_block(state, &store, &multi_engine, &mut params, 10);
+        assert!(state.externs().get_beacon_randomness(0, 10, &[]).is_err());
+
+        let events = begin_block(&mut state).unwrap();
+        assert_eq!(events.len(), 1);
+
+        // The messages of the block see the new value, the same as the next block will.
+        let state_root = state.flush().unwrap();
+        let externs = FendermintExterns::load(&store, state_root).unwrap();
+        assert_eq!(
+            state.externs().get_beacon_randomness(0, 10, &[]).unwrap(),
+            externs.get_beacon_randomness(0, 10, &[]).unwrap()
+        );
+
+        let beacon = get_state(&mut state).unwrap().expect("beacon exists");
+        assert_eq!(beacon.latest().unwrap().contributors, 1);
+        assert!(beacon.suspension(&addr(&sks[0]), 1).is_none());
+        assert!(beacon.suspension(&addr(&sks[1]), 1).is_some());
+
+        // The withholder can't commit in the next epoch, and doesn't try to.
+        let code = exit_code(&mut state, commit(&sks[1], 1, 1, &[3; 32]));
+        assert_eq!(code, ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let progress = BeaconProgress::default();
+        let next = next_contribution(&mut state, &progress, &sks[1], &addr(&sks[1])).unwrap();
+        assert!(next.is_none());
+        let next = next_contribution(&mut state, &progress, &sks[0], &addr(&sks[0])).unwrap();
+        assert!(next.is_some());
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::anyhow;
+use fendermint_vm_actor_interface::blocktime::{self, BLOCK_TIME_ACTOR_ID};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+
+use super::state::FvmExecState;
+
+impl<DB> FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    /// Largest number of seconds the time of a block can be ahead of the previous one,
+    /// or `None` if the chain was started without a limit.
+    pub fn max_block_interval(&mut self) -> anyhow::Result<Option<u64>> {
+        let state_tree = self.state_tree_mut();
+        match state_tree.get_actor(BLOCK_TIME_ACTOR_ID)? {
+            None => Ok(None),
+            Some(actor) => {
+                let st: blocktime::State = state_tree
+                    .store()
+                    .get_cbor(&actor.state)?
+                    .ok_or_else(|| anyhow!("block time state not found"))?;
+                Ok(Some(st.max_interval))
+            }
+        }
+    }
+}
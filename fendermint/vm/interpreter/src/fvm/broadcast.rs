@@ -8,7 +8,9 @@ use ethers::types as et;
 use fendermint_rpc::response::decode_fevm_return_data;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::error::ExitCode;
-use fvm_shared::{address::Address, chainid::ChainID, econ::TokenAmount, BLOCK_GAS_LIMIT};
+use fvm_shared::{
+    address::Address, chainid::ChainID, econ::TokenAmount, MethodNum, BLOCK_GAS_LIMIT,
+};
 use num_traits::Zero;
 use tendermint_rpc::Client;
 
@@ -19,6 +21,8 @@ use fendermint_rpc::tx::{CallClient, TxClient, TxSync};
 use fendermint_rpc::{client::FendermintClient, message::MessageFactory};
 use fendermint_vm_message::query::FvmQueryHeight;
 
+use super::outbox::{self, Outbox};
+
 macro_rules! retry {
     ($max_retries:expr, $retry_delay:expr, $block:expr) => {{
         let mut attempt = 0;
@@ -59,6 +63,8 @@ pub struct Broadcaster<C> {
     gas_overestimation_rate: f64,
     max_retries: u8,
     retry_delay: Duration,
+    /// Durable queue of transactions to keep broadcasting until they are included.
+    outbox: Option<Outbox>,
 }
 
 impl<C> Broadcaster<C>
@@ -84,6 +90,7 @@ where
             max_retries: 0,
             // Set the retry delay to rougly the block creation time.
             retry_delay: Duration::from_secs(1),
+            outbox: None,
         }
     }
 
@@ -97,10 +104,20 @@ where
         self
     }
 
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
     pub fn retry_delay(&self) -> Duration {
         self.retry_delay
     }
 
+    /// The address the transactions are sent from.
+    pub fn address(&self) -> &Address {
+        &self.addr
+    }
+
     /// Send a transaction to the chain and return is hash.
     ///
     /// It currently doesn't wait for the execution, only that it has successfully been added to the mempool,
@@ -116,79 +133,62 @@ where
     ) -> anyhow::Result<tendermint::hash::Hash> {
         let tx_hash = retry!(self.max_retries, self.retry_delay, {
             let sequence = self
-                .sequence()
+                .sequence(FvmQueryHeight::Pending)
+                .await
+                .context("failed to get broadcaster sequence")?;
+
+            self.try_invoke(contract, &calldata, chain_id, sequence)
+                .await?
+        });
+
+        Ok(tx_hash)
+    }
+
+    /// Send a message to a native actor with a fixed gas limit, and return its hash.
+    ///
+    /// Like [Self::fevm_invoke], it only waits for the transaction to be added to the mempool.
+    pub async fn transaction(
+        &self,
+        to: Address,
+        method_num: MethodNum,
+        params: RawBytes,
+        gas_limit: u64,
+        chain_id: ChainID,
+    ) -> anyhow::Result<tendermint::hash::Hash> {
+        let tx_hash = retry!(self.max_retries, self.retry_delay, {
+            let sequence = self
+                .sequence(FvmQueryHeight::Pending)
                 .await
                 .context("failed to get broadcaster sequence")?;
 
             let factory =
                 MessageFactory::new(self.secret_key.clone(), self.addr, sequence, chain_id);
-
-            // Using the bound client as a one-shot transaction sender.
             let mut client = self.client.clone().bind(factory);
 
-            // TODO: Maybe we should implement something like the Ethereum facade for estimating fees?
-            // I don't want to call the Ethereum API directly (it would be one more dependency).
-            // Another option is for Fendermint to recognise transactions coming from validators
-            // and always put them into the block to facilitate checkpointing.
-            let mut gas_params = GasParams {
-                gas_limit: BLOCK_GAS_LIMIT,
+            let gas_params = GasParams {
+                gas_limit,
                 gas_fee_cap: self.gas_fee_cap.clone(),
                 gas_premium: self.gas_premium.clone(),
             };
 
-            // Not expecting to send any tokens to the contracts.
-            let value = TokenAmount::zero();
-
-            // We can use the `Committed` state to execute the message, which is more efficient than doing it on `Pending`.
-            let gas_estimate = client
-                .fevm_estimate_gas(
-                    contract,
-                    calldata.0.clone(),
-                    value.clone(),
-                    gas_params.clone(),
-                    FvmQueryHeight::Committed,
-                )
-                .await
-                .context("failed to estimate gas")?;
-
-            if gas_estimate.value.exit_code.is_success() {
-                gas_params.gas_limit =
-                    (gas_estimate.value.gas_limit as f64 * self.gas_overestimation_rate) as u64;
-            } else {
-                bail!(
-                    "failed to estimate gas: {} - {}",
-                    gas_estimate.value.exit_code,
-                    gas_estimate.value.info
-                );
-            }
-
-            // Using TxSync instead of TxCommit because TxCommit times out if the `check_tx` part fails,
-            // instead of returning as soon as the check failed with some default values for `deliver_tx`.
-            let res = TxClient::<TxSync>::fevm_invoke(
+            let res = TxClient::<TxSync>::transaction(
                 &mut client,
-                contract,
-                calldata.0.clone(),
-                value,
+                to,
+                method_num,
+                params.clone(),
+                TokenAmount::zero(),
                 gas_params,
             )
             .await
-            .context("failed to invoke contract")?;
+            .context("failed to send transaction")?;
 
             if res.response.code.is_err() {
-                // Not sure what exactly arrives in the data and how it's encoded.
-                // It might need the Base64 decoding or it may not. Let's assume
-                // that it doesn't because unlike `DeliverTx::data`, this response
-                // does have some Base64 lreated annotations.
-                let data = decode_fevm_return_data(RawBytes::new(res.response.data.to_vec()))
-                    .map(hex::encode)
-                    .unwrap_or_else(|_| hex::encode(res.response.data));
-
                 Err((
                     res.response.code,
                     format!(
-                        "broadcasted transaction failed during check: {}; data = {}",
+                        "broadcasted transaction failed during check: {}; log = {}",
                         res.response.code.value(),
-                        data
+                        res.response.log
                     ),
                 ))
             } else {
@@ -199,12 +199,142 @@ where
         Ok(tx_hash)
     }
 
-    /// Fetch the current nonce to be used in the next message.
-    async fn sequence(&self) -> anyhow::Result<u64> {
-        // Using the `Pending` state to query just in case there are other transactions initiated by the validator.
+    /// Submit a transaction which has to make it to the chain eventually.
+    ///
+    /// If the broadcaster has an outbox, the transaction is persisted there and broadcast in the background,
+    /// retrying until it's included in a block; otherwise it's broadcast right away, like [Self::fevm_invoke].
+    pub async fn submit(
+        &self,
+        kind: &str,
+        contract: Address,
+        calldata: et::Bytes,
+        chain_id: ChainID,
+    ) -> anyhow::Result<()> {
+        match self.outbox {
+            Some(ref outbox) => {
+                if let Some(id) = outbox
+                    .push(kind, contract, calldata, chain_id)
+                    .await
+                    .context("failed to add transaction to the outbox")?
+                {
+                    tracing::info!(id, kind, "added transaction to the outbox");
+                }
+            }
+            None => {
+                let tx_hash = self.fevm_invoke(contract, calldata, chain_id).await?;
+                tracing::info!(
+                    tx_hash = tx_hash.to_string(),
+                    kind,
+                    "broadcasted transaction"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep broadcasting the transactions in the outbox until they are included in a block.
+    ///
+    /// Returns immediately if there is no outbox.
+    pub async fn run_outbox(self) {
+        if let Some(outbox) = self.outbox.clone() {
+            outbox::run(outbox, self).await
+        }
+    }
+
+    /// Estimate the gas and broadcast a transaction with a given nonce.
+    ///
+    /// Returns the code and the error message if the transaction failed the checks.
+    pub(super) async fn try_invoke(
+        &self,
+        contract: Address,
+        calldata: &et::Bytes,
+        chain_id: ChainID,
+        sequence: u64,
+    ) -> anyhow::Result<Result<tendermint::hash::Hash, (tendermint::abci::Code, String)>> {
+        let factory = MessageFactory::new(self.secret_key.clone(), self.addr, sequence, chain_id);
+
+        // Using the bound client as a one-shot transaction sender.
+        let mut client = self.client.clone().bind(factory);
+
+        // TODO: Maybe we should implement something like the Ethereum facade for estimating fees?
+        // I don't want to call the Ethereum API directly (it would be one more dependency).
+        // Another option is for Fendermint to recognise transactions coming from validators
+        // and always put them into the block to facilitate checkpointing.
+        let mut gas_params = GasParams {
+            gas_limit: BLOCK_GAS_LIMIT,
+            gas_fee_cap: self.gas_fee_cap.clone(),
+            gas_premium: self.gas_premium.clone(),
+        };
+
+        // Not expecting to send any tokens to the contracts.
+        let value = TokenAmount::zero();
+
+        // We can use the `Committed` state to execute the message, which is more efficient than doing it on `Pending`.
+        let gas_estimate = client
+            .fevm_estimate_gas(
+                contract,
+                calldata.0.clone(),
+                value.clone(),
+                gas_params.clone(),
+                FvmQueryHeight::Committed,
+            )
+            .await
+            .context("failed to estimate gas")?;
+
+        if gas_estimate.value.exit_code.is_success() {
+            gas_params.gas_limit =
+                (gas_estimate.value.gas_limit as f64 * self.gas_overestimation_rate) as u64;
+        } else {
+            bail!(
+                "failed to estimate gas: {} - {}",
+                gas_estimate.value.exit_code,
+                gas_estimate.value.info
+            );
+        }
+
+        // Using TxSync instead of TxCommit because TxCommit times out if the `check_tx` part fails,
+        // instead of returning as soon as the check failed with some default values for `deliver_tx`.
+        let res = TxClient::<TxSync>::fevm_invoke(
+            &mut client,
+            contract,
+            calldata.0.clone(),
+            value,
+            gas_params,
+        )
+        .await
+        .context("failed to invoke contract")?;
+
+        if res.response.code.is_err() {
+            // Not sure what exactly arrives in the data and how it's encoded.
+            // It might need the Base64 decoding or it may not. Let's assume
+            // that it doesn't because unlike `DeliverTx::data`, this response
+            // does have some Base64 lreated annotations.
+            let data = decode_fevm_return_data(RawBytes::new(res.response.data.to_vec()))
+                .map(hex::encode)
+                .unwrap_or_else(|_| hex::encode(res.response.data));
+
+            Ok(Err((
+                res.response.code,
+                format!(
+                    "broadcasted transaction failed during check: {}; data = {}",
+                    res.response.code.value(),
+                    data
+                ),
+            )))
+        } else {
+            Ok(Ok(res.response.hash))
+        }
+    }
+
+    /// Fetch the nonce of the broadcaster account.
+    ///
+    /// Using the `Pending` state gives the nonce to be used in the next message, taking into account
+    /// the transactions initiated by the validator which are already in the mempool, while the
+    /// `Committed` state tells which of them have been included in a block.
+    pub(super) async fn sequence(&self, height: FvmQueryHeight) -> anyhow::Result<u64> {
         let res = self
             .client
-            .actor_state(&self.addr, FvmQueryHeight::Pending)
+            .actor_state(&self.addr, height)
             .await
             .context("failed to get broadcaster actor state")?;
 
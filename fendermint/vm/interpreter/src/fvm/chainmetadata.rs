@@ -0,0 +1,59 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::{
+    multihash::{Code, Multihash},
+    Cid,
+};
+use fendermint_vm_actor_interface::chainmetadata::{self, BlockMetadata, CHAIN_METADATA_ACTOR_ID};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, IPLD_RAW};
+
+use super::state::{BlockHash, FvmExecState};
+
+/// Wrap a Tendermint block hash into a CID, which is what the FVM uses to identify tipsets.
+pub fn block_cid(block_hash: &BlockHash) -> anyhow::Result<Cid> {
+    let mh = Multihash::wrap(Code::Sha2_256.into(), block_hash)?;
+    Ok(Cid::new_v1(IPLD_RAW, mh))
+}
+
+/// Record the hash of the block being executed, so the externs can look it up in subsequent blocks.
+///
+/// Does nothing if the chain was started without the chain metadata actor.
+pub fn record_block<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let block_hash = state
+        .block_hash()
+        .ok_or_else(|| anyhow!("block hash is only available during execution"))?;
+
+    let block = BlockMetadata {
+        height: state.block_height(),
+        block_cid: block_cid(&block_hash)?,
+    };
+
+    let state_tree = state.state_tree_mut();
+
+    let mut actor = match state_tree.get_actor(CHAIN_METADATA_ACTOR_ID)? {
+        Some(actor) => actor,
+        None => return Ok(()),
+    };
+
+    let mut md: chainmetadata::State = state_tree
+        .store()
+        .get_cbor(&actor.state)?
+        .ok_or_else(|| anyhow!("chain metadata state not found"))?;
+
+    md.push(block);
+
+    actor.state = state_tree
+        .store()
+        .put_cbor(&md, Code::Blake2b256)
+        .context("failed to store chain metadata")?;
+
+    state_tree.set_actor(CHAIN_METADATA_ACTOR_ID, actor);
+
+    Ok(())
+}
@@ -4,11 +4,15 @@
 use async_trait::async_trait;
 
 use fvm_ipld_blockstore::Blockstore;
-use fvm_shared::{address::Address, error::ExitCode};
+use fvm_shared::{address::Address, econ::TokenAmount, error::ExitCode};
+use num_traits::Zero;
 
 use crate::CheckInterpreter;
 
-use super::{state::FvmExecState, store::ReadOnlyBlockstore, FvmMessage, FvmMessageInterpreter};
+use super::{
+    access, beacon, exec, governance, policy, state::FvmExecState, store::ReadOnlyBlockstore,
+    FvmMessage, FvmMessageInterpreter,
+};
 
 /// Transaction check results are expressed by the exit code, so that hopefully
 /// they would result in the same error code if they were applied.
@@ -19,6 +23,41 @@ pub struct FvmCheckRet {
     pub info: Option<String>,
 }
 
+/// Conditions on the balance of the sender for a transaction to be admitted to the mempool,
+/// on top of covering the gas, so that insolvent accounts can't flood it with transactions
+/// which are bound to fail. They are evaluated against the pending state of the checks.
+#[derive(Debug, Clone, Default)]
+pub struct AdmissionRules {
+    /// Require the balance to cover the value transferred as well as the gas.
+    pub cover_value: bool,
+    /// Minimum balance the sender must have to send messages with a non-zero value.
+    pub min_balance: Option<TokenAmount>,
+}
+
+impl AdmissionRules {
+    /// The balance needed to admit the message, which is reserved in the pending state.
+    pub fn balance_needed(&self, msg: &FvmMessage) -> TokenAmount {
+        let gas_cost = msg.gas_fee_cap.clone() * msg.gas_limit;
+        if self.cover_value {
+            gas_cost + msg.value.clone()
+        } else {
+            gas_cost
+        }
+    }
+
+    /// Return the reason to reject the message if the sender doesn't have the minimum balance.
+    pub fn check_min_balance(&self, balance: &TokenAmount, msg: &FvmMessage) -> Option<String> {
+        match self.min_balance {
+            Some(ref min_balance) if !msg.value.is_zero() && balance < min_balance => {
+                Some(format!(
+                    "actor balance {balance} less than the minimum {min_balance} to send value"
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[async_trait]
 impl<DB, TC> CheckInterpreter for FvmMessageInterpreter<DB, TC>
 where
@@ -34,12 +73,28 @@ where
     /// Check that:
     /// * sender exists
     /// * sender nonce matches the message sequence
-    /// * sender has enough funds to cover the gas cost
+    /// * sender has enough funds to cover the gas cost, and the value if the [`AdmissionRules`] say so
+    /// * sender has the minimum balance of the [`AdmissionRules`] to send value, if there is one
+    /// * sender is allowed by the access control lists, if there are any
+    /// * the message is allowed by the policy module, if there is one
+    /// * sender is a governance member, if the message is a governance proposal or approval
+    /// * sender is an active validator, if the message is a beacon commitment or reveal
+    /// * gas limit fits in the block gas limit set by governance, if there is one
+    /// * the subnet is not shutting down
+    ///
+    /// Rechecks after a block has been committed only stack the nonce and the balance
+    /// changes, even if full execution is enabled: the transaction has been executed
+    /// when it was added to the mempool, and rechecking every pending transaction after
+    /// every block would make the check latency grow with the size of the mempool.
+    ///
+    /// If checks are deferred, new transactions only have their basic fields checked, so
+    /// that they can be gossiped without waiting on the state; everything else is checked
+    /// when they are rechecked, and by the proposer before including them in a block.
     async fn check(
         &self,
         mut state: Self::State,
         msg: Self::Message,
-        _is_recheck: bool,
+        is_recheck: bool,
     ) -> anyhow::Result<(Self::State, Self::Output)> {
         let checked = |state, exit_code: ExitCode, gas_used: Option<u64>, info: Option<String>| {
             tracing::info!(
@@ -70,13 +125,60 @@ where
             );
         }
 
+        if self.defer_check && !is_recheck {
+            return checked(state, ExitCode::OK, None, None);
+        }
+
+        if let Some(acl) = access::get_state(&mut state)? {
+            if let Some(reason) = access::check_access(&acl, &msg) {
+                return checked(state, ExitCode::USR_FORBIDDEN, None, Some(reason));
+            }
+        }
+
+        if let Some(reason) = policy::check_policy(self.policy.as_deref(), &mut state, &msg)? {
+            return checked(state, ExitCode::USR_FORBIDDEN, None, Some(reason));
+        }
+
+        if let Some(gov) = governance::get_state(&mut state)? {
+            if let Some(ref shutdown) = gov.shutdown {
+                let reason = format!(
+                    "the subnet is shutting down since height {}",
+                    shutdown.since
+                );
+                return checked(state, ExitCode::USR_FORBIDDEN, None, Some(reason));
+            }
+            if governance::is_governance(&msg) {
+                if let Some(reason) = governance::check_member(&gov, &msg) {
+                    return checked(state, ExitCode::USR_FORBIDDEN, None, Some(reason));
+                }
+            }
+            let block_gas_limit = gov.params.block_gas_limit;
+            if block_gas_limit > 0 && msg.gas_limit > block_gas_limit {
+                return checked(
+                    state,
+                    ExitCode::SYS_OUT_OF_GAS,
+                    None,
+                    Some(format!(
+                        "gas limit {} exceeds the block gas limit {block_gas_limit}",
+                        msg.gas_limit
+                    )),
+                );
+            }
+        }
+
+        if beacon::is_beacon(&msg) {
+            if let Some(reason) = beacon::check_validator(&mut state, &msg)? {
+                return checked(state, ExitCode::USR_FORBIDDEN, None, Some(reason));
+            }
+        }
+
         // NOTE: This would be a great place for let-else, but clippy runs into a compilation bug.
         let state_tree = state.state_tree_mut();
 
         // This code is left in place for reference of a partial check performed on top of `FvmCheckState`.
         if let Some(id) = state_tree.lookup_id(&msg.from)? {
             if let Some(mut actor) = state_tree.get_actor(id)? {
-                let balance_needed = msg.gas_fee_cap.clone() * msg.gas_limit;
+                let balance_needed = self.admission.balance_needed(&msg);
                 if actor.balance < balance_needed {
                     return checked(
                         state,
@@ -86,6 +188,14 @@ where
                             format! {"actor balance {} less than needed {}", actor.balance, balance_needed},
                         ),
                     );
+                } else if let Some(reason) = self.admission.check_min_balance(&actor.balance, &msg)
+                {
+                    return checked(
+                        state,
+                        ExitCode::SYS_SENDER_STATE_INVALID,
+                        None,
+                        Some(reason),
+                    );
                 } else if actor.sequence != msg.sequence {
                     return checked(
                         state,
@@ -95,12 +205,12 @@ where
                             format! {"expected sequence {}, got {}", actor.sequence, msg.sequence},
                         ),
                     );
-                } else if self.exec_in_check {
+                } else if self.exec_in_check && !is_recheck {
                     // Instead of modifying just the partial state, we will execute the call in earnest.
                     // This is required for fully supporting the Ethereum API "pending" queries, if that's needed.
 
                     // This will stack the effect for subsequent transactions added to the mempool.
-                    let (apply_ret, _) = state.execute_explicit(msg.clone())?;
+                    let (apply_ret, _) = exec::execute_user_msg(&mut state, msg.clone())?;
                     return checked(
                         state,
                         apply_ret.msg_receipt.exit_code,
@@ -128,3 +238,398 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_actor_interface::beacon::{self, CommitParams, BEACON_ACTOR_ADDR};
+    use fendermint_vm_actor_interface::deadletter::{self, RetryParams, DEADLETTER_ACTOR_ADDR};
+    use fendermint_vm_actor_interface::governance::{
+        self, ChainParams, ProposeParams, GOVERNANCE_ACTOR_ADDR,
+    };
+    use fendermint_vm_actor_interface::rewardpool::{self, REWARD_POOL_ACTOR_ADDR};
+    use fendermint_vm_actor_interface::{ipc::GATEWAY_ACTOR_ID, placeholder};
+    use fendermint_vm_genesis::{
+        Beacon, Collateral, Genesis, Governance, Rewards, SignerAddr, Validator, ValidatorKey,
+    };
+    use fvm::engine::MultiEngine;
+    use fvm::state_tree::ActorState;
+    use fvm::EMPTY_ARR_CID;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use ipc_sdk::cross::{CrossMsg, StorableMsg};
+    use ipc_sdk::subnet_id::SubnetID;
+    use tendermint_rpc::{MockClient, MockRequestMethodMatcher};
+
+    use super::{AdmissionRules, FvmCheckRet};
+    use crate::fvm::bundle::contracts_path;
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::{memory::MemoryBlockstore, ReadOnlyBlockstore};
+    use crate::fvm::testing::{self, interpreter, TestInterpreter};
+    use crate::fvm::{FvmMessage, FvmMessageInterpreter};
+    use crate::CheckInterpreter;
+
+    type CheckState = FvmExecState<ReadOnlyBlockstore<MemoryBlockstore>>;
+
+    /// Create the genesis state of a chain without any accounts.
+    async fn genesis_state() -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(testing::genesis()).await
+    }
+
+    async fn check(
+        interpreter: &TestInterpreter,
+        state: CheckState,
+        is_recheck: bool,
+    ) -> (CheckState, ExitCode) {
+        let (state, ret) = interpreter
+            .check(state, msg(0), is_recheck)
+            .await
+            .expect("check failed");
+        (state, ret.exit_code)
+    }
+
+    fn msg(value: u64) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from: Address::new_id(100),
+            to: Address::new_id(101),
+            sequence: 0,
+            value: TokenAmount::from_atto(value),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 10,
+            gas_fee_cap: TokenAmount::from_atto(2),
+            gas_premium: TokenAmount::from_atto(1),
+        }
+    }
+
+    fn key(seed: u8) -> SecretKey {
+        SecretKey::try_from(vec![seed; 32]).unwrap()
+    }
+
+    fn addr(seed: u8) -> Address {
+        Address::new_secp256k1(&key(seed).public_key().serialize()).unwrap()
+    }
+
+    /// A call with enough gas to be executed.
+    fn call(from: Address, to: Address, method_num: u64, params: RawBytes) -> FvmMessage {
+        FvmMessage {
+            from,
+            to,
+            method_num,
+            params,
+            gas_limit: 10_000_000,
+            gas_fee_cap: TokenAmount::from_atto(10_000),
+            ..msg(0)
+        }
+    }
+
+    /// An interpreter which executes new transactions on the check state.
+    fn exec_interpreter() -> TestInterpreter {
+        let (client, _) = MockClient::new(MockRequestMethodMatcher::default());
+        FvmMessageInterpreter::new(client, None, contracts_path(), 1.05, 1.05, true)
+    }
+
+    /// Check a new transaction by executing it on the check state.
+    async fn check_executed(state: CheckState, msg: FvmMessage) -> (CheckState, FvmCheckRet) {
+        exec_interpreter()
+            .check(state, msg, false)
+            .await
+            .expect("check failed")
+    }
+
+    #[test]
+    fn balance_needed_covers_value() {
+        let rules = AdmissionRules::default();
+        assert_eq!(rules.balance_needed(&msg(5)), TokenAmount::from_atto(20));
+
+        let rules = AdmissionRules {
+            cover_value: true,
+            ..Default::default()
+        };
+        assert_eq!(rules.balance_needed(&msg(5)), TokenAmount::from_atto(25));
+    }
+
+    #[test]
+    fn min_balance_only_for_value() {
+        let rules = AdmissionRules {
+            min_balance: Some(TokenAmount::from_atto(100)),
+            ..Default::default()
+        };
+        let balance = TokenAmount::from_atto(50);
+        assert!(rules.check_min_balance(&balance, &msg(0)).is_none());
+        assert!(rules.check_min_balance(&balance, &msg(1)).is_some());
+        assert!(rules
+            .check_min_balance(&TokenAmount::from_atto(100), &msg(1))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn deferred_checks_skip_the_state_until_the_recheck() {
+        let (store, params) = genesis_state().await;
+        let multi_engine = MultiEngine::default();
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params).unwrap();
+
+        // The sender doesn't exist, which a full check finds out straight away.
+        let (state, exit_code) = check(&interpreter(), state, false).await;
+        assert_eq!(exit_code, ExitCode::SYS_SENDER_INVALID);
+
+        // A deferred check only looks at the message itself...
+        let deferred = interpreter().with_deferred_check(true);
+        let (state, exit_code) = check(&deferred, state, false).await;
+        assert_eq!(exit_code, ExitCode::OK);
+
+        // ...leaving the rest to the rechecks and the proposer.
+        let (_, exit_code) = check(&deferred, state, true).await;
+        assert_eq!(exit_code, ExitCode::SYS_SENDER_INVALID);
+    }
+
+    #[tokio::test]
+    async fn governance_messages_are_executed_in_the_checks() {
+        let (member, other) = (addr(1), addr(2));
+        let (store, params) = testing::genesis_state(Genesis {
+            accounts: vec![testing::account(member, TokenAmount::from_whole(1))],
+            governance: Some(Governance {
+                members: vec![SignerAddr(member), SignerAddr(other)],
+                threshold: 2,
+                epoch_length: 10,
+                params: Default::default(),
+            }),
+            ..testing::genesis()
+        })
+        .await;
+        let multi_engine = MultiEngine::default();
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params).unwrap();
+
+        let propose = call(
+            member,
+            GOVERNANCE_ACTOR_ADDR,
+            governance::Method::Propose as u64,
+            RawBytes::serialize(ProposeParams {
+                params: ChainParams {
+                    block_gas_limit: 100_000_000,
+                    ..Default::default()
+                },
+            })
+            .unwrap(),
+        );
+
+        // The governance actor is only a placeholder, which can't handle the proposal.
+        let (mut state, ret) = check_executed(state, propose).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+
+        // The proposal is open in the state the next checks build on.
+        let gov = crate::fvm::governance::get_state(&mut state)
+            .unwrap()
+            .expect("governance is enabled");
+        assert_eq!(gov.proposals.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn beacon_commitments_are_executed_in_the_checks() {
+        let validator = addr(1);
+        let (store, params) = testing::genesis_state(Genesis {
+            validators: vec![Validator {
+                public_key: ValidatorKey::new(key(1).public_key()),
+                power: Collateral(TokenAmount::from_whole(1)),
+            }],
+            accounts: vec![testing::account(validator, TokenAmount::from_whole(1))],
+            beacon: Some(Beacon { epoch_length: 10 }),
+            ..testing::genesis()
+        })
+        .await;
+        let multi_engine = MultiEngine::default();
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params).unwrap();
+
+        let epoch = 0;
+        let commitment = beacon::commitment(&[1; 32], epoch, &validator);
+        let commit = call(
+            validator,
+            BEACON_ACTOR_ADDR,
+            beacon::Method::Commit as u64,
+            RawBytes::serialize(CommitParams { epoch, commitment }).unwrap(),
+        );
+
+        // The beacon actor is only a placeholder, which can't handle the commitment.
+        let (mut state, ret) = check_executed(state, commit).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+
+        let beacon = crate::fvm::beacon::get_state(&mut state)
+            .unwrap()
+            .expect("the beacon is enabled");
+        assert_eq!(beacon.round.contributions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dead_letter_retries_are_executed_in_the_checks() {
+        let member = addr(1);
+        let (store, mut params) = testing::genesis_state(Genesis {
+            accounts: vec![testing::account(member, TokenAmount::from_whole(1))],
+            governance: Some(Governance {
+                members: vec![SignerAddr(member)],
+                threshold: 1,
+                epoch_length: 10,
+                params: Default::default(),
+            }),
+            ..testing::genesis()
+        })
+        .await;
+        let multi_engine = MultiEngine::default();
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 1, params.clone()).unwrap();
+
+        // The gateway only has to exist to send the letter from.
+        let code = *state
+            .builtin_actors()
+            .code_by_id(placeholder::PLACEHOLDER_ACTOR_CODE_ID)
+            .unwrap();
+        state.state_tree_mut().set_actor(
+            GATEWAY_ACTOR_ID,
+            ActorState {
+                code,
+                state: *EMPTY_ARR_CID,
+                sequence: 0,
+                balance: TokenAmount::default(),
+                delegated_address: None,
+            },
+        );
+
+        let subnet_id = SubnetID::new(10, vec![Address::new_id(1000)]);
+        let msg = StorableMsg::new_fund_msg(
+            &subnet_id,
+            &Address::new_id(1),
+            &member,
+            TokenAmount::default(),
+        )
+        .unwrap();
+        let letter = CrossMsg {
+            msg,
+            wrapped: false,
+        };
+        crate::fvm::deadletter::record(&mut state, vec![letter], "failed".to_owned()).unwrap();
+
+        let (state_root, _, _) = state.commit().unwrap();
+        params.state_root = state_root;
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 2, params).unwrap();
+
+        let retry = call(
+            member,
+            DEADLETTER_ACTOR_ADDR,
+            deadletter::Method::Retry as u64,
+            RawBytes::serialize(RetryParams { id: 0 }).unwrap(),
+        );
+
+        // The dead-letter actor is only a placeholder, which can't handle the retry.
+        let (mut state, ret) = check_executed(state, retry).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+
+        let letters = crate::fvm::deadletter::list(&mut state).unwrap();
+        assert!(letters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reward_claims_are_executed_in_the_checks() {
+        let validator = addr(1);
+        let (store, mut params) = testing::genesis_state(Genesis {
+            validators: vec![Validator {
+                public_key: ValidatorKey::new(key(1).public_key()),
+                power: Collateral(TokenAmount::from_whole(1)),
+            }],
+            accounts: vec![testing::account(validator, TokenAmount::from_whole(1))],
+            rewards: Some(Rewards {
+                epoch_length: 10,
+                inflation_per_epoch: TokenAmount::from_whole(10),
+                collect_base_fees: true,
+            }),
+            ..testing::genesis()
+        })
+        .await;
+        let multi_engine = MultiEngine::default();
+
+        // Close the first reward epoch, so the validator has something to claim.
+        for height in [1, 10] {
+            let mut state =
+                FvmExecState::new(store.clone(), &multi_engine, height, params.clone()).unwrap();
+            crate::fvm::rewards::begin_block(&mut state).unwrap();
+            let (state_root, updated, _) = state.commit().unwrap();
+            params.state_root = state_root;
+            params.circ_supply = updated.circ_supply;
+        }
+
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 11, params).unwrap();
+
+        let claim = call(
+            validator,
+            REWARD_POOL_ACTOR_ADDR,
+            rewardpool::Method::Claim as u64,
+            RawBytes::default(),
+        );
+
+        // The reward pool actor is only a placeholder, which can't handle the claim.
+        let (mut state, ret) = check_executed(state, claim).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+
+        let (pool, _) = crate::fvm::rewards::get_state(&mut state)
+            .unwrap()
+            .expect("the reward pool is enabled");
+        assert!(pool.claimable.is_empty());
+    }
+
+    #[tokio::test]
+    async fn native_messages_stack_in_the_check_state() {
+        let (member, other) = (addr(1), addr(2));
+        let (store, params) = testing::genesis_state(Genesis {
+            accounts: vec![testing::account(member, TokenAmount::from_whole(1))],
+            governance: Some(Governance {
+                members: vec![SignerAddr(member), SignerAddr(other)],
+                threshold: 2,
+                epoch_length: 10,
+                params: Default::default(),
+            }),
+            ..testing::genesis()
+        })
+        .await;
+        let multi_engine = MultiEngine::default();
+        let state =
+            FvmExecState::new(ReadOnlyBlockstore::new(store), &multi_engine, 1, params).unwrap();
+
+        let propose = |sequence| FvmMessage {
+            sequence,
+            ..call(
+                member,
+                GOVERNANCE_ACTOR_ADDR,
+                governance::Method::ProposeShutdown as u64,
+                RawBytes::default(),
+            )
+        };
+        let proposals = |state: &mut CheckState| {
+            crate::fvm::governance::get_state(state)
+                .unwrap()
+                .expect("governance is enabled")
+                .proposals
+                .len()
+        };
+
+        // Each executed message builds on the effects of the ones before it, the nonce included.
+        let (state, ret) = check_executed(state, propose(0)).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+        let (mut state, ret) = check_executed(state, propose(1)).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+        assert_eq!(proposals(&mut state), 2);
+
+        // A deferred message isn't executed, but its nonce is stacked when it's rechecked.
+        let deferred = exec_interpreter().with_deferred_check(true);
+        let (state, ret) = deferred.check(state, propose(2), false).await.unwrap();
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+        let (state, ret) = deferred.check(state, propose(2), true).await.unwrap();
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+        let (mut state, ret) = check_executed(state, propose(3)).await;
+        assert_eq!(ret.exit_code, ExitCode::OK, "{:?}", ret.info);
+        assert_eq!(proposals(&mut state), 3);
+    }
+}
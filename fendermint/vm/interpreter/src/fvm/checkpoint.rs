@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{anyhow, Context};
@@ -29,6 +30,7 @@ use ipc_actors_abis::gateway_router_facet as router;
 use super::state::ipc::tokens_to_burn;
 use super::{
     broadcast::Broadcaster,
+    routing,
     state::{ipc::GatewayCaller, FvmExecState},
     ValidatorContext,
 };
@@ -41,6 +43,44 @@ pub struct PowerTable(pub Vec<Validator<Power>>);
 #[derive(Debug, Clone, Default)]
 pub struct PowerUpdates(pub Vec<Validator<Power>>);
 
+/// The power table in effect after the last checkpoint.
+///
+/// The validator set only changes when the pending staking changes are applied at a checkpoint,
+/// so there is no need to read the whole membership from the gateway at every checkpoint:
+/// it is only read again when the configuration changed, to diff it against the cached table.
+#[derive(Clone, Default)]
+pub struct PowerTableCache(Arc<Mutex<Option<CachedPowerTable>>>);
+
+struct CachedPowerTable {
+    /// Height of the checkpoint which established the table.
+    height: u64,
+    power_scale: PowerScale,
+    power_table: PowerTable,
+}
+
+impl PowerTableCache {
+    /// The cached table, if it was established before the given height with the same scale.
+    ///
+    /// A block which is executed again, e.g. after the application was rolled back,
+    /// is at or below the height of the cached table, and reads the gateway instead.
+    fn get(&self, height: u64, power_scale: PowerScale) -> Option<PowerTable> {
+        match *self.0.lock().unwrap() {
+            Some(ref c) if c.height < height && c.power_scale == power_scale => {
+                Some(c.power_table.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn set(&self, height: u64, power_scale: PowerScale, power_table: PowerTable) {
+        *self.0.lock().unwrap() = Some(CachedPowerTable {
+            height,
+            power_scale,
+            power_table,
+        });
+    }
+}
+
 /// Construct and store a checkpoint if this is the end of the checkpoint period.
 /// Perform end-of-checkpoint-period transitions in the ledger.
 ///
@@ -49,6 +89,7 @@ pub struct PowerUpdates(pub Vec<Validator<Power>>);
 pub fn maybe_create_checkpoint<DB>(
     gateway: &GatewayCaller<DB>,
     state: &mut FvmExecState<DB>,
+    cache: &PowerTableCache,
 ) -> anyhow::Result<Option<(router::BottomUpCheckpoint, PowerUpdates)>>
 where
     DB: Blockstore + Sync + Send + 'static,
@@ -67,8 +108,15 @@ where
         None => Ok(None),
         Some(subnet_id) => {
             // Get the current power table from the ledger, not CometBFT.
-            let (_, curr_power_table) =
-                ipc_power_table(gateway, state).context("failed to get the current power table")?;
+            let power_scale = state.power_scale();
+            let curr_power_table = match cache.get(height.value(), power_scale) {
+                Some(power_table) => power_table,
+                None => {
+                    ipc_power_table(gateway, state)
+                        .context("failed to get the current power table")?
+                        .1
+                }
+            };
 
             // Apply any validator set transitions.
             let next_configuration_number = gateway
@@ -80,6 +128,10 @@ where
                 .bottom_up_msgs(state, height.value())
                 .context("failed to retrieve bottom-up messages")?;
 
+            // The gateway decided these messages go up; make sure they have somewhere to go.
+            let current = routing::current_subnet_id(gateway, state)?;
+            routing::check_bottomup_routes(&current, &cross_msgs);
+
             // Sum up the value leaving the subnet as part of the bottom-up messages.
             let burnt_tokens = tokens_to_burn(&cross_msgs);
 
@@ -116,6 +168,7 @@ where
 
             // Figure out the power updates if there was some change in the configuration.
             let power_updates = if next_configuration_number == 0 {
+                cache.set(height.value(), power_scale, curr_power_table);
                 PowerUpdates(Vec::new())
             } else {
                 let (next_power_configuration_number, next_power_table) =
@@ -123,6 +176,7 @@ where
 
                 debug_assert_eq!(next_power_configuration_number, next_configuration_number);
 
+                cache.set(height.value(), power_scale, next_power_table.clone());
                 power_diff(curr_power_table, next_power_table)
             };
 
@@ -275,14 +329,16 @@ where
         .add_checkpoint_signature_calldata(checkpoint, &power_table.0, validator, secret_key)
         .context("failed to produce checkpoint signature calldata")?;
 
-    let tx_hash = broadcaster
-        .fevm_invoke(Address::from(gateway.addr()), calldata, chain_id)
+    broadcaster
+        .submit(
+            "checkpoint-signature",
+            Address::from(gateway.addr()),
+            calldata,
+            chain_id,
+        )
         .await
         .context("failed to broadcast signature")?;
 
-    // The transaction should be in the mempool now.
-    tracing::info!(tx_hash = tx_hash.to_string(), "broadcasted signature");
-
     Ok(())
 }
 
@@ -332,7 +388,7 @@ where
 }
 
 /// Get the current power table from the Gateway actor.
-fn ipc_power_table<DB>(
+pub(crate) fn ipc_power_table<DB>(
     gateway: &GatewayCaller<DB>,
     state: &mut FvmExecState<DB>,
 ) -> anyhow::Result<(ConfigurationNumber, PowerTable)>
@@ -422,7 +478,7 @@ mod tests {
 
     use crate::fvm::checkpoint::{into_power_map, power_diff};
 
-    use super::{PowerTable, PowerUpdates};
+    use super::{PowerTable, PowerTableCache, PowerUpdates};
 
     fn power_update(current: PowerTable, updates: PowerUpdates) -> PowerTable {
         let mut current = into_power_map(current);
@@ -487,4 +543,19 @@ mod tests {
         let next = PowerTable(vec![v2, v1]);
         assert!(power_diff(current, next).0.is_empty());
     }
+
+    #[quickcheck]
+    fn prop_power_table_cache(v: Validator<Power>) {
+        let cache = PowerTableCache::default();
+        assert!(cache.get(10, 0).is_none());
+
+        let power_table = PowerTable(vec![v]);
+        cache.set(10, 0, power_table.clone());
+
+        assert_eq!(cache.get(20, 0), Some(power_table));
+        // The same or an earlier height is executed again, and the scale can change.
+        assert!(cache.get(10, 0).is_none());
+        assert!(cache.get(5, 0).is_none());
+        assert!(cache.get(20, 3).is_none());
+    }
 }
@@ -0,0 +1,274 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use anyhow::{anyhow, Context};
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use ethers::abi::AbiEncode;
+use fendermint_vm_actor_interface::checkpointarchive::{
+    self, Archive, ArchiveContent, ArchivedCheckpoint, CHECKPOINT_ARCHIVE_ACTOR_ID,
+};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::{load_car, CarHeader};
+use fvm_ipld_encoding::{CborStore, RawBytes, DAG_CBOR};
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use super::{
+    state::{ipc::GatewayCaller, FvmExecState},
+    store::memory::MemoryBlockstore,
+};
+
+/// Checkpoints which have been pruned from the gateway and need to be exported.
+pub struct ArchiveExport {
+    pub archive: Archive,
+    /// The IPLD encoded [ArchiveContent], which hashes to the root of the archive.
+    pub content: Vec<u8>,
+}
+
+/// Load the archive state; if the actor doesn't exist, the chain doesn't prune checkpoints.
+pub fn get_state<DB>(
+    state: &mut FvmExecState<DB>,
+) -> anyhow::Result<Option<checkpointarchive::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(CHECKPOINT_ARCHIVE_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("checkpoint archive state not found"))
+            .map(Some),
+    }
+}
+
+/// Prune the checkpoints which fell out of the retention window after a new checkpoint
+/// has been created at `height`, and record the root of the archive they go into.
+///
+/// Checkpoints which are still collecting signatures are kept, and so is everything
+/// after them, so the archives never have gaps.
+pub fn maybe_archive_checkpoints<DB>(
+    gateway: &GatewayCaller<DB>,
+    state: &mut FvmExecState<DB>,
+    height: u64,
+) -> anyhow::Result<Option<ArchiveExport>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut archives = match get_state(state)? {
+        Some(archives) if archives.retention > 0 => archives,
+        _ => return Ok(None),
+    };
+
+    let period = gateway.bottom_up_check_period(state)?;
+
+    // Keep the last `retention` checkpoints, including the one just created.
+    let mut to_height = height.saturating_sub(archives.retention * period);
+
+    if let Some(first_incomplete) = gateway
+        .incomplete_checkpoints(state)
+        .context("failed to fetch incomplete checkpoints")?
+        .iter()
+        .map(|cp| cp.block_height)
+        .min()
+    {
+        to_height = to_height.min(first_incomplete.saturating_sub(period));
+    }
+
+    let from_height = archives
+        .archived_height()
+        .map(|h| h + period)
+        .unwrap_or(period);
+
+    if to_height < from_height {
+        return Ok(None);
+    }
+
+    let mut content = ArchiveContent::new();
+
+    for h in (from_height..=to_height).step_by(period as usize) {
+        let (checkpoint, quorum_info, signatories, signatures) = gateway
+            .checkpoint_bundle(state, h)
+            .with_context(|| format!("failed to get checkpoint at height {h}"))?;
+
+        content.push(ArchivedCheckpoint {
+            height: h,
+            checkpoint: checkpoint.encode(),
+            quorum_info: quorum_info.encode(),
+            signatories: signatories.into_iter().map(|a| a.into()).collect(),
+            signatures: signatures
+                .into_iter()
+                .map(|s| RawBytes::new(s.to_vec()))
+                .collect(),
+        });
+    }
+
+    let content = fvm_ipld_encoding::to_vec(&content)?;
+    let root = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&content));
+
+    let archive = Archive {
+        from_height,
+        to_height,
+        root,
+    };
+
+    gateway
+        .prune_bottom_up_checkpoints(state, to_height + 1)
+        .context("failed to prune checkpoints")?;
+
+    archives.archives.push(archive.clone());
+    set_state(state, &archives)?;
+
+    tracing::info!(
+        from_height,
+        to_height,
+        root = root.to_string(),
+        "archived bottom-up checkpoints"
+    );
+
+    Ok(Some(ArchiveExport { archive, content }))
+}
+
+/// Write the archive to a CAR file in the directory, with the content as its only block.
+pub async fn export_archive(dir: &Path, export: ArchiveExport) -> anyhow::Result<PathBuf> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("failed to create checkpoint archive directory")?;
+
+    let path = dir.join(export.archive.file_name());
+    // Write to a temporary file first, so that a crash doesn't leave a truncated archive behind.
+    let part_path = path.with_extension("car.part");
+
+    let file = tokio::fs::File::create(&part_path).await?;
+    let header = CarHeader::new(vec![export.archive.root], 1);
+    let mut blocks = tokio_stream::iter(vec![(export.archive.root, export.content)]);
+    let mut write = file.compat_write();
+
+    header
+        .write_stream_async(&mut Pin::new(&mut write), &mut blocks)
+        .await?;
+
+    tokio::fs::rename(&part_path, &path).await?;
+
+    Ok(path)
+}
+
+/// Look up a checkpoint in an archive exported to the directory.
+///
+/// Returns `None` if this node doesn't have the archive file.
+pub async fn read_archived_checkpoint(
+    dir: &Path,
+    archive: &Archive,
+    height: u64,
+) -> anyhow::Result<Option<ArchivedCheckpoint>> {
+    let path = dir.join(archive.file_name());
+
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("failed to open checkpoint archive"),
+    };
+
+    // Loading validates the hashes, so if the root is there, the content is what the state says it is.
+    let store = MemoryBlockstore::new();
+    load_car(&store, file.compat()).await.with_context(|| {
+        format!(
+            "failed to load checkpoint archive {}",
+            path.to_string_lossy()
+        )
+    })?;
+
+    let content: ArchiveContent = store
+        .get_cbor(&archive.root)?
+        .ok_or_else(|| anyhow!("archive root not found in {}", path.to_string_lossy()))?;
+
+    Ok(content.into_iter().find(|cp| cp.height == height))
+}
+
+fn set_state<DB>(
+    state: &mut FvmExecState<DB>,
+    archives: &checkpointarchive::State,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+
+    let mut actor = state_tree
+        .get_actor(CHECKPOINT_ARCHIVE_ACTOR_ID)?
+        .ok_or_else(|| anyhow!("checkpoint archive actor not found"))?;
+
+    actor.state = state_tree
+        .store()
+        .put_cbor(archives, Code::Blake2b256)
+        .context("failed to store checkpoint archive state")?;
+
+    state_tree.set_actor(CHECKPOINT_ARCHIVE_ACTOR_ID, actor);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fendermint_vm_actor_interface::checkpointarchive::{
+        Archive, ArchiveContent, ArchivedCheckpoint,
+    };
+    use fendermint_vm_actor_interface::eam::EthAddress;
+    use fvm_ipld_encoding::{RawBytes, DAG_CBOR};
+
+    use super::{export_archive, read_archived_checkpoint, ArchiveExport};
+
+    #[tokio::test]
+    async fn export_and_read_archive() {
+        let content: ArchiveContent = [10u64, 20]
+            .into_iter()
+            .map(|height| ArchivedCheckpoint {
+                height,
+                checkpoint: vec![1, 2, 3],
+                quorum_info: vec![4, 5],
+                signatories: vec![EthAddress([height as u8; 20])],
+                signatures: vec![RawBytes::new(vec![6; 65])],
+            })
+            .collect();
+
+        let bytes = fvm_ipld_encoding::to_vec(&content).unwrap();
+        let archive = Archive {
+            from_height: 10,
+            to_height: 20,
+            root: Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&bytes)),
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = export_archive(
+            dir.path(),
+            ArchiveExport {
+                archive: archive.clone(),
+                content: bytes,
+            },
+        )
+        .await
+        .expect("failed to export");
+
+        assert!(path.exists());
+
+        let cp = read_archived_checkpoint(dir.path(), &archive, 20)
+            .await
+            .expect("failed to read")
+            .expect("checkpoint in archive");
+
+        assert_eq!(cp, content[1]);
+
+        let cp = read_archived_checkpoint(dir.path(), &archive, 30)
+            .await
+            .expect("failed to read");
+
+        assert!(cp.is_none());
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::multihash::Code;
+use fendermint_vm_actor_interface::{
+    deadletter::{self, DeadLetter, RetryParams, DEADLETTER_ACTOR_ADDR, DEADLETTER_ACTOR_ID},
+    ipc::GATEWAY_ACTOR_ADDR,
+    placeholder,
+};
+use fvm::state_tree::ActorState;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{econ::TokenAmount, error::ExitCode, METHOD_SEND};
+use ipc_sdk::cross::CrossMsg;
+use num_traits::Zero;
+
+use super::{
+    access, governance,
+    state::{ExecResult, FvmExecState},
+    FvmMessage,
+};
+
+/// Load the dead letters; if the actor doesn't exist yet, there aren't any.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<deadletter::State>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(DEADLETTER_ACTOR_ID)? {
+        None => deadletter::State::new(state_tree.store()),
+        Some(actor) => state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("dead-letter state not found")),
+    }
+}
+
+/// List the dead letters, in the order they arrived.
+pub fn list<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<DeadLetter>>
+where
+    DB: Blockstore + 'static,
+{
+    let dl = get_state(state)?;
+    dl.letters(state.state_tree_mut().store())
+}
+
+/// Move top-down messages which failed to be applied to the dead-letter queue.
+///
+/// If the queue is full, the oldest letters are evicted to make room for them.
+pub fn record<DB>(
+    state: &mut FvmExecState<DB>,
+    msgs: Vec<CrossMsg>,
+    reason: String,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let height = state.block_height();
+    let mut dl = get_state(state)?;
+    for msg in msgs {
+        let (id, evicted) = dl.push(state.state_tree_mut().store(), height, msg, reason.clone())?;
+        tracing::warn!(
+            id,
+            height,
+            reason,
+            "top-down message moved to the dead-letter queue"
+        );
+        if let Some(evicted) = evicted {
+            tracing::error!(
+                id = evicted.id,
+                nonce = evicted.msg.msg.nonce,
+                "dead letter evicted from the full queue"
+            );
+        }
+    }
+    set_state(state, &dl)
+}
+
+/// Check whether the message asks for a dead letter to be retried.
+pub fn is_retry(msg: &FvmMessage) -> bool {
+    msg.to == DEADLETTER_ACTOR_ADDR && msg.method_num == deadletter::Method::Retry as u64
+}
+
+/// Check whether the sender is allowed to retry dead letters.
+///
+/// Retries are a governance decision: on chains with a governance multisig any of
+/// its members can ask for one, on chains with access control only the governor,
+/// and on chains with neither nobody.
+///
+/// Returns the reason for rejection if it is not.
+fn check_retrier<DB>(
+    state: &mut FvmExecState<DB>,
+    msg: &FvmMessage,
+) -> anyhow::Result<Option<String>>
+where
+    DB: Blockstore + 'static,
+{
+    if let Some(gov) = governance::get_state(state)? {
+        return Ok(governance::check_member(&gov, msg));
+    }
+    if let Some(acl) = access::get_state(state)? {
+        return Ok(
+            (msg.from != acl.governor).then(|| format!("sender {} is not the governor", msg.from))
+        );
+    }
+    Ok(Some(
+        "dead letters can only be retried on chains with governance".to_owned(),
+    ))
+}
+
+/// Deliver a dead letter again, on behalf of governance.
+///
+/// The nonce of the letter has been taken by a no-op when it failed, so it can't be
+/// applied through the gateway again; instead its value and parameters are sent to the
+/// recipient directly, from the gateway, which has been holding the funds.
+///
+/// The message itself is executed as a simple send, so that the sender is charged for
+/// gas and its nonce is incremented, even if the retry is rejected; if the retry fails,
+/// the letter stays in the queue with the new failure reason and the receipt shows
+/// [ExitCode::USR_ILLEGAL_STATE].
+pub fn execute_retry<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    if let Some(reason) = check_retrier(state, &msg)? {
+        return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+    }
+
+    let params: RetryParams = match msg.params.deserialize() {
+        Ok(params) => params,
+        Err(e) => {
+            return state.execute_rejected(
+                msg,
+                ExitCode::USR_ILLEGAL_ARGUMENT,
+                format!("invalid retry parameters: {e}"),
+            )
+        }
+    };
+
+    let mut dl = get_state(state)?;
+
+    let letter = match dl.get(state.state_tree_mut().store(), params.id)? {
+        Some(letter) => letter,
+        None => {
+            return state.execute_rejected(
+                msg,
+                ExitCode::USR_NOT_FOUND,
+                format!("dead letter {} not found", params.id),
+            )
+        }
+    };
+
+    if !governance::is_topdown_sender_allowed(state, &letter.msg)? {
+        return state.execute_rejected(
+            msg,
+            ExitCode::USR_FORBIDDEN,
+            format!(
+                "sender of dead letter {} is blocked by governance",
+                params.id
+            ),
+        );
+    }
+
+    let send = FvmMessage {
+        method_num: METHOD_SEND,
+        params: RawBytes::default(),
+        value: TokenAmount::zero(),
+        ..msg
+    };
+
+    let (mut apply_ret, emitters) = state.execute_explicit(send)?;
+
+    if !apply_ret.msg_receipt.exit_code.is_success() {
+        return Ok((apply_ret, emitters));
+    }
+
+    let failure = match deliver(state, &letter.msg)? {
+        Ok(()) => None,
+        Err(reason) => Some(reason),
+    };
+
+    let height = state.block_height();
+    let store = state.state_tree_mut().store();
+    match failure {
+        None => {
+            tracing::info!(id = letter.id, "dead letter delivered");
+            dl.remove(store, letter.id)?;
+        }
+        Some(reason) => {
+            tracing::warn!(id = letter.id, reason, "dead letter failed again");
+            dl.failed(store, letter.id, height, reason)?;
+            apply_ret.msg_receipt.exit_code = ExitCode::USR_ILLEGAL_STATE;
+        }
+    }
+
+    set_state(state, &dl).context("failed to update dead letters")?;
+
+    Ok((apply_ret, emitters))
+}
+
+/// Send the value and parameters of a top-down message from the gateway to its recipient.
+///
+/// Returns the reason of the failure if the recipient can't be resolved or the call fails,
+/// in which case the funds stay with the gateway.
+fn deliver<DB>(state: &mut FvmExecState<DB>, msg: &CrossMsg) -> anyhow::Result<Result<(), String>>
+where
+    DB: Blockstore + 'static,
+{
+    let to = match msg.msg.to.raw_addr() {
+        Ok(to) => to,
+        Err(e) => return Ok(Err(format!("invalid recipient: {e}"))),
+    };
+
+    let send = FvmMessage {
+        version: Default::default(),
+        from: GATEWAY_ACTOR_ADDR,
+        to,
+        sequence: 0,
+        value: msg.msg.value.clone(),
+        method_num: msg.msg.method,
+        params: msg.msg.params.clone(),
+        gas_limit: fvm_shared::BLOCK_GAS_LIMIT,
+        gas_fee_cap: TokenAmount::zero(),
+        gas_premium: TokenAmount::zero(),
+    };
+
+    let (ret, _) = state.execute_implicit(send)?;
+
+    if ret.msg_receipt.exit_code.is_success() {
+        Ok(Ok(()))
+    } else {
+        let reason = ret
+            .failure_info
+            .map(|f| f.to_string())
+            .unwrap_or_else(|| format!("exit code {}", ret.msg_receipt.exit_code));
+        Ok(Err(reason))
+    }
+}
+
+/// Save the dead letters, creating the actor if this is the first one.
+fn set_state<DB>(state: &mut FvmExecState<DB>, dl: &deadletter::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let code = *state
+        .builtin_actors()
+        .code_by_id(placeholder::PLACEHOLDER_ACTOR_CODE_ID)
+        .ok_or_else(|| anyhow!("can't find the placeholder actor in the manifest"))?;
+
+    let state_tree = state.state_tree_mut();
+    let state_cid = state_tree.store().put_cbor(dl, Code::Blake2b256)?;
+
+    let actor = match state_tree.get_actor(DEADLETTER_ACTOR_ID)? {
+        Some(actor) => ActorState {
+            state: state_cid,
+            ..actor
+        },
+        None => ActorState {
+            code,
+            state: state_cid,
+            sequence: 0,
+            balance: TokenAmount::default(),
+            delegated_address: None,
+        },
+    };
+
+    state_tree.set_actor(DEADLETTER_ACTOR_ID, actor);
+    Ok(())
+}
@@ -0,0 +1,22 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fendermint_vm_actor_interface::ethblockhash::ETH_BLOCK_HASH_ACTOR_ID;
+use fendermint_vm_message::block_hash::EthBlockHashVersion;
+use fvm_ipld_blockstore::Blockstore;
+
+use super::state::FvmExecState;
+
+impl<DB> FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    /// The derivation of the block hashes the Ethereum API exposes, chosen at genesis.
+    pub fn eth_block_hash_version(&mut self) -> anyhow::Result<EthBlockHashVersion> {
+        let actor = self.state_tree_mut().get_actor(ETH_BLOCK_HASH_ACTOR_ID)?;
+        Ok(match actor {
+            Some(_) => EthBlockHashVersion::V1,
+            None => EthBlockHashVersion::V0,
+        })
+    }
+}
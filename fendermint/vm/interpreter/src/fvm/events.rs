@@ -0,0 +1,24 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Events emitted on behalf of the actors which are handled by the interpreter.
+
+use fvm_ipld_encoding::IPLD_RAW;
+use fvm_shared::{
+    event::{ActorEvent, Entry, Flags, StampedEvent},
+    ActorID,
+};
+
+/// An indexed entry with a raw value, which is what all of these events consist of.
+pub fn entry(key: &str, value: impl Into<Vec<u8>>) -> Entry {
+    Entry {
+        flags: Flags::FLAG_INDEXED_ALL,
+        key: key.to_owned(),
+        codec: IPLD_RAW,
+        value: value.into(),
+    }
+}
+
+/// An event emitted by an actor, made of the given entries.
+pub fn event(emitter: ActorID, entries: Vec<Entry>) -> StampedEvent {
+    StampedEvent::new(emitter, ActorEvent { entries })
+}
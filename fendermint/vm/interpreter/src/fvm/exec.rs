@@ -6,18 +6,22 @@ use async_trait::async_trait;
 use fendermint_vm_genesis::{Power, Validator};
 use std::collections::HashMap;
 
-use fendermint_vm_actor_interface::{cron, system};
-use fvm::executor::ApplyRet;
+use fendermint_vm_actor_interface::{beacon::BEACON_ACTOR_ADDR, cron, system};
+use fvm::executor::{ApplyFailure, ApplyRet};
 use fvm_ipld_blockstore::Blockstore;
-use fvm_shared::{address::Address, ActorID, MethodNum, BLOCK_GAS_LIMIT};
+use fvm_shared::{
+    address::Address, error::ExitCode, event::StampedEvent, ActorID, MethodNum, BLOCK_GAS_LIMIT,
+};
 use tendermint_rpc::Client;
 
 use crate::ExecInterpreter;
 
 use super::{
+    access, beacon, chainmetadata,
     checkpoint::{self, PowerUpdates},
-    state::FvmExecState,
-    FvmMessage, FvmMessageInterpreter,
+    checkpointarchive, deadletter, governance, policy, rewards, scheduler,
+    state::{reject_message, ExecResult, FvmExecState},
+    topdown, validators, FvmMessage, FvmMessageInterpreter,
 };
 
 /// The return value extended with some things from the message that
@@ -34,6 +38,17 @@ pub struct FvmApplyRet {
     pub emitters: HashMap<ActorID, Address>,
 }
 
+/// The outcome of the beginning of a block.
+pub struct FvmBeginRet {
+    /// The result of the cron message.
+    pub cron: FvmApplyRet,
+    /// Events emitted at the beginning of the block outside of the cron, e.g. about the
+    /// progress of a shutdown or by deferred top-down messages; they don't belong to any message.
+    pub events: Vec<StampedEvent>,
+    /// Delegated addresses of the emitters of these events, if they have one.
+    pub emitters: HashMap<ActorID, Address>,
+}
+
 #[async_trait]
 impl<DB, TC> ExecInterpreter for FvmMessageInterpreter<DB, TC>
 where
@@ -42,7 +57,7 @@ where
 {
     type State = FvmExecState<DB>;
     type Message = FvmMessage;
-    type BeginOutput = FvmApplyRet;
+    type BeginOutput = FvmBeginRet;
     type DeliverOutput = FvmApplyRet;
     /// Return validator power updates.
     /// Currently ignoring events as there aren't any emitted by the smart contract,
@@ -55,6 +70,42 @@ where
     ) -> anyhow::Result<(Self::State, Self::BeginOutput)> {
         // Block height (FVM epoch) as sequence is intentional
         let height = state.block_height();
+
+        chainmetadata::record_block(&mut state).context("failed to record block metadata")?;
+
+        let governance_events =
+            governance::begin_block(&mut state).context("failed to apply governance parameters")?;
+
+        let beacon_events =
+            beacon::begin_block(&mut state).context("failed to finalize the beacon round")?;
+
+        let reward_events =
+            rewards::begin_block(&mut state).context("failed to distribute rewards")?;
+
+        let mut events = governance_events;
+        events.extend(beacon_events);
+        events.extend(reward_events);
+        let mut emitters = HashMap::new();
+
+        // Top-down messages over the batch limit of an earlier block; nothing more
+        // comes into the subnet once it's shutting down.
+        let deferred = if governance::check_shutdown(&mut state)?.is_some() {
+            Vec::new()
+        } else {
+            governance::take_deferred_topdown_msgs(&mut state)
+                .context("failed to take deferred top-down messages")?
+        };
+
+        if !deferred.is_empty() {
+            let count = deferred.len();
+            let ret = topdown::execute_topdown_msgs(&self.gateway, &mut state, deferred)
+                .await
+                .context("failed to execute deferred top-down messages")?;
+            events.extend(ret.apply_ret.events);
+            emitters.extend(ret.emitters);
+            tracing::debug!(count, "applied deferred top-down messages");
+        }
+
         // Arbitrarily large gas limit for cron (matching how Forest does it, which matches Lotus).
         // XXX: Our blocks are not necessarily expected to be 30 seconds apart, so the gas limit might be wrong.
         let gas_limit = BLOCK_GAS_LIMIT * 10000;
@@ -76,22 +127,48 @@ where
             gas_premium: Default::default(),
         };
 
-        let (apply_ret, emitters) = state.execute_implicit(msg)?;
+        let (mut apply_ret, mut cron_emitters) = state.execute_implicit(msg)?;
 
         // Failing cron would be fatal.
         if let Some(err) = apply_ret.failure_info {
             anyhow::bail!("failed to apply block cron message: {}", err);
         }
 
-        let ret = FvmApplyRet {
+        let (scheduled_events, scheduled_emitters) = scheduler::execute_due_calls(&mut state)
+            .context("failed to execute scheduled calls")?;
+
+        // The scheduled calls are run by the cron, as far as anyone can tell.
+        apply_ret.events.extend(scheduled_events);
+        cron_emitters.extend(scheduled_emitters);
+
+        let cron = FvmApplyRet {
             apply_ret,
             from,
             to,
             method_num,
             gas_limit,
+            emitters: cron_emitters,
+        };
+
+        let ret = FvmBeginRet {
+            cron,
+            events,
             emitters,
         };
 
+        // The experiment only replays the transactions, so it starts from the state they see.
+        #[cfg(feature = "block-stm")]
+        if let Some(ref stm) = self.block_stm {
+            let state_root = state
+                .flush()
+                .context("failed to flush the state for the Block-STM experiment")?;
+            let params = super::state::FvmStateParams {
+                state_root,
+                ..state.initial_state_params()
+            };
+            stm.begin_block(height, params, state.executed_msgs());
+        }
+
         Ok((state, ret))
     }
 
@@ -105,12 +182,62 @@ where
         let method_num = msg.method_num;
         let gas_limit = msg.gas_limit;
 
-        let (apply_ret, emitters) = if from == system::SYSTEM_ACTOR_ADDR {
-            state.execute_implicit(msg)?
-        } else {
-            state.execute_explicit(msg)?
+        let span = tracing::info_span!(
+            "fvm_deliver",
+            from = from.to_string(),
+            to = to.to_string(),
+            method_num,
+            sequence = msg.sequence
+        );
+
+        // Only the messages which go through the regular path can be executed in parallel.
+        #[cfg(feature = "block-stm")]
+        let stm_msg = match self.block_stm {
+            Some(ref stm) => {
+                if from == system::SYSTEM_ACTOR_ADDR
+                    || governance::is_governance(&msg)
+                    || beacon::is_beacon(&msg)
+                    || deadletter::is_retry(&msg)
+                    || rewards::is_claim(&msg)
+                {
+                    stm.not_comparable();
+                    None
+                } else {
+                    Some((stm, msg.clone(), state.executed_msgs()))
+                }
+            }
+            None => None,
         };
 
+        let (apply_ret, emitters) = span.in_scope(|| {
+            if from == system::SYSTEM_ACTOR_ADDR {
+                return state.execute_implicit(msg);
+            }
+            if let Some(reason) = governance::check_shutdown(&mut state)? {
+                return Ok(reject_message(
+                    ExitCode::USR_FORBIDDEN,
+                    ApplyFailure::PreValidation(reason),
+                ));
+            }
+            if let Some(reason) = policy::check_policy(self.policy.as_deref(), &mut state, &msg)? {
+                return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+            }
+            if !state.take_block_gas(gas_limit) {
+                governance::block_gas_exceeded(&mut state, msg)
+            } else {
+                execute_user_msg(&mut state, msg)
+            }
+        })?;
+
+        // Messages rejected before execution, e.g. over the block gas limit, are not replayed;
+        // if the rejection changed the state, the experiment notices it at the next message.
+        #[cfg(feature = "block-stm")]
+        if let Some((stm, msg, executed)) = stm_msg {
+            if !matches!(apply_ret.failure_info, Some(ApplyFailure::PreValidation(_))) {
+                stm.record(msg, &apply_ret.msg_receipt, executed, state.executed_msgs());
+            }
+        }
+
         tracing::info!(
             height = state.block_height(),
             from = from.to_string(),
@@ -135,9 +262,37 @@ where
 
     async fn end(&self, mut state: Self::State) -> anyhow::Result<(Self::State, Self::EndOutput)> {
         let updates = if let Some((checkpoint, updates)) =
-            checkpoint::maybe_create_checkpoint(&self.gateway, &mut state)
+            checkpoint::maybe_create_checkpoint(&self.gateway, &mut state, &self.power_table_cache)
                 .context("failed to create checkpoint")?
         {
+            governance::record_final_checkpoint(&mut state, checkpoint.block_height)
+                .context("failed to record the final checkpoint")?;
+
+            if let Some(export) = checkpointarchive::maybe_archive_checkpoints(
+                &self.gateway,
+                &mut state,
+                checkpoint.block_height,
+            )
+            .context("failed to archive checkpoints")?
+            {
+                // The archive is only a convenience for this node; the root in the state
+                // means anyone else's copy can be verified, so failing here isn't fatal.
+                match self.checkpoint_archive_dir {
+                    Some(ref dir) => {
+                        let root = export.archive.root;
+                        if let Err(e) = checkpointarchive::export_archive(dir, export).await {
+                            tracing::error!(error =? e, root = root.to_string(), "failed to export checkpoint archive");
+                        }
+                    }
+                    None => {
+                        tracing::debug!(
+                            root = export.archive.root.to_string(),
+                            "checkpoint archive not exported"
+                        );
+                    }
+                }
+            }
+
             // Asynchronously broadcast signature, if validating.
             if let Some(ref ctx) = self.validator_ctx {
                 // Do not resend past signatures.
@@ -183,6 +338,70 @@ where
             PowerUpdates::default()
         };
 
+        // Contribute to the beacon, if validating; each message is only sent once per phase.
+        if let Some(ref ctx) = self.validator_ctx {
+            if !self.syncing().await {
+                if let Some((method_num, params)) = beacon::next_contribution(
+                    &mut state,
+                    &self.beacon_progress,
+                    &ctx.secret_key,
+                    ctx.broadcaster.address(),
+                )
+                .context("failed to prepare beacon contribution")?
+                {
+                    let broadcaster = ctx.broadcaster.clone();
+                    let chain_id = state.chain_id();
+                    let height = state.block_height();
+
+                    tokio::spawn(async move {
+                        let res = broadcaster
+                            .transaction(
+                                BEACON_ACTOR_ADDR,
+                                method_num,
+                                params,
+                                beacon::BEACON_GAS_LIMIT,
+                                chain_id,
+                            )
+                            .await;
+
+                        if let Err(e) = res {
+                            tracing::error!(error =? e, height, method_num, "error broadcasting beacon contribution");
+                        }
+                    });
+                }
+            }
+        }
+
+        validators::record_power_updates(&mut state, &updates.0)
+            .context("failed to record validator power updates")?;
+
+        #[cfg(feature = "block-stm")]
+        if let Some(ref stm) = self.block_stm {
+            stm.end_block();
+        }
+
         Ok((state, updates.0))
     }
 }
+
+/// Execute a message of a user, passing the ones the interpreter handles natively to their
+/// handlers, since those actors only have a placeholder code in the state.
+///
+/// The checks execute the messages the same way as the delivery, so that what gets into the
+/// mempool is what can be executed in a block.
+pub(crate) fn execute_user_msg<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    if governance::is_governance(&msg) {
+        governance::execute(state, msg)
+    } else if beacon::is_beacon(&msg) {
+        beacon::execute(state, msg)
+    } else if deadletter::is_retry(&msg) {
+        deadletter::execute_retry(state, msg)
+    } else if rewards::is_claim(&msg) {
+        rewards::execute_claim(state, msg)
+    } else {
+        access::execute_explicit(state, msg)
+    }
+}
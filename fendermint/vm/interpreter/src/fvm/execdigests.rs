@@ -0,0 +1,293 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::cell::RefCell;
+
+use anyhow::{anyhow, bail, Context};
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use fendermint_vm_actor_interface::execdigests::{
+    self, merkle_proof, merkle_root, BlockDigest, ReceiptProof, TxReceipt, EXEC_DIGESTS_ACTOR_ID,
+};
+use fvm::state_tree::StateTree;
+use fvm_ipld_blockstore::{Block, Blockstore, MemoryBlockstore};
+use fvm_ipld_encoding::{CborStore, RawBytes, DAG_CBOR};
+use fvm_shared::clock::ChainEpoch;
+
+use super::state::{FvmExecState, FvmStateParams};
+use crate::bytes::HasExecDigests;
+
+impl<DB> HasExecDigests for FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    fn records_exec_digests(&mut self) -> anyhow::Result<bool> {
+        let actor = self.state_tree_mut().get_actor(EXEC_DIGESTS_ACTOR_ID)?;
+        Ok(actor.is_some())
+    }
+
+    fn push_tx_receipt(&mut self, receipt: TxReceipt, events: Vec<Vec<u8>>) {
+        self.collect_tx_receipt(receipt, events)
+    }
+
+    /// Record the roots of the receipts and events collected during the block,
+    /// and store the receipts, so proofs can be built from them later.
+    ///
+    /// Does nothing if the chain was started without the execution digests actor.
+    fn record_exec_digest(&mut self) -> anyhow::Result<()> {
+        let (receipts, events) = self.take_tx_receipts();
+        let height = self.block_height();
+
+        let state_tree = self.state_tree_mut();
+
+        let mut actor = match state_tree.get_actor(EXEC_DIGESTS_ACTOR_ID)? {
+            Some(actor) => actor,
+            None => return Ok(()),
+        };
+
+        let mut st: execdigests::State = state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("execution digests state not found"))?;
+
+        let leaves = receipts.iter().map(|r| r.leaf()).collect::<Vec<_>>();
+
+        let digest = BlockDigest {
+            height,
+            tx_count: receipts.len() as u64,
+            receipts_root: merkle_root(&leaves),
+            events_root: merkle_root(&events),
+            receipts: state_tree
+                .store()
+                .put_cbor(&receipts, Code::Blake2b256)
+                .context("failed to store receipts")?,
+        };
+
+        st.push(digest);
+
+        actor.state = state_tree
+            .store()
+            .put_cbor(&st, Code::Blake2b256)
+            .context("failed to store execution digests")?;
+
+        state_tree.set_actor(EXEC_DIGESTS_ACTOR_ID, actor);
+
+        Ok(())
+    }
+}
+
+/// Remembers the blocks read through it, which are the path to whatever was looked up.
+struct RecordingBlockstore<'a, DB> {
+    inner: &'a DB,
+    blocks: RefCell<Vec<(Cid, Vec<u8>)>>,
+}
+
+impl<DB: Blockstore> Blockstore for RecordingBlockstore<'_, DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let block = self.inner.get(k)?;
+        if let Some(ref bz) = block {
+            let mut blocks = self.blocks.borrow_mut();
+            if !blocks.iter().any(|(c, _)| c == k) {
+                blocks.push((*k, bz.clone()));
+            }
+        }
+        Ok(block)
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        bail!("proofs are built from a read-only view of the state")
+    }
+}
+
+/// Build the proof of a receipt in a block from the state with the given parameters, if the
+/// chain records execution digests, the block is within the lookback and the index is in range.
+///
+/// Besides the Merkle proof of the receipt, it includes the blocks on the path from the app
+/// hash, which is the CID of the state parameters, to the state of the execution digests actor.
+pub fn receipt_proof<DB: Blockstore>(
+    store: &DB,
+    state_params: &FvmStateParams,
+    height: ChainEpoch,
+    index: u64,
+) -> anyhow::Result<Option<ReceiptProof>> {
+    let recorder = RecordingBlockstore {
+        inner: store,
+        blocks: Default::default(),
+    };
+    let state_tree = StateTree::new_from_root(&recorder, &state_params.state_root)?;
+
+    let actor = match state_tree.get_actor(EXEC_DIGESTS_ACTOR_ID)? {
+        Some(actor) => actor,
+        None => return Ok(None),
+    };
+    let digests: execdigests::State = recorder
+        .get_cbor(&actor.state)?
+        .ok_or_else(|| anyhow!("execution digests state not found"))?;
+
+    let digest = match digests.get(height) {
+        Some(digest) => digest.clone(),
+        None => return Ok(None),
+    };
+    let receipts: Vec<TxReceipt> = store
+        .get_cbor(&digest.receipts)?
+        .ok_or_else(|| anyhow!("receipts not found"))?;
+    let leaves = receipts.iter().map(|r| r.leaf()).collect::<Vec<_>>();
+
+    let proof = match merkle_proof(&leaves, index as usize) {
+        Some(proof) => proof,
+        None => return Ok(None),
+    };
+
+    let mut state_blocks = vec![RawBytes::new(fvm_ipld_encoding::to_vec(state_params)?)];
+    state_blocks.extend(
+        recorder
+            .blocks
+            .into_inner()
+            .into_iter()
+            .map(|(_, bz)| RawBytes::new(bz)),
+    );
+
+    Ok(Some(ReceiptProof {
+        digest,
+        receipt: receipts[index as usize].clone(),
+        proof,
+        state_blocks,
+    }))
+}
+
+/// Check a receipt proof against the app hash in the header following the block of the state
+/// the proof was built from: the receipt has to be in the digest, and the digest in the state
+/// of the execution digests actor the app hash commits to.
+///
+/// Missing or altered blocks make the check fail; errors are only returned for blocks which
+/// hash correctly but can't be decoded.
+pub fn verify_receipt_proof(app_hash: &[u8], proof: &ReceiptProof) -> anyhow::Result<bool> {
+    if !proof.verify() {
+        return Ok(false);
+    }
+    let (params, blocks) = match proof.state_blocks.split_first() {
+        Some(split) => split,
+        None => return Ok(false),
+    };
+    let params_cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(params.bytes()));
+    if params_cid.to_bytes() != app_hash {
+        return Ok(false);
+    }
+    let params: FvmStateParams = fvm_ipld_encoding::from_slice(params.bytes())?;
+
+    // Only the blocks which hash to the CIDs they are looked up by can be found.
+    let store = MemoryBlockstore::new();
+    for block in blocks {
+        store.put(Code::Blake2b256, &Block::new(DAG_CBOR, block.bytes()))?;
+    }
+
+    let state_tree = match StateTree::new_from_root(&store, &params.state_root) {
+        Ok(state_tree) => state_tree,
+        Err(_) => return Ok(false),
+    };
+    let actor = match state_tree.get_actor(EXEC_DIGESTS_ACTOR_ID) {
+        Ok(Some(actor)) => actor,
+        _ => return Ok(false),
+    };
+    let digests: execdigests::State = match store.get_cbor(&actor.state) {
+        Ok(Some(digests)) => digests,
+        _ => return Ok(false),
+    };
+
+    Ok(digests.get(proof.digest.height) == Some(&proof.digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_actor_interface::execdigests::{merkle_root, TxReceipt};
+    use fendermint_vm_core::Timestamp;
+    use fendermint_vm_genesis::Genesis;
+    use fvm::engine::MultiEngine;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::error::ExitCode;
+
+    use super::{receipt_proof, verify_receipt_proof};
+    use crate::bytes::HasExecDigests;
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::testing;
+
+    /// Create the genesis state of a chain which records execution digests.
+    async fn genesis_state() -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(Genesis {
+            exec_digests: true,
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    fn app_hash(params: &FvmStateParams) -> Vec<u8> {
+        fendermint_vm_message::cid(params).unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn receipt_proof_verifies_against_app_hash() {
+        let (store, params) = genesis_state().await;
+        let multi_engine = MultiEngine::default();
+
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 1, params.clone())
+            .expect("failed to create state");
+
+        assert!(state.records_exec_digests().unwrap());
+
+        let receipts = (0..3u8)
+            .map(|i| TxReceipt {
+                tx_hash: [i; 32],
+                exit_code: Some(ExitCode::new(i as u32)),
+                gas_used: i as u64 * 100,
+                return_data: RawBytes::new(vec![i]),
+                events_root: merkle_root(&[vec![i]]),
+            })
+            .collect::<Vec<_>>();
+
+        for r in receipts.iter() {
+            state.push_tx_receipt(r.clone(), vec![vec![r.tx_hash[0]]]);
+        }
+        state.record_exec_digest().expect("failed to record digest");
+
+        let (state_root, _, _) = state.commit().expect("failed to commit");
+        let params = FvmStateParams {
+            state_root,
+            ..params
+        };
+
+        let proof = receipt_proof(&store, &params, 1, 2)
+            .expect("failed to build proof")
+            .expect("receipt is in the block");
+
+        assert_eq!(proof.receipt, receipts[2]);
+        assert_eq!(proof.digest.tx_count, 3);
+        assert_eq!(
+            proof.digest.events_root,
+            merkle_root(&[vec![0], vec![1], vec![2]])
+        );
+        assert!(verify_receipt_proof(&app_hash(&params), &proof).unwrap());
+
+        // A different state doesn't have the same app hash.
+        let other = FvmStateParams {
+            timestamp: Timestamp(params.timestamp.0 + 1),
+            ..params.clone()
+        };
+        assert!(!verify_receipt_proof(&app_hash(&other), &proof).unwrap());
+
+        // The digest has to be the one in the state, not just agree with the receipt.
+        let mut forged = proof.clone();
+        forged.digest.height = 2;
+        assert!(forged.verify());
+        assert!(!verify_receipt_proof(&app_hash(&params), &forged).unwrap());
+
+        // Every block on the path is needed.
+        let mut incomplete = proof.clone();
+        incomplete.state_blocks.pop();
+        assert!(!verify_receipt_proof(&app_hash(&params), &incomplete).unwrap());
+
+        // Nothing to prove outside the block, or for blocks without digests.
+        assert!(receipt_proof(&store, &params, 1, 3).unwrap().is_none());
+        assert!(receipt_proof(&store, &params, 2, 0).unwrap().is_none());
+    }
+}
@@ -1,30 +1,130 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
-use cid::Cid;
-use fvm::externs::{Chain, Consensus, Externs, Rand};
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Context};
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use fendermint_vm_actor_interface::beacon::{self, BEACON_ACTOR_ID};
+use fendermint_vm_actor_interface::chainmetadata::{self, CHAIN_METADATA_ACTOR_ID};
+use fvm::{
+    externs::{Chain, Consensus, Externs, Rand},
+    state_tree::StateTree,
+};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
 use fvm_shared::clock::ChainEpoch;
 
-/// Dummy externs - these are related to Expected Consensus,
-/// which I believe we have nothing to do with.
-pub struct FendermintExterns;
+/// Externs backed by the recent block metadata recorded in the state.
+///
+/// The data is loaded from the state root the execution starts from,
+/// so every validator sees the same values at the same height.
+#[derive(Default)]
+pub struct FendermintExterns {
+    /// Recent blocks, if the chain metadata actor exists.
+    chain_metadata: Option<chainmetadata::State>,
+    /// Recent beacon values, if the beacon actor exists; updated when a round is closed.
+    beacon: RwLock<Option<beacon::State>>,
+}
+
+impl FendermintExterns {
+    /// Load the recent block metadata from the state.
+    pub fn load<DB: Blockstore>(store: &DB, state_root: Cid) -> anyhow::Result<Self> {
+        let state_tree =
+            StateTree::new_from_root(store, &state_root).context("failed to load state tree")?;
+
+        let chain_metadata = match state_tree.get_actor(CHAIN_METADATA_ACTOR_ID)? {
+            None => None,
+            Some(actor) => {
+                let state = store
+                    .get_cbor(&actor.state)?
+                    .ok_or_else(|| anyhow!("chain metadata state not found"))?;
+                Some(state)
+            }
+        };
+
+        let beacon = match state_tree.get_actor(BEACON_ACTOR_ID)? {
+            None => None,
+            Some(actor) => {
+                let state = store
+                    .get_cbor(&actor.state)?
+                    .ok_or_else(|| anyhow!("beacon state not found"))?;
+                Some(state)
+            }
+        };
+
+        Ok(Self {
+            chain_metadata,
+            beacon: RwLock::new(beacon),
+        })
+    }
+
+    /// Replace the beacon values after the round was closed at the beginning of a block,
+    /// so the messages of the block draw on the value it produced, rather than the one before.
+    pub fn set_beacon(&self, beacon: beacon::State) {
+        *self.beacon.write().unwrap() = Some(beacon);
+    }
+
+    fn get_block(&self, epoch: ChainEpoch) -> anyhow::Result<&chainmetadata::BlockMetadata> {
+        self.chain_metadata
+            .as_ref()
+            .and_then(|s| s.get(epoch))
+            .ok_or_else(|| anyhow!("block {epoch} is not within the lookback window"))
+    }
+
+    /// Derive randomness from the hash of the block at the given epoch,
+    /// the same way Lotus draws it from the beacon or ticket of a tipset.
+    fn draw_randomness(
+        &self,
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> anyhow::Result<[u8; 32]> {
+        let block = self.get_block(round)?;
+        Ok(draw(pers, block.block_cid.hash().digest(), round, entropy))
+    }
+}
+
+fn draw(pers: i64, seed: &[u8], round: ChainEpoch, entropy: &[u8]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(8 + 32 + 8 + entropy.len());
+    data.extend_from_slice(&pers.to_be_bytes());
+    data.extend_from_slice(&blake2b_256(seed));
+    data.extend_from_slice(&round.to_be_bytes());
+    data.extend_from_slice(entropy);
+    blake2b_256(&data)
+}
+
+fn blake2b_256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(Code::Blake2b256.digest(data).digest());
+    hash
+}
 
 impl Rand for FendermintExterns {
     fn get_chain_randomness(
         &self,
-        _pers: i64,
-        _round: ChainEpoch,
-        _entropy: &[u8],
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
     ) -> anyhow::Result<[u8; 32]> {
-        todo!("might need randomness")
+        self.draw_randomness(pers, round, entropy)
     }
 
+    /// Draw from the value of the validators' beacon in effect at the given epoch;
+    /// without a beacon, or before it produced its first value, the block hashes stand in for it.
     fn get_beacon_randomness(
         &self,
-        _pers: i64,
-        _round: ChainEpoch,
-        _entropy: &[u8],
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
     ) -> anyhow::Result<[u8; 32]> {
-        unimplemented!("not expecting to use the beacon")
+        let beacon = self.beacon.read().unwrap();
+        match beacon.as_ref().and_then(|b| b.at_height(round)) {
+            Some(entry) => Ok(draw(pers, &entry.value, round, entropy)),
+            None => self.draw_randomness(pers, round, entropy),
+        }
     }
 }
 
@@ -40,8 +140,9 @@ impl Consensus for FendermintExterns {
 }
 
 impl Chain for FendermintExterns {
-    fn get_tipset_cid(&self, _epoch: ChainEpoch) -> anyhow::Result<Cid> {
-        unimplemented!("not expecting to use tipsets")
+    /// Every block is a tipset of its own, identified by the Tendermint block hash.
+    fn get_tipset_cid(&self, epoch: ChainEpoch) -> anyhow::Result<Cid> {
+        self.get_block(epoch).map(|b| b.block_cid)
     }
 }
 
@@ -0,0 +1,18 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fendermint_vm_actor_interface::fastfinality::FAST_FINALITY_ACTOR_ID;
+use fvm_ipld_blockstore::Blockstore;
+
+use super::state::FvmExecState;
+
+impl<DB> FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    /// Whether the genesis chose the fast top-down finality mode.
+    pub fn fast_topdown_finality(&mut self) -> anyhow::Result<bool> {
+        let actor = self.state_tree_mut().get_actor(FAST_FINALITY_ACTOR_ID)?;
+        Ok(actor.is_some())
+    }
+}
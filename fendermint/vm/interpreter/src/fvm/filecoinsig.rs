@@ -0,0 +1,18 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fendermint_vm_actor_interface::filecoinsig::FILECOIN_SIG_ACTOR_ID;
+use fvm_ipld_blockstore::Blockstore;
+
+use super::state::FvmExecState;
+use crate::signed::HasFilecoinSignatures;
+
+impl<DB> HasFilecoinSignatures for FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    fn accepts_filecoin_signatures(&mut self) -> anyhow::Result<bool> {
+        let actor = self.state_tree_mut().get_actor(FILECOIN_SIG_ACTOR_ID)?;
+        Ok(actor.is_some())
+    }
+}
@@ -14,10 +14,14 @@ use fendermint_vm_actor_interface::diamond::{EthContract, EthContractMap};
 use fendermint_vm_actor_interface::eam::EthAddress;
 use fendermint_vm_actor_interface::ipc::IPC_CONTRACTS;
 use fendermint_vm_actor_interface::{
-    account, burntfunds, cron, eam, init, ipc, reward, system, EMPTY_ARR,
+    accesscontrol, account, beacon, blocktime, burntfunds, chainmetadata, checkpointarchive,
+    contractbook, cron, diamondlayout, eam, ethblockhash, execdigests, fastfinality, filecoinsig,
+    governance, init, ipc, placeholder, policy, reward, rewardpool, scheduler, syscontracts,
+    system, topdownnonces, txcompression, txorder, validators, EMPTY_ARR,
 };
 use fendermint_vm_core::{chainid, Timestamp};
 use fendermint_vm_genesis::{ActorMeta, Genesis, Power, PowerScale, Validator};
+use fendermint_vm_message::chain::MAX_DECOMPRESSED_SIZE;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_shared::chainid::ChainID;
 use fvm_shared::econ::TokenAmount;
@@ -65,6 +69,7 @@ where
     /// * rewards (placeholder)
     /// * accounts
     /// * IPC
+    /// * access control (optional)
     ///
     /// TODO:
     /// * faucet?
@@ -103,7 +108,7 @@ where
             timestamp: genesis.timestamp,
             network_version: genesis.network_version,
             circ_supply: circ_supply(&genesis),
-            base_fee: genesis.base_fee,
+            base_fee: base_fee(&genesis),
             power_scale: genesis.power_scale,
             validators,
         };
@@ -119,6 +124,7 @@ where
         if genesis.ipc.is_some() {
             eth_contracts.extend(IPC_CONTRACTS.clone());
         }
+        eth_contracts.extend(syscontracts::system_contracts(&genesis.system_contracts));
 
         eth_builtin_ids.extend(eth_contracts.values().map(|c| c.actor_id));
         eth_root_contracts.extend(eth_contracts.keys());
@@ -228,6 +234,232 @@ where
             )
             .context("failed to create reward actor")?;
 
+        // Access control is enforced by the interpreter; the actor only holds the state,
+        // so it's created with placeholder code which doesn't do anything on its own.
+        if let Some(acl) = genesis.access_control.clone() {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    accesscontrol::ACCESS_CONTROL_ACTOR_ID,
+                    &accesscontrol::State::from(acl),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create access control actor")?;
+        }
+
+        // Same for governance: the interpreter handles the proposals and applies the parameters.
+        if let Some(gov) = genesis.governance.clone() {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    governance::GOVERNANCE_ACTOR_ID,
+                    &governance::State::from(gov),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create governance actor")?;
+        }
+
+        // Bottom-up checkpoints pruned from the gateway are archived by the interpreter.
+        if let Some(ref ipc_params) = genesis.ipc {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    checkpointarchive::CHECKPOINT_ARCHIVE_ACTOR_ID,
+                    &checkpointarchive::State::new(ipc_params.checkpoint_retention),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create checkpoint archive actor")?;
+
+            // The nonces of the applied top-down messages are checked and recorded by the interpreter.
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    topdownnonces::TOPDOWN_NONCES_ACTOR_ID,
+                    &topdownnonces::State::default(),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create top-down nonces actor")?;
+        }
+
+        // System messages to run at fixed heights are sent by the interpreter at the beginning of blocks.
+        if !genesis.scheduled_calls.is_empty() {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    scheduler::SCHEDULER_ACTOR_ID,
+                    &scheduler::State::from(genesis.scheduled_calls.clone()),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create scheduler actor")?;
+        }
+
+        // Compressed transactions are unwrapped by the interpreter, if the actor exists.
+        if genesis.tx_compression {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    txcompression::TX_COMPRESSION_ACTOR_ID,
+                    &txcompression::State {
+                        max_decompressed_size: MAX_DECOMPRESSED_SIZE as u64,
+                    },
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create transaction compression actor")?;
+        }
+
+        // Signatures without the chain ID are only accepted by the interpreter if the actor exists.
+        if genesis.filecoin_signatures {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    filecoinsig::FILECOIN_SIG_ACTOR_ID,
+                    &(),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create Filecoin signatures actor")?;
+        }
+
+        // The digests of every block are recorded by the interpreter, if the actor exists.
+        if genesis.exec_digests {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    execdigests::EXEC_DIGESTS_ACTOR_ID,
+                    &execdigests::State::new(execdigests::DEFAULT_LOOKBACK_LEN),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create execution digests actor")?;
+        }
+
+        // The Ethereum API exposes derived block hashes, if the actor exists.
+        if genesis.eth_block_hash_v1 {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    ethblockhash::ETH_BLOCK_HASH_ACTOR_ID,
+                    &(),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create Ethereum block hash actor")?;
+        }
+
+        // Proposals have to be in the canonical transaction order, if the actor exists.
+        if genesis.canonical_tx_order {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    txorder::TX_ORDER_ACTOR_ID,
+                    &(),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create transaction order actor")?;
+        }
+
+        // The validators follow the parent in the fast top-down finality mode, if the actor exists.
+        if genesis.fast_topdown_finality {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    fastfinality::FAST_FINALITY_ACTOR_ID,
+                    &(),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create fast finality actor")?;
+        }
+
+        // Proposals are only accepted within the interval after the previous block, if the actor exists.
+        if let Some(max_interval) = genesis.max_block_interval {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    blocktime::BLOCK_TIME_ACTOR_ID,
+                    &blocktime::State { max_interval },
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create block time actor")?;
+        }
+
+        // The validators contribute to the beacon with messages handled by the interpreter.
+        if let Some(ref b) = genesis.beacon {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    beacon::BEACON_ACTOR_ID,
+                    &beacon::State::new(b.epoch_length, beacon::DEFAULT_LOOKBACK_LEN),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create beacon actor")?;
+        }
+
+        // Fees are collected into the pool and distributed to the validators by the interpreter.
+        if let Some(ref r) = genesis.rewards {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    rewardpool::REWARD_POOL_ACTOR_ID,
+                    &rewardpool::State::new(
+                        r.epoch_length,
+                        r.inflation_per_epoch.clone(),
+                        r.collect_base_fees,
+                    ),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create reward pool actor")?;
+        }
+
+        // The policy module is consulted by the interpreter; the actor pins which one.
+        if let Some(ref p) = genesis.policy {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    policy::POLICY_ACTOR_ID,
+                    &policy::State::from(p.clone()),
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create policy actor")?;
+        }
+
+        // The validators are recorded by the interpreter, so their identities can be looked up by their keys.
+        let mut validator_book = validators::State::default();
+        for v in out.validators.iter() {
+            validator_book.update(0, v.public_key.0.serialize().to_vec(), v.power.0);
+        }
+        state
+            .create_actor(
+                placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                validators::VALIDATORS_ACTOR_ID,
+                &validator_book,
+                TokenAmount::zero(),
+                None,
+            )
+            .context("failed to create validator address book actor")?;
+
+        // Recent block hashes are recorded by the interpreter, to be looked up by the externs.
+        state
+            .create_actor(
+                placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                chainmetadata::CHAIN_METADATA_ACTOR_ID,
+                &chainmetadata::State::new(chainmetadata::DEFAULT_LOOKBACK_LEN),
+                TokenAmount::zero(),
+                None,
+            )
+            .context("failed to create chain metadata actor")?;
+
         // STAGE 2: Create non-builtin accounts which do not have a fixed ID.
 
         // The next ID is going to be _after_ the accounts, which have already been assigned an ID by the `Init` actor.
@@ -266,6 +498,10 @@ where
 
         let mut deployer = ContractDeployer::<DB>::new(&self.contracts, &eth_contracts);
 
+        deployer
+            .check_abis()
+            .context("contract artifacts are incompatible with the bindings")?;
+
         // Deploy Ethereum libraries.
         for (lib_src, lib_name) in eth_libs {
             deployer.deploy_library(&mut state, &mut next_id, lib_src, &lib_name)?;
@@ -283,6 +519,7 @@ where
                     .facets(ipc::gateway::CONTRACT_NAME)
                     .context("failed to collect gateway facets")?;
 
+                deployer.record_diamond(ipc::gateway::CONTRACT_NAME, &facets)?;
                 deployer.deploy_contract(
                     &mut state,
                     ipc::gateway::CONTRACT_NAME,
@@ -311,6 +548,7 @@ where
                     subnet_manager_selectors: manager_facet.function_selectors,
                 };
 
+                deployer.record_diamond(ipc::registry::CONTRACT_NAME, &facets)?;
                 deployer.deploy_contract(
                     &mut state,
                     ipc::registry::CONTRACT_NAME,
@@ -319,6 +557,37 @@ where
             };
         }
 
+        // Utility contracts, which have no constructor parameters.
+        for kind in genesis.system_contracts.iter().collect::<BTreeSet<_>>() {
+            let (contract_name, _) = syscontracts::system_contract(*kind);
+            deployer.deploy_contract(&mut state, contract_name, ())?;
+        }
+
+        // Record how the diamonds were cut, so facet upgrades can be checked against it.
+        let diamond_layout = deployer.diamond_layout();
+        if !diamond_layout.diamonds.is_empty() {
+            state
+                .create_actor(
+                    placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                    diamondlayout::DIAMOND_LAYOUT_ACTOR_ID,
+                    &diamond_layout,
+                    TokenAmount::zero(),
+                    None,
+                )
+                .context("failed to create diamond layout actor")?;
+        }
+
+        // Record what has been deployed, so the addresses can be looked up rather than recomputed.
+        state
+            .create_actor(
+                placeholder::PLACEHOLDER_ACTOR_CODE_ID,
+                contractbook::CONTRACT_BOOK_ACTOR_ID,
+                &deployer.into_contract_book(),
+                TokenAmount::zero(),
+                None,
+            )
+            .context("failed to create contract address book actor")?;
+
         Ok((state, out))
     }
 }
@@ -332,6 +601,10 @@ struct ContractDeployer<'a, DB> {
     top_contracts: &'a EthContractMap,
     // Assign dynamic ID addresses to libraries, but use fixed addresses for the top level contracts.
     lib_addrs: HashMap<FQN, et::Address>,
+    // Everything deployed so far, in order.
+    deployed: Vec<contractbook::ContractEntry>,
+    // The facets of the diamonds deployed so far, in order.
+    diamonds: Vec<diamondlayout::DiamondEntry>,
     phantom_db: PhantomData<DB>,
 }
 
@@ -344,10 +617,27 @@ where
             hardhat,
             top_contracts,
             lib_addrs: Default::default(),
+            deployed: Default::default(),
+            diamonds: Default::default(),
             phantom_db: PhantomData,
         }
     }
 
+    /// Check that the top contracts and their facets we are about to deploy have the same ABI
+    /// as the bindings we use to call them, otherwise calls would fail with missing methods.
+    pub fn check_abis(&self) -> anyhow::Result<()> {
+        for (contract_name, contract) in self.top_contracts {
+            self.hardhat
+                .check_abi(contract_src(contract_name), contract_name, &contract.abi)?;
+
+            for facet in contract.facets.iter() {
+                self.hardhat
+                    .check_abi(contract_src(facet.name), facet.name, &facet.abi)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Deploy a library contract with a dynamic ID and no constructor.
     pub fn deploy_library(
         &mut self,
@@ -367,6 +657,13 @@ where
             .create_evm_actor(*next_id, bytecode)
             .with_context(|| format!("failed to create library actor {fqn}"))?;
 
+        self.deployed.push(contractbook::ContractEntry {
+            fqn: fqn.clone(),
+            actor_id: *next_id,
+            eth_address: eth_addr,
+            library: true,
+        });
+
         let id_addr = et::Address::from(EthAddress::from_id(*next_id).0);
         let eth_addr = et::Address::from(eth_addr.0);
 
@@ -388,8 +685,11 @@ where
     }
 
     /// Construct the bytecode of a top-level contract and deploy it with some constructor parameters.
+    ///
+    /// Fails if the ABI of the contract in the build artifacts differs from the one we encode the
+    /// constructor parameters with and call the contract through.
     pub fn deploy_contract<T>(
-        &self,
+        &mut self,
         state: &mut FvmGenesisState<DB>,
         contract_name: &str,
         constructor_params: T,
@@ -401,15 +701,25 @@ where
         let contract_id = contract.actor_id;
         let contract_src = contract_src(contract_name);
 
+        self.hardhat
+            .check_abi(&contract_src, contract_name, &contract.abi)?;
+
         let bytecode = self
             .hardhat
-            .bytecode(contract_src, contract_name, &self.lib_addrs)
+            .bytecode(&contract_src, contract_name, &self.lib_addrs)
             .with_context(|| format!("failed to load {contract_name} bytecode"))?;
 
         let eth_addr = state
             .create_evm_actor_with_cons(contract_id, &contract.abi, bytecode, constructor_params)
             .with_context(|| format!("failed to create {contract_name} actor"))?;
 
+        self.deployed.push(contractbook::ContractEntry {
+            fqn: self.hardhat.fqn(&contract_src, contract_name),
+            actor_id: contract_id,
+            eth_address: eth_addr,
+            library: false,
+        });
+
         let id_addr = et::Address::from(EthAddress::from_id(contract_id).0);
         let eth_addr = et::Address::from(eth_addr.0);
 
@@ -426,6 +736,9 @@ where
     }
 
     /// Collect Facet Cuts for the diamond pattern, where the facet address comes from already deployed library facets.
+    ///
+    /// The selectors are taken from the build artifacts of the facets, which is what gets deployed,
+    /// ordered by the function names, so the cuts only depend on the artifacts.
     pub fn facets(&self, contract_name: &str) -> anyhow::Result<Vec<FacetCut>> {
         let contract = self.top_contract(contract_name)?;
         let mut facet_cuts = Vec::new();
@@ -440,8 +753,12 @@ where
                 .get(&facet_fqn)
                 .ok_or_else(|| anyhow!("facet {facet_name} has not been deployed"))?;
 
-            let method_sigs = facet
-                .abi
+            let abi = self
+                .hardhat
+                .abi(&facet_src, facet_name)
+                .with_context(|| format!("failed to load facet ABI {facet_fqn}"))?;
+
+            let method_sigs = abi
                 .functions()
                 .filter(|f| f.signature() != "init(bytes)")
                 .map(|f| f.short_signature())
@@ -459,6 +776,58 @@ where
         Ok(facet_cuts)
     }
 
+    /// Remember the facets a diamond is cut with.
+    pub fn record_diamond(
+        &mut self,
+        contract_name: &str,
+        facets: &[FacetCut],
+    ) -> anyhow::Result<()> {
+        let contract = self.top_contract(contract_name)?;
+        let actor_id = contract.actor_id;
+
+        let facets = facets
+            .iter()
+            .map(|cut| {
+                let name = self
+                    .lib_addrs
+                    .iter()
+                    .find(|(_, addr)| **addr == cut.facet_address)
+                    .and_then(|(fqn, _)| fqn.rsplit(':').next())
+                    .ok_or_else(|| {
+                        anyhow!("facet {:?} has not been deployed", cut.facet_address)
+                    })?;
+
+                Ok(diamondlayout::FacetEntry {
+                    name: name.to_string(),
+                    address: EthAddress(cut.facet_address.0),
+                    selectors: cut.function_selectors.clone(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.diamonds.push(diamondlayout::DiamondEntry {
+            name: contract_name.to_string(),
+            actor_id,
+            facets,
+        });
+
+        Ok(())
+    }
+
+    /// The facets of the diamonds deployed so far.
+    pub fn diamond_layout(&self) -> diamondlayout::State {
+        diamondlayout::State {
+            diamonds: self.diamonds.clone(),
+        }
+    }
+
+    /// The address book of everything deployed so far.
+    pub fn into_contract_book(self) -> contractbook::State {
+        contractbook::State {
+            contracts: self.deployed,
+        }
+    }
+
     fn top_contract(&self, contract_name: &str) -> anyhow::Result<&EthContract> {
         self.top_contracts
             .get(contract_name)
@@ -466,6 +835,16 @@ where
     }
 }
 
+/// The base fee, raised to the floor set by governance, if any.
+fn base_fee(g: &Genesis) -> TokenAmount {
+    match g.governance {
+        Some(ref gov) if gov.params.base_fee_floor > g.base_fee => {
+            gov.params.base_fee_floor.clone()
+        }
+        _ => g.base_fee.clone(),
+    }
+}
+
 /// Sum of balances in the genesis accounts.
 fn circ_supply(g: &Genesis) -> TokenAmount {
     g.accounts
@@ -478,8 +857,10 @@ mod tests {
     use std::{str::FromStr, sync::Arc};
 
     use cid::Cid;
+    use fendermint_vm_actor_interface::{contractbook, diamondlayout, ipc};
     use fendermint_vm_genesis::{ipc::IpcParams, Genesis};
     use fvm::engine::MultiEngine;
+    use fvm_ipld_encoding::CborStore;
     use quickcheck::Arbitrary;
     use tendermint_rpc::{MockClient, MockRequestMethodMatcher};
 
@@ -525,6 +906,59 @@ mod tests {
 
         assert_eq!(period, genesis.ipc.unwrap().gateway.bottom_up_check_period);
 
+        // The deployed libraries and contracts are in the address book.
+        let state_tree = state
+            .exec_state()
+            .expect("should be in exec stage")
+            .state_tree_mut();
+
+        let actor = state_tree
+            .get_actor(contractbook::CONTRACT_BOOK_ACTOR_ID)
+            .expect("failed to get contract book actor")
+            .expect("contract book actor exists");
+
+        let book: contractbook::State = state_tree
+            .store()
+            .get_cbor(&actor.state)
+            .expect("failed to load contract book")
+            .expect("contract book state exists");
+
+        let gateway_fqn = format!("{0}.sol:{0}", ipc::gateway::CONTRACT_NAME);
+        let gateway = book.get(&gateway_fqn).expect("gateway is in the book");
+        assert_eq!(gateway.actor_id, ipc::GATEWAY_ACTOR_ID);
+        assert!(!gateway.library);
+        assert!(book.contracts.iter().any(|c| c.library));
+
+        // The facets the diamonds were cut with are recorded, at the addresses of the libraries.
+        let actor = state_tree
+            .get_actor(diamondlayout::DIAMOND_LAYOUT_ACTOR_ID)
+            .expect("failed to get diamond layout actor")
+            .expect("diamond layout actor exists");
+
+        let layout: diamondlayout::State = state_tree
+            .store()
+            .get_cbor(&actor.state)
+            .expect("failed to load diamond layout")
+            .expect("diamond layout state exists");
+
+        let gateway = layout
+            .get(ipc::gateway::CONTRACT_NAME)
+            .expect("gateway is in the layout");
+        assert_eq!(gateway.actor_id, ipc::GATEWAY_ACTOR_ID);
+        assert!(layout.get(ipc::registry::CONTRACT_NAME).is_some());
+
+        for facet in gateway.facets.iter() {
+            let entry = book
+                .contracts
+                .iter()
+                .find(|c| c.link_address() == facet.address)
+                .expect("facet is in the book");
+            assert!(entry.library);
+            assert!(entry.fqn.ends_with(&format!(":{}", facet.name)));
+            assert!(!facet.selectors.is_empty());
+            assert_eq!(gateway.facet_of(&facet.selectors[0]), Some(facet));
+        }
+
         let _state_root = state.commit().expect("failed to commit");
     }
 
@@ -596,6 +1030,8 @@ mod tests {
 
         // Make sure we have IPC enabled.
         genesis.ipc = Some(IpcParams::arbitrary(&mut g));
+        // The utility contracts are built separately from the IPC ones.
+        genesis.system_contracts = Vec::new();
         genesis
     }
 
@@ -0,0 +1,493 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::multihash::Code;
+use cid::Cid;
+use fendermint_vm_actor_interface::governance::{
+    self, ApproveParams, BlockTopdownSenderParams, ProposeParams, ProposeTopdownFilterParams,
+    Shutdown, TopdownFilter, GOVERNANCE_ACTOR_ADDR, GOVERNANCE_ACTOR_ID,
+};
+use fvm::executor::ApplyFailure;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{
+    address::Address, clock::ChainEpoch, error::ExitCode, event::StampedEvent, METHOD_SEND,
+};
+use ipc_sdk::cross::CrossMsg;
+
+use super::{
+    access, events,
+    state::{reject_message, ActorAddressMap, ExecResult, FvmExecState},
+    FvmMessage,
+};
+
+/// Load the governance state, if the chain was started with one.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<governance::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(GOVERNANCE_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let gov = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("governance state not found"))?;
+            Ok(Some(gov))
+        }
+    }
+}
+
+impl<DB> FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    /// Limit on the sum of the gas limits of the transactions in the block at a height,
+    /// taking into account a change scheduled for it; 0 means no limit.
+    ///
+    /// Meant to be called on the committed state, to check proposals for the next block.
+    pub fn block_gas_limit_at(&mut self, height: ChainEpoch) -> anyhow::Result<u64> {
+        let gov = match get_state(self)? {
+            None => return Ok(0),
+            Some(gov) => gov,
+        };
+        let params = match gov.scheduled {
+            Some(ref change) if !change.shutdown && gov.is_epoch_boundary(height) => &change.params,
+            _ => &gov.params,
+        };
+        Ok(params.block_gas_limit)
+    }
+}
+
+/// Anything other than a simple send to the governance actor is a proposal or an approval.
+pub fn is_governance(msg: &FvmMessage) -> bool {
+    msg.to == GOVERNANCE_ACTOR_ADDR && msg.method_num != METHOD_SEND
+}
+
+/// Check whether the sender is allowed to send a governance message.
+///
+/// Returns the reason for rejection if it is not.
+pub fn check_member(gov: &governance::State, msg: &FvmMessage) -> Option<String> {
+    if gov.is_member(&msg.from) {
+        None
+    } else {
+        Some(format!("sender {} is not a governance member", msg.from))
+    }
+}
+
+/// Check whether user transactions are rejected because the subnet is shutting down.
+///
+/// Returns the reason for rejection if they are.
+pub fn check_shutdown<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<String>>
+where
+    DB: Blockstore + 'static,
+{
+    let reason = get_state(state)?
+        .and_then(|gov| gov.shutdown)
+        .map(|s| format!("the subnet is shutting down since height {}", s.since));
+    Ok(reason)
+}
+
+/// Put the parameters in effect at the beginning of a block.
+///
+/// Approved changes are applied at epoch boundaries. The base fee floor affects the
+/// next block, as the current one is already executing with the base fee it was
+/// created with, while the block gas limit affects the current block.
+///
+/// Returns the events to emit about the applied changes, the expired proposals and the
+/// progress of a shutdown.
+pub fn begin_block<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<StampedEvent>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut gov = match get_state(state)? {
+        None => return Ok(Vec::new()),
+        Some(gov) => gov,
+    };
+
+    let height = state.block_height();
+    let mut events = Vec::new();
+    let mut changed = false;
+
+    if gov.is_epoch_boundary(height) {
+        if let Some(change) = gov.apply_scheduled(height) {
+            if change.shutdown {
+                tracing::warn!(
+                    height,
+                    proposal_id = change.proposal_id,
+                    "subnet shutdown in effect; user transactions are no longer accepted"
+                );
+                events.push(event("shutdown", change.proposal_id));
+            } else {
+                tracing::info!(
+                    height,
+                    proposal_id = change.proposal_id,
+                    params = ?change.params,
+                    "governance parameter change applied"
+                );
+                events.push(event("params_changed", change.proposal_id));
+            }
+            changed = true;
+        }
+    }
+
+    for proposal_id in gov.expire_proposals(height) {
+        tracing::info!(height, proposal_id, "governance proposal expired");
+        events.push(event("expired", proposal_id));
+        changed = true;
+    }
+
+    if changed {
+        set_state(state, &gov).context("failed to update governance state")?;
+    }
+
+    // Announce the terminal state in the block after the final checkpoint.
+    if let Some(Shutdown {
+        final_checkpoint_height: Some(checkpoint_height),
+        terminal_state_root: Some(state_root),
+        ..
+    }) = gov.shutdown
+    {
+        if checkpoint_height as ChainEpoch == height - 1 {
+            events.push(terminal_event(checkpoint_height, state_root));
+        }
+    }
+
+    let floor = gov.params.base_fee_floor.clone();
+    if *state.base_fee() < floor {
+        state.update_base_fee(|base_fee| *base_fee = floor);
+    }
+
+    state.set_block_gas_limit(gov.params.block_gas_limit);
+
+    Ok(events)
+}
+
+/// Record the checkpoint created during a shutdown as the final one, unless there already is one,
+/// along with the terminal state root.
+///
+/// No user transactions have been executed since the shutdown started, so it carries all the
+/// outstanding bottom-up messages. The terminal state root is the committed root the block of
+/// the checkpoint started from, which is the app hash in its header, so the final checkpoint
+/// commits to it through its block hash, and the parent can check it.
+pub fn record_final_checkpoint<DB>(
+    state: &mut FvmExecState<DB>,
+    checkpoint_height: u64,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let mut gov = match get_state(state)? {
+        Some(gov) => gov,
+        None => return Ok(()),
+    };
+    match gov.shutdown {
+        Some(ref mut shutdown) if shutdown.final_checkpoint_height.is_none() => {
+            let state_root = state.initial_state_params().state_root;
+            shutdown.final_checkpoint_height = Some(checkpoint_height);
+            shutdown.terminal_state_root = Some(state_root);
+            tracing::warn!(
+                checkpoint_height,
+                state_root = state_root.to_string(),
+                "final bottom-up checkpoint created; subnet reached its terminal state"
+            );
+            set_state(state, &gov).context("failed to update governance state")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reject a transaction which doesn't fit in the block gas limit, charging its sender for gas.
+pub fn block_gas_exceeded<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    let reason = format!(
+        "gas limit {} exceeds what is left of the block gas limit",
+        msg.gas_limit
+    );
+    state.execute_rejected(msg, ExitCode::SYS_OUT_OF_GAS, reason)
+}
+
+/// Limit the number of top-down messages executed in a block.
+///
+/// The messages are appended to the ones deferred earlier; the ones over what's left
+/// of the limit, after the deferred batch executed at the beginning of the block,
+/// are deferred again, to be executed at the beginning of the following blocks.
+pub fn limit_topdown_msgs<DB>(
+    state: &mut FvmExecState<DB>,
+    msgs: Vec<CrossMsg>,
+) -> anyhow::Result<Vec<CrossMsg>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut gov = match get_state(state)? {
+        None => return Ok(msgs),
+        Some(gov) => gov,
+    };
+
+    if gov.params.topdown_max_msgs == 0 && gov.deferred_topdown_msgs.is_empty() {
+        return Ok(msgs);
+    }
+
+    let mut queue = std::mem::take(&mut gov.deferred_topdown_msgs);
+    queue.extend(msgs);
+
+    let batch = take_batch(&mut gov, queue, state.topdown_msgs_taken());
+    state.take_topdown_msgs(batch.len() as u64);
+    set_state(state, &gov).context("failed to update governance state")?;
+    Ok(batch)
+}
+
+/// The filter on the senders of top-down messages, if governance has set one.
+pub fn topdown_filter<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<TopdownFilter>>
+where
+    DB: Blockstore + 'static,
+{
+    Ok(get_state(state)?
+        .map(|gov| gov.topdown_filter)
+        .filter(|filter| *filter != TopdownFilter::default()))
+}
+
+/// Check the sender of a top-down message against the filter.
+///
+/// A blocked message isn't executed, but an event is emitted about it, which is returned.
+pub fn check_topdown_sender(filter: &TopdownFilter, msg: &CrossMsg) -> Option<StampedEvent> {
+    if passes_filter(filter, msg) {
+        return None;
+    }
+    let sender = msg.msg.from.raw_addr().ok();
+    tracing::warn!(
+        sender = sender.map(|a| a.to_string()),
+        nonce = msg.msg.nonce,
+        "top-down message from a blocked sender"
+    );
+    Some(sender_event(
+        "topdown_msg_blocked",
+        sender,
+        Some(msg.msg.nonce),
+    ))
+}
+
+/// Check whether the sender of a top-down message, e.g. a dead letter, passes the filter.
+pub fn is_topdown_sender_allowed<DB>(
+    state: &mut FvmExecState<DB>,
+    msg: &CrossMsg,
+) -> anyhow::Result<bool>
+where
+    DB: Blockstore + 'static,
+{
+    Ok(get_state(state)?
+        .map(|gov| passes_filter(&gov.topdown_filter, msg))
+        .unwrap_or(true))
+}
+
+fn passes_filter(filter: &TopdownFilter, msg: &CrossMsg) -> bool {
+    match msg.msg.from.raw_addr() {
+        Ok(sender) => filter.is_allowed(&sender),
+        // Without an allow-list there's nothing to match unknown senders against.
+        Err(_) => filter.allowed.is_none(),
+    }
+}
+
+/// Take the next batch of deferred top-down messages, if there are any.
+pub fn take_deferred_topdown_msgs<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<CrossMsg>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut gov = match get_state(state)? {
+        Some(gov) if !gov.deferred_topdown_msgs.is_empty() => gov,
+        _ => return Ok(Vec::new()),
+    };
+
+    let queue = std::mem::take(&mut gov.deferred_topdown_msgs);
+    let batch = take_batch(&mut gov, queue, state.topdown_msgs_taken());
+    state.take_topdown_msgs(batch.len() as u64);
+    set_state(state, &gov).context("failed to update governance state")?;
+    Ok(batch)
+}
+
+/// Handle a proposal or an approval sent by a member.
+///
+/// The message itself is executed as a simple send, so that the member is charged
+/// for gas and its nonce is incremented. The outcome is recorded as events in the
+/// receipt, so it can be audited later.
+pub fn execute<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    let mut gov = match get_state(state)? {
+        None => return access::execute_explicit(state, msg),
+        Some(gov) => gov,
+    };
+
+    if let Some(acl) = access::get_state(state)? {
+        if let Some(reason) = access::check_access(&acl, &msg) {
+            return Ok(forbidden(reason));
+        }
+    }
+
+    if let Some(reason) = check_member(&gov, &msg) {
+        return Ok(forbidden(reason));
+    }
+
+    let events = match apply(&mut gov, state.block_height(), &msg) {
+        Ok(events) => events,
+        Err(e) => {
+            return Ok(reject_message(
+                ExitCode::USR_ILLEGAL_ARGUMENT,
+                ApplyFailure::PreValidation(format!("invalid governance message: {e:#}")),
+            ))
+        }
+    };
+
+    let send = FvmMessage {
+        method_num: METHOD_SEND,
+        params: RawBytes::default(),
+        ..msg
+    };
+
+    let (mut apply_ret, emitters) = state.execute_explicit(send)?;
+
+    if apply_ret.msg_receipt.exit_code.is_success() {
+        set_state(state, &gov).context("failed to update governance state")?;
+        apply_ret.events.extend(events);
+    }
+
+    Ok((apply_ret, emitters))
+}
+
+fn forbidden(reason: String) -> (fvm::executor::ApplyRet, ActorAddressMap) {
+    reject_message(ExitCode::USR_FORBIDDEN, ApplyFailure::PreValidation(reason))
+}
+
+/// Apply the message to the state, returning the events to emit.
+fn apply(
+    gov: &mut governance::State,
+    height: ChainEpoch,
+    msg: &FvmMessage,
+) -> anyhow::Result<Vec<StampedEvent>> {
+    use governance::Method;
+    let mut events = Vec::new();
+    let filter = gov.topdown_filter.clone();
+    let id = match msg.method_num {
+        m if m == Method::Propose as u64 => {
+            let params: ProposeParams = msg.params.deserialize()?;
+            let id = gov.propose(height, msg.from, params.params);
+            events.push(event("proposed", id));
+            id
+        }
+        m if m == Method::ProposeShutdown as u64 => {
+            if gov.is_shutting_down() {
+                return Err(anyhow!("the subnet is already shutting down"));
+            }
+            let id = gov.propose_shutdown(height, msg.from);
+            events.push(event("proposed", id));
+            id
+        }
+        m if m == Method::ProposeTopdownFilter as u64 => {
+            let params: ProposeTopdownFilterParams = msg.params.deserialize()?;
+            let id = gov.propose_topdown_filter(height, msg.from, params.filter);
+            events.push(event("proposed", id));
+            id
+        }
+        m if m == Method::Approve as u64 => {
+            let params: ApproveParams = msg.params.deserialize()?;
+            gov.approve(params.id, msg.from, height)
+                .map_err(|e| anyhow!(e))?;
+            events.push(event("approved", params.id));
+            params.id
+        }
+        m if m == Method::BlockTopdownSender as u64 => {
+            let params: BlockTopdownSenderParams = msg.params.deserialize()?;
+            if !gov.block_topdown_sender(params.sender) {
+                return Err(anyhow!("sender {} is already blocked", params.sender));
+            }
+            tracing::warn!(
+                sender = params.sender.to_string(),
+                member = msg.from.to_string(),
+                "top-down sender blocked"
+            );
+            events.push(sender_event(
+                "topdown_sender_blocked",
+                Some(params.sender),
+                None,
+            ));
+            return Ok(events);
+        }
+        m => return Err(anyhow!("unknown method: {m}")),
+    };
+    if gov.scheduled.as_ref().map(|s| s.proposal_id) == Some(id) {
+        events.push(event("scheduled", id));
+    }
+    if gov.topdown_filter != filter {
+        tracing::warn!(proposal_id = id, filter = ?gov.topdown_filter, "top-down filter replaced");
+        events.push(event("topdown_filter_changed", id));
+    }
+    Ok(events)
+}
+
+/// An event emitted by the governance actor about a proposal.
+fn event(name: &str, proposal_id: u64) -> StampedEvent {
+    events::event(
+        GOVERNANCE_ACTOR_ID,
+        vec![
+            events::entry("governance", name),
+            events::entry("proposal_id", proposal_id.to_be_bytes()),
+        ],
+    )
+}
+
+/// An event emitted by the governance actor about the sender of top-down messages.
+fn sender_event(name: &str, sender: Option<Address>, nonce: Option<u64>) -> StampedEvent {
+    let mut entries = vec![events::entry("governance", name)];
+    if let Some(sender) = sender {
+        entries.push(events::entry("sender", sender.to_bytes()));
+    }
+    if let Some(nonce) = nonce {
+        entries.push(events::entry("nonce", nonce.to_be_bytes()));
+    }
+    events::event(GOVERNANCE_ACTOR_ID, entries)
+}
+
+/// An event emitted by the governance actor when the subnet reached its terminal state.
+fn terminal_event(checkpoint_height: u64, state_root: Cid) -> StampedEvent {
+    events::event(
+        GOVERNANCE_ACTOR_ID,
+        vec![
+            events::entry("governance", "terminated"),
+            events::entry("final_checkpoint_height", checkpoint_height.to_be_bytes()),
+            events::entry("state_root", state_root.to_bytes()),
+        ],
+    )
+}
+
+/// Split off the first batch of messages that fits in what's left of the limit
+/// after `taken` messages, deferring the rest.
+fn take_batch(gov: &mut governance::State, mut queue: Vec<CrossMsg>, taken: u64) -> Vec<CrossMsg> {
+    let limit = gov.params.topdown_max_msgs;
+    let left = limit.saturating_sub(taken) as usize;
+    if limit > 0 && queue.len() > left {
+        gov.deferred_topdown_msgs = queue.split_off(left);
+        tracing::info!(
+            deferred = gov.deferred_topdown_msgs.len(),
+            "top-down messages over the batch limit deferred"
+        );
+    }
+    queue
+}
+
+fn set_state<DB>(state: &mut FvmExecState<DB>, gov: &governance::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let mut actor = state_tree
+        .get_actor(GOVERNANCE_ACTOR_ID)?
+        .ok_or_else(|| anyhow!("governance actor not found"))?;
+    actor.state = state_tree.store().put_cbor(gov, Code::Blake2b256)?;
+    state_tree.set_actor(GOVERNANCE_ACTOR_ID, actor);
+    Ok(())
+}
@@ -1,32 +1,60 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 use std::path::PathBuf;
+use std::sync::Arc;
 
+mod access;
+mod beacon;
+mod blocktime;
 mod broadcast;
+mod chainmetadata;
 mod check;
 mod checkpoint;
+mod checkpointarchive;
+mod deadletter;
+mod ethblockhash;
+mod events;
 mod exec;
+mod execdigests;
 mod externs;
+mod fastfinality;
+mod filecoinsig;
 mod genesis;
+pub(crate) mod governance;
+mod outbox;
+pub mod policy;
 mod query;
+mod rewards;
+pub mod routing;
+mod scheduler;
 pub mod state;
+#[cfg(feature = "block-stm")]
+pub mod stm;
 pub mod store;
 
 #[cfg(any(test, feature = "bundle"))]
 pub mod bundle;
+#[cfg(test)]
+pub(crate) mod testing;
 pub(crate) mod topdown;
+mod txcompression;
+mod txorder;
+mod validators;
 
-pub use check::FvmCheckRet;
+pub use check::{AdmissionRules, FvmCheckRet};
 pub use checkpoint::PowerUpdates;
-pub use exec::FvmApplyRet;
+pub use exec::{FvmApplyRet, FvmBeginRet};
+pub use execdigests::{receipt_proof, verify_receipt_proof};
 use fendermint_crypto::{PublicKey, SecretKey};
 use fendermint_eth_hardhat::Hardhat;
 pub use fendermint_vm_message::query::FvmQuery;
 pub use genesis::FvmGenesisOutput;
 pub use query::FvmQueryRet;
 use tendermint_rpc::Client;
+pub use topdown::{expected_topdown_nonce, topdown_progress, TopdownProgress};
 
 pub use self::broadcast::Broadcaster;
+pub use self::outbox::{Outbox, OutboxItem, OutboxOpt};
 use self::state::ipc::GatewayCaller;
 
 pub type FvmMessage = fvm_shared::message::Message;
@@ -71,7 +99,23 @@ pub struct FvmMessageInterpreter<DB, C> {
     /// Indicate whether transactions should be fully executed during the checks performed
     /// when they are added to the mempool, or just the most basic ones are performed.
     exec_in_check: bool,
+    /// Indicate whether only the basic fields of new transactions are checked when they are
+    /// added to the mempool, leaving the checks against the state to rechecks and proposals.
+    defer_check: bool,
+    /// Extra conditions on the balance of the sender to admit transactions to the mempool.
+    admission: AdmissionRules,
     gateway: GatewayCaller<DB>,
+    /// Directory to export the pruned bottom-up checkpoints to, if this node keeps them.
+    checkpoint_archive_dir: Option<PathBuf>,
+    /// The power table after the last checkpoint, to only read the validator set when it changed.
+    power_table_cache: checkpoint::PowerTableCache,
+    /// The beacon phase this validator last contributed in.
+    beacon_progress: beacon::BeaconProgress,
+    /// The Wasm module enforcing the custom message policy of the chain, if it has one.
+    policy: Option<Arc<policy::PolicyModule>>,
+    /// Optimistic parallel execution of the blocks, running alongside the sequential one.
+    #[cfg(feature = "block-stm")]
+    block_stm: Option<stm::BlockStm<DB>>,
 }
 
 impl<DB, C> FvmMessageInterpreter<DB, C> {
@@ -90,9 +134,50 @@ impl<DB, C> FvmMessageInterpreter<DB, C> {
             gas_overestimation_rate,
             gas_search_step,
             exec_in_check,
+            defer_check: false,
+            admission: Default::default(),
             gateway: GatewayCaller::default(),
+            checkpoint_archive_dir: None,
+            power_table_cache: Default::default(),
+            beacon_progress: Default::default(),
+            policy: None,
+            #[cfg(feature = "block-stm")]
+            block_stm: None,
         }
     }
+
+    /// Export the checkpoints pruned from the gateway to CAR files in a directory,
+    /// where they can be looked up by queries.
+    pub fn with_checkpoint_archive(mut self, dir: PathBuf) -> Self {
+        self.checkpoint_archive_dir = Some(dir);
+        self
+    }
+
+    /// Only check the basic fields of new transactions, so they can be admitted to the mempool
+    /// and gossiped without looking at the state; see [CheckInterpreter::check](crate::CheckInterpreter::check).
+    pub fn with_deferred_check(mut self, defer_check: bool) -> Self {
+        self.defer_check = defer_check;
+        self
+    }
+
+    /// Require the senders of new transactions to be able to afford more than the gas.
+    pub fn with_admission(mut self, admission: AdmissionRules) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    /// Consult a policy module about the user messages; it has to be the one pinned by the genesis.
+    pub fn with_policy(mut self, policy: policy::PolicyModule) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Run the Block-STM experiment on every block.
+    #[cfg(feature = "block-stm")]
+    pub fn with_block_stm(mut self, block_stm: stm::BlockStm<DB>) -> Self {
+        self.block_stm = Some(block_stm);
+        self
+    }
 }
 
 impl<DB, C> FvmMessageInterpreter<DB, C>
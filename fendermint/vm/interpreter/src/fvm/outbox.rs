@@ -0,0 +1,492 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use ethers::types as et;
+use fendermint_vm_encoding::IsHumanReadable;
+use fendermint_vm_message::query::FvmQueryHeight;
+use fendermint_vm_topdown::SubnetLabel;
+use fvm_shared::{address::Address, chainid::ChainID};
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use tendermint_rpc::Client;
+use tokio::sync::Notify;
+
+use super::broadcast::Broadcaster;
+
+lazy_static! {
+    static ref OUTBOX_PENDING: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_outbox_pending",
+        "Number of validator transactions waiting to be included in a block",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref OUTBOX_STUCK: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_outbox_stuck",
+        "Number of validator transactions waiting for longer than expected",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref OUTBOX_OLDEST_AGE: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_outbox_oldest_age_seconds",
+        "Age of the oldest validator transaction waiting to be included in a block",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref OUTBOX_BROADCASTS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_outbox_broadcasts_total",
+        "Number of attempts to broadcast validator transactions",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref OUTBOX_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_outbox_dropped_total",
+        "Number of validator transactions given up on after too many attempts",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxOpt {
+    /// Directory to persist the pending transactions in.
+    pub dir: PathBuf,
+    /// Give up on a transaction after this many attempts to broadcast it.
+    pub max_attempts: u32,
+    /// Broadcast a transaction again if it hasn't been included in a block for this long,
+    /// for example because it got evicted from the mempool.
+    pub resubmit_after: Duration,
+    /// Upper limit of the exponentially growing delay between failed attempts.
+    pub max_backoff: Duration,
+    /// Time after which a pending transaction is reported as stuck.
+    pub stuck_after: Duration,
+    /// Label of the pending, stuck, broadcast and dropped transaction metrics.
+    pub subnet_id: SubnetLabel,
+}
+
+/// A transaction the validator needs to get included in a block, e.g. a checkpoint signature.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutboxItem {
+    pub id: u64,
+    /// What the transaction is for, to show in the logs.
+    pub kind: String,
+    #[serde_as(as = "IsHumanReadable")]
+    pub contract: Address,
+    pub calldata: et::Bytes,
+    pub chain_id: u64,
+    /// Seconds since the epoch when the item was added.
+    pub created_at: u64,
+    /// Number of attempts to broadcast it so far.
+    pub attempts: u32,
+    /// Seconds since the epoch when it was last attempted.
+    pub last_attempt_at: Option<u64>,
+    /// The nonce it's been added to the mempool with, if the last broadcast succeeded.
+    pub nonce: Option<u64>,
+    pub tx_hash: Option<String>,
+    /// Why the last attempt failed.
+    pub last_error: Option<String>,
+}
+
+/// Durable queue of transactions originated by the validator.
+///
+/// The transactions are persisted as JSON files, one per item, so they survive restarts,
+/// and broadcast one at a time, in the order they were added, which keeps the nonces
+/// sequential. An item is removed when the committed nonce of the validator moves past
+/// the one it was broadcast with.
+///
+/// A transaction that seems to have been dropped is broadcast again as it was, with the same nonce
+/// and gas: CometBFT has no replace-by-fee, so a higher premium wouldn't get it replaced in the mempool.
+#[derive(Clone)]
+pub struct Outbox {
+    opt: Arc<OutboxOpt>,
+    items: Arc<Mutex<BTreeMap<u64, OutboxItem>>>,
+    notify: Arc<Notify>,
+}
+
+impl Outbox {
+    /// Load the pending items from the outbox directory, creating it if needed.
+    pub async fn open(opt: OutboxOpt) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(&opt.dir)
+            .await
+            .context("failed to create outbox directory")?;
+
+        let mut items = BTreeMap::new();
+        let mut entries = tokio::fs::read_dir(&opt.dir)
+            .await
+            .context("failed to read outbox directory")?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map(|e| e == "json").unwrap_or_default() {
+                let json = tokio::fs::read_to_string(&path)
+                    .await
+                    .context("failed to read outbox item")?;
+                match serde_json::from_str::<OutboxItem>(&json) {
+                    Ok(item) => {
+                        items.insert(item.id, item);
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            error = e.to_string(),
+                            path = path.to_string_lossy().to_string(),
+                            "failed to parse outbox item"
+                        );
+                    }
+                }
+            }
+        }
+
+        if !items.is_empty() {
+            tracing::info!(pending = items.len(), "loaded transactions from the outbox");
+        }
+
+        Ok(Self {
+            opt: Arc::new(opt),
+            items: Arc::new(Mutex::new(items)),
+            notify: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Add a transaction to the outbox and return its ID.
+    ///
+    /// Returns `None` if the same transaction is already pending.
+    pub async fn push(
+        &self,
+        kind: &str,
+        contract: Address,
+        calldata: et::Bytes,
+        chain_id: ChainID,
+    ) -> anyhow::Result<Option<u64>> {
+        let item = {
+            let mut items = self.items.lock().unwrap();
+
+            if items
+                .values()
+                .any(|i| i.contract == contract && i.calldata == calldata)
+            {
+                return Ok(None);
+            }
+
+            let id = items
+                .keys()
+                .next_back()
+                .map(|id| id + 1)
+                .unwrap_or_default();
+            let item = OutboxItem {
+                id,
+                kind: kind.to_owned(),
+                contract,
+                calldata,
+                chain_id: chain_id.into(),
+                created_at: now_secs(),
+                attempts: 0,
+                last_attempt_at: None,
+                nonce: None,
+                tx_hash: None,
+                last_error: None,
+            };
+            // Reserve the ID while the item is written, so a concurrent push doesn't take it too.
+            items.insert(id, item.clone());
+            item
+        };
+        let id = item.id;
+
+        if let Err(e) = write_item(&self.opt.dir, &item).await {
+            self.items.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        OUTBOX_PENDING
+            .with_label_values(&[&self.opt.subnet_id])
+            .set(self.items.lock().unwrap().len() as i64);
+
+        self.notify.notify_one();
+
+        Ok(Some(id))
+    }
+
+    /// List the pending items in the order they will be broadcast.
+    pub fn items(&self) -> Vec<OutboxItem> {
+        self.items.lock().unwrap().values().cloned().collect()
+    }
+
+    fn head(&self) -> Option<OutboxItem> {
+        self.items.lock().unwrap().values().next().cloned()
+    }
+
+    async fn update(&self, item: OutboxItem) -> anyhow::Result<()> {
+        write_item(&self.opt.dir, &item).await?;
+        self.items.lock().unwrap().insert(item.id, item);
+        Ok(())
+    }
+
+    async fn remove(&self, id: u64) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(item_path(&self.opt.dir, id)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("failed to remove outbox item"),
+        }
+        self.items.lock().unwrap().remove(&id);
+        Ok(())
+    }
+
+    fn update_metrics(&self, now: u64) {
+        let items = self.items.lock().unwrap();
+        let stuck_after = self.opt.stuck_after.as_secs();
+        let ages = items.values().map(|i| now.saturating_sub(i.created_at));
+        let label = [self.opt.subnet_id.as_str()];
+        OUTBOX_PENDING
+            .with_label_values(&label)
+            .set(items.len() as i64);
+        OUTBOX_STUCK
+            .with_label_values(&label)
+            .set(ages.clone().filter(|age| *age > stuck_after).count() as i64);
+        OUTBOX_OLDEST_AGE
+            .with_label_values(&label)
+            .set(ages.max().unwrap_or_default() as i64);
+    }
+
+    /// Delay before trying again after a failed attempt.
+    fn backoff(&self, retry_delay: Duration, attempts: u32) -> Duration {
+        let exp = attempts.saturating_sub(1).min(16);
+        (retry_delay * 2u32.pow(exp)).min(self.opt.max_backoff)
+    }
+
+    /// Take the next step with the item at the head of the queue.
+    ///
+    /// Returns how long to wait before the next step, or `None` if the outbox is empty.
+    async fn step<C>(&self, broadcaster: &Broadcaster<C>) -> anyhow::Result<Option<Duration>>
+    where
+        C: Client + Clone + Send + Sync,
+    {
+        let now = now_secs();
+        self.update_metrics(now);
+
+        let mut item = match self.head() {
+            None => return Ok(None),
+            Some(item) => item,
+        };
+
+        let since_last = now.saturating_sub(item.last_attempt_at.unwrap_or_default());
+
+        if let Some(nonce) = item.nonce {
+            let committed = broadcaster
+                .sequence(FvmQueryHeight::Committed)
+                .await
+                .context("failed to get committed sequence")?;
+
+            if committed > nonce {
+                tracing::info!(
+                    id = item.id,
+                    kind = item.kind,
+                    nonce,
+                    attempts = item.attempts,
+                    "outbox transaction included in a block"
+                );
+                self.remove(item.id).await?;
+                return Ok(Some(Duration::ZERO));
+            }
+
+            let resubmit_after = self.opt.resubmit_after.as_secs();
+            if since_last < resubmit_after {
+                return Ok(Some(broadcaster.retry_delay()));
+            }
+        } else if item.last_attempt_at.is_some() {
+            let backoff = self.backoff(broadcaster.retry_delay(), item.attempts);
+            if since_last < backoff.as_secs() {
+                return Ok(Some(Duration::from_secs(backoff.as_secs() - since_last)));
+            }
+        }
+
+        if item.attempts >= self.opt.max_attempts {
+            tracing::error!(
+                id = item.id,
+                kind = item.kind,
+                attempts = item.attempts,
+                last_error = item.last_error,
+                "giving up on outbox transaction"
+            );
+            OUTBOX_DROPPED
+                .with_label_values(&[&self.opt.subnet_id])
+                .inc();
+            self.remove(item.id).await?;
+            return Ok(Some(Duration::ZERO));
+        }
+
+        // Reuse the nonce if the transaction is resubmitted because it seems to have been dropped.
+        let nonce = match item.nonce {
+            Some(nonce) => nonce,
+            None => broadcaster
+                .sequence(FvmQueryHeight::Pending)
+                .await
+                .context("failed to get pending sequence")?,
+        };
+
+        item.attempts += 1;
+        item.last_attempt_at = Some(now);
+        OUTBOX_BROADCASTS
+            .with_label_values(&[&self.opt.subnet_id])
+            .inc();
+
+        let res = broadcaster
+            .try_invoke(
+                item.contract,
+                &item.calldata,
+                ChainID::from(item.chain_id),
+                nonce,
+            )
+            .await;
+
+        match res {
+            Ok(Ok(tx_hash)) => {
+                tracing::info!(
+                    id = item.id,
+                    kind = item.kind,
+                    nonce,
+                    attempt = item.attempts,
+                    tx_hash = tx_hash.to_string(),
+                    "broadcasted outbox transaction"
+                );
+                item.nonce = Some(nonce);
+                item.tx_hash = Some(tx_hash.to_string());
+                item.last_error = None;
+            }
+            Ok(Err((_, msg))) => {
+                tracing::warn!(
+                    id = item.id,
+                    kind = item.kind,
+                    error = msg,
+                    "outbox transaction rejected"
+                );
+                item.last_error = Some(msg);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    id = item.id,
+                    kind = item.kind,
+                    error = e.to_string(),
+                    "failed to broadcast outbox transaction"
+                );
+                item.last_error = Some(format!("{e:#}"));
+            }
+        }
+
+        self.update(item).await?;
+
+        Ok(Some(broadcaster.retry_delay()))
+    }
+}
+
+/// Keep broadcasting the items in the outbox until they are included in a block.
+pub(super) async fn run<C>(outbox: Outbox, broadcaster: Broadcaster<C>)
+where
+    C: Client + Clone + Send + Sync,
+{
+    loop {
+        let wait = match outbox.step(&broadcaster).await {
+            Ok(wait) => wait,
+            Err(e) => {
+                tracing::warn!(error = e.to_string(), "failed to process the outbox");
+                Some(broadcaster.retry_delay())
+            }
+        };
+        match wait {
+            Some(d) if d.is_zero() => {}
+            Some(d) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(d) => {},
+                    _ = outbox.notify.notified() => {},
+                }
+            }
+            None => outbox.notify.notified().await,
+        }
+    }
+}
+
+fn item_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id}.json"))
+}
+
+/// Write the item to a temporary file first, so a crash can't leave a truncated one behind.
+async fn write_item(dir: &Path, item: &OutboxItem) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(item).context("failed to serialize outbox item")?;
+    let path = item_path(dir, item.id);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json)
+        .await
+        .context("failed to write outbox item")?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .context("failed to move outbox item")?;
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::types as et;
+    use fvm_shared::{address::Address, chainid::ChainID};
+
+    use super::{Outbox, OutboxOpt};
+
+    async fn open(dir: &std::path::Path) -> Outbox {
+        Outbox::open(OutboxOpt {
+            dir: dir.into(),
+            max_attempts: 10,
+            resubmit_after: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(60),
+            stuck_after: Duration::from_secs(300),
+            subnet_id: String::new(),
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn items_survive_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = open(dir.path()).await;
+        let contract = Address::new_id(64);
+        let chain_id = ChainID::from(1);
+
+        let id0 = outbox
+            .push("test", contract, et::Bytes::from(vec![1]), chain_id)
+            .await
+            .unwrap();
+        let id1 = outbox
+            .push("test", contract, et::Bytes::from(vec![2]), chain_id)
+            .await
+            .unwrap();
+        let dup = outbox
+            .push("test", contract, et::Bytes::from(vec![1]), chain_id)
+            .await
+            .unwrap();
+
+        assert_eq!(id0, Some(0));
+        assert_eq!(id1, Some(1));
+        assert_eq!(dup, None);
+
+        outbox.remove(0).await.unwrap();
+
+        let outbox = open(dir.path()).await;
+        let items = outbox.items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, 1);
+        assert_eq!(items[0].calldata, et::Bytes::from(vec![2]));
+    }
+}
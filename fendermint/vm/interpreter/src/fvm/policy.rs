@@ -0,0 +1,298 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Custom message policies, enforced by a Wasm module the genesis pins.
+//!
+//! The module is consulted about every user message, both when it's added to the mempool and
+//! when it's executed, and it can reject them. It runs in a sandbox of its own, not in the FVM:
+//! it has no imports, so it cannot see the state or anything else about the node, and every
+//! call runs on a fresh instance with a limited amount of fuel, so the outcome only depends on
+//! the message.
+//!
+//! The module has to export:
+//! * `memory`, its linear memory;
+//! * `alloc(len: i32) -> i32`, returning a pointer to `len` bytes the input can be written to;
+//! * `check(ptr: i32, len: i32) -> i32`, returning 0 to let the message through, or anything
+//!   else to reject it.
+//!
+//! The input is the concatenation of:
+//! * the sender address: a 4 byte little endian length, then the address bytes;
+//! * the recipient address, the same way;
+//! * the method number, as an 8 byte little endian integer;
+//! * the value: a 4 byte little endian length, then the big endian bytes of the atto amount.
+//!
+//! Traps, including running out of fuel, reject the message as well. The memory of the module
+//! is limited to [`MAX_MEMORY_BYTES`], so that growing it fails the same way on every validator,
+//! regardless of how much memory they have.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use cid::Cid;
+use fendermint_vm_actor_interface::policy::{self, POLICY_ACTOR_ID};
+use fendermint_vm_genesis::Policy;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimitsBuilder};
+
+use super::{state::FvmExecState, FvmMessage};
+
+/// The most memory a call can use; `memory.grow` returns -1 beyond it.
+pub const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+/// The most elements the tables of the module can have.
+const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+/// A compiled policy module, loaded from the disk of the validator.
+pub struct PolicyModule {
+    cid: Cid,
+    engine: Engine,
+    module: Module,
+}
+
+impl PolicyModule {
+    /// Read and compile a module, checking that it has the expected interface.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("failed to read the policy module from {path:?}"))?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Compile a module, checking that it has the expected interface.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        // Floats are allowed, but they have to come out the same on every validator.
+        config.cranelift_nan_canonicalization(true);
+
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, bytes).context("failed to compile the policy module")?;
+
+        if let Some(import) = module.imports().next() {
+            bail!(
+                "the policy module cannot have imports, but it imports {}::{}",
+                import.module(),
+                import.name()
+            );
+        }
+        for export in ["memory", "alloc", "check"] {
+            if module.get_export(export).is_none() {
+                bail!("the policy module does not export `{export}`");
+            }
+        }
+
+        Ok(Self {
+            cid: Policy::module_cid(bytes),
+            engine,
+            module,
+        })
+    }
+
+    /// CID of the module, to compare with the one pinned by the genesis.
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
+
+    /// Ask the module about a message.
+    ///
+    /// Returns the reason for rejection if the module doesn't let it through.
+    pub fn check(&self, fuel_limit: u64, msg: &FvmMessage) -> Option<String> {
+        match self.call(fuel_limit, &encode_input(msg)) {
+            Ok(0) => None,
+            Ok(code) => Some(format!("rejected by the policy module with code {code}")),
+            Err(e) => Some(format!("the policy module failed: {e:#}")),
+        }
+    }
+
+    fn call(&self, fuel_limit: u64, input: &[u8]) -> anyhow::Result<i32> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.add_fuel(fuel_limit)?;
+
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("memory not exported"))?;
+        let alloc = instance.get_typed_func::<i32, i32, _>(&mut store, "alloc")?;
+        let check = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "check")?;
+
+        let len = i32::try_from(input.len()).context("input too long")?;
+        let ptr = alloc.call(&mut store, len)?;
+
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .context("failed to write the input")?;
+
+        check.call(&mut store, (ptr, len))
+    }
+}
+
+/// Load the policy state, if the chain was started with a policy module.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<policy::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(POLICY_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let policy = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("policy state not found"))?;
+            Ok(Some(policy))
+        }
+    }
+}
+
+/// Check whether the policy of the chain, if it has one, allows the message.
+///
+/// Returns the reason for rejection if it is not allowed. Fails if the chain has a policy,
+/// but this node has not loaded the module pinned by it, because then it cannot tell.
+pub fn check_policy<DB>(
+    module: Option<&PolicyModule>,
+    state: &mut FvmExecState<DB>,
+    msg: &FvmMessage,
+) -> anyhow::Result<Option<String>>
+where
+    DB: Blockstore + 'static,
+{
+    let policy = match get_state(state)? {
+        None => return Ok(None),
+        Some(policy) => policy,
+    };
+    let module = match module {
+        Some(module) if module.cid() == policy.module => module,
+        Some(module) => bail!(
+            "the loaded policy module {} is not the one pinned by the genesis: {}",
+            module.cid(),
+            policy.module
+        ),
+        None => bail!(
+            "the chain requires the policy module {}; set `fvm.policy_module`",
+            policy.module
+        ),
+    };
+    Ok(module.check(policy.fuel_limit, msg))
+}
+
+fn encode_input(msg: &FvmMessage) -> Vec<u8> {
+    let mut input = Vec::new();
+    put_len_prefixed(&mut input, &msg.from.to_bytes());
+    put_len_prefixed(&mut input, &msg.to.to_bytes());
+    input.extend_from_slice(&msg.method_num.to_le_bytes());
+    let (_, value) = msg.value.atto().to_bytes_be();
+    put_len_prefixed(&mut input, &value);
+    input
+}
+
+fn put_len_prefixed(input: &mut Vec<u8>, bytes: &[u8]) {
+    input.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    input.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::{address::Address, econ::TokenAmount};
+
+    use super::{encode_input, PolicyModule};
+    use crate::fvm::FvmMessage;
+
+    const FUEL: u64 = 100_000;
+
+    fn msg(value: u64) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from: Address::new_id(1),
+            to: Address::new_id(300),
+            sequence: 0,
+            value: TokenAmount::from_atto(value),
+            method_num: 2,
+            params: Default::default(),
+            gas_limit: 0,
+            gas_fee_cap: Default::default(),
+            gas_premium: Default::default(),
+        }
+    }
+
+    /// A module with the given body for `check`, which gets the input at offset 1024.
+    fn module(check: &str) -> PolicyModule {
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "check") (param $ptr i32) (param $len i32) (result i32) {check}))"#
+        );
+        PolicyModule::from_bytes(&wat::parse_str(wat).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn input_reaches_module() {
+        // Return the last byte of the input, which is the lowest byte of the value.
+        let module = module(
+            "(i32.load8_u (i32.add (local.get $ptr) (i32.sub (local.get $len) (i32.const 1))))",
+        );
+        assert_eq!(module.check(FUEL, &msg(0)), None);
+        assert_eq!(
+            module.check(FUEL, &msg(5)),
+            Some("rejected by the policy module with code 5".to_owned())
+        );
+    }
+
+    #[test]
+    fn traps_reject() {
+        let module = module("unreachable");
+        let reason = module.check(FUEL, &msg(0)).unwrap();
+        assert!(reason.starts_with("the policy module failed"), "{reason}");
+    }
+
+    #[test]
+    fn running_out_of_fuel_rejects() {
+        let module = module("(loop $l (br $l)) (i32.const 0)");
+        let reason = module.check(FUEL, &msg(0)).unwrap();
+        assert!(reason.starts_with("the policy module failed"), "{reason}");
+    }
+
+    #[test]
+    fn memory_is_limited() {
+        // 16 MiB is 256 pages, of which the module already has one.
+        let module = module("(memory.grow (i32.const 256))");
+        assert_eq!(
+            module.check(FUEL, &msg(0)),
+            Some("rejected by the policy module with code -1".to_owned())
+        );
+        let module = module("(i32.sub (memory.grow (i32.const 255)) (i32.const 1))");
+        assert_eq!(module.check(FUEL, &msg(0)), None);
+    }
+
+    #[test]
+    fn imports_are_refused() {
+        let wat = r#"(module
+            (import "env" "now" (func))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 0)
+            (func (export "check") (param i32 i32) (result i32) i32.const 0))"#;
+        assert!(PolicyModule::from_bytes(&wat::parse_str(wat).unwrap()).is_err());
+    }
+
+    #[test]
+    fn input_encoding() {
+        let msg = FvmMessage {
+            value: TokenAmount::from_atto(258),
+            ..msg(0)
+        };
+
+        let mut expected = vec![2, 0, 0, 0, 0, 1];
+        expected.extend([3, 0, 0, 0, 0, 0xac, 0x02]);
+        expected.extend(2u64.to_le_bytes());
+        expected.extend([2, 0, 0, 0, 1, 2]);
+
+        assert_eq!(encode_input(&msg), expected);
+    }
+}
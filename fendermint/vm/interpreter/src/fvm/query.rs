@@ -1,17 +1,36 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use fendermint_vm_message::query::{ActorState, FvmQuery, GasEstimate, StateParams};
+use ethers::abi::{self, Token};
+use fendermint_vm_actor_interface::checkpointarchive::{
+    self, ArchivedCheckpoint, CHECKPOINT_ARCHIVE_ACTOR_ADDR,
+};
+use fendermint_vm_actor_interface::deadletter::DeadLetter;
+use fendermint_vm_actor_interface::execdigests::ReceiptProof;
+use fendermint_vm_genesis::MAX_TOTAL_VOTING_POWER;
+use fendermint_vm_message::conv::from_fvm::to_eth_tokens;
+use fendermint_vm_message::query::{
+    AccessList, ActorState, FvmQuery, GasEstimate, SimulatedValidator, StakingChange,
+    StakingOperation, StakingSimulation, StakingSimulationParams, StateParams,
+};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::{
-    bigint::BigInt, econ::TokenAmount, error::ExitCode, message::Message, ActorID, BLOCK_GAS_LIMIT,
+    bigint::BigInt, clock::ChainEpoch, econ::TokenAmount, error::ExitCode, message::Message,
+    ActorID, BLOCK_GAS_LIMIT,
 };
+use ipc_sdk::staking::{self as sdk, StakingChangeRequest};
 use num_traits::Zero;
 
 use crate::QueryInterpreter;
 
-use super::{state::FvmQueryState, FvmApplyRet, FvmMessageInterpreter};
+use super::{
+    checkpoint::ipc_power_table,
+    deadletter, execdigests,
+    state::{ipc::GatewayCaller, FvmExecState, FvmQueryState},
+    FvmApplyRet, FvmMessageInterpreter,
+};
 
 /// Internal return type for queries. It will never be serialized
 /// and sent over the wire as it is, only its internal parts are
@@ -26,8 +45,18 @@ pub enum FvmQueryRet {
     Call(FvmApplyRet),
     /// The estimated gas limit.
     EstimateGas(GasEstimate),
+    /// The estimated gas limit with the actors called.
+    AccessList(AccessList),
     /// Current state parameters.
     StateParams(StateParams),
+    /// A pruned checkpoint, if it's in one of the archives this node has.
+    ArchivedCheckpoint(Option<Box<ArchivedCheckpoint>>),
+    /// The power table resulting from staking changes which weren't applied.
+    StakingSimulation(StakingSimulation),
+    /// A receipt with its proof, if the block has execution digests.
+    ReceiptProof(Option<Box<ReceiptProof>>),
+    /// The top-down messages which failed to be applied.
+    DeadLetters(Vec<DeadLetter>),
 }
 
 #[async_trait]
@@ -116,27 +145,35 @@ where
                     method_num = msg.method_num,
                     "query estimate gas"
                 );
-                // Populate gas message parameters.
-
-                match self.estimate_gassed_msg(state, &mut msg).await? {
-                    (state, Some(est)) => {
-                        // return immediately if something is returned,
-                        // it means that the message failed to execute so there's
-                        // no point on estimating the gas.
-                        Ok((state, FvmQueryRet::EstimateGas(est)))
-                    }
-                    (state, None) => {
-                        // perform a gas search for an accurate value
-                        let (state, mut est) = self.gas_search(state, &msg).await?;
-                        // we need an additional overestimation for the case where
-                        // the exact value is returned as part of the gas search
-                        // (for some reason with subsequent calls sometimes this is the case).
-                        est.gas_limit =
-                            (est.gas_limit as f64 * self.gas_overestimation_rate) as u64;
-
-                        Ok((state, FvmQueryRet::EstimateGas(est)))
-                    }
+                let (state, est) = self.estimate_gas(state, &mut msg).await?;
+                Ok((state, FvmQueryRet::EstimateGas(est)))
+            }
+            FvmQuery::AccessList(mut msg) => {
+                let (state, estimate) = self.estimate_gas(state, &mut msg).await?;
+
+                if !estimate.exit_code.is_success() {
+                    let out = FvmQueryRet::AccessList(AccessList {
+                        estimate,
+                        actors: Vec::new(),
+                    });
+                    return Ok((state, out));
                 }
+
+                // Run it once more, with the estimated limit, to see which actors it calls.
+                msg.gas_limit = estimate.gas_limit;
+                msg.sequence = 0;
+                let (state, (_, actors)) = state.call_traced(*msg).await?;
+
+                tracing::info!(
+                    height = state.block_height(),
+                    pending = state.pending(),
+                    gas_limit = estimate.gas_limit,
+                    actors = actors.len(),
+                    "query access list"
+                );
+
+                let out = FvmQueryRet::AccessList(AccessList { estimate, actors });
+                Ok((state, out))
             }
             FvmQuery::StateParams => {
                 let state_params = state.state_params();
@@ -148,6 +185,94 @@ where
                 };
                 Ok((state, FvmQueryRet::StateParams(state_params)))
             }
+            FvmQuery::ArchivedCheckpoint(height) => {
+                let (state, ret) = state.actor_state(&CHECKPOINT_ARCHIVE_ACTOR_ADDR).await?;
+
+                let archive = match ret {
+                    None => None,
+                    Some((_, actor)) => {
+                        let bz = state
+                            .store_get(&actor.state)?
+                            .ok_or_else(|| anyhow!("checkpoint archive state not found"))?;
+                        let archives: checkpointarchive::State =
+                            fvm_ipld_encoding::from_slice(&bz)?;
+                        archives.find(height).cloned()
+                    }
+                };
+
+                let checkpoint = match (archive, &self.checkpoint_archive_dir) {
+                    (Some(archive), Some(dir)) => {
+                        super::checkpointarchive::read_archived_checkpoint(dir, &archive, height)
+                            .await?
+                    }
+                    _ => None,
+                };
+
+                tracing::info!(
+                    height = state.block_height(),
+                    pending = state.pending(),
+                    checkpoint_height = height,
+                    found = checkpoint.is_some(),
+                    "query archived checkpoint"
+                );
+
+                let out = FvmQueryRet::ArchivedCheckpoint(checkpoint.map(Box::new));
+                Ok((state, out))
+            }
+            FvmQuery::SimulateStakingChanges(params) => {
+                let num_changes = params.changes.len();
+
+                let (state, simulation) = state
+                    .simulate(|s| simulate_staking_changes(s, *params))
+                    .await?;
+
+                tracing::info!(
+                    height = state.block_height(),
+                    pending = state.pending(),
+                    num_changes,
+                    configuration_number = simulation.configuration_number,
+                    validators = simulation.validators.len(),
+                    violations = simulation.violations.len(),
+                    "query simulate staking changes"
+                );
+
+                Ok((state, FvmQueryRet::StakingSimulation(simulation)))
+            }
+            FvmQuery::ReceiptProof(height, index) => {
+                // Always from the committed state, which is what the app hash commits to.
+                let proof = execdigests::receipt_proof(
+                    state.store(),
+                    state.state_params(),
+                    height as ChainEpoch,
+                    index,
+                )?;
+
+                tracing::info!(
+                    height = state.block_height(),
+                    pending = state.pending(),
+                    block_height = height,
+                    index,
+                    found = proof.is_some(),
+                    "query receipt proof"
+                );
+
+                Ok((state, FvmQueryRet::ReceiptProof(proof.map(Box::new))))
+            }
+            FvmQuery::DeadLetters => {
+                let (state, letters) = state.simulate(deadletter::list).await?;
+
+                tracing::info!(
+                    height = state.block_height(),
+                    pending = state.pending(),
+                    count = letters.len(),
+                    "query dead letters"
+                );
+
+                Ok((state, FvmQueryRet::DeadLetters(letters)))
+            }
+            FvmQuery::Capabilities => {
+                bail!("capabilities are reported by the application, not the interpreter")
+            }
         }
     }
 }
@@ -156,6 +281,32 @@ impl<DB, TC> FvmMessageInterpreter<DB, TC>
 where
     DB: Blockstore + 'static + Send + Sync + Clone,
 {
+    /// Estimate the gas limit of a message, populating the gas parameters of the message on the way.
+    async fn estimate_gas(
+        &self,
+        state: FvmQueryState<DB>,
+        msg: &mut Message,
+    ) -> anyhow::Result<(FvmQueryState<DB>, GasEstimate)> {
+        match self.estimate_gassed_msg(state, msg).await? {
+            (state, Some(est)) => {
+                // return immediately if something is returned,
+                // it means that the message failed to execute so there's
+                // no point on estimating the gas.
+                Ok((state, est))
+            }
+            (state, None) => {
+                // perform a gas search for an accurate value
+                let (state, mut est) = self.gas_search(state, msg).await?;
+                // we need an additional overestimation for the case where
+                // the exact value is returned as part of the gas search
+                // (for some reason with subsequent calls sometimes this is the case).
+                est.gas_limit = (est.gas_limit as f64 * self.gas_overestimation_rate) as u64;
+
+                Ok((state, est))
+            }
+        }
+    }
+
     async fn estimate_gassed_msg(
         &self,
         state: FvmQueryState<DB>,
@@ -293,3 +444,107 @@ where
         Ok((state, None))
     }
 }
+
+/// Store the staking changes in the gateway and apply them, to see the power table they result in,
+/// and check it against the constraints in the parameters.
+///
+/// The state is expected to be reverted afterwards.
+fn simulate_staking_changes<DB>(
+    state: &mut FvmExecState<DB>,
+    params: StakingSimulationParams,
+) -> anyhow::Result<StakingSimulation>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    let gateway = GatewayCaller::<DB>::default();
+    let mut violations = Vec::new();
+
+    let changes = params
+        .changes
+        .into_iter()
+        .map(to_staking_change_request)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // If the gateway doesn't accept the changes, they wouldn't be applied on the subnet either;
+    // the power table is what it is now.
+    if let Err(e) = gateway
+        .store_validator_changes(state, changes)
+        .and_then(|()| gateway.apply_validator_changes(state))
+    {
+        violations.push(format!("the gateway rejected the changes: {e:#}"));
+    }
+
+    let (configuration_number, power_table) = ipc_power_table(&gateway, state)?;
+
+    let validators = power_table
+        .0
+        .into_iter()
+        .map(|v| SimulatedValidator {
+            public_key: v.public_key.0.serialize().to_vec(),
+            power: v.power.0,
+        })
+        .collect::<Vec<_>>();
+
+    let total_power = validators
+        .iter()
+        .fold(0u64, |acc, v| acc.saturating_add(v.power));
+
+    if validators.is_empty() {
+        violations.push("the validator set would be empty".to_string());
+    }
+    if params.max_validators > 0 && validators.len() > params.max_validators as usize {
+        violations.push(format!(
+            "there would be {} validators, more than the maximum of {}",
+            validators.len(),
+            params.max_validators
+        ));
+    }
+    for v in validators.iter().filter(|v| v.power < params.min_power) {
+        violations.push(format!(
+            "validator {} would have {} power, less than the minimum of {}",
+            hex::encode(&v.public_key),
+            v.power,
+            params.min_power
+        ));
+    }
+    if total_power > MAX_TOTAL_VOTING_POWER {
+        violations.push(format!(
+            "the total power would be {total_power}, more than the {MAX_TOTAL_VOTING_POWER} Tendermint allows"
+        ));
+    }
+
+    Ok(StakingSimulation {
+        configuration_number,
+        validators,
+        total_power,
+        violations,
+    })
+}
+
+/// Convert a change to what the parent syncer would deliver from the subnet actor,
+/// where the collateral is the ABI encoded amount and the metadata is passed on as it is.
+fn to_staking_change_request(change: StakingChange) -> anyhow::Result<StakingChangeRequest> {
+    let (op, payload) = match change.operation {
+        StakingOperation::Deposit(amount) => {
+            (sdk::StakingOperation::Deposit, encode_amount(&amount)?)
+        }
+        StakingOperation::Withdraw(amount) => {
+            (sdk::StakingOperation::Withdraw, encode_amount(&amount)?)
+        }
+        StakingOperation::SetMetadata(metadata) => {
+            (sdk::StakingOperation::SetMetadata, metadata.into())
+        }
+    };
+    Ok(StakingChangeRequest {
+        configuration_number: change.configuration_number,
+        change: sdk::StakingChange {
+            op,
+            payload,
+            validator: change.validator,
+        },
+    })
+}
+
+fn encode_amount(amount: &TokenAmount) -> anyhow::Result<Vec<u8>> {
+    Ok(abi::encode(&[Token::Uint(to_eth_tokens(amount)?)]))
+}
@@ -0,0 +1,450 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::multihash::Code;
+use fendermint_vm_actor_interface::account;
+use fendermint_vm_actor_interface::burntfunds::BURNT_FUNDS_ACTOR_ID;
+use fendermint_vm_actor_interface::reward::REWARD_ACTOR_ID;
+use fendermint_vm_actor_interface::rewardpool::{
+    self, REWARD_POOL_ACTOR_ADDR, REWARD_POOL_ACTOR_ID,
+};
+use fendermint_vm_actor_interface::validators::{self, VALIDATORS_ACTOR_ID};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{
+    address::Address, econ::TokenAmount, error::ExitCode, event::StampedEvent, ActorID, METHOD_SEND,
+};
+use num_traits::Zero;
+
+use super::{
+    access, events,
+    state::{ExecResult, FvmExecState},
+    FvmMessage,
+};
+
+/// Load the reward pool state and the balance of the actor, if the chain was started with one.
+pub fn get_state<DB>(
+    state: &mut FvmExecState<DB>,
+) -> anyhow::Result<Option<(rewardpool::State, TokenAmount)>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(REWARD_POOL_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let pool = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("reward pool state not found"))?;
+            Ok(Some((pool, actor.balance)))
+        }
+    }
+}
+
+/// Check whether the message claims the rewards of a validator.
+pub fn is_claim(msg: &FvmMessage) -> bool {
+    msg.to == REWARD_POOL_ACTOR_ADDR && msg.method_num == rewardpool::Method::Claim as u64
+}
+
+/// Collect the fees of the previous block into the pool, and at the beginning of a new epoch
+/// mint the inflation of the epochs which ended and share the pool among the validators.
+///
+/// Returns the event to emit about the distribution, if there was one.
+pub fn begin_block<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<StampedEvent>>
+where
+    DB: Blockstore + 'static,
+{
+    let (mut pool, _) = match get_state(state)? {
+        None => return Ok(Vec::new()),
+        Some(pool) => pool,
+    };
+
+    collect_fees(state, REWARD_ACTOR_ID)?;
+    if pool.collect_base_fees {
+        collect_fees(state, BURNT_FUNDS_ACTOR_ID)?;
+    }
+
+    let height = state.block_height();
+    let epoch = pool.epoch(height);
+    if epoch <= pool.epoch {
+        return Ok(Vec::new());
+    }
+
+    let ended = epoch - pool.epoch;
+    let minted = TokenAmount::from_atto(pool.inflation_per_epoch.atto() * ended);
+    if !minted.is_zero() {
+        transfer_to_pool(state, None, &minted)?;
+        state.update_circ_supply(|circ_supply| {
+            *circ_supply += minted.clone();
+        });
+    }
+
+    let balance = pool_balance(state)?;
+    let amount = pool.undistributed(&balance);
+    let validators = active_validators(state)?;
+    let shares = pool.distribute(&amount, &validators);
+    let mut distributed = TokenAmount::zero();
+    for (_, share) in shares.iter() {
+        distributed += share;
+    }
+
+    tracing::info!(
+        height,
+        epoch = pool.epoch,
+        minted = minted.to_string(),
+        distributed = distributed.to_string(),
+        validators = shares.len(),
+        "rewards distributed"
+    );
+
+    let event = event(pool.epoch, &distributed, shares.len());
+    pool.epoch = epoch;
+    set_state(state, &pool).context("failed to update reward pool state")?;
+
+    Ok(vec![event])
+}
+
+/// Transfer the rewards of a validator to the sender of the message.
+///
+/// The message itself is executed as a simple send, so that the sender is charged for gas
+/// and its nonce is incremented; any value it carries is added to the pool. Claims which
+/// fail are charged for the same way, so they can't be used to spam the chain for free.
+pub fn execute_claim<DB>(state: &mut FvmExecState<DB>, msg: FvmMessage) -> ExecResult
+where
+    DB: Blockstore + 'static,
+{
+    let mut pool = match get_state(state)? {
+        None => return access::execute_explicit(state, msg),
+        Some((pool, _)) => pool,
+    };
+
+    if let Some(acl) = access::get_state(state)? {
+        if let Some(reason) = access::check_access(&acl, &msg) {
+            return state.execute_rejected(msg, ExitCode::USR_FORBIDDEN, reason);
+        }
+    }
+
+    let claim = claimant_addresses(state, &msg.from)?
+        .iter()
+        .find_map(|addr| pool.claim(addr));
+
+    let claim = match claim {
+        Some(claim) => claim,
+        None => {
+            let reason = format!("{} has no rewards to claim", msg.from);
+            return state.execute_rejected(msg, ExitCode::USR_NOT_FOUND, reason);
+        }
+    };
+
+    let from = msg.from;
+    let send = FvmMessage {
+        method_num: METHOD_SEND,
+        params: RawBytes::default(),
+        ..msg
+    };
+
+    let (apply_ret, emitters) = state.execute_explicit(send)?;
+
+    if apply_ret.msg_receipt.exit_code.is_success() {
+        let state_tree = state.state_tree_mut();
+        let sender_id = state_tree
+            .lookup_id(&from)?
+            .ok_or_else(|| anyhow!("sender {from} not found"))?;
+
+        state_tree.mutate_actor(REWARD_POOL_ACTOR_ID, |actor| {
+            actor.deduct_funds(&claim.amount)?;
+            Ok(())
+        })?;
+        state_tree.mutate_actor(sender_id, |actor| {
+            actor.deposit_funds(&claim.amount);
+            Ok(())
+        })?;
+
+        set_state(state, &pool).context("failed to update reward pool state")?;
+
+        tracing::info!(
+            sender = from.to_string(),
+            amount = claim.amount.to_string(),
+            "rewards claimed"
+        );
+    }
+
+    Ok((apply_ret, emitters))
+}
+
+/// The addresses a claim can match the key of a validator by: the sender itself, and if it's
+/// an `f0` address, the key address of the account or the delegated address behind it.
+fn claimant_addresses<DB>(
+    state: &mut FvmExecState<DB>,
+    from: &Address,
+) -> anyhow::Result<Vec<Address>>
+where
+    DB: Blockstore + 'static,
+{
+    let mut addrs = vec![*from];
+
+    let id = match from.id() {
+        Ok(id) => id,
+        Err(_) => return Ok(addrs),
+    };
+    let actor = match state.state_tree_mut().get_actor(id)? {
+        Some(actor) => actor,
+        None => return Ok(addrs),
+    };
+
+    addrs.extend(actor.delegated_address);
+
+    if state.builtin_actors().is_account_actor(&actor.code) {
+        let account: account::State = state
+            .state_tree_mut()
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("account state of {from} not found"))?;
+        addrs.push(account.address);
+    }
+
+    Ok(addrs)
+}
+
+/// Move the balance of an actor which receives fees into the pool.
+fn collect_fees<DB>(state: &mut FvmExecState<DB>, id: ActorID) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let balance = match state.state_tree_mut().get_actor(id)? {
+        Some(actor) => actor.balance,
+        None => return Ok(()),
+    };
+    if !balance.is_zero() {
+        transfer_to_pool(state, Some(id), &balance)?;
+    }
+    Ok(())
+}
+
+/// Add funds to the pool, either taken from an actor, or minted.
+fn transfer_to_pool<DB>(
+    state: &mut FvmExecState<DB>,
+    from: Option<ActorID>,
+    amount: &TokenAmount,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    if let Some(id) = from {
+        state_tree.mutate_actor(id, |actor| {
+            actor.deduct_funds(amount)?;
+            Ok(())
+        })?;
+    }
+    state_tree.mutate_actor(REWARD_POOL_ACTOR_ID, |actor| {
+        actor.deposit_funds(amount);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn pool_balance<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<TokenAmount>
+where
+    DB: Blockstore + 'static,
+{
+    state
+        .state_tree_mut()
+        .get_actor(REWARD_POOL_ACTOR_ID)?
+        .map(|actor| actor.balance)
+        .ok_or_else(|| anyhow!("reward pool actor not found"))
+}
+
+/// The public keys and the power of the validators currently in the power table.
+fn active_validators<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Vec<(Vec<u8>, u64)>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let book: validators::State = match state_tree.get_actor(VALIDATORS_ACTOR_ID)? {
+        None => return Ok(Vec::new()),
+        Some(actor) => state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("validator address book state not found"))?,
+    };
+    Ok(book
+        .validators
+        .into_iter()
+        .filter(|v| v.power > 0)
+        .map(|v| (v.public_key, v.power))
+        .collect())
+}
+
+/// An event emitted by the reward pool actor about a distribution.
+fn event(epoch: u64, distributed: &TokenAmount, validators: usize) -> StampedEvent {
+    events::event(
+        REWARD_POOL_ACTOR_ID,
+        vec![
+            events::entry("epoch", epoch.to_be_bytes()),
+            events::entry("distributed", distributed.atto().to_signed_bytes_be()),
+            events::entry("validators", (validators as u64).to_be_bytes()),
+        ],
+    )
+}
+
+fn set_state<DB>(state: &mut FvmExecState<DB>, pool: &rewardpool::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    let mut actor = state_tree
+        .get_actor(REWARD_POOL_ACTOR_ID)?
+        .ok_or_else(|| anyhow!("reward pool actor not found"))?;
+    actor.state = state_tree.store().put_cbor(pool, Code::Blake2b256)?;
+    state_tree.set_actor(REWARD_POOL_ACTOR_ID, actor);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_actor_interface::burntfunds::BURNT_FUNDS_ACTOR_ID;
+    use fendermint_vm_actor_interface::reward::REWARD_ACTOR_ID;
+    use fendermint_vm_actor_interface::rewardpool::{self, REWARD_POOL_ACTOR_ADDR};
+    use fendermint_vm_genesis::{Collateral, Genesis, Rewards, Validator, ValidatorKey};
+    use fvm::engine::MultiEngine;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::ActorID;
+    use num_traits::Zero;
+
+    use super::{begin_block, execute_claim, get_state};
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::{testing, FvmMessage};
+
+    /// Create the genesis state of a chain with a reward pool and a single validator,
+    /// who has an account to claim the rewards with.
+    async fn genesis_state(sk: &SecretKey) -> (MemoryBlockstore, FvmStateParams) {
+        let owner = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        testing::genesis_state(Genesis {
+            validators: vec![Validator {
+                public_key: ValidatorKey::new(sk.public_key()),
+                power: Collateral(TokenAmount::from_whole(1)),
+            }],
+            accounts: vec![testing::account(owner, TokenAmount::from_whole(1))],
+            rewards: Some(Rewards {
+                epoch_length: 10,
+                inflation_per_epoch: TokenAmount::from_whole(10),
+                collect_base_fees: true,
+            }),
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    fn balance(state: &mut FvmExecState<MemoryBlockstore>, id: ActorID) -> TokenAmount {
+        state
+            .state_tree_mut()
+            .get_actor(id)
+            .unwrap()
+            .expect("actor exists")
+            .balance
+    }
+
+    fn deposit(state: &mut FvmExecState<MemoryBlockstore>, id: ActorID, amount: u64) {
+        state
+            .state_tree_mut()
+            .mutate_actor(id, |actor| {
+                actor.deposit_funds(&TokenAmount::from_atto(amount));
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    fn claim(from: Address, sequence: u64) -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from,
+            to: REWARD_POOL_ACTOR_ADDR,
+            sequence,
+            value: TokenAmount::default(),
+            method_num: rewardpool::Method::Claim as u64,
+            params: Default::default(),
+            gas_limit: 10_000_000,
+            gas_fee_cap: TokenAmount::from_atto(10_000),
+            gas_premium: TokenAmount::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn fees_and_inflation_are_distributed_and_claimed() {
+        let sk = SecretKey::try_from(vec![1u8; 32]).unwrap();
+        let (store, params) = genesis_state(&sk).await;
+        let multi_engine = MultiEngine::default();
+
+        // The fees of the previous block are collected into the pool, but the epoch hasn't ended yet.
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 1, params.clone())
+            .expect("failed to create state");
+
+        deposit(&mut state, REWARD_ACTOR_ID, 100);
+        deposit(&mut state, BURNT_FUNDS_ACTOR_ID, 50);
+
+        let events = begin_block(&mut state).unwrap();
+        assert!(events.is_empty());
+        assert!(balance(&mut state, REWARD_ACTOR_ID).is_zero());
+        assert!(balance(&mut state, BURNT_FUNDS_ACTOR_ID).is_zero());
+
+        let (pool, pool_balance) = get_state(&mut state).unwrap().expect("pool exists");
+        assert_eq!(pool_balance, TokenAmount::from_atto(150));
+        assert!(pool.claimable.is_empty());
+
+        let (state_root, updated, _) = state.commit().unwrap();
+        assert_eq!(updated.circ_supply, params.circ_supply);
+
+        // The first epoch ends: the inflation is minted and the pool is shared.
+        let params = FvmStateParams {
+            state_root,
+            ..params
+        };
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 10, params.clone())
+            .expect("failed to create state");
+
+        let events = begin_block(&mut state).unwrap();
+        assert_eq!(events.len(), 1);
+
+        let reward = TokenAmount::from_whole(10) + TokenAmount::from_atto(150);
+        let (pool, _) = get_state(&mut state).unwrap().expect("pool exists");
+        assert_eq!(pool.total_claimable, reward);
+        assert_eq!(pool.epoch, 1);
+
+        // The validator claims with the ID address of its account.
+        let owner = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        let owner_id = state
+            .state_tree_mut()
+            .lookup_id(&owner)
+            .unwrap()
+            .expect("account exists");
+        let before = balance(&mut state, owner_id);
+
+        let (ret, _) = execute_claim(&mut state, claim(Address::new_id(owner_id), 0)).unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK);
+        assert!(balance(&mut state, owner_id) > before);
+
+        let (pool, _) = get_state(&mut state).unwrap().expect("pool exists");
+        assert!(pool.claimable.is_empty());
+        assert!(pool.total_claimable.is_zero());
+
+        // Claiming again fails, but it's not free.
+        let before = balance(&mut state, owner_id);
+        let (ret, _) = execute_claim(&mut state, claim(owner, 1)).unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::USR_NOT_FOUND);
+        assert!(ret.msg_receipt.gas_used > 0);
+        assert!(balance(&mut state, owner_id) < before);
+
+        let (_, updated, _) = state.commit().unwrap();
+        assert_eq!(
+            updated.circ_supply,
+            params.circ_supply + TokenAmount::from_whole(10)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Routing of cross-messages through multi-level subnet hierarchies.
+//!
+//! A message between two subnets travels up from the sender to their closest common ancestor,
+//! then down to the destination, passing through every subnet in between. Messages which arrive
+//! from the parent but are meant for a descendant of this subnet are forwarded to the next child
+//! on the path by the gateway; here we check that the path is valid and that the child exists,
+//! so that a single unroutable message doesn't make the gateway reject the whole batch.
+//! Messages leaving for the parent are checked when they are put into a checkpoint.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use fendermint_vm_actor_interface::eam::EthAddress;
+use fendermint_vm_actor_interface::ipc::GATEWAY_ACTOR_ID;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::address::Address;
+use fvm_shared::event::StampedEvent;
+use ipc_actors_abis::gateway_getter_facet as getter;
+use ipc_sdk::cross::CrossMsg;
+use ipc_sdk::subnet_id::SubnetID;
+
+use super::events;
+use super::state::ipc::GatewayCaller;
+use super::state::FvmExecState;
+
+/// The next hop of a message, as seen from the subnet it's in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// The recipient is in this subnet.
+    Deliver,
+    /// Forward to a child of this subnet, on the way down to the destination.
+    Down(SubnetID),
+    /// Forward to the parent of this subnet, on the way up to the common ancestor.
+    Up(SubnetID),
+}
+
+/// Find the next hop of a message destined to a subnet.
+pub fn route(current: &SubnetID, destination: &SubnetID) -> anyhow::Result<Route> {
+    if current.root_id() != destination.root_id() {
+        bail!(
+            "destination {destination} is under a different root than {current}; there is no route"
+        );
+    }
+    if current == destination {
+        return Ok(Route::Deliver);
+    }
+
+    let here = current.children();
+    let there = destination.children();
+
+    if there.len() > here.len() && there[..here.len()] == here[..] {
+        let mut next = here;
+        next.push(there[next.len()]);
+        return Ok(Route::Down(SubnetID::new(current.root_id(), next)));
+    }
+
+    match current.parent() {
+        Some(parent) => Ok(Route::Up(parent)),
+        None => bail!("destination {destination} is not under the root {current}"),
+    }
+}
+
+/// Checks the routes of the top-down messages arriving from the parent, one at a time.
+///
+/// Messages coming from the parent can only be delivered here or forwarded down to a child
+/// registered with the gateway; sending them back up would be a loop.
+pub struct TopdownRoutes {
+    current: SubnetID,
+    /// Whether each child on the paths is registered, so the gateway is only asked once per batch.
+    registered: HashMap<String, bool>,
+}
+
+impl TopdownRoutes {
+    pub fn new(current: SubnetID) -> Self {
+        Self {
+            current,
+            registered: HashMap::new(),
+        }
+    }
+
+    /// Check the route of a message, returning the reason for rejecting it if it can't be
+    /// routed, and the routing event to emit if it's forwarded or rejected.
+    pub fn check<DB>(
+        &mut self,
+        gateway_caller: &GatewayCaller<DB>,
+        state: &mut FvmExecState<DB>,
+        msg: &CrossMsg,
+    ) -> anyhow::Result<(Option<String>, Option<StampedEvent>)>
+    where
+        DB: Blockstore + 'static,
+    {
+        let current = &self.current;
+        let next_hop = msg
+            .msg
+            .to
+            .subnet()
+            .and_then(|destination| route(current, &destination));
+
+        let rejection = match next_hop {
+            Ok(Route::Deliver) => return Ok((None, None)),
+            Ok(Route::Down(child)) => {
+                let is_registered = match self.registered.get(&child.to_string()) {
+                    Some(is_registered) => *is_registered,
+                    None => {
+                        let is_registered = gateway_caller
+                            .is_registered_subnet(state, &child)
+                            .context("failed to look up child subnet")?;
+                        self.registered.insert(child.to_string(), is_registered);
+                        is_registered
+                    }
+                };
+                if is_registered {
+                    tracing::debug!(
+                        nonce = msg.msg.nonce,
+                        child = child.to_string(),
+                        "forwarding top-down message to a child subnet"
+                    );
+                    let event = routing_event(
+                        "forwarded_down",
+                        msg.msg.nonce,
+                        msg.msg.to.subnet().ok(),
+                        Some(&child),
+                    );
+                    return Ok((None, Some(event)));
+                }
+                format!("child subnet {child} is not registered")
+            }
+            Ok(Route::Up(_)) => {
+                format!("top-down message is not destined to a subnet under {current}")
+            }
+            Err(e) => format!("invalid route: {e:#}"),
+        };
+
+        tracing::warn!(
+            nonce = msg.msg.nonce,
+            reason = rejection,
+            "rejecting unroutable top-down message"
+        );
+        let event = routing_event("unroutable", msg.msg.nonce, msg.msg.to.subnet().ok(), None);
+        Ok((Some(rejection), Some(event)))
+    }
+}
+
+/// Check the routes of the bottom-up messages leaving this subnet with a checkpoint.
+///
+/// The gateway puts the messages which aren't for this subnet or one of its descendants
+/// into the bottom-up batch, so each of them has to go up: either to the parent itself,
+/// or through it to some other part of the hierarchy, where the parent's gateway forwards
+/// it further up or down. The batch is committed to by the checkpoint, so a message can't
+/// be taken out of it here; instead the ones which have nowhere to go are reported, with
+/// the reason, before the parent rejects them.
+pub fn check_bottomup_routes(current: &SubnetID, msgs: &[getter::CrossMsg]) -> Vec<(u64, String)> {
+    let mut misrouted = Vec::new();
+    for msg in msgs {
+        let nonce = msg.message.nonce;
+        let destination = to_subnet_id(&msg.message.to.subnet_id);
+        match route(current, &destination) {
+            Ok(Route::Up(parent)) => {
+                tracing::debug!(
+                    nonce,
+                    destination = destination.to_string(),
+                    parent = parent.to_string(),
+                    "forwarding bottom-up message to the parent subnet"
+                );
+            }
+            Ok(_) => {
+                misrouted.push((
+                    nonce,
+                    format!("bottom-up message is destined to {destination}, under {current}"),
+                ));
+            }
+            Err(e) => misrouted.push((nonce, format!("invalid route: {e:#}"))),
+        }
+    }
+    for (nonce, reason) in misrouted.iter() {
+        tracing::error!(
+            nonce,
+            reason,
+            "unroutable bottom-up message in the checkpoint"
+        );
+    }
+    misrouted
+}
+
+/// The ID of this subnet, according to the gateway.
+pub fn current_subnet_id<DB>(
+    gateway_caller: &GatewayCaller<DB>,
+    state: &mut FvmExecState<DB>,
+) -> anyhow::Result<SubnetID>
+where
+    DB: Blockstore + 'static,
+{
+    let id = gateway_caller
+        .subnet_id(state)
+        .context("failed to get the subnet ID")?;
+    Ok(to_subnet_id(&id))
+}
+
+fn to_subnet_id(id: &getter::SubnetID) -> SubnetID {
+    let route = id
+        .route
+        .iter()
+        .map(|a| Address::from(EthAddress(a.0)))
+        .collect();
+    SubnetID::new(id.root, route)
+}
+
+/// An event emitted by the gateway about the routing of a cross-message.
+fn routing_event(
+    name: &str,
+    nonce: u64,
+    destination: Option<SubnetID>,
+    next: Option<&SubnetID>,
+) -> StampedEvent {
+    let mut entries = vec![
+        events::entry("routing", name),
+        events::entry("nonce", nonce.to_be_bytes()),
+    ];
+    if let Some(destination) = destination {
+        entries.push(events::entry("destination", destination.to_string()));
+    }
+    if let Some(next) = next {
+        entries.push(events::entry("next", next.to_string()));
+    }
+    events::event(GATEWAY_ACTOR_ID, entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use super::{route, Route};
+
+    fn id(s: &str) -> SubnetID {
+        SubnetID::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn routes_through_the_hierarchy() {
+        let root = id("/r314159");
+        let a = id("/r314159/f410fnfmitm2ww7oehhtbokf6wulhrr62sgq3sgqmenq");
+        let ab = id("/r314159/f410fnfmitm2ww7oehhtbokf6wulhrr62sgq3sgqmenq/f410fggjevhgketpz6gw6ordusynlgcd5piyug4aomuq");
+        let c = id("/r314159/f410frbdnwklaitcjsqe7swjwp5naple6vthq4woyfry");
+
+        assert_eq!(route(&a, &a).unwrap(), Route::Deliver);
+        // Down to the grandchild goes through the child first.
+        assert_eq!(route(&root, &ab).unwrap(), Route::Down(a.clone()));
+        assert_eq!(route(&a, &ab).unwrap(), Route::Down(ab.clone()));
+        // A sibling is reached through the common parent.
+        assert_eq!(route(&ab, &c).unwrap(), Route::Up(a.clone()));
+        assert_eq!(route(&a, &c).unwrap(), Route::Up(root.clone()));
+        assert_eq!(route(&a, &root).unwrap(), Route::Up(root.clone()));
+        // Nowhere to go from the root, or across roots.
+        assert!(route(&root, &id("/r1")).is_err());
+        assert!(route(&id("/r1"), &a).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::anyhow;
+use fendermint_vm_actor_interface::scheduler::{self, ScheduledCall, SCHEDULER_ACTOR_ID};
+use fendermint_vm_actor_interface::system;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use fvm_shared::{event::StampedEvent, BLOCK_GAS_LIMIT};
+
+use super::{
+    events,
+    state::{ActorAddressMap, FvmExecState},
+    FvmMessage,
+};
+
+/// Load the scheduler state; if the actor doesn't exist, nothing was registered in genesis.
+pub fn get_state<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<scheduler::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(SCHEDULER_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => state_tree
+            .store()
+            .get_cbor(&actor.state)?
+            .ok_or_else(|| anyhow!("scheduler state not found"))
+            .map(Some),
+    }
+}
+
+/// Send the scheduled calls which are due at the current height from the system actor.
+///
+/// The calls are implicit, so they don't need gas or a nonce. A failing call doesn't
+/// fail the block, because it would halt the chain at every height it's due; instead
+/// it's logged, and the exit code is in the event emitted for each call.
+pub fn execute_due_calls<DB>(
+    state: &mut FvmExecState<DB>,
+) -> anyhow::Result<(Vec<StampedEvent>, ActorAddressMap)>
+where
+    DB: Blockstore + 'static,
+{
+    let sched = match get_state(state)? {
+        None => return Ok(Default::default()),
+        Some(sched) => sched,
+    };
+
+    let height = state.block_height() as u64;
+    let mut events = Vec::new();
+    let mut emitters = ActorAddressMap::default();
+
+    for call in sched.due(height) {
+        let msg = FvmMessage {
+            from: system::SYSTEM_ACTOR_ADDR,
+            to: call.to,
+            sequence: height,
+            // Same as cron; the calls are part of the protocol and aren't charged.
+            gas_limit: BLOCK_GAS_LIMIT * 10000,
+            method_num: call.method_num,
+            params: call.params.clone(),
+            value: Default::default(),
+            version: Default::default(),
+            gas_fee_cap: Default::default(),
+            gas_premium: Default::default(),
+        };
+
+        let (apply_ret, addrs) = state.execute_implicit(msg)?;
+        let exit_code = apply_ret.msg_receipt.exit_code;
+
+        if let Some(err) = apply_ret.failure_info {
+            tracing::warn!(
+                height,
+                name = call.name.as_str(),
+                exit_code = exit_code.value(),
+                error = err.to_string(),
+                "scheduled call failed"
+            );
+        } else {
+            tracing::debug!(
+                height,
+                name = call.name.as_str(),
+                gas_used = apply_ret.msg_receipt.gas_used,
+                "scheduled call executed"
+            );
+        }
+
+        events.extend(apply_ret.events);
+        events.push(event(call, exit_code.value()));
+        emitters.extend(addrs);
+    }
+
+    Ok((events, emitters))
+}
+
+/// An event emitted by the scheduler actor for each call it sent, so that the
+/// outcome is recorded in the block even if the target doesn't emit anything.
+fn event(call: &ScheduledCall, exit_code: u32) -> StampedEvent {
+    events::event(
+        SCHEDULER_ACTOR_ID,
+        vec![
+            events::entry("scheduled_call", call.name.as_str()),
+            events::entry("exit_code", exit_code.to_be_bytes()),
+        ],
+    )
+}
@@ -3,7 +3,9 @@
 
 use std::collections::{HashMap, HashSet};
 
+use anyhow::Context;
 use cid::Cid;
+use fendermint_vm_actor_interface::execdigests::TxReceipt;
 use fendermint_vm_genesis::PowerScale;
 use fvm::{
     call_manager::DefaultCallManager,
@@ -17,7 +19,7 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::{
     address::Address, chainid::ChainID, clock::ChainEpoch, econ::TokenAmount, error::ExitCode,
-    message::Message, receipt::Receipt, version::NetworkVersion, ActorID,
+    message::Message, receipt::Receipt, version::NetworkVersion, ActorID, METHOD_SEND,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -63,10 +65,10 @@ pub struct FvmStateParams {
 /// Parts of the state which can be updated by message execution, apart from the actor state.
 ///
 /// This is just a technical thing to help us not forget about saving something.
-///
-/// TODO: `base_fee` should surely be here.
 #[derive(Debug)]
 pub struct FvmUpdatableParams {
+    /// The base fee can be raised by governance.
+    pub base_fee: TokenAmount,
     /// The circulating supply changes if IPC is enabled and
     /// funds/releases are carried out with the parent.
     pub circ_supply: TokenAmount,
@@ -97,6 +99,21 @@ where
 
     /// Indicate whether the parameters have been updated.
     params_dirty: bool,
+
+    /// Gas which can still be allocated to transactions in the block, if it's limited.
+    block_gas_available: Option<u64>,
+
+    /// Number of top-down messages taken for execution so far in the block.
+    topdown_msgs_taken: u64,
+
+    /// Receipts of the transactions delivered so far in the block, if the chain records execution digests.
+    tx_receipts: Vec<TxReceipt>,
+
+    /// CBOR encoded events emitted by the transactions delivered so far in the block, in order.
+    tx_events: Vec<Vec<u8>>,
+
+    /// Number of messages executed by the FVM so far, whichever path they came through.
+    executed_msgs: u64,
 }
 
 impl<DB> FvmExecState<DB>
@@ -112,6 +129,27 @@ where
         multi_engine: &MultiEngine,
         block_height: ChainEpoch,
         params: FvmStateParams,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_tracing(blockstore, multi_engine, block_height, params, false)
+    }
+
+    /// Create a new FVM execution environment which records the execution trace of messages,
+    /// for when we need to know which actors they called.
+    pub fn new_traced(
+        blockstore: DB,
+        multi_engine: &MultiEngine,
+        block_height: ChainEpoch,
+        params: FvmStateParams,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_tracing(blockstore, multi_engine, block_height, params, true)
+    }
+
+    fn new_with_tracing(
+        blockstore: DB,
+        multi_engine: &MultiEngine,
+        block_height: ChainEpoch,
+        params: FvmStateParams,
+        tracing: bool,
     ) -> anyhow::Result<Self> {
         let mut nc = NetworkConfig::new(params.network_version);
         nc.chain_id = ChainID::from(params.chain_id);
@@ -122,26 +160,53 @@ where
         let mut mc = nc.for_epoch(block_height, params.timestamp.0, params.state_root);
         mc.set_base_fee(params.base_fee);
         mc.set_circulating_supply(params.circ_supply.clone());
+        if tracing {
+            mc.enable_tracing();
+        }
 
         // Creating a new machine every time is prohibitively slow.
         // let ec = EngineConfig::from(&nc);
         // let engine = EnginePool::new_default(ec)?;
 
+        // Load the recent block hashes before the blockstore is moved into the machine.
+        let externs = FendermintExterns::load(&blockstore, params.state_root)
+            .context("failed to load externs")?;
+
         let engine = multi_engine.get(&nc)?;
-        let machine = DefaultMachine::new(&mc, blockstore, FendermintExterns)?;
+        let machine = DefaultMachine::new(&mc, blockstore, externs)?;
         let executor = DefaultExecutor::new(engine, machine)?;
 
         Ok(Self {
             executor,
             block_hash: None,
             params: FvmUpdatableParams {
+                base_fee: params.base_fee,
                 circ_supply: params.circ_supply,
                 power_scale: params.power_scale,
             },
             params_dirty: false,
+            block_gas_available: None,
+            topdown_msgs_taken: 0,
+            tx_receipts: Vec::new(),
+            tx_events: Vec::new(),
+            executed_msgs: 0,
         })
     }
 
+    /// The parameters the state was created with, before any message was executed.
+    pub fn initial_state_params(&self) -> FvmStateParams {
+        let ctx = self.executor.context();
+        FvmStateParams {
+            state_root: ctx.initial_state_root,
+            timestamp: Timestamp(ctx.timestamp),
+            network_version: ctx.network.network_version,
+            base_fee: ctx.base_fee.clone(),
+            circ_supply: ctx.circ_supply.clone(),
+            chain_id: ctx.network.chain_id.into(),
+            power_scale: self.params.power_scale,
+        }
+    }
+
     /// Set the block hash during execution.
     pub fn with_block_hash(mut self, block_hash: BlockHash) -> Self {
         self.block_hash = Some(block_hash);
@@ -158,6 +223,31 @@ where
         self.execute_message(msg, ApplyKind::Explicit)
     }
 
+    /// Execute a message the interpreter decided not to carry out, e.g. because it failed a
+    /// check the FVM doesn't know about, as a simple send of nothing, so that the sender is
+    /// still charged for gas and its nonce is incremented.
+    ///
+    /// Unless the send itself fails, the receipt shows the given exit code and reason.
+    pub fn execute_rejected(
+        &mut self,
+        msg: Message,
+        exit_code: ExitCode,
+        reason: String,
+    ) -> ExecResult {
+        let send = Message {
+            method_num: METHOD_SEND,
+            params: RawBytes::default(),
+            value: TokenAmount::from_atto(0),
+            ..msg
+        };
+        let (mut ret, emitters) = self.execute_explicit(send)?;
+        if ret.msg_receipt.exit_code.is_success() {
+            ret.msg_receipt.exit_code = exit_code;
+            ret.failure_info = Some(ApplyFailure::PreValidation(reason));
+        }
+        Ok((ret, emitters))
+    }
+
     pub fn execute_message(&mut self, msg: Message, kind: ApplyKind) -> ExecResult {
         if let Err(e) = msg.check() {
             return Ok(check_error(e));
@@ -165,6 +255,7 @@ where
 
         // TODO: We could preserve the message length by changing the input type.
         let raw_length = fvm_ipld_encoding::to_vec(&msg).map(|bz| bz.len())?;
+        self.executed_msgs += 1;
         let ret = self.executor.execute_message(msg, kind, raw_length)?;
         let addrs = self.emitter_delegated_addresses(&ret)?;
         Ok((ret, addrs))
@@ -182,6 +273,18 @@ where
         Ok((cid, self.params, self.params_dirty))
     }
 
+    /// Write the state as it is now to the blockstore, without ending the block,
+    /// so it can be loaded from the returned root by someone else.
+    pub fn flush(&mut self) -> anyhow::Result<Cid> {
+        self.executor.flush()
+    }
+
+    /// Number of messages the FVM executed so far; if it changes between two points,
+    /// something was executed in between.
+    pub fn executed_msgs(&self) -> u64 {
+        self.executed_msgs
+    }
+
     /// The height of the currently executing block.
     pub fn block_height(&self) -> ChainEpoch {
         self.executor.context().epoch
@@ -197,6 +300,11 @@ where
         Timestamp(self.executor.context().timestamp)
     }
 
+    /// The base fee the block is executed with.
+    pub fn base_fee(&self) -> &TokenAmount {
+        &self.executor.context().base_fee
+    }
+
     /// Conversion between collateral and voting power.
     pub fn power_scale(&self) -> PowerScale {
         self.params.power_scale
@@ -217,6 +325,11 @@ where
         self.executor.context().network.chain_id
     }
 
+    /// The externs of the machine, loaded when the state was created.
+    pub(crate) fn externs(&self) -> &FendermintExterns {
+        self.executor.externs()
+    }
+
     /// Collect all the event emitters' delegated addresses, for those who have any.
     fn emitter_delegated_addresses(&self, apply_ret: &ApplyRet) -> anyhow::Result<ActorAddressMap> {
         let emitter_ids = apply_ret
@@ -246,6 +359,57 @@ where
         self.update_params(|p| f(&mut p.circ_supply))
     }
 
+    /// Update the base fee, effective from the next block.
+    pub fn update_base_fee<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut TokenAmount),
+    {
+        self.update_params(|p| f(&mut p.base_fee))
+    }
+
+    /// Limit the sum of the gas limits of the transactions in the block; 0 means no limit.
+    pub fn set_block_gas_limit(&mut self, limit: u64) {
+        self.block_gas_available = if limit == 0 { None } else { Some(limit) };
+    }
+
+    /// Allocate gas to a transaction from what's left in the block.
+    ///
+    /// Returns `false` if there isn't enough gas left.
+    pub fn take_block_gas(&mut self, gas_limit: u64) -> bool {
+        match self.block_gas_available {
+            None => true,
+            Some(available) if available >= gas_limit => {
+                self.block_gas_available = Some(available - gas_limit);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Number of top-down messages taken for execution so far in the block.
+    pub fn topdown_msgs_taken(&self) -> u64 {
+        self.topdown_msgs_taken
+    }
+
+    /// Count top-down messages taken for execution, so that the batch limit applies to the whole block.
+    pub fn take_topdown_msgs(&mut self, count: u64) {
+        self.topdown_msgs_taken += count;
+    }
+
+    /// Collect the receipt of a transaction and the events it emitted, for the digests of the block.
+    pub fn collect_tx_receipt(&mut self, receipt: TxReceipt, events: Vec<Vec<u8>>) {
+        self.tx_receipts.push(receipt);
+        self.tx_events.extend(events);
+    }
+
+    /// Take the receipts and the events collected so far in the block.
+    pub fn take_tx_receipts(&mut self) -> (Vec<TxReceipt>, Vec<Vec<u8>>) {
+        (
+            std::mem::take(&mut self.tx_receipts),
+            std::mem::take(&mut self.tx_events),
+        )
+    }
+
     /// Update the parameters and mark them as dirty.
     fn update_params<F>(&mut self, f: F)
     where
@@ -273,10 +437,18 @@ where
 /// use ABCI++ to filter out messages from blocks, but that doesn't affect queries, so we
 /// might as well encode it as an error. To keep the types simpler, let's fabricate an `ApplyRet`.
 fn check_error(e: anyhow::Error) -> (ApplyRet, ActorAddressMap) {
+    reject_message(
+        ExitCode::SYS_ASSERTION_FAILED,
+        ApplyFailure::PreValidation(format!("{:#}", e)),
+    )
+}
+
+/// Fabricate an `ApplyRet` for a message which we decided not to pass to the FVM at all.
+pub fn reject_message(exit_code: ExitCode, failure: ApplyFailure) -> (ApplyRet, ActorAddressMap) {
     let zero = TokenAmount::from_atto(0);
     let ret = ApplyRet {
         msg_receipt: Receipt {
-            exit_code: ExitCode::SYS_ASSERTION_FAILED,
+            exit_code,
             return_data: RawBytes::default(),
             gas_used: 0,
             events_root: None,
@@ -288,7 +460,7 @@ fn check_error(e: anyhow::Error) -> (ApplyRet, ActorAddressMap) {
         refund: zero,
         gas_refund: 0,
         gas_burned: 0,
-        failure_info: Some(ApplyFailure::PreValidation(format!("{:#}", e))),
+        failure_info: Some(failure),
         exec_trace: Vec::new(),
         events: Vec::new(),
     };
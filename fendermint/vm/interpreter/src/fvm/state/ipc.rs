@@ -5,14 +5,17 @@ use anyhow::{anyhow, Context};
 use ethers::types as et;
 
 use fendermint_vm_message::conv::{from_eth, from_fvm};
+use fvm::executor::ApplyFailure;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_shared::ActorID;
 
 use fendermint_crypto::SecretKey;
 use fendermint_vm_actor_interface::{
     eam::EthAddress,
+    evm,
     init::builtin_actor_eth_addr,
     ipc::{AbiHash, ValidatorMerkleTree, GATEWAY_ACTOR_ID},
+    system,
 };
 use fendermint_vm_genesis::{Power, Validator};
 use fendermint_vm_message::signed::sign_secp256k1;
@@ -23,9 +26,11 @@ use ipc_actors_abis::gateway_router_facet as router;
 use ipc_actors_abis::gateway_router_facet::GatewayRouterFacet;
 use ipc_sdk::cross::CrossMsg;
 use ipc_sdk::staking::StakingChangeRequest;
+use ipc_sdk::subnet_id::SubnetID;
 
 use super::{
-    fevm::{ContractCaller, MockProvider, NoRevert},
+    exec::reject_message,
+    fevm::{CallError, ContractCaller, MockProvider, NoRevert},
     FvmExecState,
 };
 use crate::fvm::FvmApplyRet;
@@ -82,11 +87,43 @@ impl<DB: Blockstore> GatewayCaller<DB> {
         self.getter.call(state, |c| c.get_network_name())
     }
 
+    /// Check whether a child subnet is registered with the gateway.
+    pub fn is_registered_subnet(
+        &self,
+        state: &mut FvmExecState<DB>,
+        subnet_id: &SubnetID,
+    ) -> anyhow::Result<bool> {
+        let route = subnet_id
+            .children()
+            .iter()
+            .map(|a| {
+                from_fvm::to_eth_address(a)
+                    .ok_or_else(|| anyhow!("subnet actor {a} doesn't have an Ethereum address"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let id = getter::SubnetID {
+            root: subnet_id.root_id(),
+            route,
+        };
+        let (found, _) = self.getter.call(state, |c| c.get_subnet(id))?;
+        Ok(found)
+    }
+
     /// Fetch the period with which the current subnet has to submit checkpoints to its parent.
     pub fn bottom_up_check_period(&self, state: &mut FvmExecState<DB>) -> anyhow::Result<u64> {
         self.getter.call(state, |c| c.bottom_up_check_period())
     }
 
+    /// Fetch the nonce the gateway gives to the next bottom-up message.
+    pub fn bottom_up_nonce(&self, state: &mut FvmExecState<DB>) -> anyhow::Result<u64> {
+        self.getter.call(state, |c| c.bottom_up_nonce())
+    }
+
+    /// Fetch the nonce the gateway expects the next top-down message to have.
+    pub fn applied_topdown_nonce(&self, state: &mut FvmExecState<DB>) -> anyhow::Result<u64> {
+        self.getter.call(state, |c| c.applied_top_down_nonce())
+    }
+
     /// Fetch the bottom-up messages enqueued for a given checkpoint height.
     pub fn bottom_up_msgs(
         &self,
@@ -236,20 +273,45 @@ impl<DB: Blockstore> GatewayCaller<DB> {
         Ok(())
     }
 
-    pub fn apply_cross_messages(
+    /// Apply top-down messages, returning the reason of the failure along with the receipt, if the call reverted.
+    ///
+    /// The gateway applies the messages in a single call, so if one fails, none of them are applied.
+    pub fn try_apply_cross_messages(
         &self,
         state: &mut FvmExecState<DB>,
         cross_messages: Vec<CrossMsg>,
-    ) -> anyhow::Result<FvmApplyRet> {
+    ) -> anyhow::Result<(FvmApplyRet, Option<String>)> {
         let messages = cross_messages
             .into_iter()
             .map(router::CrossMsg::try_from)
             .collect::<Result<Vec<_>, _>>()
             .context("failed to convert cross messages")?;
-        let r = self
+
+        match self
             .router
-            .call_with_return(state, |c| c.apply_cross_messages(messages))?;
-        Ok(r.into_return())
+            .try_call_with_ret(state, |c| c.apply_cross_messages(messages))?
+        {
+            Ok(r) => Ok((r.into_return(), None)),
+            Err(CallError {
+                exit_code,
+                failure_info,
+                error,
+            }) => {
+                let reason = format!("{error:?}");
+                let failure =
+                    failure_info.unwrap_or_else(|| ApplyFailure::PreValidation(reason.clone()));
+                let (apply_ret, emitters) = reject_message(exit_code, failure);
+                let ret = FvmApplyRet {
+                    apply_ret,
+                    from: system::SYSTEM_ACTOR_ADDR,
+                    to: self.addr.into(),
+                    method_num: evm::Method::InvokeContract as u64,
+                    gas_limit: fvm_shared::BLOCK_GAS_LIMIT,
+                    emitters,
+                };
+                Ok((ret, Some(reason)))
+            }
+        }
     }
 
     pub fn get_latest_parent_finality(
@@ -276,6 +338,30 @@ impl<DB: Blockstore> GatewayCaller<DB> {
 
         Ok(addrs)
     }
+
+    /// Get a checkpoint along with the quorum information and signatures collected for it.
+    pub fn checkpoint_bundle(
+        &self,
+        state: &mut FvmExecState<DB>,
+        height: u64,
+    ) -> anyhow::Result<(
+        getter::BottomUpCheckpoint,
+        getter::QuorumInfo,
+        Vec<et::Address>,
+        Vec<et::Bytes>,
+    )> {
+        self.getter.call(state, |c| c.get_signature_bundle(height))
+    }
+
+    /// Remove the checkpoints, their signatures and bottom-up messages below a height.
+    pub fn prune_bottom_up_checkpoints(
+        &self,
+        state: &mut FvmExecState<DB>,
+        retention_height: u64,
+    ) -> anyhow::Result<()> {
+        self.router
+            .call(state, |c| c.prune_bottom_up_checkpoints(retention_height))
+    }
 }
 
 /// Total amount of tokens to mint as a result of top-down messages arriving at the subnet.
@@ -12,9 +12,12 @@ pub mod snapshot;
 use std::sync::Arc;
 
 pub use check::FvmCheckState;
-pub use exec::{BlockHash, FvmExecState, FvmStateParams, FvmUpdatableParams};
+pub use exec::{
+    reject_message, ActorAddressMap, BlockHash, ExecResult, FvmExecState, FvmStateParams,
+    FvmUpdatableParams,
+};
 pub use genesis::{empty_state_tree, FvmGenesisState};
-pub use query::FvmQueryState;
+pub use query::{FvmQueryState, QueryBudget, QueryBudgetExceeded};
 
 use super::store::ReadOnlyBlockstore;
 
@@ -1,26 +1,62 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use std::{cell::RefCell, sync::Arc};
 
 use anyhow::{anyhow, Context};
 
 use cid::Cid;
-use fendermint_vm_actor_interface::system::is_system_addr;
+use fendermint_vm_actor_interface::{evm, init::FIRST_NON_SINGLETON_ADDR, system::is_system_addr};
 use fendermint_vm_core::chainid::HasChainID;
-use fendermint_vm_message::query::ActorState;
+use fendermint_vm_message::query::{AccessedActor, ActorState};
 use fvm::engine::MultiEngine;
 use fvm::executor::ApplyRet;
 use fvm::state_tree::StateTree;
+use fvm::trace::ExecutionEvent;
 use fvm_ipld_blockstore::Blockstore;
-use fvm_shared::{address::Address, chainid::ChainID, clock::ChainEpoch, ActorID};
+use fvm_ipld_encoding::CborStore;
+use fvm_shared::{
+    address::Address, chainid::ChainID, clock::ChainEpoch, error::ExitCode, ActorID,
+    BLOCK_GAS_LIMIT,
+};
+use libipld::Ipld;
 use num_traits::Zero;
 
-use crate::fvm::{store::ReadOnlyBlockstore, FvmMessage};
+use crate::fvm::{
+    store::{overlay::OverlayBlockstore, read_deadline, ReadOnlyBlockstore},
+    FvmMessage,
+};
 
 use super::{CheckStateRef, FvmExecState, FvmStateParams};
 
+/// Limits on the resources a query can use.
+///
+/// Queries don't cost anything to the caller, so without limits a crafted read-only
+/// call could keep the node busy for as long as the block gas limit allows, or longer
+/// with queries running many calls, like gas estimation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryBudget {
+    /// Maximum gas a single call can use; 0 means the block gas limit.
+    pub gas_limit: u64,
+    /// Maximum time a query can take; `None` means no limit.
+    ///
+    /// A call running past it is stopped at its next read from the state, so pure
+    /// computation is only bounded by the gas budget.
+    pub timeout: Option<Duration>,
+}
+
+/// Error returned when a query runs out of its [QueryBudget].
+#[derive(Debug, thiserror::Error)]
+pub enum QueryBudgetExceeded {
+    #[error("the call ran out of the query gas budget of {0}")]
+    Gas(u64),
+    #[error("the query took longer than its time budget of {0:?}")]
+    Time(Duration),
+}
+
 /// The state over which we run queries. These can interrogate the IPLD block store or the state tree.
 pub struct FvmQueryState<DB>
 where
@@ -42,6 +78,10 @@ where
     check_state: CheckStateRef<DB>,
     /// Whether to try ot use the check state or not.
     pending: bool,
+    /// Limits on the calls made by the query.
+    budget: QueryBudget,
+    /// Time by which the query has to be done, if it has a time budget.
+    deadline: Option<Instant>,
 }
 
 impl<DB> FvmQueryState<DB>
@@ -75,11 +115,30 @@ where
             exec_state: RefCell::new(None),
             check_state,
             pending,
+            budget: QueryBudget::default(),
+            deadline: None,
         };
 
         Ok(state)
     }
 
+    /// Limit the resources the query can use; the time budget starts now.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.deadline = budget.timeout.map(|t| Instant::now() + t);
+        self.budget = budget;
+        self
+    }
+
+    /// Fail if the query has run out of time.
+    fn check_deadline(&self) -> anyhow::Result<()> {
+        match (self.deadline, self.budget.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() > deadline => {
+                Err(QueryBudgetExceeded::Time(timeout).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Do not make the changes in the call persistent. They should be run on top of
     /// transactions added to the mempool, but they can run independent of each other.
     ///
@@ -92,9 +151,12 @@ where
     where
         F: FnOnce(&mut FvmExecState<ReadOnlyBlockstore<DB>>) -> anyhow::Result<T>,
     {
+        // Checked here because getting the state might have involved waiting for the lock.
+        self.check_deadline()?;
+
         exec_state.state_tree_mut().begin_transaction();
 
-        let res = f(exec_state);
+        let res = self.within_deadline(|| f(exec_state));
 
         exec_state
             .state_tree_mut()
@@ -103,6 +165,19 @@ where
         res
     }
 
+    /// Run a function which reads the state, failing with the time budget error
+    /// if it ran out of time, even if that only made one of its reads fail.
+    fn within_deadline<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce() -> anyhow::Result<T>,
+    {
+        let guard = read_deadline(self.deadline);
+        let res = f();
+        drop(guard);
+        self.check_deadline()?;
+        res
+    }
+
     /// If we know the query is over the state, cache the state tree.
     async fn with_exec_state<T, F>(self, f: F) -> anyhow::Result<(Self, T)>
     where
@@ -144,11 +219,25 @@ where
         res.map(|r| (self, r))
     }
 
+    /// Run a function on the execution state, reverting whatever it changes afterwards,
+    /// e.g. to see what applying some changes in the actors would result in.
+    pub async fn simulate<T, F>(self, f: F) -> anyhow::Result<(Self, T)>
+    where
+        F: FnOnce(&mut FvmExecState<ReadOnlyBlockstore<DB>>) -> anyhow::Result<T>,
+    {
+        self.with_exec_state(f).await
+    }
+
     /// Read a CID from the underlying IPLD store.
     pub fn store_get(&self, key: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         self.store.get(key)
     }
 
+    /// The underlying IPLD store, with the committed state at the height of the query.
+    pub fn store(&self) -> &ReadOnlyBlockstore<DB> {
+        &self.store
+    }
+
     /// Get the state of an actor, if it exists.
     pub async fn actor_state(
         self,
@@ -169,35 +258,93 @@ where
     /// unless it's called with `revert`.
     pub async fn call(
         self,
-        mut msg: FvmMessage,
+        msg: FvmMessage,
     ) -> anyhow::Result<(Self, (ApplyRet, HashMap<u64, Address>))> {
-        self.with_exec_state(|s| {
-            // If the sequence is zero, treat it as a signal to use whatever is in the state.
-            if msg.sequence.is_zero() {
-                let state_tree = s.state_tree_mut();
-                if let Some(id) = state_tree.lookup_id(&msg.from)? {
-                    state_tree.get_actor(id)?.map(|st| {
-                        msg.sequence = st.sequence;
-                        st
-                    });
-                }
-            }
+        let gas_budget = self.budget.gas_limit;
+        self.with_exec_state(|s| execute_call(s, msg, gas_budget))
+            .await
+    }
 
-            // If the gas_limit is zero, set it to the block gas limit so that call will not hit
-            // gas limit not set error. It is possible, in the future, to estimate the gas limit
-            // based on the account balance and base fee + premium for higher accuracy.
-            if msg.gas_limit == 0 {
-                msg.gas_limit = fvm_shared::BLOCK_GAS_LIMIT;
-            }
+    /// Run a "read-only" message with tracing, and list the actors it accessed, apart from
+    /// the sender and the builtin singletons, with the EVM storage slots it touched.
+    ///
+    /// Like Ethereum, the recipient is only listed if its storage was accessed.
+    ///
+    /// The message is executed on a fresh state, because the cached one doesn't record traces,
+    /// but it's over the pending changes if the query asked for them.
+    pub async fn call_traced(
+        self,
+        msg: FvmMessage,
+    ) -> anyhow::Result<(Self, (ApplyRet, Vec<AccessedActor>))> {
+        self.check_deadline()?;
+
+        let (state, (store, state_params)) = self.traced_base().await?;
+
+        let reads = Arc::new(Mutex::new(HashSet::new()));
+        let recording = RecordingBlockstore {
+            inner: store.clone(),
+            reads: reads.clone(),
+        };
+
+        let mut exec_state = FvmExecState::new_traced(
+            recording,
+            state.multi_engine.as_ref(),
+            state.block_height,
+            state_params.clone(),
+        )
+        .context("error creating execution state")?;
+
+        let (from, to) = (msg.from, msg.to);
+        let (ret, _) =
+            state.within_deadline(|| execute_call(&mut exec_state, msg, state.budget.gas_limit))?;
+
+        // Anything read from now on is just us looking at the results.
+        let reads = std::mem::take(&mut *reads.lock().unwrap());
+
+        let actors = state.within_deadline(|| {
+            accessed_actors(
+                &mut exec_state,
+                &store,
+                state_params.state_root,
+                &reads,
+                &ret,
+                from,
+                to,
+            )
+        })?;
+
+        Ok((state, (ret, actors)))
+    }
+
+    /// The state to trace a call on: the pending one if the query asked for it and there
+    /// is any, otherwise the one at the height of the query.
+    async fn traced_base(
+        self,
+    ) -> anyhow::Result<(
+        Self,
+        (OverlayBlockstore<ReadOnlyBlockstore<DB>>, FvmStateParams),
+    )> {
+        let store = OverlayBlockstore::new(self.store.clone());
+        let mut state_params = self.state_params.clone();
+
+        if self.pending {
+            let mut guard = self.check_state.lock().await;
 
-            if is_system_addr(&msg.from) {
-                // Explicit execution requires `from` to be an account kind.
-                s.execute_implicit(msg)
-            } else {
-                s.execute_explicit(msg)
+            if let Some(ref mut exec_state) = *guard {
+                // The pending changes only exist in the buffer of the FVM, so they are
+                // flushed there and copied over to the store of the new execution.
+                let state_tree = exec_state.state_tree_mut();
+                let state_root = state_tree
+                    .flush()
+                    .context("failed to flush the pending state")?;
+
+                self.within_deadline(|| store.copy_from(state_tree.store(), state_root))?;
+
+                state_params.state_root = state_root;
             }
-        })
-        .await
+        }
+
+        Ok((self, (store, state_params)))
     }
 
     pub fn state_params(&self) -> &FvmStateParams {
@@ -213,6 +360,216 @@ where
     }
 }
 
+/// List the actors called during the execution of a message, with the storage keys of
+/// the EVM contracts among them which were read or written.
+fn accessed_actors<DB>(
+    exec_state: &mut FvmExecState<RecordingBlockstore<DB>>,
+    base: &DB,
+    base_root: Cid,
+    reads: &HashSet<Cid>,
+    ret: &ApplyRet,
+    from: Address,
+    to: Address,
+) -> anyhow::Result<Vec<AccessedActor>>
+where
+    DB: Blockstore + Clone + 'static,
+{
+    let evm_code = exec_state
+        .builtin_actors()
+        .code_by_id(evm::EVM_ACTOR_CODE_ID)
+        .cloned();
+
+    let before = StateTree::new_from_root(base.clone(), &base_root)?;
+    let state_tree = exec_state.state_tree_mut();
+
+    let from = state_tree.lookup_id(&from)?;
+    let to = state_tree.lookup_id(&to)?;
+
+    let mut seen = Vec::new();
+    let mut actors = Vec::new();
+    for event in ret.exec_trace.iter() {
+        if let ExecutionEvent::Call { to: callee, .. } = event {
+            let id = match state_tree.lookup_id(callee)? {
+                Some(id) if Some(id) != from && !seen.contains(&id) => id,
+                _ => continue,
+            };
+            seen.push(id);
+
+            let actor = state_tree.get_actor(id)?;
+            let is_evm = actor.as_ref().map(|a| Some(a.code) == evm_code) == Some(true);
+
+            // The system contracts are deployed at fixed IDs among the singletons.
+            if id < FIRST_NON_SINGLETON_ADDR && !is_evm {
+                continue;
+            }
+
+            // Slots which were only read are in the nodes of the storage before the call,
+            // the ones which were written can be in new nodes after it.
+            let mut storage_keys = BTreeSet::new();
+            if let Some(prev) = before.get_actor(id)? {
+                if Some(prev.code) == evm_code {
+                    collect_storage_keys(base, prev.state, base, reads, &mut storage_keys)?;
+                }
+            }
+            if let Some(ref actor) = actor {
+                if is_evm {
+                    let store = state_tree.store();
+                    collect_storage_keys(store, actor.state, base, reads, &mut storage_keys)?;
+                }
+            }
+
+            if Some(id) == to && storage_keys.is_empty() {
+                continue;
+            }
+
+            let address = actor
+                .and_then(|a| a.delegated_address)
+                .unwrap_or_else(|| Address::new_id(id));
+
+            actors.push(AccessedActor {
+                address,
+                storage_keys: storage_keys.into_iter().collect(),
+            });
+        }
+    }
+
+    Ok(actors)
+}
+
+/// Collect the keys in the storage of an EVM actor from the nodes which were either read
+/// during the execution or are new, i.e. not in the base store.
+///
+/// The storage is a KAMT, where a node is `[bitfield, pointers]` and a pointer is either
+/// a link to another node, potentially with an extension, or a list of key-value pairs,
+/// with the keys as big-endian bytes without the leading zeros.
+fn collect_storage_keys<S, DB>(
+    store: &S,
+    actor_state: Cid,
+    base: &DB,
+    reads: &HashSet<Cid>,
+    keys: &mut BTreeSet<[u8; 32]>,
+) -> anyhow::Result<()>
+where
+    S: Blockstore,
+    DB: Blockstore,
+{
+    let state: evm::State = store
+        .get_cbor(&actor_state)?
+        .ok_or_else(|| anyhow!("EVM actor state {actor_state} not found"))?;
+
+    let mut todo = vec![state.contract_state];
+    while let Some(cid) = todo.pop() {
+        if !reads.contains(&cid) && base.has(&cid)? {
+            continue;
+        }
+        let node: Ipld = store
+            .get_cbor(&cid)?
+            .ok_or_else(|| anyhow!("EVM storage node {cid} not found"))?;
+
+        let pointers = match node {
+            Ipld::List(mut node) if node.len() == 2 => match node.pop() {
+                Some(Ipld::List(pointers)) => pointers,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        for pointer in pointers {
+            match pointer {
+                Ipld::Link(cid) => todo.push(cid),
+                Ipld::List(items) => {
+                    for item in items {
+                        match item {
+                            Ipld::Link(cid) => todo.push(cid),
+                            Ipld::List(kv) => match kv.first() {
+                                Some(Ipld::Bytes(k)) if k.len() <= 32 => {
+                                    let mut key = [0u8; 32];
+                                    key[32 - k.len()..].copy_from_slice(k);
+                                    keys.insert(key);
+                                }
+                                _ => {}
+                            },
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Remembers which blocks were read, to tell which parts of the state a call accessed.
+#[derive(Clone)]
+struct RecordingBlockstore<DB> {
+    inner: DB,
+    reads: Arc<Mutex<HashSet<Cid>>>,
+}
+
+impl<DB: Blockstore> Blockstore for RecordingBlockstore<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.reads.lock().unwrap().insert(*k);
+        self.inner.get(k)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        self.inner.has(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_keyed(k, block)
+    }
+}
+
+/// Execute a read-only call, with its gas limit capped by the budget of the query;
+/// running out of the budget is an error rather than a failed call.
+fn execute_call<DB>(
+    s: &mut FvmExecState<DB>,
+    mut msg: FvmMessage,
+    gas_budget: u64,
+) -> anyhow::Result<(ApplyRet, HashMap<u64, Address>)>
+where
+    DB: Blockstore + 'static,
+{
+    // If the sequence is zero, treat it as a signal to use whatever is in the state.
+    if msg.sequence.is_zero() {
+        let state_tree = s.state_tree_mut();
+        if let Some(id) = state_tree.lookup_id(&msg.from)? {
+            state_tree.get_actor(id)?.map(|st| {
+                msg.sequence = st.sequence;
+                st
+            });
+        }
+    }
+
+    // If the gas_limit is zero, set it to the block gas limit so that call will not hit
+    // gas limit not set error. It is possible, in the future, to estimate the gas limit
+    // based on the account balance and base fee + premium for higher accuracy.
+    if msg.gas_limit == 0 {
+        msg.gas_limit = BLOCK_GAS_LIMIT;
+    }
+
+    let capped = gas_budget > 0 && msg.gas_limit > gas_budget;
+    if capped {
+        msg.gas_limit = gas_budget;
+    }
+
+    let (ret, emitters) = if is_system_addr(&msg.from) {
+        // Explicit execution requires `from` to be an account kind.
+        s.execute_implicit(msg)?
+    } else {
+        s.execute_explicit(msg)?
+    };
+
+    if capped && ret.msg_receipt.exit_code == ExitCode::SYS_OUT_OF_GAS {
+        return Err(QueryBudgetExceeded::Gas(gas_budget).into());
+    }
+
+    Ok((ret, emitters))
+}
+
 impl<DB> HasChainID for FvmQueryState<DB>
 where
     DB: Blockstore + 'static,
@@ -244,3 +601,218 @@ where
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_actor_interface::{burntfunds::BURNT_FUNDS_ACTOR_ADDR, eam, evm, system};
+    use fendermint_vm_message::query::AccessedActor;
+    use fvm::engine::MultiEngine;
+    use fvm_ipld_encoding::{BytesSer, RawBytes};
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::BLOCK_GAS_LIMIT;
+
+    use super::{FvmQueryState, QueryBudget, QueryBudgetExceeded};
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::{memory::MemoryBlockstore, ReadOnlyBlockstore};
+    use crate::fvm::{testing, FvmMessage};
+
+    /// Create the genesis state of a chain without any accounts.
+    async fn genesis_state() -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(testing::genesis()).await
+    }
+
+    async fn query_state(budget: QueryBudget) -> FvmQueryState<MemoryBlockstore> {
+        let (store, params) = genesis_state().await;
+        FvmQueryState::new(
+            store,
+            Arc::new(MultiEngine::default()),
+            1,
+            params,
+            Default::default(),
+            false,
+        )
+        .expect("failed to create query state")
+        .with_budget(budget)
+    }
+
+    /// A call without a gas limit, which means it can use as much as the budget allows.
+    fn msg() -> FvmMessage {
+        FvmMessage {
+            version: 0,
+            from: system::SYSTEM_ACTOR_ADDR,
+            to: BURNT_FUNDS_ACTOR_ADDR,
+            sequence: 0,
+            value: TokenAmount::from_atto(0),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 0,
+            gas_fee_cap: TokenAmount::from_atto(0),
+            gas_premium: TokenAmount::from_atto(0),
+        }
+    }
+
+    fn exceeded(res: anyhow::Result<impl Sized>) -> QueryBudgetExceeded {
+        match res {
+            Ok(_) => panic!("expected the query to run out of its budget"),
+            Err(e) => e
+                .downcast::<QueryBudgetExceeded>()
+                .expect("expected a budget error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_within_the_budget_succeed() {
+        let budget = QueryBudget {
+            gas_limit: 10_000_000,
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let (state, (ret, _)) = query_state(budget).await.call(msg()).await.unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK);
+
+        let (_, (ret, _)) = state.call_traced(msg()).await.unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK);
+    }
+
+    #[tokio::test]
+    async fn calls_fail_past_the_gas_budget() {
+        let budget = QueryBudget {
+            gas_limit: 1,
+            timeout: None,
+        };
+        let state = query_state(budget).await;
+        let res = state.call(msg()).await;
+        assert!(matches!(exceeded(res), QueryBudgetExceeded::Gas(1)));
+
+        let state = query_state(budget).await;
+        let res = state.call_traced(msg()).await;
+        assert!(matches!(exceeded(res), QueryBudgetExceeded::Gas(1)));
+    }
+
+    #[tokio::test]
+    async fn calls_fail_past_the_time_budget() {
+        let budget = QueryBudget {
+            gas_limit: 0,
+            timeout: Some(Duration::ZERO),
+        };
+        let state = query_state(budget).await;
+        std::thread::sleep(Duration::from_millis(1));
+        let res = state.call(msg()).await;
+        assert!(matches!(exceeded(res), QueryBudgetExceeded::Time(_)));
+
+        let state = query_state(budget).await;
+        std::thread::sleep(Duration::from_millis(1));
+        let res = state.call_traced(msg()).await;
+        assert!(matches!(exceeded(res), QueryBudgetExceeded::Time(_)));
+    }
+
+    /// A contract which sets slot 8 in its constructor, and then on every call reads
+    /// slot 8 and increments slot 7:
+    ///
+    /// ```text
+    /// init:    PUSH1 1 PUSH1 8 SSTORE PUSH1 14 PUSH1 17 PUSH1 0 CODECOPY PUSH1 14 PUSH1 0 RETURN
+    /// runtime: PUSH1 8 SLOAD POP PUSH1 7 SLOAD PUSH1 1 ADD PUSH1 7 SSTORE STOP
+    /// ```
+    const STORAGE_CONTRACT: &str = "6001600855600e6011600039600e6000f36008545060075460010160075500";
+
+    fn storage_key(slot: u8) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[31] = slot;
+        key
+    }
+
+    #[tokio::test]
+    async fn traced_calls_see_the_pending_state_and_list_storage_keys() {
+        let (store, params) = genesis_state().await;
+        let multi_engine = Arc::new(MultiEngine::default());
+
+        // Deploy the contract in the check state only, through a new account.
+        let mut check_state = FvmExecState::new(
+            ReadOnlyBlockstore::new(store.clone()),
+            multi_engine.as_ref(),
+            1,
+            params.clone(),
+        )
+        .unwrap();
+
+        let sk = SecretKey::try_from(vec![1u8; 32]).unwrap();
+        let deployer = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        let (ret, _) = check_state
+            .execute_implicit(FvmMessage {
+                to: deployer,
+                gas_limit: BLOCK_GAS_LIMIT,
+                ..msg()
+            })
+            .unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK);
+
+        let initcode = hex::decode(STORAGE_CONTRACT).unwrap();
+        let (ret, _) = check_state
+            .execute_implicit(FvmMessage {
+                from: deployer,
+                to: eam::EAM_ACTOR_ADDR,
+                method_num: eam::Method::CreateExternal as u64,
+                params: RawBytes::serialize(BytesSer(&initcode)).unwrap(),
+                gas_limit: BLOCK_GAS_LIMIT,
+                ..msg()
+            })
+            .unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK, "{ret:?}");
+        let created = ret
+            .msg_receipt
+            .return_data
+            .deserialize::<eam::CreateReturn>()
+            .unwrap();
+
+        let check_state = Arc::new(tokio::sync::Mutex::new(Some(check_state)));
+
+        let call = || FvmMessage {
+            to: Address::new_id(created.actor_id),
+            method_num: evm::Method::InvokeContract as u64,
+            ..msg()
+        };
+
+        let query_state = |pending| {
+            FvmQueryState::new(
+                store.clone(),
+                multi_engine.clone(),
+                1,
+                params.clone(),
+                check_state.clone(),
+                pending,
+            )
+            .unwrap()
+        };
+
+        // The contract doesn't exist in the committed state.
+        let (_, (ret, actors)) = query_state(false).call_traced(call()).await.unwrap();
+        assert_ne!(ret.msg_receipt.exit_code, ExitCode::OK);
+        assert!(actors.is_empty());
+
+        let (_, (ret, actors)) = query_state(true).call_traced(call()).await.unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK, "{ret:?}");
+        assert_eq!(
+            actors,
+            vec![AccessedActor {
+                address: created.delegated_address(),
+                storage_keys: vec![storage_key(7), storage_key(8)],
+            }]
+        );
+
+        // A call which doesn't touch the storage of the recipient doesn't list it.
+        let (_, (ret, actors)) = query_state(true)
+            .call_traced(FvmMessage {
+                method_num: evm::Method::GetBytecode as u64,
+                ..call()
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret.msg_receipt.exit_code, ExitCode::OK, "{ret:?}");
+        assert!(actors.is_empty());
+    }
+}
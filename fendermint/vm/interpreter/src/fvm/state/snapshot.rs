@@ -78,7 +78,12 @@ where
             return Err(anyhow!("invalid snapshot, should have 1 root cid"));
         }
 
-        let metadata_cid = roots[0];
+        Self::from_metadata(store, roots[0])
+    }
+
+    /// Load a snapshot which has already been imported into the store,
+    /// starting from the CID of its metadata, which is the root of the CAR file.
+    pub fn from_metadata(store: BS, metadata_cid: Cid) -> anyhow::Result<Self> {
         let metadata = if let Some(metadata) = store.get_cbor::<SnapshotMetadata>(&metadata_cid)? {
             metadata
         } else {
@@ -269,7 +274,8 @@ impl<BS: Blockstore> Stream for StateTreeStreamer<BS> {
     }
 }
 
-fn walk_ipld_cids(ipld: Ipld, dfs: &mut VecDeque<Cid>) {
+/// Collect the CIDs linked from an IPLD value.
+pub(crate) fn walk_ipld_cids(ipld: Ipld, dfs: &mut VecDeque<Cid>) {
     match ipld {
         Ipld::List(v) => {
             for i in v {
@@ -0,0 +1,663 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Experimental Block-STM style optimistic parallel execution.
+//!
+//! CometBFT delivers the transactions of a block one by one, and the results are part of
+//! consensus, so the sequential executor stays in charge of the ledger. When the experiment
+//! is enabled, the messages of every block are recorded as they are delivered, and once the
+//! block ends they are executed again on a separate thread pool against the state after the
+//! implicit execution at the beginning of the block, optimistically and in parallel:
+//!
+//! 1. every pending transaction is executed against the latest writes of the transactions
+//!    before it, in parallel with the others;
+//! 2. the transactions are validated in order: if any actor a transaction touched has since
+//!    been written by a transaction before it, or by a different incarnation of one, it is
+//!    scheduled for re-execution in the next round.
+//!
+//! Conflicts are detected at the level of actors: the sender and every actor called during
+//! the execution count as both read and written. The gas fee recipients are excluded, because
+//! every message pays them, and their balances don't influence the execution. Reading the
+//! balance of another actor doesn't show up as a call, so an execution which does that is
+//! treated as if it read every actor.
+//!
+//! The outcome is a set of statistics about how much parallelism the block would allow, and
+//! if the determinism check is enabled, a comparison of the receipts with the ones produced
+//! by the sequential executor. Only the user transactions are replayed, so the receipts of a
+//! block where anything else was executed between them, e.g. top-down messages or system calls,
+//! cannot be compared.
+//!
+//! Writes go to an in-memory overlay, so nothing is persisted in the state store, apart from
+//! the state after the beginning of the block, which the sequential executor flushes for the
+//! experiment to start from.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::Context;
+use fendermint_vm_actor_interface::{burntfunds, reward};
+use fendermint_vm_topdown::SubnetLabel;
+use fvm::engine::MultiEngine;
+use fvm::state_tree::{ActorState, StateTree};
+use fvm::trace::ExecutionEvent;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::{clock::ChainEpoch, receipt::Receipt, ActorID};
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+
+use super::{
+    access, state::FvmExecState, state::FvmStateParams, store::overlay::OverlayBlockstore,
+    FvmMessage,
+};
+
+lazy_static! {
+    static ref STM_TXS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_block_stm_txs",
+        "Transactions executed by the Block-STM experiment",
+        &["subnet"]
+    )
+    .unwrap();
+    static ref STM_EXECUTIONS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_block_stm_executions",
+        "Executions by the Block-STM experiment, including re-executions after conflicts",
+        &["subnet"]
+    )
+    .unwrap();
+    static ref STM_ROUNDS: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_block_stm_rounds",
+        "Rounds of parallel execution needed by the last block",
+        &["subnet"]
+    )
+    .unwrap();
+    static ref STM_SKIPPED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_block_stm_skipped_blocks",
+        "Blocks not executed by the Block-STM experiment because the previous one was still running",
+        &["subnet"]
+    )
+    .unwrap();
+    static ref STM_MISMATCHES: IntCounterVec = register_int_counter_vec!(
+        "fendermint_block_stm_mismatches",
+        "Receipts of the Block-STM experiment which differ from the sequential execution",
+        &["subnet"]
+    )
+    .unwrap();
+}
+
+/// Actors which receive the gas fees of every message.
+const FEE_ACTORS: [ActorID; 2] = [burntfunds::BURNT_FUNDS_ACTOR_ID, reward::REWARD_ACTOR_ID];
+
+/// Name of the gas charge of reading the balance of an actor, the only sign of it in the trace.
+const BALANCE_OF_CHARGE: &str = "OnBalanceOf";
+
+/// The outcome of executing a transaction against a view of the state.
+#[derive(Debug, Clone)]
+pub struct Execution<W, R> {
+    /// Actors the execution read or wrote.
+    pub touched: BTreeSet<ActorID>,
+    /// Whether the execution read something which cannot be attributed to the touched actors,
+    /// in which case it conflicts with any write before it.
+    pub reads_all: bool,
+    /// The final state of the touched actors.
+    pub writes: BTreeMap<ActorID, W>,
+    /// Whatever result the transaction produced.
+    pub result: R,
+}
+
+/// Statistics of an optimistic execution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StmStats {
+    /// Number of transactions in the block.
+    pub txs: usize,
+    /// Number of rounds of parallel execution; with unlimited threads, the block would take
+    /// as long as this many transactions executed one after the other.
+    pub rounds: usize,
+    /// Number of executions, including the re-executions after conflicts.
+    pub executions: usize,
+}
+
+/// Version of an actor state: the index of the transaction which wrote it, and which incarnation.
+type Version = (usize, usize);
+
+struct Slot<W, R> {
+    incarnation: usize,
+    execution: Execution<W, R>,
+    /// Versions of the touched actors the execution saw, or of all of them if it read everything.
+    reads: BTreeMap<ActorID, Option<Version>>,
+}
+
+impl<W, R> Slot<W, R> {
+    /// Check whether the execution saw the same versions as the ones in the current view.
+    fn is_valid(&self, current: &HashMap<ActorID, (W, Version)>) -> bool {
+        let same = |id: &ActorID, v: &Option<Version>| current.get(id).map(|(_, v)| *v) == *v;
+
+        if self.execution.reads_all {
+            current.len() == self.reads.len() && self.reads.iter().all(|(id, v)| same(id, v))
+        } else {
+            self.reads.iter().all(|(id, v)| same(id, v))
+        }
+    }
+}
+
+/// Execute `txs` transactions in rounds, on up to `threads` threads, until every transaction
+/// has been executed against the final writes of the ones before it.
+///
+/// The `exec` function gets the index of the transaction and the latest writes of the ones
+/// before it, which it should apply over the state at the start of the block.
+pub fn execute_optimistically<W, R, F>(
+    txs: usize,
+    threads: usize,
+    exec: F,
+) -> anyhow::Result<(Vec<R>, StmStats)>
+where
+    W: Clone + Send + Sync,
+    R: Send + Sync,
+    F: Fn(usize, BTreeMap<ActorID, W>) -> anyhow::Result<Execution<W, R>> + Sync,
+{
+    let threads = threads.max(1);
+    let mut slots: Vec<Option<Slot<W, R>>> = (0..txs).map(|_| None).collect();
+    let mut pending: Vec<usize> = (0..txs).collect();
+    let mut stats = StmStats {
+        txs,
+        ..Default::default()
+    };
+
+    while !pending.is_empty() {
+        stats.rounds += 1;
+        stats.executions += pending.len();
+
+        let views = pending
+            .iter()
+            .map(|i| (*i, view(&slots, *i)))
+            .collect::<Vec<_>>();
+
+        let chunk_size = views.len().div_ceil(threads);
+        let exec = &exec;
+
+        let executed = std::thread::scope(|s| {
+            let handles = views
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|(i, view)| {
+                                let writes =
+                                    view.iter().map(|(id, (w, _))| (*id, w.clone())).collect();
+                                let execution = exec(*i, writes)?;
+                                let reads = if execution.reads_all {
+                                    view.iter().map(|(id, (_, v))| (*id, Some(*v))).collect()
+                                } else {
+                                    execution
+                                        .touched
+                                        .iter()
+                                        .map(|id| (*id, view.get(id).map(|(_, v)| *v)))
+                                        .collect()
+                                };
+                                Ok((*i, execution, reads))
+                            })
+                            .collect::<anyhow::Result<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("execution thread panicked"))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        for (i, execution, reads) in executed.into_iter().flatten() {
+            let incarnation = slots[i].as_ref().map(|s| s.incarnation + 1).unwrap_or(0);
+            slots[i] = Some(Slot {
+                incarnation,
+                execution,
+                reads,
+            });
+        }
+
+        // Validate in order; a re-executed transaction invalidates the ones after it
+        // which saw its previous incarnation.
+        pending = (0..txs)
+            .filter(|i| {
+                let slot = slots[*i].as_ref().expect("all transactions executed");
+                !slot.is_valid(&view(&slots, *i))
+            })
+            .collect();
+    }
+
+    let results = slots
+        .into_iter()
+        .map(|s| s.expect("all transactions executed").execution.result)
+        .collect();
+
+    Ok((results, stats))
+}
+
+/// The latest writes of the transactions before `i`, along with their versions.
+fn view<W: Clone, R>(slots: &[Option<Slot<W, R>>], i: usize) -> HashMap<ActorID, (W, Version)> {
+    let mut view = HashMap::new();
+    for (j, slot) in slots.iter().enumerate().take(i) {
+        if let Some(slot) = slot {
+            for (id, w) in slot.execution.writes.iter() {
+                view.insert(*id, (w.clone(), (j, slot.incarnation)));
+            }
+        }
+    }
+    view
+}
+
+/// Messages delivered in the current block, along with the receipts of the sequential execution.
+struct BlockRecord {
+    height: ChainEpoch,
+    /// Parameters of the state the transactions were executed on, after the beginning of the block.
+    params: FvmStateParams,
+    msgs: Vec<FvmMessage>,
+    receipts: Vec<Receipt>,
+    /// Whether the block only had messages which the experiment executes the same way as
+    /// the sequential executor, so the receipts can be compared.
+    comparable: bool,
+    /// Number of messages the sequential executor ran up to the last recorded one.
+    executed: u64,
+}
+
+/// Runs the Block-STM experiment on the blocks executed by the node.
+#[derive(Clone)]
+pub struct BlockStm<DB> {
+    store: DB,
+    multi_engine: Arc<MultiEngine>,
+    threads: usize,
+    determinism_check: bool,
+    block: Arc<Mutex<Option<BlockRecord>>>,
+    running: Arc<AtomicBool>,
+    /// Label of the metrics of the experiment.
+    subnet_id: SubnetLabel,
+}
+
+impl<DB> BlockStm<DB>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    pub fn new(store: DB, threads: usize, determinism_check: bool) -> Self {
+        Self {
+            store,
+            multi_engine: Arc::new(MultiEngine::new(threads.max(1) as u32)),
+            threads,
+            determinism_check,
+            block: Default::default(),
+            running: Default::default(),
+            subnet_id: String::new(),
+        }
+    }
+
+    pub fn with_subnet_id(mut self, subnet_id: String) -> Self {
+        self.subnet_id = subnet_id;
+        self
+    }
+
+    /// Start recording a block, the transactions of which are executed on top of the state
+    /// described by `params`, once the executor ran `executed` messages.
+    pub fn begin_block(&self, height: ChainEpoch, params: FvmStateParams, executed: u64) {
+        *self.block.lock().unwrap() = Some(BlockRecord {
+            height,
+            params,
+            msgs: Vec::new(),
+            receipts: Vec::new(),
+            comparable: true,
+            executed,
+        });
+    }
+
+    /// Record a message delivered in the current block, with the number of messages the
+    /// executor ran before and after it.
+    ///
+    /// If anything was executed since the last recorded message, the experiment doesn't
+    /// see its effects, so the receipts from here on cannot be compared.
+    pub fn record(&self, msg: FvmMessage, receipt: &Receipt, before: u64, after: u64) {
+        if let Some(ref mut block) = *self.block.lock().unwrap() {
+            if before != block.executed {
+                block.comparable = false;
+            }
+            block.msgs.push(msg);
+            block.receipts.push(receipt.clone());
+            block.executed = after;
+        }
+    }
+
+    /// Indicate that the block had a message which the sequential executor handled
+    /// in a special way, which means the receipts cannot be compared.
+    pub fn not_comparable(&self) {
+        if let Some(ref mut block) = *self.block.lock().unwrap() {
+            block.comparable = false;
+        }
+    }
+
+    /// Execute the recorded block in the background.
+    ///
+    /// If the previous block is still being executed, this one is skipped, so the experiment
+    /// never holds up consensus.
+    pub fn end_block(&self) {
+        let block = match self.block.lock().unwrap().take() {
+            Some(block) if !block.msgs.is_empty() => block,
+            _ => return,
+        };
+
+        if self.running.swap(true, Ordering::SeqCst) {
+            STM_SKIPPED.with_label_values(&[&self.subnet_id]).inc();
+            tracing::debug!(
+                height = block.height,
+                "skipping block in Block-STM experiment"
+            );
+            return;
+        }
+
+        let this = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            match this.execute_block(&block) {
+                Ok((receipts, _)) => {
+                    if this.determinism_check && block.comparable {
+                        compare_receipts(&this.subnet_id, &block, &receipts);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(
+                        height = block.height,
+                        error = e.to_string(),
+                        "Block-STM experiment failed"
+                    );
+                }
+            }
+            this.running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    fn execute_block(&self, block: &BlockRecord) -> anyhow::Result<(Vec<Receipt>, StmStats)> {
+        let store = OverlayBlockstore::new(self.store.clone());
+        let start = Instant::now();
+
+        let (receipts, stats) =
+            execute_optimistically(block.msgs.len(), self.threads, |i, writes| {
+                self.execute_message(
+                    &store,
+                    block.height,
+                    &block.params,
+                    block.msgs[i].clone(),
+                    writes,
+                )
+            })?;
+
+        let elapsed = start.elapsed();
+
+        STM_TXS
+            .with_label_values(&[&self.subnet_id])
+            .inc_by(stats.txs as u64);
+        STM_EXECUTIONS
+            .with_label_values(&[&self.subnet_id])
+            .inc_by(stats.executions as u64);
+        STM_ROUNDS
+            .with_label_values(&[&self.subnet_id])
+            .set(stats.rounds as i64);
+
+        tracing::info!(
+            height = block.height,
+            txs = stats.txs,
+            rounds = stats.rounds,
+            executions = stats.executions,
+            threads = self.threads,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "Block-STM experiment executed block"
+        );
+
+        Ok((receipts, stats))
+    }
+
+    /// Execute a message on top of the state at the start of the block, overwritten with the
+    /// latest actor states written by the transactions before it.
+    fn execute_message(
+        &self,
+        store: &OverlayBlockstore<DB>,
+        height: ChainEpoch,
+        params: &FvmStateParams,
+        msg: FvmMessage,
+        writes: BTreeMap<ActorID, ActorState>,
+    ) -> anyhow::Result<Execution<ActorState, Receipt>> {
+        let mut state =
+            FvmExecState::new_traced(store.clone(), &self.multi_engine, height, params.clone())
+                .context("error creating execution state")?;
+
+        for (id, actor) in writes {
+            state.state_tree_mut().set_actor(id, actor);
+        }
+
+        let from = msg.from;
+        let (ret, _) = access::execute_explicit(&mut state, msg)?;
+
+        let mut touched = BTreeSet::new();
+        let mut reads_all = false;
+        if let Some(id) = state.state_tree_mut().lookup_id(&from)? {
+            touched.insert(id);
+        }
+        for event in ret.exec_trace.iter() {
+            match event {
+                ExecutionEvent::Call { from, to, .. } => {
+                    touched.insert(*from);
+                    if let Some(id) = state.state_tree_mut().lookup_id(to)? {
+                        touched.insert(id);
+                    }
+                }
+                ExecutionEvent::GasCharge(charge) if charge.name == BALANCE_OF_CHARGE => {
+                    reads_all = true;
+                }
+                _ => {}
+            }
+        }
+        touched.retain(|id| !FEE_ACTORS.contains(id));
+
+        let (state_root, _, _) = state.commit().context("failed to flush state")?;
+        let state_tree = StateTree::new_from_root(store.clone(), &state_root)?;
+
+        let mut writes = BTreeMap::new();
+        for id in touched.iter() {
+            if let Some(actor) = state_tree.get_actor(*id)? {
+                writes.insert(*id, actor);
+            }
+        }
+
+        Ok(Execution {
+            touched,
+            reads_all,
+            writes,
+            result: ret.msg_receipt,
+        })
+    }
+}
+
+/// Compare the receipts of the experiment with the ones of the sequential execution.
+fn compare_receipts(subnet_id: &str, block: &BlockRecord, receipts: &[Receipt]) {
+    for (i, (stm, seq)) in receipts.iter().zip(block.receipts.iter()).enumerate() {
+        if stm != seq {
+            STM_MISMATCHES.with_label_values(&[subnet_id]).inc();
+            tracing::error!(
+                height = block.height,
+                index = i,
+                stm_exit_code = stm.exit_code.value(),
+                seq_exit_code = seq.exit_code.value(),
+                stm_gas_used = stm.gas_used,
+                seq_gas_used = seq.gas_used,
+                "Block-STM receipt differs from the sequential execution"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use fendermint_vm_genesis::{Genesis, ValidatorKey};
+    use fvm::engine::MultiEngine;
+    use fvm_shared::{address::Address, econ::TokenAmount, ActorID};
+    use quickcheck::Arbitrary;
+
+    use super::{execute_optimistically, BlockRecord, BlockStm, Execution};
+    use crate::fvm::state::{FvmExecState, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::{testing, FvmMessage};
+
+    /// Transfers between accounts with a starting balance of 100.
+    fn transfer(
+        from: ActorID,
+        to: ActorID,
+        amount: i64,
+        writes: BTreeMap<ActorID, i64>,
+    ) -> Execution<i64, (i64, i64)> {
+        let balance = |id| writes.get(&id).copied().unwrap_or(100);
+        let (b_from, b_to) = (balance(from) - amount, balance(to) + amount);
+        Execution {
+            touched: BTreeSet::from([from, to]),
+            reads_all: false,
+            writes: BTreeMap::from([(from, b_from), (to, b_to)]),
+            result: (b_from, b_to),
+        }
+    }
+
+    fn sequential(txs: &[(ActorID, ActorID, i64)]) -> Vec<(i64, i64)> {
+        let mut writes = BTreeMap::new();
+        txs.iter()
+            .map(|(from, to, amount)| {
+                let ex = transfer(*from, *to, *amount, writes.clone());
+                writes.extend(ex.writes);
+                ex.result
+            })
+            .collect()
+    }
+
+    #[test]
+    fn independent_transfers_take_one_round() {
+        let txs = [(1, 2, 10), (3, 4, 20), (5, 6, 30)];
+        let (results, stats) = execute_optimistically(txs.len(), 2, |i, writes| {
+            let (from, to, amount) = txs[i];
+            Ok(transfer(from, to, amount, writes))
+        })
+        .unwrap();
+
+        assert_eq!(results, sequential(&txs));
+        assert_eq!(stats.rounds, 1);
+        assert_eq!(stats.executions, 3);
+    }
+
+    #[test]
+    fn conflicting_transfers_match_sequential() {
+        let txs = [(1, 2, 10), (2, 3, 20), (4, 5, 1), (3, 1, 30), (6, 7, 1)];
+        let (results, stats) = execute_optimistically(txs.len(), 3, |i, writes| {
+            let (from, to, amount) = txs[i];
+            Ok(transfer(from, to, amount, writes))
+        })
+        .unwrap();
+
+        assert_eq!(results, sequential(&txs));
+        // The chain 1->2, 2->3, 3->1 needs three rounds.
+        assert_eq!(stats.rounds, 3);
+        assert!(stats.executions > txs.len());
+    }
+
+    #[test]
+    fn reading_all_conflicts_with_any_write() {
+        // The second transaction reads the balance of 1 without touching it.
+        let (results, stats) = execute_optimistically(2, 2, |i, writes| {
+            if i == 0 {
+                return Ok(transfer(1, 2, 10, writes));
+            }
+            let seen = writes.get(&1).copied().unwrap_or(100);
+            Ok(Execution {
+                touched: BTreeSet::from([3]),
+                reads_all: true,
+                writes: BTreeMap::from([(3, seen)]),
+                result: (seen, seen),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(results[1], (90, 90));
+        assert_eq!(stats.rounds, 2);
+    }
+
+    /// Create the genesis state with accounts for the given addresses.
+    async fn genesis_state(owners: &[Address]) -> (MemoryBlockstore, FvmStateParams) {
+        testing::genesis_state(Genesis {
+            accounts: owners
+                .iter()
+                .map(|owner| testing::account(*owner, TokenAmount::from_whole(10)))
+                .collect(),
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn block_matches_sequential_execution() {
+        let mut g = quickcheck::Gen::new(10);
+        let owners = (0..5)
+            .map(|_| {
+                Address::new_secp256k1(&ValidatorKey::arbitrary(&mut g).0.serialize()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let (store, params) = genesis_state(&owners).await;
+
+        // A cycle of transfers, an independent one, and a second message from the same sender.
+        let mut sequences = BTreeMap::new();
+        let msgs = [(0, 1), (1, 2), (2, 0), (3, 4), (0, 3)]
+            .into_iter()
+            .map(|(from, to)| {
+                let sequence = sequences.entry(from).or_insert(0u64);
+                let msg = FvmMessage {
+                    version: 0,
+                    from: owners[from],
+                    to: owners[to],
+                    sequence: *sequence,
+                    value: TokenAmount::from_whole(1),
+                    method_num: fvm_shared::METHOD_SEND,
+                    params: Default::default(),
+                    gas_limit: 10_000_000,
+                    gas_fee_cap: params.base_fee.clone(),
+                    gas_premium: TokenAmount::from_atto(0),
+                };
+                *sequence += 1;
+                msg
+            })
+            .collect::<Vec<_>>();
+
+        let multi_engine = MultiEngine::default();
+        let mut state = FvmExecState::new(store.clone(), &multi_engine, 1, params.clone())
+            .expect("failed to create state");
+
+        let receipts = msgs
+            .iter()
+            .map(|msg| {
+                let (ret, _) = state.execute_explicit(msg.clone())?;
+                Ok(ret.msg_receipt)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .expect("failed to execute messages");
+
+        assert!(receipts.iter().all(|r| r.exit_code.is_success()));
+
+        let block = BlockRecord {
+            height: 1,
+            params,
+            msgs,
+            receipts: receipts.clone(),
+            comparable: true,
+            executed: 0,
+        };
+
+        let stm = BlockStm::new(store, 3, true);
+        let (results, stats) = stm.execute_block(&block).expect("failed to execute block");
+
+        assert_eq!(results, receipts);
+        assert!(stats.rounds > 1);
+    }
+}
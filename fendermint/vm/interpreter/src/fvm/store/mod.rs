@@ -1,10 +1,38 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::cell::Cell;
+use std::time::Instant;
+
+use anyhow::anyhow;
 use cid::Cid;
 use fvm::EMPTY_ARR_CID;
 use fvm_ipld_blockstore::Blockstore;
 
 pub mod memory;
+pub mod overlay;
+
+thread_local! {
+    /// Time after which reads from a [ReadOnlyBlockstore] on this thread fail.
+    static READ_DEADLINE: Cell<Option<Instant>> = Cell::new(None);
+}
+
+/// Make the reads from any [ReadOnlyBlockstore] on this thread fail after the deadline,
+/// until the returned guard is dropped.
+///
+/// The FVM doesn't let us interrupt an execution, but calls can't get far without
+/// reading the state, so this is how a query is stopped when it runs out of time.
+pub fn read_deadline(deadline: Option<Instant>) -> ReadDeadlineGuard {
+    ReadDeadlineGuard(READ_DEADLINE.with(|d| d.replace(deadline)))
+}
+
+/// Restores the previous read deadline of the thread when dropped.
+pub struct ReadDeadlineGuard(Option<Instant>);
+
+impl Drop for ReadDeadlineGuard {
+    fn drop(&mut self) {
+        READ_DEADLINE.with(|d| d.set(self.0));
+    }
+}
 
 #[derive(Clone)]
 pub struct ReadOnlyBlockstore<DB>(DB);
@@ -20,6 +48,9 @@ where
     DB: Blockstore,
 {
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if READ_DEADLINE.with(|d| d.get().map_or(false, |d| Instant::now() > d)) {
+            return Err(anyhow!("read past the deadline"));
+        }
         self.0.get(k)
     }
 
@@ -31,3 +62,33 @@ where
         panic!("never intended to use put on the read-only blockstore")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fvm_ipld_blockstore::Blockstore;
+
+    use super::{memory::MemoryBlockstore, read_deadline, ReadOnlyBlockstore};
+
+    #[test]
+    fn reads_fail_past_the_deadline() {
+        let mem = MemoryBlockstore::new();
+        let k = Cid::new_v1(0x55, Code::Blake2b256.digest(b"data"));
+        mem.put_keyed(&k, b"data").unwrap();
+        let store = ReadOnlyBlockstore::new(mem);
+
+        {
+            let _guard = read_deadline(Some(Instant::now() + Duration::from_secs(60)));
+            assert!(store.get(&k).unwrap().is_some());
+        }
+        {
+            let _guard = read_deadline(Some(Instant::now() - Duration::from_millis(1)));
+            assert!(store.get(&k).is_err());
+        }
+        // The deadline is lifted when the guard is dropped.
+        assert!(store.get(&k).unwrap().is_some());
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::anyhow;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{from_slice, DAG_CBOR};
+use libipld::Ipld;
+
+use crate::fvm::state::snapshot::walk_ipld_cids;
+
+/// Reads through to the underlying store, but keeps the writes in memory.
+#[derive(Clone)]
+pub struct OverlayBlockstore<DB> {
+    inner: DB,
+    overlay: Arc<RwLock<HashMap<Cid, Vec<u8>>>>,
+}
+
+impl<DB> OverlayBlockstore<DB> {
+    pub fn new(inner: DB) -> Self {
+        Self {
+            inner,
+            overlay: Default::default(),
+        }
+    }
+}
+
+impl<DB: Blockstore> OverlayBlockstore<DB> {
+    /// Copy the blocks reachable from the root into the overlay, apart from the ones
+    /// the store already has, e.g. to see a state which only exists in the buffer of the FVM.
+    pub fn copy_from<S: Blockstore>(&self, from: &S, root: Cid) -> anyhow::Result<()> {
+        let mut todo = VecDeque::from([root]);
+        while let Some(cid) = todo.pop_front() {
+            if self.has(&cid)? {
+                continue;
+            }
+            let bz = from
+                .get(&cid)?
+                .ok_or_else(|| anyhow!("block {cid} is missing from the source store"))?;
+
+            if cid.codec() == DAG_CBOR {
+                walk_ipld_cids(from_slice::<Ipld>(&bz)?, &mut todo);
+            }
+            self.put_keyed(&cid, &bz)?;
+        }
+        Ok(())
+    }
+}
+
+impl<DB: Blockstore> Blockstore for OverlayBlockstore<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(bz) = self.overlay.read().unwrap().get(k) {
+            return Ok(Some(bz.clone()));
+        }
+        self.inner.get(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.overlay.write().unwrap().insert(*k, block.to_vec());
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Fixtures shared by the tests of the interpreter.
+
+use std::sync::Arc;
+
+use fendermint_vm_core::Timestamp;
+use fendermint_vm_genesis::{Account, Actor, ActorMeta, Genesis, SignerAddr};
+use fvm::engine::MultiEngine;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::version::NetworkVersion;
+use tendermint_rpc::{MockClient, MockRequestMethodMatcher};
+
+use crate::fvm::bundle::{bundle_path, contracts_path};
+use crate::fvm::state::{FvmGenesisState, FvmStateParams};
+use crate::fvm::store::memory::MemoryBlockstore;
+use crate::fvm::FvmMessageInterpreter;
+use crate::GenesisInterpreter;
+
+pub type TestInterpreter =
+    FvmMessageInterpreter<MemoryBlockstore, MockClient<MockRequestMethodMatcher>>;
+
+/// An interpreter which isn't connected to CometBFT.
+pub fn interpreter() -> TestInterpreter {
+    let (client, _) = MockClient::new(MockRequestMethodMatcher::default());
+    FvmMessageInterpreter::new(client, None, contracts_path(), 1.05, 1.05, false)
+}
+
+/// A genesis without validators, accounts or any of the optional features,
+/// for the tests to fill in what they need.
+pub fn genesis() -> Genesis {
+    Genesis {
+        chain_name: "test".to_owned(),
+        timestamp: Timestamp(0),
+        network_version: NetworkVersion::V20,
+        base_fee: TokenAmount::from_atto(1000),
+        power_scale: 3,
+        validators: Vec::new(),
+        accounts: Vec::new(),
+        ipc: None,
+        access_control: None,
+        governance: None,
+        scheduled_calls: Vec::new(),
+        tx_compression: false,
+        filecoin_signatures: false,
+        system_contracts: Vec::new(),
+        beacon: None,
+        policy: None,
+        rewards: None,
+        exec_digests: false,
+        max_block_interval: None,
+        eth_block_hash_v1: false,
+        canonical_tx_order: false,
+        fast_topdown_finality: false,
+    }
+}
+
+/// An account of the owner with the given balance at genesis.
+pub fn account(owner: Address, balance: TokenAmount) -> Actor {
+    Actor {
+        meta: ActorMeta::Account(Account {
+            owner: SignerAddr(owner),
+        }),
+        balance,
+    }
+}
+
+/// Execute the genesis and return the committed state.
+pub async fn genesis_state(genesis: Genesis) -> (MemoryBlockstore, FvmStateParams) {
+    let bundle = std::fs::read(bundle_path()).expect("failed to read bundle");
+    let store = MemoryBlockstore::new();
+
+    let state = FvmGenesisState::new(store.clone(), Arc::new(MultiEngine::default()), &bundle)
+        .await
+        .expect("failed to create state");
+
+    let (state, out) = interpreter()
+        .init(state, genesis)
+        .await
+        .expect("failed to create actors");
+
+    let state_root = state.commit().expect("failed to commit genesis");
+
+    let params = FvmStateParams {
+        state_root,
+        timestamp: out.timestamp,
+        network_version: out.network_version,
+        base_fee: out.base_fee,
+        circ_supply: out.circ_supply,
+        chain_id: out.chain_id.into(),
+        power_scale: out.power_scale,
+    };
+
+    (store, params)
+}
@@ -4,14 +4,27 @@
 
 use crate::chain::TopDownFinalityProvider;
 use crate::fvm::state::ipc::GatewayCaller;
-use crate::fvm::state::FvmExecState;
+use crate::fvm::state::{reject_message, FvmExecState};
 use crate::fvm::FvmApplyRet;
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
+use cid::multihash::Code;
+use fendermint_vm_actor_interface::burntfunds::BURNT_FUNDS_ACTOR_ADDR;
+use fendermint_vm_actor_interface::topdownnonces::{self, TOPDOWN_NONCES_ACTOR_ID};
+use fendermint_vm_actor_interface::{evm, placeholder, system};
 use fendermint_vm_topdown::{BlockHeight, IPCParentFinality, ParentViewProvider};
+use fvm::executor::ApplyFailure;
+use fvm::state_tree::ActorState;
 use fvm_ipld_blockstore::Blockstore;
-use ipc_sdk::cross::CrossMsg;
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::{econ::TokenAmount, error::ExitCode, METHOD_SEND};
+use ipc_sdk::address::IPCAddress;
+use ipc_sdk::cross::{CrossMsg, StorableMsg};
+use ipc_sdk::subnet_id::SubnetID;
+use num_traits::Zero;
+use std::collections::HashSet;
 
 use super::state::ipc::tokens_to_mint;
+use super::{deadletter, governance, routing};
 
 /// Commit the parent finality. Returns the height that the previous parent finality is committed and
 /// the committed finality itself. If there is no parent finality committed, genesis epoch is returned.
@@ -40,6 +53,17 @@ where
 
 /// Execute the top down messages implicitly. Before the execution, mint to the gateway of the funds
 /// transferred in the messages, and increase the circulating supply with the incoming value.
+///
+/// Messages which have been applied before are dropped, and the rest have to continue exactly
+/// where the previous ones left off; the ones after a gap are moved to the dead-letter queue,
+/// see [split_nonces]. The nonces are recorded as the gateway applies the messages.
+///
+/// The messages are applied one by one, so a failure only affects the message which failed.
+/// A message which cannot be applied is moved to the dead-letter queue, rather than failing
+/// the block, and a no-op with the same nonce is applied in its place, so the gateway moves
+/// on to the next message; the funds stay with the gateway until the message is retried.
+/// The same happens to messages whose senders are blocked by governance, and to messages
+/// which cannot be routed to their destination; see [routing::TopdownRoutes].
 pub async fn execute_topdown_msgs<DB>(
     gateway_caller: &GatewayCaller<DB>,
     state: &mut FvmExecState<DB>,
@@ -48,7 +72,13 @@ pub async fn execute_topdown_msgs<DB>(
 where
     DB: Blockstore + Sync + Send + 'static,
 {
-    let minted_tokens = tokens_to_mint(&messages);
+    let mut record =
+        get_or_init_nonces(gateway_caller, state).context("failed to get the top-down nonces")?;
+    let record_nonces = !messages.is_empty();
+    let (messages, gapped) = split_nonces(&record, messages);
+
+    // The messages after a gap are minted for as well, so they can be retried from the dead-letter queue.
+    let minted_tokens = tokens_to_mint(&messages) + tokens_to_mint(&gapped);
 
     gateway_caller
         .mint_to_gateway(state, minted_tokens.clone())
@@ -58,5 +88,382 @@ where
         *circ_supply += minted_tokens;
     });
 
-    gateway_caller.apply_cross_messages(state, messages)
+    if !gapped.is_empty() {
+        let next_nonce = record.next_nonce + messages.len() as u64;
+        let reason =
+            format!("top-down messages skip nonces; the next nonce should be {next_nonce}");
+        tracing::error!(
+            next_nonce,
+            first_nonce = gapped[0].msg.nonce,
+            count = gapped.len(),
+            "top-down messages after a nonce gap moved to the dead-letter queue"
+        );
+        deadletter::record(state, gapped, reason).context("failed to record dead letters")?;
+    }
+
+    let mut events = Vec::new();
+    let height = state.block_height() as u64;
+
+    let mut ret = if messages.is_empty() {
+        // Still call the gateway, so the receipt looks the same as it does with messages.
+        let (ret, _) = gateway_caller.try_apply_cross_messages(state, Vec::new())?;
+        ret
+    } else {
+        let subnet_id = routing::current_subnet_id(gateway_caller, state)?;
+        let filter =
+            governance::topdown_filter(state).context("failed to get the top-down filter")?;
+        let mut routes = routing::TopdownRoutes::new(subnet_id.clone());
+        let mut acc: Option<FvmApplyRet> = None;
+        for msg in messages {
+            let nonce = msg.msg.nonce;
+            let mut skip = filter
+                .as_ref()
+                .and_then(|filter| governance::check_topdown_sender(filter, &msg))
+                .map(|event| {
+                    events.push(event);
+                    "sender blocked by governance".to_owned()
+                });
+            if skip.is_none() {
+                let (rejection, event) = routes
+                    .check(gateway_caller, state, &msg)
+                    .context("failed to check top-down route")?;
+                events.extend(event);
+                skip = rejection;
+            }
+            let ret = apply_or_skip(gateway_caller, state, &subnet_id, msg, skip)?;
+            // Either the message or a no-op in its place has been applied by the gateway.
+            record.applied(nonce, height);
+            acc = Some(match acc {
+                None => ret,
+                Some(acc) => merge_rets(acc, ret),
+            });
+        }
+        acc.expect("there was at least one message")
+    };
+
+    ret.apply_ret.events.extend(events);
+
+    if record_nonces {
+        set_nonces(state, &record).context("failed to record top-down nonces")?;
+    }
+
+    Ok(ret)
+}
+
+/// The result of a top-down proposal which isn't executed at all, e.g. because the subnet is shutting down.
+pub fn rejected<DB>(gateway_caller: &GatewayCaller<DB>, reason: String) -> FvmApplyRet {
+    let (apply_ret, emitters) =
+        reject_message(ExitCode::USR_FORBIDDEN, ApplyFailure::PreValidation(reason));
+    FvmApplyRet {
+        apply_ret,
+        from: system::SYSTEM_ACTOR_ADDR,
+        to: gateway_caller.addr().into(),
+        method_num: evm::Method::InvokeContract as u64,
+        gas_limit: fvm_shared::BLOCK_GAS_LIMIT,
+        emitters,
+    }
+}
+
+/// Apply a single top-down message, unless there is a reason to skip it; if it's skipped
+/// or it fails, move it to the dead-letter queue and apply a no-op in its place.
+fn apply_or_skip<DB>(
+    gateway_caller: &GatewayCaller<DB>,
+    state: &mut FvmExecState<DB>,
+    subnet_id: &SubnetID,
+    msg: CrossMsg,
+    skip: Option<String>,
+) -> anyhow::Result<FvmApplyRet>
+where
+    DB: Blockstore + Sync + Send + 'static,
+{
+    let nonce = msg.msg.nonce;
+
+    let (ret, reason) = match skip {
+        Some(reason) => (None, reason),
+        None => match gateway_caller.try_apply_cross_messages(state, vec![msg.clone()])? {
+            (ret, None) => return Ok(ret),
+            (ret, Some(reason)) => {
+                tracing::error!(nonce, reason, "failed to apply top-down message");
+                (Some(ret), reason)
+            }
+        },
+    };
+
+    deadletter::record(state, vec![msg.clone()], reason)
+        .context("failed to record dead letters")?;
+
+    let (noop_ret, failure) =
+        gateway_caller.try_apply_cross_messages(state, vec![noop(subnet_id, &msg)?])?;
+    if let Some(reason) = failure {
+        bail!("failed to apply no-op in place of top-down message {nonce}: {reason}");
+    }
+
+    // The receipt shows the failure of the original message, if it was tried.
+    Ok(ret.unwrap_or(noop_ret))
+}
+
+/// A message which does nothing but take the nonce of a top-down message which couldn't be applied,
+/// so the gateway can carry on with the following ones: it sends nothing to the burnt funds actor.
+pub fn noop(subnet_id: &SubnetID, msg: &CrossMsg) -> anyhow::Result<CrossMsg> {
+    let to = IPCAddress::new(subnet_id, &BURNT_FUNDS_ACTOR_ADDR)
+        .map_err(|e| anyhow!("failed to create no-op recipient: {e}"))?;
+    Ok(CrossMsg {
+        msg: StorableMsg {
+            to,
+            method: METHOD_SEND,
+            params: RawBytes::default(),
+            value: TokenAmount::zero(),
+            ..msg.msg.clone()
+        },
+        wrapped: false,
+    })
+}
+
+/// Combine the results of applying the messages of a batch one by one, as if they were applied together.
+///
+/// The exit code is the one of the first failure, if any of them failed.
+fn merge_rets(mut acc: FvmApplyRet, ret: FvmApplyRet) -> FvmApplyRet {
+    let a = &mut acc.apply_ret;
+    let r = ret.apply_ret;
+    a.msg_receipt.gas_used += r.msg_receipt.gas_used;
+    a.events.extend(r.events);
+    if a.msg_receipt.exit_code.is_success() && !r.msg_receipt.exit_code.is_success() {
+        a.msg_receipt.exit_code = r.msg_receipt.exit_code;
+        a.msg_receipt.return_data = r.msg_receipt.return_data;
+        a.failure_info = r.failure_info;
+    }
+    acc.emitters.extend(ret.emitters);
+    acc
+}
+
+/// Split the messages into the ones which follow on from the last applied nonce without gaps
+/// and the ones after a gap; the messages which have already been applied are dropped.
+///
+/// Applying a message twice would mint its funds twice, and the gateway refuses to skip a nonce,
+/// so the messages after a gap can't be applied; rather than halting the chain, the caller moves
+/// them to the dead-letter queue, from where their funds can still be delivered.
+fn split_nonces(
+    record: &topdownnonces::State,
+    msgs: Vec<CrossMsg>,
+) -> (Vec<CrossMsg>, Vec<CrossMsg>) {
+    let mut next_nonce = record.next_nonce;
+    let mut fresh = Vec::with_capacity(msgs.len());
+    let mut gapped = Vec::new();
+    for msg in msgs {
+        let nonce = msg.msg.nonce;
+        if nonce < next_nonce {
+            tracing::warn!(
+                nonce,
+                next_nonce,
+                "dropping top-down message which has already been applied"
+            );
+        } else if nonce == next_nonce && gapped.is_empty() {
+            next_nonce += 1;
+            fresh.push(msg);
+        } else {
+            gapped.push(msg);
+        }
+    }
+    (fresh, gapped)
+}
+
+/// Find the first nonce which would skip messages if the top-down messages were applied
+/// after the ones with nonces up to `next_nonce`, which is what proposals are checked with.
+pub fn find_nonce_gap(mut next_nonce: u64, msgs: &[CrossMsg]) -> Option<u64> {
+    for msg in msgs {
+        let nonce = msg.msg.nonce;
+        if nonce == next_nonce {
+            next_nonce += 1;
+        } else if nonce > next_nonce {
+            return Some(nonce);
+        }
+    }
+    None
+}
+
+/// The nonce the next batch of top-down messages has to start with, taking the ones
+/// deferred to the following blocks into account, or `None` if the chain doesn't
+/// record the nonces yet.
+pub fn expected_topdown_nonce<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<u64>>
+where
+    DB: Blockstore + 'static,
+{
+    let record = match get_nonces(state)? {
+        None => return Ok(None),
+        Some(record) => record,
+    };
+    let deferred = governance::get_state(state)?
+        .map(|gov| gov.deferred_topdown_msgs)
+        .unwrap_or_default();
+    let next_nonce = deferred
+        .iter()
+        .map(|msg| msg.msg.nonce + 1)
+        .fold(record.next_nonce, u64::max);
+    Ok(Some(next_nonce))
+}
+
+/// How far the top-down messages have been delivered, to tell which ones were executed.
+#[derive(Debug, Clone, Default)]
+pub struct TopdownProgress {
+    /// The messages with nonces below this have been applied by the gateway.
+    pub next_nonce: u64,
+    /// Nonces of the messages in the dead-letter queue, which the gateway applied a no-op for.
+    pub dead_letters: HashSet<u64>,
+}
+
+impl TopdownProgress {
+    /// Check whether a message has been executed, rather than deferred or dead-lettered.
+    pub fn is_executed(&self, nonce: u64) -> bool {
+        nonce < self.next_nonce && !self.dead_letters.contains(&nonce)
+    }
+}
+
+/// Get how far the top-down messages have been delivered in the state.
+pub fn topdown_progress<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<TopdownProgress>
+where
+    DB: Blockstore + 'static,
+{
+    let next_nonce = get_nonces(state)?
+        .map(|record| record.next_nonce)
+        .unwrap_or_default();
+    let dead_letters = deadletter::list(state)?
+        .into_iter()
+        .map(|letter| letter.msg.msg.nonce)
+        .collect();
+    Ok(TopdownProgress {
+        next_nonce,
+        dead_letters,
+    })
+}
+
+/// Load the record of the applied top-down nonces, if the chain keeps one.
+pub fn get_nonces<DB>(state: &mut FvmExecState<DB>) -> anyhow::Result<Option<topdownnonces::State>>
+where
+    DB: Blockstore + 'static,
+{
+    let state_tree = state.state_tree_mut();
+    match state_tree.get_actor(TOPDOWN_NONCES_ACTOR_ID)? {
+        None => Ok(None),
+        Some(actor) => {
+            let record = state_tree
+                .store()
+                .get_cbor(&actor.state)?
+                .ok_or_else(|| anyhow!("top-down nonces state not found"))?;
+            Ok(Some(record))
+        }
+    }
+}
+
+/// Load the record of the applied top-down nonces; chains which started before the record
+/// existed start it from the nonce the gateway expects next.
+fn get_or_init_nonces<DB>(
+    gateway_caller: &GatewayCaller<DB>,
+    state: &mut FvmExecState<DB>,
+) -> anyhow::Result<topdownnonces::State>
+where
+    DB: Blockstore + Sync + Send + 'static,
+{
+    match get_nonces(state)? {
+        Some(record) => Ok(record),
+        None => {
+            let next_nonce = gateway_caller
+                .applied_topdown_nonce(state)
+                .context("failed to get the applied top-down nonce")?;
+            Ok(topdownnonces::State::new(next_nonce))
+        }
+    }
+}
+
+/// Save the record of the applied top-down nonces, creating the actor if it doesn't exist yet.
+fn set_nonces<DB>(state: &mut FvmExecState<DB>, record: &topdownnonces::State) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    let code = *state
+        .builtin_actors()
+        .code_by_id(placeholder::PLACEHOLDER_ACTOR_CODE_ID)
+        .ok_or_else(|| anyhow!("can't find the placeholder actor in the manifest"))?;
+
+    let state_tree = state.state_tree_mut();
+    let state_cid = state_tree.store().put_cbor(record, Code::Blake2b256)?;
+
+    let actor = match state_tree.get_actor(TOPDOWN_NONCES_ACTOR_ID)? {
+        Some(actor) => ActorState {
+            state: state_cid,
+            ..actor
+        },
+        None => ActorState {
+            code,
+            state: state_cid,
+            sequence: 0,
+            balance: TokenAmount::default(),
+            delegated_address: None,
+        },
+    };
+
+    state_tree.set_actor(TOPDOWN_NONCES_ACTOR_ID, actor);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_actor_interface::topdownnonces;
+    use fvm_shared::{address::Address, econ::TokenAmount};
+    use ipc_sdk::cross::{CrossMsg, StorableMsg};
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use super::{find_nonce_gap, split_nonces};
+
+    fn cross_msg(nonce: u64) -> CrossMsg {
+        let subnet_id = SubnetID::new(10, vec![Address::new_id(1000)]);
+        let mut msg = StorableMsg::new_fund_msg(
+            &subnet_id,
+            &Address::new_id(1),
+            &Address::new_id(2),
+            TokenAmount::from_atto(100),
+        )
+        .unwrap();
+        msg.nonce = nonce;
+        CrossMsg {
+            msg,
+            wrapped: false,
+        }
+    }
+
+    fn nonces(msgs: &[CrossMsg]) -> Vec<u64> {
+        msgs.iter().map(|m| m.msg.nonce).collect()
+    }
+
+    #[test]
+    fn applies_each_nonce_once() {
+        let record = topdownnonces::State::new(2);
+
+        // Replayed and duplicated messages are dropped.
+        let (fresh, gapped) = split_nonces(
+            &record,
+            vec![cross_msg(1), cross_msg(2), cross_msg(2), cross_msg(3)],
+        );
+        assert_eq!(nonces(&fresh), vec![2, 3]);
+        assert!(gapped.is_empty());
+    }
+
+    #[test]
+    fn splits_at_gaps() {
+        let record = topdownnonces::State::new(0);
+        let (fresh, gapped) = split_nonces(
+            &record,
+            vec![cross_msg(0), cross_msg(2), cross_msg(1), cross_msg(3)],
+        );
+        assert_eq!(nonces(&fresh), vec![0]);
+        assert_eq!(nonces(&gapped), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn finds_gaps() {
+        let msgs = vec![cross_msg(3), cross_msg(4), cross_msg(5)];
+        assert_eq!(find_nonce_gap(3, &msgs), None);
+        assert_eq!(find_nonce_gap(4, &msgs), None);
+        assert_eq!(find_nonce_gap(2, &msgs), Some(3));
+        assert_eq!(find_nonce_gap(0, &[]), None);
+    }
 }
@@ -0,0 +1,29 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::anyhow;
+use fendermint_vm_actor_interface::txcompression::{self, TX_COMPRESSION_ACTOR_ID};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+
+use super::state::FvmExecState;
+use crate::bytes::HasTxCompression;
+
+impl<DB> HasTxCompression for FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    fn max_decompressed_size(&mut self) -> anyhow::Result<Option<usize>> {
+        let state_tree = self.state_tree_mut();
+        match state_tree.get_actor(TX_COMPRESSION_ACTOR_ID)? {
+            None => Ok(None),
+            Some(actor) => {
+                let st: txcompression::State = state_tree
+                    .store()
+                    .get_cbor(&actor.state)?
+                    .ok_or_else(|| anyhow!("transaction compression state not found"))?;
+                Ok(Some(st.max_decompressed_size as usize))
+            }
+        }
+    }
+}
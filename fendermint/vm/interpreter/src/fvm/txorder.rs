@@ -0,0 +1,28 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fendermint_vm_actor_interface::txorder::TX_ORDER_ACTOR_ID;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_shared::address::Address;
+
+use super::state::FvmExecState;
+
+impl<DB> FvmExecState<DB>
+where
+    DB: Blockstore + 'static,
+{
+    /// Whether the genesis requires the signed messages in blocks to be in the canonical order.
+    pub fn canonical_tx_order(&mut self) -> anyhow::Result<bool> {
+        let actor = self.state_tree_mut().get_actor(TX_ORDER_ACTOR_ID)?;
+        Ok(actor.is_some())
+    }
+
+    /// The ID address of the sender to order its messages by, or the address as it is
+    /// if the account doesn't exist yet.
+    pub fn tx_order_sender(&mut self, from: &Address) -> anyhow::Result<Address> {
+        Ok(match self.state_tree_mut().lookup_id(from)? {
+            Some(id) => Address::new_id(id),
+            None => *from,
+        })
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::{anyhow, Context};
+use cid::multihash::Code;
+use fendermint_vm_actor_interface::validators::{self, VALIDATORS_ACTOR_ID};
+use fendermint_vm_genesis::{Power, Validator};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+
+use super::state::FvmExecState;
+
+/// Record the changes in the power table in the validator address book.
+///
+/// Does nothing if the chain was started without the address book actor.
+pub fn record_power_updates<DB>(
+    state: &mut FvmExecState<DB>,
+    updates: &[Validator<Power>],
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + 'static,
+{
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let height = state.block_height();
+    let state_tree = state.state_tree_mut();
+
+    let mut actor = match state_tree.get_actor(VALIDATORS_ACTOR_ID)? {
+        Some(actor) => actor,
+        None => return Ok(()),
+    };
+
+    let mut book: validators::State = state_tree
+        .store()
+        .get_cbor(&actor.state)?
+        .ok_or_else(|| anyhow!("validator address book state not found"))?;
+
+    for v in updates {
+        book.update(height, v.public_key.0.serialize().to_vec(), v.power.0);
+    }
+
+    actor.state = state_tree
+        .store()
+        .put_cbor(&book, Code::Blake2b256)
+        .context("failed to store validator address book")?;
+
+    state_tree.set_actor(VALIDATORS_ACTOR_ID, actor);
+
+    Ok(())
+}
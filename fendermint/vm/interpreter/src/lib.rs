@@ -2,10 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 use async_trait::async_trait;
 
+use crate::signed::BlockSignatureCheck;
+
 pub mod bytes;
+pub mod cache;
 pub mod chain;
 pub mod fvm;
 pub mod signed;
+pub mod stack;
+pub mod vectors;
 
 /// Initialize the chain state.
 ///
@@ -50,8 +55,99 @@ pub trait ProposalInterpreter: Sync + Send {
     ///
     /// This is our chance check whether CIDs proposed for execution are available.
     ///
-    /// Return `true` if we can accept this block, `false` to reject it.
-    async fn process(&self, state: Self::State, msgs: Vec<Self::Message>) -> anyhow::Result<bool>;
+    /// Return [`ProposalVerdict::Reject`] with the reason if we cannot accept this block.
+    async fn process(
+        &self,
+        state: Self::State,
+        msgs: Vec<Self::Message>,
+    ) -> anyhow::Result<ProposalVerdict>;
+}
+
+/// Verify the signatures of the messages in a block before they are delivered one by one.
+pub trait VerifyBlockInterpreter: Sync + Send {
+    type Message: Send;
+
+    /// Verify the signatures which haven't been verified yet in parallel, on the current
+    /// [rayon] thread pool, so that delivery doesn't have to verify them one at a time.
+    ///
+    /// Messages which cannot be decoded or have an invalid signature are skipped; they
+    /// fail the same way during delivery as they would have without this.
+    ///
+    /// Returns the number of signatures verified.
+    fn verify_block(&self, check: &BlockSignatureCheck, msgs: &[Self::Message]) -> usize;
+}
+
+/// The decision whether to vote for a proposed block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProposalVerdict {
+    Accept,
+    Reject(ProposalRejection),
+}
+
+impl ProposalVerdict {
+    pub fn is_accept(&self) -> bool {
+        matches!(self, Self::Accept)
+    }
+}
+
+/// Why a proposed block was rejected; the index is that of the offending transaction in the block.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ProposalRejection {
+    #[error("transaction {index} cannot be decoded: {error}")]
+    MalformedTx { index: usize, error: String },
+    #[error("transaction {index} executes a bottom-up checkpoint which hasn't been resolved")]
+    UnresolvedCheckpoint { index: usize },
+    #[error("transaction {index} proposes parent finality at height {height}, which isn't final")]
+    InvalidFinality { index: usize, height: u64 },
+    #[error("transaction {index} failed the signature check: {error}")]
+    InvalidSignature { index: usize, error: String },
+    #[error("transaction {index} is out of the canonical order")]
+    NonCanonicalOrder { index: usize },
+    #[error("transaction {index} exceeds what is left of the block gas limit")]
+    BlockGasLimit { index: usize },
+    #[error("transaction {index} executes top-down messages which skip to nonce {nonce}")]
+    TopDownNonceGap { index: usize, nonce: u64 },
+}
+
+impl ProposalRejection {
+    /// Short name of the reason, e.g. to label metrics with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MalformedTx { .. } => "malformed_tx",
+            Self::UnresolvedCheckpoint { .. } => "unresolved_checkpoint",
+            Self::InvalidFinality { .. } => "invalid_finality",
+            Self::InvalidSignature { .. } => "invalid_signature",
+            Self::NonCanonicalOrder { .. } => "non_canonical_order",
+            Self::BlockGasLimit { .. } => "block_gas_limit",
+            Self::TopDownNonceGap { .. } => "topdown_nonce_gap",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Self::MalformedTx { index, .. }
+            | Self::UnresolvedCheckpoint { index }
+            | Self::InvalidFinality { index, .. }
+            | Self::InvalidSignature { index, .. }
+            | Self::NonCanonicalOrder { index }
+            | Self::BlockGasLimit { index }
+            | Self::TopDownNonceGap { index, .. } => *index,
+        }
+    }
+
+    /// Change the index, when an outer interpreter dropped some of the transactions.
+    pub fn with_index(mut self, new_index: usize) -> Self {
+        match &mut self {
+            Self::MalformedTx { index, .. }
+            | Self::UnresolvedCheckpoint { index }
+            | Self::InvalidFinality { index, .. }
+            | Self::InvalidSignature { index, .. }
+            | Self::NonCanonicalOrder { index }
+            | Self::BlockGasLimit { index }
+            | Self::TopDownNonceGap { index, .. } => *index = new_index,
+        }
+        self
+    }
 }
 
 /// The `ExecInterpreter` applies messages on some state, which is
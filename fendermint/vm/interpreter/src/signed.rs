@@ -1,5 +1,7 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::time::Duration;
+
 use anyhow::anyhow;
 use async_trait::async_trait;
 
@@ -10,9 +12,11 @@ use fendermint_vm_message::{
 };
 use fvm_ipld_encoding::Error as IpldError;
 use fvm_shared::{chainid::ChainID, crypto::signature::Signature};
+use rayon::prelude::*;
 use serde::Serialize;
 
 use crate::{
+    cache::MessageCache,
     fvm::{FvmApplyRet, FvmCheckRet, FvmMessage},
     CheckInterpreter, ExecInterpreter, GenesisInterpreter, QueryInterpreter,
 };
@@ -20,6 +24,47 @@ use crate::{
 /// Message validation failed due to an invalid signature.
 pub struct InvalidSignature(pub String);
 
+/// States which know whether the chain accepts messages signed the way Filecoin wallets do.
+pub trait HasFilecoinSignatures {
+    /// Whether signatures over the message CID alone, without the chain ID, are accepted.
+    fn accepts_filecoin_signatures(&mut self) -> anyhow::Result<bool>;
+}
+
+/// What it takes to verify the signatures in a proposal before voting on it.
+#[derive(Debug, Clone)]
+pub struct ProposalSignatureCheck {
+    pub chain_id: ChainID,
+    /// Whether messages signed the way Filecoin wallets do are accepted.
+    pub filecoin_signatures: bool,
+    /// Time after which the rest of the signatures are left to be verified during delivery.
+    pub budget: Duration,
+}
+
+/// What it takes to verify the signatures in a block before executing it.
+#[derive(Debug, Clone)]
+pub struct BlockSignatureCheck {
+    pub chain_id: ChainID,
+    /// Whether messages signed the way Filecoin wallets do are accepted.
+    pub filecoin_signatures: bool,
+}
+
+/// Interpreters which can verify signatures before the messages are delivered.
+pub trait VerifySignatures {
+    /// Verify the signature of a message in a proposal, remembering the result so
+    /// the check can be skipped when the message is delivered.
+    fn verify_ahead(
+        &self,
+        msg: &VerifiableMessage,
+        check: &ProposalSignatureCheck,
+    ) -> Result<(), SignedMessageError>;
+
+    /// Verify the signatures of the messages in a block in parallel, on the current [rayon]
+    /// thread pool, remembering the valid ones so the check can be skipped when they are delivered.
+    ///
+    /// Returns the number of signatures verified.
+    fn verify_many(&self, msgs: &[VerifiableMessage], check: &BlockSignatureCheck) -> usize;
+}
+
 pub struct SignedMessageApplyRet {
     pub fvm: FvmApplyRet,
     pub domain_hash: Option<DomainHash>,
@@ -43,9 +88,20 @@ pub enum VerifiableMessage {
 }
 
 impl VerifiableMessage {
-    pub fn verify(&self, chain_id: &ChainID) -> Result<(), SignedMessageError> {
+    /// Verify the signature with the chain ID, or without it if `filecoin_signatures` is enabled
+    /// and the message was signed by a Filecoin wallet.
+    pub fn verify(
+        &self,
+        chain_id: &ChainID,
+        filecoin_signatures: bool,
+    ) -> Result<(), SignedMessageError> {
         match self {
-            Self::Signed(m) => m.verify(chain_id),
+            Self::Signed(m) => match m.verify(chain_id) {
+                Err(SignedMessageError::InvalidSignature(_)) if filecoin_signatures => {
+                    m.verify_filecoin()
+                }
+                res => res,
+            },
             Self::Synthetic(m) => m.verify(chain_id),
             Self::NotVerify(_) => Ok(()),
         }
@@ -109,11 +165,77 @@ impl SyntheticMessage {
 #[derive(Clone)]
 pub struct SignedMessageInterpreter<I> {
     inner: I,
+    /// Signed messages which passed the signature check in `CheckTx`,
+    /// so it can be skipped when they are delivered.
+    verified: MessageCache<cid::Cid, ()>,
 }
 
 impl<I> SignedMessageInterpreter<I> {
-    pub fn new(inner: I) -> Self {
-        Self { inner }
+    pub fn new(inner: I, verify_cache_size: usize) -> Self {
+        Self {
+            inner,
+            verified: MessageCache::new(verify_cache_size),
+        }
+    }
+
+    /// Key of a signed message in the cache of verified signatures.
+    ///
+    /// It's the CID of the whole message, signature included, so the check
+    /// can only be skipped for the exact same content.
+    fn verified_key(&self, msg: &VerifiableMessage) -> Option<cid::Cid> {
+        match msg {
+            VerifiableMessage::Signed(m) if self.verified.is_enabled() => {
+                fendermint_vm_message::cid(m).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<I> VerifySignatures for SignedMessageInterpreter<I> {
+    fn verify_ahead(
+        &self,
+        msg: &VerifiableMessage,
+        check: &ProposalSignatureCheck,
+    ) -> Result<(), SignedMessageError> {
+        let key = self.verified_key(msg);
+
+        // Checked in `CheckTx`, or in an earlier round at the same height.
+        if key.and_then(|key| self.verified.get(&key)).is_some() {
+            return Ok(());
+        }
+
+        msg.verify(&check.chain_id, check.filecoin_signatures)?;
+
+        if let Some(key) = key {
+            self.verified.insert(key, ());
+        }
+        Ok(())
+    }
+
+    /// Without the cache there is nowhere to remember the results, so nothing is verified.
+    ///
+    /// Invalid signatures are not remembered, so they are verified again, and reported, during delivery.
+    fn verify_many(&self, msgs: &[VerifiableMessage], check: &BlockSignatureCheck) -> usize {
+        // Checked in `CheckTx` or in `ProcessProposal`.
+        let pending = msgs
+            .iter()
+            .filter_map(|msg| self.verified_key(msg).map(|key| (key, msg)))
+            .filter(|(key, _)| self.verified.get(key).is_none())
+            .collect::<Vec<_>>();
+
+        let count = pending.len();
+        let verified = &self.verified;
+
+        pending
+            .into_par_iter()
+            .filter(|(_, msg)| {
+                msg.verify(&check.chain_id, check.filecoin_signatures)
+                    .is_ok()
+            })
+            .for_each(|(key, _)| verified.insert(key, ()));
+
+        count
     }
 }
 
@@ -121,7 +243,7 @@ impl<I> SignedMessageInterpreter<I> {
 impl<I> ExecInterpreter for SignedMessageInterpreter<I>
 where
     I: ExecInterpreter<Message = FvmMessage, DeliverOutput = FvmApplyRet>,
-    I::State: HasChainID,
+    I::State: HasChainID + HasFilecoinSignatures,
 {
     type State = I::State;
     type Message = VerifiableMessage;
@@ -131,14 +253,26 @@ where
 
     async fn deliver(
         &self,
-        state: Self::State,
+        mut state: Self::State,
         msg: Self::Message,
     ) -> anyhow::Result<(Self::State, Self::DeliverOutput)> {
         // Doing these first, so the compiler doesn't need `Send` bound, which it would if the
         // async call to `inner.deliver` would be inside a match holding a reference to `state`.
         let chain_id = state.chain_id();
 
-        match msg.verify(&chain_id) {
+        let is_verified = self
+            .verified_key(&msg)
+            .map(|key| self.verified.remove(&key).is_some())
+            .unwrap_or_default();
+
+        let verify_result = if is_verified {
+            Ok(())
+        } else {
+            let filecoin_signatures = state.accepts_filecoin_signatures()?;
+            msg.verify(&chain_id, filecoin_signatures)
+        };
+
+        match verify_result {
             Err(SignedMessageError::Ipld(e)) => Err(anyhow!(e)),
             Err(SignedMessageError::Ethereum(e)) => {
                 Ok((state, Err(InvalidSignature(e.to_string()))))
@@ -172,7 +306,7 @@ where
 impl<I> CheckInterpreter for SignedMessageInterpreter<I>
 where
     I: CheckInterpreter<Message = FvmMessage, Output = FvmCheckRet>,
-    I::State: HasChainID + Send + 'static,
+    I::State: HasChainID + HasFilecoinSignatures + Send + 'static,
 {
     type State = I::State;
     type Message = VerifiableMessage;
@@ -180,16 +314,23 @@ where
 
     async fn check(
         &self,
-        state: Self::State,
+        mut state: Self::State,
         msg: Self::Message,
         is_recheck: bool,
     ) -> anyhow::Result<(Self::State, Self::Output)> {
         let verify_result = if is_recheck {
             Ok(())
         } else {
-            msg.verify(&state.chain_id())
+            let filecoin_signatures = state.accepts_filecoin_signatures()?;
+            msg.verify(&state.chain_id(), filecoin_signatures)
         };
 
+        if !is_recheck && verify_result.is_ok() {
+            if let Some(key) = self.verified_key(&msg) {
+                self.verified.insert(key, ());
+            }
+        }
+
         match verify_result {
             Err(SignedMessageError::Ipld(e)) => Err(anyhow!(e)),
             Err(SignedMessageError::Ethereum(e)) => {
@@ -246,3 +387,68 @@ where
         self.inner.init(state, genesis).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_message::signed::SignedMessage;
+    use fvm_shared::{address::Address, chainid::ChainID, econ::TokenAmount};
+
+    use super::{
+        BlockSignatureCheck, SignedMessageInterpreter, VerifiableMessage, VerifySignatures,
+    };
+    use crate::fvm::FvmMessage;
+
+    fn signed(sk: &SecretKey, chain_id: &ChainID, sequence: u64) -> SignedMessage {
+        let message = FvmMessage {
+            version: 0,
+            from: Address::new_secp256k1(&sk.public_key().serialize()).unwrap(),
+            to: Address::new_id(200),
+            sequence,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 1000,
+            gas_fee_cap: TokenAmount::from_atto(1),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        SignedMessage::new_secp256k1(message, sk, chain_id).unwrap()
+    }
+
+    #[test]
+    fn remembers_valid_signatures() {
+        let sk = SecretKey::try_from(vec![1u8; 32]).unwrap();
+        let chain_id = ChainID::from(1);
+
+        let valid = signed(&sk, &chain_id, 0);
+        let mut invalid = signed(&sk, &chain_id, 1);
+        invalid.message.sequence = 2;
+
+        let check = BlockSignatureCheck {
+            chain_id,
+            filecoin_signatures: false,
+        };
+
+        let msgs = vec![
+            VerifiableMessage::Signed(valid),
+            VerifiableMessage::Signed(invalid),
+        ];
+
+        let interpreter = SignedMessageInterpreter::new((), 10);
+        assert_eq!(interpreter.verify_many(&msgs, &check), 2);
+
+        let is_verified = |msg: &VerifiableMessage| {
+            let key = interpreter.verified_key(msg).unwrap();
+            interpreter.verified.get(&key).is_some()
+        };
+        assert!(is_verified(&msgs[0]));
+        assert!(!is_verified(&msgs[1]));
+
+        // Only the one which failed is tried again.
+        assert_eq!(interpreter.verify_many(&msgs, &check), 1);
+
+        // Without a cache there is nowhere to remember the results.
+        let interpreter = SignedMessageInterpreter::new((), 0);
+        assert_eq!(interpreter.verify_many(&msgs, &check), 0);
+    }
+}
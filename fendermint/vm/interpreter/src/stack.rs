@@ -0,0 +1,290 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Explicit composition of the interpreter stack.
+//!
+//! The interpreters wrap each other from the FVM outwards, changing the type of message along
+//! the way: FVM messages are wrapped by signed ones, those by chain messages, which are finally
+//! decoded from raw bytes. The [`InterpreterBuilder`] has a method for each of these layers,
+//! which is only available if the interpreter built so far takes the messages the layer
+//! passes on, so a stack assembled in the wrong order doesn't compile.
+//!
+//! Cross-cutting concerns, such as metrics, tracing or policies applied to every message,
+//! can be inserted anywhere in the stack as a [`Layer`], without the other interpreters
+//! having to know about them.
+
+use std::marker::PhantomData;
+
+use fendermint_vm_message::chain::ChainMessage;
+
+use crate::{
+    bytes::{BytesMessageInterpreter, ProposalPrepareMode},
+    chain::{ChainMessageApplyRet, ChainMessageInterpreter},
+    fvm::{state::FvmExecState, FvmApplyRet, FvmMessage},
+    signed::{SignedMessageApplyRes, SignedMessageInterpreter, VerifiableMessage},
+    ExecInterpreter,
+};
+
+/// Wrap an interpreter into another one, like a middleware.
+pub trait Layer<I> {
+    type Interpreter;
+
+    fn layer(self, inner: I) -> Self::Interpreter;
+}
+
+/// Any function from one interpreter to another can be used as a layer, e.g. a constructor.
+impl<I, O, F> Layer<I> for F
+where
+    F: FnOnce(I) -> O,
+{
+    type Interpreter = O;
+
+    fn layer(self, inner: I) -> O {
+        self(inner)
+    }
+}
+
+/// Verify the signatures of messages before passing them on to the FVM.
+#[derive(Debug, Clone)]
+pub struct SignedLayer {
+    /// Number of messages to remember whose signature has already been checked.
+    pub verify_cache_size: usize,
+}
+
+impl<I> Layer<I> for SignedLayer
+where
+    I: ExecInterpreter<Message = FvmMessage, DeliverOutput = FvmApplyRet>,
+{
+    type Interpreter = SignedMessageInterpreter<I>;
+
+    fn layer(self, inner: I) -> Self::Interpreter {
+        SignedMessageInterpreter::new(inner, self.verify_cache_size)
+    }
+}
+
+/// Handle the IPC specific chain messages, passing the signed ones on.
+///
+/// The blockstore is that of the execution state of the inner interpreter.
+pub struct ChainLayer<DB>(PhantomData<DB>);
+
+impl<DB> Default for ChainLayer<DB> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I, DB> Layer<I> for ChainLayer<DB>
+where
+    I: ExecInterpreter<
+        Message = VerifiableMessage,
+        DeliverOutput = SignedMessageApplyRes,
+        State = FvmExecState<DB>,
+    >,
+{
+    type Interpreter = ChainMessageInterpreter<I, DB>;
+
+    fn layer(self, inner: I) -> Self::Interpreter {
+        ChainMessageInterpreter::new(inner)
+    }
+}
+
+/// Decode chain messages from the raw bytes of transactions.
+#[derive(Debug, Clone)]
+pub struct BytesLayer {
+    pub prepare_mode: ProposalPrepareMode,
+    pub reject_malformed_proposal: bool,
+    /// Number of decoded messages to remember between checks, proposals and delivery.
+    pub decode_cache_size: usize,
+}
+
+impl<I> Layer<I> for BytesLayer
+where
+    I: ExecInterpreter<Message = ChainMessage, DeliverOutput = ChainMessageApplyRet>,
+{
+    type Interpreter = BytesMessageInterpreter<I>;
+
+    fn layer(self, inner: I) -> Self::Interpreter {
+        BytesMessageInterpreter::new(
+            inner,
+            self.prepare_mode,
+            self.reject_malformed_proposal,
+            self.decode_cache_size,
+        )
+    }
+}
+
+/// Assemble the interpreter stack from the innermost layer outwards.
+///
+/// ```text
+/// let interpreter = InterpreterBuilder::new(fvm_interpreter)
+///     .signed(cache_size)
+///     .chain()
+///     .bytes(ProposalPrepareMode::AppendOnly, false, cache_size)
+///     .build();
+/// ```
+pub struct InterpreterBuilder<I> {
+    inner: I,
+}
+
+impl<I> InterpreterBuilder<I> {
+    /// Start with the innermost interpreter, which is normally the FVM.
+    pub fn new(inner: I) -> Self {
+        Self { inner }
+    }
+
+    /// Wrap the interpreter built so far with a layer.
+    pub fn layer<L>(self, layer: L) -> InterpreterBuilder<L::Interpreter>
+    where
+        L: Layer<I>,
+    {
+        InterpreterBuilder {
+            inner: layer.layer(self.inner),
+        }
+    }
+
+    /// Verify signed messages before passing them on to an FVM interpreter.
+    pub fn signed(self, verify_cache_size: usize) -> InterpreterBuilder<SignedMessageInterpreter<I>>
+    where
+        I: ExecInterpreter<Message = FvmMessage, DeliverOutput = FvmApplyRet>,
+    {
+        self.layer(SignedLayer { verify_cache_size })
+    }
+
+    /// Handle chain messages, passing the signed ones on to a signed message interpreter.
+    pub fn chain<DB>(self) -> InterpreterBuilder<ChainMessageInterpreter<I, DB>>
+    where
+        I: ExecInterpreter<
+            Message = VerifiableMessage,
+            DeliverOutput = SignedMessageApplyRes,
+            State = FvmExecState<DB>,
+        >,
+    {
+        self.layer(ChainLayer::default())
+    }
+
+    /// Decode the raw bytes of transactions into chain messages.
+    pub fn bytes(
+        self,
+        prepare_mode: ProposalPrepareMode,
+        reject_malformed_proposal: bool,
+        decode_cache_size: usize,
+    ) -> InterpreterBuilder<BytesMessageInterpreter<I>>
+    where
+        I: ExecInterpreter<Message = ChainMessage, DeliverOutput = ChainMessageApplyRet>,
+    {
+        self.layer(BytesLayer {
+            prepare_mode,
+            reject_malformed_proposal,
+            decode_cache_size,
+        })
+    }
+
+    /// The outermost interpreter.
+    pub fn build(self) -> I {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use async_trait::async_trait;
+
+    use super::{InterpreterBuilder, Layer};
+    use crate::ExecInterpreter;
+
+    /// Returns the messages it's given.
+    struct Echo;
+
+    #[async_trait]
+    impl ExecInterpreter for Echo {
+        type State = ();
+        type Message = u64;
+        type BeginOutput = ();
+        type DeliverOutput = u64;
+        type EndOutput = ();
+
+        async fn begin(&self, state: ()) -> anyhow::Result<((), ())> {
+            Ok((state, ()))
+        }
+
+        async fn deliver(&self, state: (), msg: u64) -> anyhow::Result<((), u64)> {
+            Ok((state, msg))
+        }
+
+        async fn end(&self, state: ()) -> anyhow::Result<((), ())> {
+            Ok((state, ()))
+        }
+    }
+
+    /// Counts the messages passing through.
+    struct Counting<I> {
+        inner: I,
+        count: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl<I> ExecInterpreter for Counting<I>
+    where
+        I: ExecInterpreter<Message = u64>,
+    {
+        type State = I::State;
+        type Message = u64;
+        type BeginOutput = I::BeginOutput;
+        type DeliverOutput = I::DeliverOutput;
+        type EndOutput = I::EndOutput;
+
+        async fn begin(&self, state: Self::State) -> anyhow::Result<(Self::State, I::BeginOutput)> {
+            self.inner.begin(state).await
+        }
+
+        async fn deliver(
+            &self,
+            state: Self::State,
+            msg: u64,
+        ) -> anyhow::Result<(Self::State, I::DeliverOutput)> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.inner.deliver(state, msg).await
+        }
+
+        async fn end(&self, state: Self::State) -> anyhow::Result<(Self::State, I::EndOutput)> {
+            self.inner.end(state).await
+        }
+    }
+
+    struct CountingLayer(Arc<AtomicUsize>);
+
+    impl<I> Layer<I> for CountingLayer {
+        type Interpreter = Counting<I>;
+
+        fn layer(self, inner: I) -> Counting<I> {
+            Counting {
+                inner,
+                count: self.0,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn layers_wrap_from_the_inside_out() {
+        let inner = Arc::new(AtomicUsize::new(0));
+        let outer = Arc::new(AtomicUsize::new(0));
+
+        let interpreter = InterpreterBuilder::new(Echo)
+            .layer(CountingLayer(inner.clone()))
+            .layer(|i| Counting {
+                inner: i,
+                count: outer.clone(),
+            })
+            .build();
+
+        let ((), out) = interpreter.deliver((), 42).await.unwrap();
+
+        assert_eq!(out, 42);
+        assert_eq!(inner.load(Ordering::Relaxed), 1);
+        assert_eq!(outer.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,717 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Test vectors to check that an implementation of the interpreter, or a refactoring of it,
+//! executes blocks exactly the same way as the one the vectors were recorded with.
+//!
+//! A vector is a directory with the following files:
+//! * `genesis.car`: the state right after genesis, in the same format as the snapshots
+//! * `blocks.jsonl`: one [BlockVector] per line, in the order they were executed
+//! * `parent.jsonl`: one [ParentVector] per line with what the parent answered the node,
+//!   if top-down finality was enabled
+//!
+//! The genesis is captured as a snapshot, rather than the genesis file, so that the vectors
+//! can be replayed without the builtin actors bundle and the contracts they were recorded with.
+//!
+//! The answers of the parent stand in for the parent during the replay, so that the top-down
+//! messages and validator changes the blocks execute are the same as when they were recorded.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use async_stm::atomically;
+use async_trait::async_trait;
+use cid::Cid;
+use fendermint_vm_core::Timestamp;
+use fendermint_vm_encoding::IsHumanReadable;
+use fendermint_vm_genesis::{Power, Validator};
+use fendermint_vm_topdown::proxy::{DynParentQueryProxy, ParentQueryProxy};
+use fendermint_vm_topdown::{
+    BlockHeight, CachedFinalityProvider, Config, IPCParentFinality, Toggle, NULL_ROUND_ERR_MSG,
+};
+use fvm::engine::MultiEngine;
+use fvm_shared::{clock::ChainEpoch, receipt::Receipt};
+use ipc_provider::manager::{GetBlockHashResult, TopDownQueryPayload};
+use ipc_sdk::cross::CrossMsg;
+use ipc_sdk::staking::StakingChangeRequest;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::serde_as;
+
+use crate::{
+    bytes::BytesMessageApplyRes,
+    chain::{ChainMessageApplyRet, CheckpointPool, TopDownFinalityProvider},
+    fvm::{
+        state::{ipc::GatewayCaller, snapshot::Snapshot, BlockHash, FvmExecState, FvmStateParams},
+        store::memory::MemoryBlockstore,
+        FvmBeginRet,
+    },
+    ExecInterpreter,
+};
+
+/// Name of the genesis snapshot in the vector directory.
+pub const GENESIS_FILE: &str = "genesis.car";
+/// Name of the file with the blocks in the vector directory.
+pub const BLOCKS_FILE: &str = "blocks.jsonl";
+/// Name of the file with the answers of the parent in the vector directory.
+pub const PARENT_FILE: &str = "parent.jsonl";
+
+/// The outcome of a transaction which was executed by the FVM.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptVector {
+    pub exit_code: u32,
+    /// Hex encoded return value.
+    pub return_data: String,
+    pub gas_used: u64,
+    /// Root of the events emitted, which commits to their content and order.
+    #[serde_as(as = "Option<IsHumanReadable>")]
+    pub events_root: Option<Cid>,
+}
+
+impl From<&Receipt> for ReceiptVector {
+    fn from(value: &Receipt) -> Self {
+        Self {
+            exit_code: value.exit_code.value(),
+            return_data: hex::encode(value.return_data.bytes()),
+            gas_used: value.gas_used,
+            events_root: value.events_root,
+        }
+    }
+}
+
+impl ReceiptVector {
+    /// Take the receipt from the result of delivering a transaction; `None` if the
+    /// transaction was rejected before it got to the FVM, e.g. because of a bad signature.
+    pub fn from_apply_res(res: &BytesMessageApplyRes) -> Option<Self> {
+        match res {
+            Ok(ChainMessageApplyRet::Signed(Ok(ret))) => {
+                Some(Self::from(&ret.fvm.apply_ret.msg_receipt))
+            }
+            Ok(ChainMessageApplyRet::Ipc(ret)) => Some(Self::from(&ret.apply_ret.msg_receipt)),
+            Ok(ChainMessageApplyRet::Signed(Err(_))) | Err(_) => None,
+        }
+    }
+}
+
+/// A block with everything needed to execute it, and the expected outcome.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockVector {
+    pub height: u64,
+    /// Unix timestamp of the block, in seconds.
+    pub timestamp: u64,
+    /// Hex encoded hash of the block, which is recorded in the state.
+    pub block_hash: String,
+    /// Hex encoded transactions, as they were delivered by CometBFT.
+    pub txs: Vec<String>,
+    /// Receipt of each transaction.
+    pub receipts: Vec<Option<ReceiptVector>>,
+    /// Validator power changes at the end of the block.
+    pub power_updates: Vec<Validator<Power>>,
+    /// State root after the block has been committed.
+    #[serde_as(as = "IsHumanReadable")]
+    pub state_root: Cid,
+}
+
+impl BlockVector {
+    /// Describe the first difference between the expected and the actual outcome, if any.
+    pub fn mismatch(&self, actual: &BlockVector) -> Option<String> {
+        if self.receipts.len() != actual.receipts.len() {
+            return Some(format!(
+                "expected {} receipts, got {}",
+                self.receipts.len(),
+                actual.receipts.len()
+            ));
+        }
+        for (i, (e, a)) in self.receipts.iter().zip(actual.receipts.iter()).enumerate() {
+            if e != a {
+                return Some(format!("receipt {i}: expected {e:?}, got {a:?}"));
+            }
+        }
+        if self.power_updates != actual.power_updates {
+            return Some(format!(
+                "power updates: expected {:?}, got {:?}",
+                self.power_updates, actual.power_updates
+            ));
+        }
+        if self.state_root != actual.state_root {
+            return Some(format!(
+                "state root: expected {}, got {}",
+                self.state_root, actual.state_root
+            ));
+        }
+        None
+    }
+}
+
+/// Hex encoded hashes of a parent block and the non-null block before it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockHashVector {
+    pub parent_block_hash: String,
+    pub block_hash: String,
+}
+
+impl From<&GetBlockHashResult> for BlockHashVector {
+    fn from(value: &GetBlockHashResult) -> Self {
+        Self {
+            parent_block_hash: hex::encode(&value.parent_block_hash),
+            block_hash: hex::encode(&value.block_hash),
+        }
+    }
+}
+
+/// The top-down messages or validator changes of a parent block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PayloadVector {
+    /// Hex encoded hash of the parent block.
+    pub block_hash: String,
+    /// Hex encoded CBOR of the payload.
+    pub value: String,
+}
+
+impl PayloadVector {
+    pub fn new<T: Serialize>(payload: &TopDownQueryPayload<T>) -> anyhow::Result<Self> {
+        Ok(Self {
+            block_hash: hex::encode(&payload.block_hash),
+            value: hex::encode(fvm_ipld_encoding::to_vec(&payload.value)?),
+        })
+    }
+
+    fn to_payload<T: DeserializeOwned>(&self) -> anyhow::Result<TopDownQueryPayload<T>> {
+        Ok(TopDownQueryPayload {
+            block_hash: hex::decode(&self.block_hash).context("invalid block hash")?,
+            value: fvm_ipld_encoding::from_slice(&hex::decode(&self.value)?)?,
+        })
+    }
+}
+
+/// An answer of the parent to a query of the node; a `None` result means a null round.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum ParentVector {
+    GenesisEpoch {
+        height: BlockHeight,
+    },
+    BlockHash {
+        height: BlockHeight,
+        result: Option<BlockHashVector>,
+    },
+    TopDownMsgs {
+        height: BlockHeight,
+        result: Option<PayloadVector>,
+    },
+    ValidatorChanges {
+        height: BlockHeight,
+        result: Option<PayloadVector>,
+    },
+}
+
+/// Stands in for the parent during a replay, giving the answers it gave when the vector was recorded.
+#[derive(Default)]
+pub struct ReplayParentProxy {
+    genesis_epoch: Option<BlockHeight>,
+    block_hashes: BTreeMap<BlockHeight, Option<BlockHashVector>>,
+    top_down_msgs: BTreeMap<BlockHeight, Option<PayloadVector>>,
+    validator_changes: BTreeMap<BlockHeight, Option<PayloadVector>>,
+}
+
+impl ReplayParentProxy {
+    /// Index the answers; if the same query was answered more than once, the last answer wins.
+    pub fn new(answers: Vec<ParentVector>) -> Self {
+        let mut proxy = Self::default();
+        for answer in answers {
+            match answer {
+                ParentVector::GenesisEpoch { height } => {
+                    proxy.genesis_epoch = Some(height);
+                }
+                ParentVector::BlockHash { height, result } => {
+                    proxy.block_hashes.insert(height, result);
+                }
+                ParentVector::TopDownMsgs { height, result } => {
+                    proxy.top_down_msgs.insert(height, result);
+                }
+                ParentVector::ValidatorChanges { height, result } => {
+                    proxy.validator_changes.insert(height, result);
+                }
+            }
+        }
+        proxy
+    }
+
+    fn answer<'a, T>(
+        answers: &'a BTreeMap<BlockHeight, Option<T>>,
+        query: &str,
+        height: BlockHeight,
+    ) -> anyhow::Result<&'a T> {
+        match answers.get(&height) {
+            Some(Some(answer)) => Ok(answer),
+            Some(None) => Err(anyhow!(NULL_ROUND_ERR_MSG)),
+            None => Err(anyhow!("{query} at parent height {height} wasn't recorded")),
+        }
+    }
+}
+
+#[async_trait]
+impl ParentQueryProxy for ReplayParentProxy {
+    async fn get_chain_head_height(&self) -> anyhow::Result<BlockHeight> {
+        self.block_hashes
+            .keys()
+            .last()
+            .cloned()
+            .ok_or_else(|| anyhow!("no parent blocks were recorded"))
+    }
+
+    async fn get_genesis_epoch(&self) -> anyhow::Result<BlockHeight> {
+        self.genesis_epoch
+            .ok_or_else(|| anyhow!("the genesis epoch wasn't recorded"))
+    }
+
+    async fn get_block_hash(&self, height: BlockHeight) -> anyhow::Result<GetBlockHashResult> {
+        let answer = Self::answer(&self.block_hashes, "block hash", height)?;
+        Ok(GetBlockHashResult {
+            parent_block_hash: hex::decode(&answer.parent_block_hash)?,
+            block_hash: hex::decode(&answer.block_hash)?,
+        })
+    }
+
+    async fn get_top_down_msgs(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<CrossMsg>>> {
+        Self::answer(&self.top_down_msgs, "top-down messages", height)?.to_payload()
+    }
+
+    async fn get_validator_changes(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<StakingChangeRequest>>> {
+        Self::answer(&self.validator_changes, "validator changes", height)?.to_payload()
+    }
+}
+
+fn read_lines<T: DeserializeOwned>(path: &Path) -> anyhow::Result<Vec<T>> {
+    let file = std::fs::File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+    let mut items = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(&line)
+            .with_context(|| format!("failed to parse line {} of {path:?}", i + 1))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Read the blocks of a vector.
+pub fn read_blocks(dir: &Path) -> anyhow::Result<Vec<BlockVector>> {
+    read_lines(&dir.join(BLOCKS_FILE))
+}
+
+/// Read the answers of the parent in a vector; empty if top-down finality was disabled.
+pub fn read_parent(dir: &Path) -> anyhow::Result<Vec<ParentVector>> {
+    let path = dir.join(PARENT_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    read_lines(&path)
+}
+
+/// Replay the blocks of a vector on top of its genesis, checking after every block
+/// that the outcome is exactly the recorded one.
+///
+/// Returns the number of blocks replayed.
+pub async fn replay<I>(interpreter: &I, dir: &Path) -> anyhow::Result<usize>
+where
+    I: ExecInterpreter<
+        State = (
+            CheckpointPool,
+            TopDownFinalityProvider,
+            FvmExecState<MemoryBlockstore>,
+        ),
+        Message = Vec<u8>,
+        BeginOutput = FvmBeginRet,
+        DeliverOutput = BytesMessageApplyRes,
+        EndOutput = Vec<Validator<Power>>,
+    >,
+{
+    let store = MemoryBlockstore::new();
+
+    let snapshot = Snapshot::read_car(dir.join(GENESIS_FILE), store.clone(), true)
+        .await
+        .context("failed to load genesis snapshot")?;
+
+    let (mut params, genesis_height) = match snapshot {
+        Snapshot::V1(snapshot) => (snapshot.state_params().clone(), snapshot.block_height()),
+    };
+
+    let blocks = read_blocks(dir)?;
+    let answers = read_parent(dir)?;
+    let multi_engine = MultiEngine::new(1);
+    let pool = CheckpointPool::new();
+
+    let provider: TopDownFinalityProvider = if answers.is_empty() {
+        Arc::new(Toggle::disabled())
+    } else {
+        let parent: DynParentQueryProxy = Arc::new(ReplayParentProxy::new(answers));
+        let parent = Arc::new(parent);
+        // Whatever wasn't recorded isn't going to turn up by waiting for it.
+        let config = Config::new(0, Duration::ZERO, Duration::ZERO, 0);
+        let provider = CachedFinalityProvider::uninitialized(config, parent.clone())
+            .await
+            .context("failed to create the parent finality provider")?;
+        let provider = Arc::new(Toggle::enabled(provider));
+
+        let mut state = FvmExecState::new(
+            store.clone(),
+            &multi_engine,
+            genesis_height as ChainEpoch,
+            params.clone(),
+        )
+        .context("error creating genesis state")?;
+
+        let finality = starting_finality(&mut state, &parent).await?;
+        atomically(|| provider.set_new_finality(finality.clone(), None)).await;
+
+        provider
+    };
+
+    for expected in blocks.iter() {
+        let actual = execute_block(
+            interpreter,
+            &store,
+            &multi_engine,
+            &mut params,
+            &pool,
+            &provider,
+            expected,
+        )
+        .await?;
+
+        if let Some(mismatch) = expected.mismatch(&actual) {
+            bail!("block {} diverged: {mismatch}", expected.height);
+        }
+    }
+
+    Ok(blocks.len())
+}
+
+/// The finality the node started syncing the parent from, the same way the syncer finds it.
+async fn starting_finality(
+    state: &mut FvmExecState<MemoryBlockstore>,
+    parent: &DynParentQueryProxy,
+) -> anyhow::Result<IPCParentFinality> {
+    let finality = GatewayCaller::default()
+        .get_latest_parent_finality(state)
+        .context("failed to get the latest parent finality")?;
+
+    if finality.height > 0 {
+        return Ok(finality);
+    }
+
+    let genesis_epoch = parent.get_genesis_epoch().await?;
+    let r = parent.get_block_hash(genesis_epoch).await?;
+
+    Ok(IPCParentFinality {
+        height: genesis_epoch,
+        block_hash: r.block_hash,
+    })
+}
+
+/// Execute a block on top of the state in `params`, moving them on to the state after the block,
+/// and return the block with the actual outcome.
+async fn execute_block<I>(
+    interpreter: &I,
+    store: &MemoryBlockstore,
+    multi_engine: &MultiEngine,
+    params: &mut FvmStateParams,
+    pool: &CheckpointPool,
+    provider: &TopDownFinalityProvider,
+    block: &BlockVector,
+) -> anyhow::Result<BlockVector>
+where
+    I: ExecInterpreter<
+        State = (
+            CheckpointPool,
+            TopDownFinalityProvider,
+            FvmExecState<MemoryBlockstore>,
+        ),
+        Message = Vec<u8>,
+        BeginOutput = FvmBeginRet,
+        DeliverOutput = BytesMessageApplyRes,
+        EndOutput = Vec<Validator<Power>>,
+    >,
+{
+    let block_hash: BlockHash = hex::decode(&block.block_hash)
+        .context("invalid block hash")?
+        .try_into()
+        .map_err(|_| anyhow!("block hash must be 32 bytes"))?;
+
+    params.timestamp = Timestamp(block.timestamp);
+
+    let state = FvmExecState::new(
+        store.clone(),
+        multi_engine,
+        block.height as ChainEpoch,
+        params.clone(),
+    )
+    .context("error creating new state")?
+    .with_block_hash(block_hash);
+
+    let (mut state, _) = interpreter
+        .begin((pool.clone(), provider.clone(), state))
+        .await
+        .with_context(|| format!("begin failed at height {}", block.height))?;
+
+    let mut receipts = Vec::new();
+    for tx in block.txs.iter() {
+        let tx = hex::decode(tx).context("invalid transaction")?;
+        let (s, ret) = interpreter
+            .deliver(state, tx)
+            .await
+            .with_context(|| format!("deliver failed at height {}", block.height))?;
+        receipts.push(ReceiptVector::from_apply_res(&ret));
+        state = s;
+    }
+
+    let ((_, _, state), power_updates) = interpreter
+        .end(state)
+        .await
+        .with_context(|| format!("end failed at height {}", block.height))?;
+
+    let (state_root, updatable, _) = state.commit().context("failed to commit FVM")?;
+
+    params.state_root = state_root;
+    params.base_fee = updatable.base_fee;
+    params.power_scale = updatable.power_scale;
+    params.circ_supply = updatable.circ_supply;
+
+    Ok(BlockVector {
+        receipts,
+        power_updates,
+        state_root,
+        ..block.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fendermint_crypto::SecretKey;
+    use fendermint_vm_genesis::Genesis;
+    use fendermint_vm_message::{chain::ChainMessage, signed::SignedMessage};
+    use fendermint_vm_topdown::breaker::ParentErrorKind;
+    use fendermint_vm_topdown::proxy::ParentQueryProxy;
+    use fendermint_vm_topdown::Toggle;
+    use fvm::engine::MultiEngine;
+    use fvm_ipld_encoding::DAG_CBOR;
+    use fvm_shared::address::Address;
+    use fvm_shared::chainid::ChainID;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::message::Message;
+    use ipc_provider::manager::TopDownQueryPayload;
+    use ipc_sdk::cross::CrossMsg;
+
+    use super::{
+        execute_block, replay, BlockHashVector, BlockVector, ParentVector, PayloadVector,
+        ReceiptVector, ReplayParentProxy, BLOCKS_FILE, GENESIS_FILE,
+    };
+    use crate::bytes::ProposalPrepareMode;
+    use crate::chain::CheckpointPool;
+    use crate::fvm::state::{snapshot::Snapshot, FvmStateParams};
+    use crate::fvm::store::memory::MemoryBlockstore;
+    use crate::fvm::testing::{self, interpreter};
+    use crate::stack::InterpreterBuilder;
+
+    /// Create the genesis state of a chain with an account for the secret key.
+    async fn genesis_state(sk: &SecretKey) -> (MemoryBlockstore, FvmStateParams) {
+        let owner = Address::new_secp256k1(&sk.public_key().serialize()).unwrap();
+        testing::genesis_state(Genesis {
+            accounts: vec![testing::account(owner, TokenAmount::from_whole(1))],
+            ..testing::genesis()
+        })
+        .await
+    }
+
+    fn write_blocks(dir: &Path, blocks: &[BlockVector]) {
+        let lines = blocks
+            .iter()
+            .map(|b| serde_json::to_string(b).unwrap() + "\n")
+            .collect::<String>();
+        std::fs::write(dir.join(BLOCKS_FILE), lines).unwrap();
+    }
+
+    #[test]
+    fn block_vector_mismatch() {
+        let receipt = ReceiptVector {
+            exit_code: 0,
+            return_data: "00".into(),
+            gas_used: 100,
+            events_root: None,
+        };
+        let expected = BlockVector {
+            height: 1,
+            timestamp: 1000,
+            block_hash: hex::encode([0u8; 32]),
+            txs: vec!["01".into(), "02".into()],
+            receipts: vec![Some(receipt.clone()), None],
+            power_updates: Vec::new(),
+            state_root: Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"state")),
+        };
+
+        let json = serde_json::to_string(&expected).unwrap();
+        let parsed: BlockVector = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, expected);
+        assert!(expected.mismatch(&parsed).is_none());
+
+        let actual = BlockVector {
+            receipts: vec![
+                Some(ReceiptVector {
+                    gas_used: 101,
+                    ..receipt
+                }),
+                None,
+            ],
+            ..expected.clone()
+        };
+
+        let mismatch = expected.mismatch(&actual).expect("gas differs");
+        assert!(mismatch.starts_with("receipt 0"));
+    }
+
+    #[tokio::test]
+    async fn replays_what_the_parent_answered() {
+        let payload = TopDownQueryPayload {
+            value: Vec::<CrossMsg>::new(),
+            block_hash: vec![1; 32],
+        };
+        let answers = vec![
+            ParentVector::GenesisEpoch { height: 10 },
+            ParentVector::BlockHash {
+                height: 10,
+                result: Some(BlockHashVector {
+                    parent_block_hash: hex::encode([0u8; 32]),
+                    block_hash: hex::encode([1u8; 32]),
+                }),
+            },
+            ParentVector::BlockHash {
+                height: 11,
+                result: None,
+            },
+            ParentVector::TopDownMsgs {
+                height: 10,
+                result: Some(PayloadVector::new(&payload).unwrap()),
+            },
+        ];
+
+        // Go through JSON, the way the answers are read from the vector.
+        let answers = answers
+            .iter()
+            .map(|a| serde_json::from_str(&serde_json::to_string(a).unwrap()).unwrap())
+            .collect::<Vec<ParentVector>>();
+
+        let parent = ReplayParentProxy::new(answers);
+
+        assert_eq!(parent.get_genesis_epoch().await.unwrap(), 10);
+        assert_eq!(parent.get_chain_head_height().await.unwrap(), 11);
+        assert_eq!(
+            parent.get_block_hash(10).await.unwrap().block_hash,
+            vec![1; 32]
+        );
+
+        let msgs = parent.get_top_down_msgs(10).await.unwrap();
+        assert!(msgs.value.is_empty());
+        assert_eq!(msgs.block_hash, payload.block_hash);
+
+        let err = parent.get_block_hash(11).await.unwrap_err();
+        assert_eq!(ParentErrorKind::of(&err), ParentErrorKind::NullRound);
+
+        let err = parent.get_validator_changes(10).await.unwrap_err();
+        assert_ne!(ParentErrorKind::of(&err), ParentErrorKind::NullRound);
+    }
+
+    #[tokio::test]
+    async fn replays_executed_blocks() {
+        let sk = SecretKey::try_from(vec![1u8; 32]).unwrap();
+        let (store, params) = genesis_state(&sk).await;
+        let dir = tempfile::tempdir().unwrap();
+
+        Snapshot::new(store.clone(), params.clone(), 0)
+            .unwrap()
+            .write_car(dir.path().join(GENESIS_FILE))
+            .await
+            .unwrap();
+
+        let interpreter = InterpreterBuilder::new(interpreter())
+            .signed(0)
+            .chain()
+            .bytes(ProposalPrepareMode::AppendOnly, false, 0)
+            .build();
+
+        // A transfer to the burnt funds actor.
+        let msg = Message {
+            version: 0,
+            from: Address::new_secp256k1(&sk.public_key().serialize()).unwrap(),
+            to: Address::new_id(99),
+            sequence: 0,
+            value: TokenAmount::from_atto(1),
+            method_num: 0,
+            params: Default::default(),
+            gas_limit: 10_000_000,
+            gas_fee_cap: TokenAmount::from_atto(10_000),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        let chain_id = ChainID::from(params.chain_id);
+        let signed = SignedMessage::new_secp256k1(msg, &sk, &chain_id).unwrap();
+        let tx = fvm_ipld_encoding::to_vec(&ChainMessage::Signed(signed)).unwrap();
+
+        let block = BlockVector {
+            height: 1,
+            timestamp: 1,
+            block_hash: hex::encode([1u8; 32]),
+            txs: vec![hex::encode(tx)],
+            receipts: Vec::new(),
+            power_updates: Vec::new(),
+            state_root: Cid::default(),
+        };
+
+        let block = execute_block(
+            &interpreter,
+            &store,
+            &MultiEngine::new(1),
+            &mut params.clone(),
+            &CheckpointPool::new(),
+            &Arc::new(Toggle::disabled()),
+            &block,
+        )
+        .await
+        .expect("failed to execute block");
+
+        let receipt = block.receipts[0].clone().expect("transfer got to the FVM");
+        assert_eq!(receipt.exit_code, 0);
+
+        write_blocks(dir.path(), &[block.clone()]);
+        assert_eq!(replay(&interpreter, dir.path()).await.unwrap(), 1);
+
+        // Any difference in the outcome is caught.
+        let tampered = BlockVector {
+            receipts: vec![Some(ReceiptVector {
+                gas_used: receipt.gas_used + 1,
+                ..receipt
+            })],
+            ..block
+        };
+        write_blocks(dir.path(), &[tampered]);
+        let err = replay(&interpreter, dir.path()).await.unwrap_err();
+        assert!(err.to_string().contains("diverged"), "{err:#}");
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Replay the test vectors recorded on the docker test networks.
+//!
+//! Every subdirectory of `TEST_VECTORS_DIR` is expected to be a vector; see
+//! [fendermint_vm_interpreter::vectors] for the format. The test is ignored by default,
+//! because it needs vectors from a network; recording and replaying a vector is covered
+//! by the unit tests of the module.
+//!
+//! Example:
+//!
+//! ```text
+//! cd fendermint/testing/smoke-test
+//! TEST_VECTORS_DIR=/tmp/vectors cargo make --profile ci
+//! cd -
+//! TEST_VECTORS_DIR=/tmp/vectors cargo test -p fendermint_vm_interpreter --test vectors -- --ignored
+//! ```
+
+use std::path::PathBuf;
+
+use fendermint_vm_interpreter::{
+    bytes::ProposalPrepareMode,
+    fvm::{store::memory::MemoryBlockstore, FvmMessageInterpreter},
+    stack::InterpreterBuilder,
+    vectors,
+};
+use tendermint_rpc::{MockClient, MockRequestMethodMatcher};
+
+#[tokio::test]
+#[ignore = "needs TEST_VECTORS_DIR"]
+async fn replay_vectors() {
+    let dir = std::env::var("TEST_VECTORS_DIR")
+        .map(PathBuf::from)
+        .expect("TEST_VECTORS_DIR is not set");
+
+    let (client, _) = MockClient::new(MockRequestMethodMatcher::default());
+
+    // Replaying only ever delivers transactions, so the parameters for checks and estimates don't matter.
+    let interpreter = FvmMessageInterpreter::<MemoryBlockstore, _>::new(
+        client,
+        None,
+        PathBuf::new(),
+        1.0,
+        1.0,
+        false,
+    );
+    let interpreter = InterpreterBuilder::new(interpreter)
+        .signed(0)
+        .chain()
+        .bytes(ProposalPrepareMode::AppendOnly, false, 0)
+        .build();
+
+    let mut entries = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("failed to read {dir:?}: {e}"))
+        .map(|e| e.expect("failed to read entry").path())
+        .filter(|p| p.is_dir())
+        .collect::<Vec<_>>();
+
+    entries.sort();
+
+    assert!(!entries.is_empty(), "no vectors found in {dir:?}");
+
+    for path in entries {
+        let blocks = vectors::replay(&interpreter, &path)
+            .await
+            .unwrap_or_else(|e| panic!("vector {path:?} failed: {e:#}"));
+
+        eprintln!("replayed {blocks} blocks from {path:?}");
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The block hashes exposed by the Ethereum API, derived from the CometBFT block hashes.
+//!
+//! The derivation only depends on the CometBFT hash, so every node exposes the same hash
+//! for the same block, before and after restarts. It is versioned so that it can't change
+//! under clients: the version is chosen in the genesis, so existing chains keep exposing
+//! the CometBFT hashes, and nodes of chains which derive them advertise it among their
+//! [`crate::query::Capabilities`].
+
+use ethers_core::utils::keccak256;
+
+/// Domain separation tag of [`EthBlockHashVersion::V1`], so that a derived hash is never
+/// mistaken for a CometBFT one.
+pub const ETH_BLOCK_HASH_TAG_V1: &[u8] = b"fendermint/eth-block-hash/v1";
+
+/// The ways of deriving the Ethereum block hash from the CometBFT block hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EthBlockHashVersion {
+    /// The CometBFT block hash as is; what chains which didn't choose otherwise expose.
+    #[default]
+    V0,
+    /// `keccak256("fendermint/eth-block-hash/v1" ++ cometbft_hash)`.
+    V1,
+}
+
+impl EthBlockHashVersion {
+    /// Derive the Ethereum block hash from the CometBFT block hash.
+    pub fn to_eth_block_hash(&self, block_hash: &[u8; 32]) -> [u8; 32] {
+        match self {
+            Self::V0 => *block_hash,
+            Self::V1 => keccak256([ETH_BLOCK_HASH_TAG_V1, block_hash.as_slice()].concat()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EthBlockHashVersion;
+
+    #[test]
+    fn v0_is_identity() {
+        let h = [1u8; 32];
+        assert_eq!(EthBlockHashVersion::V0.to_eth_block_hash(&h), h);
+    }
+
+    #[test]
+    fn v1_is_stable() {
+        // Changing this value breaks every client that stored a block hash.
+        let h = EthBlockHashVersion::V1.to_eth_block_hash(&[0u8; 32]);
+        assert_eq!(
+            hex::encode(h),
+            "4ac5c79eaed68d2bdee3b130960b82dd149318fb2f72f3e3f8e866db5e4c0b5f"
+        );
+    }
+}
@@ -1,9 +1,22 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::io::Read;
+
+use anyhow::{bail, Context};
+use fvm_ipld_encoding::RawBytes;
 use serde::{Deserialize, Serialize};
 
 use crate::{ipc::IpcMessage, signed::SignedMessage};
 
+/// Compression level used for [ChainMessage::Compressed]; the default of zstd.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Largest size a compressed message can expand to, to protect nodes from decompression bombs.
+///
+/// This is what the genesis sets as the limit of the chain. It's well above what fits
+/// into a block with the default CometBFT settings even after compression.
+pub const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
 /// The different kinds of messages that can appear in blocks, ie. the transactions
 /// we can receive from Tendermint through the ABCI.
 ///
@@ -28,6 +41,39 @@ pub enum ChainMessage {
     /// Because of the involvement of data availability voting and CID resolution, these messages require support
     /// from the application, which is why they are handled in a special way.
     Ipc(IpcMessage),
+
+    /// A signed message encoded as CBOR and compressed with zstd, so that large messages,
+    /// such as contract deployments, fit into blocks more easily.
+    ///
+    /// It's only accepted if the chain enabled compression in its genesis.
+    Compressed(RawBytes),
+}
+
+impl ChainMessage {
+    /// Wrap a signed message into a compressed envelope.
+    pub fn compress(msg: &SignedMessage) -> anyhow::Result<Self> {
+        let bz = fvm_ipld_encoding::to_vec(msg).context("failed to encode message")?;
+        let bz = zstd::bulk::compress(&bz, COMPRESSION_LEVEL).context("failed to compress")?;
+        Ok(Self::Compressed(RawBytes::new(bz)))
+    }
+
+    /// Unwrap the message from a compressed envelope, refusing to expand it beyond `max_size` bytes.
+    pub fn decompress(data: &[u8], max_size: usize) -> anyhow::Result<SignedMessage> {
+        // Streaming into a buffer which only grows as needed, rather than allocating the maximum up front.
+        let mut bz = Vec::new();
+        zstd::stream::read::Decoder::with_buffer(data)
+            .context("failed to create decompressor")?
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut bz)
+            .context("failed to decompress message")?;
+        if bz.len() > max_size {
+            bail!("compressed message expands beyond {max_size} bytes");
+        }
+        if bz.is_empty() {
+            bail!("empty compressed message");
+        }
+        fvm_ipld_encoding::from_slice(&bz).context("failed to decode compressed message")
+    }
 }
 
 #[cfg(feature = "arb")]
@@ -38,9 +84,11 @@ mod arb {
 
     impl quickcheck::Arbitrary for ChainMessage {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            match u8::arbitrary(g) % 2 {
+            match u8::arbitrary(g) % 3 {
                 0 => ChainMessage::Signed(SignedMessage::arbitrary(g)),
-                _ => ChainMessage::Ipc(IpcMessage::arbitrary(g)),
+                1 => ChainMessage::Ipc(IpcMessage::arbitrary(g)),
+                _ => ChainMessage::compress(&SignedMessage::arbitrary(g))
+                    .expect("failed to compress message"),
             }
         }
     }
@@ -49,6 +97,7 @@ mod arb {
 #[cfg(test)]
 mod tests {
     use crate::chain::ChainMessage;
+    use crate::signed::SignedMessage;
     use quickcheck_macros::quickcheck;
 
     #[quickcheck]
@@ -59,4 +108,19 @@ mod tests {
 
         assert_eq!(value1, value0)
     }
+
+    #[quickcheck]
+    fn chain_message_compression(msg0: SignedMessage) {
+        let size = fvm_ipld_encoding::to_vec(&msg0).unwrap().len();
+
+        let bz = match ChainMessage::compress(&msg0).expect("failed to compress") {
+            ChainMessage::Compressed(bz) => bz,
+            other => panic!("unexpected message: {other:?}"),
+        };
+
+        let msg1 = ChainMessage::decompress(bz.bytes(), size).expect("failed to decompress");
+        assert_eq!(msg1, msg0);
+
+        assert!(ChainMessage::decompress(bz.bytes(), size - 1).is_err());
+    }
 }
@@ -4,9 +4,11 @@ use cid::{multihash, multihash::MultihashDigest, Cid};
 use fvm_ipld_encoding::{to_vec, Error as IpldError, DAG_CBOR};
 use serde::Serialize;
 
+pub mod block_hash;
 pub mod chain;
 pub mod conv;
 pub mod ipc;
+pub mod logs;
 pub mod query;
 pub mod signed;
 
@@ -0,0 +1,189 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Derive Ethereum log topics and data from FVM actor events.
+//!
+//! Events emitted by the EVM actor have their topics in the `t1`..`t4` entries and
+//! their data in the `d` entry, which are taken over verbatim.
+//!
+//! Events emitted by native actors, e.g. the builtin ones or those in Fendermint, have
+//! arbitrary keys, so the following rules apply to make them visible to Ethereum tools:
+//! * the first topic is the Keccak-256 hash of a signature made of the keys in order,
+//!   with the first one being the name, e.g. `governance(proposal_id)` for an event with
+//!   the entries `governance` and `proposal_id`
+//! * the values of the first three indexed entries are the rest of the topics, left padded
+//!   with zeroes if they are shorter than 32 bytes, and hashed with Keccak-256 if longer
+//! * the data is the ABI encoding of the values of all the entries as `bytes[]`, in order,
+//!   so that nothing is lost by padding or hashing the topics
+
+use anyhow::anyhow;
+use ethers_core::abi::{self, Token};
+use ethers_core::types as et;
+use ethers_core::utils::keccak256;
+
+/// Maximum number of topics a log can have, including the signature.
+const MAX_TOPICS: usize = 4;
+
+/// An entry of an actor event.
+#[derive(Debug, Clone, Copy)]
+pub struct EventEntry<'a> {
+    pub key: &'a str,
+    pub value: &'a [u8],
+    /// Whether the actor marked the entry to be indexed.
+    pub indexed: bool,
+}
+
+/// Check whether the entries follow the layout used by the EVM actor.
+fn is_evm_event(entries: &[EventEntry]) -> bool {
+    entries
+        .iter()
+        .any(|e| matches!(e.key, "t1" | "t2" | "t3" | "t4" | "d"))
+}
+
+/// Derive the Ethereum topics and data of an actor event from its entries.
+pub fn to_topics_and_data(entries: &[EventEntry]) -> anyhow::Result<(Vec<et::H256>, et::Bytes)> {
+    if is_evm_event(entries) {
+        evm_topics_and_data(entries)
+    } else {
+        Ok(native_topics_and_data(entries))
+    }
+}
+
+// Based on https://github.com/filecoin-project/lotus/blob/6cc506f5cf751215be6badc94a960251c6453202/node/impl/full/eth.go#L1534
+fn evm_topics_and_data(entries: &[EventEntry]) -> anyhow::Result<(Vec<et::H256>, et::Bytes)> {
+    let mut topics = Vec::new();
+    let mut data = None;
+    for entry in entries {
+        match entry.key {
+            "t1" | "t2" | "t3" | "t4" => {
+                if entry.value.len() != 32 {
+                    return Err(anyhow!("unexpected topic value: {entry:?}"));
+                }
+                let h = et::H256::from_slice(entry.value);
+                let i = entry.key[1..].parse::<usize>().unwrap().saturating_sub(1);
+                while topics.len() <= i {
+                    topics.push(et::H256::default())
+                }
+                topics[i] = h;
+            }
+            "d" => data = Some(et::Bytes::from(entry.value.to_vec())),
+            _ => {}
+        }
+    }
+    Ok((topics, data.unwrap_or_default()))
+}
+
+fn native_topics_and_data(entries: &[EventEntry]) -> (Vec<et::H256>, et::Bytes) {
+    let mut topics = vec![et::H256::from(keccak256(signature(entries)))];
+
+    topics.extend(
+        entries
+            .iter()
+            .filter(|e| e.indexed)
+            .take(MAX_TOPICS - 1)
+            .map(|e| to_topic(e.value)),
+    );
+
+    let values = entries
+        .iter()
+        .map(|e| Token::Bytes(e.value.to_vec()))
+        .collect();
+
+    let data = abi::encode(&[Token::Array(values)]);
+
+    (topics, et::Bytes::from(data))
+}
+
+/// The signature of a native event, e.g. `governance(proposal_id)`.
+pub fn signature(entries: &[EventEntry]) -> String {
+    match entries.split_first() {
+        None => "()".to_owned(),
+        Some((name, args)) => {
+            let args = args.iter().map(|e| e.key).collect::<Vec<_>>().join(",");
+            format!("{}({args})", name.key)
+        }
+    }
+}
+
+fn to_topic(value: &[u8]) -> et::H256 {
+    if value.len() > 32 {
+        et::H256::from(keccak256(value))
+    } else {
+        let mut bz = [0u8; 32];
+        bz[32 - value.len()..].copy_from_slice(value);
+        et::H256::from(bz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::abi::{self, ParamType, Token};
+    use ethers_core::types as et;
+    use ethers_core::utils::keccak256;
+
+    use super::{signature, to_topics_and_data, EventEntry};
+
+    fn entry<'a>(key: &'a str, value: &'a [u8], indexed: bool) -> EventEntry<'a> {
+        EventEntry {
+            key,
+            value,
+            indexed,
+        }
+    }
+
+    #[test]
+    fn evm_event_topics() {
+        let t1 = [1u8; 32];
+        let t2 = [2u8; 32];
+        let entries = [
+            entry("t2", &t2, true),
+            entry("t1", &t1, true),
+            entry("d", b"data", false),
+        ];
+        let (topics, data) = to_topics_and_data(&entries).unwrap();
+        assert_eq!(topics, vec![et::H256::from(t1), et::H256::from(t2)]);
+        assert_eq!(data.as_ref(), b"data");
+
+        assert!(to_topics_and_data(&[entry("t1", b"short", true)]).is_err());
+    }
+
+    #[test]
+    fn native_event_topics() {
+        let long = [3u8; 40];
+        let id = 5u64.to_be_bytes();
+        let entries = [
+            entry("governance", b"proposal_approved", true),
+            entry("proposal_id", &id, true),
+            entry("note", b"not indexed", false),
+            entry("hash", &long, true),
+            entry("extra", b"over the limit", true),
+        ];
+
+        assert_eq!(
+            signature(&entries),
+            "governance(proposal_id,note,hash,extra)"
+        );
+
+        let (topics, data) = to_topics_and_data(&entries).unwrap();
+
+        let mut name = [0u8; 32];
+        name[32 - 17..].copy_from_slice(b"proposal_approved");
+
+        assert_eq!(
+            topics,
+            vec![
+                et::H256::from(keccak256("governance(proposal_id,note,hash,extra)")),
+                et::H256::from(name),
+                et::H256::from_low_u64_be(5),
+                et::H256::from(keccak256(long)),
+            ]
+        );
+
+        let decoded = abi::decode(&[ParamType::Array(Box::new(ParamType::Bytes))], &data).unwrap();
+        let values = entries
+            .iter()
+            .map(|e| Token::Bytes(e.value.to_vec()))
+            .collect();
+        assert_eq!(decoded, vec![Token::Array(values)]);
+    }
+}
@@ -1,16 +1,23 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 use cid::Cid;
-use fvm_ipld_encoding::RawBytes;
+use ethers_core::types as et;
+use ethers_core::utils::keccak256;
+use fendermint_vm_actor_interface::validators::{self, ValidatorEntry};
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::{strict_bytes, RawBytes};
 use fvm_shared::{
     address::Address, econ::TokenAmount, error::ExitCode, message::Message as FvmMessage,
     version::NetworkVersion,
 };
+use ipc_sdk::cross::CrossMsg;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use fendermint_vm_encoding::IsHumanReadable;
 
+use crate::block_hash::EthBlockHashVersion;
+
 /// Height at which to run a query.
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
 pub enum FvmQueryHeight {
@@ -74,6 +81,283 @@ pub enum FvmQuery {
     EstimateGas(Box<FvmMessage>),
     /// Retrieve the slowly changing state parameters that aren't part of the state tree.
     StateParams,
+    /// Ask the node what it supports, so clients can adapt to older versions.
+    ///
+    /// Nodes which don't know this query fail to decode it, which tells the client it's talking to an old version.
+    /// It's answered by the application itself, without looking at the state, so it works before the genesis too.
+    Capabilities,
+    /// Look up a bottom-up checkpoint which has been pruned from the gateway, by its height.
+    ///
+    /// The response is the IPLD encoded `ArchivedCheckpoint`, read from the archive file
+    /// the node exported it to, which is checked against the archive root in the state.
+    ArchivedCheckpoint(u64),
+    /// Estimate the gas required to execute a message, like [`FvmQuery::EstimateGas`],
+    /// and list the actors it calls, to facilitate `eth_createAccessList`.
+    AccessList(Box<FvmMessage>),
+    /// Apply staking changes made on the parent to the validators in the gateway without
+    /// committing them, to see the power table they would result in.
+    ///
+    /// The response is the IPLD encoded `StakingSimulation`.
+    SimulateStakingChanges(Box<StakingSimulationParams>),
+    /// Get the receipt of a transaction, with the Merkle proof that it's in the execution digests
+    /// recorded for its block; the parameters are the height of the block and the index of the
+    /// transaction in it.
+    ///
+    /// The response is the IPLD encoded `ReceiptProof`, if the chain records execution digests
+    /// and the block is still within their lookback window.
+    ReceiptProof(u64, u64),
+    /// List the top-down messages which failed to be applied.
+    ///
+    /// The response is the IPLD encoded list of `DeadLetter`s, in the order they arrived.
+    DeadLetters,
+}
+
+/// ABCI query path to ask about the progress of the snapshot download during state sync,
+/// e.g. `curl 'localhost:26657/abci_query?path="/state_sync/status"'`
+pub const STATE_SYNC_STATUS_PATH: &str = "/state_sync/status";
+
+/// ABCI query path to get the logs blooms of a range of blocks, so that `eth_getLogs` can skip
+/// the blocks which cannot contain matching logs without fetching their results.
+///
+/// The data is the IPLD encoded inclusive `(from, to)` height range; the value is an IPLD encoded
+/// list of `(height, bloom)` pairs for the heights which have a bloom, for at most
+/// [`MAX_LOGS_BLOOM_RANGE`] heights starting from `from`.
+///
+/// Each address appearing in a transaction (sender, recipient, and event emitters by both
+/// their ID and delegated address) is added to the bloom in its binary form, and so is each
+/// event topic, see [`accrue_bloom`].
+pub const LOGS_BLOOM_PATH: &str = "/logs_bloom";
+
+/// ABCI query path to get the gas used by recent blocks by class of message and by callee actor.
+///
+/// Only the `/rpc` route of the admin endpoint answers it, e.g. with a JSON-RPC request like
+/// `{"jsonrpc": "2.0", "id": 0, "method": "abci_query", "params": {"path": "/gas_stats"}}`.
+///
+/// The data is optional; if given, it's a JSON object with the number of `blocks` to aggregate
+/// and the number of `top` actors to list. The value is the JSON encoded report.
+pub const GAS_STATS_PATH: &str = "/gas_stats";
+
+/// ABCI query path to list the latest proposals the node voted against, with the reason,
+/// e.g. the index of a transaction which cannot be included.
+///
+/// Only the `/rpc` route of the admin endpoint answers it; the value is a JSON list, the latest first.
+pub const REJECTED_PROPOSALS_PATH: &str = "/rejected_proposals";
+
+/// ABCI query path to get the times a cross-subnet message reached each stage on its way,
+/// to find out where a message got stuck, or how long it took to get through.
+///
+/// Only the `/rpc` route of the admin endpoint answers it. The data is a JSON object with the
+/// `direction`, either `top_down` or `bottom_up`, and the `nonce` of the message; the value is
+/// the JSON encoded trace, or `null` if the node hasn't seen the message or no longer has it.
+pub const MSG_TRACE_PATH: &str = "/msg_trace";
+
+/// ABCI query path to get the power table which was in effect at a height, so that light clients
+/// and auditors can check old checkpoint signatures against the membership at the time, even
+/// after the state of that height has been pruned.
+///
+/// The data is the IPLD encoded height, with 0 meaning the latest; the value is the IPLD encoded
+/// `Option<ValidatorSet>` recorded at the last change of the power table at or before that height.
+///
+/// Nodes only have the history if they executed every block since the genesis.
+pub const VALIDATOR_SET_PATH: &str = "/validator_set";
+
+/// Path of the ABCI query for the components of the app hash at a height, to find out which part
+/// of the state differs when validators diverge.
+///
+/// The data is the IPLD encoded height, with 0 meaning the latest; the value is the IPLD encoded
+/// `Option<AppHashReport>`, which is `None` if the node no longer has the state of that height.
+pub const APP_HASH_PATH: &str = "/app_hash";
+
+/// ABCI query path to get the parent finality committed in the state at a height.
+///
+/// The data is the IPLD encoded height, with 0 meaning the latest; the value is the IPLD encoded
+/// `Option<ParentFinality>`, which is `None` if the subnet doesn't have IPC enabled.
+pub const PARENT_FINALITY_PATH: &str = "/parent_finality";
+
+/// ABCI query path to get the top-down messages the parent sent to this subnet in a range
+/// of parent heights, so that bridges can follow cross-chain transfers.
+///
+/// The data is the IPLD encoded inclusive `(from, to)` parent height range; the value is an IPLD
+/// encoded list of [`TopDownMessage`]s, in the order of their nonces. The range is cut short at
+/// the latest committed parent finality, and at [`MAX_TOPDOWN_MSGS_RANGE`] heights starting from `from`.
+pub const TOPDOWN_MSGS_PATH: &str = "/topdown_msgs";
+
+/// ABCI query path to look up the height of a committed block by its hash, so that blocks can be
+/// found by hash without scanning; nodes without [`feature::ETH_BLOCK_HASH_V1`] expose the same
+/// hash in the Ethereum API as CometBFT.
+///
+/// The data is the 32 byte hash; the value is the IPLD encoded `(Option<u64>, Option<u64>)` pair of
+/// the height of the block and the first height in the index. The height is `None` if the node
+/// hasn't executed the block; if the index doesn't start at 1 because the node restored its state
+/// from a snapshot, that doesn't mean the block doesn't exist.
+pub const BLOCK_HEIGHT_BY_HASH_PATH: &str = "/block_height_by_hash";
+
+/// ABCI query path to look up the height of a committed block by the hash the Ethereum API exposes,
+/// derived with [`crate::block_hash::EthBlockHashVersion::V1`] on chains which chose it in the genesis.
+///
+/// The data and the value are the same as with [`BLOCK_HEIGHT_BY_HASH_PATH`]. Nodes backfill the
+/// hashes of the blocks CometBFT has from before their first executed block, so the first height
+/// is only later than 1 if CometBFT itself doesn't have the earlier blocks.
+pub const ETH_BLOCK_HEIGHT_BY_HASH_PATH: &str = "/eth_block_height_by_hash";
+
+/// Maximum number of heights to return logs blooms for in a single query.
+pub const MAX_LOGS_BLOOM_RANGE: u64 = 10_000;
+
+/// Maximum number of parent heights to return top-down messages for in a single query;
+/// the messages might have to be fetched from the parent.
+pub const MAX_TOPDOWN_MSGS_RANGE: u64 = 100;
+
+/// Add an input to an Ethereum style bloom filter: 3 bits are set, each chosen by a pair of bytes
+/// from the Keccak-256 hash of the input.
+pub fn accrue_bloom(bloom: &mut et::Bloom, input: &[u8]) {
+    for (i, mask) in bloom_bits(input) {
+        bloom.0[i] |= mask;
+    }
+}
+
+/// Check whether an input might have been added to the bloom filter.
+pub fn bloom_contains(bloom: &et::Bloom, input: &[u8]) -> bool {
+    bloom_bits(input)
+        .into_iter()
+        .all(|(i, mask)| bloom.0[i] & mask == mask)
+}
+
+fn bloom_bits(input: &[u8]) -> [(usize, u8); 3] {
+    let hash = keccak256(input);
+    let len = et::Bloom::len_bytes();
+    [0, 1, 2].map(|i| {
+        let bit = ((hash[2 * i] as usize) << 8 | hash[2 * i + 1] as usize) % (len * 8);
+        (len - 1 - bit / 8, 1 << (bit % 8))
+    })
+}
+
+/// The power table after the changes made by a block.
+///
+/// The changes are returned to CometBFT at the end of the block at `height`,
+/// which means they take effect from the block at `height + 2`.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorSet {
+    /// Height of the block which changed the power table, or of the genesis.
+    pub height: u64,
+    /// Height of the previous change, if there was any.
+    pub prev_height: Option<u64>,
+    /// Validators with non-zero power, in the order they joined.
+    pub validators: Vec<ValidatorEntry>,
+}
+
+impl ValidatorSet {
+    /// The power table at genesis, given as `(public_key, power)` pairs.
+    pub fn genesis(height: u64, validators: impl IntoIterator<Item = (Vec<u8>, u64)>) -> Self {
+        let set = Self {
+            height,
+            prev_height: None,
+            validators: Vec::new(),
+        };
+        Self {
+            prev_height: None,
+            ..set.apply(height, validators)
+        }
+    }
+
+    /// The power table after applying the `(public_key, power)` updates of a block to this one.
+    pub fn apply(&self, height: u64, updates: impl IntoIterator<Item = (Vec<u8>, u64)>) -> Self {
+        let mut book = validators::State {
+            validators: self.validators.clone(),
+        };
+        for (public_key, power) in updates {
+            book.update(height as i64, public_key, power);
+        }
+        book.validators.retain(|v| v.power > 0);
+
+        Self {
+            height,
+            prev_height: Some(self.height),
+            validators: book.validators,
+        }
+    }
+}
+
+/// The values which go into the app hash of the state committed at a height.
+///
+/// The app hash is the CID of the CBOR encoded `FvmStateParams`, a map keyed by the field
+/// names of the components, so the order they are listed in doesn't matter.
+/// The indexes kept by the application, such as the logs blooms or the gas statistics,
+/// live outside the state and don't contribute to it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AppHashReport {
+    /// Height of the block which committed the state; its app hash is in the header of the next block.
+    pub height: u64,
+    /// Hex encoded app hash, the way CometBFT displays it.
+    pub app_hash: String,
+    pub components: Vec<AppHashComponent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AppHashComponent {
+    pub name: String,
+    /// Human readable value; token amounts are in atto.
+    pub value: String,
+}
+
+/// A message sent by the parent to this subnet, as returned by the [`TOPDOWN_MSGS_PATH`] query.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug, Clone, PartialEq, Eq)]
+pub struct TopDownMessage {
+    /// Nonce of the message in the gateway of the parent, which determines the order of execution.
+    pub nonce: u64,
+    /// The sender in the parent, unless it's not a valid IPC address.
+    pub from: Option<Address>,
+    /// The recipient in this subnet, unless it's not a valid IPC address.
+    pub to: Option<Address>,
+    pub method_num: u64,
+    pub params: RawBytes,
+    pub value: TokenAmount,
+}
+
+impl From<&CrossMsg> for TopDownMessage {
+    fn from(msg: &CrossMsg) -> Self {
+        Self {
+            nonce: msg.msg.nonce,
+            from: msg.msg.from.raw_addr().ok(),
+            to: msg.msg.to.raw_addr().ok(),
+            method_num: msg.msg.method,
+            params: msg.msg.params.clone(),
+            value: msg.msg.value.clone(),
+        }
+    }
+}
+
+/// Version of the [`ChainMessage`](crate::chain::ChainMessage) format.
+pub const CHAIN_MESSAGE_VERSION: u64 = 1;
+
+/// Names of optional features a node can report in its [`Capabilities`].
+pub mod feature {
+    /// The node has snapshots enabled, which it can offer to peers for state sync.
+    pub const SNAPSHOTS: &str = "snapshots";
+    /// The progress of the state sync can be queried at [`super::STATE_SYNC_STATUS_PATH`].
+    pub const STATE_SYNC_STATUS: &str = "state_sync_status";
+    /// Traces are exported to an OpenTelemetry collector.
+    pub const TRACING: &str = "tracing";
+    /// Failed top-down messages are kept in a dead-letter queue, which can be listed
+    /// with [`super::FvmQuery::DeadLetters`].
+    pub const DEAD_LETTERS: &str = "dead_letters";
+    /// The logs blooms of blocks can be queried at [`super::LOGS_BLOOM_PATH`].
+    pub const LOGS_BLOOM: &str = "logs_bloom";
+    /// The heights of blocks can be looked up by hash at [`super::BLOCK_HEIGHT_BY_HASH_PATH`].
+    pub const BLOCK_HASH_INDEX: &str = "block_hash_index";
+    /// The chain derives the Ethereum block hashes with [`crate::block_hash::EthBlockHashVersion::V1`],
+    /// as chosen in its genesis, and the heights of blocks can be looked up by them at [`super::ETH_BLOCK_HEIGHT_BY_HASH_PATH`].
+    pub const ETH_BLOCK_HASH_V1: &str = "eth_block_hash_v1";
+    /// Pruned bottom-up checkpoints can be looked up with [`super::FvmQuery::ArchivedCheckpoint`].
+    pub const CHECKPOINT_ARCHIVE: &str = "checkpoint_archive";
+    /// The actors called by a message can be listed with [`super::FvmQuery::AccessList`].
+    pub const ACCESS_LIST: &str = "access_list";
+    /// Staking changes can be simulated with [`super::FvmQuery::SimulateStakingChanges`].
+    pub const SIMULATE_STAKING: &str = "simulate_staking";
+    /// The subnet follows its parent, and the top-down messages can be queried at [`super::TOPDOWN_MSGS_PATH`].
+    pub const TOPDOWN: &str = "topdown";
+    /// Receipts can be proven against the execution digests with [`super::FvmQuery::ReceiptProof`],
+    /// if the chain was started with them.
+    pub const EXEC_DIGESTS: &str = "exec_digests";
 }
 
 /// State of all actor implementations.
@@ -120,6 +404,80 @@ pub struct GasEstimate {
     pub gas_limit: u64,
 }
 
+/// Result of gas estimation, with the actors called by the message.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct AccessList {
+    pub estimate: GasEstimate,
+    /// The actors accessed during the execution, in the order they were first called,
+    /// apart from the sender, and the recipient unless its storage was accessed.
+    ///
+    /// Empty if the estimation failed.
+    pub actors: Vec<AccessedActor>,
+}
+
+/// An actor accessed by a message, with the storage slots it read or wrote.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct AccessedActor {
+    /// The delegated address of the actor where it has one, otherwise its ID address.
+    pub address: Address,
+    /// The keys of the storage slots of an EVM contract that were touched by the message.
+    ///
+    /// This can include neighbouring slots which are stored in the same node of the
+    /// contract storage, which only makes the access list a bit more generous.
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+/// What a staking change does to a validator.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum StakingOperation {
+    /// Add collateral.
+    Deposit(TokenAmount),
+    /// Take out collateral.
+    Withdraw(TokenAmount),
+    /// Set the metadata of the validator, which is its uncompressed public key.
+    SetMetadata(RawBytes),
+}
+
+/// A staking change made on the parent, which reaches the subnet with the parent finality.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct StakingChange {
+    /// Configuration number of the change in the subnet actor on the parent.
+    pub configuration_number: u64,
+    pub validator: Address,
+    pub operation: StakingOperation,
+}
+
+/// Staking changes to simulate, and the constraints to check the resulting power table against.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct StakingSimulationParams {
+    pub changes: Vec<StakingChange>,
+    /// Maximum number of validators; 0 means it's not checked.
+    pub max_validators: u16,
+    /// Minimum power every validator needs to have in the CometBFT scale.
+    pub min_power: u64,
+}
+
+/// A validator in the simulated power table.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SimulatedValidator {
+    /// Uncompressed secp256k1 public key.
+    #[serde(with = "strict_bytes")]
+    pub public_key: Vec<u8>,
+    /// Power in the CometBFT scale.
+    pub power: u64,
+}
+
+/// The power table the staking changes would result in, without them being applied.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct StakingSimulation {
+    /// Configuration number of the power table after the changes.
+    pub configuration_number: u64,
+    pub validators: Vec<SimulatedValidator>,
+    pub total_power: u64,
+    /// Constraints the power table would violate; if empty, the changes are safe to make.
+    pub violations: Vec<String>,
+}
+
 /// Slowly changing state parameters outside the state tree.
 #[serde_as]
 #[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
@@ -142,6 +500,38 @@ pub struct StateParams {
     pub network_version: NetworkVersion,
 }
 
+/// Versions and optional features of a node.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Version of the application, as in the ABCI `Info` response.
+    pub app_version: u64,
+    /// Version of the node software.
+    pub version: String,
+    /// Optional features enabled on the node, see [`feature`].
+    ///
+    /// They are strings rather than an enum so that clients don't fail on features added later.
+    pub features: Vec<String>,
+    /// Number of recent blocks whose state can be queried; 0 means unlimited.
+    pub state_hist_size: u64,
+    /// Versions of the transaction format the node accepts.
+    pub message_versions: Vec<u64>,
+}
+
+impl Capabilities {
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// The latest derivation of the Ethereum block hashes the node supports.
+    pub fn eth_block_hash_version(&self) -> EthBlockHashVersion {
+        if self.has_feature(feature::ETH_BLOCK_HASH_V1) {
+            EthBlockHashVersion::V1
+        } else {
+            EthBlockHashVersion::V0
+        }
+    }
+}
+
 #[cfg(feature = "arb")]
 mod arb {
     use fendermint_testing::arb::{ArbAddress, ArbCid, ArbTokenAmount};
@@ -152,12 +542,17 @@ mod arb {
 
     impl quickcheck::Arbitrary for FvmQuery {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            match u8::arbitrary(g) % 5 {
+            match u8::arbitrary(g) % 10 {
                 0 => FvmQuery::Ipld(ArbCid::arbitrary(g).0),
                 1 => FvmQuery::ActorState(ArbAddress::arbitrary(g).0),
                 2 => FvmQuery::Call(Box::new(SignedMessage::arbitrary(g).into_message())),
                 3 => FvmQuery::EstimateGas(Box::new(SignedMessage::arbitrary(g).into_message())),
-                _ => FvmQuery::StateParams,
+                4 => FvmQuery::StateParams,
+                5 => FvmQuery::ArchivedCheckpoint(u64::arbitrary(g)),
+                6 => FvmQuery::AccessList(Box::new(SignedMessage::arbitrary(g).into_message())),
+                7 => FvmQuery::ReceiptProof(u64::arbitrary(g), u64::arbitrary(g)),
+                8 => FvmQuery::DeadLetters,
+                _ => FvmQuery::Capabilities,
             }
         }
     }
@@ -174,3 +569,43 @@ mod arb {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::types as et;
+
+    use super::{accrue_bloom, bloom_contains, ValidatorSet};
+
+    #[test]
+    fn bloom_is_compatible_with_ethereum() {
+        let input = b"fendermint";
+
+        let mut bloom = et::Bloom::zero();
+        accrue_bloom(&mut bloom, input);
+
+        let mut expected = et::Bloom::zero();
+        expected.accrue(ethers_core::abi::ethereum_types::BloomInput::Raw(input));
+
+        assert_eq!(bloom, expected);
+        assert!(bloom_contains(&bloom, input));
+        assert!(!bloom_contains(&bloom, b"lotus"));
+    }
+
+    #[test]
+    fn validator_set_apply_updates() {
+        let genesis = ValidatorSet::genesis(1, [(vec![1], 10), (vec![2], 20), (vec![3], 0)]);
+        assert_eq!(genesis.prev_height, None);
+        assert_eq!(genesis.validators.len(), 2);
+
+        let set = genesis.apply(5, [(vec![1], 0), (vec![2], 25), (vec![4], 40)]);
+        assert_eq!(set.height, 5);
+        assert_eq!(set.prev_height, Some(1));
+
+        let powers = set
+            .validators
+            .iter()
+            .map(|v| (v.public_key.clone(), v.power, v.joined_at, v.updated_at))
+            .collect::<Vec<_>>();
+        assert_eq!(powers, vec![(vec![2], 25, 1, 5), (vec![4], 40, 5, 5)]);
+    }
+}
@@ -183,6 +183,24 @@ impl SignedMessage {
         Self::verify_signature(&self.message, &self.signature, chain_id)
     }
 
+    /// Verifies that the from address signed the CID of the message alone, which is
+    /// how Filecoin wallets such as Lotus sign messages.
+    ///
+    /// Unlike [`SignedMessage::verify`], this has no replay protection: a message signed
+    /// for another chain is valid here as well, if the nonce of the sender matches.
+    pub fn verify_filecoin(&self) -> Result<(), SignedMessageError> {
+        if maybe_eth_address(&self.message.from).is_some() {
+            return Err(SignedMessageError::InvalidSignature(
+                "Ethereum accounts have to use the Ethereum signature scheme".into(),
+            ));
+        }
+        let data = Self::cid(&self.message)?.to_bytes();
+
+        self.signature
+            .verify(&data, &self.message.from)
+            .map_err(SignedMessageError::InvalidSignature)
+    }
+
     /// Returns reference to the unsigned message.
     pub fn message(&self) -> &Message {
         &self.message
@@ -309,7 +327,7 @@ mod tests {
 
     use crate::conv::tests::{EthMessage, KeyPair};
 
-    use super::SignedMessage;
+    use super::{sign_regular, SignedMessage};
 
     #[quickcheck]
     fn chain_id_in_signature(
@@ -339,6 +357,37 @@ mod tests {
         Ok(())
     }
 
+    #[quickcheck]
+    fn filecoin_signature(msg: SignedMessage, chain_id: u64, key: KeyPair) -> Result<(), String> {
+        let KeyPair { sk, pk } = key;
+        let chain_id = ChainID::from(chain_id);
+
+        let mut msg = msg.into_message();
+        msg.from = Address::new_secp256k1(&pk.serialize())
+            .map_err(|e| format!("failed to conver to address: {e}"))?;
+
+        // Lotus signs the CID of the message, without the chain ID.
+        let cid = SignedMessage::cid(&msg).map_err(|e| e.to_string())?;
+        let signature = sign_regular(&sk, &cid.to_bytes());
+        let signed = SignedMessage::new_unchecked(msg, signature);
+
+        signed
+            .verify_filecoin()
+            .map_err(|e| format!("verifying failed: {e}"))?;
+
+        if signed.verify(&chain_id).is_ok() {
+            return Err("verifying with the chain ID should fail".into());
+        }
+
+        let protected = SignedMessage::new_secp256k1(signed.into_message(), &sk, &chain_id)
+            .map_err(|e| format!("signing failed: {e}"))?;
+
+        if protected.verify_filecoin().is_ok() {
+            return Err("verifying a signature with the chain ID should fail".into());
+        }
+        Ok(())
+    }
+
     #[quickcheck]
     fn eth_sign_and_verify(msg: EthMessage, chain_id: u64, key: KeyPair) -> Result<(), String> {
         let chain_id = ChainID::from(chain_id);
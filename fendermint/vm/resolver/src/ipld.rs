@@ -36,13 +36,20 @@ impl IpldResolver {
     /// Start taking tasks from the resolver pool and resolving them using the IPLD Resolver.
     pub async fn run(self) {
         loop {
-            let (task, use_own_subnet) = atomically(|| {
+            let (task, use_own_subnet, is_cancelled) = atomically(|| {
                 let task = self.queue.read()?;
                 let use_own_subnet = task.use_own_subnet()?;
-                Ok((task, use_own_subnet))
+                let is_cancelled = task.is_cancelled()?;
+                Ok((task, use_own_subnet, is_cancelled))
             })
             .await;
 
+            // The item expired in the pool while it was waiting to be retried.
+            if is_cancelled {
+                tracing::debug!(cid = ?task.cid(), "content resolution cancelled");
+                continue;
+            }
+
             start_resolve(
                 task,
                 self.client.clone(),
@@ -1,7 +1,11 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{collections::HashSet, hash::Hash};
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    time::{Duration, Instant},
+};
 
 use async_stm::{
     queues::{tchan::TChan, TQueueLike},
@@ -28,6 +32,10 @@ pub struct ResolveStatus<T> {
     use_own_subnet: TVar<bool>,
     /// The collection of items that all resolve to the same root CID and subnet.
     items: TVar<im::HashSet<T>>,
+    /// Indicate whether the item has been dropped from the pool and resolution should stop.
+    is_cancelled: TVar<bool>,
+    /// When the item was first added, to drop it if it cannot be resolved in time.
+    added_at: Instant,
 }
 
 impl<T> ResolveStatus<T>
@@ -41,12 +49,19 @@ where
             is_resolved: TVar::new(false),
             use_own_subnet: TVar::new(use_own_subnet),
             items: TVar::new(items),
+            is_cancelled: TVar::new(false),
+            added_at: Instant::now(),
         }
     }
 
     pub fn is_resolved(&self) -> Stm<bool> {
         self.is_resolved.read_clone()
     }
+
+    fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        ttl.map(|ttl| self.added_at.elapsed() > ttl)
+            .unwrap_or_default()
+    }
 }
 
 /// Tasks emitted by the pool for background resolution.
@@ -59,6 +74,8 @@ pub struct ResolveTask {
     /// Flag to flip if consensus reached a state on its own
     /// where the majority of our own peers should have an item.
     use_own_subnet: TVar<bool>,
+    /// Flag flipped by the pool if the item expired before it could be resolved.
+    is_cancelled: TVar<bool>,
 }
 
 impl ResolveTask {
@@ -77,10 +94,44 @@ impl ResolveTask {
     pub fn use_own_subnet(&self) -> Stm<bool> {
         self.use_own_subnet.read_clone()
     }
+
+    /// Check whether the item has been dropped from the pool, in which case there is no point retrying.
+    pub fn is_cancelled(&self) -> Stm<bool> {
+        self.is_cancelled.read_clone()
+    }
 }
 
 pub type ResolveQueue = TChan<ResolveTask>;
 
+/// Limits to apply backpressure on the resolution of the items in the pool.
+#[derive(Clone, Copy, Debug)]
+pub struct ResolveLimits {
+    /// Maximum number of items handed to the resolver at the same time;
+    /// further items wait in the pool until earlier ones are resolved or dropped.
+    pub max_in_flight: usize,
+    /// Time after which items that haven't been resolved are dropped from the pool,
+    /// so they are never proposed; `None` means they are kept until resolved.
+    pub ttl: Option<Duration>,
+}
+
+impl Default for ResolveLimits {
+    fn default() -> Self {
+        Self {
+            max_in_flight: usize::MAX,
+            ttl: None,
+        }
+    }
+}
+
+/// Outcome of [ResolvePool::maintain].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResolveMaintenance {
+    /// Number of unresolved items dropped because they expired.
+    pub expired: usize,
+    /// Number of waiting items handed to the resolver.
+    pub released: usize,
+}
+
 /// A data structure used to communicate resolution requirements and outcomes
 /// between the resolver running in the background and the application waiting
 /// for the results.
@@ -103,6 +154,9 @@ where
     items: TVar<im::HashMap<ResolveKey, ResolveStatus<T>>>,
     /// Items queued for resolution.
     queue: ResolveQueue,
+    /// Tasks held back until there is room for them in the queue.
+    waiting: TVar<im::Vector<ResolveTask>>,
+    limits: ResolveLimits,
 }
 
 impl<T> ResolvePool<T>
@@ -111,9 +165,15 @@ where
     T: Sync + Send + Clone + Hash + Eq + PartialEq + 'static,
 {
     pub fn new() -> Self {
+        Self::with_limits(Default::default())
+    }
+
+    pub fn with_limits(limits: ResolveLimits) -> Self {
         Self {
             items: Default::default(),
             queue: Default::default(),
+            waiting: Default::default(),
+            limits,
         }
     }
 
@@ -127,6 +187,7 @@ where
     /// Add an item to the resolution targets.
     ///
     /// If the item is new, enqueue it from background resolution, otherwise just return its existing status.
+    /// If there are too many items being resolved already, the new one waits for its turn.
     pub fn add(&self, item: T, use_own_subnet: bool) -> Stm<ResolveStatus<T>> {
         let key = ResolveKey::from(&item);
         let mut items = self.items.read_clone()?;
@@ -142,11 +203,17 @@ where
             let status = ResolveStatus::new(item, use_own_subnet);
             items.insert(key.clone(), status.clone());
             self.items.write(items)?;
-            self.queue.write(ResolveTask {
+            let task = ResolveTask {
                 key,
                 is_resolved: status.is_resolved.clone(),
                 use_own_subnet: status.use_own_subnet.clone(),
-            })?;
+                is_cancelled: status.is_cancelled.clone(),
+            };
+            if self.in_flight()? < self.limits.max_in_flight {
+                self.queue.write(task)?;
+            } else {
+                self.waiting.update_mut(|waiting| waiting.push_back(task))?;
+            }
             Ok(status)
         }
     }
@@ -177,11 +244,71 @@ where
         self.queue.read()
     }
 
+    /// Drop the items which couldn't be resolved before they expired, and hand
+    /// waiting items to the resolver for the ones which have been completed.
+    ///
+    /// Expired items are never proposed, and blocks proposing them are rejected,
+    /// unless they are added again.
+    pub fn maintain(&self) -> Stm<ResolveMaintenance> {
+        let mut expired = 0;
+        let mut items = self.items.read_clone()?;
+        for (key, status) in items.clone().iter() {
+            if !status.is_resolved()? && status.is_expired(self.limits.ttl) {
+                status.is_cancelled.write(true)?;
+                items.remove(key);
+                expired += 1;
+            }
+        }
+        if expired > 0 {
+            self.items.write(items)?;
+            let mut waiting = im::Vector::new();
+            for task in self.waiting.read_clone()? {
+                if !task.is_cancelled()? {
+                    waiting.push_back(task);
+                }
+            }
+            self.waiting.write(waiting)?;
+        }
+
+        let mut released = 0;
+        let mut in_flight = self.in_flight()?;
+        let mut waiting = self.waiting.read_clone()?;
+        while in_flight < self.limits.max_in_flight {
+            match waiting.pop_front() {
+                Some(task) => {
+                    self.queue.write(task)?;
+                    in_flight += 1;
+                    released += 1;
+                }
+                None => break,
+            }
+        }
+        if released > 0 {
+            self.waiting.write(waiting)?;
+        }
+
+        Ok(ResolveMaintenance { expired, released })
+    }
+
+    /// Number of unresolved items which have been handed to the resolver.
+    fn in_flight(&self) -> Stm<usize> {
+        let mut unresolved = 0;
+        for status in self.items.read()?.values() {
+            if !status.is_resolved()? {
+                unresolved += 1;
+            }
+        }
+        let waiting = self.waiting.read()?.len();
+        Ok(unresolved.saturating_sub(waiting))
+    }
+
     // TODO #197: Implement methods to remove executed items.
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use async_stm::{atomically, queues::TQueueLike};
     use cid::Cid;
     use ipc_sdk::subnet_id::SubnetID;
@@ -207,7 +334,7 @@ mod tests {
         }
     }
 
-    use super::{ResolveKey, ResolvePool};
+    use super::{ResolveKey, ResolveLimits, ResolveMaintenance, ResolvePool};
 
     #[tokio::test]
     async fn add_new_item() {
@@ -299,4 +426,73 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn hold_back_beyond_limit() {
+        let pool = ResolvePool::with_limits(ResolveLimits {
+            max_in_flight: 1,
+            ttl: None,
+        });
+        let item1 = TestItem::dummy(0);
+        let item2 = TestItem::dummy(1);
+
+        atomically(|| {
+            pool.add(item1.clone(), false)?;
+            pool.add(item2.clone(), false)?;
+            Ok(())
+        })
+        .await;
+
+        // Only the first one is handed to the resolver; nothing changes until it's done.
+        let task = atomically(|| {
+            let task = pool.queue.read()?;
+            assert!(pool.queue.is_empty()?);
+            assert_eq!(pool.maintain()?, ResolveMaintenance::default());
+            Ok(task)
+        })
+        .await;
+
+        assert_eq!(task.key, ResolveKey::from(&item1));
+
+        atomically(|| {
+            task.set_resolved()?;
+            let m = pool.maintain()?;
+            assert_eq!(m.released, 1);
+            assert_eq!(pool.queue.read()?.key, ResolveKey::from(&item2));
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn drop_expired() {
+        let pool = ResolvePool::with_limits(ResolveLimits {
+            max_in_flight: 1,
+            ttl: Some(Duration::ZERO),
+        });
+        let item1 = TestItem::dummy(0);
+        let item2 = TestItem::dummy(1);
+
+        atomically(|| {
+            pool.add(item1.clone(), false)?;
+            pool.add(item2.clone(), false)?;
+            Ok(())
+        })
+        .await;
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        atomically(|| {
+            let task = pool.queue.read()?;
+            let m = pool.maintain()?;
+            assert_eq!(m.expired, 2);
+            // The waiting item expired as well, so there is nothing to release.
+            assert_eq!(m.released, 0);
+            assert!(task.is_cancelled()?);
+            assert!(pool.get_status(&item1)?.is_none());
+            assert!(pool.queue.is_empty()?);
+            Ok(())
+        })
+        .await;
+    }
 }
@@ -15,6 +15,9 @@ use self::{chunker::ChunkWriter, streamer::BlockStreamer};
 
 mod chunker;
 mod streamer;
+mod verifier;
+
+pub use verifier::{verify_block, CarVerifier, CarVerifyError};
 
 /// Take an existing CAR file and split it up into an output directory by creating
 /// files with a limited size for each file.
@@ -0,0 +1,267 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::io::Cursor;
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_car::CarHeader;
+
+/// Incrementally parse a CAR file as it arrives in chunks and check that
+/// the content of every block hashes to its CID.
+///
+/// Chunks are split at arbitrary byte offsets, so a block can span several
+/// of them; the bytes of an incomplete block are buffered until the rest
+/// arrives. This allows us to reject a corrupted download as soon as we
+/// see the first bad block, rather than after importing the whole snapshot.
+#[derive(Debug, Clone)]
+pub struct CarVerifier {
+    /// The expected size of the CAR file; no single section can be larger.
+    total_size: u64,
+    /// Bytes received so far.
+    bytes: u64,
+    /// Number of content blocks received so far.
+    blocks: u64,
+    /// Whether the header has been read already.
+    has_header: bool,
+    /// Bytes of a section which hasn't been fully received yet.
+    pending: Vec<u8>,
+    /// The index of the chunk the first pending byte came from.
+    pending_chunk: u32,
+    /// The roots listed in the header.
+    roots: Vec<Cid>,
+}
+
+/// A problem found while verifying a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarVerifyError {
+    /// Index of the chunk where the offending section started.
+    pub chunk: u32,
+    pub reason: String,
+}
+
+impl CarVerifier {
+    pub fn new(total_size: u64) -> Self {
+        Self {
+            total_size,
+            bytes: 0,
+            blocks: 0,
+            has_header: false,
+            pending: Vec::new(),
+            pending_chunk: 0,
+            roots: Vec::new(),
+        }
+    }
+
+    /// Number of bytes received so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of content blocks which have been received so far.
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The roots listed in the header, once it has been received.
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Append the next chunk and verify all the blocks which have been completed by it.
+    #[cfg(test)]
+    pub fn feed(&mut self, index: u32, contents: &[u8]) -> Result<(), CarVerifyError> {
+        for (chunk, section) in self.split(index, contents)? {
+            verify_block(&section).map_err(|reason| CarVerifyError { chunk, reason })?;
+        }
+        Ok(())
+    }
+
+    /// Append the next chunk and return the sections of all the blocks completed by it,
+    /// along with the index of the chunk each of them started in, without checking their
+    /// contents, so that can be done elsewhere with [verify_block].
+    pub fn split(
+        &mut self,
+        index: u32,
+        contents: &[u8],
+    ) -> Result<Vec<(u32, Vec<u8>)>, CarVerifyError> {
+        let mut blocks = Vec::new();
+        if self.pending.is_empty() {
+            self.pending_chunk = index;
+        }
+        self.pending.extend_from_slice(contents);
+        self.bytes += contents.len() as u64;
+
+        let mut offset = 0;
+        while let Some((prefix, size)) = self.next_section(offset)? {
+            let start = offset + prefix;
+            let end = start + size;
+            let section = &self.pending[start..end];
+
+            if self.has_header {
+                blocks.push((self.pending_chunk, section.to_vec()));
+                self.blocks += 1;
+            } else {
+                self.roots = verify_header(section).map_err(|reason| self.error(reason))?;
+                self.has_header = true;
+            }
+            offset = end;
+            // Whatever is left after a completed section comes from the current chunk.
+            self.pending_chunk = index;
+        }
+        self.pending.drain(..offset);
+
+        Ok(blocks)
+    }
+
+    /// Check that there is nothing left over after the last chunk.
+    pub fn finish(&self) -> Result<(), CarVerifyError> {
+        if !self.has_header {
+            return Err(self.error("missing CAR header".to_owned()));
+        }
+        if !self.pending.is_empty() {
+            return Err(self.error(format!(
+                "truncated CAR file; {} bytes left over",
+                self.pending.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Try to read the length prefix of the next section starting at `offset`.
+    ///
+    /// Returns the length of the prefix and the size of the section,
+    /// or `None` if the section hasn't been fully received yet.
+    fn next_section(&self, offset: usize) -> Result<Option<(usize, usize)>, CarVerifyError> {
+        let buf = &self.pending[offset..];
+        let (size, prefix) = match read_varint(buf) {
+            Ok(Some(x)) => x,
+            Ok(None) => return Ok(None),
+            Err(reason) => return Err(self.error(reason)),
+        };
+        if size == 0 || size > self.total_size {
+            return Err(self.error(format!("invalid section size: {size}")));
+        }
+        let size = size as usize;
+        if buf.len() < prefix + size {
+            return Ok(None);
+        }
+        Ok(Some((prefix, size)))
+    }
+
+    fn error(&self, reason: String) -> CarVerifyError {
+        CarVerifyError {
+            chunk: self.pending_chunk,
+            reason,
+        }
+    }
+}
+
+/// Read an unsigned LEB128 varint.
+///
+/// Returns the value and the number of bytes it took, or `None` if more bytes are needed.
+fn read_varint(buf: &[u8]) -> Result<Option<(u64, usize)>, String> {
+    let mut value: u64 = 0;
+    for (i, b) in buf.iter().enumerate() {
+        if i >= 9 {
+            return Err("varint overflow".to_owned());
+        }
+        value |= ((b & 0x7f) as u64) << (i * 7);
+        if b & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Ok(None)
+}
+
+fn verify_header(section: &[u8]) -> Result<Vec<Cid>, String> {
+    let header: CarHeader = fvm_ipld_encoding::from_slice(section)
+        .map_err(|e| format!("failed to decode CAR header: {e}"))?;
+
+    if header.version != 1 {
+        return Err(format!("unexpected CAR version: {}", header.version));
+    }
+    Ok(header.roots)
+}
+
+/// Check a block section and split it into its CID and contents.
+pub fn verify_block(section: &[u8]) -> Result<(Cid, Vec<u8>), String> {
+    let mut cursor = Cursor::new(section);
+    let cid = Cid::read_bytes(&mut cursor).map_err(|e| format!("failed to read CID: {e}"))?;
+    let data = &section[cursor.position() as usize..];
+
+    let code = Code::try_from(cid.hash().code())
+        .map_err(|e| format!("unsupported hash in CID {cid}: {e}"))?;
+
+    if code.digest(data) != *cid.hash() {
+        return Err(format!("block content does not match CID {cid}"));
+    }
+    Ok((cid, data.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use fendermint_vm_interpreter::fvm::bundle::bundle_path;
+
+    use super::CarVerifier;
+
+    fn bundle_chunks(count: usize) -> Vec<Vec<u8>> {
+        let bytes = std::fs::read(bundle_path()).unwrap();
+        let size = bytes.len() / count + 1;
+        bytes.chunks(size).map(|c| c.to_vec()).collect()
+    }
+
+    #[test]
+    fn verify_bundle_chunks() {
+        let chunks = bundle_chunks(10);
+        let total = chunks.iter().map(|c| c.len() as u64).sum();
+        let mut verifier = CarVerifier::new(total);
+
+        for (i, c) in chunks.iter().enumerate() {
+            verifier.feed(i as u32, c).expect("chunk should verify");
+        }
+        verifier.finish().expect("should be complete");
+
+        assert_eq!(verifier.bytes(), total);
+        assert!(verifier.blocks() > 0);
+    }
+
+    #[test]
+    fn detect_corrupt_chunk() {
+        let mut chunks = bundle_chunks(10);
+        let total = chunks.iter().map(|c| c.len() as u64).sum();
+        let mut verifier = CarVerifier::new(total);
+
+        let corrupt = 5;
+        let mid = chunks[corrupt].len() / 2;
+        chunks[corrupt][mid] ^= 0xff;
+
+        let mut res = Ok(());
+        for (i, c) in chunks.iter().enumerate() {
+            res = verifier.feed(i as u32, c);
+            if res.is_err() {
+                break;
+            }
+        }
+        let err = res
+            .and_then(|()| verifier.finish())
+            .expect_err("corruption should be detected");
+
+        assert!(err.chunk <= corrupt as u32);
+    }
+
+    #[test]
+    fn detect_truncation() {
+        let mut chunks = bundle_chunks(10);
+        let total = chunks.iter().map(|c| c.len() as u64).sum();
+        let mut verifier = CarVerifier::new(total);
+        chunks.last_mut().unwrap().pop();
+
+        for (i, c) in chunks.iter().enumerate() {
+            verifier.feed(i as u32, c).expect("chunk should verify");
+        }
+        assert!(verifier.finish().is_err());
+    }
+}
@@ -1,17 +1,24 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 
-use async_stm::{abort, Stm, StmResult, TVar};
+use async_stm::{atomically, Stm, TVar};
+use cid::Cid;
 use fendermint_vm_interpreter::fvm::state::{
     snapshot::{BlockHeight, SnapshotVersion},
     FvmStateParams,
 };
+use fvm_ipld_blockstore::Blockstore;
 
 use crate::{
     manifest,
-    state::{SnapshotDownload, SnapshotState},
+    restore::RestorePipeline,
+    state::{DownloadProgress, SnapshotDownload, SnapshotState},
     SnapshotError, SnapshotItem, SnapshotManifest, MANIFEST_FILE_NAME,
 };
 
@@ -21,6 +28,8 @@ pub struct SnapshotClient {
     download_dir: PathBuf,
     /// The client will only notify the manager of snapshottable heights.
     snapshot_interval: BlockHeight,
+    /// Number of chunks to restore in parallel.
+    restore_concurrency: usize,
     state: SnapshotState,
 }
 
@@ -28,11 +37,13 @@ impl SnapshotClient {
     pub fn new(
         download_dir: PathBuf,
         snapshot_interval: BlockHeight,
+        restore_concurrency: usize,
         state: SnapshotState,
     ) -> Self {
         Self {
             download_dir,
             snapshot_interval,
+            restore_concurrency,
             state,
         }
     }
@@ -78,106 +89,110 @@ impl SnapshotClient {
     }
 
     /// If the offered snapshot is accepted, we create a temporary directory to hold the chunks
-    /// and remember it as our current snapshot being downloaded.
-    pub fn offer_snapshot(&self, manifest: SnapshotManifest) -> StmResult<PathBuf, SnapshotError> {
-        if manifest.version != 1 {
-            abort(SnapshotError::IncompatibleVersion(manifest.version))
-        } else {
-            match tempfile::tempdir_in(&self.download_dir) {
-                Ok(dir) => {
-                    // Save the manifest into the temp directory;
-                    // that way we can always see on the file system what's happening.
-                    let json = match serde_json::to_string_pretty(&manifest)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
-                    {
-                        Ok(json) => json,
-                        Err(e) => return abort(SnapshotError::from(e)),
-                    };
-
-                    let download_path: PathBuf = dir.path().into();
-                    let download = SnapshotDownload {
-                        manifest,
-                        download_dir: Arc::new(dir),
-                        next_index: TVar::new(0),
-                    };
-
-                    // Create a `parts` sub-directory for the chunks.
-                    if let Err(e) = std::fs::create_dir(download.parts_dir()) {
-                        return abort(SnapshotError::from(e));
-                    };
-
-                    if let Err(e) = std::fs::write(download_path.join(MANIFEST_FILE_NAME), json) {
-                        return abort(SnapshotError::from(e));
-                    }
-
-                    self.state.current_download.write(Some(download))?;
-
-                    Ok(download_path)
-                }
-                Err(e) => abort(SnapshotError::from(e))?,
-            }
+    /// and remember it as our current snapshot being downloaded, then start a pipeline to restore
+    /// the chunks into the blockstore as they arrive.
+    pub async fn offer_snapshot<BS>(
+        &self,
+        manifest: SnapshotManifest,
+        store: BS,
+    ) -> Result<PathBuf, SnapshotError>
+    where
+        BS: Blockstore + Clone + Send + Sync + 'static,
+    {
+        // Only the first version of the contents exists, with or without compression.
+        if manifest.version != manifest.compression.snapshot_version(1) {
+            return Err(SnapshotError::IncompatibleVersion(manifest.version));
+        }
+
+        let dir = tempfile::tempdir_in(&self.download_dir)?;
+
+        // Save the manifest into the temp directory;
+        // that way we can always see on the file system what's happening.
+        let json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        let download_path: PathBuf = dir.path().into();
+        let download = SnapshotDownload {
+            pipeline: RestorePipeline::spawn(store, &manifest, self.restore_concurrency),
+            manifest,
+            download_dir: Arc::new(dir),
+            next_index: TVar::new(0),
+            started_at: Instant::now(),
+        };
+
+        // Create a `parts` sub-directory for the chunks.
+        std::fs::create_dir(download.parts_dir())?;
+        std::fs::write(download_path.join(MANIFEST_FILE_NAME), json)?;
+
+        // Replacing a previous download stops its pipeline.
+        atomically(|| self.state.current_download.write(Some(download.clone()))).await;
+
+        Ok(download_path)
+    }
+
+    /// Report the progress of the ongoing snapshot download, if there is one.
+    pub fn download_progress(&self) -> Stm<Option<DownloadProgress>> {
+        match self.state.current_download.read()?.as_ref() {
+            Some(cd) => cd.progress().map(Some),
+            None => Ok(None),
         }
     }
 
     /// Take a chunk sent to us by a remote peer. This is our chance to validate chunks on the fly.
     ///
-    /// Returns `None` while there are more chunks to download and `Some` when all
-    /// the chunks have been received and basic file integrity validated.
+    /// The chunk is saved to disk and handed over to the restore pipeline, which decompresses
+    /// it, verifies the CID of every CAR block as soon as all of its bytes have arrived, and
+    /// writes the blocks to the blockstore, while CometBFT goes on to fetch the next ones.
+    /// A corrupted download is detected at the first offending chunk, but it might only be
+    /// reported when one of the next chunks is saved, rather than after the whole snapshot
+    /// has been downloaded.
     ///
-    /// Then we can import the snapshot into the blockstore separately.
-    pub fn save_chunk(
+    /// Returns `None` while there are more chunks to download and `Some` when all the chunks
+    /// have been received, restored and basic file integrity validated, along with the CID of
+    /// the snapshot metadata, which can be used to load the snapshot from the blockstore.
+    pub async fn save_chunk(
         &self,
         index: u32,
         contents: Vec<u8>,
-    ) -> StmResult<Option<SnapshotItem>, SnapshotError> {
-        if let Some(cd) = self.state.current_download.read()?.as_ref() {
-            let next_index = cd.next_index.read_clone()?;
-            if index != next_index {
-                abort(SnapshotError::UnexpectedChunk(next_index, index))
-            } else {
-                let part_path = cd.parts_dir().join(format!("{}.part", index));
-
-                // We are doing IO inside the STM transaction, but that's okay because there is no contention on the download.
-                match std::fs::write(part_path, contents) {
-                    Ok(()) => {
-                        let next_index = index + 1;
-                        cd.next_index.write(next_index)?;
-
-                        if next_index == cd.manifest.chunks {
-                            // Verify the checksum then load the snapshot and remove the current download from memory.
-                            match manifest::parts_checksum(cd.parts_dir()) {
-                                Ok(checksum) => {
-                                    if checksum == cd.manifest.checksum {
-                                        let item = SnapshotItem::new(
-                                            cd.download_dir.path().into(),
-                                            cd.manifest.clone(),
-                                        );
-                                        Ok(Some(item))
-                                    } else {
-                                        abort(SnapshotError::WrongChecksum(
-                                            cd.manifest.checksum,
-                                            checksum,
-                                        ))
-                                    }
-                                }
-                                Err(e) => abort(SnapshotError::IoError(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    e.to_string(),
-                                ))),
-                            }
-                        } else {
-                            Ok(None)
-                        }
-                    }
-                    Err(e) => {
-                        // If we failed to save the data to disk we can return an error that will cause all snapshots to be aborted.
-                        // There is no point trying to clear download from the state here because if we `abort` then all changes will be dropped.
-                        abort(SnapshotError::from(e))
-                    }
-                }
-            }
-        } else {
-            abort(SnapshotError::NoDownload)
+    ) -> Result<Option<(SnapshotItem, Cid)>, SnapshotError> {
+        let cd = atomically(|| self.state.current_download.read_clone())
+            .await
+            .ok_or(SnapshotError::NoDownload)?;
+
+        let next_index = atomically(|| cd.next_index.read_clone()).await;
+        if index != next_index {
+            return Err(SnapshotError::UnexpectedChunk(next_index, index));
         }
+
+        // Don't bother with the rest of the chunks if something was wrong with the previous ones.
+        cd.pipeline.check().await?;
+
+        // The chunks are stored as they were received, so they can be checksummed at the end.
+        let part_path = cd.parts_dir().join(format!("{}.part", index));
+        // If we failed to save the data to disk we can return an error that will cause all snapshots to be aborted.
+        std::fs::write(part_path, &contents)?;
+
+        cd.pipeline.send(index, contents).await?;
+
+        let next_index = index + 1;
+        atomically(|| cd.next_index.write(next_index)).await;
+
+        if next_index < cd.manifest.chunks {
+            return Ok(None);
+        }
+
+        // Wait for the pipeline to finish restoring the chunks, then verify the checksum.
+        let root = cd.pipeline.finish().await?;
+
+        let checksum = manifest::parts_checksum(cd.parts_dir(), cd.manifest.compression)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        if checksum != cd.manifest.checksum {
+            return Err(SnapshotError::WrongChecksum(cd.manifest.checksum, checksum));
+        }
+
+        let item = SnapshotItem::new(cd.download_dir.path().into(), cd.manifest.clone());
+
+        Ok(Some((item, root)))
     }
 }
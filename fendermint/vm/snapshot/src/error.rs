@@ -16,4 +16,6 @@ pub enum SnapshotError {
     UnexpectedChunk(u32, u32),
     #[error("wrong checksum; expected {0}, got {1}")]
     WrongChecksum(tendermint::Hash, tendermint::Hash),
+    #[error("corrupt snapshot chunk {0}: {1}")]
+    CorruptChunk(u32, String),
 }
@@ -5,6 +5,9 @@ mod client;
 mod error;
 mod manager;
 mod manifest;
+mod mirror;
+mod publish;
+mod restore;
 mod state;
 
 /// The file name to export the CAR to.
@@ -16,8 +19,15 @@ const MANIFEST_FILE_NAME: &str = "manifest.json";
 /// Name of the subdirectory where `{idx}.part` files are stored within a snapshot.
 const PARTS_DIR_NAME: &str = "parts";
 
+/// The file name in snapshot directories that records where the snapshot has been published.
+const PUBLISHED_FILE_NAME: &str = "published.json";
+
 pub use client::SnapshotClient;
 pub use error::SnapshotError;
 pub use manager::{SnapshotManager, SnapshotParams};
-pub use manifest::SnapshotManifest;
-pub use state::SnapshotItem;
+pub use manifest::{SnapshotCompression, SnapshotManifest};
+pub use mirror::{mirror_snapshot, SnapshotSource};
+pub use publish::{
+    read_published, IpfsPublisher, PublishedSnapshot, S3Config, S3Publisher, SnapshotPublisher,
+};
+pub use state::{DownloadProgress, SnapshotItem};
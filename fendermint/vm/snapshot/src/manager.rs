@@ -2,9 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::manifest::{file_checksum, list_manifests, write_manifest, SnapshotManifest};
+use crate::manifest::{
+    file_checksum, list_manifests, list_parts, write_manifest, SnapshotCompression,
+    SnapshotManifest, MAX_CHUNK_SIZE,
+};
+use crate::publish::{publish_snapshot, SnapshotPublisher};
 use crate::state::SnapshotState;
 use crate::{car, SnapshotClient, SnapshotItem, PARTS_DIR_NAME, SNAPSHOT_FILE_NAME};
 use anyhow::Context;
@@ -21,6 +26,13 @@ pub struct SnapshotParams {
     pub block_interval: BlockHeight,
     /// Target size in bytes for snapshot chunks.
     pub chunk_size: usize,
+    /// Maximum number of chunks in a snapshot; the chunks of large snapshots are made
+    /// bigger than the target to stay under it, as much as CometBFT allows.
+    ///
+    /// 0 means unlimited.
+    pub max_chunks: usize,
+    /// Number of chunks to decompress and verify in parallel while restoring a snapshot.
+    pub restore_concurrency: usize,
     /// Number of snapshots to keep.
     ///
     /// 0 means unlimited.
@@ -30,6 +42,10 @@ pub struct SnapshotParams {
     pub last_access_hold: Duration,
     /// How often to check CometBFT whether it has finished syncing.
     pub sync_poll_interval: Duration,
+    /// Compression applied to each chunk.
+    pub compression: SnapshotCompression,
+    /// Compression level; the meaning depends on the algorithm.
+    pub compression_level: i32,
 }
 
 /// Create snapshots at regular block intervals.
@@ -37,14 +53,21 @@ pub struct SnapshotManager<BS> {
     store: BS,
     snapshots_dir: PathBuf,
     chunk_size: usize,
+    max_chunks: usize,
     hist_size: usize,
     last_access_hold: Duration,
     sync_poll_interval: Duration,
+    compression: SnapshotCompression,
+    compression_level: i32,
     /// Shared state of snapshots.
     state: SnapshotState,
     /// Indicate whether CometBFT has finished syncing with the chain,
     /// so that we can skip snapshotting old states while catching up.
     is_syncing: TVar<bool>,
+    /// Where to upload the snapshots to once they are created.
+    publishers: Vec<Arc<dyn SnapshotPublisher>>,
+    /// Heights of the snapshots being uploaded, which must not be pruned in the meantime.
+    publishing: TVar<im::HashSet<BlockHeight>>,
 }
 
 impl<BS> SnapshotManager<BS>
@@ -66,19 +89,35 @@ where
             store,
             snapshots_dir: params.snapshots_dir,
             chunk_size: params.chunk_size,
+            max_chunks: params.max_chunks,
             hist_size: params.hist_size,
             last_access_hold: params.last_access_hold,
             sync_poll_interval: params.sync_poll_interval,
+            compression: params.compression,
+            compression_level: params.compression_level,
             state: state.clone(),
             // Assume we are syncing until we can determine otherwise.
             is_syncing: TVar::new(true),
+            publishers: Vec::new(),
+            publishing: TVar::new(im::HashSet::new()),
         };
 
-        let client = SnapshotClient::new(params.download_dir, params.block_interval, state);
+        let client = SnapshotClient::new(
+            params.download_dir,
+            params.block_interval,
+            params.restore_concurrency,
+            state,
+        );
 
         Ok((manager, client))
     }
 
+    /// Publish every snapshot created from now on.
+    pub fn with_publishers(mut self, publishers: Vec<Arc<dyn SnapshotPublisher>>) -> Self {
+        self.publishers = publishers;
+        self
+    }
+
     /// Produce snapshots.
     pub async fn run<C>(self, client: C)
     where
@@ -136,6 +175,17 @@ where
                             .modify_mut(|items| items.push_back(item.clone()))
                     })
                     .await;
+
+                    // Uploading can take a while; the snapshot is available to peers in the meantime.
+                    if !self.publishers.is_empty() {
+                        let publishers = self.publishers.clone();
+                        let publishing = self.publishing.clone();
+                        atomically(|| publishing.modify_mut(|p| p.insert(block_height))).await;
+                        tokio::spawn(async move {
+                            publish_snapshot(&publishers, &item).await;
+                            atomically(|| publishing.modify_mut(|p| p.remove(&block_height))).await;
+                        });
+                    }
                 }
                 Err(e) => {
                     tracing::warn!(error =? e, block_height, "failed to create snapshot");
@@ -156,9 +206,16 @@ where
         }
 
         let removables = atomically(|| {
+            let publishing = self.publishing.read()?;
             self.state.snapshots.modify_mut(|snapshots| {
                 let mut removables = Vec::new();
                 while snapshots.len() > self.hist_size {
+                    // Stop at the first snapshot that is still being uploaded.
+                    if let Some(snapshot) = snapshots.head() {
+                        if publishing.contains(&snapshot.manifest.block_height) {
+                            break;
+                        }
+                    }
                     // Stop at the first snapshot that was accessed recently.
                     if let Some(last_access) =
                         snapshots.head().and_then(|s| s.last_access.elapsed().ok())
@@ -239,12 +296,19 @@ where
         // They can be listed in the right order with e.g. `ls | sort -n`
         // Alternatively we could pad them with zeroes based on the original file size and the chunk size,
         // but this way it will be easier to return them based on a numeric index.
-        let chunks_count = car::split(&snapshot_path, &parts_path, self.chunk_size, |idx| {
+        let chunk_size = chunk_size(snapshot_size, self.chunk_size, self.max_chunks);
+        let chunks_count = car::split(&snapshot_path, &parts_path, chunk_size, |idx| {
             format!("{idx}.part")
         })
         .await
         .context("failed to split CAR into chunks")?;
 
+        // Compress the chunks individually, so they can be verified as they are downloaded.
+        if self.compression != SnapshotCompression::None {
+            self.compress_parts(&parts_path)
+                .context("failed to compress chunks")?;
+        }
+
         // Create and export a manifest that we can easily look up.
         let manifest = SnapshotManifest {
             block_height,
@@ -252,7 +316,9 @@ where
             chunks: chunks_count as u32,
             checksum: checksum_bytes,
             state_params,
-            version: snapshot_version,
+            version: self.compression.snapshot_version(snapshot_version),
+            compression: self.compression,
+            chunk_size: chunk_size as u64,
         };
         let _ = write_manifest(temp_dir.path(), &manifest).context("failed to export manifest")?;
 
@@ -261,6 +327,30 @@ where
 
         Ok(SnapshotItem::new(snapshots_dir, manifest))
     }
+
+    /// Replace the contents of each part with its compressed version.
+    fn compress_parts(&self, parts_path: &Path) -> anyhow::Result<()> {
+        for part in list_parts(parts_path)? {
+            let contents = std::fs::read(&part).context("failed to read part")?;
+            let compressed = self
+                .compression
+                .compress(&contents, self.compression_level)
+                .context("failed to compress part")?;
+            std::fs::write(&part, compressed).context("failed to write part")?;
+        }
+        Ok(())
+    }
+}
+
+/// Pick the size of the chunks for a snapshot: the target, unless that would result in more than
+/// `max_chunks` of them, in which case they are made bigger, up to what CometBFT can transfer.
+fn chunk_size(snapshot_size: usize, target: usize, max_chunks: usize) -> usize {
+    let max = MAX_CHUNK_SIZE as usize;
+    let target = target.clamp(1, max);
+    if max_chunks == 0 {
+        return target;
+    }
+    snapshot_size.div_ceil(max_chunks).clamp(target, max)
 }
 
 /// Periodically ask CometBFT if it has caught up with the chain.
@@ -325,9 +415,13 @@ mod tests {
     use fvm::engine::MultiEngine;
     use quickcheck::Arbitrary;
 
-    use crate::{manager::SnapshotParams, manifest, PARTS_DIR_NAME};
+    use crate::{
+        manager::SnapshotParams,
+        manifest::{self, SnapshotCompression, MAX_CHUNK_SIZE},
+        SnapshotError, PARTS_DIR_NAME,
+    };
 
-    use super::SnapshotManager;
+    use super::{chunk_size, SnapshotManager};
 
     // Initialise genesis and export it directly to see if it works.
     #[tokio::test]
@@ -362,9 +456,13 @@ mod tests {
                 download_dir: download_dir.path().into(),
                 block_interval: 1,
                 chunk_size: 10000,
+                max_chunks: 0,
+                restore_concurrency: 4,
                 hist_size: 1,
                 last_access_hold: Duration::ZERO,
                 sync_poll_interval: never_poll_sync,
+                compression: SnapshotCompression::Zstd,
+                compression_level: 3,
             },
         )
         .expect("failed to create snapshot manager");
@@ -413,9 +511,13 @@ mod tests {
         assert_eq!(snapshots.len(), 1, "can list manifests");
         assert_eq!(snapshots[0], snapshot);
 
-        let checksum =
-            manifest::parts_checksum(snapshot.snapshot_dir.as_path().join(PARTS_DIR_NAME))
-                .expect("parts checksum can be calculated");
+        assert_eq!(snapshot.manifest.compression, SnapshotCompression::Zstd);
+
+        let checksum = manifest::parts_checksum(
+            snapshot.snapshot_dir.as_path().join(PARTS_DIR_NAME),
+            snapshot.manifest.compression,
+        )
+        .expect("parts checksum can be calculated");
 
         assert_eq!(
             checksum, snapshot.manifest.checksum,
@@ -430,15 +532,78 @@ mod tests {
                 download_dir: download_dir.path().into(),
                 block_interval: 1,
                 chunk_size: 10000,
+                max_chunks: 0,
+                restore_concurrency: 4,
                 hist_size: 1,
                 last_access_hold: Duration::ZERO,
                 sync_poll_interval: never_poll_sync,
+                compression: SnapshotCompression::Zstd,
+                compression_level: 3,
             },
         )
         .expect("failed to create snapshot manager");
 
         let snapshots = atomically(|| new_client.list_snapshots()).await;
         assert!(!snapshots.is_empty(), "loads manifests on start");
+
+        // Restore the snapshot into an empty store, the way state sync would.
+        let restored_store = MemoryBlockstore::new();
+        new_client
+            .offer_snapshot(snapshot.manifest.clone(), restored_store.clone())
+            .await
+            .expect("failed to offer snapshot");
+
+        let mut restored = None;
+        for i in 0..snapshot.manifest.chunks {
+            let chunk = snapshot.load_chunk(i).expect("failed to load chunk");
+            restored = new_client
+                .save_chunk(i, chunk)
+                .await
+                .expect("failed to save chunk");
+        }
+        let (item, metadata_cid) = restored.expect("restored after the last chunk");
+        let Snapshot::V1(restored) = item
+            .load(restored_store, metadata_cid)
+            .expect("failed to load restored snapshot");
+        assert_eq!(*restored.state_params(), state_params);
+
+        // A corrupted chunk gets the snapshot rejected.
+        new_client
+            .offer_snapshot(snapshot.manifest.clone(), MemoryBlockstore::new())
+            .await
+            .expect("failed to offer snapshot");
+
+        let corrupt = snapshot.manifest.chunks / 2;
+        let mut res = Ok(None);
+        for i in 0..snapshot.manifest.chunks {
+            let mut chunk = snapshot.load_chunk(i).expect("failed to load chunk");
+            if i == corrupt {
+                let mid = chunk.len() / 2;
+                chunk[mid] ^= 0xff;
+            }
+            res = new_client.save_chunk(i, chunk).await;
+            if res.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(res, Err(SnapshotError::CorruptChunk(..))));
+    }
+
+    #[test]
+    fn adaptive_chunk_size() {
+        const MB: usize = 1024 * 1024;
+        // Small snapshots use the target.
+        assert_eq!(chunk_size(100 * MB, 10 * MB, 1000), 10 * MB);
+        // Large ones get bigger chunks, up to what CometBFT allows.
+        assert_eq!(chunk_size(12000 * MB, 10 * MB, 1000), 12 * MB);
+        assert_eq!(
+            chunk_size(100000 * MB, 10 * MB, 1000),
+            MAX_CHUNK_SIZE as usize
+        );
+        // Unlimited number of chunks.
+        assert_eq!(chunk_size(100000 * MB, 10 * MB, 0), 10 * MB);
+        // Too big a target is capped as well.
+        assert_eq!(chunk_size(100 * MB, 20 * MB, 0), MAX_CHUNK_SIZE as usize);
     }
 
     async fn init_genesis() -> (FvmStateParams, MemoryBlockstore) {
@@ -1,6 +1,8 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::borrow::Cow;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
@@ -13,6 +15,18 @@ use sha2::{Digest, Sha256};
 
 use crate::{SnapshotItem, MANIFEST_FILE_NAME};
 
+/// CometBFT drops state-sync messages larger than 16MB; leave some room for
+/// the compression overhead of incompressible chunks and the message envelope.
+///
+/// This is also the most a chunk can be decompressed into, regardless of what
+/// the manifest offered by a peer says.
+pub const MAX_CHUNK_SIZE: u64 = 15 * 1024 * 1024;
+
+/// Format version of snapshots with compressed chunks, which otherwise have the same contents
+/// as version 1. Peers which predate compression only accept version 1 and would fail to
+/// restore compressed chunks, so the version tells them to reject the snapshot up front.
+pub const COMPRESSED_SNAPSHOT_VERSION: SnapshotVersion = 2;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct SnapshotManifest {
     /// Block height where the snapshot was taken.
@@ -31,6 +45,91 @@ pub struct SnapshotManifest {
     pub state_params: FvmStateParams,
     /// Snapshot format version
     pub version: SnapshotVersion,
+    /// How the chunks are compressed.
+    ///
+    /// Manifests created before compression was supported don't have this field.
+    #[serde(default)]
+    pub compression: SnapshotCompression,
+    /// Size of the chunks before compression; all but the last one are exactly this big.
+    ///
+    /// Manifests created before the chunk size was recorded have 0 here.
+    #[serde(default)]
+    pub chunk_size: u64,
+}
+
+/// Compression applied to each chunk individually, so they can be
+/// decompressed and verified one by one as they are downloaded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotCompression {
+    /// The chunks are slices of the CAR file as they are.
+    #[default]
+    None,
+    /// The chunks are compressed with zstd.
+    Zstd,
+}
+
+impl SnapshotCompression {
+    /// The format version of a snapshot whose contents have the given version,
+    /// with the chunks compressed like this.
+    pub fn snapshot_version(&self, version: SnapshotVersion) -> SnapshotVersion {
+        match self {
+            Self::None => version,
+            Self::Zstd => COMPRESSED_SNAPSHOT_VERSION,
+        }
+    }
+
+    /// Compress the contents of a chunk.
+    pub fn compress(&self, contents: &[u8], level: i32) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(contents.to_vec()),
+            Self::Zstd => zstd::bulk::compress(contents, level),
+        }
+    }
+
+    /// Read the decompressed contents of a chunk, failing if they are larger than [MAX_CHUNK_SIZE],
+    /// so that a malicious peer cannot make us allocate or write arbitrary amounts of data.
+    pub fn decoder<'a, R: Read + 'a>(&self, chunk: R) -> std::io::Result<Box<dyn Read + 'a>> {
+        match self {
+            Self::None => Ok(Box::new(chunk)),
+            Self::Zstd => Ok(Box::new(CappedReader {
+                inner: zstd::stream::read::Decoder::new(chunk)?,
+                remaining: MAX_CHUNK_SIZE,
+            })),
+        }
+    }
+
+    /// Decompress the contents of a chunk held in memory; see [SnapshotCompression::decoder].
+    pub fn decompress<'a>(&self, contents: &'a [u8]) -> std::io::Result<Cow<'a, [u8]>> {
+        match self {
+            Self::None => Ok(Cow::Borrowed(contents)),
+            Self::Zstd => {
+                let mut buf = Vec::new();
+                self.decoder(contents)?.read_to_end(&mut buf)?;
+                Ok(Cow::Owned(buf))
+            }
+        }
+    }
+}
+
+/// Fails the read once more than the allowed number of bytes have been read.
+struct CappedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for CappedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n as u64 > self.remaining {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("decompressed chunk is larger than {MAX_CHUNK_SIZE} bytes"),
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
 }
 
 /// Save a manifest along with the other snapshot files into a snapshot specific directory.
@@ -109,14 +208,20 @@ pub fn file_checksum(path: impl AsRef<Path>) -> anyhow::Result<tendermint::Hash>
 }
 
 /// Calculate the Sha256 checksum of all `{idx}.part` files in a directory.
-pub fn parts_checksum(path: impl AsRef<Path>) -> anyhow::Result<tendermint::Hash> {
+///
+/// The checksum is over the decompressed contents, which is the original CAR file.
+pub fn parts_checksum(
+    path: impl AsRef<Path>,
+    compression: SnapshotCompression,
+) -> anyhow::Result<tendermint::Hash> {
     let mut hasher = Sha256::new();
 
     let chunks = list_parts(path)?;
 
     for path in chunks {
-        let mut file = std::fs::File::open(path).context("failed to open part")?;
-        let _ = std::io::copy(&mut file, &mut hasher)?;
+        let file = std::fs::File::open(path).context("failed to open part")?;
+        let mut decoder = compression.decoder(file)?;
+        let _ = std::io::copy(&mut decoder, &mut hasher).context("failed to decompress part")?;
     }
 
     let hash = hasher.finalize().into();
@@ -164,7 +269,7 @@ mod arb {
     use fvm_shared::version::NetworkVersion;
     use quickcheck::Arbitrary;
 
-    use super::SnapshotManifest;
+    use super::{SnapshotCompression, SnapshotManifest};
 
     impl quickcheck::Arbitrary for SnapshotManifest {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
@@ -191,6 +296,10 @@ mod arb {
                     power_scale: *g.choose(&[-1, 0, 3]).unwrap(),
                 },
                 version: Arbitrary::arbitrary(g),
+                compression: *g
+                    .choose(&[SnapshotCompression::None, SnapshotCompression::Zstd])
+                    .unwrap(),
+                chunk_size: Arbitrary::arbitrary(g),
             }
         }
     }
@@ -203,7 +312,9 @@ mod tests {
     use cid::multihash::MultihashDigest;
     use tempfile::NamedTempFile;
 
-    use crate::manifest::file_checksum;
+    use crate::manifest::{
+        file_checksum, SnapshotCompression, COMPRESSED_SNAPSHOT_VERSION, MAX_CHUNK_SIZE,
+    };
 
     #[test]
     fn test_file_checksum() {
@@ -219,4 +330,50 @@ mod tests {
 
         assert_eq!(file_digest.as_bytes(), content_digest)
     }
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let content = b"Hello Compression! Hello Compression! Hello Compression!".repeat(10);
+
+        for compression in [SnapshotCompression::None, SnapshotCompression::Zstd] {
+            let compressed = compression.compress(&content, 3).expect("compress");
+            let decompressed = compression.decompress(&compressed).expect("decompress");
+            assert_eq!(decompressed.as_ref(), content.as_slice());
+        }
+
+        let compressed = SnapshotCompression::Zstd
+            .compress(&content, 3)
+            .expect("compress");
+        assert!(compressed.len() < content.len());
+    }
+
+    #[test]
+    fn test_decompression_is_capped() {
+        let content = vec![0u8; MAX_CHUNK_SIZE as usize];
+        let compression = SnapshotCompression::Zstd;
+
+        let compressed = compression.compress(&content, 3).expect("compress");
+        assert!(compression.decompress(&compressed).is_ok());
+
+        // A tiny chunk which would decompress to more than what we ever send.
+        let mut bomb = content;
+        bomb.push(0);
+        let compressed = compression.compress(&bomb, 3).expect("compress");
+        assert!(compressed.len() < 1024);
+        assert!(compression.decompress(&compressed).is_err());
+        assert!(std::io::copy(
+            &mut compression.decoder(compressed.as_slice()).unwrap(),
+            &mut std::io::sink()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_compressed_snapshot_version() {
+        assert_eq!(SnapshotCompression::None.snapshot_version(1), 1);
+        assert_eq!(
+            SnapshotCompression::Zstd.snapshot_version(1),
+            COMPRESSED_SNAPSHOT_VERSION
+        );
+    }
 }
@@ -0,0 +1,110 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Downloading published snapshots into the snapshot directory of a node,
+//! which then offers them to its peers during state sync like its own ones.
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::manifest::{parts_checksum, write_manifest, SnapshotManifest};
+use crate::{SnapshotItem, MANIFEST_FILE_NAME, PARTS_DIR_NAME};
+
+/// Where to download a published snapshot from.
+#[derive(Debug, Clone)]
+pub enum SnapshotSource {
+    /// A directory on IPFS, fetched through an HTTP gateway, e.g. `http://127.0.0.1:8080`.
+    Ipfs { gateway: String, cid: String },
+    /// The URL of the snapshot directory, e.g. in a public S3 bucket.
+    Url(String),
+}
+
+impl SnapshotSource {
+    fn base_url(&self) -> String {
+        match self {
+            Self::Ipfs { gateway, cid } => format!("{}/ipfs/{cid}", gateway.trim_end_matches('/')),
+            Self::Url(url) => url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+/// Download a snapshot into `snapshots_dir`, checking the chunks against the checksum in the manifest.
+///
+/// The snapshot only appears in the directory once it is complete.
+pub async fn mirror_snapshot(
+    source: &SnapshotSource,
+    snapshots_dir: &Path,
+) -> anyhow::Result<SnapshotItem> {
+    let base_url = source.base_url();
+    let client = reqwest::Client::new();
+
+    let get = |path: String| {
+        let client = client.clone();
+        let url = format!("{base_url}/{path}");
+        async move {
+            let res = client
+                .get(&url)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+                .with_context(|| format!("failed to fetch {url}"))?;
+            let bytes = res
+                .bytes()
+                .await
+                .with_context(|| format!("failed to fetch {url}"))?;
+            anyhow::Ok(bytes)
+        }
+    };
+
+    let manifest = get(MANIFEST_FILE_NAME.to_string()).await?;
+    let manifest: SnapshotManifest =
+        serde_json::from_slice(&manifest).context("failed to parse the manifest")?;
+
+    let snapshot_name = format!("snapshot-{}", manifest.block_height);
+    let snapshot_dir = snapshots_dir.join(&snapshot_name);
+    if snapshot_dir.exists() {
+        bail!(
+            "there is already a snapshot at {}",
+            snapshot_dir.to_string_lossy()
+        );
+    }
+
+    std::fs::create_dir_all(snapshots_dir).context("failed to create snapshots directory")?;
+
+    // Download next to the final place, so it can be moved there in one go.
+    let temp_dir = tempfile::Builder::new()
+        .prefix(&format!(".{snapshot_name}"))
+        .tempdir_in(snapshots_dir)
+        .context("failed to create temp dir for snapshot")?;
+
+    let parts_dir = temp_dir.path().join(PARTS_DIR_NAME);
+    std::fs::create_dir(&parts_dir).context("failed to create parts dir")?;
+
+    for idx in 0..manifest.chunks {
+        let name = format!("{idx}.part");
+        let chunk = get(format!("{PARTS_DIR_NAME}/{name}")).await?;
+        std::fs::write(parts_dir.join(&name), chunk).context("failed to write part")?;
+        tracing::debug!(
+            chunk = idx,
+            chunks = manifest.chunks,
+            "downloaded snapshot chunk"
+        );
+    }
+
+    let checksum = parts_checksum(&parts_dir, manifest.compression)
+        .context("failed to compute the checksum of the parts")?;
+
+    if checksum != manifest.checksum {
+        bail!(
+            "wrong snapshot checksum; expected {}, got {checksum}",
+            manifest.checksum
+        );
+    }
+
+    write_manifest(temp_dir.path(), &manifest).context("failed to write manifest")?;
+
+    std::fs::rename(temp_dir.path(), &snapshot_dir).context("failed to move snapshot")?;
+
+    Ok(SnapshotItem::new(snapshot_dir, manifest))
+}
@@ -0,0 +1,259 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_stm::{atomically, retry, Stm, TVar};
+use cid::Cid;
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use fvm_ipld_blockstore::Blockstore;
+
+use crate::{
+    car::{verify_block, CarVerifier, CarVerifyError},
+    SnapshotError, SnapshotManifest,
+};
+
+/// How far the pipeline got with restoring a snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct RestoreStatus {
+    /// Number of decompressed bytes in the chunks whose blocks have all been written to the blockstore.
+    pub bytes: u64,
+    /// Number of CAR blocks verified and written to the blockstore.
+    pub blocks: u64,
+    /// The root of the CAR file, once all the chunks have been restored.
+    pub root: Option<Cid>,
+    /// The problem the pipeline stopped at, if any.
+    pub error: Option<RestoreError>,
+}
+
+#[derive(Debug, Clone)]
+pub enum RestoreError {
+    /// The contents of the snapshot are invalid.
+    Corrupt(CarVerifyError),
+    /// Something went wrong on our side.
+    Internal(String),
+}
+
+impl From<RestoreError> for SnapshotError {
+    fn from(value: RestoreError) -> Self {
+        match value {
+            RestoreError::Corrupt(CarVerifyError { chunk, reason }) => {
+                SnapshotError::CorruptChunk(chunk, reason)
+            }
+            RestoreError::Internal(e) => {
+                SnapshotError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e))
+            }
+        }
+    }
+}
+
+/// Restore the chunks of a snapshot into the blockstore as they arrive.
+///
+/// Up to `concurrency` chunks are decompressed, and the blocks of as many chunks are verified
+/// and written to the store in parallel. Only cutting the decompressed chunks up into blocks
+/// is sequential, because blocks can span chunks. Chunks are handed over through a bounded
+/// channel, so that if the pipeline falls behind, CometBFT has to wait before sending more,
+/// rather than us holding an unbounded number of chunks in memory.
+#[derive(Clone)]
+pub struct RestorePipeline {
+    chunks: mpsc::Sender<(u32, Vec<u8>)>,
+    status: TVar<RestoreStatus>,
+}
+
+impl RestorePipeline {
+    /// Start restoring a snapshot in the background.
+    pub fn spawn<BS>(store: BS, manifest: &SnapshotManifest, concurrency: usize) -> Self
+    where
+        BS: Blockstore + Clone + Send + Sync + 'static,
+    {
+        let concurrency = concurrency.max(1);
+        let (tx, rx) = mpsc::channel(concurrency);
+        let status = TVar::new(RestoreStatus::default());
+
+        tokio::spawn(run(
+            store,
+            manifest.clone(),
+            concurrency,
+            rx,
+            status.clone(),
+        ));
+
+        Self { chunks: tx, status }
+    }
+
+    pub fn status(&self) -> Stm<RestoreStatus> {
+        self.status.read_clone()
+    }
+
+    /// Hand over the next chunk, waiting if the pipeline is too far behind.
+    pub async fn send(&self, index: u32, contents: Vec<u8>) -> Result<(), SnapshotError> {
+        let mut chunks = self.chunks.clone();
+        if chunks.send((index, contents)).await.is_err() {
+            // The pipeline only stops early if it failed.
+            return Err(self.failure().await);
+        }
+        Ok(())
+    }
+
+    /// Check whether the pipeline has failed on any of the chunks sent so far.
+    pub async fn check(&self) -> Result<(), SnapshotError> {
+        match atomically(|| self.status.read_clone()).await.error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait until all the chunks have been restored, returning the root of the CAR file.
+    pub async fn finish(&self) -> Result<Cid, SnapshotError> {
+        let status = atomically(|| {
+            let status = self.status.read_clone()?;
+            if status.error.is_none() && status.root.is_none() {
+                retry()
+            } else {
+                Ok(status)
+            }
+        })
+        .await;
+
+        match (status.error, status.root) {
+            (Some(e), _) => Err(e.into()),
+            (None, Some(root)) => Ok(root),
+            (None, None) => unreachable!("waited for a root or an error"),
+        }
+    }
+
+    async fn failure(&self) -> SnapshotError {
+        match atomically(|| self.status.read_clone()).await.error {
+            Some(e) => e.into(),
+            None => RestoreError::Internal("the restore pipeline stopped".to_owned()).into(),
+        }
+    }
+}
+
+/// A chunk cut up into the blocks completed by it.
+struct Blocks {
+    /// Bytes received up to and including this chunk.
+    bytes: u64,
+    /// Blocks completed up to and including this chunk.
+    count: u64,
+    blocks: Vec<(u32, Vec<u8>)>,
+    /// The root of the CAR file, after the last chunk.
+    root: Option<Cid>,
+}
+
+async fn run<BS>(
+    store: BS,
+    manifest: SnapshotManifest,
+    concurrency: usize,
+    chunks: mpsc::Receiver<(u32, Vec<u8>)>,
+    status: TVar<RestoreStatus>,
+) where
+    BS: Blockstore + Clone + Send + Sync + 'static,
+{
+    let compression = manifest.compression;
+    let last_index = manifest.chunks.saturating_sub(1);
+    let mut verifier = CarVerifier::new(manifest.size);
+
+    let restored = chunks
+        .map(|(index, contents)| async move {
+            let decompressed = tokio::task::spawn_blocking(move || {
+                compression.decompress(&contents).map(|c| c.into_owned())
+            })
+            .await
+            .map_err(|e| RestoreError::Internal(e.to_string()))?;
+
+            decompressed.map(|c| (index, c)).map_err(|e| {
+                RestoreError::Corrupt(CarVerifyError {
+                    chunk: index,
+                    reason: format!("failed to decompress: {e}"),
+                })
+            })
+        })
+        .buffered(concurrency)
+        .map(move |decompressed| {
+            let (index, contents) = decompressed?;
+            let blocks = verifier
+                .split(index, &contents)
+                .map_err(RestoreError::Corrupt)?;
+
+            let root = if index == last_index {
+                verifier.finish().map_err(RestoreError::Corrupt)?;
+                match verifier.roots() {
+                    [root] => Some(*root),
+                    roots => {
+                        return Err(RestoreError::Corrupt(CarVerifyError {
+                            chunk: 0,
+                            reason: format!("expected 1 root, got {}", roots.len()),
+                        }))
+                    }
+                }
+            } else {
+                None
+            };
+
+            Ok(Blocks {
+                bytes: verifier.bytes(),
+                count: verifier.blocks(),
+                blocks,
+                root,
+            })
+        })
+        .map(|blocks| {
+            let store = store.clone();
+            async move {
+                let mut blocks = blocks?;
+                let sections = std::mem::take(&mut blocks.blocks);
+
+                tokio::task::spawn_blocking(move || write_blocks(&store, sections))
+                    .await
+                    .map_err(|e| RestoreError::Internal(e.to_string()))??;
+
+                Ok::<_, RestoreError>(blocks)
+            }
+        })
+        .buffered(concurrency);
+
+    futures::pin_mut!(restored);
+
+    while let Some(res) = restored.next().await {
+        let done = !matches!(res, Ok(Blocks { root: None, .. }));
+
+        atomically(|| {
+            status.update_mut(|s| match &res {
+                Ok(blocks) => {
+                    s.bytes = blocks.bytes;
+                    s.blocks = blocks.count;
+                    s.root = blocks.root;
+                }
+                Err(e) => {
+                    s.error = Some(e.clone());
+                }
+            })
+        })
+        .await;
+
+        if done {
+            break;
+        }
+    }
+}
+
+/// Verify the blocks against their CIDs and write them to the store.
+fn write_blocks<BS: Blockstore>(
+    store: &BS,
+    sections: Vec<(u32, Vec<u8>)>,
+) -> Result<(), RestoreError> {
+    let blocks = sections
+        .iter()
+        .map(|(chunk, section)| {
+            verify_block(section).map_err(|reason| {
+                RestoreError::Corrupt(CarVerifyError {
+                    chunk: *chunk,
+                    reason,
+                })
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    store
+        .put_many_keyed(blocks)
+        .map_err(|e| RestoreError::Internal(format!("failed to write blocks: {e}")))
+}
@@ -1,16 +1,25 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fs::File, io, path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+    fs::File,
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 
 use anyhow::{bail, Context};
-use async_stm::TVar;
-use fendermint_vm_interpreter::fvm::state::snapshot::{BlockStateParams, Snapshot};
+use async_stm::{Stm, TVar};
+use cid::Cid;
+use fendermint_vm_interpreter::fvm::state::snapshot::{BlockHeight, BlockStateParams, Snapshot};
 use fvm_ipld_blockstore::Blockstore;
+use serde::Serialize;
 use tempfile::TempDir;
 
 use crate::{
     manifest::{self, SnapshotManifest},
+    restore::RestorePipeline,
     PARTS_DIR_NAME, SNAPSHOT_FILE_NAME,
 };
 
@@ -88,16 +97,22 @@ impl SnapshotItem {
         let parts =
             manifest::list_parts(self.parts_dir()).context("failed to list snapshot parts")?;
 
-        // 1. Restore the snapshots into a complete `snapshot.car` file.
+        // 1. Restore the snapshots into a complete `snapshot.car` file, decompressing the parts if needed.
         let car_path = self.snapshot_dir.join(SNAPSHOT_FILE_NAME);
         let mut car_file = File::create(&car_path).context("failed to create CAR file")?;
 
         for part in parts {
-            let mut part_file = File::open(&part).with_context(|| {
+            let part_file = File::open(&part).with_context(|| {
                 format!("failed to open snapshot part {}", part.to_string_lossy())
             })?;
 
-            io::copy(&mut part_file, &mut car_file)?;
+            let mut decoder = self.manifest.compression.decoder(part_file)?;
+            io::copy(&mut decoder, &mut car_file).with_context(|| {
+                format!(
+                    "failed to decompress snapshot part {}",
+                    part.to_string_lossy()
+                )
+            })?;
         }
 
         // 2. Import the contents.
@@ -137,28 +152,51 @@ impl SnapshotItem {
 
         // 4. See if we actually imported what we thought we would.
         if validate {
-            match snapshot {
-                Snapshot::V1(ref snapshot) => {
-                    if snapshot.block_height() != self.manifest.block_height {
-                        bail!(
-                            "invalid snapshot block height; expected {}, imported {}",
-                            self.manifest.block_height,
-                            snapshot.block_height()
-                        );
-                    }
-                    if *snapshot.state_params() != self.manifest.state_params {
-                        bail!(
-                            "invalid state params; expected {:?}, imported {:?}",
-                            self.manifest.state_params,
-                            snapshot.state_params()
-                        )
-                    }
-                }
-            }
+            self.check(&snapshot)?;
         }
 
         Ok(snapshot)
     }
+
+    /// Load a snapshot which has been restored into the blockstore chunk by chunk,
+    /// starting from the CID of its metadata, and check that it is what we expected.
+    pub fn load<BS>(&self, store: BS, metadata_cid: Cid) -> anyhow::Result<Snapshot<BS>>
+    where
+        BS: Blockstore + Send + 'static,
+    {
+        let snapshot = Snapshot::from_metadata(store, metadata_cid)
+            .context("failed to load the restored snapshot from the blockstore")?;
+
+        self.check(&snapshot)?;
+
+        Ok(snapshot)
+    }
+
+    /// Check that the snapshot is at the height and has the state parameters in the manifest.
+    fn check<BS>(&self, snapshot: &Snapshot<BS>) -> anyhow::Result<()>
+    where
+        BS: Blockstore + Send + 'static,
+    {
+        match snapshot {
+            Snapshot::V1(ref snapshot) => {
+                if snapshot.block_height() != self.manifest.block_height {
+                    bail!(
+                        "invalid snapshot block height; expected {}, imported {}",
+                        self.manifest.block_height,
+                        snapshot.block_height()
+                    );
+                }
+                if *snapshot.state_params() != self.manifest.state_params {
+                    bail!(
+                        "invalid state params; expected {:?}, imported {:?}",
+                        self.manifest.state_params,
+                        snapshot.state_params()
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// An ongoing, incomplete download of a snapshot.
@@ -169,12 +207,56 @@ pub struct SnapshotDownload {
     pub download_dir: Arc<TempDir>,
     // Next expected chunk index.
     pub next_index: TVar<u32>,
+    // Restores the chunks into the blockstore as they arrive.
+    pub pipeline: RestorePipeline,
+    // When the download was started, to estimate the remaining time.
+    pub started_at: Instant,
 }
 
 impl SnapshotDownload {
     pub fn parts_dir(&self) -> PathBuf {
         self.download_dir.path().join(PARTS_DIR_NAME)
     }
+
+    /// Summarize how far along we are with the download.
+    pub fn progress(&self) -> Stm<DownloadProgress> {
+        let status = self.pipeline.status()?;
+        let bytes_received = status.bytes;
+        let bytes_total = self.manifest.size;
+
+        let eta_secs = if bytes_received == 0 {
+            None
+        } else {
+            let elapsed = self.started_at.elapsed().as_secs_f64();
+            let remaining = bytes_total.saturating_sub(bytes_received) as f64;
+            Some((elapsed * remaining / bytes_received as f64).round() as u64)
+        };
+
+        Ok(DownloadProgress {
+            block_height: self.manifest.block_height,
+            chunks_received: *self.next_index.read()?,
+            chunks_total: self.manifest.chunks,
+            bytes_received,
+            bytes_total,
+            blocks_verified: status.blocks,
+            eta_secs,
+        })
+    }
+}
+
+/// Progress of an ongoing snapshot download, reported by the state-sync status query.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Height of the snapshot being downloaded.
+    pub block_height: BlockHeight,
+    pub chunks_received: u32,
+    pub chunks_total: u32,
+    pub bytes_received: u64,
+    pub bytes_total: u64,
+    /// Number of CAR blocks which have been checked against their CIDs and written to the blockstore.
+    pub blocks_verified: u64,
+    /// Estimated number of seconds until the download completes, based on the rate so far.
+    pub eta_secs: Option<u64>,
 }
 
 #[cfg(feature = "arb")]
@@ -0,0 +1,544 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Circuit breaking for the queries to the parent.
+//!
+//! The syncer and the finality provider query the parent concurrently, and each of them used to
+//! retry on its own, so when the parent endpoint went down, the retries piled up on top of each
+//! other. The breaker is shared by all of them: after a number of consecutive failures it opens,
+//! and the queries fail fast until it lets a single probe through to see if the parent recovered.
+//!
+//! Errors are categorized so that only the ones indicating a problem with the endpoint count as
+//! failures; null rounds and errors which would recur no matter how many times we retry don't.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ipc_provider::manager::{GetBlockHashResult, TopDownQueryPayload};
+use ipc_sdk::cross::CrossMsg;
+use ipc_sdk::staking::StakingChangeRequest;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use serde::Serialize;
+
+use crate::proxy::ParentQueryProxy;
+use crate::{is_null_round_str, BlockHeight, SubnetLabel};
+
+lazy_static! {
+    static ref PARENT_CIRCUIT_OPEN: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_parent_circuit_open",
+        "Whether the queries to the parent are failing fast because the parent is unreachable",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref PARENT_QUERY_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "fendermint_parent_query_errors",
+        "Number of failed queries to the parent by kind of error",
+        &["subnet", "kind"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Parts of error messages which indicate that the parent could not be reached.
+const NETWORK_ERRORS: &[&str] = &[
+    "error sending request",
+    "connection refused",
+    "connection reset",
+    "connection closed",
+    "broken pipe",
+    "dns error",
+    "tcp connect",
+    "timed out",
+    "timeout",
+];
+
+/// Parts of error messages which indicate that the parent answered, but is overloaded or lagging.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "429",
+    "too many requests",
+    "rate limit",
+    "502",
+    "503",
+    "504",
+    "bad gateway",
+    "service unavailable",
+    "gateway timeout",
+    "header not found",
+    "temporarily unavailable",
+];
+
+/// Parts of error messages which indicate that the same query would fail again.
+const PERMANENT_ERRORS: &[&str] = &[
+    "execution reverted",
+    "method not found",
+    "invalid params",
+    "invalid argument",
+    "abi decoding",
+    "invalid data",
+];
+
+/// The category of an error returned by a query to the parent.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentErrorKind {
+    /// The requested epoch was a null round; not an error with the parent.
+    NullRound,
+    /// The parent could not be reached.
+    Network,
+    /// The parent answered with an error which may go away, e.g. rate limiting.
+    Transient,
+    /// The query is not going to succeed no matter how many times it's retried.
+    Permanent,
+    /// The query wasn't sent, because the circuit breaker is open.
+    CircuitOpen,
+}
+
+impl ParentErrorKind {
+    pub fn of(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<CircuitOpen>().is_some() {
+            return Self::CircuitOpen;
+        }
+        // Look at the whole chain of causes; the outermost is usually just context.
+        let msg = format!("{e:#}").to_lowercase();
+        let contains = |patterns: &[&str]| patterns.iter().any(|p| msg.contains(p));
+
+        if is_null_round_str(&msg) {
+            Self::NullRound
+        } else if contains(NETWORK_ERRORS) {
+            Self::Network
+        } else if contains(TRANSIENT_ERRORS) {
+            Self::Transient
+        } else if contains(PERMANENT_ERRORS) {
+            Self::Permanent
+        } else {
+            // Anything we don't recognise is retried, the way every error used to be.
+            Self::Transient
+        }
+    }
+
+    /// Whether it's worth retrying the query.
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, Self::Network | Self::Transient | Self::CircuitOpen)
+    }
+
+    /// Whether the error indicates that something is wrong with the parent endpoint.
+    fn is_failure(&self) -> bool {
+        matches!(self, Self::Network | Self::Transient)
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::NullRound => "null_round",
+            Self::Network => "network",
+            Self::Transient => "transient",
+            Self::Permanent => "permanent",
+            Self::CircuitOpen => "circuit_open",
+        }
+    }
+}
+
+/// The error returned instead of querying the parent while the circuit is open.
+#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[error("the parent is unreachable; not querying it for another {retry_in:?}")]
+pub struct CircuitOpen {
+    /// How long until the breaker lets a probe through.
+    pub retry_in: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakerConfig {
+    /// Number of consecutive failures after which the circuit opens.
+    pub failure_threshold: u32,
+    /// How long to wait before probing the parent after the circuit opened.
+    pub open_duration: Duration,
+    /// The wait doubles every time a probe fails, up to this.
+    pub max_open_duration: Duration,
+    /// Label of the circuit state and query error metrics.
+    pub subnet_id: SubnetLabel,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(10),
+            max_open_duration: Duration::from_secs(300),
+            subnet_id: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Queries go through.
+    Closed,
+    /// Queries fail fast.
+    Open,
+    /// A single query is let through to probe the parent.
+    HalfOpen,
+}
+
+/// Health of the connection to the parent, as seen by the breaker.
+#[derive(Serialize, Debug, Clone)]
+pub struct ParentHealth {
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until the next probe, if the circuit is open.
+    pub retry_in_secs: Option<u64>,
+    /// Seconds since the last query which reached the parent, if there was one.
+    pub secs_since_success: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_error_kind: Option<ParentErrorKind>,
+    /// Number of errors since the start, by kind.
+    pub errors: BTreeMap<ParentErrorKind, u64>,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    circuit: CircuitState,
+    /// Until when the circuit stays open.
+    open_until: Instant,
+    /// How long the circuit stays open the next time it opens.
+    open_duration: Duration,
+    consecutive_failures: u32,
+    probe_in_flight: bool,
+    last_success: Option<Instant>,
+    last_error: Option<(ParentErrorKind, String)>,
+    errors: BTreeMap<ParentErrorKind, u64>,
+}
+
+/// A circuit breaker shared between all the queries to the parent.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        let state = BreakerState {
+            circuit: CircuitState::Closed,
+            open_until: Instant::now(),
+            open_duration: config.open_duration,
+            consecutive_failures: 0,
+            probe_in_flight: false,
+            last_success: None,
+            last_error: None,
+            errors: Default::default(),
+        };
+        Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Run a query unless the circuit is open, and record its outcome.
+    pub async fn call<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let mut permit = self.acquire(Instant::now())?;
+        let res = f.await;
+        match &res {
+            Ok(_) => permit.succeeded(Instant::now()),
+            Err(e) => permit.failed(Instant::now(), ParentErrorKind::of(e), e.to_string()),
+        }
+        res
+    }
+
+    /// Check whether a query can go ahead.
+    fn acquire(&self, now: Instant) -> Result<Permit, CircuitOpen> {
+        let mut state = self.lock();
+        match state.circuit {
+            CircuitState::Closed => {}
+            CircuitState::Open if now < state.open_until => {
+                return Err(state.rejected(now, &self.config));
+            }
+            CircuitState::Open => {
+                tracing::info!("probing the parent after the circuit breaker opened");
+                state.circuit = CircuitState::HalfOpen;
+                state.probe_in_flight = true;
+            }
+            CircuitState::HalfOpen if state.probe_in_flight => {
+                return Err(state.rejected(now, &self.config));
+            }
+            CircuitState::HalfOpen => {
+                state.probe_in_flight = true;
+            }
+        }
+        Ok(Permit {
+            breaker: self,
+            done: false,
+        })
+    }
+
+    /// The current health of the connection to the parent.
+    pub fn health(&self) -> ParentHealth {
+        let now = Instant::now();
+        let state = self.lock();
+        ParentHealth {
+            circuit: state.circuit,
+            consecutive_failures: state.consecutive_failures,
+            retry_in_secs: (state.circuit == CircuitState::Open)
+                .then(|| state.open_until.saturating_duration_since(now).as_secs()),
+            secs_since_success: state.last_success.map(|t| now.duration_since(t).as_secs()),
+            last_error: state.last_error.as_ref().map(|(_, e)| e.clone()),
+            last_error_kind: state.last_error.as_ref().map(|(k, _)| *k),
+            errors: state.errors.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().expect("circuit breaker poisoned")
+    }
+}
+
+impl BreakerState {
+    fn rejected(&mut self, now: Instant, config: &BreakerConfig) -> CircuitOpen {
+        self.count(ParentErrorKind::CircuitOpen, config);
+        CircuitOpen {
+            retry_in: self.open_until.saturating_duration_since(now),
+        }
+    }
+
+    fn count(&mut self, kind: ParentErrorKind, config: &BreakerConfig) {
+        *self.errors.entry(kind).or_default() += 1;
+        PARENT_QUERY_ERRORS
+            .with_label_values(&[&config.subnet_id, kind.as_str()])
+            .inc();
+    }
+
+    fn open(&mut self, now: Instant, config: &BreakerConfig) {
+        tracing::warn!(
+            consecutive_failures = self.consecutive_failures,
+            open_duration = ?self.open_duration,
+            "the parent is unreachable; opening the circuit breaker"
+        );
+        self.circuit = CircuitState::Open;
+        self.open_until = now + self.open_duration;
+        self.open_duration = (self.open_duration * 2).min(config.max_open_duration);
+        PARENT_CIRCUIT_OPEN
+            .with_label_values(&[&config.subnet_id])
+            .set(1);
+    }
+
+    fn close(&mut self, config: &BreakerConfig) {
+        if self.circuit != CircuitState::Closed {
+            tracing::info!("the parent is reachable again; closing the circuit breaker");
+        }
+        self.circuit = CircuitState::Closed;
+        self.open_duration = config.open_duration;
+        self.consecutive_failures = 0;
+        PARENT_CIRCUIT_OPEN
+            .with_label_values(&[&config.subnet_id])
+            .set(0);
+    }
+}
+
+/// Permission to send a query, which has to report the outcome.
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    done: bool,
+}
+
+impl Permit<'_> {
+    fn succeeded(&mut self, now: Instant) {
+        self.done = true;
+        let mut state = self.breaker.lock();
+        state.probe_in_flight = false;
+        state.last_success = Some(now);
+        state.close(&self.breaker.config);
+    }
+
+    fn failed(&mut self, now: Instant, kind: ParentErrorKind, error: String) {
+        self.done = true;
+        let mut state = self.breaker.lock();
+        state.probe_in_flight = false;
+        state.count(kind, &self.breaker.config);
+        state.last_error = Some((kind, error));
+
+        if !kind.is_failure() {
+            // The parent answered, so it's reachable.
+            state.last_success = Some(now);
+            state.close(&self.breaker.config);
+            return;
+        }
+
+        state.consecutive_failures += 1;
+        match state.circuit {
+            CircuitState::HalfOpen => state.open(now, &self.breaker.config),
+            CircuitState::Closed
+                if state.consecutive_failures >= self.breaker.config.failure_threshold =>
+            {
+                state.open(now, &self.breaker.config)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    /// Let another probe through if the query was cancelled.
+    fn drop(&mut self) {
+        if !self.done {
+            self.breaker.lock().probe_in_flight = false;
+        }
+    }
+}
+
+/// A [ParentQueryProxy] which stops querying the parent while it's unreachable.
+pub struct CircuitBreakerProxy<P> {
+    inner: P,
+    breaker: CircuitBreaker,
+}
+
+impl<P> CircuitBreakerProxy<P> {
+    pub fn new(inner: P, config: BreakerConfig) -> Self {
+        Self {
+            inner,
+            breaker: CircuitBreaker::new(config),
+        }
+    }
+
+    /// The breaker, to report on the health of the parent.
+    pub fn breaker(&self) -> CircuitBreaker {
+        self.breaker.clone()
+    }
+}
+
+#[async_trait]
+impl<P: ParentQueryProxy + Send + Sync> ParentQueryProxy for CircuitBreakerProxy<P> {
+    async fn get_chain_head_height(&self) -> anyhow::Result<BlockHeight> {
+        self.breaker.call(self.inner.get_chain_head_height()).await
+    }
+
+    async fn get_genesis_epoch(&self) -> anyhow::Result<BlockHeight> {
+        self.breaker.call(self.inner.get_genesis_epoch()).await
+    }
+
+    async fn get_block_hash(&self, height: BlockHeight) -> anyhow::Result<GetBlockHashResult> {
+        self.breaker.call(self.inner.get_block_hash(height)).await
+    }
+
+    async fn get_top_down_msgs(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<CrossMsg>>> {
+        self.breaker
+            .call(self.inner.get_top_down_msgs(height))
+            .await
+    }
+
+    async fn get_validator_changes(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<StakingChangeRequest>>> {
+        self.breaker
+            .call(self.inner.get_validator_changes(height))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use anyhow::anyhow;
+
+    use super::{BreakerConfig, CircuitBreaker, CircuitState, ParentErrorKind};
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            failure_threshold: 2,
+            open_duration: Duration::from_secs(10),
+            max_open_duration: Duration::from_secs(30),
+            ..Default::default()
+        })
+    }
+
+    fn fail(b: &CircuitBreaker, now: Instant, kind: ParentErrorKind) {
+        b.acquire(now)
+            .expect("should let the query through")
+            .failed(now, kind, "error".to_owned());
+    }
+
+    #[test]
+    fn classify_errors() {
+        let cases = [
+            (
+                anyhow!("requested epoch was a null round"),
+                ParentErrorKind::NullRound,
+            ),
+            (
+                anyhow!("tcp connect error: Connection refused").context("error sending request"),
+                ParentErrorKind::Network,
+            ),
+            (
+                anyhow!("(code: 3, message: execution reverted)"),
+                ParentErrorKind::Permanent,
+            ),
+            (
+                anyhow!("HTTP status client error (429 Too Many Requests)"),
+                ParentErrorKind::Transient,
+            ),
+            (anyhow!("something unexpected"), ParentErrorKind::Transient),
+        ];
+        for (e, kind) in cases {
+            assert_eq!(ParentErrorKind::of(&e), kind, "{e:#}");
+        }
+        assert!(!ParentErrorKind::NullRound.is_retriable());
+        assert!(!ParentErrorKind::Permanent.is_retriable());
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let b = breaker();
+        let now = Instant::now();
+
+        fail(&b, now, ParentErrorKind::Network);
+        // Errors which don't indicate a problem with the parent reset the count.
+        fail(&b, now, ParentErrorKind::NullRound);
+        fail(&b, now, ParentErrorKind::Network);
+        assert_eq!(b.health().circuit, CircuitState::Closed);
+
+        fail(&b, now, ParentErrorKind::Transient);
+        assert_eq!(b.health().circuit, CircuitState::Open);
+
+        let e = b.acquire(now + Duration::from_secs(4)).err().unwrap();
+        assert_eq!(e.retry_in, Duration::from_secs(6));
+        assert_eq!(b.health().errors[&ParentErrorKind::CircuitOpen], 1);
+    }
+
+    #[test]
+    fn single_probe_when_half_open() {
+        let b = breaker();
+        let now = Instant::now();
+        fail(&b, now, ParentErrorKind::Network);
+        fail(&b, now, ParentErrorKind::Network);
+
+        // The probe fails, which doubles the wait.
+        let now = now + Duration::from_secs(10);
+        let mut probe = b.acquire(now).expect("should let a probe through");
+        assert_eq!(b.health().circuit, CircuitState::HalfOpen);
+        assert!(b.acquire(now).is_err(), "only one probe at a time");
+        probe.failed(now, ParentErrorKind::Network, "error".to_owned());
+        drop(probe);
+        assert_eq!(b.health().circuit, CircuitState::Open);
+        assert!(b.acquire(now + Duration::from_secs(19)).is_err());
+
+        // A cancelled probe lets another one through.
+        let now = now + Duration::from_secs(20);
+        drop(b.acquire(now).unwrap());
+        let mut probe = b.acquire(now).expect("should let another probe through");
+        probe.succeeded(now);
+        drop(probe);
+
+        let health = b.health();
+        assert_eq!(health.circuit, CircuitState::Closed);
+        assert_eq!(health.consecutive_failures, 0);
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A safety switch which trips when the parent finality committed by the subnet turns out
+//! to conflict with what the parent chain finalized, e.g. after a deep reorg on the parent,
+//! or if a quorum of validators was fed a wrong view of the parent.
+//!
+//! The syncer checks the committed finality against the parent whenever it detects a reorg.
+//! Once tripped, the application takes the configured safety action, and unless the action
+//! is only to raise the alert, the node stops proposing parent finalities on top of the
+//! conflicting one, until the operator clears it. The application persists the conflict
+//! through [`Divergence::on_change`], so that a restart doesn't clear it.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::utils::hex;
+use lazy_static::lazy_static;
+use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockHash, BlockHeight, IPCParentFinality, SubnetLabel};
+
+lazy_static! {
+    static ref TOPDOWN_FINALITY_CONFLICT: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_topdown_finality_conflict",
+        "Whether the committed parent finality conflicts with what the parent chain finalized",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// What the application does when the committed parent finality conflicts with the parent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceAction {
+    /// Only raise the alert: log an error and set the metric.
+    #[default]
+    Alert,
+    /// Keep following the chain and answering queries, but refuse new transactions.
+    ReadOnly,
+    /// Stop processing blocks at the last committed height, the same way as the halt height.
+    Halt,
+}
+
+/// The committed parent finality which the parent chain disagrees with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalityConflict {
+    /// The parent height of the committed finality.
+    pub height: BlockHeight,
+    /// Hex encoded block hash in the committed finality.
+    pub committed_hash: String,
+    /// Hex encoded block hash the parent has at the same height now, or none if it's a null round.
+    pub parent_hash: Option<String>,
+    /// Seconds since the Unix epoch when the conflict was detected.
+    pub detected_at: u64,
+}
+
+impl FinalityConflict {
+    pub fn new(finality: &IPCParentFinality, parent_hash: Option<&BlockHash>) -> Self {
+        Self {
+            height: finality.height,
+            committed_hash: hex::encode(&finality.block_hash),
+            parent_hash: parent_hash.map(hex::encode),
+            detected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Called with the conflict when it's tripped, and with nothing when it's cleared.
+#[derive(Clone)]
+struct Listener(Arc<dyn Fn(Option<&FinalityConflict>) + Send + Sync>);
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Listener")
+    }
+}
+
+#[derive(Debug, Default)]
+struct DivergenceState {
+    conflict: Option<FinalityConflict>,
+    /// Whether the application has taken its safety action for the conflict.
+    handled: bool,
+    action: DivergenceAction,
+    listener: Option<Listener>,
+    /// Label of the finality conflict metric.
+    subnet_id: SubnetLabel,
+}
+
+impl DivergenceState {
+    fn set_metric(&self, tripped: bool) {
+        TOPDOWN_FINALITY_CONFLICT
+            .with_label_values(&[&self.subnet_id])
+            .set(tripped as i64);
+    }
+}
+
+/// The conflict detected by the syncer, if any, shared with the application.
+#[derive(Debug, Clone, Default)]
+pub struct Divergence(Arc<Mutex<DivergenceState>>);
+
+impl Divergence {
+    pub fn new(subnet_id: &str) -> Self {
+        Self(Arc::new(Mutex::new(DivergenceState {
+            subnet_id: subnet_id.to_owned(),
+            ..Default::default()
+        })))
+    }
+
+    /// Record a conflict, unless one has already been; returns whether it's new.
+    pub fn trip(&self, conflict: FinalityConflict) -> bool {
+        let mut state = self.0.lock().unwrap();
+        if state.conflict.is_some() {
+            return false;
+        }
+        tracing::error!(
+            height = conflict.height,
+            committed_hash = conflict.committed_hash,
+            parent_hash = conflict.parent_hash,
+            "the committed parent finality conflicts with the parent chain"
+        );
+        state.set_metric(true);
+        if let Some(Listener(ref f)) = state.listener {
+            f(Some(&conflict));
+        }
+        state.conflict = Some(conflict);
+        state.handled = false;
+        true
+    }
+
+    /// Bring back a conflict which was detected before a restart, without telling the
+    /// listener, which is where it came from; the safety action is taken again.
+    pub fn restore(&self, conflict: FinalityConflict) {
+        let mut state = self.0.lock().unwrap();
+        tracing::error!(
+            height = conflict.height,
+            committed_hash = conflict.committed_hash,
+            parent_hash = conflict.parent_hash,
+            "the committed parent finality was found to conflict with the parent chain before the restart"
+        );
+        state.set_metric(true);
+        state.conflict = Some(conflict);
+        state.handled = false;
+    }
+
+    /// Call `f` whenever a conflict is tripped or cleared, replacing any earlier listener.
+    pub fn on_change<F>(&self, f: F)
+    where
+        F: Fn(Option<&FinalityConflict>) + Send + Sync + 'static,
+    {
+        self.0.lock().unwrap().listener = Some(Listener(Arc::new(f)));
+    }
+
+    /// Set the safety action the application takes.
+    pub fn set_action(&self, action: DivergenceAction) {
+        self.0.lock().unwrap().action = action;
+    }
+
+    /// Whether parent finalities shouldn't be proposed, which is the case while there
+    /// is a conflict, unless the action is only to raise the alert.
+    pub fn blocks_proposals(&self) -> bool {
+        let state = self.0.lock().unwrap();
+        state.conflict.is_some() && state.action != DivergenceAction::Alert
+    }
+
+    pub fn conflict(&self) -> Option<FinalityConflict> {
+        self.0.lock().unwrap().conflict.clone()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.0.lock().unwrap().conflict.is_some()
+    }
+
+    /// Return the conflict the first time it's asked for after being detected,
+    /// so the safety action is taken only once.
+    pub fn take_unhandled(&self) -> Option<FinalityConflict> {
+        let mut state = self.0.lock().unwrap();
+        if state.handled {
+            return None;
+        }
+        state.handled = true;
+        state.conflict.clone()
+    }
+
+    /// Forget the conflict, after the operator looked into it; returns the one cleared.
+    pub fn clear(&self) -> Option<FinalityConflict> {
+        let mut state = self.0.lock().unwrap();
+        state.set_metric(false);
+        state.handled = false;
+        let conflict = state.conflict.take();
+        if let (Some(_), Some(Listener(ref f))) = (&conflict, &state.listener) {
+            f(None);
+        }
+        conflict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::hex;
+
+    use crate::IPCParentFinality;
+
+    use std::sync::{Arc, Mutex};
+
+    use super::{Divergence, DivergenceAction, FinalityConflict};
+
+    #[test]
+    fn trip_once_until_cleared() {
+        let divergence = Divergence::default();
+        assert!(divergence.take_unhandled().is_none());
+
+        let finality = IPCParentFinality::new(10, vec![1; 32]);
+        assert!(divergence.trip(FinalityConflict::new(&finality, Some(&vec![2; 32]))));
+        assert!(!divergence.trip(FinalityConflict::new(&finality, None)));
+
+        let conflict = divergence.conflict().unwrap();
+        assert_eq!(conflict.height, 10);
+        assert_eq!(conflict.parent_hash, Some(hex::encode([2; 32])));
+
+        assert_eq!(divergence.take_unhandled(), Some(conflict.clone()));
+        assert!(divergence.take_unhandled().is_none());
+        assert!(divergence.is_tripped());
+
+        assert_eq!(divergence.clear(), Some(conflict));
+        assert!(!divergence.is_tripped());
+        assert!(divergence.trip(FinalityConflict::new(&finality, None)));
+    }
+
+    #[test]
+    fn listener_sees_changes() {
+        let divergence = Divergence::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        {
+            let seen = seen.clone();
+            divergence.on_change(move |c| seen.lock().unwrap().push(c.map(|c| c.height)));
+        }
+
+        let finality = IPCParentFinality::new(10, vec![1; 32]);
+        divergence.trip(FinalityConflict::new(&finality, None));
+        divergence.trip(FinalityConflict::new(&finality, None));
+        divergence.clear();
+        divergence.clear();
+        assert_eq!(*seen.lock().unwrap(), vec![Some(10), None]);
+
+        // A restored conflict was persisted before, but the action has to be taken again.
+        divergence.restore(FinalityConflict::new(&finality, None));
+        assert!(divergence.take_unhandled().is_some());
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn only_alert_keeps_proposing() {
+        let divergence = Divergence::default();
+        let finality = IPCParentFinality::new(10, vec![1; 32]);
+        divergence.trip(FinalityConflict::new(&finality, None));
+        assert!(!divergence.blocks_proposals());
+
+        divergence.set_action(DivergenceAction::ReadOnly);
+        assert!(divergence.blocks_proposals());
+
+        divergence.clear();
+        assert!(!divergence.blocks_proposals());
+    }
+}
@@ -1,12 +1,15 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::divergence::Divergence;
 use crate::finality::null::FinalityWithNull;
 use crate::finality::ParentViewPayload;
+use crate::observe::Observations;
 use crate::proxy::ParentQueryProxy;
+use crate::voting::VoteTally;
 use crate::{
-    handle_null_round, BlockHash, BlockHeight, Config, Error, IPCParentFinality,
-    ParentFinalityProvider, ParentViewProvider,
+    handle_null_round, BlockHash, BlockHeight, CacheStats, Config, Error, FinalityMode,
+    IPCParentFinality, ParentFinalityProvider, ParentViewProvider,
 };
 use async_stm::{Stm, StmResult};
 use ipc_sdk::cross::CrossMsg;
@@ -20,9 +23,15 @@ pub struct CachedFinalityProvider<T> {
     config: Config,
     /// The ipc client proxy that works as a back up if cache miss
     parent_client: Arc<T>,
+    /// When the top down messages waiting to be finalized were first seen.
+    observations: Observations,
+    divergence: Divergence,
 }
 
-/// Exponential backoff for futures
+/// Exponential backoff for futures, only retrying the errors which can go away.
+///
+/// While the circuit breaker of the parent client is open, it waits at least until the
+/// breaker lets a probe through, rather than failing fast through all the retries.
 macro_rules! retry {
     ($wait:expr, $retires:expr, $f:expr) => {{
         let mut retries = $retires;
@@ -31,25 +40,35 @@ macro_rules! retry {
         loop {
             let res = $f;
             if let Err(e) = &res {
-                // there is no point in retrying if the current block is null round
-                if crate::is_null_round_str(&e.to_string()) {
+                let kind = crate::breaker::ParentErrorKind::of(e);
+
+                // there is no point in retrying null rounds, or errors which would recur
+                if !kind.is_retriable() {
                     tracing::warn!(
-                        "cannot query ipc parent_client due to null round, skip retry"
+                        error = e.to_string(),
+                        ?kind,
+                        "cannot query ipc parent_client, skip retry"
                     );
                     break res;
                 }
 
+                let wait_open = e
+                    .downcast_ref::<crate::breaker::CircuitOpen>()
+                    .map(|c| c.retry_in)
+                    .unwrap_or_default();
+
                 tracing::warn!(
                     error = e.to_string(),
+                    ?kind,
                     retries,
-                    wait = ?wait,
+                    wait = ?wait.max(wait_open),
                     "cannot query ipc parent_client"
                 );
 
                 if retries > 0 {
                     retries -= 1;
 
-                    tokio::time::sleep(wait).await;
+                    tokio::time::sleep(wait.max(wait_open)).await;
 
                     wait *= 2;
                     continue;
@@ -112,6 +131,10 @@ impl<T: ParentQueryProxy + Send + Sync + 'static> ParentFinalityProvider
     for CachedFinalityProvider<T>
 {
     fn next_proposal(&self) -> Stm<Option<IPCParentFinality>> {
+        // Nothing built on top of a finality the parent disagrees with can be valid.
+        if self.divergence.blocks_proposals() {
+            return Ok(None);
+        }
         self.inner.next_proposal()
     }
 
@@ -192,10 +215,13 @@ impl<T> CachedFinalityProvider<T> {
         parent_client: Arc<T>,
     ) -> Self {
         let inner = FinalityWithNull::new(config.clone(), genesis_epoch, committed_finality);
+        let divergence = Divergence::new(&config.subnet_id);
         Self {
             inner,
             config,
             parent_client,
+            observations: Observations::default(),
+            divergence,
         }
     }
 
@@ -221,6 +247,11 @@ impl<T> CachedFinalityProvider<T> {
         self.inner.reset(finality)
     }
 
+    /// Switch to the mode the chain chose in its genesis, once it's known.
+    pub fn set_mode(&self, mode: FinalityMode) -> Stm<()> {
+        self.inner.set_mode(mode)
+    }
+
     pub fn new_parent_view(
         &self,
         height: BlockHeight,
@@ -233,17 +264,39 @@ impl<T> CachedFinalityProvider<T> {
     pub fn cached_blocks(&self) -> Stm<BlockHeight> {
         self.inner.cached_blocks()
     }
+
+    /// Returns the occupancy of the cache.
+    pub fn cache_stats(&self) -> Stm<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    /// The votes of the validators about the parent blocks they have seen.
+    pub fn vote_tally(&self) -> &VoteTally {
+        self.inner.vote_tally()
+    }
+
+    /// When the top down messages waiting to be finalized were first seen by the syncer.
+    pub fn observations(&self) -> &Observations {
+        &self.observations
+    }
+
+    /// The conflict between the committed finality and the parent, if the syncer found one.
+    pub fn divergence(&self) -> &Divergence {
+        &self.divergence
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::finality::null::FinalityWithNull;
     use crate::finality::ParentViewPayload;
     use crate::proxy::ParentQueryProxy;
     use crate::{
-        BlockHeight, CachedFinalityProvider, Config, IPCParentFinality, ParentViewProvider,
+        BlockHeight, CachedFinalityProvider, Config, Error, IPCParentFinality, ParentViewProvider,
         SequentialKeyCache, NULL_ROUND_ERR_MSG,
     };
     use anyhow::anyhow;
+    use async_stm::{atomically, atomically_or_err};
     use async_trait::async_trait;
     use fvm_shared::address::Address;
     use fvm_shared::econ::TokenAmount;
@@ -338,11 +391,15 @@ mod tests {
         let config = Config {
             chain_head_delay: 2,
             polling_interval: Default::default(),
+            min_polling_interval: None,
             exponential_back_off: Default::default(),
             exponential_retry_limit: 0,
             max_proposal_range: Some(1),
             max_cache_blocks: None,
+            max_cache_msgs: None,
             proposal_delay: None,
+            mode: None,
+            subnet_id: String::new(),
         };
         let genesis_epoch = blocks.lower_bound().unwrap();
         let proxy = Arc::new(TestParentProxy { blocks });
@@ -389,9 +446,9 @@ mod tests {
         }
 
         impl Test {
-            async fn run(&self) -> Result<(), &'static str> {
+            async fn run(&self) -> anyhow::Result<()> {
                 self.nums_run.fetch_add(1, Ordering::SeqCst);
-                Err("mocked error")
+                Err(anyhow::anyhow!("mocked error"))
             }
         }
 
@@ -405,6 +462,19 @@ mod tests {
         assert_eq!(t.nums_run.load(Ordering::SeqCst), 3);
     }
 
+    #[tokio::test]
+    async fn test_no_retry_on_permanent_error() {
+        let nums_run = AtomicUsize::new(0);
+        let run = || async {
+            nums_run.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(anyhow::anyhow!("execution reverted"))
+        };
+
+        let res = retry!(Duration::from_secs(1), 2, run().await);
+        assert!(res.is_err());
+        assert_eq!(nums_run.load(Ordering::SeqCst), 1);
+    }
+
     #[tokio::test]
     async fn test_query_topdown_msgs() {
         let parent_blocks = new_parent_blocks!(
@@ -431,6 +501,51 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn test_evicted_payloads_are_fetched() {
+        let parent_blocks = new_parent_blocks!(
+            100 => Some((vec![0; 32], vec![], vec![])),   // genesis block
+            101 => Some((vec![1; 32], vec![new_validator_changes(1)], vec![])),
+            102 => Some((vec![2; 32], vec![new_validator_changes(2)], vec![])),
+            103 => Some((vec![3; 32], vec![new_validator_changes(3)], vec![]))
+        );
+        let mut provider = new_provider(parent_blocks.clone());
+        provider.config.max_cache_msgs = Some(2);
+        provider.inner = FinalityWithNull::new(
+            provider.config.clone(),
+            100,
+            Some(IPCParentFinality {
+                height: 100,
+                block_hash: vec![0; 32],
+            }),
+        );
+
+        atomically_or_err::<_, Error, _>(|| {
+            for h in 101..=103 {
+                provider.new_parent_view(h, parent_blocks.get_value(h).unwrap().clone())?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let stats = atomically(|| provider.cache_stats()).await;
+        assert_eq!(stats.blocks, 3);
+        assert_eq!(stats.msgs, 2);
+        assert_eq!(stats.evicted, 1);
+
+        // The hash of the evicted block is still known for proposals.
+        assert_eq!(
+            atomically(|| provider.block_hash(103)).await,
+            Some(vec![3; 32])
+        );
+
+        // The payload comes from the parent.
+        let changes = provider.validator_changes_from(101, 103).await.unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2].configuration_number, 3);
+    }
+
     #[tokio::test]
     async fn test_query_validator_changes() {
         let parent_blocks = new_parent_blocks!(
@@ -106,11 +106,15 @@ mod tests {
         let config = Config {
             chain_head_delay: 20,
             polling_interval: Duration::from_secs(10),
+            min_polling_interval: None,
             exponential_back_off: Duration::from_secs(10),
             exponential_retry_limit: 10,
             max_proposal_range: None,
             max_cache_blocks: None,
+            max_cache_msgs: None,
             proposal_delay: None,
+            mode: None,
+            subnet_id: String::new(),
         };
 
         CachedFinalityProvider::new(config, 10, Some(genesis_finality()), mocked_agent_proxy())
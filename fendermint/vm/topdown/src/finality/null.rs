@@ -4,22 +4,62 @@
 use crate::finality::{
     ensure_sequential, topdown_cross_msgs, validator_changes, ParentViewPayload,
 };
-use crate::{BlockHash, BlockHeight, Config, Error, IPCParentFinality, SequentialKeyCache};
+use crate::voting::VoteTally;
+use crate::{
+    BlockHash, BlockHeight, CacheStats, Config, Error, FinalityMode, IPCParentFinality,
+    SequentialKeyCache,
+};
 use async_stm::{abort, atomically, Stm, StmResult, TVar};
 use ipc_sdk::cross::CrossMsg;
 use ipc_sdk::staking::StakingChangeRequest;
 use std::cmp::min;
 
+/// A parent block as it is kept in the cache.
+#[derive(Clone, Debug)]
+enum CachedView {
+    /// The height was a null round on the parent.
+    Null,
+    /// The block hash with everything needed to execute it.
+    Full(ParentViewPayload),
+    /// Only the block hash is kept; the payload was dropped to keep the cache within
+    /// its size limit, and will be fetched from the parent again when it's needed.
+    Evicted(BlockHash),
+}
+
+impl CachedView {
+    fn block_hash(&self) -> Option<&BlockHash> {
+        match self {
+            CachedView::Null => None,
+            CachedView::Full(p) => Some(&p.0),
+            CachedView::Evicted(h) => Some(h),
+        }
+    }
+
+    /// Number of validator changes and top down messages held in memory.
+    fn num_msgs(&self) -> usize {
+        match self {
+            CachedView::Full(p) => p.1.len() + p.2.len(),
+            _ => 0,
+        }
+    }
+}
+
 /// Finality provider that can handle null blocks
 #[derive(Clone)]
 pub struct FinalityWithNull {
     config: Config,
     genesis_epoch: BlockHeight,
     /// Cached data that always syncs with the latest parent chain proactively
-    cached_data: TVar<SequentialKeyCache<BlockHeight, Option<ParentViewPayload>>>,
+    cached_data: TVar<SequentialKeyCache<BlockHeight, CachedView>>,
+    /// Number of payloads dropped from the cache since the start, to stay within its size limit.
+    evicted: TVar<u64>,
     /// This is a in memory view of the committed parent finality. We need this as a starting point
     /// for populating the cache
     last_committed_finality: TVar<Option<IPCParentFinality>>,
+    /// The parent blocks the other validators have seen, if they gossip about it.
+    votes: VoteTally,
+    /// The mode the chain chose; it's only known once the state can be queried.
+    mode: TVar<FinalityMode>,
 }
 
 impl FinalityWithNull {
@@ -28,11 +68,15 @@ impl FinalityWithNull {
         genesis_epoch: BlockHeight,
         committed_finality: Option<IPCParentFinality>,
     ) -> Self {
+        let votes = VoteTally::new(&config.subnet_id);
         Self {
+            mode: TVar::new(config.mode()),
             config,
             genesis_epoch,
             cached_data: TVar::new(SequentialKeyCache::sequential()),
+            evicted: TVar::new(0),
             last_committed_finality: TVar::new(committed_finality),
+            votes,
         }
     }
 
@@ -63,9 +107,22 @@ impl FinalityWithNull {
     /// Clear the cache and set the committed finality to the provided value
     pub fn reset(&self, finality: IPCParentFinality) -> Stm<()> {
         self.cached_data.write(SequentialKeyCache::sequential())?;
+        self.votes.set_finalized(finality.height)?;
         self.last_committed_finality.write(Some(finality))
     }
 
+    pub fn vote_tally(&self) -> &VoteTally {
+        &self.votes
+    }
+
+    pub fn mode(&self) -> Stm<FinalityMode> {
+        Ok(*self.mode.read()?)
+    }
+
+    pub fn set_mode(&self, mode: FinalityMode) -> Stm<()> {
+        self.mode.write(mode)
+    }
+
     pub fn new_parent_view(
         &self,
         height: BlockHeight,
@@ -79,6 +136,12 @@ impl FinalityWithNull {
     }
 
     pub fn next_proposal(&self) -> Stm<Option<IPCParentFinality>> {
+        // What the validators agree on trumps our own view, which they might not share yet.
+        if let Some(proposal) = self.propose_from_votes()? {
+            tracing::debug!(proposal = proposal.to_string(), "new proposal from votes");
+            return Ok(Some(proposal));
+        }
+
         let height = if let Some(h) = self.propose_next_height()? {
             h
         } else {
@@ -94,6 +157,9 @@ impl FinalityWithNull {
     }
 
     pub fn check_proposal(&self, proposal: &IPCParentFinality) -> Stm<bool> {
+        if self.mode()? == FinalityMode::Fast {
+            return self.check_proposal_fast(proposal);
+        }
         if !self.check_height(proposal)? {
             return Ok(false);
         }
@@ -116,6 +182,8 @@ impl FinalityWithNull {
             cache
         })?;
 
+        self.votes.set_finalized(height)?;
+
         self.last_committed_finality.write(Some(finality))
     }
 }
@@ -127,6 +195,16 @@ impl FinalityWithNull {
         Ok(cache.size() as BlockHeight)
     }
 
+    /// Returns the occupancy of the cache.
+    pub(crate) fn cache_stats(&self) -> Stm<CacheStats> {
+        let cache = self.cached_data.read()?;
+        Ok(CacheStats {
+            blocks: cache.size() as u64,
+            msgs: cache.values().map(|v| v.num_msgs()).sum::<usize>() as u64,
+            evicted: *self.evicted.read()?,
+        })
+    }
+
     pub(crate) fn block_hash_at_height(&self, height: BlockHeight) -> Stm<Option<BlockHash>> {
         if let Some(f) = self.last_committed_finality.read()?.as_ref() {
             if f.height == height {
@@ -134,7 +212,11 @@ impl FinalityWithNull {
             }
         }
 
-        self.get_at_height(height, |i| i.0.clone())
+        let cache = self.cached_data.read()?;
+        Ok(cache
+            .get_value(height)
+            .and_then(|v| v.block_hash())
+            .cloned())
     }
 
     pub(crate) fn latest_height_in_cache(&self) -> Stm<Option<BlockHeight>> {
@@ -162,14 +244,43 @@ impl FinalityWithNull {
         let cache = self.cached_data.read()?;
         Ok(cache.lower_bound().and_then(|lower_bound| {
             for h in (lower_bound..height).rev() {
-                if let Some(Some(_)) = cache.get_value(h) {
-                    return Some(h);
+                match cache.get_value(h) {
+                    Some(CachedView::Null) | None => {}
+                    Some(_) => return Some(h),
                 }
             }
             None
         }))
     }
 
+    /// Propose the highest block which validators with more than 2/3 of the power have seen,
+    /// within the range we can propose. If the validators aren't voting, or they haven't formed
+    /// a quorum about any block after the last committed one yet, it's up to our own view.
+    fn propose_from_votes(&self) -> Stm<Option<IPCParentFinality>> {
+        if !self.votes.has_power_table()? {
+            return Ok(None);
+        }
+
+        let last_committed_height = match self.last_committed_finality.read()?.as_ref() {
+            Some(f) => f.height,
+            None => return Ok(None),
+        };
+
+        let max_proposal_height = last_committed_height + self.config.max_proposal_range();
+
+        // Only propose what we can execute, i.e. blocks we have the same view of as the voters.
+        let has_block = |height, block_hash: &BlockHash| {
+            Ok(self.block_hash_at_height(height)?.as_ref() == Some(block_hash))
+        };
+
+        match self.votes.find_quorum(max_proposal_height, has_block)? {
+            Some((height, block_hash)) if height > last_committed_height => {
+                Ok(Some(IPCParentFinality { height, block_hash }))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn propose_next_height(&self) -> Stm<Option<BlockHeight>> {
         let latest_height = if let Some(h) = self.latest_height_in_cache()? {
             h
@@ -197,9 +308,13 @@ impl FinalityWithNull {
             };
 
         tracing::debug!(first_non_null_height, candidate_height);
-        // an extra layer of delay
-        let maybe_proposal_height =
-            self.first_non_null_block_before(first_non_null_height - self.config.proposal_delay())?;
+        // an extra layer of delay, unless we are in a hurry
+        let maybe_proposal_height = match self.mode()? {
+            FinalityMode::Safe => self.first_non_null_block_before(
+                first_non_null_height - self.config.proposal_delay(),
+            )?,
+            FinalityMode::Fast => Some(first_non_null_height),
+        };
         tracing::debug!(
             delayed_height = maybe_proposal_height,
             delay = self.config.proposal_delay()
@@ -231,26 +346,17 @@ impl FinalityWithNull {
         d: D,
     ) -> Stm<Option<T>> {
         let cache = self.cached_data.read()?;
-        Ok(cache.get_value(height).map(|v| {
-            if let Some(i) = v.as_ref() {
-                f(i)
-            } else {
+        Ok(match cache.get_value(height) {
+            None => None,
+            Some(CachedView::Full(p)) => Some(f(p)),
+            Some(CachedView::Null) => {
                 tracing::debug!(height, "a null round detected, return default");
-                d()
+                Some(d())
+            }
+            Some(CachedView::Evicted(_)) => {
+                tracing::debug!(height, "payload evicted from cache, needs to be fetched");
+                None
             }
-        }))
-    }
-
-    fn get_at_height<T, F: Fn(&ParentViewPayload) -> T>(
-        &self,
-        height: BlockHeight,
-        f: F,
-    ) -> Stm<Option<T>> {
-        let cache = self.cached_data.read()?;
-        Ok(if let Some(Some(v)) = cache.get_value(height) {
-            Some(f(v))
-        } else {
-            None
         })
     }
 
@@ -271,9 +377,34 @@ impl FinalityWithNull {
             ensure_sequential(&validator_changes, |change| change.configuration_number)?;
         }
 
+        // Only keep the payload in memory while the cache is within its limit; beyond that
+        // we only remember the block hash and go back to the parent when we need the rest.
+        let max_cache_msgs = self.config.max_cache_msgs();
+        let cached_msgs: usize = self
+            .cached_data
+            .read()?
+            .values()
+            .map(|v| v.num_msgs())
+            .sum();
+        let num_msgs = validator_changes.len() + top_down_msgs.len();
+
+        let view = if cached_msgs + num_msgs > max_cache_msgs {
+            tracing::debug!(
+                height,
+                cached_msgs,
+                num_msgs,
+                max_cache_msgs,
+                "cache full, evicting payload"
+            );
+            self.evicted.update(|n| n + 1)?;
+            CachedView::Evicted(block_hash)
+        } else {
+            CachedView::Full((block_hash, validator_changes, top_down_msgs))
+        };
+
         let r = self.cached_data.modify(|mut cache| {
             let r = cache
-                .append(height, Some((block_hash, validator_changes, top_down_msgs)))
+                .append(height, view)
                 .map_err(Error::NonSequentialParentViewInsert);
             (cache, r)
         })?;
@@ -289,7 +420,7 @@ impl FinalityWithNull {
     fn parent_null_round(&self, height: BlockHeight) -> StmResult<(), Error> {
         let r = self.cached_data.modify(|mut cache| {
             let r = cache
-                .append(height, None)
+                .append(height, CachedView::Null)
                 .map_err(Error::NonSequentialParentViewInsert);
             (cache, r)
         })?;
@@ -333,6 +464,45 @@ impl FinalityWithNull {
         }
     }
 
+    /// Accept anything which isn't committed yet and doesn't contradict our own view of the parent,
+    /// trusting the proposer about the heights we haven't seen yet; the data is fetched when needed.
+    ///
+    /// The trust only goes as far as a normal proposal could: no further than the maximum
+    /// proposal range past the committed height, and with a hash shaped like the committed one,
+    /// so a faulty proposer can't commit a height the parent will never reach or a junk hash.
+    fn check_proposal_fast(&self, proposal: &IPCParentFinality) -> Stm<bool> {
+        match self.last_committed_finality.read()?.as_ref() {
+            Some(f) if f.height < proposal.height => {
+                if proposal.height > f.height + self.config.max_proposal_range() {
+                    tracing::debug!(
+                        proposal = proposal.to_string(),
+                        committed = f.height,
+                        "proposal beyond the maximum range in fast mode"
+                    );
+                    return Ok(false);
+                }
+                if proposal.block_hash.len() != f.block_hash.len() {
+                    tracing::debug!(
+                        proposal = proposal.to_string(),
+                        "proposal with a malformed block hash in fast mode"
+                    );
+                    return Ok(false);
+                }
+            }
+            _ => return Ok(false),
+        }
+        match self.latest_height_in_cache()? {
+            Some(h) if h >= proposal.height => self.check_block_hash(proposal),
+            _ => {
+                tracing::debug!(
+                    proposal = proposal.to_string(),
+                    "accept unseen height in fast mode"
+                );
+                Ok(true)
+            }
+        }
+    }
+
     fn check_block_hash(&self, proposal: &IPCParentFinality) -> Stm<bool> {
         Ok(
             if let Some(block_hash) = self.block_hash_at_height(proposal.height)? {
@@ -350,20 +520,31 @@ impl FinalityWithNull {
 #[cfg(test)]
 mod tests {
     use crate::finality::{FinalityWithNull, ParentViewPayload};
-    use crate::{BlockHeight, Config, IPCParentFinality};
+    use crate::{BlockHeight, Config, FinalityMode, IPCParentFinality};
     use async_stm::{atomically, atomically_or_err};
 
     async fn new_provider(
+        blocks: Vec<(BlockHeight, Option<ParentViewPayload>)>,
+    ) -> FinalityWithNull {
+        new_provider_with_mode(blocks, None).await
+    }
+
+    async fn new_provider_with_mode(
         mut blocks: Vec<(BlockHeight, Option<ParentViewPayload>)>,
+        mode: Option<FinalityMode>,
     ) -> FinalityWithNull {
         let config = Config {
             chain_head_delay: 2,
             polling_interval: Default::default(),
+            min_polling_interval: None,
             exponential_back_off: Default::default(),
             exponential_retry_limit: 0,
             max_proposal_range: Some(6),
             max_cache_blocks: None,
+            max_cache_msgs: None,
             proposal_delay: Some(2),
+            mode,
+            subnet_id: String::new(),
         };
         let committed_finality = IPCParentFinality {
             height: blocks[0].0,
@@ -424,6 +605,166 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_proposal_from_votes() {
+        let parent_blocks = vec![
+            (100, Some((vec![0; 32], vec![], vec![]))), // last committed block
+            (101, Some((vec![1; 32], vec![], vec![]))),
+            (102, Some((vec![2; 32], vec![], vec![]))), // local proposal height
+            (103, Some((vec![3; 32], vec![], vec![]))),
+            (104, Some((vec![4; 32], vec![], vec![]))),
+            (105, Some((vec![5; 32], vec![], vec![]))),
+            (106, Some((vec![6; 32], vec![], vec![]))), // max proposal height
+            (107, Some((vec![7; 32], vec![], vec![]))),
+            (108, Some((vec![8; 32], vec![], vec![]))),
+        ];
+        let provider = new_provider(parent_blocks).await;
+        let votes = provider.vote_tally();
+        let (a, b, c) = (vec![1u8], vec![2u8], vec![3u8]);
+
+        let proposal = |height, hash| {
+            Some(IPCParentFinality {
+                height,
+                block_hash: vec![hash; 32],
+            })
+        };
+
+        // Without a quorum, it's our own view.
+        atomically(|| {
+            votes.set_power_table(vec![(a.clone(), 1), (b.clone(), 1), (c.clone(), 1)])?;
+            votes.add_vote(a.clone(), 104, vec![4; 32])?;
+            votes.add_vote(b.clone(), 104, vec![4; 32])?;
+            Ok(())
+        })
+        .await;
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            proposal(102, 2)
+        );
+
+        // Beyond our view, but within the proposal range.
+        atomically(|| {
+            votes.add_vote(c.clone(), 104, vec![4; 32])?;
+            for v in [&a, &b, &c] {
+                votes.add_vote(v.clone(), 108, vec![8; 32])?;
+            }
+            Ok(())
+        })
+        .await;
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            proposal(104, 4)
+        );
+
+        // Not a block we have, so we can't execute it.
+        atomically(|| {
+            for v in [&a, &b, &c] {
+                votes.add_vote(v.clone(), 105, vec![9; 32])?;
+            }
+            Ok(())
+        })
+        .await;
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            proposal(104, 4)
+        );
+
+        // Committed heights are forgotten.
+        atomically(|| {
+            let last = provider.last_committed_finality.read_clone()?;
+            provider.set_new_finality(proposal(104, 4).unwrap(), last)
+        })
+        .await;
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            proposal(108, 8)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fast_mode() {
+        // max_proposal_range is 6; the proposal_delay is ignored
+        let parent_blocks = vec![
+            (100, Some((vec![0; 32], vec![], vec![]))), // last committed block
+            (101, Some((vec![1; 32], vec![], vec![]))),
+            (102, Some((vec![2; 32], vec![], vec![]))),
+            (103, Some((vec![3; 32], vec![], vec![]))),
+            (104, Some((vec![4; 32], vec![], vec![]))),
+            (105, Some((vec![5; 32], vec![], vec![]))), // final proposal height
+            (106, Some((vec![6; 32], vec![], vec![]))), // max proposal height
+            (107, Some((vec![7; 32], vec![], vec![]))), // cache latest height
+        ];
+        let provider = new_provider_with_mode(parent_blocks, Some(FinalityMode::Fast)).await;
+
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            Some(IPCParentFinality {
+                height: 105,
+                block_hash: vec![5; 32]
+            })
+        );
+
+        let check = |height, hash| {
+            let provider = provider.clone();
+            async move {
+                atomically(|| {
+                    provider.check_proposal(&IPCParentFinality {
+                        height,
+                        block_hash: vec![hash; 32],
+                    })
+                })
+                .await
+            }
+        };
+
+        // Already committed.
+        assert!(!check(100, 0).await);
+        // Seen, but with a different hash.
+        assert!(!check(104, 5).await);
+        assert!(check(104, 4).await);
+        // Not seen yet, but beyond the range a normal proposal could reach.
+        assert!(!check(110, 9).await);
+        assert!(!check(u64::MAX, 9).await);
+    }
+
+    #[tokio::test]
+    async fn test_mode_set_later() {
+        let parent_blocks = vec![
+            (100, Some((vec![0; 32], vec![], vec![]))), // last committed block
+            (101, Some((vec![1; 32], vec![], vec![]))),
+            (102, Some((vec![2; 32], vec![], vec![]))),
+            (103, Some((vec![3; 32], vec![], vec![]))),
+            (104, Some((vec![4; 32], vec![], vec![]))),
+        ];
+        let provider = new_provider(parent_blocks).await;
+        let unseen = IPCParentFinality {
+            height: 105,
+            block_hash: vec![5; 32],
+        };
+
+        assert!(!atomically(|| provider.check_proposal(&unseen)).await);
+
+        atomically(|| provider.set_mode(FinalityMode::Fast)).await;
+
+        // Not seen yet, so the proposer is trusted, as long as the hash looks like the others.
+        assert!(atomically(|| provider.check_proposal(&unseen)).await);
+
+        let long_hash = IPCParentFinality {
+            height: 105,
+            block_hash: vec![5; 1024],
+        };
+        assert!(!atomically(|| provider.check_proposal(&long_hash)).await);
+
+        // The proposal delay doesn't apply any more.
+        assert_eq!(
+            atomically(|| provider.next_proposal()).await,
+            Some(IPCParentFinality {
+                height: 103,
+                block_hash: vec![3; 32]
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_not_enough_view() {
         // max_proposal_range is 6. proposal_delay is 2
@@ -1,14 +1,19 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod breaker;
 mod cache;
+pub mod divergence;
 mod error;
 mod finality;
+pub mod observe;
 pub mod sync;
 
 pub mod convert;
 pub mod proxy;
+pub mod relayer;
 mod toggle;
+pub mod voting;
 
 use async_stm::Stm;
 use async_trait::async_trait;
@@ -29,11 +34,17 @@ pub type BlockHeight = u64;
 pub type Bytes = Vec<u8>;
 pub type BlockHash = Bytes;
 
+/// The subnet ID a component labels its metrics with, to tell apart the subnets
+/// hosted in the same process. Empty when there's only one.
+pub type SubnetLabel = String;
+
 /// The null round error message
-pub(crate) const NULL_ROUND_ERR_MSG: &str = "requested epoch was a null round";
+pub const NULL_ROUND_ERR_MSG: &str = "requested epoch was a null round";
 /// Default topdown proposal height range
 pub(crate) const DEFAULT_MAX_PROPOSAL_RANGE: BlockHeight = 100;
 pub(crate) const DEFAULT_MAX_CACHE_BLOCK: BlockHeight = 500;
+pub(crate) const DEFAULT_MAX_CACHE_MSGS: usize = 10_000;
+pub(crate) const DEFAULT_MIN_POLLING_INTERVAL: Duration = Duration::from_secs(1);
 pub(crate) const DEFAULT_PROPOSAL_DELAY: BlockHeight = 2;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -43,8 +54,11 @@ pub struct Config {
     /// conservative and avoid other from rejecting the proposal because they don't see the
     /// height as final yet.
     pub chain_head_delay: BlockHeight,
-    /// Parent syncing cron period, in seconds
+    /// Parent syncing cron period, in seconds; the longest the syncer waits between polls,
+    /// which it does when it has caught up with the parent and doesn't know its block time yet.
     pub polling_interval: Duration,
+    /// The shortest the syncer waits between polls, which it does while catching up with the parent.
+    pub min_polling_interval: Option<Duration>,
     /// Top down exponential back off retry base
     pub exponential_back_off: Duration,
     /// The max number of retries for exponential backoff before giving up
@@ -53,7 +67,31 @@ pub struct Config {
     pub max_proposal_range: Option<BlockHeight>,
     /// Max number of blocks that should be stored in cache
     pub max_cache_blocks: Option<BlockHeight>,
+    /// Max number of validator changes and top down messages to hold in the cache;
+    /// beyond this only block hashes are kept, and the rest is fetched again when needed.
+    pub max_cache_msgs: Option<usize>,
     pub proposal_delay: Option<BlockHeight>,
+    /// How cautious to be about what is final on the parent; see [FinalityMode].
+    ///
+    /// The chain chooses it in its genesis, so it's only set once the state can be queried.
+    pub mode: Option<FinalityMode>,
+    /// Label of the metrics of the parent syncer and the finality provider.
+    #[serde(default)]
+    pub subnet_id: SubnetLabel,
+}
+
+/// How the parent finality is observed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalityMode {
+    /// Only heights past the `chain_head_delay` and the `proposal_delay` are proposed, and
+    /// validators only vote for proposals which agree with their own view of the parent.
+    #[default]
+    Safe,
+    /// Fast but unsafe, meant for local development: the delays are ignored, and validators
+    /// vote for proposals of heights they haven't seen yet, trusting the proposer, instead of
+    /// waiting for a quorum of them to see the height as final.
+    Fast,
 }
 
 impl Config {
@@ -66,11 +104,15 @@ impl Config {
         Self {
             chain_head_delay,
             polling_interval,
+            min_polling_interval: None,
             exponential_back_off,
             exponential_retry_limit,
             max_proposal_range: None,
             max_cache_blocks: None,
+            max_cache_msgs: None,
             proposal_delay: None,
+            mode: None,
+            subnet_id: String::new(),
         }
     }
 
@@ -79,23 +121,85 @@ impl Config {
         self
     }
 
+    pub fn with_max_cache_blocks(mut self, max_cache_blocks: BlockHeight) -> Self {
+        self.max_cache_blocks = Some(max_cache_blocks);
+        self
+    }
+
+    pub fn with_max_cache_msgs(mut self, max_cache_msgs: usize) -> Self {
+        self.max_cache_msgs = Some(max_cache_msgs);
+        self
+    }
+
+    pub fn with_min_polling_interval(mut self, min_polling_interval: Duration) -> Self {
+        self.min_polling_interval = Some(min_polling_interval);
+        self
+    }
+
     pub fn with_proposal_delay(mut self, proposal_delay: BlockHeight) -> Self {
         self.proposal_delay = Some(proposal_delay);
         self
     }
 
+    pub fn with_mode(mut self, mode: FinalityMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn with_subnet_id(mut self, subnet_id: String) -> Self {
+        self.subnet_id = subnet_id;
+        self
+    }
+
+    pub fn mode(&self) -> FinalityMode {
+        self.mode.unwrap_or_default()
+    }
+
+    /// The number of blocks to stay behind the parent chain head; none in fast mode.
+    pub fn chain_head_delay(&self) -> BlockHeight {
+        match self.mode() {
+            FinalityMode::Safe => self.chain_head_delay,
+            FinalityMode::Fast => 0,
+        }
+    }
+
     pub fn max_proposal_range(&self) -> BlockHeight {
         self.max_proposal_range
             .unwrap_or(DEFAULT_MAX_PROPOSAL_RANGE)
     }
 
+    /// The number of blocks to wait on top of the `chain_head_delay`; none in fast mode.
     pub fn proposal_delay(&self) -> BlockHeight {
-        self.proposal_delay.unwrap_or(DEFAULT_PROPOSAL_DELAY)
+        match self.mode() {
+            FinalityMode::Safe => self.proposal_delay.unwrap_or(DEFAULT_PROPOSAL_DELAY),
+            FinalityMode::Fast => 0,
+        }
     }
 
     pub fn max_cache_blocks(&self) -> BlockHeight {
         self.max_cache_blocks.unwrap_or(DEFAULT_MAX_CACHE_BLOCK)
     }
+
+    pub fn min_polling_interval(&self) -> Duration {
+        self.min_polling_interval
+            .unwrap_or(DEFAULT_MIN_POLLING_INTERVAL)
+            .min(self.polling_interval)
+    }
+
+    pub fn max_cache_msgs(&self) -> usize {
+        self.max_cache_msgs.unwrap_or(DEFAULT_MAX_CACHE_MSGS)
+    }
+}
+
+/// Occupancy of the parent view cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of parent blocks in the cache, including null rounds.
+    pub blocks: u64,
+    /// Number of validator changes and top down messages held in memory.
+    pub msgs: u64,
+    /// Number of payloads dropped from the cache since the start.
+    pub evicted: u64,
 }
 
 /// The finality view for IPC parent at certain height.
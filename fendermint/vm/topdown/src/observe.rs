@@ -0,0 +1,110 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The time this node first saw each top-down message on the parent, so that the application
+//! can tell how long the messages took to be proposed and executed once they are finalized.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use ipc_sdk::cross::CrossMsg;
+
+use crate::BlockHeight;
+
+/// When a top-down message was seen by the syncer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+    /// Height of the parent block the message was sent in.
+    pub parent_height: BlockHeight,
+    pub time: SystemTime,
+}
+
+/// The observations of the top-down messages which haven't been finalized yet, by their nonce.
+#[derive(Clone, Default)]
+pub struct Observations(Arc<Mutex<BTreeMap<u64, Observation>>>);
+
+impl Observations {
+    /// Record the messages of a parent block; the ones seen before, e.g. before a reorg,
+    /// keep their original time.
+    pub fn observe(&self, parent_height: BlockHeight, msgs: &[CrossMsg], time: SystemTime) {
+        let mut observed = self.0.lock().unwrap();
+        for msg in msgs {
+            observed.entry(msg.msg.nonce).or_insert(Observation {
+                parent_height,
+                time,
+            });
+        }
+    }
+
+    /// Remove the messages executed by the finality of a parent height, which are
+    /// the ones sent in the blocks before it, in the order of their nonces.
+    ///
+    /// Only the ones the gateway has applied, i.e. with a nonce below `next_nonce`, are
+    /// removed; the ones deferred to later blocks stay until they are applied too.
+    pub fn take_finalized(&self, height: BlockHeight, next_nonce: u64) -> Vec<(u64, Observation)> {
+        let mut observed = self.0.lock().unwrap();
+        let finalized = observed
+            .range(..next_nonce)
+            .filter(|(_, o)| o.parent_height < height)
+            .map(|(nonce, o)| (*nonce, *o))
+            .collect::<Vec<_>>();
+        for (nonce, _) in finalized.iter() {
+            observed.remove(nonce);
+        }
+        finalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use ipc_sdk::cross::{CrossMsg, StorableMsg};
+    use ipc_sdk::subnet_id::SubnetID;
+
+    use super::Observations;
+
+    fn cross_msg(nonce: u64) -> CrossMsg {
+        let subnet_id = SubnetID::new(10, vec![Address::new_id(1000)]);
+        let mut msg = StorableMsg::new_fund_msg(
+            &subnet_id,
+            &Address::new_id(1),
+            &Address::new_id(2),
+            TokenAmount::from_atto(100),
+        )
+        .unwrap();
+        msg.nonce = nonce;
+
+        CrossMsg {
+            msg,
+            wrapped: false,
+        }
+    }
+
+    #[test]
+    fn take_finalized_in_nonce_order() {
+        let observations = Observations::default();
+        let t0 = SystemTime::UNIX_EPOCH;
+        let t1 = t0 + Duration::from_secs(1);
+
+        observations.observe(10, &[cross_msg(1), cross_msg(2)], t0);
+        observations.observe(11, &[cross_msg(3)], t1);
+        // Seen again after a reorg, later.
+        observations.observe(10, &[cross_msg(2)], t1);
+
+        // The finality of height 10 executes the messages up to height 9.
+        assert!(observations.take_finalized(10, 4).is_empty());
+
+        let taken = observations.take_finalized(11, 4);
+        assert_eq!(
+            taken.iter().map(|(n, o)| (*n, o.time)).collect::<Vec<_>>(),
+            vec![(1, t0), (2, t0)]
+        );
+        // The ones deferred to later blocks aren't taken until they are applied.
+        assert!(observations.take_finalized(12, 3).is_empty());
+        assert_eq!(observations.take_finalized(12, 4).len(), 1);
+    }
+}
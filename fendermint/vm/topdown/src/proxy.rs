@@ -10,6 +10,7 @@ use ipc_provider::IpcProvider;
 use ipc_sdk::cross::CrossMsg;
 use ipc_sdk::staking::StakingChangeRequest;
 use ipc_sdk::subnet_id::SubnetID;
+use std::sync::Arc;
 
 /// The interface to querying state of the parent
 #[async_trait]
@@ -37,6 +38,41 @@ pub trait ParentQueryProxy {
     ) -> anyhow::Result<TopDownQueryPayload<Vec<StakingChangeRequest>>>;
 }
 
+/// A proxy picked at runtime, e.g. to record what the parent answered.
+pub type DynParentQueryProxy = Arc<dyn ParentQueryProxy + Send + Sync>;
+
+#[async_trait]
+impl<P> ParentQueryProxy for Arc<P>
+where
+    P: ParentQueryProxy + Send + Sync + ?Sized,
+{
+    async fn get_chain_head_height(&self) -> anyhow::Result<BlockHeight> {
+        self.as_ref().get_chain_head_height().await
+    }
+
+    async fn get_genesis_epoch(&self) -> anyhow::Result<BlockHeight> {
+        self.as_ref().get_genesis_epoch().await
+    }
+
+    async fn get_block_hash(&self, height: BlockHeight) -> anyhow::Result<GetBlockHashResult> {
+        self.as_ref().get_block_hash(height).await
+    }
+
+    async fn get_top_down_msgs(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<CrossMsg>>> {
+        self.as_ref().get_top_down_msgs(height).await
+    }
+
+    async fn get_validator_changes(
+        &self,
+        height: BlockHeight,
+    ) -> anyhow::Result<TopDownQueryPayload<Vec<StakingChangeRequest>>> {
+        self.as_ref().get_validator_changes(height).await
+    }
+}
+
 /// The proxy to the subnet's parent
 pub struct IPCProviderProxy {
     ipc_provider: IpcProvider,
@@ -62,6 +98,7 @@ impl IPCProviderProxy {
 
 #[async_trait]
 impl ParentQueryProxy for IPCProviderProxy {
+    #[tracing::instrument(skip(self))]
     async fn get_chain_head_height(&self) -> anyhow::Result<BlockHeight> {
         let height = self.ipc_provider.chain_head(&self.parent_subnet).await?;
         Ok(height as BlockHeight)
@@ -69,12 +106,14 @@ impl ParentQueryProxy for IPCProviderProxy {
 
     /// Get the genesis epoch of the child subnet, i.e. the epoch that the subnet was created in
     /// the parent subnet.
+    #[tracing::instrument(skip(self))]
     async fn get_genesis_epoch(&self) -> anyhow::Result<BlockHeight> {
         let height = self.ipc_provider.genesis_epoch(&self.child_subnet).await?;
         Ok(height as BlockHeight)
     }
 
     /// Getting the block hash at the target height.
+    #[tracing::instrument(skip(self))]
     async fn get_block_hash(&self, height: BlockHeight) -> anyhow::Result<GetBlockHashResult> {
         self.ipc_provider
             .get_block_hash(&self.parent_subnet, height as ChainEpoch)
@@ -82,6 +121,7 @@ impl ParentQueryProxy for IPCProviderProxy {
     }
 
     /// Get the top down messages from the starting to the ending height.
+    #[tracing::instrument(skip(self))]
     async fn get_top_down_msgs(
         &self,
         height: BlockHeight,
@@ -97,6 +137,7 @@ impl ParentQueryProxy for IPCProviderProxy {
     }
 
     /// Get the validator set at the specified height.
+    #[tracing::instrument(skip(self))]
     async fn get_validator_changes(
         &self,
         height: BlockHeight,
@@ -0,0 +1,357 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Monitoring of the account which submits the bottom-up checkpoints to the parent.
+//!
+//! The relayer pays for the submissions from its balance on the parent, and if it runs dry,
+//! checkpoints silently stop flowing, because nothing fails in the subnet itself. The monitor
+//! estimates what a submission costs with the current base fee and gas premium of the parent,
+//! and raises an alert if the balance covers fewer submissions than the configured minimum.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, BlockNumber, U256};
+use lazy_static::lazy_static;
+use prometheus::{register_gauge_vec, register_int_gauge_vec, GaugeVec, IntGaugeVec};
+use serde::{Serialize, Serializer};
+
+use crate::SubnetLabel;
+
+lazy_static! {
+    static ref RELAYER_BALANCE: GaugeVec = register_gauge_vec!(
+        "fendermint_relayer_balance",
+        "Balance of the checkpoint relayer account on the parent, in whole tokens",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref RELAYER_BASE_FEE: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_relayer_parent_base_fee",
+        "Base fee on the parent when the relayer was last checked, in atto per gas",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref RELAYER_GAS_PREMIUM: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_relayer_parent_gas_premium",
+        "Estimated gas premium on the parent when the relayer was last checked, in atto per gas",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref RELAYER_CHECKPOINTS_COVERED: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_relayer_checkpoints_covered",
+        "Number of checkpoint submissions the balance of the relayer covers at current prices",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref RELAYER_UNDERFUNDED: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_relayer_underfunded",
+        "Whether the relayer covers fewer checkpoint submissions than the configured minimum",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Number of atto in a whole token.
+const ATTO_PER_TOKEN: f64 = 1e18;
+
+#[derive(Debug, Clone)]
+pub struct RelayerConfig {
+    /// The account which submits the checkpoints on the parent.
+    pub address: Address,
+    /// The gas limit of a checkpoint submission.
+    pub checkpoint_gas_limit: u64,
+    /// Raise an alert if the balance covers fewer submissions than this.
+    pub min_checkpoints: u64,
+    /// How often to check the balance and the gas prices.
+    pub check_interval: Duration,
+    /// Label of the balance and gas price metrics.
+    pub subnet_id: SubnetLabel,
+}
+
+/// Gas prices on the parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParentGasPrices {
+    pub base_fee: U256,
+    pub gas_premium: U256,
+}
+
+impl ParentGasPrices {
+    /// The fee cap to submit with, which leaves room for the base fee to double
+    /// before the message is included, the same way `ethers` estimates it.
+    pub fn gas_fee_cap(&self) -> U256 {
+        self.base_fee
+            .saturating_mul(2.into())
+            .saturating_add(self.gas_premium)
+    }
+}
+
+/// What the relayer can afford at the current prices of the parent.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RelayerStatus {
+    pub address: Address,
+    /// The parent height the balance and the prices were queried at.
+    pub parent_height: u64,
+    #[serde(serialize_with = "decimal")]
+    pub balance: U256,
+    #[serde(serialize_with = "decimal")]
+    pub base_fee: U256,
+    #[serde(serialize_with = "decimal")]
+    pub gas_premium: U256,
+    #[serde(serialize_with = "decimal")]
+    pub gas_fee_cap: U256,
+    /// The most a checkpoint submission can cost: the gas limit times the fee cap,
+    /// which is also what the balance has to cover for the parent to accept it.
+    #[serde(serialize_with = "decimal")]
+    pub checkpoint_cost: U256,
+    pub checkpoints_covered: u64,
+    /// Whether the balance covers at least one submission.
+    pub can_submit: bool,
+    /// Whether the balance covers fewer submissions than the configured minimum.
+    pub underfunded: bool,
+}
+
+impl RelayerStatus {
+    pub fn new(
+        config: &RelayerConfig,
+        parent_height: u64,
+        balance: U256,
+        prices: ParentGasPrices,
+    ) -> Self {
+        let gas_fee_cap = prices.gas_fee_cap();
+        let checkpoint_cost = gas_fee_cap.saturating_mul(config.checkpoint_gas_limit.into());
+
+        let checkpoints_covered = if checkpoint_cost.is_zero() {
+            u64::MAX
+        } else {
+            let n = balance / checkpoint_cost;
+            if n > U256::from(u64::MAX) {
+                u64::MAX
+            } else {
+                n.as_u64()
+            }
+        };
+
+        Self {
+            address: config.address,
+            parent_height,
+            balance,
+            base_fee: prices.base_fee,
+            gas_premium: prices.gas_premium,
+            gas_fee_cap,
+            checkpoint_cost,
+            checkpoints_covered,
+            can_submit: checkpoints_covered > 0,
+            underfunded: checkpoints_covered < config.min_checkpoints,
+        }
+    }
+}
+
+fn decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// The latest status of the relayer, shared with the admin endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct RelayerStatusHandle(Arc<RwLock<Option<RelayerStatus>>>);
+
+impl RelayerStatusHandle {
+    /// The status at the last successful check, if there was one.
+    pub fn get(&self) -> Option<RelayerStatus> {
+        self.0.read().expect("relayer status poisoned").clone()
+    }
+
+    fn set(&self, status: RelayerStatus) {
+        *self.0.write().expect("relayer status poisoned") = Some(status);
+    }
+}
+
+/// Periodically checks the balance of the relayer against the gas prices of the parent.
+pub struct RelayerMonitor {
+    config: RelayerConfig,
+    provider: Provider<Http>,
+    status: RelayerStatusHandle,
+}
+
+impl RelayerMonitor {
+    pub fn new(config: RelayerConfig, parent_http_endpoint: &str) -> anyhow::Result<Self> {
+        let provider = Provider::<Http>::try_from(parent_http_endpoint)
+            .context("failed to create parent provider")?;
+        Ok(Self {
+            config,
+            provider,
+            status: RelayerStatusHandle::default(),
+        })
+    }
+
+    pub fn status(&self) -> RelayerStatusHandle {
+        self.status.clone()
+    }
+
+    /// Estimate the gas prices on the parent; the premium is what the parent suggests
+    /// to get a message included in the next few blocks.
+    pub async fn gas_prices(&self) -> anyhow::Result<(u64, ParentGasPrices)> {
+        let block = self
+            .provider
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or_else(|| anyhow!("the parent has no latest block"))?;
+
+        let gas_premium: U256 = self
+            .provider
+            .request("eth_maxPriorityFeePerGas", ())
+            .await
+            .context("failed to estimate the gas premium")?;
+
+        let prices = ParentGasPrices {
+            base_fee: block.base_fee_per_gas.unwrap_or_default(),
+            gas_premium,
+        };
+
+        Ok((block.number.unwrap_or_default().as_u64(), prices))
+    }
+
+    /// Check what the relayer can afford at the current prices.
+    pub async fn check(&self) -> anyhow::Result<RelayerStatus> {
+        let (height, prices) = self.gas_prices().await?;
+
+        let balance = self
+            .provider
+            .get_balance(self.config.address, Some(height.into()))
+            .await
+            .context("failed to get the relayer balance")?;
+
+        Ok(RelayerStatus::new(&self.config, height, balance, prices))
+    }
+
+    /// Check the relayer until the process exits.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.config.check_interval);
+        loop {
+            interval.tick().await;
+            match self.check().await {
+                Ok(status) => {
+                    record_metrics(&self.config.subnet_id, &status);
+                    if !status.can_submit {
+                        tracing::error!(
+                            address = ?status.address,
+                            balance = status.balance.to_string(),
+                            checkpoint_cost = status.checkpoint_cost.to_string(),
+                            "the relayer cannot afford to submit checkpoints to the parent"
+                        );
+                    } else if status.underfunded {
+                        tracing::warn!(
+                            address = ?status.address,
+                            balance = status.balance.to_string(),
+                            checkpoints_covered = status.checkpoints_covered,
+                            min_checkpoints = self.config.min_checkpoints,
+                            "the relayer is running out of funds on the parent"
+                        );
+                    }
+                    self.status.set(status);
+                }
+                Err(e) => {
+                    tracing::warn!(error = e.to_string(), "failed to check the relayer");
+                }
+            }
+        }
+    }
+}
+
+fn record_metrics(subnet_id: &str, status: &RelayerStatus) {
+    let label = [subnet_id];
+    RELAYER_BALANCE
+        .with_label_values(&label)
+        .set(to_f64(status.balance) / ATTO_PER_TOKEN);
+    RELAYER_BASE_FEE
+        .with_label_values(&label)
+        .set(to_i64(status.base_fee));
+    RELAYER_GAS_PREMIUM
+        .with_label_values(&label)
+        .set(to_i64(status.gas_premium));
+    RELAYER_CHECKPOINTS_COVERED
+        .with_label_values(&label)
+        .set(status.checkpoints_covered.min(i64::MAX as u64) as i64);
+    RELAYER_UNDERFUNDED
+        .with_label_values(&label)
+        .set(status.underfunded as i64);
+}
+
+fn to_i64(value: U256) -> i64 {
+    if value > U256::from(i64::MAX) {
+        i64::MAX
+    } else {
+        value.as_u64() as i64
+    }
+}
+
+fn to_f64(value: U256) -> f64 {
+    // Precision isn't important for a metric; the string conversion can't fail.
+    value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::types::{Address, U256};
+
+    use super::{ParentGasPrices, RelayerConfig, RelayerStatus};
+
+    fn config(min_checkpoints: u64) -> RelayerConfig {
+        RelayerConfig {
+            address: Address::zero(),
+            checkpoint_gas_limit: 1000,
+            min_checkpoints,
+            check_interval: Duration::from_secs(60),
+            subnet_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn checkpoints_covered_by_balance() {
+        let prices = ParentGasPrices {
+            base_fee: U256::from(100),
+            gas_premium: U256::from(10),
+        };
+        assert_eq!(prices.gas_fee_cap(), U256::from(210));
+
+        let status = RelayerStatus::new(&config(5), 1, U256::from(1_000_000), prices);
+        assert_eq!(status.checkpoint_cost, U256::from(210_000));
+        assert_eq!(status.checkpoints_covered, 4);
+        assert!(status.can_submit);
+        assert!(status.underfunded);
+
+        let status = RelayerStatus::new(&config(5), 1, U256::from(100_000), prices);
+        assert_eq!(status.checkpoints_covered, 0);
+        assert!(!status.can_submit);
+
+        let status = RelayerStatus::new(&config(5), 1, U256::from(2_000_000), prices);
+        assert_eq!(status.checkpoints_covered, 9);
+        assert!(!status.underfunded);
+    }
+
+    #[test]
+    fn free_submissions() {
+        let prices = ParentGasPrices {
+            base_fee: U256::zero(),
+            gas_premium: U256::zero(),
+        };
+        let status = RelayerStatus::new(&config(5), 1, U256::zero(), prices);
+        assert_eq!(status.checkpoints_covered, u64::MAX);
+        assert!(!status.underfunded);
+    }
+
+    #[test]
+    fn status_json_has_decimal_amounts() {
+        let prices = ParentGasPrices {
+            base_fee: U256::from(100),
+            gas_premium: U256::from(10),
+        };
+        let status = RelayerStatus::new(&config(1), 1, U256::exp10(18), prices);
+        let json = serde_json::to_value(status).unwrap();
+        assert_eq!(json["balance"], "1000000000000000000");
+        assert_eq!(json["checkpoint_cost"], "210000");
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Adaptive polling interval for the parent syncer
+
+use crate::BlockHeight;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Fraction of the delay which is randomized, so that validators sharing the same
+/// parent RPC provider don't all hit it at the same time.
+const JITTER: f64 = 0.1;
+/// Weight of the latest observation in the moving average of the parent block time.
+const BLOCK_TIME_WEIGHT: f64 = 0.2;
+
+/// Decides how long to wait before polling the parent again:
+/// * as fast as allowed while the syncer is behind the finalized parent chain head,
+/// * about when the next parent block is expected once it has caught up.
+pub(crate) struct AdaptiveInterval {
+    min: Duration,
+    max: Duration,
+    /// The latest finalized parent height and the time it was first seen.
+    last_head: Option<(BlockHeight, Instant)>,
+    /// Moving average of the time between parent blocks.
+    block_time: Option<Duration>,
+}
+
+impl AdaptiveInterval {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            last_head: None,
+            block_time: None,
+        }
+    }
+
+    /// Estimated time between parent blocks, if we have seen the chain advance.
+    pub fn block_time(&self) -> Option<Duration> {
+        self.block_time
+    }
+
+    /// Record the finalized chain head observed on the parent.
+    pub fn observe_head(&mut self, height: BlockHeight, now: Instant) {
+        match self.last_head {
+            Some((last_height, _)) if last_height == height => {}
+            Some((last_height, last_seen)) if last_height < height => {
+                let blocks = u32::try_from(height - last_height).unwrap_or(u32::MAX);
+                let sample = now.saturating_duration_since(last_seen) / blocks;
+                self.block_time = Some(match self.block_time {
+                    Some(avg) => {
+                        avg.mul_f64(1.0 - BLOCK_TIME_WEIGHT) + sample.mul_f64(BLOCK_TIME_WEIGHT)
+                    }
+                    None => sample,
+                });
+                self.last_head = Some((height, now));
+            }
+            // First observation, or the head went backwards, which the syncer treats as a reorg.
+            _ => self.last_head = Some((height, now)),
+        }
+    }
+
+    /// The delay before the next poll, without jitter, given how many blocks we are behind.
+    pub fn delay(&self, lag: BlockHeight, now: Instant) -> Duration {
+        if lag > 0 {
+            return self.min;
+        }
+        let delay = match (self.block_time, self.last_head) {
+            (Some(block_time), Some((_, last_seen))) => {
+                block_time.saturating_sub(now.saturating_duration_since(last_seen))
+            }
+            _ => self.max,
+        };
+        delay.clamp(self.min, self.max)
+    }
+
+    /// The delay before the next poll, with jitter.
+    pub fn next_delay(&self, lag: BlockHeight, now: Instant) -> Duration {
+        let factor = rand::thread_rng().gen_range((1.0 - JITTER)..=(1.0 + JITTER));
+        self.delay(lag, now).mul_f64(factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdaptiveInterval;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn adapts_to_block_time_and_lag() {
+        let min = Duration::from_secs(1);
+        let max = Duration::from_secs(60);
+        let mut interval = AdaptiveInterval::new(min, max);
+        let start = Instant::now();
+
+        // Nothing known yet.
+        assert_eq!(interval.delay(0, start), max);
+        assert_eq!(interval.delay(10, start), min);
+
+        interval.observe_head(100, start);
+        interval.observe_head(101, start + Duration::from_secs(30));
+        interval.observe_head(103, start + Duration::from_secs(90));
+        assert_eq!(interval.block_time(), Some(Duration::from_secs(30)));
+
+        // Caught up: wait for the next block, which is expected 30s after the last one.
+        let now = start + Duration::from_secs(100);
+        assert_eq!(interval.delay(0, now), Duration::from_secs(20));
+        // Overdue blocks are polled for as fast as allowed.
+        let now = start + Duration::from_secs(200);
+        assert_eq!(interval.delay(0, now), min);
+        // Behind the head.
+        assert_eq!(interval.delay(5, now), min);
+
+        let delay = interval.next_delay(0, start + Duration::from_secs(90));
+        assert!(delay >= Duration::from_secs(27) && delay <= Duration::from_secs(33));
+    }
+}
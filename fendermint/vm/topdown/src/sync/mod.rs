@@ -2,24 +2,33 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 //! A constant running process that fetch or listener to parent state
 
+mod interval;
 mod pointers;
 mod syncer;
 mod tendermint;
 
 use crate::proxy::ParentQueryProxy;
+use crate::sync::interval::AdaptiveInterval;
 use crate::sync::syncer::LotusParentSyncer;
 use crate::sync::tendermint::TendermintAwareSyncer;
-use crate::{CachedFinalityProvider, Config, IPCParentFinality, ParentFinalityProvider, Toggle};
+use crate::{
+    CachedFinalityProvider, Config, FinalityMode, IPCParentFinality, ParentFinalityProvider, Toggle,
+};
 use anyhow::anyhow;
 use async_stm::atomically;
 use ethers::utils::hex;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Query the parent finality from the block chain state
 pub trait ParentFinalityStateQuery {
     /// Get the latest committed finality from the state
     fn get_latest_committed_finality(&self) -> anyhow::Result<Option<IPCParentFinality>>;
+
+    /// Get the finality mode the chain chose in its genesis.
+    fn get_finality_mode(&self) -> anyhow::Result<FinalityMode> {
+        Ok(FinalityMode::Safe)
+    }
 }
 
 /// Constantly syncing with parent through polling
@@ -103,6 +112,16 @@ where
     let finality = query_starting_finality(&query, &parent_client).await?;
     atomically(|| view_provider.set_new_finality(finality.clone(), None)).await;
 
+    // The state can be queried by now, so we can tell how the chain wants to follow its parent.
+    let mode = query.get_finality_mode()?;
+    if mode == FinalityMode::Fast {
+        tracing::warn!(
+            "topdown finality in fast mode; parent heights are not waited on to be final"
+        );
+    }
+    atomically(|| view_provider.set_mode(mode)).await;
+    let config = config.with_mode(mode);
+
     tracing::info!(
         finality = finality.to_string(),
         "launching parent syncer with last committed finality"
@@ -152,7 +171,8 @@ where
         let query = self.committed_state_query;
         let tendermint_client = self.tendermint_client;
 
-        let mut interval = tokio::time::interval(config.polling_interval);
+        let mut interval =
+            AdaptiveInterval::new(config.min_polling_interval(), config.polling_interval);
 
         tokio::spawn(async move {
             let lotus_syncer = LotusParentSyncer::new(config, parent_client, provider, query)
@@ -161,11 +181,24 @@ where
             let mut tendermint_syncer = TendermintAwareSyncer::new(lotus_syncer, tendermint_client);
 
             loop {
-                interval.tick().await;
-
                 if let Err(e) = tendermint_syncer.sync().await {
                     tracing::error!(error = e.to_string(), "sync with parent encountered error");
                 }
+
+                let now = Instant::now();
+                let lag = tendermint_syncer.lag();
+                if let Some(chain_head) = tendermint_syncer.chain_head() {
+                    interval.observe_head(chain_head, now);
+                }
+                let delay = interval.next_delay(lag, now);
+                tracing::debug!(
+                    lag,
+                    block_time = ?interval.block_time(),
+                    delay = ?delay,
+                    "waiting to poll parent"
+                );
+
+                tokio::time::sleep(delay).await;
             }
         });
     }
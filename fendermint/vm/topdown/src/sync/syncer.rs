@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 //! The inner type of parent syncer
 
+use crate::divergence::FinalityConflict;
 use crate::finality::ParentViewPayload;
 use crate::proxy::ParentQueryProxy;
 use crate::sync::pointers::SyncPointers;
@@ -12,7 +13,31 @@ use crate::{
 use anyhow::anyhow;
 use async_stm::{atomically, atomically_or_err};
 use ethers::utils::hex;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
 use std::sync::Arc;
+use std::time::SystemTime;
+
+lazy_static! {
+    static ref TOPDOWN_CACHE_BLOCKS: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_topdown_cache_blocks",
+        "Number of parent blocks waiting in the cache to be finalized",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref TOPDOWN_CACHE_MSGS: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_topdown_cache_msgs",
+        "Number of validator changes and top down messages held in the parent view cache",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+    static ref TOPDOWN_CACHE_EVICTED: IntCounterVec = register_int_counter_vec!(
+        "fendermint_topdown_cache_evicted_total",
+        "Number of parent block payloads dropped from the cache to stay within its size limit",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
 
 /// Parent syncer that constantly poll parent. This struct handles lotus null blocks and deferred
 /// execution. For ETH based parent, it should work out of the box as well.
@@ -24,6 +49,10 @@ pub(crate) struct LotusParentSyncer<T, P> {
 
     /// The pointers that indicate which height to poll parent next
     sync_pointers: SyncPointers,
+    /// The finalized chain head seen in the last round.
+    chain_head: Option<BlockHeight>,
+    /// Whether the last round stopped because the cache was full.
+    cache_full: bool,
 }
 
 impl<T, P> LotusParentSyncer<T, P>
@@ -47,9 +76,25 @@ where
             provider,
             query,
             sync_pointers: SyncPointers::new(last_committed_finality.height),
+            chain_head: None,
+            cache_full: false,
         })
     }
 
+    /// The finalized chain head seen the last time the parent was polled.
+    pub fn chain_head(&self) -> Option<BlockHeight> {
+        self.chain_head
+    }
+
+    /// Number of finalized parent blocks we have yet to fetch. It is zero while the cache is
+    /// full, because polling more often wouldn't help until the finality is committed.
+    pub fn lag(&self) -> BlockHeight {
+        match self.chain_head {
+            Some(h) if !self.cache_full => h.saturating_sub(self.sync_pointers.head()),
+            _ => 0,
+        }
+    }
+
     /// There are 2 pointers, each refers to a block height, when syncing with parent. As Lotus has
     /// delayed execution and null round, we need to ensure the topdown messages and validator
     /// changes polled are indeed finalized and executed. The following three pointers are introduced:
@@ -72,11 +117,16 @@ where
     /// time, since block 3 is a null block, empty data will also be pushed to cache. Block 4 is ready
     /// to be proposed.
     pub async fn sync(&mut self) -> anyhow::Result<()> {
+        self.report_cache_stats().await;
+
         let chain_head = if let Some(h) = self.finalized_chain_head().await? {
             h
         } else {
             return Ok(());
         };
+        self.chain_head = Some(chain_head);
+        self.cache_full = false;
+
         tracing::debug!(
             chain_head,
             pointers = self.sync_pointers.to_string(),
@@ -89,6 +139,7 @@ where
                 chain_head,
                 "reorg detected from height"
             );
+            self.check_committed_finality(chain_head).await;
             return self.reset_cache().await;
         }
 
@@ -99,12 +150,17 @@ where
 
         if self.exceed_cache_size_limit().await {
             tracing::debug!("exceeded cache size limit");
+            self.cache_full = true;
             return Ok(());
         }
 
-        self.poll_next().await?;
-
-        Ok(())
+        match self.poll_next().await {
+            Err(Error::ParentChainReorgDetected) => {
+                self.check_committed_finality(chain_head).await;
+                Err(Error::ParentChainReorgDetected.into())
+            }
+            r => Ok(r?),
+        }
     }
 }
 
@@ -113,6 +169,20 @@ where
     T: ParentFinalityStateQuery + Send + Sync + 'static,
     P: ParentQueryProxy + Send + Sync + 'static,
 {
+    async fn report_cache_stats(&self) {
+        let stats = atomically(|| self.provider.cache_stats()).await;
+        let subnet_id = &self.config.subnet_id;
+        TOPDOWN_CACHE_BLOCKS
+            .with_label_values(&[subnet_id])
+            .set(stats.blocks as i64);
+        TOPDOWN_CACHE_MSGS
+            .with_label_values(&[subnet_id])
+            .set(stats.msgs as i64);
+        // The provider keeps the running total, so the counter catches up with it.
+        let evicted = TOPDOWN_CACHE_EVICTED.with_label_values(&[subnet_id]);
+        evicted.inc_by(stats.evicted.saturating_sub(evicted.get()));
+    }
+
     async fn exceed_cache_size_limit(&self) -> bool {
         let max_cache_blocks = self.config.max_cache_blocks();
         atomically(|| self.provider.cached_blocks()).await > max_cache_blocks
@@ -179,6 +249,10 @@ where
                 Ok(())
             })
             .await?;
+
+            if let Some(observations) = self.provider.observations() {
+                observations.observe(to_confirm_height, &data.2, SystemTime::now());
+            }
         } else {
             tracing::debug!(height, "non-null round at height, waiting for confirmation");
         };
@@ -272,15 +346,49 @@ where
     async fn finalized_chain_head(&self) -> anyhow::Result<Option<BlockHeight>> {
         let parent_chain_head_height = self.parent_proxy.get_chain_head_height().await?;
         // sanity check
-        if parent_chain_head_height < self.config.chain_head_delay {
+        let chain_head_delay = self.config.chain_head_delay();
+        if parent_chain_head_height < chain_head_delay {
             tracing::debug!("latest height not more than the chain head delay");
             return Ok(None);
         }
 
         // we consider the chain head finalized only after the `chain_head_delay`
-        Ok(Some(
-            parent_chain_head_height - self.config.chain_head_delay,
-        ))
+        Ok(Some(parent_chain_head_height - chain_head_delay))
+    }
+
+    /// Check whether the reorg on the parent went deep enough to replace the block in the parent
+    /// finality which has already been committed, in which case it can't be undone by the syncer.
+    async fn check_committed_finality(&self, chain_head: BlockHeight) {
+        let divergence = match self.provider.divergence() {
+            Some(d) if !d.is_tripped() => d,
+            _ => return,
+        };
+
+        let committed = match atomically(|| self.provider.last_committed_finality()).await {
+            Some(f) => f,
+            None => return,
+        };
+
+        // The parent hasn't finalized that height (again), so there is nothing to compare to yet.
+        if committed.height > chain_head {
+            return;
+        }
+
+        let parent_hash = match self.parent_proxy.get_block_hash(committed.height).await {
+            Ok(res) if res.block_hash == committed.block_hash => return,
+            Ok(res) => Some(res.block_hash),
+            Err(e) if is_null_round_str(&e.to_string()) => None,
+            Err(e) => {
+                tracing::warn!(
+                    error = e.to_string(),
+                    height = committed.height,
+                    "cannot check committed finality against the parent"
+                );
+                return;
+            }
+        };
+
+        divergence.trip(FinalityConflict::new(&committed, parent_hash.as_ref()));
     }
 
     /// Reset the cache in the face of a reorg
@@ -293,12 +401,13 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::divergence::DivergenceAction;
     use crate::proxy::ParentQueryProxy;
     use crate::sync::syncer::LotusParentSyncer;
     use crate::sync::ParentFinalityStateQuery;
     use crate::{
         BlockHash, BlockHeight, CachedFinalityProvider, Config, IPCParentFinality,
-        SequentialKeyCache, Toggle, NULL_ROUND_ERR_MSG,
+        ParentFinalityProvider, SequentialKeyCache, Toggle, NULL_ROUND_ERR_MSG,
     };
     use anyhow::anyhow;
     use async_stm::atomically;
@@ -378,11 +487,15 @@ mod tests {
         let config = Config {
             chain_head_delay: 2,
             polling_interval: Default::default(),
+            min_polling_interval: None,
             exponential_back_off: Default::default(),
             exponential_retry_limit: 0,
             max_proposal_range: Some(1),
             max_cache_blocks: None,
+            max_cache_msgs: None,
             proposal_delay: None,
+            mode: None,
+            subnet_id: String::new(),
         };
         let genesis_epoch = blocks.lower_bound().unwrap();
         let proxy = Arc::new(TestParentProxy { blocks });
@@ -521,4 +634,45 @@ mod tests {
             Some(104)
         );
     }
+
+    #[tokio::test]
+    async fn detect_committed_finality_conflict() {
+        let parent_blocks = new_parent_blocks!(
+            100 => Some(vec![0; 32]),   // genesis block
+            101 => Some(vec![1; 32]),
+            102 => Some(vec![2; 32]),
+            103 => None,
+            104 => Some(vec![4; 32]),
+            105 => Some(vec![5; 32])    // chain head
+        );
+
+        let syncer = new_syncer(parent_blocks).await;
+        let divergence = syncer.provider.divergence().unwrap().clone();
+
+        // The committed finality agrees with the parent.
+        let finality = IPCParentFinality::new(102, vec![2; 32]);
+        atomically(|| syncer.provider.reset(finality.clone())).await;
+        syncer.check_committed_finality(105).await;
+        assert!(!divergence.is_tripped());
+
+        // The parent hasn't finalized the committed height yet.
+        let finality = IPCParentFinality::new(106, vec![6; 32]);
+        atomically(|| syncer.provider.reset(finality.clone())).await;
+        syncer.check_committed_finality(105).await;
+        assert!(!divergence.is_tripped());
+
+        // The committed block has been replaced by a null round.
+        let finality = IPCParentFinality::new(103, vec![3; 32]);
+        atomically(|| syncer.provider.reset(finality.clone())).await;
+        syncer.check_committed_finality(105).await;
+        let conflict = divergence.conflict().expect("tripped");
+        assert_eq!(conflict.height, 103);
+        assert_eq!(conflict.parent_hash, None);
+
+        // Unless the action is only to alert, nothing is proposed on top of the conflict.
+        divergence.set_action(DivergenceAction::ReadOnly);
+        assert!(atomically(|| syncer.provider.next_proposal())
+            .await
+            .is_none());
+    }
 }
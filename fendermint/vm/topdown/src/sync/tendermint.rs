@@ -34,6 +34,16 @@ where
         self.inner.sync().await
     }
 
+    /// See [`LotusParentSyncer::chain_head`].
+    pub fn chain_head(&self) -> Option<crate::BlockHeight> {
+        self.inner.chain_head()
+    }
+
+    /// See [`LotusParentSyncer::lag`].
+    pub fn lag(&self) -> crate::BlockHeight {
+        self.inner.lag()
+    }
+
     async fn is_syncing_peer(&self) -> anyhow::Result<bool> {
         let status: tendermint_rpc::endpoint::status::Response = self
             .tendermint_client
@@ -1,10 +1,13 @@
 // Copyright 2022-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::divergence::Divergence;
 use crate::finality::ParentViewPayload;
+use crate::observe::Observations;
+use crate::voting::VoteTally;
 use crate::{
-    BlockHash, BlockHeight, CachedFinalityProvider, Error, IPCParentFinality,
-    ParentFinalityProvider, ParentViewProvider,
+    BlockHash, BlockHeight, CacheStats, CachedFinalityProvider, Error, FinalityMode,
+    IPCParentFinality, ParentFinalityProvider, ParentViewProvider,
 };
 use anyhow::anyhow;
 use async_stm::{Stm, StmResult};
@@ -120,7 +123,27 @@ impl<P> Toggle<CachedFinalityProvider<P>> {
         self.perform_or_else(|p| p.reset(finality), ())
     }
 
+    pub fn set_mode(&self, mode: FinalityMode) -> Stm<()> {
+        self.perform_or_else(|p| p.set_mode(mode), ())
+    }
+
     pub fn cached_blocks(&self) -> Stm<BlockHeight> {
         self.perform_or_else(|p| p.cached_blocks(), BlockHeight::MAX)
     }
+
+    pub fn cache_stats(&self) -> Stm<CacheStats> {
+        self.perform_or_else(|p| p.cache_stats(), CacheStats::default())
+    }
+
+    pub fn vote_tally(&self) -> Option<&VoteTally> {
+        self.inner.as_ref().map(|p| p.vote_tally())
+    }
+
+    pub fn observations(&self) -> Option<&Observations> {
+        self.inner.as_ref().map(|p| p.observations())
+    }
+
+    pub fn divergence(&self) -> Option<&Divergence> {
+        self.inner.as_ref().map(|p| p.divergence())
+    }
 }
@@ -0,0 +1,338 @@
+// Copyright 2022-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Tally of the parent blocks the validators have seen as final, as they gossip it to each other.
+//!
+//! Without votes, the proposer can only go by its own view of the parent, and if the others are
+//! lagging behind, they reject its proposal and the round is wasted. With votes, the proposer can
+//! pick the highest parent block that validators with more than 2/3 of the power have seen, which
+//! is what they are going to accept.
+//!
+//! The votes are [SignedVote]s, which the validators publish through the IPLD Resolver.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use async_stm::{atomically, Stm, TVar};
+use cid::multihash::{Code, MultihashDigest};
+use fendermint_crypto::{PublicKey, SecretKey, Signature};
+use fvm_ipld_encoding::tuple::*;
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_gauge_vec, HistogramVec, IntGaugeVec,
+};
+
+use crate::{BlockHash, BlockHeight, SubnetLabel};
+
+lazy_static! {
+    static ref QUORUM_LATENCY: HistogramVec = register_histogram_vec!(
+        "fendermint_topdown_quorum_latency_secs",
+        "Time between the first vote for a parent block and it being backed by a quorum of validators",
+        &["subnet"],
+        exponential_buckets(0.1, 2.0, 14).unwrap()
+    )
+    .expect("failed to register metric");
+    static ref QUORUM_HEIGHT: IntGaugeVec = register_int_gauge_vec!(
+        "fendermint_topdown_quorum_height",
+        "The highest parent block backed by a quorum of validators",
+        &["subnet"]
+    )
+    .expect("failed to register metric");
+}
+
+/// Public key of a validator, in the same format as in the power updates sent to CometBFT.
+pub type ValidatorKey = Vec<u8>;
+/// Voting power of a validator.
+pub type Weight = u64;
+
+/// A validator saying it has seen a parent block as final, to be gossiped to the others.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug, Clone, PartialEq, Eq)]
+pub struct SignedVote {
+    pub height: BlockHeight,
+    pub block_hash: BlockHash,
+    /// Public key of the validator, in the same format as in the power table.
+    pub public_key: ValidatorKey,
+    pub signature: Vec<u8>,
+}
+
+impl SignedVote {
+    /// Sign a vote; the subnet ID is part of what is signed, so the vote can't be replayed in another subnet.
+    pub fn sign(
+        sk: &SecretKey,
+        subnet_id: &str,
+        height: BlockHeight,
+        block_hash: BlockHash,
+    ) -> anyhow::Result<Self> {
+        let digest = vote_digest(subnet_id, height, &block_hash)?;
+        let (signature, _) = sk.sign(&digest);
+        Ok(Self {
+            height,
+            block_hash,
+            public_key: sk.public_key().serialize().to_vec(),
+            signature: signature.serialize().to_vec(),
+        })
+    }
+
+    /// Check that the vote was signed by the validator it claims to be from, for this subnet.
+    pub fn verify(&self, subnet_id: &str) -> anyhow::Result<()> {
+        let digest = vote_digest(subnet_id, self.height, &self.block_hash)?;
+        let public_key = PublicKey::parse_slice(&self.public_key, None)
+            .map_err(|e| anyhow!("invalid public key: {e}"))?;
+        let signature = Signature::parse_standard_slice(&self.signature)
+            .map_err(|e| anyhow!("invalid signature: {e}"))?;
+        if !fendermint_crypto::verify(&digest, &signature, &public_key) {
+            bail!("the vote isn't signed by its validator");
+        }
+        Ok(())
+    }
+}
+
+fn vote_digest(
+    subnet_id: &str,
+    height: BlockHeight,
+    block_hash: &BlockHash,
+) -> anyhow::Result<[u8; 32]> {
+    let bz = fvm_ipld_encoding::to_vec(&(subnet_id, height, block_hash))?;
+    let digest = Code::Blake2b256.digest(&bz);
+    Ok(digest
+        .digest()
+        .try_into()
+        .expect("Blake2b256 digests are 32 bytes"))
+}
+
+/// The validators who have seen a block, and when the first of them said so.
+#[derive(Clone, Debug)]
+struct Voters {
+    validators: HashSet<ValidatorKey>,
+    first_seen: Instant,
+    /// Whether the block has been backed by a quorum already, to only record the latency once.
+    has_quorum: bool,
+}
+
+/// Votes about the parent blocks which haven't been committed as final yet.
+#[derive(Clone, Default)]
+pub struct VoteTally {
+    /// Power of the current validators; votes from anyone else are ignored.
+    power_table: TVar<HashMap<ValidatorKey, Weight>>,
+    /// The validators who have seen each block, by height and hash.
+    votes: TVar<BTreeMap<BlockHeight, HashMap<BlockHash, Voters>>>,
+    /// The height of the last committed finality; older votes are of no use.
+    last_finalized_height: TVar<BlockHeight>,
+    /// Label of the quorum latency and height metrics.
+    subnet_id: SubnetLabel,
+}
+
+impl VoteTally {
+    pub fn new(subnet_id: &str) -> Self {
+        Self {
+            subnet_id: subnet_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Replace the power table, e.g. after the validator set changed.
+    pub fn set_power_table(&self, power_table: Vec<(ValidatorKey, Weight)>) -> Stm<()> {
+        self.power_table
+            .write(power_table.into_iter().filter(|(_, w)| *w > 0).collect())
+    }
+
+    /// Check whether there is anyone to count the votes of.
+    pub fn has_power_table(&self) -> Stm<bool> {
+        Ok(!self.power_table.read()?.is_empty())
+    }
+
+    /// Add a vote from a validator saying they have seen a parent block as final.
+    ///
+    /// Returns how long it took for the block to be backed by a quorum, if this vote completed it.
+    /// Votes about committed heights and from validators not in the power table are ignored.
+    pub fn add_vote(
+        &self,
+        validator: ValidatorKey,
+        height: BlockHeight,
+        block_hash: BlockHash,
+    ) -> Stm<Option<Duration>> {
+        if height <= *self.last_finalized_height.read()? {
+            return Ok(None);
+        }
+        if !self.power_table.read()?.contains_key(&validator) {
+            return Ok(None);
+        }
+
+        let mut votes = self.votes.read_clone()?;
+        let voters = votes
+            .entry(height)
+            .or_default()
+            .entry(block_hash)
+            .or_insert_with(|| Voters {
+                validators: HashSet::new(),
+                first_seen: Instant::now(),
+                has_quorum: false,
+            });
+
+        if !voters.validators.insert(validator) {
+            return Ok(None);
+        }
+
+        let latency = if !voters.has_quorum && self.is_quorum(&voters.validators)? {
+            voters.has_quorum = true;
+            Some(voters.first_seen.elapsed())
+        } else {
+            None
+        };
+
+        self.votes.write(votes)?;
+
+        Ok(latency)
+    }
+
+    /// Add a vote, and record the time it took to form a quorum in the metrics.
+    pub async fn receive_vote(
+        &self,
+        validator: ValidatorKey,
+        height: BlockHeight,
+        block_hash: BlockHash,
+    ) {
+        let latency =
+            atomically(|| self.add_vote(validator.clone(), height, block_hash.clone())).await;
+
+        if let Some(latency) = latency {
+            tracing::debug!(height, ?latency, "parent block backed by a quorum");
+            QUORUM_LATENCY
+                .with_label_values(&[&self.subnet_id])
+                .observe(latency.as_secs_f64());
+            let gauge = QUORUM_HEIGHT.with_label_values(&[&self.subnet_id]);
+            gauge.set(gauge.get().max(height as i64));
+        }
+    }
+
+    /// Find the highest block up to a height which validators with more than 2/3 of the power have seen,
+    /// among the ones accepted by a filter, e.g. the ones we have the data of.
+    pub fn find_quorum<F>(
+        &self,
+        max_height: BlockHeight,
+        mut accept: F,
+    ) -> Stm<Option<(BlockHeight, BlockHash)>>
+    where
+        F: FnMut(BlockHeight, &BlockHash) -> Stm<bool>,
+    {
+        let votes = self.votes.read()?;
+        for (height, blocks) in votes.range(..=max_height).rev() {
+            for (block_hash, voters) in blocks.iter() {
+                if self.is_quorum(&voters.validators)? && accept(*height, block_hash)? {
+                    return Ok(Some((*height, block_hash.clone())));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Forget the votes about the heights which have been committed.
+    pub fn set_finalized(&self, height: BlockHeight) -> Stm<()> {
+        self.last_finalized_height.write(height)?;
+        self.votes
+            .update(|mut votes| votes.split_off(&(height + 1)))
+    }
+
+    fn is_quorum(&self, validators: &HashSet<ValidatorKey>) -> Stm<bool> {
+        let power_table = self.power_table.read()?;
+        let total: u128 = power_table.values().map(|w| *w as u128).sum();
+        let voted: u128 = validators
+            .iter()
+            .filter_map(|v| power_table.get(v))
+            .map(|w| *w as u128)
+            .sum();
+        Ok(total > 0 && voted * 3 > total * 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_stm::{atomically, Stm};
+    use fendermint_crypto::SecretKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{SignedVote, VoteTally};
+    use crate::{BlockHash, BlockHeight};
+
+    fn any(_: BlockHeight, _: &BlockHash) -> Stm<bool> {
+        Ok(true)
+    }
+
+    #[test]
+    fn votes_are_signed_for_a_subnet() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let sk = SecretKey::random(&mut rng);
+        let vote = SignedVote::sign(&sk, "/r314/f410f", 10, vec![1; 32]).unwrap();
+        assert_eq!(vote.public_key, sk.public_key().serialize().to_vec());
+        assert!(vote.verify("/r314/f410f").is_ok());
+        assert!(vote.verify("/r314/f410g").is_err());
+
+        let forged = SignedVote {
+            height: 11,
+            ..vote.clone()
+        };
+        assert!(forged.verify("/r314/f410f").is_err());
+
+        let bz = fvm_ipld_encoding::to_vec(&vote).unwrap();
+        let decoded: SignedVote = fvm_ipld_encoding::from_slice(&bz).unwrap();
+        assert_eq!(decoded, vote);
+    }
+
+    #[tokio::test]
+    async fn finds_highest_quorum() {
+        let tally = VoteTally::new("");
+        let (a, b, c) = (vec![1u8], vec![2u8], vec![3u8]);
+
+        atomically(|| {
+            tally.set_power_table(vec![(a.clone(), 1), (b.clone(), 1), (c.clone(), 1)])?;
+
+            // Everyone saw block 10, only two out of three saw block 12, and only one saw 13.
+            for v in [&a, &b, &c] {
+                tally.add_vote(v.clone(), 10, vec![10])?;
+            }
+            assert!(tally.add_vote(a.clone(), 12, vec![12])?.is_none());
+            assert!(tally.add_vote(b.clone(), 12, vec![12])?.is_none());
+            tally.add_vote(a.clone(), 13, vec![13])?;
+
+            assert_eq!(tally.find_quorum(20, any)?, Some((10, vec![10])));
+
+            // The third vote completes the quorum at 12.
+            assert!(tally.add_vote(c.clone(), 12, vec![12])?.is_some());
+            assert_eq!(tally.find_quorum(20, any)?, Some((12, vec![12])));
+            assert_eq!(tally.find_quorum(11, any)?, Some((10, vec![10])));
+
+            // Unknown validators don't count.
+            tally.add_vote(vec![4u8], 13, vec![13])?;
+            tally.add_vote(vec![5u8], 13, vec![13])?;
+            assert_eq!(tally.find_quorum(20, any)?, Some((12, vec![12])));
+
+            tally.set_finalized(12)?;
+            assert_eq!(tally.find_quorum(20, any)?, None);
+            assert!(tally.add_vote(b.clone(), 11, vec![11])?.is_none());
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn conflicting_hashes_split_the_vote() {
+        let tally = VoteTally::new("");
+        let (a, b, c) = (vec![1u8], vec![2u8], vec![3u8]);
+
+        atomically(|| {
+            tally.set_power_table(vec![(a.clone(), 2), (b.clone(), 1), (c.clone(), 1)])?;
+            tally.add_vote(a.clone(), 10, vec![1])?;
+            tally.add_vote(b.clone(), 10, vec![1])?;
+            tally.add_vote(c.clone(), 10, vec![2])?;
+            // 3 out of 4 is more than 2/3.
+            assert_eq!(tally.find_quorum(10, any)?, Some((10, vec![1])));
+
+            tally.set_power_table(vec![(a.clone(), 1), (b.clone(), 1), (c.clone(), 2)])?;
+            // 2 out of 4 isn't.
+            assert_eq!(tally.find_quorum(10, any)?, None);
+            Ok(())
+        })
+        .await;
+    }
+}